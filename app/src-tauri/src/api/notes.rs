@@ -1,5 +1,5 @@
 use anyhow::{Result, bail};
-use serde_json::Value;
+use serde_json::{Value, json};
 use tauri_plugin_http::reqwest::{Method, StatusCode};
 use uuid::Uuid;
 
@@ -39,6 +39,75 @@ impl super::Client {
     Ok(())
   }
 
+  pub async fn note_file(&self, note_id: Uuid, folder_id: Option<Uuid>) -> Result<()> {
+    self
+      .notes_send(
+        Method::PUT,
+        "/api/notes/management/folders/note",
+        json!({ "note_id": note_id, "folder_id": folder_id }),
+      )
+      .await?;
+
+    Ok(())
+  }
+
+  pub async fn note_search(&self, query: &str) -> Result<Value> {
+    let req = self
+      .builder(Method::GET, "/api/notes/management/search")
+      .await?
+      .query(&[("q", query)]);
+    Ok(self.send_auth(req).await?.json().await?)
+  }
+
+  /// Uploads a Markdown file or ZIP archive, the result lists every imported
+  /// file with its new note or the error.
+  pub async fn note_import(&self, filename: &str, data: Vec<u8>) -> Result<Value> {
+    let req = self
+      .builder(Method::POST, "/api/notes/management/import")
+      .await?
+      .query(&[("filename", filename)])
+      .body(data);
+    Ok(self.send_auth(req).await?.json().await?)
+  }
+
+  pub async fn note_attachment_upload(
+    &self,
+    note_id: Uuid,
+    filename: &str,
+    data: Vec<u8>,
+  ) -> Result<Value> {
+    let req = self
+      .builder(Method::POST, &format!("/api/notes/attachments/{note_id}"))
+      .await?
+      .query(&[("filename", filename)])
+      .body(data);
+    Ok(self.send_auth(req).await?.json().await?)
+  }
+
+  pub async fn note_tags(&self, note_id: Uuid, tags: &[String]) -> Result<()> {
+    self
+      .notes_send(
+        Method::PUT,
+        "/api/notes/management/tags",
+        json!({ "note_id": note_id, "tags": tags }),
+      )
+      .await?;
+
+    Ok(())
+  }
+
+  pub async fn note_favourite(&self, note_id: Uuid, favourite: bool) -> Result<()> {
+    self
+      .notes_send(
+        Method::PUT,
+        "/api/notes/management/favourite",
+        json!({ "note_id": note_id, "favourite": favourite }),
+      )
+      .await?;
+
+    Ok(())
+  }
+
   /// Send a request behind auth without bailing on non-success, returning the
   /// status code alongside the parsed body. Lets callers distinguish specific
   /// statuses (e.g. 409 note-limit) instead of collapsing them into one error.
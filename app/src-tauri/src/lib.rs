@@ -6,12 +6,24 @@ use crate::{
   auth::{auth_status, confirm_code, logout, start_auth},
   notes::{
     commands::{
-      create_note, delete_note, delete_note_snapshot, edit_note, list_note_snapshots, list_notes,
-      list_users_note, note_info, note_snapshot_content, note_snapshot_info, notes_config,
-      restore_note_snapshot, share_note, share_note_public, transfer_note,
+      create_comment_thread, create_note, create_note_folder, create_note_snapshot,
+      delete_comment_thread, delete_note, delete_note_attachment, delete_note_comment,
+      delete_note_folder, delete_note_snapshot, diff_note_snapshots, edit_note, edit_note_comment,
+      export_note, export_note_snapshot, file_note, get_note_snapshot_policy, import_notes,
+      label_note_snapshot, list_note_attachments, list_note_backlinks, list_note_comments,
+      list_note_folders, list_note_links, list_note_snapshots, list_note_tags, list_notes,
+      list_trashed_notes, list_users_note, move_note_folder, note_attachment_content,
+      note_attachment_thumbnail, note_attachment_usage, note_info, note_snapshot_content,
+      note_snapshot_info, notes_config, pin_note_snapshot, purge_note, rename_note_folder,
+      reply_note_comment, resolve_comment_thread, restore_note, restore_note_snapshot,
+      search_notes, set_note_favourite, set_note_snapshot_policy, set_note_tags, share_note,
+      share_note_public, transfer_note, upload_note_attachment,
     },
     connection::{NoteState, connect_note, disconnect_note, send_note},
-    storage::{NotesStore, get_note_store, list_notes_store, note_content, save_note_content},
+    storage::{
+      NotesStore, get_note_store, list_note_folders_store, list_notes_store, note_content,
+      save_note_content,
+    },
   },
   settings::{get_settings, save_settings},
   setup::{reset_setup, setup, setup_status},
@@ -77,20 +89,58 @@ pub fn run() {
       disconnect_note,
       list_notes,
       note_info,
+      list_note_backlinks,
+      list_note_links,
       notes_config,
       list_users_note,
       list_note_snapshots,
       note_snapshot_info,
       note_snapshot_content,
+      export_note,
+      export_note_snapshot,
+      import_notes,
       edit_note,
       share_note,
       share_note_public,
       restore_note_snapshot,
       delete_note,
+      list_trashed_notes,
+      restore_note,
+      purge_note,
       delete_note_snapshot,
+      diff_note_snapshots,
+      create_note_snapshot,
+      pin_note_snapshot,
+      label_note_snapshot,
+      get_note_snapshot_policy,
+      set_note_snapshot_policy,
+      list_note_comments,
+      create_comment_thread,
+      reply_note_comment,
+      resolve_comment_thread,
+      edit_note_comment,
+      delete_note_comment,
+      delete_comment_thread,
+      list_note_attachments,
+      upload_note_attachment,
+      note_attachment_content,
+      note_attachment_thumbnail,
+      delete_note_attachment,
+      note_attachment_usage,
       create_note,
       transfer_note,
+      list_note_folders,
+      create_note_folder,
+      rename_note_folder,
+      move_note_folder,
+      delete_note_folder,
+      file_note,
+      list_note_tags,
+      search_notes,
+      set_note_tags,
+      set_note_favourite,
       list_notes_store,
+      list_note_folders_store,
       get_note_store,
       note_content,
       save_note_content,
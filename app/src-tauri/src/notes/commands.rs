@@ -1,4 +1,5 @@
 use anyhow::Result;
+use serde::Deserialize;
 use serde_json::{Value, json};
 use tauri::State;
 use tauri_plugin_http::reqwest::Method;
@@ -6,27 +7,204 @@ use uuid::Uuid;
 
 use crate::{
   api::Client,
-  notes::storage::{NoteInfo, NotesStore},
+  notes::storage::{NoteFolderInfo, NoteInfo, NotesStore},
 };
 
+/// The full list is always fetched so the offline store stays complete, the
+/// tag filter is applied afterwards.
 #[tauri::command]
 pub async fn list_notes(
   client: State<'_, Client>,
   store: State<'_, NotesStore>,
+  tag: Option<String>,
 ) -> tauri::Result<Vec<NoteInfo>> {
-  let raw_notes = match client.notes_get("/api/notes/management").await {
-    Ok(raw_notes) => raw_notes,
+  let mut notes = match client.notes_get("/api/notes/management").await {
+    Ok(raw_notes) => {
+      let notes: Vec<NoteInfo> = serde_json::from_value(raw_notes)?;
+      store.set_notes(notes.clone()).await?;
+      notes
+    }
     Err(e) => {
       println!("{e}");
-      return Ok(store.get_notes().await);
+      store.get_notes().await
     }
   };
-  let notes: Vec<NoteInfo> = serde_json::from_value(raw_notes)?;
-  store.set_notes(notes.clone()).await?;
+  if let Some(tag) = tag {
+    notes.retain(|note| note.tags.contains(&tag));
+  }
 
   Ok(notes)
 }
 
+#[tauri::command]
+pub async fn list_note_tags(
+  client: State<'_, Client>,
+  store: State<'_, NotesStore>,
+) -> tauri::Result<Vec<String>> {
+  if let Ok(raw_tags) = client.notes_get("/api/notes/management/tags").await {
+    return Ok(serde_json::from_value(raw_tags)?);
+  }
+
+  let mut tags: Vec<String> = store
+    .get_notes()
+    .await
+    .into_iter()
+    .flat_map(|note| note.tags)
+    .collect();
+  tags.sort();
+  tags.dedup();
+  Ok(tags)
+}
+
+#[tauri::command]
+pub async fn list_note_folders(
+  client: State<'_, Client>,
+  store: State<'_, NotesStore>,
+) -> tauri::Result<Vec<NoteFolderInfo>> {
+  let raw_folders = match client.notes_get("/api/notes/management/folders").await {
+    Ok(raw_folders) => raw_folders,
+    Err(e) => {
+      println!("{e}");
+      return Ok(store.get_folders().await);
+    }
+  };
+  let folders: Vec<NoteFolderInfo> = serde_json::from_value(raw_folders)?;
+  store.set_folders(folders.clone()).await?;
+
+  Ok(folders)
+}
+
+#[tauri::command]
+pub async fn create_note_folder(
+  client: State<'_, Client>,
+  name: String,
+  parent: Option<Uuid>,
+) -> tauri::Result<Value> {
+  Ok(
+    client
+      .notes_send(
+        Method::POST,
+        "/api/notes/management/folders",
+        json!({ "name": name, "parent": parent }),
+      )
+      .await?,
+  )
+}
+
+#[tauri::command]
+pub async fn rename_note_folder(
+  client: State<'_, Client>,
+  folder_id: Uuid,
+  name: String,
+) -> tauri::Result<()> {
+  client
+    .notes_send(
+      Method::PUT,
+      "/api/notes/management/folders",
+      json!({ "folder_id": folder_id, "name": name }),
+    )
+    .await?;
+  Ok(())
+}
+
+#[tauri::command]
+pub async fn move_note_folder(
+  client: State<'_, Client>,
+  folder_id: Uuid,
+  parent: Option<Uuid>,
+  position: usize,
+) -> tauri::Result<()> {
+  client
+    .notes_send(
+      Method::PUT,
+      "/api/notes/management/folders/move",
+      json!({ "folder_id": folder_id, "parent": parent, "position": position }),
+    )
+    .await?;
+  Ok(())
+}
+
+#[tauri::command]
+pub async fn delete_note_folder(client: State<'_, Client>, folder_id: Uuid) -> tauri::Result<()> {
+  client
+    .notes_send(
+      Method::DELETE,
+      "/api/notes/management/folders",
+      json!({ "folder_id": folder_id }),
+    )
+    .await?;
+  Ok(())
+}
+
+/// Files a note into a folder, while offline the change is kept in the store
+/// and sent on reconnect.
+#[tauri::command]
+pub async fn file_note(
+  client: State<'_, Client>,
+  store: State<'_, NotesStore>,
+  note_id: Uuid,
+  folder_id: Option<Uuid>,
+) -> tauri::Result<()> {
+  let pending = client.note_file(note_id, folder_id).await.is_err();
+  store.set_note_folder(note_id, folder_id, pending).await?;
+  Ok(())
+}
+
+#[tauri::command]
+pub async fn import_notes(
+  client: State<'_, Client>,
+  filename: String,
+  data: Vec<u8>,
+) -> tauri::Result<Value> {
+  Ok(client.note_import(&filename, data).await?)
+}
+
+/// Search needs the server index, so it is not available offline.
+#[tauri::command]
+pub async fn search_notes(client: State<'_, Client>, query: String) -> tauri::Result<Value> {
+  Ok(client.note_search(&query).await?)
+}
+
+/// Sets the caller's tags on a note, kept in the store while offline.
+#[tauri::command]
+pub async fn set_note_tags(
+  client: State<'_, Client>,
+  store: State<'_, NotesStore>,
+  note_id: Uuid,
+  tags: Vec<String>,
+) -> tauri::Result<()> {
+  let favourite = store
+    .get_note(note_id)
+    .await
+    .map(|note| note.favourite)
+    .unwrap_or_default();
+  let pending = client.note_tags(note_id, &tags).await.is_err();
+  store
+    .set_note_tags(note_id, tags, favourite, pending)
+    .await?;
+  Ok(())
+}
+
+/// Marks a note as favourite, kept in the store while offline.
+#[tauri::command]
+pub async fn set_note_favourite(
+  client: State<'_, Client>,
+  store: State<'_, NotesStore>,
+  note_id: Uuid,
+  favourite: bool,
+) -> tauri::Result<()> {
+  let tags = store
+    .get_note(note_id)
+    .await
+    .map(|note| note.tags)
+    .unwrap_or_default();
+  let pending = client.note_favourite(note_id, favourite).await.is_err();
+  store
+    .set_note_tags(note_id, tags, favourite, pending)
+    .await?;
+  Ok(())
+}
+
 #[tauri::command]
 pub async fn note_info(client: State<'_, Client>, uuid: Uuid) -> tauri::Result<Value> {
   Ok(
@@ -36,6 +214,26 @@ pub async fn note_info(client: State<'_, Client>, uuid: Uuid) -> tauri::Result<V
   )
 }
 
+/// Notes linking to the note, limited to notes in the user's list.
+#[tauri::command]
+pub async fn list_note_backlinks(client: State<'_, Client>, uuid: Uuid) -> tauri::Result<Value> {
+  Ok(
+    client
+      .notes_get(&format!("/api/notes/management/{uuid}/backlinks"))
+      .await?,
+  )
+}
+
+/// Notes the note links to, with deleted or unshared targets marked broken.
+#[tauri::command]
+pub async fn list_note_links(client: State<'_, Client>, uuid: Uuid) -> tauri::Result<Value> {
+  Ok(
+    client
+      .notes_get(&format!("/api/notes/management/{uuid}/links"))
+      .await?,
+  )
+}
+
 #[tauri::command]
 pub async fn notes_config(client: State<'_, Client>) -> tauri::Result<Value> {
   Ok(client.notes_get("/api/notes/management/config").await?)
@@ -58,6 +256,20 @@ pub async fn list_note_snapshots(
   )
 }
 
+/// Compares `from` with `to`, or with the current note when `to` is omitted.
+#[tauri::command]
+pub async fn diff_note_snapshots(
+  client: State<'_, Client>,
+  from: Uuid,
+  to: Option<Uuid>,
+) -> tauri::Result<Value> {
+  let mut path = format!("/api/notes/snapshots/diff?from={from}");
+  if let Some(to) = to {
+    path.push_str(&format!("&to={to}"));
+  }
+  Ok(client.notes_get(&path).await?)
+}
+
 #[tauri::command]
 pub async fn note_snapshot_info(
   client: State<'_, Client>,
@@ -82,6 +294,53 @@ pub async fn note_snapshot_content(
   )
 }
 
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+  Md,
+  Html,
+}
+
+impl ExportFormat {
+  fn as_str(self) -> &'static str {
+    match self {
+      ExportFormat::Md => "md",
+      ExportFormat::Html => "html",
+    }
+  }
+}
+
+/// Rendered Markdown or HTML of the current note content.
+#[tauri::command]
+pub async fn export_note(
+  client: State<'_, Client>,
+  uuid: Uuid,
+  format: ExportFormat,
+) -> tauri::Result<String> {
+  let bytes = client
+    .notes_get_bytes(&format!(
+      "/api/notes/management/{uuid}/export?format={}",
+      format.as_str()
+    ))
+    .await?;
+  Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[tauri::command]
+pub async fn export_note_snapshot(
+  client: State<'_, Client>,
+  snapshot_id: Uuid,
+  format: ExportFormat,
+) -> tauri::Result<String> {
+  let bytes = client
+    .notes_get_bytes(&format!(
+      "/api/notes/snapshots/{snapshot_id}/export?format={}",
+      format.as_str()
+    ))
+    .await?;
+  Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
 #[tauri::command]
 pub async fn edit_note(
   client: State<'_, Client>,
@@ -157,6 +416,133 @@ pub async fn delete_note(client: State<'_, Client>, note_id: Uuid) -> tauri::Res
   Ok(())
 }
 
+/// Trashed notes with the time they are purged at.
+#[tauri::command]
+pub async fn list_trashed_notes(client: State<'_, Client>) -> tauri::Result<Value> {
+  Ok(client.notes_get("/api/notes/trash").await?)
+}
+
+/// Returns the error code `"limit"` when restoring would exceed the note quota.
+#[tauri::command]
+pub async fn restore_note(client: State<'_, Client>, note_id: Uuid) -> Result<(), String> {
+  let (status, _) = client
+    .notes_raw(
+      Method::PUT,
+      "/api/notes/trash/restore",
+      Some(json!({ "note_id": note_id })),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+
+  match status {
+    200..=299 => Ok(()),
+    409 => Err("limit".into()),
+    s => Err(format!("status {s}")),
+  }
+}
+
+#[tauri::command]
+pub async fn purge_note(client: State<'_, Client>, note_id: Uuid) -> tauri::Result<()> {
+  client
+    .notes_send(
+      Method::DELETE,
+      "/api/notes/trash",
+      json!({ "note_id": note_id }),
+    )
+    .await?;
+  Ok(())
+}
+
+/// Takes a labelled snapshot right away, pinned unless `pinned` is false.
+#[tauri::command]
+pub async fn create_note_snapshot(
+  client: State<'_, Client>,
+  note_id: Uuid,
+  label: String,
+  pinned: Option<bool>,
+) -> tauri::Result<Value> {
+  Ok(
+    client
+      .notes_send(
+        Method::POST,
+        "/api/notes/snapshots",
+        json!({ "note_id": note_id, "label": label, "pinned": pinned }),
+      )
+      .await?,
+  )
+}
+
+#[tauri::command]
+pub async fn pin_note_snapshot(
+  client: State<'_, Client>,
+  snapshot_id: Uuid,
+  pinned: bool,
+) -> tauri::Result<()> {
+  client
+    .notes_send(
+      Method::PUT,
+      "/api/notes/snapshots/pin",
+      json!({ "snapshot_id": snapshot_id, "pinned": pinned }),
+    )
+    .await?;
+  Ok(())
+}
+
+#[tauri::command]
+pub async fn label_note_snapshot(
+  client: State<'_, Client>,
+  snapshot_id: Uuid,
+  label: Option<String>,
+) -> tauri::Result<()> {
+  client
+    .notes_send(
+      Method::PUT,
+      "/api/notes/snapshots/label",
+      json!({ "snapshot_id": snapshot_id, "label": label }),
+    )
+    .await?;
+  Ok(())
+}
+
+#[tauri::command]
+pub async fn get_note_snapshot_policy(
+  client: State<'_, Client>,
+  note_uuid: Uuid,
+) -> tauri::Result<Value> {
+  Ok(
+    client
+      .notes_get(&format!("/api/notes/snapshot-policy/{note_uuid}"))
+      .await?,
+  )
+}
+
+/// Fields left as `None` fall back to the instance policy.
+#[tauri::command]
+pub async fn set_note_snapshot_policy(
+  client: State<'_, Client>,
+  note_id: Uuid,
+  min_interval_mins: Option<i64>,
+  max_interval_mins: Option<i64>,
+  min_size_change: Option<i64>,
+  retention: Option<Value>,
+) -> tauri::Result<Value> {
+  Ok(
+    client
+      .notes_send(
+        Method::PUT,
+        "/api/notes/snapshot-policy",
+        json!({
+          "note_id": note_id,
+          "min_interval_mins": min_interval_mins,
+          "max_interval_mins": max_interval_mins,
+          "min_size_change": min_size_change,
+          "retention": retention,
+        }),
+      )
+      .await?,
+  )
+}
+
 #[tauri::command]
 pub async fn delete_note_snapshot(
   client: State<'_, Client>,
@@ -215,3 +601,195 @@ pub async fn transfer_note(
     s => Err(format!("status {s}")),
   }
 }
+
+#[tauri::command]
+pub async fn list_note_comments(
+  client: State<'_, Client>,
+  note_uuid: Uuid,
+) -> tauri::Result<Value> {
+  Ok(
+    client
+      .notes_get(&format!("/api/notes/comments/{note_uuid}"))
+      .await?,
+  )
+}
+
+/// `start` and `end` are Y.js relative positions in the `default` fragment.
+#[tauri::command]
+pub async fn create_comment_thread(
+  client: State<'_, Client>,
+  note_id: Uuid,
+  start: Value,
+  end: Value,
+  quote: String,
+  body: String,
+) -> tauri::Result<Value> {
+  Ok(
+    client
+      .notes_send(
+        Method::POST,
+        "/api/notes/comments",
+        json!({
+          "note_id": note_id,
+          "start": start,
+          "end": end,
+          "quote": quote,
+          "body": body,
+        }),
+      )
+      .await?,
+  )
+}
+
+#[tauri::command]
+pub async fn reply_note_comment(
+  client: State<'_, Client>,
+  thread_id: Uuid,
+  body: String,
+) -> tauri::Result<Value> {
+  Ok(
+    client
+      .notes_send(
+        Method::POST,
+        "/api/notes/comments/reply",
+        json!({ "thread_id": thread_id, "body": body }),
+      )
+      .await?,
+  )
+}
+
+#[tauri::command]
+pub async fn resolve_comment_thread(
+  client: State<'_, Client>,
+  thread_id: Uuid,
+  resolved: bool,
+) -> tauri::Result<()> {
+  client
+    .notes_send(
+      Method::PUT,
+      "/api/notes/comments/resolve",
+      json!({ "thread_id": thread_id, "resolved": resolved }),
+    )
+    .await?;
+  Ok(())
+}
+
+#[tauri::command]
+pub async fn edit_note_comment(
+  client: State<'_, Client>,
+  comment_id: Uuid,
+  body: String,
+) -> tauri::Result<()> {
+  client
+    .notes_send(
+      Method::PUT,
+      "/api/notes/comments",
+      json!({ "comment_id": comment_id, "body": body }),
+    )
+    .await?;
+  Ok(())
+}
+
+#[tauri::command]
+pub async fn delete_note_comment(client: State<'_, Client>, comment_id: Uuid) -> tauri::Result<()> {
+  client
+    .notes_send(
+      Method::DELETE,
+      "/api/notes/comments",
+      json!({ "comment_id": comment_id }),
+    )
+    .await?;
+  Ok(())
+}
+
+#[tauri::command]
+pub async fn delete_comment_thread(
+  client: State<'_, Client>,
+  thread_id: Uuid,
+) -> tauri::Result<()> {
+  client
+    .notes_send(
+      Method::DELETE,
+      "/api/notes/comments/thread",
+      json!({ "thread_id": thread_id }),
+    )
+    .await?;
+  Ok(())
+}
+
+#[tauri::command]
+pub async fn list_note_attachments(
+  client: State<'_, Client>,
+  note_uuid: Uuid,
+) -> tauri::Result<Value> {
+  Ok(
+    client
+      .notes_get(&format!("/api/notes/attachments/{note_uuid}"))
+      .await?,
+  )
+}
+
+#[tauri::command]
+pub async fn upload_note_attachment(
+  client: State<'_, Client>,
+  note_id: Uuid,
+  filename: String,
+  data: Vec<u8>,
+) -> tauri::Result<Value> {
+  Ok(
+    client
+      .note_attachment_upload(note_id, &filename, data)
+      .await?,
+  )
+}
+
+#[tauri::command]
+pub async fn note_attachment_content(
+  client: State<'_, Client>,
+  note_uuid: Uuid,
+  attachment_uuid: Uuid,
+) -> tauri::Result<Vec<u8>> {
+  Ok(
+    client
+      .notes_get_bytes(&format!(
+        "/api/notes/attachments/{note_uuid}/{attachment_uuid}"
+      ))
+      .await?,
+  )
+}
+
+#[tauri::command]
+pub async fn note_attachment_thumbnail(
+  client: State<'_, Client>,
+  note_uuid: Uuid,
+  attachment_uuid: Uuid,
+) -> tauri::Result<Vec<u8>> {
+  Ok(
+    client
+      .notes_get_bytes(&format!(
+        "/api/notes/attachments/{note_uuid}/{attachment_uuid}/thumbnail"
+      ))
+      .await?,
+  )
+}
+
+#[tauri::command]
+pub async fn delete_note_attachment(
+  client: State<'_, Client>,
+  attachment_id: Uuid,
+) -> tauri::Result<()> {
+  client
+    .notes_send(
+      Method::DELETE,
+      "/api/notes/attachments",
+      json!({ "attachment_id": attachment_id }),
+    )
+    .await?;
+  Ok(())
+}
+
+/// Bytes used by attachments in the user's own notes and the quota.
+#[tauri::command]
+pub async fn note_attachment_usage(client: State<'_, Client>) -> tauri::Result<Value> {
+  Ok(client.notes_get("/api/notes/attachments/usage").await?)
+}
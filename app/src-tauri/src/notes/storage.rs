@@ -24,6 +24,7 @@ use crate::{
 
 const STORE_PATH: &str = "notes.json";
 const NOTES_KEY: &str = "notes";
+const FOLDERS_KEY: &str = "folders";
 
 #[tauri::command]
 pub async fn list_notes_store(store: State<'_, NotesStore>) -> tauri::Result<Vec<NoteInfo>> {
@@ -32,6 +33,13 @@ pub async fn list_notes_store(store: State<'_, NotesStore>) -> tauri::Result<Vec
   Ok(notes)
 }
 
+#[tauri::command]
+pub async fn list_note_folders_store(
+  store: State<'_, NotesStore>,
+) -> tauri::Result<Vec<NoteFolderInfo>> {
+  Ok(store.get_folders().await)
+}
+
 #[tauri::command]
 pub async fn get_note_store(
   store: State<'_, NotesStore>,
@@ -63,6 +71,7 @@ pub async fn save_note_content(
 pub struct NotesStore {
   store: Arc<tauri_plugin_store::Store<Wry>>,
   notes: Arc<Mutex<Vec<NoteInfo>>>,
+  folders: Arc<Mutex<Vec<NoteFolderInfo>>>,
   dir: PathBuf,
   handle: AppHandle,
   initialized: Arc<AtomicBool>,
@@ -101,7 +110,27 @@ pub struct NoteInfo {
   pub can_edit: bool,
   pub last_updated: NaiveDateTime,
   #[serde(default)]
+  pub folder: Option<Uuid>,
+  #[serde(default)]
+  pub tags: Vec<String>,
+  #[serde(default)]
+  pub favourite: bool,
+  #[serde(default)]
   pub local_changes: bool,
+  /// The folder was changed while offline and still needs to be sent.
+  #[serde(default)]
+  pub local_folder: bool,
+  /// Tags or favourite were changed while offline and still need to be sent.
+  #[serde(default)]
+  pub local_tags: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NoteFolderInfo {
+  pub id: Uuid,
+  pub parent: Option<Uuid>,
+  pub name: String,
+  pub position: i32,
 }
 
 impl NotesStore {
@@ -111,6 +140,10 @@ impl NotesStore {
       .get(NOTES_KEY)
       .and_then(|v| serde_json::from_value(v).ok())
       .unwrap_or_default();
+    let folders: Vec<NoteFolderInfo> = store
+      .get(FOLDERS_KEY)
+      .and_then(|v| serde_json::from_value(v).ok())
+      .unwrap_or_default();
 
     let dir = handle.path().app_data_dir()?;
     let syncing = Arc::new(Mutex::new(()));
@@ -118,6 +151,7 @@ impl NotesStore {
     let state = NotesStore {
       store,
       notes: Arc::new(Mutex::new(notes)),
+      folders: Arc::new(Mutex::new(folders)),
       dir,
       handle: handle.clone(),
       initialized: Arc::new(AtomicBool::new(false)),
@@ -177,12 +211,16 @@ impl NotesStore {
   }
 
   async fn handle_update(&self, update: WsUpdateMessage) {
-    let WsUpdateMessage::NoteContent { uuid } = update else {
-      return;
-    };
-
     let client = self.handle.state::<Client>();
-    self.sync_note_content(uuid, &client).await.ok();
+    match update {
+      WsUpdateMessage::NoteContent { uuid } => {
+        self.sync_note_content(uuid, &client).await.ok();
+      }
+      WsUpdateMessage::NoteFolders => {
+        self.sync_folders(&client).await.ok();
+      }
+      _ => (),
+    }
   }
 
   async fn initial_sync(handle: &AppHandle) -> Result<()> {
@@ -211,6 +249,9 @@ impl NotesStore {
 
     state.set_notes(notes).await?;
     state.write_local_edits(&old_notes).await;
+    if let Err(e) = state.sync_folders(&client).await {
+      eprintln!("Failed to sync note folders: {}", e);
+    }
 
     for note_id in note_content_to_sync {
       if let Err(e) = state.sync_note_content(note_id, &client).await {
@@ -251,6 +292,39 @@ impl NotesStore {
     let client = self.handle.state::<Client>();
 
     for note in notes {
+      if note.local_folder {
+        let pending = match client.note_file(note.id, note.folder).await {
+          Ok(()) => false,
+          Err(e) => {
+            eprintln!("Failed to sync note folder: {}", e);
+            true
+          }
+        };
+        // the note list may have been replaced by the server copy meanwhile
+        self
+          .set_note_folder(note.id, note.folder, pending)
+          .await
+          .ok();
+      }
+
+      if note.local_tags {
+        let res = match client.note_tags(note.id, &note.tags).await {
+          Ok(()) => client.note_favourite(note.id, note.favourite).await,
+          Err(e) => Err(e),
+        };
+        let pending = match res {
+          Ok(()) => false,
+          Err(e) => {
+            eprintln!("Failed to sync note tags: {}", e);
+            true
+          }
+        };
+        self
+          .set_note_tags(note.id, note.tags.clone(), note.favourite, pending)
+          .await
+          .ok();
+      }
+
       if !note.local_changes {
         continue;
       }
@@ -288,6 +362,60 @@ impl NotesStore {
     Ok(())
   }
 
+  async fn sync_folders(&self, client: &Client) -> Result<()> {
+    let raw_folders = client.notes_get("/api/notes/management/folders").await?;
+    let folders: Vec<NoteFolderInfo> = serde_json::from_value(raw_folders)?;
+    self.set_folders(folders).await
+  }
+
+  pub async fn get_folders(&self) -> Vec<NoteFolderInfo> {
+    self.folders.lock().await.clone()
+  }
+
+  pub async fn set_folders(&self, folders: Vec<NoteFolderInfo>) -> Result<()> {
+    self.store.set(FOLDERS_KEY, serde_json::to_value(&folders)?);
+    *self.folders.lock().await = folders;
+    self.store.save()?;
+    Ok(())
+  }
+
+  /// Records the folder of a note locally, `pending` marks it to be sent once
+  /// the connection is back.
+  pub async fn set_note_folder(&self, id: Uuid, folder: Option<Uuid>, pending: bool) -> Result<()> {
+    let mut notes = self.notes.lock().await;
+    if let Some(note) = notes.iter_mut().find(|n| n.id == id) {
+      note.folder = folder;
+      note.local_folder = pending;
+    }
+
+    self.store.set(NOTES_KEY, serde_json::to_value(&*notes)?);
+    drop(notes);
+    self.store.save()?;
+    Ok(())
+  }
+
+  /// Records tags and favourite of a note locally, `pending` marks them to be
+  /// sent once the connection is back.
+  pub async fn set_note_tags(
+    &self,
+    id: Uuid,
+    tags: Vec<String>,
+    favourite: bool,
+    pending: bool,
+  ) -> Result<()> {
+    let mut notes = self.notes.lock().await;
+    if let Some(note) = notes.iter_mut().find(|n| n.id == id) {
+      note.tags = tags;
+      note.favourite = favourite;
+      note.local_tags = pending;
+    }
+
+    self.store.set(NOTES_KEY, serde_json::to_value(&*notes)?);
+    drop(notes);
+    self.store.save()?;
+    Ok(())
+  }
+
   pub async fn get_note(&self, id: Uuid) -> Option<NoteInfo> {
     let notes = self.notes.lock().await;
     notes.iter().find(|n| n.id == id).cloned()
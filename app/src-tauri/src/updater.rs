@@ -51,6 +51,10 @@ pub enum WsUpdateMessage {
   NoteSnapshot { uuid: Uuid, note_id: Uuid },
   NoteSnapshotsCleaned,
   NoteContent { uuid: Uuid },
+  NoteFolders,
+  NoteComments { note_id: Uuid },
+  NoteMention { note_id: Uuid },
+  NoteAttachments { note_id: Uuid },
 }
 
 #[derive(Serialize, Clone)]
@@ -268,7 +272,11 @@ impl Updater {
         WsUpdateMessage::Note { .. }
         | WsUpdateMessage::NoteSnapshot { .. }
         | WsUpdateMessage::NoteSnapshotsCleaned
-        | WsUpdateMessage::NoteContent { .. } => UpdateMessage::NotesUpdated,
+        | WsUpdateMessage::NoteContent { .. }
+        | WsUpdateMessage::NoteFolders
+        | WsUpdateMessage::NoteComments { .. }
+        | WsUpdateMessage::NoteMention { .. }
+        | WsUpdateMessage::NoteAttachments { .. } => UpdateMessage::NotesUpdated,
         WsUpdateMessage::User { .. } => UpdateMessage::UsersUpdated,
       };
 
@@ -10,10 +10,17 @@ pub struct Model {
   #[sea_orm(primary_key, auto_increment = false)]
   pub id: Uuid,
   pub name: String,
+  pub require_mfa: bool,
   #[sea_orm(has_many)]
   pub group_permissions: HasMany<super::group_permission::Entity>,
   #[sea_orm(has_many)]
+  pub group_parents: HasMany<super::group_parent::Entity>,
+  #[sea_orm(has_many)]
   pub o_auth_policy_contents: HasMany<super::o_auth_policy_content::Entity>,
+  #[sea_orm(has_many)]
+  pub oidc_provider_groups: HasMany<super::oidc_provider_group::Entity>,
+  #[sea_orm(has_many, via = "note_group")]
+  pub shared_notes: HasMany<super::note::Entity>,
   #[sea_orm(has_many, via = "o_auth_client_group")]
   pub o_auth_clients: HasMany<super::o_auth_client::Entity>,
   #[sea_orm(has_many, via = "group_user")]
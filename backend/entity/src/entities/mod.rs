@@ -2,14 +2,30 @@
 
 pub mod prelude;
 
+pub mod account_deletion;
 pub mod apod;
+pub mod data_export;
 pub mod group;
+pub mod group_parent;
 pub mod group_permission;
 pub mod group_user;
+pub mod impersonation_audit;
 pub mod invalid_jwt;
 pub mod key;
+pub mod known_device;
 pub mod note;
+pub mod note_attachment;
+pub mod note_comment;
+pub mod note_comment_thread;
+pub mod note_favourite;
+pub mod note_folder;
+pub mod note_folder_note;
+pub mod note_group;
+pub mod note_link;
+pub mod note_mention;
 pub mod note_snapshot;
+pub mod note_snapshot_policy;
+pub mod note_tag;
 pub mod note_user;
 pub mod o_auth_client;
 pub mod o_auth_client_additional_redirect_uri;
@@ -20,6 +36,8 @@ pub mod o_auth_policy;
 pub mod o_auth_policy_content;
 pub mod o_auth_scope;
 pub mod o_auth_scope_o_auth_policy;
+pub mod oidc_provider;
+pub mod oidc_provider_group;
 pub mod passkey;
 pub mod sea_orm_active_enums;
 pub mod session;
@@ -27,4 +45,5 @@ pub mod settings;
 pub mod setup;
 pub mod user;
 pub mod user_avatar;
+pub mod user_identity;
 pub mod user_settings;
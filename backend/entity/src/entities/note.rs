@@ -17,8 +17,27 @@ pub struct Model {
   pub owner: Uuid,
   pub public_access: Option<NoteShareAccess>,
   pub last_updated: DateTime,
+  pub deleted_at: Option<DateTime>,
+  #[sea_orm(has_many)]
+  pub note_attachments: HasMany<super::note_attachment::Entity>,
+  #[sea_orm(has_many)]
+  pub note_comment_threads: HasMany<super::note_comment_thread::Entity>,
+  #[sea_orm(has_many)]
+  pub note_favourites: HasMany<super::note_favourite::Entity>,
+  #[sea_orm(has_many)]
+  pub note_folder_notes: HasMany<super::note_folder_note::Entity>,
+  #[sea_orm(has_many)]
+  pub note_links: HasMany<super::note_link::Entity>,
+  #[sea_orm(has_many)]
+  pub note_mentions: HasMany<super::note_mention::Entity>,
   #[sea_orm(has_many)]
   pub note_snapshots: HasMany<super::note_snapshot::Entity>,
+  #[sea_orm(has_one)]
+  pub note_snapshot_policy: HasOne<super::note_snapshot_policy::Entity>,
+  #[sea_orm(has_many)]
+  pub note_tags: HasMany<super::note_tag::Entity>,
+  #[sea_orm(has_many, via = "note_group")]
+  pub shared_groups: HasMany<super::group::Entity>,
   #[sea_orm(has_many, via = "note_user", relation_enum = "SharedUser")]
   pub shared_users: HasMany<super::user::Entity>,
   #[sea_orm(
@@ -12,6 +12,8 @@ pub struct Model {
   pub created_at: DateTime,
   pub preview: String,
   pub note_id: Uuid,
+  pub label: Option<String>,
+  pub pinned: bool,
   #[sea_orm(
     belongs_to,
     from = "note_id",
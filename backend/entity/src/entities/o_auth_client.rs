@@ -1,5 +1,6 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 2.0
 
+use super::sea_orm_active_enums::AuthLevel;
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
@@ -15,6 +16,7 @@ pub struct Model {
   pub salt: String,
   pub confidential: bool,
   pub require_pkce: bool,
+  pub required_acr: Option<AuthLevel>,
   #[sea_orm(has_many)]
   pub o_auth_client_additional_redirect_uris:
     HasMany<super::o_auth_client_additional_redirect_uri::Entity>,
@@ -15,6 +15,11 @@ pub struct Model {
   pub user_id: Uuid,
   pub created: DateTime,
   pub used: DateTime,
+  pub aaguid: Option<Uuid>,
+  pub authenticator_type: String,
+  pub user_verified: bool,
+  pub backup_eligible: bool,
+  pub backup_state: bool,
   #[sea_orm(
     belongs_to,
     from = "user_id",
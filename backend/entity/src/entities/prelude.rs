@@ -1,13 +1,29 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 2.0
 
+pub use super::account_deletion::Entity as AccountDeletion;
 pub use super::apod::Entity as Apod;
+pub use super::data_export::Entity as DataExport;
 pub use super::group::Entity as Group;
+pub use super::group_parent::Entity as GroupParent;
 pub use super::group_permission::Entity as GroupPermission;
 pub use super::group_user::Entity as GroupUser;
+pub use super::impersonation_audit::Entity as ImpersonationAudit;
 pub use super::invalid_jwt::Entity as InvalidJwt;
 pub use super::key::Entity as Key;
+pub use super::known_device::Entity as KnownDevice;
 pub use super::note::Entity as Note;
+pub use super::note_attachment::Entity as NoteAttachment;
+pub use super::note_comment::Entity as NoteComment;
+pub use super::note_comment_thread::Entity as NoteCommentThread;
+pub use super::note_favourite::Entity as NoteFavourite;
+pub use super::note_folder::Entity as NoteFolder;
+pub use super::note_folder_note::Entity as NoteFolderNote;
+pub use super::note_group::Entity as NoteGroup;
+pub use super::note_link::Entity as NoteLink;
+pub use super::note_mention::Entity as NoteMention;
 pub use super::note_snapshot::Entity as NoteSnapshot;
+pub use super::note_snapshot_policy::Entity as NoteSnapshotPolicy;
+pub use super::note_tag::Entity as NoteTag;
 pub use super::note_user::Entity as NoteUser;
 pub use super::o_auth_client::Entity as OAuthClient;
 pub use super::o_auth_client_additional_redirect_uri::Entity as OAuthClientAdditionalRedirectUri;
@@ -18,10 +34,13 @@ pub use super::o_auth_policy::Entity as OAuthPolicy;
 pub use super::o_auth_policy_content::Entity as OAuthPolicyContent;
 pub use super::o_auth_scope::Entity as OAuthScope;
 pub use super::o_auth_scope_o_auth_policy::Entity as OAuthScopeOAuthPolicy;
+pub use super::oidc_provider::Entity as OidcProvider;
+pub use super::oidc_provider_group::Entity as OidcProviderGroup;
 pub use super::passkey::Entity as Passkey;
 pub use super::session::Entity as Session;
 pub use super::settings::Entity as Settings;
 pub use super::setup::Entity as Setup;
 pub use super::user::Entity as User;
 pub use super::user_avatar::Entity as UserAvatar;
+pub use super::user_identity::Entity as UserIdentity;
 pub use super::user_settings::Entity as UserSettings;
@@ -15,3 +15,42 @@ pub enum NoteShareAccess {
   #[sea_orm(string_value = "edit")]
   Edit,
 }
+
+#[derive(
+  Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::None)")]
+pub enum DataExportStatus {
+  #[sea_orm(string_value = "pending")]
+  Pending,
+  #[sea_orm(string_value = "ready")]
+  Ready,
+  #[sea_orm(string_value = "failed")]
+  Failed,
+}
+
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  PartialEq,
+  Eq,
+  PartialOrd,
+  Ord,
+  EnumIter,
+  DeriveActiveEnum,
+  Serialize,
+  Deserialize,
+  JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::None)")]
+pub enum AuthLevel {
+  #[sea_orm(string_value = "basic")]
+  Basic,
+  #[sea_orm(string_value = "mfa")]
+  Mfa,
+  #[sea_orm(string_value = "passkey")]
+  Passkey,
+}
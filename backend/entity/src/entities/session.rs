@@ -20,6 +20,13 @@ pub struct Model {
   pub created_at: DateTime,
   pub last_used_at: DateTime,
   pub refreshed_at: Option<DateTime>,
+  pub impersonator_id: Option<Uuid>,
+  pub auth_methods: String,
+  pub ip: Option<String>,
+  pub fingerprint: String,
+  pub new_device: bool,
+  pub revoke_token: Option<String>,
+  pub enrolment_only: bool,
   #[sea_orm(
     belongs_to,
     from = "user_id",
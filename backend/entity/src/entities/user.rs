@@ -18,12 +18,40 @@ pub struct Model {
   pub totp: Option<String>,
   #[sea_orm(unique)]
   pub oidc_subject: Option<String>,
+  pub disabled: bool,
+  pub disabled_reason: Option<String>,
+  pub disabled_until: Option<DateTime>,
+  pub tokens_revoked_at: Option<DateTime>,
   #[sea_orm(has_many)]
   pub apods: HasMany<super::apod::Entity>,
   #[sea_orm(has_many)]
+  pub data_exports: HasMany<super::data_export::Entity>,
+  #[sea_orm(has_many)]
+  pub known_devices: HasMany<super::known_device::Entity>,
+  #[sea_orm(has_many)]
+  pub note_attachments: HasMany<super::note_attachment::Entity>,
+  #[sea_orm(has_many)]
+  pub note_comment_threads: HasMany<super::note_comment_thread::Entity>,
+  #[sea_orm(has_many)]
+  pub note_comments: HasMany<super::note_comment::Entity>,
+  #[sea_orm(has_many)]
+  pub note_favourites: HasMany<super::note_favourite::Entity>,
+  #[sea_orm(has_many)]
+  pub note_folder_notes: HasMany<super::note_folder_note::Entity>,
+  #[sea_orm(has_many)]
+  pub note_folders: HasMany<super::note_folder::Entity>,
+  #[sea_orm(has_many)]
+  pub note_mentions: HasMany<super::note_mention::Entity>,
+  #[sea_orm(has_many)]
+  pub note_tags: HasMany<super::note_tag::Entity>,
+  #[sea_orm(has_many)]
   pub passkeys: HasMany<super::passkey::Entity>,
   #[sea_orm(has_many)]
   pub sessions: HasMany<super::session::Entity>,
+  #[sea_orm(has_many)]
+  pub user_identities: HasMany<super::user_identity::Entity>,
+  #[sea_orm(has_one)]
+  pub account_deletion: HasOne<super::account_deletion::Entity>,
   #[sea_orm(has_one)]
   pub user_avatar: HasOne<super::user_avatar::Entity>,
   #[sea_orm(has_one)]
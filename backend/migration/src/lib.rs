@@ -18,6 +18,27 @@ mod m20260622_145232_drop_invalid_jwt_table;
 mod m20260625_134247_note_last_updated;
 mod m20260704_120000_recreate_invalid_jwt;
 mod m20260806_091801_fix_forgein_keys_name;
+mod m20261019_080000_oidc_providers;
+mod m20261019_090000_impersonation;
+mod m20261019_100000_data_export;
+mod m20261019_110000_account_deletion;
+mod m20261019_120000_user_disabled;
+mod m20261019_130000_auth_context;
+mod m20261019_140000_passkey_metadata;
+mod m20261019_150000_session_devices;
+mod m20261019_160000_group_mfa;
+mod m20261019_170000_group_hierarchy;
+mod m20261019_180000_note_group_share;
+mod m20261019_190000_note_folders;
+mod m20261019_200000_note_tags;
+mod m20261019_210000_note_search;
+mod m20261019_220000_note_trash;
+mod m20261019_230000_note_snapshot_labels;
+mod m20261019_240000_note_snapshot_policy;
+mod m20261020_000000_note_comments;
+mod m20261021_000000_note_mentions;
+mod m20261022_000000_note_links;
+mod m20261023_000000_note_attachments;
 
 pub struct Migrator;
 
@@ -50,6 +71,27 @@ impl MigratorTrait for Migrator {
       Box::new(m20260625_134247_note_last_updated::Migration),
       Box::new(m20260704_120000_recreate_invalid_jwt::Migration),
       Box::new(m20260806_091801_fix_forgein_keys_name::Migration),
+      Box::new(m20261019_080000_oidc_providers::Migration),
+      Box::new(m20261019_090000_impersonation::Migration),
+      Box::new(m20261019_100000_data_export::Migration),
+      Box::new(m20261019_110000_account_deletion::Migration),
+      Box::new(m20261019_120000_user_disabled::Migration),
+      Box::new(m20261019_130000_auth_context::Migration),
+      Box::new(m20261019_140000_passkey_metadata::Migration),
+      Box::new(m20261019_150000_session_devices::Migration),
+      Box::new(m20261019_160000_group_mfa::Migration),
+      Box::new(m20261019_170000_group_hierarchy::Migration),
+      Box::new(m20261019_180000_note_group_share::Migration),
+      Box::new(m20261019_190000_note_folders::Migration),
+      Box::new(m20261019_200000_note_tags::Migration),
+      Box::new(m20261019_210000_note_search::Migration),
+      Box::new(m20261019_220000_note_trash::Migration),
+      Box::new(m20261019_230000_note_snapshot_labels::Migration),
+      Box::new(m20261019_240000_note_snapshot_policy::Migration),
+      Box::new(m20261020_000000_note_comments::Migration),
+      Box::new(m20261021_000000_note_mentions::Migration),
+      Box::new(m20261022_000000_note_links::Migration),
+      Box::new(m20261023_000000_note_attachments::Migration),
     ]
   }
 }
@@ -0,0 +1,157 @@
+use centaurus::db::migrations::{m3_user::User, m4_groups::Group};
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .create_table(
+        Table::create()
+          .table(OidcProvider::Table)
+          .if_not_exists()
+          .col(pk_uuid(OidcProvider::Id))
+          .col(string(OidcProvider::Name).unique_key())
+          .col(string(OidcProvider::Issuer))
+          .col(string(OidcProvider::ClientId))
+          .col(string(OidcProvider::ClientSecret))
+          .col(string(OidcProvider::Scopes))
+          .col(boolean(OidcProvider::Pkce))
+          .col(boolean(OidcProvider::CreateUser))
+          .col(boolean(OidcProvider::Enabled))
+          .col(string(OidcProvider::NameClaim))
+          .col(string(OidcProvider::EmailClaim))
+          .col(string_null(OidcProvider::GroupClaim))
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .create_table(
+        Table::create()
+          .table(OidcProviderGroup::Table)
+          .if_not_exists()
+          .primary_key(
+            Index::create()
+              .table(OidcProviderGroup::Table)
+              .col(OidcProviderGroup::ProviderId)
+              .col(OidcProviderGroup::ClaimValue)
+              .col(OidcProviderGroup::GroupId),
+          )
+          .col(uuid(OidcProviderGroup::ProviderId))
+          .col(string(OidcProviderGroup::ClaimValue))
+          .col(uuid(OidcProviderGroup::GroupId))
+          .foreign_key(
+            ForeignKey::create()
+              .from(OidcProviderGroup::Table, OidcProviderGroup::ProviderId)
+              .to(OidcProvider::Table, OidcProvider::Id)
+              .on_delete(ForeignKeyAction::Cascade)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .foreign_key(
+            ForeignKey::create()
+              .from(OidcProviderGroup::Table, OidcProviderGroup::GroupId)
+              .to(Group::Table, Group::Id)
+              .on_delete(ForeignKeyAction::Cascade)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .create_table(
+        Table::create()
+          .table(UserIdentity::Table)
+          .if_not_exists()
+          .col(pk_uuid(UserIdentity::Id))
+          .col(uuid(UserIdentity::UserId))
+          .col(uuid(UserIdentity::ProviderId))
+          .col(string(UserIdentity::Subject))
+          .col(string(UserIdentity::Email))
+          .col(date_time(UserIdentity::LinkedAt))
+          .col(date_time_null(UserIdentity::LastLoginAt))
+          .foreign_key(
+            ForeignKey::create()
+              .from(UserIdentity::Table, UserIdentity::UserId)
+              .to(User::Table, User::Id)
+              .on_delete(ForeignKeyAction::Cascade)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .foreign_key(
+            ForeignKey::create()
+              .from(UserIdentity::Table, UserIdentity::ProviderId)
+              .to(OidcProvider::Table, OidcProvider::Id)
+              .on_delete(ForeignKeyAction::Cascade)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_user_identity_provider_subject")
+          .table(UserIdentity::Table)
+          .col(UserIdentity::ProviderId)
+          .col(UserIdentity::Subject)
+          .unique()
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(UserIdentity::Table).to_owned())
+      .await?;
+
+    manager
+      .drop_table(Table::drop().table(OidcProviderGroup::Table).to_owned())
+      .await?;
+
+    manager
+      .drop_table(Table::drop().table(OidcProvider::Table).to_owned())
+      .await
+  }
+}
+
+#[derive(DeriveIden)]
+pub enum OidcProvider {
+  Table,
+  Id,
+  Name,
+  Issuer,
+  ClientId,
+  ClientSecret,
+  Scopes,
+  Pkce,
+  CreateUser,
+  Enabled,
+  NameClaim,
+  EmailClaim,
+  GroupClaim,
+}
+
+#[derive(DeriveIden)]
+pub enum OidcProviderGroup {
+  Table,
+  ProviderId,
+  ClaimValue,
+  GroupId,
+}
+
+#[derive(DeriveIden)]
+pub enum UserIdentity {
+  Table,
+  Id,
+  UserId,
+  ProviderId,
+  Subject,
+  Email,
+  LinkedAt,
+  LastLoginAt,
+}
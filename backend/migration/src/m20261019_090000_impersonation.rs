@@ -0,0 +1,70 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Session::Table)
+          .add_column(uuid_null(Session::ImpersonatorId))
+          .to_owned(),
+      )
+      .await?;
+
+    // user ids are not foreign keys so the trail outlives deleted accounts
+    manager
+      .create_table(
+        Table::create()
+          .table(ImpersonationAudit::Table)
+          .if_not_exists()
+          .col(pk_uuid(ImpersonationAudit::Id))
+          .col(uuid(ImpersonationAudit::AdminId))
+          .col(uuid(ImpersonationAudit::UserId))
+          .col(uuid(ImpersonationAudit::SessionId))
+          .col(string(ImpersonationAudit::Reason))
+          .col(date_time(ImpersonationAudit::StartedAt))
+          .col(date_time(ImpersonationAudit::ExpiresAt))
+          .col(date_time_null(ImpersonationAudit::EndedAt))
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(ImpersonationAudit::Table).to_owned())
+      .await?;
+
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Session::Table)
+          .drop_column(Session::ImpersonatorId)
+          .to_owned(),
+      )
+      .await
+  }
+}
+
+#[derive(DeriveIden)]
+enum Session {
+  Table,
+  ImpersonatorId,
+}
+
+#[derive(DeriveIden)]
+enum ImpersonationAudit {
+  Table,
+  Id,
+  AdminId,
+  UserId,
+  SessionId,
+  Reason,
+  StartedAt,
+  ExpiresAt,
+  EndedAt,
+}
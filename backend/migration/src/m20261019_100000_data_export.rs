@@ -0,0 +1,53 @@
+use centaurus::db::migrations::m3_user::User;
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .create_table(
+        Table::create()
+          .table(DataExport::Table)
+          .if_not_exists()
+          .col(pk_uuid(DataExport::Id))
+          .col(uuid(DataExport::UserId))
+          .col(string(DataExport::Status))
+          .col(boolean(DataExport::SendMail))
+          .col(big_integer_null(DataExport::Size))
+          .col(date_time(DataExport::CreatedAt))
+          .col(date_time_null(DataExport::FinishedAt))
+          .col(date_time(DataExport::ExpiresAt))
+          .foreign_key(
+            ForeignKey::create()
+              .from(DataExport::Table, DataExport::UserId)
+              .to(User::Table, User::Id)
+              .on_delete(ForeignKeyAction::Cascade)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(DataExport::Table).to_owned())
+      .await
+  }
+}
+
+#[derive(DeriveIden)]
+enum DataExport {
+  Table,
+  Id,
+  UserId,
+  Status,
+  SendMail,
+  Size,
+  CreatedAt,
+  FinishedAt,
+  ExpiresAt,
+}
@@ -0,0 +1,71 @@
+use centaurus::db::migrations::m3_user::User;
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // sqlite only supports a single column per alter statement
+    manager
+      .alter_table(
+        Table::alter()
+          .table(User::Table)
+          .add_column_if_not_exists(boolean(UserDisabled::Disabled).default(false))
+          .to_owned(),
+      )
+      .await?;
+    manager
+      .alter_table(
+        Table::alter()
+          .table(User::Table)
+          .add_column_if_not_exists(string_null(UserDisabled::DisabledReason))
+          .to_owned(),
+      )
+      .await?;
+    manager
+      .alter_table(
+        Table::alter()
+          .table(User::Table)
+          .add_column_if_not_exists(date_time_null(UserDisabled::DisabledUntil))
+          .to_owned(),
+      )
+      .await?;
+    manager
+      .alter_table(
+        Table::alter()
+          .table(User::Table)
+          .add_column_if_not_exists(date_time_null(UserDisabled::TokensRevokedAt))
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    for column in [
+      UserDisabled::Disabled,
+      UserDisabled::DisabledReason,
+      UserDisabled::DisabledUntil,
+      UserDisabled::TokensRevokedAt,
+    ] {
+      manager
+        .alter_table(
+          Table::alter()
+            .table(User::Table)
+            .drop_column(column)
+            .to_owned(),
+        )
+        .await?;
+    }
+    Ok(())
+  }
+}
+
+#[derive(DeriveIden)]
+enum UserDisabled {
+  Disabled,
+  DisabledReason,
+  DisabledUntil,
+  TokensRevokedAt,
+}
@@ -0,0 +1,60 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::m20241204_195924_create_oauth_client_table::OAuthClient;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Session::Table)
+          .add_column_if_not_exists(string(Session::AuthMethods).default(""))
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .alter_table(
+        Table::alter()
+          .table(OAuthClient::Table)
+          .add_column_if_not_exists(string_null(OAuthClientAcr::RequiredAcr))
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .alter_table(
+        Table::alter()
+          .table(OAuthClient::Table)
+          .drop_column(OAuthClientAcr::RequiredAcr)
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Session::Table)
+          .drop_column(Session::AuthMethods)
+          .to_owned(),
+      )
+      .await
+  }
+}
+
+#[derive(DeriveIden)]
+enum Session {
+  Table,
+  AuthMethods,
+}
+
+#[derive(DeriveIden)]
+enum OAuthClientAcr {
+  RequiredAcr,
+}
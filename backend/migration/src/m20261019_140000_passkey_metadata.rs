@@ -0,0 +1,71 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::m20241204_191710_create_passkey_table::Passkey;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    let columns = [
+      uuid_null(PasskeyMetadata::Aaguid),
+      string(PasskeyMetadata::AuthenticatorType)
+        .default("unknown")
+        .to_owned(),
+      boolean(PasskeyMetadata::UserVerified)
+        .default(false)
+        .to_owned(),
+      boolean(PasskeyMetadata::BackupEligible)
+        .default(false)
+        .to_owned(),
+      boolean(PasskeyMetadata::BackupState)
+        .default(false)
+        .to_owned(),
+    ];
+
+    // sqlite only supports a single column per alter statement
+    for mut column in columns {
+      manager
+        .alter_table(
+          Table::alter()
+            .table(Passkey::Table)
+            .add_column_if_not_exists(&mut column)
+            .to_owned(),
+        )
+        .await?;
+    }
+
+    Ok(())
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    for column in [
+      PasskeyMetadata::BackupState,
+      PasskeyMetadata::BackupEligible,
+      PasskeyMetadata::UserVerified,
+      PasskeyMetadata::AuthenticatorType,
+      PasskeyMetadata::Aaguid,
+    ] {
+      manager
+        .alter_table(
+          Table::alter()
+            .table(Passkey::Table)
+            .drop_column(column)
+            .to_owned(),
+        )
+        .await?;
+    }
+
+    Ok(())
+  }
+}
+
+#[derive(DeriveIden)]
+enum PasskeyMetadata {
+  Aaguid,
+  AuthenticatorType,
+  UserVerified,
+  BackupEligible,
+  BackupState,
+}
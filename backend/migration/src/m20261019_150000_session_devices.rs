@@ -0,0 +1,96 @@
+use centaurus::db::migrations::m3_user::User;
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    let columns = [
+      string_null(Session::Ip),
+      string(Session::Fingerprint).default("").to_owned(),
+      boolean(Session::NewDevice).default(false).to_owned(),
+      string_null(Session::RevokeToken),
+    ];
+
+    // sqlite only supports a single column per alter statement
+    for mut column in columns {
+      manager
+        .alter_table(
+          Table::alter()
+            .table(Session::Table)
+            .add_column_if_not_exists(&mut column)
+            .to_owned(),
+        )
+        .await?;
+    }
+
+    manager
+      .create_table(
+        Table::create()
+          .table(KnownDevice::Table)
+          .if_not_exists()
+          .col(uuid(KnownDevice::UserId))
+          .col(string(KnownDevice::Fingerprint))
+          .col(date_time(KnownDevice::FirstSeenAt))
+          .col(date_time(KnownDevice::LastSeenAt))
+          .primary_key(
+            Index::create()
+              .col(KnownDevice::UserId)
+              .col(KnownDevice::Fingerprint),
+          )
+          .foreign_key(
+            ForeignKey::create()
+              .from(KnownDevice::Table, KnownDevice::UserId)
+              .to(User::Table, User::Id)
+              .on_delete(ForeignKeyAction::Cascade)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(KnownDevice::Table).to_owned())
+      .await?;
+
+    for column in [
+      Session::RevokeToken,
+      Session::NewDevice,
+      Session::Fingerprint,
+      Session::Ip,
+    ] {
+      manager
+        .alter_table(
+          Table::alter()
+            .table(Session::Table)
+            .drop_column(column)
+            .to_owned(),
+        )
+        .await?;
+    }
+
+    Ok(())
+  }
+}
+
+#[derive(DeriveIden)]
+enum Session {
+  Table,
+  Ip,
+  Fingerprint,
+  NewDevice,
+  RevokeToken,
+}
+
+#[derive(DeriveIden)]
+enum KnownDevice {
+  Table,
+  UserId,
+  Fingerprint,
+  FirstSeenAt,
+  LastSeenAt,
+}
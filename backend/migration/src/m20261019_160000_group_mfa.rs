@@ -0,0 +1,59 @@
+use centaurus::db::migrations::m4_groups::Group;
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Group::Table)
+          .add_column_if_not_exists(boolean(GroupMfa::RequireMfa).default(false))
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Session::Table)
+          .add_column_if_not_exists(boolean(Session::EnrolmentOnly).default(false))
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Session::Table)
+          .drop_column(Session::EnrolmentOnly)
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Group::Table)
+          .drop_column(GroupMfa::RequireMfa)
+          .to_owned(),
+      )
+      .await
+  }
+}
+
+#[derive(DeriveIden)]
+enum GroupMfa {
+  RequireMfa,
+}
+
+#[derive(DeriveIden)]
+enum Session {
+  Table,
+  EnrolmentOnly,
+}
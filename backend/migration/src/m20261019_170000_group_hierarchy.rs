@@ -0,0 +1,53 @@
+use centaurus::db::migrations::m4_groups::Group;
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .create_table(
+        Table::create()
+          .table(GroupParent::Table)
+          .if_not_exists()
+          .col(uuid(GroupParent::GroupId))
+          .col(uuid(GroupParent::ParentId))
+          .primary_key(
+            Index::create()
+              .col(GroupParent::GroupId)
+              .col(GroupParent::ParentId),
+          )
+          .foreign_key(
+            ForeignKey::create()
+              .from(GroupParent::Table, GroupParent::GroupId)
+              .to(Group::Table, Group::Id)
+              .on_delete(ForeignKeyAction::Cascade)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .foreign_key(
+            ForeignKey::create()
+              .from(GroupParent::Table, GroupParent::ParentId)
+              .to(Group::Table, Group::Id)
+              .on_delete(ForeignKeyAction::Cascade)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(GroupParent::Table).to_owned())
+      .await
+  }
+}
+
+#[derive(DeriveIden)]
+enum GroupParent {
+  Table,
+  GroupId,
+  ParentId,
+}
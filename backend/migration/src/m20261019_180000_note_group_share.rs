@@ -0,0 +1,67 @@
+use centaurus::db::migrations::m4_groups::Group;
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .create_table(
+        Table::create()
+          .table(NoteGroup::Table)
+          .if_not_exists()
+          .col(uuid(NoteGroup::NoteId))
+          .col(uuid(NoteGroup::GroupId))
+          .col(custom(NoteGroup::Access, NoteShareAccess::Enum).default("view"))
+          .primary_key(
+            Index::create()
+              .col(NoteGroup::NoteId)
+              .col(NoteGroup::GroupId),
+          )
+          .foreign_key(
+            ForeignKey::create()
+              .from(NoteGroup::Table, NoteGroup::NoteId)
+              .to(Note::Table, Note::Id)
+              .on_delete(ForeignKeyAction::Cascade)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .foreign_key(
+            ForeignKey::create()
+              .from(NoteGroup::Table, NoteGroup::GroupId)
+              .to(Group::Table, Group::Id)
+              .on_delete(ForeignKeyAction::Cascade)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(NoteGroup::Table).to_owned())
+      .await
+  }
+}
+
+#[derive(DeriveIden)]
+enum NoteGroup {
+  Table,
+  NoteId,
+  GroupId,
+  Access,
+}
+
+#[derive(DeriveIden)]
+enum Note {
+  Table,
+  Id,
+}
+
+#[derive(DeriveIden)]
+enum NoteShareAccess {
+  #[sea_orm(iden = "note_share_access")]
+  Enum,
+}
@@ -0,0 +1,110 @@
+use centaurus::db::migrations::m3_user::User;
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .create_table(
+        Table::create()
+          .table(NoteFolder::Table)
+          .if_not_exists()
+          .col(pk_uuid(NoteFolder::Id))
+          .col(uuid(NoteFolder::UserId))
+          .col(uuid_null(NoteFolder::ParentId))
+          .col(string(NoteFolder::Name))
+          .col(integer(NoteFolder::Position).default(0))
+          .foreign_key(
+            ForeignKey::create()
+              .from(NoteFolder::Table, NoteFolder::UserId)
+              .to(User::Table, User::Id)
+              .on_delete(ForeignKeyAction::Cascade)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .foreign_key(
+            ForeignKey::create()
+              .from(NoteFolder::Table, NoteFolder::ParentId)
+              .to(NoteFolder::Table, NoteFolder::Id)
+              .on_delete(ForeignKeyAction::Cascade)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .create_table(
+        Table::create()
+          .table(NoteFolderNote::Table)
+          .if_not_exists()
+          .col(uuid(NoteFolderNote::UserId))
+          .col(uuid(NoteFolderNote::NoteId))
+          .col(uuid(NoteFolderNote::FolderId))
+          .primary_key(
+            Index::create()
+              .col(NoteFolderNote::UserId)
+              .col(NoteFolderNote::NoteId),
+          )
+          .foreign_key(
+            ForeignKey::create()
+              .from(NoteFolderNote::Table, NoteFolderNote::UserId)
+              .to(User::Table, User::Id)
+              .on_delete(ForeignKeyAction::Cascade)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .foreign_key(
+            ForeignKey::create()
+              .from(NoteFolderNote::Table, NoteFolderNote::NoteId)
+              .to(Note::Table, Note::Id)
+              .on_delete(ForeignKeyAction::Cascade)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .foreign_key(
+            ForeignKey::create()
+              .from(NoteFolderNote::Table, NoteFolderNote::FolderId)
+              .to(NoteFolder::Table, NoteFolder::Id)
+              .on_delete(ForeignKeyAction::Cascade)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(NoteFolderNote::Table).to_owned())
+      .await?;
+
+    manager
+      .drop_table(Table::drop().table(NoteFolder::Table).to_owned())
+      .await
+  }
+}
+
+#[derive(DeriveIden)]
+enum NoteFolder {
+  Table,
+  Id,
+  UserId,
+  ParentId,
+  Name,
+  Position,
+}
+
+#[derive(DeriveIden)]
+enum NoteFolderNote {
+  Table,
+  UserId,
+  NoteId,
+  FolderId,
+}
+
+#[derive(DeriveIden)]
+enum Note {
+  Table,
+  Id,
+}
@@ -0,0 +1,103 @@
+use centaurus::db::migrations::m3_user::User;
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .create_table(
+        Table::create()
+          .table(NoteTag::Table)
+          .if_not_exists()
+          .col(uuid(NoteTag::UserId))
+          .col(uuid(NoteTag::NoteId))
+          .col(string(NoteTag::Tag))
+          .primary_key(
+            Index::create()
+              .col(NoteTag::UserId)
+              .col(NoteTag::NoteId)
+              .col(NoteTag::Tag),
+          )
+          .foreign_key(
+            ForeignKey::create()
+              .from(NoteTag::Table, NoteTag::UserId)
+              .to(User::Table, User::Id)
+              .on_delete(ForeignKeyAction::Cascade)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .foreign_key(
+            ForeignKey::create()
+              .from(NoteTag::Table, NoteTag::NoteId)
+              .to(Note::Table, Note::Id)
+              .on_delete(ForeignKeyAction::Cascade)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .create_table(
+        Table::create()
+          .table(NoteFavourite::Table)
+          .if_not_exists()
+          .col(uuid(NoteFavourite::UserId))
+          .col(uuid(NoteFavourite::NoteId))
+          .primary_key(
+            Index::create()
+              .col(NoteFavourite::UserId)
+              .col(NoteFavourite::NoteId),
+          )
+          .foreign_key(
+            ForeignKey::create()
+              .from(NoteFavourite::Table, NoteFavourite::UserId)
+              .to(User::Table, User::Id)
+              .on_delete(ForeignKeyAction::Cascade)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .foreign_key(
+            ForeignKey::create()
+              .from(NoteFavourite::Table, NoteFavourite::NoteId)
+              .to(Note::Table, Note::Id)
+              .on_delete(ForeignKeyAction::Cascade)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(NoteFavourite::Table).to_owned())
+      .await?;
+
+    manager
+      .drop_table(Table::drop().table(NoteTag::Table).to_owned())
+      .await
+  }
+}
+
+#[derive(DeriveIden)]
+enum NoteTag {
+  Table,
+  UserId,
+  NoteId,
+  Tag,
+}
+
+#[derive(DeriveIden)]
+enum NoteFavourite {
+  Table,
+  UserId,
+  NoteId,
+}
+
+#[derive(DeriveIden)]
+enum Note {
+  Table,
+  Id,
+}
@@ -0,0 +1,114 @@
+use sea_orm_migration::{prelude::*, schema::*, sea_orm::DatabaseBackend};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// The index is a plain table on PostgreSQL with a generated `tsvector` and a
+/// FTS5 virtual table on SQLite. Existing notes are seeded with their preview
+/// and queued in `note_search_pending` until their full text is indexed.
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    let db = manager.get_connection();
+
+    match manager.get_database_backend() {
+      DatabaseBackend::Postgres => {
+        db.execute_unprepared(
+          r#"CREATE TABLE IF NOT EXISTS "note_search" (
+            "note_id" uuid PRIMARY KEY REFERENCES "note" ("id") ON DELETE CASCADE ON UPDATE CASCADE,
+            "title" text NOT NULL DEFAULT '',
+            "body" text NOT NULL DEFAULT '',
+            "document" tsvector GENERATED ALWAYS AS (
+              setweight(to_tsvector('simple', "title"), 'A') ||
+              setweight(to_tsvector('simple', "body"), 'B')
+            ) STORED
+          )"#,
+        )
+        .await?;
+        db.execute_unprepared(
+          r#"CREATE INDEX IF NOT EXISTS "idx-note_search-document" ON "note_search" USING GIN ("document")"#,
+        )
+        .await?;
+      }
+      _ => {
+        db.execute_unprepared(
+          r#"CREATE VIRTUAL TABLE IF NOT EXISTS "note_search" USING fts5(
+            "note_id" UNINDEXED,
+            "title",
+            "body",
+            tokenize = 'unicode61 remove_diacritics 2'
+          )"#,
+        )
+        .await?;
+        // virtual tables cannot have foreign keys, rows of deleted notes,
+        // including those removed with their owner, are dropped here
+        db.execute_unprepared(
+          r#"CREATE TRIGGER IF NOT EXISTS "note_search_note_delete" AFTER DELETE ON "note" BEGIN
+            DELETE FROM "note_search" WHERE "note_id" = OLD."id";
+          END"#,
+        )
+        .await?;
+      }
+    }
+
+    db.execute_unprepared(
+      r#"INSERT INTO "note_search" ("note_id", "title", "body") SELECT "id", "title", "preview" FROM "note""#,
+    )
+    .await?;
+
+    manager
+      .create_table(
+        Table::create()
+          .table(NoteSearchPending::Table)
+          .if_not_exists()
+          .col(uuid(NoteSearchPending::NoteId).primary_key())
+          .foreign_key(
+            ForeignKey::create()
+              .from(NoteSearchPending::Table, NoteSearchPending::NoteId)
+              .to(Note::Table, Note::Id)
+              .on_delete(ForeignKeyAction::Cascade)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .to_owned(),
+      )
+      .await?;
+    db.execute_unprepared(
+      r#"INSERT INTO "note_search_pending" ("note_id") SELECT "id" FROM "note""#,
+    )
+    .await?;
+
+    Ok(())
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(
+        Table::drop()
+          .table(NoteSearchPending::Table)
+          .if_exists()
+          .to_owned(),
+      )
+      .await?;
+    let db = manager.get_connection();
+    if manager.get_database_backend() == DatabaseBackend::Sqlite {
+      db.execute_unprepared(r#"DROP TRIGGER IF EXISTS "note_search_note_delete""#)
+        .await?;
+    }
+    db.execute_unprepared(r#"DROP TABLE IF EXISTS "note_search""#)
+      .await?;
+
+    Ok(())
+  }
+}
+
+#[derive(DeriveIden)]
+enum NoteSearchPending {
+  Table,
+  NoteId,
+}
+
+#[derive(DeriveIden)]
+enum Note {
+  Table,
+  Id,
+}
@@ -0,0 +1,48 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // sqlite only supports a single column per alter statement
+    manager
+      .alter_table(
+        Table::alter()
+          .table(NoteSnapshot::Table)
+          .add_column_if_not_exists(string_null(NoteSnapshot::Label))
+          .to_owned(),
+      )
+      .await?;
+    manager
+      .alter_table(
+        Table::alter()
+          .table(NoteSnapshot::Table)
+          .add_column_if_not_exists(boolean(NoteSnapshot::Pinned).default(false))
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    for column in [NoteSnapshot::Label, NoteSnapshot::Pinned] {
+      manager
+        .alter_table(
+          Table::alter()
+            .table(NoteSnapshot::Table)
+            .drop_column(column)
+            .to_owned(),
+        )
+        .await?;
+    }
+    Ok(())
+  }
+}
+
+#[derive(DeriveIden)]
+enum NoteSnapshot {
+  Table,
+  Label,
+  Pinned,
+}
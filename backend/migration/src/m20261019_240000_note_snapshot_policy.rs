@@ -0,0 +1,52 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .create_table(
+        Table::create()
+          .table(NoteSnapshotPolicy::Table)
+          .if_not_exists()
+          .col(uuid(NoteSnapshotPolicy::NoteId).primary_key())
+          .col(big_integer_null(NoteSnapshotPolicy::MinIntervalMins))
+          .col(big_integer_null(NoteSnapshotPolicy::MaxIntervalMins))
+          .col(big_integer_null(NoteSnapshotPolicy::MinSizeChange))
+          .col(string_null(NoteSnapshotPolicy::Retention))
+          .foreign_key(
+            ForeignKey::create()
+              .from(NoteSnapshotPolicy::Table, NoteSnapshotPolicy::NoteId)
+              .to(Note::Table, Note::Id)
+              .on_delete(ForeignKeyAction::Cascade)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(NoteSnapshotPolicy::Table).to_owned())
+      .await
+  }
+}
+
+#[derive(DeriveIden)]
+enum NoteSnapshotPolicy {
+  Table,
+  NoteId,
+  MinIntervalMins,
+  MaxIntervalMins,
+  MinSizeChange,
+  Retention,
+}
+
+#[derive(DeriveIden)]
+enum Note {
+  Table,
+  Id,
+}
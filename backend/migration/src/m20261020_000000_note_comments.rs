@@ -0,0 +1,130 @@
+use centaurus::db::migrations::m3_user::User;
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .create_table(
+        Table::create()
+          .table(NoteCommentThread::Table)
+          .if_not_exists()
+          .col(uuid(NoteCommentThread::Id).primary_key())
+          .col(uuid(NoteCommentThread::NoteId))
+          .col(binary(NoteCommentThread::AnchorStart))
+          .col(binary(NoteCommentThread::AnchorEnd))
+          .col(string(NoteCommentThread::Quote))
+          .col(date_time(NoteCommentThread::CreatedAt))
+          .col(date_time_null(NoteCommentThread::ResolvedAt))
+          .col(uuid_null(NoteCommentThread::ResolvedBy))
+          .foreign_key(
+            ForeignKey::create()
+              .from(NoteCommentThread::Table, NoteCommentThread::NoteId)
+              .to(Note::Table, Note::Id)
+              .on_delete(ForeignKeyAction::Cascade)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .foreign_key(
+            ForeignKey::create()
+              .from(NoteCommentThread::Table, NoteCommentThread::ResolvedBy)
+              .to(User::Table, User::Id)
+              .on_delete(ForeignKeyAction::SetNull)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_note_comment_thread_note_id")
+          .table(NoteCommentThread::Table)
+          .col(NoteCommentThread::NoteId)
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .create_table(
+        Table::create()
+          .table(NoteComment::Table)
+          .if_not_exists()
+          .col(uuid(NoteComment::Id).primary_key())
+          .col(uuid(NoteComment::ThreadId))
+          .col(uuid(NoteComment::Author))
+          .col(text(NoteComment::Body))
+          .col(date_time(NoteComment::CreatedAt))
+          .col(date_time_null(NoteComment::EditedAt))
+          .foreign_key(
+            ForeignKey::create()
+              .from(NoteComment::Table, NoteComment::ThreadId)
+              .to(NoteCommentThread::Table, NoteCommentThread::Id)
+              .on_delete(ForeignKeyAction::Cascade)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .foreign_key(
+            ForeignKey::create()
+              .from(NoteComment::Table, NoteComment::Author)
+              .to(User::Table, User::Id)
+              .on_delete(ForeignKeyAction::Cascade)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_note_comment_thread_id")
+          .table(NoteComment::Table)
+          .col(NoteComment::ThreadId)
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(NoteComment::Table).to_owned())
+      .await?;
+
+    manager
+      .drop_table(Table::drop().table(NoteCommentThread::Table).to_owned())
+      .await
+  }
+}
+
+#[derive(DeriveIden)]
+enum NoteCommentThread {
+  Table,
+  Id,
+  NoteId,
+  AnchorStart,
+  AnchorEnd,
+  Quote,
+  CreatedAt,
+  ResolvedAt,
+  ResolvedBy,
+}
+
+#[derive(DeriveIden)]
+enum NoteComment {
+  Table,
+  Id,
+  ThreadId,
+  Author,
+  Body,
+  CreatedAt,
+  EditedAt,
+}
+
+#[derive(DeriveIden)]
+enum Note {
+  Table,
+  Id,
+}
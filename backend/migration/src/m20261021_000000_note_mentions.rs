@@ -0,0 +1,61 @@
+use centaurus::db::migrations::m3_user::User;
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .create_table(
+        Table::create()
+          .table(NoteMention::Table)
+          .if_not_exists()
+          .col(uuid(NoteMention::NoteId))
+          .col(uuid(NoteMention::UserId))
+          .col(date_time(NoteMention::CreatedAt))
+          .primary_key(
+            Index::create()
+              .col(NoteMention::NoteId)
+              .col(NoteMention::UserId),
+          )
+          .foreign_key(
+            ForeignKey::create()
+              .from(NoteMention::Table, NoteMention::NoteId)
+              .to(Note::Table, Note::Id)
+              .on_delete(ForeignKeyAction::Cascade)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .foreign_key(
+            ForeignKey::create()
+              .from(NoteMention::Table, NoteMention::UserId)
+              .to(User::Table, User::Id)
+              .on_delete(ForeignKeyAction::Cascade)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(NoteMention::Table).to_owned())
+      .await
+  }
+}
+
+#[derive(DeriveIden)]
+enum NoteMention {
+  Table,
+  NoteId,
+  UserId,
+  CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Note {
+  Table,
+  Id,
+}
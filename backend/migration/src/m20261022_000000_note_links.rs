@@ -0,0 +1,63 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // The target has no foreign key, links to purged notes are kept so they
+    // can be reported as broken.
+    manager
+      .create_table(
+        Table::create()
+          .table(NoteLink::Table)
+          .if_not_exists()
+          .col(uuid(NoteLink::SourceId))
+          .col(uuid(NoteLink::TargetId))
+          .primary_key(
+            Index::create()
+              .col(NoteLink::SourceId)
+              .col(NoteLink::TargetId),
+          )
+          .foreign_key(
+            ForeignKey::create()
+              .from(NoteLink::Table, NoteLink::SourceId)
+              .to(Note::Table, Note::Id)
+              .on_delete(ForeignKeyAction::Cascade)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_note_link_target_id")
+          .table(NoteLink::Table)
+          .col(NoteLink::TargetId)
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(NoteLink::Table).to_owned())
+      .await
+  }
+}
+
+#[derive(DeriveIden)]
+enum NoteLink {
+  Table,
+  SourceId,
+  TargetId,
+}
+
+#[derive(DeriveIden)]
+enum Note {
+  Table,
+  Id,
+}
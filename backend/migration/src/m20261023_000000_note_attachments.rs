@@ -0,0 +1,76 @@
+use centaurus::db::migrations::m3_user::User;
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .create_table(
+        Table::create()
+          .table(NoteAttachment::Table)
+          .if_not_exists()
+          .col(uuid(NoteAttachment::Id).primary_key())
+          .col(uuid(NoteAttachment::NoteId))
+          .col(uuid_null(NoteAttachment::UploadedBy))
+          .col(string(NoteAttachment::FileName))
+          .col(string(NoteAttachment::ContentType))
+          .col(big_integer(NoteAttachment::Size))
+          .col(boolean(NoteAttachment::HasThumbnail))
+          .col(date_time(NoteAttachment::CreatedAt))
+          .foreign_key(
+            ForeignKey::create()
+              .from(NoteAttachment::Table, NoteAttachment::NoteId)
+              .to(Note::Table, Note::Id)
+              .on_delete(ForeignKeyAction::Cascade)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .foreign_key(
+            ForeignKey::create()
+              .from(NoteAttachment::Table, NoteAttachment::UploadedBy)
+              .to(User::Table, User::Id)
+              .on_delete(ForeignKeyAction::SetNull)
+              .on_update(ForeignKeyAction::Cascade),
+          )
+          .to_owned(),
+      )
+      .await?;
+
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_note_attachment_note_id")
+          .table(NoteAttachment::Table)
+          .col(NoteAttachment::NoteId)
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(NoteAttachment::Table).to_owned())
+      .await
+  }
+}
+
+#[derive(DeriveIden)]
+enum NoteAttachment {
+  Table,
+  Id,
+  NoteId,
+  UploadedBy,
+  FileName,
+  ContentType,
+  Size,
+  HasThumbnail,
+  CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Note {
+  Table,
+  Id,
+}
@@ -0,0 +1,142 @@
+use std::fmt::Display;
+
+use entity::sea_orm_active_enums::AuthLevel;
+
+/// A way a session was authenticated, named after its RFC 8176 `amr` value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuthMethod {
+  Password,
+  Otp,
+  Passkey,
+  Federated,
+}
+
+impl AuthMethod {
+  pub fn amr(self) -> &'static str {
+    match self {
+      AuthMethod::Password => "pwd",
+      AuthMethod::Otp => "otp",
+      AuthMethod::Passkey => "hwk",
+      AuthMethod::Federated => "fed",
+    }
+  }
+
+  fn from_amr(value: &str) -> Option<Self> {
+    match value {
+      "pwd" => Some(AuthMethod::Password),
+      "otp" => Some(AuthMethod::Otp),
+      "hwk" => Some(AuthMethod::Passkey),
+      "fed" => Some(AuthMethod::Federated),
+      _ => None,
+    }
+  }
+}
+
+/// The methods recorded on a session, stored space separated like scopes.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AuthMethods(Vec<AuthMethod>);
+
+impl AuthMethods {
+  pub fn new(methods: &[AuthMethod]) -> Self {
+    Self(methods.to_vec())
+  }
+
+  pub fn parse(value: &str) -> Self {
+    Self(
+      value
+        .split_whitespace()
+        .filter_map(AuthMethod::from_amr)
+        .collect(),
+    )
+  }
+
+  pub fn contains(&self, method: AuthMethod) -> bool {
+    self.0.contains(&method)
+  }
+
+  pub fn level(&self) -> AuthLevel {
+    if self.contains(AuthMethod::Passkey) {
+      AuthLevel::Passkey
+    } else if self.contains(AuthMethod::Password) && self.contains(AuthMethod::Otp) {
+      AuthLevel::Mfa
+    } else {
+      AuthLevel::Basic
+    }
+  }
+
+  /// The `amr` claim, `mfa` is added once more than one factor was used.
+  pub fn amr(&self) -> Vec<String> {
+    let mut amr: Vec<String> = self.0.iter().map(|m| m.amr().to_string()).collect();
+    if self.level() >= AuthLevel::Mfa {
+      amr.push("mfa".into());
+    }
+    amr
+  }
+}
+
+impl Display for AuthMethods {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let values: Vec<&str> = self.0.iter().map(|m| m.amr()).collect();
+    write!(f, "{}", values.join(" "))
+  }
+}
+
+/// Parses the `acr` value used in tokens and `acr_values`.
+pub fn parse_acr(value: &str) -> Option<AuthLevel> {
+  match value {
+    "basic" => Some(AuthLevel::Basic),
+    "mfa" => Some(AuthLevel::Mfa),
+    "passkey" => Some(AuthLevel::Passkey),
+    _ => None,
+  }
+}
+
+pub fn acr(level: AuthLevel) -> &'static str {
+  match level {
+    AuthLevel::Basic => "basic",
+    AuthLevel::Mfa => "mfa",
+    AuthLevel::Passkey => "passkey",
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use entity::sea_orm_active_enums::AuthLevel;
+
+  use super::{AuthMethod, AuthMethods, acr, parse_acr};
+
+  #[test]
+  fn levels_follow_the_methods() {
+    assert_eq!(AuthMethods::default().level(), AuthLevel::Basic);
+    assert_eq!(
+      AuthMethods::new(&[AuthMethod::Password]).level(),
+      AuthLevel::Basic
+    );
+    assert_eq!(
+      AuthMethods::new(&[AuthMethod::Password, AuthMethod::Otp]).level(),
+      AuthLevel::Mfa
+    );
+    assert_eq!(
+      AuthMethods::new(&[AuthMethod::Passkey]).level(),
+      AuthLevel::Passkey
+    );
+    assert!(AuthLevel::Passkey > AuthLevel::Mfa);
+  }
+
+  #[test]
+  fn stored_form_round_trips_and_skips_unknown_values() {
+    let methods = AuthMethods::new(&[AuthMethod::Password, AuthMethod::Otp]);
+    assert_eq!(methods.to_string(), "pwd otp");
+    assert_eq!(AuthMethods::parse("pwd  otp sms"), methods);
+    assert_eq!(methods.amr(), ["pwd", "otp", "mfa"]);
+    assert_eq!(AuthMethods::new(&[AuthMethod::Federated]).amr(), ["fed"]);
+  }
+
+  #[test]
+  fn acr_values_round_trip() {
+    for level in [AuthLevel::Basic, AuthLevel::Mfa, AuthLevel::Passkey] {
+      assert_eq!(parse_acr(acr(level)), Some(level));
+    }
+    assert_eq!(parse_acr("urn:unknown"), None);
+  }
+}
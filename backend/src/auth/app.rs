@@ -39,7 +39,11 @@ use tokio::{spawn, sync::mpsc, time::sleep};
 use tracing::warn;
 use uuid::Uuid;
 
-use crate::auth::session_auth::{SessionMeta, create_session_cookie};
+use crate::auth::{
+  amr::AuthMethods,
+  device::ClientInfo,
+  session_auth::{CurrentSession, SessionMeta, create_session_cookie},
+};
 
 pub fn router(rate_limiter: &mut RateLimiter) -> ApiRouter {
   ApiRouter::new()
@@ -66,9 +70,9 @@ pub fn router(rate_limiter: &mut RateLimiter) -> ApiRouter {
 #[derive(Clone, FromRequestParts, OperationIo)]
 #[from_request(via(Extension))]
 pub struct AppState {
-  codes: Arc<DashMap<Uuid, (Uuid, String, Instant)>>,
+  codes: Arc<DashMap<Uuid, (Uuid, String, Instant, AuthMethods)>>,
   device_login: Arc<DashMap<Uuid, (mpsc::Sender<Uuid>, String)>>,
-  approved_codes: Arc<DashMap<Uuid, (Uuid, String, Instant)>>,
+  approved_codes: Arc<DashMap<Uuid, (Uuid, String, Instant, AuthMethods)>>,
 }
 
 impl AppState {
@@ -86,9 +90,10 @@ impl AppState {
         loop {
           sleep(cleanup_interval).await;
           let now = Instant::now();
-          codes.retain(|_, &mut (_, _, instant)| now.duration_since(instant) < expiration_duration);
+          codes
+            .retain(|_, &mut (_, _, instant, _)| now.duration_since(instant) < expiration_duration);
           approved_codes
-            .retain(|_, &mut (_, _, instant)| now.duration_since(instant) < expiration_duration);
+            .retain(|_, &mut (_, _, instant, _)| now.duration_since(instant) < expiration_duration);
         }
       }
     });
@@ -113,12 +118,16 @@ struct CodeRes {
 
 async fn request_code(
   auth: JwtAuth,
+  Extension(session): Extension<CurrentSession>,
   state: AppState,
   Json(req): Json<CodeReq>,
 ) -> Result<Json<CodeRes>> {
   let code = Uuid::new_v4();
   let now = Instant::now();
-  state.codes.insert(code, (auth.user_id, req.challenge, now));
+  state.codes.insert(
+    code,
+    (auth.user_id, req.challenge, now, session.auth_methods),
+  );
   Ok(Json(CodeRes { code }))
 }
 
@@ -135,6 +144,7 @@ async fn exchange_code(
   jwt: JwtState,
   db: Connection,
   mut cookies: CookieJar,
+  client: ClientInfo,
   Json(req): Json<ExchangeCodeReq>,
 ) -> Result<(CookieJar, TokenRes<AuthRes>)> {
   let Some(code_entry) = state.codes.get(&req.code) else {
@@ -150,8 +160,10 @@ async fn exchange_code(
     bail!("Invalid verifier");
   }
 
+  // the app session inherits how the approving session signed in
   let user = code_entry.0;
-  let cookie = create_session_cookie(&db, &jwt, user, true, req.session).await?;
+  let methods = code_entry.3.clone();
+  let cookie = create_session_cookie(&db, &jwt, user, true, methods, req.session, &client).await?;
   cookies = cookies.add(cookie);
 
   drop(code_entry);
@@ -228,6 +240,7 @@ struct ApproveCodeReq {
 
 async fn approve_code(
   auth: JwtAuth,
+  Extension(session): Extension<CurrentSession>,
   state: AppState,
   Json(req): Json<ApproveCodeReq>,
 ) -> Result<()> {
@@ -237,9 +250,15 @@ async fn approve_code(
 
   let auth_code = Uuid::new_v4();
   sender.send(auth_code).await.ok();
-  state
-    .approved_codes
-    .insert(auth_code, (auth.user_id, challenge, Instant::now()));
+  state.approved_codes.insert(
+    auth_code,
+    (
+      auth.user_id,
+      challenge,
+      Instant::now(),
+      session.auth_methods,
+    ),
+  );
 
   Ok(())
 }
@@ -262,6 +281,7 @@ async fn retrieve_token(
   jwt: JwtState,
   db: Connection,
   mut cookies: CookieJar,
+  client: ClientInfo,
   Json(req): Json<RetrieveTokenReq>,
 ) -> Result<(CookieJar, TokenRes<AuthRes>)> {
   let Some(value) = state.approved_codes.get(&req.auth_code) else {
@@ -269,6 +289,7 @@ async fn retrieve_token(
   };
   let user_id = value.0;
   let challenge = value.1.clone();
+  let methods = value.3.clone();
   drop(value);
 
   if req.verifier.len() != 64 {
@@ -284,7 +305,8 @@ async fn retrieve_token(
     bail!("Invalid verifier");
   }
 
-  let cookie = create_session_cookie(&db, &jwt, user_id, false, req.session).await?;
+  let cookie =
+    create_session_cookie(&db, &jwt, user_id, false, methods, req.session, &client).await?;
   cookies = cookies.add(cookie);
 
   state.approved_codes.remove(&req.auth_code);
@@ -295,14 +317,18 @@ async fn retrieve_token(
 #[cfg(test)]
 mod test {
   use super::AppState;
-  use crate::db::{
-    DBTrait,
-    test::{auth_cookie, auth_state, body_json, insert_user, test_db},
+  use crate::{
+    auth::session_auth::deny_impersonation,
+    db::{
+      DBTrait,
+      test::{auth_cookie, auth_state, body_json, impersonation_cookie, insert_user, test_db},
+    },
   };
   use axum::{
     Extension, Router,
     body::Body,
     http::{Request, StatusCode, header},
+    middleware::from_fn,
     routing::post,
   };
   use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
@@ -318,10 +344,13 @@ mod test {
     BASE64_URL_SAFE_NO_PAD.encode(hasher.finalize())
   }
 
+  /// Mirrors the `/auth/app` nest, including its impersonation guard.
   fn app(db: Connection, jwt: JwtState) -> Router {
     Router::new()
       .route("/code", post(super::request_code))
       .route("/exchange", post(super::exchange_code))
+      .route("/approve", post(super::approve_code))
+      .route_layer(from_fn(deny_impersonation))
       .layer(Extension(AppState::init()))
       .layer(Extension(jwt))
       .layer(Extension(db))
@@ -391,9 +420,10 @@ mod test {
     let verifier = "a".repeat(64);
     let challenge = challenge_for(&verifier);
     let auth_code = Uuid::new_v4();
-    state
-      .approved_codes
-      .insert(auth_code, (user, challenge, Instant::now()));
+    state.approved_codes.insert(
+      auth_code,
+      (user, challenge, Instant::now(), Default::default()),
+    );
 
     let app = Router::new()
       .route("/retrieve", post(super::retrieve_token))
@@ -416,6 +446,29 @@ mod test {
     assert!(!sessions[0].is_app);
   }
 
+  #[tokio::test]
+  async fn impersonation_session_cannot_issue_app_logins() {
+    let db = test_db().await;
+    let jwt = auth_state(&db).await;
+    let admin = insert_user(&db, "admin", "admin@x.com").await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let cookie = impersonation_cookie(&db, &jwt, user, admin).await;
+    let app = app(db.clone(), jwt);
+
+    for (uri, body) in [
+      ("/code", json!({ "challenge": challenge_for("verifier") })),
+      ("/approve", json!({ "code": Uuid::new_v4() })),
+    ] {
+      let resp = app
+        .clone()
+        .oneshot(post_json(uri, Some(&cookie), body))
+        .await
+        .unwrap();
+      assert_eq!(resp.status(), StatusCode::FORBIDDEN, "{uri}");
+    }
+    assert_eq!(db.session().list_for_user(user).await.unwrap().len(), 1);
+  }
+
   #[tokio::test]
   async fn request_code_requires_authentication() {
     let db = test_db().await;
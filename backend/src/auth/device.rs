@@ -0,0 +1,270 @@
+use std::{
+  convert::Infallible,
+  net::{IpAddr, SocketAddr},
+};
+
+use aide::OperationIo;
+use axum::extract::{ConnectInfo, FromRequestParts};
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use centaurus::{backend::config::SiteConfig, db::init::Connection, error::Result, mail::Mailer};
+use http::request::Parts;
+use sha2::{Digest, Sha256};
+use tokio::spawn;
+use url::Url;
+use uuid::Uuid;
+
+use crate::{auth::session_auth::SessionMeta, db::DBTrait};
+
+/// Where a sign-in came from, along with what is needed to alert the user
+/// about sign-ins from unfamiliar devices.
+#[derive(Clone, Default, OperationIo)]
+pub struct ClientInfo {
+  pub ip: Option<String>,
+  mailer: Option<Mailer>,
+  site: Option<SiteConfig>,
+}
+
+impl<S: Sync> FromRequestParts<S> for ClientInfo {
+  type Rejection = Infallible;
+
+  async fn from_request_parts(
+    parts: &mut Parts,
+    _state: &S,
+  ) -> std::result::Result<Self, Self::Rejection> {
+    Ok(Self {
+      ip: client_ip(parts),
+      mailer: parts.extensions.get::<Mailer>().cloned(),
+      site: parts.extensions.get::<SiteConfig>().cloned(),
+    })
+  }
+}
+
+impl ClientInfo {
+  /// Sends the "new sign-in" mail in the background, sign-in does not wait
+  /// for the mail server.
+  pub fn alert(&self, db: &Connection, user_id: Uuid, device: String, revoke_token: &str) {
+    let (Some(mailer), Some(site)) = (self.mailer.clone(), self.site.clone()) else {
+      return;
+    };
+    let db = db.clone();
+    let ip = self.ip.clone();
+    let link = revoke_link(&site.site_url, revoke_token);
+
+    spawn(async move {
+      if !mailer.is_active().await {
+        return;
+      }
+      if let Err(err) = send_mail(&db, &mailer, &site.site_url, user_id, &device, ip, &link).await {
+        tracing::warn!(?err, "failed to send new sign-in mail");
+      }
+    });
+  }
+}
+
+/// Prefers the proxy headers over the peer address since the backend usually
+/// runs behind a reverse proxy. Only used for display, never for access
+/// decisions.
+fn client_ip(parts: &Parts) -> Option<String> {
+  let header = |name: &str| parts.headers.get(name).and_then(|v| v.to_str().ok());
+
+  header("x-forwarded-for")
+    .and_then(|v| v.split(',').next())
+    .or_else(|| header("x-real-ip"))
+    .and_then(|v| v.trim().parse::<IpAddr>().ok())
+    .or_else(|| {
+      parts
+        .extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|info| info.0.ip())
+    })
+    .map(|ip| ip.to_string())
+}
+
+/// Coarse device fingerprint, version numbers are dropped so browser and OS
+/// updates do not count as a new device.
+pub fn fingerprint(session: &SessionMeta) -> String {
+  let coarse = [
+    &session.name,
+    &session.application,
+    &session.operating_system,
+  ]
+  .map(|part| {
+    part
+      .split_whitespace()
+      .filter(|word| !word.starts_with(|c: char| c.is_ascii_digit()))
+      .collect::<Vec<_>>()
+      .join(" ")
+      .to_lowercase()
+  })
+  .join("|");
+
+  hash_token(&coarse)
+}
+
+pub fn hash_token(token: &str) -> String {
+  let mut hasher = Sha256::new();
+  hasher.update(token.as_bytes());
+  BASE64_URL_SAFE_NO_PAD.encode(hasher.finalize())
+}
+
+/// Human readable device name for the session list and mails.
+pub fn describe(session: &SessionMeta) -> String {
+  let parts: Vec<_> = [
+    &session.name,
+    &session.application,
+    &session.operating_system,
+  ]
+  .into_iter()
+  .map(|part| part.trim())
+  .filter(|part| !part.is_empty())
+  .collect();
+
+  if parts.is_empty() {
+    "Unknown device".into()
+  } else {
+    parts.join(", ")
+  }
+}
+
+fn revoke_link(site_url: &Url, token: &str) -> Url {
+  let mut link = site_url.clone();
+  if let Ok(mut segments) = link.path_segments_mut() {
+    segments.pop_if_empty();
+    segments.extend(["api", "user", "account", "sessions", "revoke", token]);
+  }
+  link
+}
+
+async fn send_mail(
+  db: &Connection,
+  mailer: &Mailer,
+  site_url: &Url,
+  user_id: Uuid,
+  device: &str,
+  ip: Option<String>,
+  revoke_link: &Url,
+) -> Result<()> {
+  let user = db.user_ext().get_user_by_id(user_id).await?;
+  let ip = ip.unwrap_or_else(|| "unknown".into());
+
+  mailer
+    .send_mail(
+      user.name,
+      user.email,
+      "Positron New Sign-In".to_string(),
+      mail_template(
+        &escape_html(device),
+        &ip,
+        revoke_link.as_str(),
+        site_url.as_str(),
+      ),
+    )
+    .await
+}
+
+/// The device name is chosen by the client signing in.
+fn escape_html(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+fn mail_template(device: &str, ip: &str, revoke_link: &str, link: &str) -> String {
+  format!(
+    r#"
+  <!DOCTYPE html>
+  <html lang="en">
+    <head>
+      <meta charset="UTF-8">
+      <meta name="viewport" content="width=device-width, initial-scale=1.0">
+      <title>New Sign-In</title>
+    </head>
+    <body>
+      <div style="display: flex; flex-direction: column;">
+        <header style="padding: 1rem; display: flex; flex-direction: column; align-items: center; justify-content: center;">
+          <h2 style="margin: 0;">New Sign-In</h2>
+          <p style="margin: 0;">Your account was signed in from a new device: {device} ({ip})</p>
+          <p style="margin: 0;">If this was not you, end the session with the link below and change your password</p>
+        </header>
+        <div style="display: flex; align-items: center; justify-content: center;">
+          <a href="{revoke_link}">Sign Out This Device</a>
+        </div>
+        <footer style="display: flex; align-items: center; justify-content: center;">
+          <p>Mail send from <a href="{link}">{link}</a></p>
+        </footer>
+      </div>
+    </body>
+  </html>
+  "#
+  )
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use axum::{body::Body, http::Request};
+
+  fn meta(name: &str, application: &str, operating_system: &str) -> SessionMeta {
+    SessionMeta {
+      name: name.into(),
+      application: application.into(),
+      operating_system: operating_system.into(),
+    }
+  }
+
+  #[test]
+  fn fingerprint_ignores_versions() {
+    assert_eq!(
+      fingerprint(&meta("", "Chrome 126", "macOS 15.1")),
+      fingerprint(&meta("", "Chrome 127.0.1", "macOS 15.2"))
+    );
+    assert_ne!(
+      fingerprint(&meta("", "Chrome 126", "macOS 15.1")),
+      fingerprint(&meta("", "Firefox 126", "macOS 15.1"))
+    );
+    assert_ne!(
+      fingerprint(&meta("", "Chrome 126", "macOS 15.1")),
+      fingerprint(&meta("", "Chrome 126", "Windows 11"))
+    );
+  }
+
+  #[test]
+  fn client_ip_prefers_forwarded_header() {
+    let (mut parts, _) = Request::builder()
+      .header("x-forwarded-for", "203.0.113.7, 10.0.0.1")
+      .header("x-real-ip", "198.51.100.1")
+      .body(Body::empty())
+      .unwrap()
+      .into_parts();
+    parts
+      .extensions
+      .insert(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 8000))));
+    assert_eq!(client_ip(&parts).as_deref(), Some("203.0.113.7"));
+
+    parts.headers.remove("x-forwarded-for");
+    assert_eq!(client_ip(&parts).as_deref(), Some("198.51.100.1"));
+
+    parts
+      .headers
+      .insert("x-real-ip", "garbage".parse().unwrap());
+    assert_eq!(client_ip(&parts).as_deref(), Some("127.0.0.1"));
+  }
+
+  #[test]
+  fn device_name_is_escaped_in_mail() {
+    let mail = mail_template(&escape_html("<b>x</b>"), "::1", "l", "s");
+    assert!(mail.contains("&lt;b&gt;x&lt;/b&gt;"));
+    assert!(!mail.contains("<b>x</b>"));
+  }
+
+  #[test]
+  fn revoke_link_points_at_the_sessions_api() {
+    let site = Url::parse("https://example.com/positron/").unwrap();
+    assert_eq!(
+      revoke_link(&site, "abc").as_str(),
+      "https://example.com/positron/api/user/account/sessions/revoke/abc"
+    );
+  }
+}
@@ -0,0 +1,52 @@
+use centaurus::{bail, db::init::Connection, error::Result};
+use uuid::Uuid;
+
+use crate::{
+  db::{DBTrait, user::user_ext::MfaEnrolment},
+  utils::{UpdateMessage, Updater},
+};
+
+/// Lifts the enrolment restriction from the user's sessions once a second
+/// factor is set up.
+pub async fn finish_enrolment(db: &Connection, updater: &Updater, user_id: Uuid) -> Result<()> {
+  db.session().lift_enrolment_restriction(user_id).await?;
+  updater.send_to(user_id, UpdateMessage::Sessions).await;
+  Ok(())
+}
+
+/// Rejects removing the last second factor of a user whose groups require
+/// MFA, `remaining` is the enrolment after the removal.
+pub async fn ensure_removable(
+  db: &Connection,
+  user_id: Uuid,
+  remaining: MfaEnrolment,
+) -> Result<()> {
+  if !remaining.enrolled() && db.group_ext().user_requires_mfa(user_id).await? {
+    bail!(CONFLICT, "MFA is required by your groups");
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::db::test::{add_user_to_group, insert_group, insert_user, test_db};
+
+  #[tokio::test]
+  async fn last_factor_is_kept_when_groups_require_mfa() {
+    let db = test_db().await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let none = MfaEnrolment::default();
+    assert!(ensure_removable(&db, user, none).await.is_ok());
+
+    let group = insert_group(&db, "admins").await;
+    add_user_to_group(&db, group, user).await;
+    db.group_ext().set_require_mfa(group, true).await.unwrap();
+    assert!(ensure_removable(&db, user, none).await.is_err());
+    let passkey = MfaEnrolment {
+      totp: false,
+      passkeys: 1,
+    };
+    assert!(ensure_removable(&db, user, passkey).await.is_ok());
+  }
+}
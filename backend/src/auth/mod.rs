@@ -1,5 +1,5 @@
 use aide::axum::ApiRouter;
-use axum::Extension;
+use axum::{Extension, middleware::from_fn};
 use centaurus::{
   backend::{
     auth::{init_pw_state, jwt_state::JwtState},
@@ -10,14 +10,24 @@ use centaurus::{
 use state::{PasskeyState, TotpState};
 
 use crate::{
-  auth::{app::AppState, jwt::JwtStateOther, session_auth::SessionAuth, state::WebauthnState},
+  auth::{
+    app::AppState,
+    jwt::JwtStateOther,
+    oidc::OidcState,
+    session_auth::{SessionAuth, SessionPolicy, allow_enrolment, deny_impersonation},
+    state::{PasskeyPolicy, WebauthnState},
+  },
   config::Config,
 };
 
+pub mod amr;
 mod app;
 mod config;
+pub mod device;
 pub mod jwt;
 mod logout;
+mod mfa;
+pub mod oidc;
 mod passkey;
 mod password;
 mod refresh;
@@ -27,24 +37,58 @@ mod totp;
 
 pub fn router(rate_limiter: &mut RateLimiter) -> ApiRouter {
   ApiRouter::new()
-    .nest("/logout", logout::router())
-    .nest("/passkey", passkey::router(rate_limiter))
-    .nest("/password", password::router(rate_limiter))
-    .nest("/totp", totp::router(rate_limiter))
+    .nest(
+      "/logout",
+      logout::router().route_layer(from_fn(allow_enrolment)),
+    )
+    .nest(
+      "/passkey",
+      passkey::router(rate_limiter)
+        .route_layer(from_fn(deny_impersonation))
+        .route_layer(from_fn(allow_enrolment)),
+    )
+    .nest(
+      "/password",
+      password::router(rate_limiter)
+        .route_layer(from_fn(deny_impersonation))
+        .route_layer(from_fn(allow_enrolment)),
+    )
+    .nest(
+      "/totp",
+      totp::router(rate_limiter)
+        .route_layer(from_fn(deny_impersonation))
+        .route_layer(from_fn(allow_enrolment)),
+    )
     .nest("/config", config::router())
-    .nest("/app", app::router(rate_limiter))
-    .merge(refresh::router())
+    .nest(
+      "/app",
+      app::router(rate_limiter).route_layer(from_fn(deny_impersonation)),
+    )
+    .nest(
+      "/oidc",
+      oidc::router(rate_limiter).route_layer(from_fn(deny_impersonation)),
+    )
+    .merge(refresh::router().route_layer(from_fn(allow_enrolment)))
 }
 
 pub async fn state(router: ApiRouter, config: &Config, db: &Connection) -> ApiRouter {
   router
     .layer(Extension(init_pw_state(&config.auth, db).await))
     .layer(Extension(
-      JwtState::init_with_auth(&config.auth, db, SessionAuth).await,
+      JwtState::init_with_auth(
+        &config.auth,
+        db,
+        SessionAuth(SessionPolicy::from_config(config)),
+      )
+      .await,
     ))
     .layer(Extension(JwtStateOther::init(&config.auth, db).await))
     .layer(Extension(PasskeyState::init()))
     .layer(Extension(TotpState::init(config)))
     .layer(Extension(WebauthnState::init(config)))
+    .layer(Extension(
+      PasskeyPolicy::from_config(config).expect("passkey policy is validated by Config::parse"),
+    ))
     .layer(Extension(AppState::init()))
+    .layer(Extension(OidcState::init(config)))
 }
@@ -0,0 +1,614 @@
+use aide::axum::{ApiRouter, routing::get_with};
+use argon2::password_hash::SaltString;
+use axum::{
+  Json,
+  extract::{Path, Query},
+  routing::get,
+};
+use axum_extra::extract::{CookieJar, cookie::Cookie};
+use centaurus::{
+  backend::{
+    auth::{jwt_state::JwtState, settings::UserSettings},
+    middleware::rate_limiter::RateLimiter,
+    request::redirect::Redirect,
+  },
+  bail,
+  db::{init::Connection, tables::ConnectionExt},
+  error::Result,
+};
+use entity::oidc_provider;
+use rsa::rand_core::OsRng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+use url::Url;
+use uuid::Uuid;
+
+use crate::{
+  auth::{
+    amr::{AuthMethod, AuthMethods},
+    device::ClientInfo,
+    jwt::{JwtAuthOther, JwtSpecial},
+    session_auth::{SessionMeta, create_session_cookie},
+  },
+  db::{
+    DBTrait,
+    oidc::provider::{OidcProviderData, OidcProviderLoginInfo, split_scopes},
+  },
+  utils::{UpdateMessage, Updater},
+};
+
+pub use state::OidcState;
+use state::{LoginIntent, PendingLogin, UpstreamUser, map_claims};
+
+pub mod provider;
+mod state;
+
+const OIDC_STATE_COOKIE: &str = "oidc_state";
+
+pub fn router(rate_limiter: &mut RateLimiter) -> ApiRouter {
+  ApiRouter::new()
+    .api_route(
+      "/",
+      get_with(providers, |op| op.id("listOidcLoginProviders")),
+    )
+    .api_route("/{provider}/url", get_with(url, |op| op.id("oidcUrl")))
+    .api_route(
+      "/{provider}/link",
+      get_with(link, |op| op.id("oidcLinkUrl")),
+    )
+    .layer(rate_limiter.create_limiter())
+    .route("/{provider}/callback", get(callback))
+    .nest("/provider", provider::router())
+}
+
+/// Moves the single provider configured through the old setup settings into
+/// the provider list and links the users that signed in through it.
+pub async fn init(db: &Connection) {
+  let settings = ConnectionExt::settings(db)
+    .get_settings::<UserSettings>()
+    .await
+    .expect("Failed to load user settings");
+  let (Some(issuer), Some(client_id), Some(client_secret)) = (
+    settings.oidc_issuer,
+    settings.oidc_client_id,
+    settings.oidc_client_secret,
+  ) else {
+    return;
+  };
+
+  if db
+    .oidc_provider()
+    .by_issuer(issuer.as_str())
+    .await
+    .expect("Failed to check for legacy OIDC provider")
+    .is_some()
+  {
+    return;
+  }
+
+  let group_claim = settings
+    .oidc_group_sync
+    .unwrap_or(false)
+    .then(|| settings.oidc_group_claim.unwrap_or("groups".into()));
+  let provider = db
+    .oidc_provider()
+    .create(OidcProviderData {
+      name: issuer.host_str().unwrap_or("SSO").to_string(),
+      issuer,
+      client_id,
+      client_secret: Some(client_secret),
+      scopes: split_scopes(
+        &settings
+          .oidc_scopes
+          .unwrap_or("openid email profile".into()),
+      ),
+      pkce: settings.oidc_pkce.unwrap_or(false),
+      create_user: settings.sso_create_user.unwrap_or(false),
+      enabled: settings.oidc_enabled.unwrap_or(false),
+      name_claim: "preferred_username".into(),
+      email_claim: "email".into(),
+      group_claim,
+      group_mappings: vec![],
+    })
+    .await
+    .expect("Failed to import legacy OIDC provider");
+
+  let linked = db
+    .user_identity()
+    .import_legacy(provider)
+    .await
+    .expect("Failed to link legacy OIDC users");
+  info!("Imported legacy OIDC provider and linked {} users", linked);
+}
+
+async fn providers(db: Connection) -> Result<Json<Vec<OidcProviderLoginInfo>>> {
+  Ok(Json(db.oidc_provider().list_enabled().await?))
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct ProviderPath {
+  provider: Uuid,
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct UrlQuery {
+  redirect_to: Option<String>,
+  #[serde(flatten)]
+  session: SessionMeta,
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct LinkQuery {
+  redirect_to: Option<String>,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct UrlRes {
+  url: String,
+}
+
+async fn enabled_provider(db: &Connection, provider: Uuid) -> Result<oidc_provider::Model> {
+  match db.oidc_provider().get(provider).await? {
+    Some(provider) if provider.enabled => Ok(provider),
+    _ => bail!(NOT_FOUND, "OIDC provider not found"),
+  }
+}
+
+async fn url(
+  db: Connection,
+  state: OidcState,
+  jwt: JwtState,
+  mut cookies: CookieJar,
+  Path(ProviderPath { provider }): Path<ProviderPath>,
+  Query(req): Query<UrlQuery>,
+) -> Result<(CookieJar, Json<UrlRes>)> {
+  let provider = enabled_provider(&db, provider).await?;
+  let redirect_to = req
+    .redirect_to
+    .as_deref()
+    .map(sanitize_redirect)
+    .transpose()?;
+
+  let (state_id, url) = state
+    .start(&provider, LoginIntent::Login(req.session), redirect_to)
+    .await?;
+  cookies = cookies.add(jwt.create_cookie(OIDC_STATE_COOKIE, state_id.to_string()));
+
+  Ok((
+    cookies,
+    Json(UrlRes {
+      url: url.to_string(),
+    }),
+  ))
+}
+
+async fn link(
+  auth: JwtAuthOther<JwtSpecial>,
+  db: Connection,
+  state: OidcState,
+  jwt: JwtState,
+  mut cookies: CookieJar,
+  Path(ProviderPath { provider }): Path<ProviderPath>,
+  Query(req): Query<LinkQuery>,
+) -> Result<(CookieJar, Json<UrlRes>)> {
+  let provider = enabled_provider(&db, provider).await?;
+  let redirect_to = req
+    .redirect_to
+    .as_deref()
+    .map(sanitize_redirect)
+    .transpose()?;
+
+  let (state_id, url) = state
+    .start(&provider, LoginIntent::Link(auth.user_id), redirect_to)
+    .await?;
+  cookies = cookies.add(jwt.create_cookie(OIDC_STATE_COOKIE, state_id.to_string()));
+
+  Ok((
+    cookies,
+    Json(UrlRes {
+      url: url.to_string(),
+    }),
+  ))
+}
+
+/// Only same origin paths are accepted as redirect targets after the login.
+fn sanitize_redirect(redirect_to: &str) -> Result<String> {
+  let base = Url::parse("http://localhost").unwrap();
+  let Ok(url) = base.join(redirect_to) else {
+    bail!("Invalid redirect url");
+  };
+  if url.origin() != base.origin() {
+    bail!("Invalid redirect url");
+  }
+
+  let query = url.query().map(|q| format!("?{q}")).unwrap_or_default();
+  let fragment = url.fragment().map(|f| format!("#{f}")).unwrap_or_default();
+  Ok(format!("{}{}{}", url.path(), query, fragment))
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct CallbackQuery {
+  code: Option<String>,
+  state: Option<Uuid>,
+  error: Option<String>,
+}
+
+struct CallbackError(&'static str);
+
+#[allow(clippy::too_many_arguments)]
+async fn callback(
+  db: Connection,
+  state: OidcState,
+  jwt: JwtState,
+  updater: Updater,
+  mut cookies: CookieJar,
+  client: ClientInfo,
+  Path(ProviderPath { provider }): Path<ProviderPath>,
+  Query(query): Query<CallbackQuery>,
+) -> Result<(CookieJar, Redirect)> {
+  let expected_state = cookies
+    .get(OIDC_STATE_COOKIE)
+    .map(|c| c.value().to_string());
+  cookies = cookies.remove(Cookie::from(OIDC_STATE_COOKIE));
+
+  let pending = query
+    .state
+    .filter(|s| expected_state.as_deref() == Some(s.to_string().as_str()))
+    .and_then(|s| state.take_pending(s))
+    .filter(|p| p.provider == provider);
+
+  let (path, error) = match pending {
+    None => ("/login".to_string(), Some("invalid_state".to_string())),
+    Some(pending) => {
+      let fallback = match pending.intent {
+        LoginIntent::Login(_) => "/login",
+        LoginIntent::Link(_) => "/account",
+      };
+
+      if let Some(error) = query.error {
+        (fallback.to_string(), Some(error))
+      } else if let Some(code) = query.code {
+        match complete(&db, &state, &jwt, &updater, &client, &pending, code).await {
+          Ok(Ok(cookie)) => {
+            if let Some(cookie) = cookie {
+              cookies = cookies.add(cookie);
+            }
+            (pending.redirect_to.unwrap_or("/".to_string()), None)
+          }
+          Ok(Err(CallbackError(error))) => (fallback.to_string(), Some(error.to_string())),
+          Err(err) => {
+            warn!(?err, %provider, "OIDC callback failed");
+            (fallback.to_string(), Some("invalid_code".to_string()))
+          }
+        }
+      } else {
+        (fallback.to_string(), Some("missing_code".to_string()))
+      }
+    }
+  };
+
+  let mut url = state.site_url().clone();
+  let target = Url::parse("http://localhost")
+    .unwrap()
+    .join(&path)
+    .unwrap_or_else(|_| Url::parse("http://localhost/").unwrap());
+  url.set_path(target.path());
+  url.set_fragment(target.fragment());
+
+  url.set_query(target.query());
+  if let Some(error) = error {
+    url.query_pairs_mut().append_pair("error", &error);
+  }
+
+  Ok((cookies, Redirect::found(url.to_string())))
+}
+
+async fn complete(
+  db: &Connection,
+  state: &OidcState,
+  jwt: &JwtState,
+  updater: &Updater,
+  client: &ClientInfo,
+  pending: &PendingLogin,
+  code: String,
+) -> Result<std::result::Result<Option<Cookie<'static>>, CallbackError>> {
+  let provider = enabled_provider(db, pending.provider).await?;
+  let claims = state.exchange(&provider, pending, code).await?;
+  let upstream = map_claims(&provider, &claims)?;
+  let identity = db
+    .user_identity()
+    .find(provider.id, &upstream.subject)
+    .await?;
+
+  match &pending.intent {
+    LoginIntent::Link(user) => {
+      match identity {
+        Some(identity) if identity.user_id != *user => {
+          return Ok(Err(CallbackError("identity_in_use")));
+        }
+        Some(identity) => {
+          db.user_identity()
+            .touch_login(identity.id, upstream.email.clone())
+            .await?;
+        }
+        None => {
+          db.user_identity()
+            .create(*user, provider.id, upstream.subject, upstream.email)
+            .await?;
+          info!("Linked {} identity to user {}", provider.name, user);
+        }
+      }
+      updater.send_to(*user, UpdateMessage::Identities).await;
+
+      Ok(Ok(None))
+    }
+    LoginIntent::Login(session) => {
+      let user = match identity {
+        Some(identity) => {
+          db.user_identity()
+            .touch_login(identity.id, upstream.email.clone())
+            .await?;
+          identity.user_id
+        }
+        None => match create_user(db, &provider, &upstream).await? {
+          Ok(user) => user,
+          Err(err) => return Ok(Err(err)),
+        },
+      };
+
+      sync_groups(db, updater, &provider, user, &upstream).await?;
+
+      debug!("OIDC user authenticated: {}", user);
+      let cookie = create_session_cookie(
+        db,
+        jwt,
+        user,
+        false,
+        AuthMethods::new(&[AuthMethod::Federated]),
+        session.clone(),
+        client,
+      )
+      .await?;
+      Ok(Ok(Some(cookie)))
+    }
+  }
+}
+
+async fn create_user(
+  db: &Connection,
+  provider: &oidc_provider::Model,
+  upstream: &UpstreamUser,
+) -> Result<std::result::Result<Uuid, CallbackError>> {
+  // existing accounts have to link the identity themselves, matching on the
+  // upstream email alone would let any provider take over local accounts
+  if db
+    .user()
+    .try_get_user_by_email(&upstream.email)
+    .await?
+    .is_some()
+  {
+    return Ok(Err(CallbackError("account_exists")));
+  }
+
+  if !provider.create_user {
+    return Ok(Err(CallbackError("user_not_found")));
+  }
+
+  let user = db
+    .user()
+    .create_user(
+      upstream.name.clone(),
+      upstream.email.clone(),
+      String::new(),
+      SaltString::generate(OsRng {}).to_string(),
+      true,
+      None,
+    )
+    .await?;
+  let identity = db
+    .user_identity()
+    .create(
+      user,
+      provider.id,
+      upstream.subject.clone(),
+      upstream.email.clone(),
+    )
+    .await?;
+  db.user_identity()
+    .touch_login(identity, upstream.email.clone())
+    .await?;
+
+  if !db.setup().is_setup().await? || db.user().count_users().await? == 1 {
+    let Some(admin_group_id) = db.setup().get_admin_group_id().await? else {
+      bail!(
+        INTERNAL_SERVER_ERROR,
+        "Admin group has not been created yet, cannot create initial user"
+      );
+    };
+
+    db.group()
+      .add_user_to_groups(user, vec![admin_group_id])
+      .await?;
+    db.setup().mark_completed().await?;
+    info!("Setup completed via OIDC, created user with ID {}", user);
+  }
+
+  Ok(Ok(user))
+}
+
+async fn sync_groups(
+  db: &Connection,
+  updater: &Updater,
+  provider: &oidc_provider::Model,
+  user: Uuid,
+  upstream: &UpstreamUser,
+) -> Result<()> {
+  if provider.group_claim.is_none() {
+    return Ok(());
+  }
+
+  let mut keep = Vec::new();
+  if let Some(admin_group) = db.setup().get_admin_group_id().await?
+    && db.group().is_last_admin(admin_group, user).await?
+  {
+    keep.push(admin_group);
+  }
+
+  if db
+    .oidc_provider()
+    .sync_user_groups(provider.id, user, &upstream.groups, &keep)
+    .await?
+  {
+    updater
+      .send_to(user, UpdateMessage::User { uuid: user })
+      .await;
+    updater.send_to(user, UpdateMessage::UserPermissions).await;
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use super::{OidcState, sanitize_redirect};
+  use crate::{
+    config::Config,
+    db::{
+      DBTrait,
+      oidc::provider::test::provider_data,
+      test::{auth_state, body_json, test_db, updater},
+    },
+  };
+  use axum::{
+    Extension, Router,
+    body::Body,
+    http::{Request, StatusCode, header},
+    routing::get,
+  };
+  use centaurus::db::init::Connection;
+  use tower::ServiceExt;
+  use uuid::Uuid;
+
+  async fn app(db: Connection) -> Router {
+    let jwt = auth_state(&db).await;
+    Router::new()
+      .route("/", get(super::providers))
+      .route("/{provider}/url", get(super::url))
+      .route("/{provider}/callback", get(super::callback))
+      .layer(Extension(OidcState::init(&Config::default())))
+      .layer(Extension(updater().await))
+      .layer(Extension(jwt))
+      .layer(Extension(db))
+  }
+
+  fn get_req(uri: &str, cookie: Option<&str>) -> Request<Body> {
+    let mut builder = Request::builder().method("GET").uri(uri);
+    if let Some(cookie) = cookie {
+      builder = builder.header(header::COOKIE, cookie);
+    }
+    builder.body(Body::empty()).unwrap()
+  }
+
+  #[tokio::test]
+  async fn init_imports_legacy_provider_once() {
+    use centaurus::{backend::auth::settings::UserSettings, db::tables::ConnectionExt};
+    use url::Url;
+
+    let db = test_db().await;
+    // nothing configured, nothing imported
+    super::init(&db).await;
+    assert!(db.oidc_provider().list().await.unwrap().is_empty());
+
+    ConnectionExt::settings(&db)
+      .save_settings(&UserSettings {
+        oidc_enabled: Some(true),
+        oidc_issuer: Some(Url::parse("https://sso.example.com").unwrap()),
+        oidc_client_id: Some("positron".into()),
+        oidc_client_secret: Some("secret".into()),
+        oidc_scopes: Some("openid email".into()),
+        oidc_group_sync: Some(true),
+        ..Default::default()
+      })
+      .await
+      .unwrap();
+    super::init(&db).await;
+    super::init(&db).await;
+
+    let providers = db.oidc_provider().list().await.unwrap();
+    assert_eq!(providers.len(), 1);
+    assert_eq!(providers[0].name, "sso.example.com");
+    assert_eq!(providers[0].scopes, vec!["openid", "email"]);
+    assert_eq!(providers[0].group_claim.as_deref(), Some("groups"));
+    assert!(providers[0].enabled);
+  }
+
+  #[test]
+  fn sanitize_redirect_only_allows_local_paths() {
+    assert_eq!(sanitize_redirect("/notes?a=1#b").unwrap(), "/notes?a=1#b");
+    assert_eq!(sanitize_redirect("notes").unwrap(), "/notes");
+    assert!(sanitize_redirect("https://evil.example.com/").is_err());
+    assert!(sanitize_redirect("//evil.example.com/path").is_err());
+  }
+
+  #[tokio::test]
+  async fn providers_lists_only_enabled_providers() {
+    let db = test_db().await;
+    db.oidc_provider()
+      .create(provider_data("GitLab", "https://gitlab.example.com"))
+      .await
+      .unwrap();
+    let mut disabled = provider_data("Old", "https://old.example.com");
+    disabled.enabled = false;
+    db.oidc_provider().create(disabled).await.unwrap();
+
+    let resp = app(db).await.oneshot(get_req("/", None)).await.unwrap();
+    let body = body_json(resp).await;
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["name"], "GitLab");
+  }
+
+  #[tokio::test]
+  async fn url_for_unknown_provider_is_not_found() {
+    let db = test_db().await;
+    let resp = app(db)
+      .await
+      .oneshot(get_req(
+        &format!(
+          "/{}/url?name=a&application=b&operating_system=c",
+          Uuid::new_v4()
+        ),
+        None,
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+  }
+
+  #[tokio::test]
+  async fn callback_without_matching_state_redirects_with_error() {
+    let db = test_db().await;
+    let provider = db
+      .oidc_provider()
+      .create(provider_data("GitLab", "https://gitlab.example.com"))
+      .await
+      .unwrap();
+    let state = Uuid::new_v4();
+
+    // the state was never issued, so even a matching cookie is rejected
+    let resp = app(db)
+      .await
+      .oneshot(get_req(
+        &format!("/{provider}/callback?code=abc&state={state}"),
+        Some(&format!("oidc_state={state}")),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::FOUND);
+    let location = resp.headers()[header::LOCATION].to_str().unwrap();
+    assert!(
+      location.ends_with("/login?error=invalid_state"),
+      "{location}"
+    );
+  }
+}
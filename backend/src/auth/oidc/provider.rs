@@ -0,0 +1,387 @@
+use aide::axum::{
+  ApiRouter,
+  routing::{delete_with, get_with, post_with, put_with},
+};
+use axum::{Json, extract::Path};
+use centaurus::{backend::auth::jwt_auth::JwtAuth, bail, db::init::Connection, error::Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+  auth::oidc::OidcState,
+  db::{
+    DBTrait,
+    oidc::provider::{OidcProviderData, OidcProviderInfo},
+  },
+  utils::{OidcProviderEdit, OidcProviderView, UpdateMessage, Updater},
+};
+
+pub fn router() -> ApiRouter {
+  ApiRouter::new()
+    .api_route("/", get_with(list, |op| op.id("listOidcProviders")))
+    .api_route("/", post_with(create, |op| op.id("createOidcProvider")))
+    .api_route("/", delete_with(delete, |op| op.id("deleteOidcProvider")))
+    .api_route("/", put_with(edit, |op| op.id("editOidcProvider")))
+    .api_route("/{uuid}", get_with(info, |op| op.id("infoOidcProvider")))
+}
+
+async fn list(
+  _auth: JwtAuth<OidcProviderView>,
+  db: Connection,
+) -> Result<Json<Vec<OidcProviderInfo>>> {
+  Ok(Json(db.oidc_provider().list().await?))
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+struct CreateRes {
+  uuid: Uuid,
+}
+
+async fn create(
+  _auth: JwtAuth<OidcProviderEdit>,
+  db: Connection,
+  updater: Updater,
+  Json(req): Json<OidcProviderData>,
+) -> Result<Json<CreateRes>> {
+  validate(&req)?;
+  if db
+    .oidc_provider()
+    .exists(req.name.clone(), Uuid::max())
+    .await?
+  {
+    bail!(CONFLICT, "provider with the given name already exists");
+  }
+  if req.client_secret.is_none() {
+    bail!(BAD_REQUEST, "client secret is required");
+  }
+
+  let uuid = db.oidc_provider().create(req).await?;
+  updater
+    .broadcast(UpdateMessage::OidcProvider { uuid })
+    .await;
+
+  Ok(Json(CreateRes { uuid }))
+}
+
+#[derive(Deserialize, Debug, JsonSchema)]
+struct DeleteReq {
+  uuid: Uuid,
+}
+
+async fn delete(
+  _auth: JwtAuth<OidcProviderEdit>,
+  db: Connection,
+  state: OidcState,
+  updater: Updater,
+  Json(req): Json<DeleteReq>,
+) -> Result<()> {
+  if !db.oidc_provider().delete(req.uuid).await? {
+    bail!(NOT_FOUND, "provider not found");
+  }
+  state.invalidate(req.uuid);
+  updater
+    .broadcast(UpdateMessage::OidcProvider { uuid: req.uuid })
+    .await;
+
+  Ok(())
+}
+
+#[derive(Deserialize, Debug, JsonSchema)]
+struct EditReq {
+  uuid: Uuid,
+  #[serde(flatten)]
+  data: OidcProviderData,
+}
+
+async fn edit(
+  _auth: JwtAuth<OidcProviderEdit>,
+  db: Connection,
+  state: OidcState,
+  updater: Updater,
+  Json(req): Json<EditReq>,
+) -> Result<()> {
+  validate(&req.data)?;
+  if db
+    .oidc_provider()
+    .exists(req.data.name.clone(), req.uuid)
+    .await?
+  {
+    bail!(CONFLICT, "provider with the given name already exists");
+  }
+  if db.oidc_provider().get(req.uuid).await?.is_none() {
+    bail!(NOT_FOUND, "provider not found");
+  }
+
+  db.oidc_provider().edit(req.uuid, req.data).await?;
+  state.invalidate(req.uuid);
+  updater
+    .broadcast(UpdateMessage::OidcProvider { uuid: req.uuid })
+    .await;
+
+  Ok(())
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct OidcProviderPath {
+  uuid: Uuid,
+}
+
+async fn info(
+  _auth: JwtAuth<OidcProviderView>,
+  db: Connection,
+  Path(OidcProviderPath { uuid }): Path<OidcProviderPath>,
+) -> Result<Json<OidcProviderInfo>> {
+  let Some(provider) = db.oidc_provider().info(uuid).await? else {
+    bail!(NOT_FOUND, "provider not found");
+  };
+  Ok(Json(provider))
+}
+
+pub fn validate(data: &OidcProviderData) -> Result<()> {
+  if data.name.trim().is_empty() {
+    bail!(BAD_REQUEST, "name cannot be empty");
+  }
+  if data.client_id.trim().is_empty() {
+    bail!(BAD_REQUEST, "client id cannot be empty");
+  }
+  if !data.scopes.iter().any(|s| s == "openid") {
+    bail!(BAD_REQUEST, "scopes must include openid");
+  }
+  if data.name_claim.trim().is_empty() || data.email_claim.trim().is_empty() {
+    bail!(BAD_REQUEST, "name and email claims cannot be empty");
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use crate::{
+    auth::oidc::OidcState,
+    config::Config,
+    db::{
+      DBTrait,
+      oidc::provider::test::provider_data,
+      test::{
+        auth_cookie, auth_state, body_json, grant_permissions, insert_user, test_db, updater,
+      },
+    },
+  };
+  use axum::{
+    Extension, Router,
+    body::Body,
+    http::{Request, StatusCode, header},
+    routing::get,
+  };
+  use centaurus::{
+    backend::auth::jwt_state::JwtState, backend::endpoints::websocket::state::Updater,
+    db::init::Connection,
+  };
+  use serde_json::{Value, json};
+  use tower::ServiceExt;
+  use uuid::Uuid;
+
+  use crate::utils::UpdateMessage;
+
+  fn app(db: Connection, jwt: JwtState, upd: Updater<UpdateMessage>) -> Router {
+    Router::new()
+      .route(
+        "/",
+        get(super::list)
+          .post(super::create)
+          .delete(super::delete)
+          .put(super::edit),
+      )
+      .route("/{uuid}", get(super::info))
+      .layer(Extension(OidcState::init(&Config::default())))
+      .layer(Extension(upd))
+      .layer(Extension(jwt))
+      .layer(Extension(db))
+  }
+
+  fn request(method: &str, uri: &str, cookie: &str, body: Option<Value>) -> Request<Body> {
+    let builder = Request::builder()
+      .method(method)
+      .uri(uri)
+      .header(header::COOKIE, cookie);
+    match body {
+      Some(value) => builder
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(value.to_string()))
+        .unwrap(),
+      None => builder.body(Body::empty()).unwrap(),
+    }
+  }
+
+  fn body(name: &str) -> Value {
+    json!({
+      "name": name,
+      "issuer": "https://idp.example.com",
+      "client_id": "positron",
+      "client_secret": "secret",
+      "scopes": ["openid", "email", "profile"],
+      "pkce": true,
+      "create_user": true,
+      "enabled": true,
+      "name_claim": "preferred_username",
+      "email_claim": "email",
+      "group_claim": null,
+      "group_mappings": []
+    })
+  }
+
+  struct Setup {
+    db: Connection,
+    jwt: JwtState,
+    upd: Updater<UpdateMessage>,
+    cookie: String,
+  }
+
+  async fn setup(perms: &[&str]) -> Setup {
+    let db = test_db().await;
+    let jwt = auth_state(&db).await;
+    let upd = updater().await;
+    let user = insert_user(&db, "admin", "admin@x.com").await;
+    grant_permissions(&db, user, perms).await;
+    let cookie = auth_cookie(&db, &jwt, user).await;
+    Setup {
+      db,
+      jwt,
+      upd,
+      cookie,
+    }
+  }
+
+  #[tokio::test]
+  async fn create_list_info_and_delete_flow() {
+    let s = setup(&["oidc_provider:view", "oidc_provider:edit"]).await;
+    let app = app(s.db.clone(), s.jwt, s.upd);
+
+    let resp = app
+      .clone()
+      .oneshot(request("POST", "/", &s.cookie, Some(body("GitLab"))))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let uuid = body_json(resp).await["uuid"].as_str().unwrap().to_string();
+
+    let resp = app
+      .clone()
+      .oneshot(request("GET", "/", &s.cookie, None))
+      .await
+      .unwrap();
+    let list = body_json(resp).await;
+    assert_eq!(list.as_array().unwrap().len(), 1);
+    // the client secret never leaves the server
+    assert!(list[0].get("client_secret").is_none());
+
+    let resp = app
+      .clone()
+      .oneshot(request("GET", &format!("/{uuid}"), &s.cookie, None))
+      .await
+      .unwrap();
+    assert_eq!(body_json(resp).await["name"], "GitLab");
+
+    let resp = app
+      .clone()
+      .oneshot(request(
+        "DELETE",
+        "/",
+        &s.cookie,
+        Some(json!({ "uuid": uuid })),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = app
+      .oneshot(request(
+        "DELETE",
+        "/",
+        &s.cookie,
+        Some(json!({ "uuid": uuid })),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+  }
+
+  #[tokio::test]
+  async fn create_validates_input() {
+    let s = setup(&["oidc_provider:view", "oidc_provider:edit"]).await;
+    s.db
+      .oidc_provider()
+      .create(provider_data("GitLab", "https://gitlab.example.com"))
+      .await
+      .unwrap();
+    let app = app(s.db, s.jwt, s.upd);
+
+    let resp = app
+      .clone()
+      .oneshot(request("POST", "/", &s.cookie, Some(body("GitLab"))))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let mut missing_openid = body("Other");
+    missing_openid["scopes"] = json!(["email"]);
+    let resp = app
+      .clone()
+      .oneshot(request("POST", "/", &s.cookie, Some(missing_openid)))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let mut missing_secret = body("Other");
+    missing_secret["client_secret"] = Value::Null;
+    let resp = app
+      .oneshot(request("POST", "/", &s.cookie, Some(missing_secret)))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+  }
+
+  #[tokio::test]
+  async fn edit_updates_provider() {
+    let s = setup(&["oidc_provider:view", "oidc_provider:edit"]).await;
+    let id = s
+      .db
+      .oidc_provider()
+      .create(provider_data("GitLab", "https://gitlab.example.com"))
+      .await
+      .unwrap();
+    let app = app(s.db.clone(), s.jwt, s.upd);
+
+    let mut req = body("Renamed");
+    req["uuid"] = json!(id);
+    req["client_secret"] = Value::Null;
+    let resp = app
+      .clone()
+      .oneshot(request("PUT", "/", &s.cookie, Some(req.clone())))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let provider = s.db.oidc_provider().get(id).await.unwrap().unwrap();
+    assert_eq!(provider.name, "Renamed");
+    assert_eq!(provider.client_secret, "secret");
+
+    req["uuid"] = json!(Uuid::new_v4());
+    req["name"] = json!("Unknown");
+    let resp = app
+      .oneshot(request("PUT", "/", &s.cookie, Some(req)))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+  }
+
+  #[tokio::test]
+  async fn view_only_user_cannot_edit() {
+    let s = setup(&["oidc_provider:view"]).await;
+    let app = app(s.db, s.jwt, s.upd);
+    let resp = app
+      .oneshot(request("POST", "/", &s.cookie, Some(body("GitLab"))))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+  }
+}
@@ -0,0 +1,454 @@
+use std::{collections::HashMap, sync::Arc, time::Instant};
+
+use aide::OperationIo;
+use axum::{Extension, extract::FromRequestParts};
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use centaurus::{backend::auth::oidc::URL_SAFE_CHARS, bail, error::Result, eyre::ContextCompat};
+use dashmap::DashMap;
+use entity::oidc_provider;
+use jsonwebtoken::{DecodingKey, Validation, jwk::JwkSet};
+use rand::seq::IndexedRandom;
+use reqwest::{Client, redirect::Policy};
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tokio::spawn;
+use url::Url;
+use uuid::Uuid;
+
+use crate::{auth::session_auth::SessionMeta, config::Config, db::oidc::provider::split_scopes};
+
+pub type Claims = HashMap<String, Value>;
+
+#[derive(Clone, FromRequestParts, OperationIo)]
+#[from_request(via(Extension))]
+pub struct OidcState {
+  client: Client,
+  site_url: Url,
+  discovered: Arc<DashMap<Uuid, Discovery>>,
+  pending: Arc<DashMap<Uuid, PendingLogin>>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct Discovery {
+  issuer: String,
+  authorization_endpoint: Url,
+  token_endpoint: Url,
+  userinfo_endpoint: Option<Url>,
+  jwks_uri: Url,
+  #[serde(skip)]
+  jwk_set: Option<JwkSet>,
+}
+
+#[derive(Clone, Debug)]
+pub enum LoginIntent {
+  Login(SessionMeta),
+  Link(Uuid),
+}
+
+#[derive(Clone, Debug)]
+pub struct PendingLogin {
+  pub provider: Uuid,
+  pub intent: LoginIntent,
+  pub redirect_to: Option<String>,
+  nonce: String,
+  code_verifier: Option<String>,
+  created: Instant,
+}
+
+/// The upstream account after the provider's claim mapping was applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpstreamUser {
+  pub subject: String,
+  pub name: String,
+  pub email: String,
+  pub groups: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenRes {
+  id_token: String,
+  access_token: Option<String>,
+}
+
+impl OidcState {
+  pub fn init(config: &Config) -> Self {
+    let pending: Arc<DashMap<Uuid, PendingLogin>> = Arc::new(DashMap::new());
+
+    spawn({
+      let pending = Arc::clone(&pending);
+
+      async move {
+        loop {
+          let now = Instant::now();
+          pending.retain(|_, login| now.duration_since(login.created).as_secs() < 600);
+          tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+        }
+      }
+    });
+
+    Self {
+      client: Client::builder()
+        .redirect(Policy::none())
+        .build()
+        .expect("Failed to build oidc http client"),
+      site_url: config.site.site_url.clone(),
+      discovered: Arc::new(DashMap::new()),
+      pending,
+    }
+  }
+
+  pub fn site_url(&self) -> &Url {
+    &self.site_url
+  }
+
+  /// Drops the cached discovery document so the next login refetches it,
+  /// used whenever a provider is edited or deleted.
+  pub fn invalidate(&self, provider: Uuid) {
+    self.discovered.remove(&provider);
+  }
+
+  pub fn redirect_uri(&self, provider: Uuid) -> Url {
+    let mut url = self.site_url.clone();
+    url.set_path(&format!("/api/auth/oidc/{provider}/callback"));
+    url.set_query(None);
+    url.set_fragment(None);
+    url
+  }
+
+  pub async fn discover(&self, provider: &oidc_provider::Model) -> Result<()> {
+    if self.discovered.contains_key(&provider.id) {
+      return Ok(());
+    }
+
+    let mut url = Url::parse(&provider.issuer)?;
+    url
+      .path_segments_mut()
+      .ok()
+      .context("Invalid issuer url")?
+      .pop_if_empty()
+      .push(".well-known")
+      .push("openid-configuration");
+
+    let res = self.client.get(url.clone()).send().await?;
+    if !res.status().is_success() {
+      bail!("Failed to retrieve OIDC configuration from {}", url);
+    }
+    let mut discovery: Discovery = res.json().await?;
+
+    let res = self.client.get(discovery.jwks_uri.clone()).send().await?;
+    if !res.status().is_success() {
+      bail!("Failed to retrieve JWKs from {}", discovery.jwks_uri);
+    }
+    discovery.jwk_set = Some(res.json().await?);
+
+    self.discovered.insert(provider.id, discovery);
+    Ok(())
+  }
+
+  pub async fn start(
+    &self,
+    provider: &oidc_provider::Model,
+    intent: LoginIntent,
+    redirect_to: Option<String>,
+  ) -> Result<(Uuid, Url)> {
+    self.discover(provider).await?;
+    let discovery = self
+      .discovered
+      .get(&provider.id)
+      .context("OIDC provider not discovered")?
+      .clone();
+
+    let state = Uuid::new_v4();
+    let nonce = Uuid::new_v4().to_string();
+    let code_verifier = provider.pkce.then(|| {
+      let mut rng = rand::rng();
+      (0..64)
+        .map(|_| *URL_SAFE_CHARS.choose(&mut rng).unwrap() as char)
+        .collect::<String>()
+    });
+
+    let mut url = discovery.authorization_endpoint;
+    {
+      let mut query = url.query_pairs_mut();
+      query
+        .append_pair("response_type", "code")
+        .append_pair("client_id", &provider.client_id)
+        .append_pair("redirect_uri", self.redirect_uri(provider.id).as_str())
+        .append_pair("state", &state.to_string())
+        .append_pair("nonce", &nonce);
+
+      let scopes = split_scopes(&provider.scopes);
+      if !scopes.is_empty() {
+        query.append_pair("scope", &scopes.join(" "));
+      }
+
+      if let Some(code_verifier) = &code_verifier {
+        let mut hasher = Sha256::new();
+        hasher.update(code_verifier.as_bytes());
+        query
+          .append_pair(
+            "code_challenge",
+            &BASE64_URL_SAFE_NO_PAD.encode(hasher.finalize()),
+          )
+          .append_pair("code_challenge_method", "S256");
+      }
+    }
+
+    self.pending.insert(
+      state,
+      PendingLogin {
+        provider: provider.id,
+        intent,
+        redirect_to,
+        nonce,
+        code_verifier,
+        created: Instant::now(),
+      },
+    );
+
+    Ok((state, url))
+  }
+
+  pub fn take_pending(&self, state: Uuid) -> Option<PendingLogin> {
+    self.pending.remove(&state).map(|(_, login)| login)
+  }
+
+  /// Redeems the authorization code, validates the id token against the
+  /// provider's keys and returns the merged id token and userinfo claims.
+  pub async fn exchange(
+    &self,
+    provider: &oidc_provider::Model,
+    pending: &PendingLogin,
+    code: String,
+  ) -> Result<Claims> {
+    self.discover(provider).await?;
+    let discovery = self
+      .discovered
+      .get(&provider.id)
+      .context("OIDC provider not discovered")?
+      .clone();
+
+    let mut form = vec![
+      ("grant_type", "authorization_code".to_string()),
+      ("code", code),
+      ("redirect_uri", self.redirect_uri(provider.id).to_string()),
+    ];
+    if let Some(code_verifier) = &pending.code_verifier {
+      form.push(("code_verifier", code_verifier.clone()));
+    }
+
+    let res = self
+      .client
+      .post(discovery.token_endpoint.clone())
+      .basic_auth(&provider.client_id, Some(&provider.client_secret))
+      .form(&form)
+      .send()
+      .await?;
+    if !res.status().is_success() {
+      let body = res.text().await.unwrap_or_default();
+      bail!(UNAUTHORIZED, "OIDC token request failed: {}", body);
+    }
+    let tokens: TokenRes = res.json().await?;
+
+    let mut claims = validate_id_token(&discovery, provider, &tokens.id_token, &pending.nonce)?;
+
+    if let (Some(endpoint), Some(access_token)) =
+      (&discovery.userinfo_endpoint, &tokens.access_token)
+    {
+      let res = self
+        .client
+        .get(endpoint.clone())
+        .bearer_auth(access_token)
+        .send()
+        .await?;
+      if res.status().is_success() {
+        let userinfo: Claims = res.json().await?;
+        // userinfo responses for a different subject must be ignored
+        if userinfo.get("sub") == claims.get("sub") {
+          claims.extend(userinfo);
+        }
+      }
+    }
+
+    Ok(claims)
+  }
+}
+
+fn validate_id_token(
+  discovery: &Discovery,
+  provider: &oidc_provider::Model,
+  token: &str,
+  nonce: &str,
+) -> Result<Claims> {
+  let header = jsonwebtoken::decode_header(token)?;
+  let jwk_set = discovery.jwk_set.as_ref().context("Missing JWKs")?;
+
+  let jwk = match &header.kid {
+    Some(kid) => jwk_set.find(kid),
+    None => jwk_set.keys.first(),
+  };
+  let Some(jwk) = jwk else {
+    bail!(UNAUTHORIZED, "Unknown id token signing key");
+  };
+  let key = DecodingKey::from_jwk(jwk)?;
+
+  let mut validation = Validation::new(header.alg);
+  validation.set_audience(std::slice::from_ref(&provider.client_id));
+  validation.set_issuer(&[&discovery.issuer]);
+
+  let data = jsonwebtoken::decode::<Claims>(token, &key, &validation)?;
+  if data.claims.get("nonce").and_then(Value::as_str) != Some(nonce) {
+    bail!(UNAUTHORIZED, "Invalid nonce");
+  }
+
+  Ok(data.claims)
+}
+
+/// Looks up a claim by a dot separated path, so nested claims like
+/// Keycloak's `realm_access.roles` can be mapped.
+fn claim<'c>(claims: &'c Claims, path: &str) -> Option<&'c Value> {
+  let mut parts = path.split('.');
+  let mut value = claims.get(parts.next()?)?;
+  for part in parts {
+    value = value.get(part)?;
+  }
+  Some(value)
+}
+
+pub fn map_claims(provider: &oidc_provider::Model, claims: &Claims) -> Result<UpstreamUser> {
+  let Some(subject) = claims.get("sub").and_then(Value::as_str) else {
+    bail!(UNAUTHORIZED, "Missing subject claim");
+  };
+  let Some(email) = claim(claims, &provider.email_claim).and_then(Value::as_str) else {
+    bail!(UNAUTHORIZED, "Missing email claim");
+  };
+  let name = claim(claims, &provider.name_claim)
+    .and_then(Value::as_str)
+    .unwrap_or(email);
+
+  let groups = match provider
+    .group_claim
+    .as_deref()
+    .and_then(|path| claim(claims, path))
+  {
+    Some(Value::Array(values)) => values
+      .iter()
+      .filter_map(|v| v.as_str().map(str::to_string))
+      .collect(),
+    Some(Value::String(value)) => vec![value.clone()],
+    _ => Vec::new(),
+  };
+
+  Ok(UpstreamUser {
+    subject: subject.to_string(),
+    name: name.to_string(),
+    email: email.to_lowercase(),
+    groups,
+  })
+}
+
+#[cfg(test)]
+mod test {
+  use super::{Claims, OidcState, map_claims};
+  use crate::config::Config;
+  use entity::oidc_provider;
+  use serde_json::json;
+  use url::Url;
+  use uuid::Uuid;
+
+  fn provider(group_claim: Option<&str>) -> oidc_provider::Model {
+    oidc_provider::Model {
+      id: Uuid::new_v4(),
+      name: "Keycloak".into(),
+      issuer: "https://idp.example.com/realms/main".into(),
+      client_id: "positron".into(),
+      client_secret: "secret".into(),
+      scopes: "openid email".into(),
+      pkce: true,
+      create_user: true,
+      enabled: true,
+      name_claim: "preferred_username".into(),
+      email_claim: "email".into(),
+      group_claim: group_claim.map(str::to_string),
+    }
+  }
+
+  fn claims(value: serde_json::Value) -> Claims {
+    serde_json::from_value(value).unwrap()
+  }
+
+  #[test]
+  fn map_claims_uses_configured_claims() {
+    let user = map_claims(
+      &provider(Some("groups")),
+      &claims(json!({
+        "sub": "abc",
+        "email": "Alice@Example.com",
+        "preferred_username": "alice",
+        "groups": ["dev", 1, "ops"],
+      })),
+    )
+    .unwrap();
+
+    assert_eq!(user.subject, "abc");
+    assert_eq!(user.name, "alice");
+    // email is normalised the same way local accounts are
+    assert_eq!(user.email, "alice@example.com");
+    // non string group values are skipped
+    assert_eq!(user.groups, vec!["dev", "ops"]);
+  }
+
+  #[test]
+  fn map_claims_resolves_nested_group_claim() {
+    let user = map_claims(
+      &provider(Some("realm_access.roles")),
+      &claims(json!({
+        "sub": "abc",
+        "email": "a@x.com",
+        "realm_access": { "roles": ["admin"] },
+      })),
+    )
+    .unwrap();
+
+    assert_eq!(user.groups, vec!["admin"]);
+    // the name falls back to the email when the name claim is missing
+    assert_eq!(user.name, "a@x.com");
+  }
+
+  #[test]
+  fn map_claims_accepts_single_string_group() {
+    let user = map_claims(
+      &provider(Some("group")),
+      &claims(json!({ "sub": "abc", "email": "a@x.com", "group": "dev" })),
+    )
+    .unwrap();
+    assert_eq!(user.groups, vec!["dev"]);
+  }
+
+  #[test]
+  fn map_claims_requires_subject_and_email() {
+    let p = provider(None);
+    assert!(map_claims(&p, &claims(json!({ "email": "a@x.com" }))).is_err());
+    assert!(map_claims(&p, &claims(json!({ "sub": "abc" }))).is_err());
+  }
+
+  #[tokio::test]
+  async fn redirect_uri_is_derived_from_site_url() {
+    let mut config = Config::default();
+    config.site.site_url = Url::parse("https://positron.example.com/some/path?x=1").unwrap();
+    let state = OidcState::init(&config);
+    let id = Uuid::new_v4();
+
+    assert_eq!(
+      state.redirect_uri(id).as_str(),
+      format!("https://positron.example.com/api/auth/oidc/{id}/callback")
+    );
+  }
+
+  #[tokio::test]
+  async fn take_pending_is_single_use() {
+    let state = OidcState::init(&Config::default());
+    assert!(state.take_pending(Uuid::new_v4()).is_none());
+  }
+}
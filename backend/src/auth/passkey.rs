@@ -24,16 +24,21 @@ use http::StatusCode;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use webauthn_rs::prelude::{Passkey, PublicKeyCredential, RegisterPublicKeyCredential};
-use webauthn_rs_proto::ResidentKeyRequirement;
+use webauthn_rs::prelude::{
+  AttestationMetadata, Credential, Passkey, PublicKeyCredential, RegisterPublicKeyCredential,
+};
+use webauthn_rs_proto::{AuthenticatorTransport, ResidentKeyRequirement};
 
 use crate::{
   auth::{
+    amr::{AuthMethod, AuthMethods},
+    device::ClientInfo,
     jwt::{JwtAuthOther, JwtStateOther},
+    mfa::{ensure_removable, finish_enrolment},
     session_auth::{SessionMeta, create_session_cookie},
-    state::WebauthnState,
+    state::{PasskeyPolicy, Registration, WebauthnState},
   },
-  db::DBTrait,
+  db::{DBTrait, user::user_ext::MfaEnrolment},
   utils::{UpdateMessage, Updater},
 };
 
@@ -74,10 +79,76 @@ pub fn router(rate_limiter: &mut RateLimiter) -> ApiRouter {
     )
 }
 
+/// What a registered credential tells about the authenticator holding it.
+#[derive(Debug)]
+struct Authenticator {
+  aaguid: Option<Uuid>,
+  kind: &'static str,
+  user_verified: bool,
+  backup_eligible: bool,
+  backup_state: bool,
+}
+
+impl Authenticator {
+  fn from_passkey(key: &Passkey) -> Self {
+    let cred = Credential::from(key.clone());
+    // only attested registrations carry the model of the authenticator
+    let aaguid = match cred.attestation.metadata {
+      AttestationMetadata::Packed { aaguid } | AttestationMetadata::Tpm { aaguid, .. } => {
+        Some(aaguid)
+      }
+      _ => None,
+    };
+
+    Self {
+      aaguid,
+      kind: authenticator_type(cred.transports.as_deref().unwrap_or_default()),
+      user_verified: cred.user_verified,
+      backup_eligible: cred.backup_eligible,
+      backup_state: cred.backup_state,
+    }
+  }
+}
+
+fn authenticator_type(transports: &[AuthenticatorTransport]) -> &'static str {
+  if transports.contains(&AuthenticatorTransport::Internal) {
+    "platform"
+  } else if transports.iter().any(|t| {
+    matches!(
+      t,
+      AuthenticatorTransport::Usb
+        | AuthenticatorTransport::Nfc
+        | AuthenticatorTransport::Ble
+        | AuthenticatorTransport::Hybrid
+    )
+  }) {
+    "cross-platform"
+  } else {
+    "unknown"
+  }
+}
+
+fn check_policy(policy: &PasskeyPolicy, authenticator: &Authenticator) -> Result<()> {
+  if policy.require_user_verification && !authenticator.user_verified {
+    bail!(FORBIDDEN, "Authenticator did not verify the user");
+  }
+
+  if policy.requires_attestation()
+    && !authenticator
+      .aaguid
+      .is_some_and(|aaguid| policy.allowed_aaguids.contains(&aaguid))
+  {
+    bail!(FORBIDDEN, "Authenticator model is not allowed");
+  }
+
+  Ok(())
+}
+
 async fn start_registration(
   auth: JwtAuthOther<JwtSpecial>,
   db: Connection,
   webauthn: WebauthnState,
+  policy: PasskeyPolicy,
   state: PasskeyState,
 ) -> Result<Json<serde_json::Value>> {
   let passkeys = db.passkey().get_passkeys_for_user(auth.user_id).await?;
@@ -88,8 +159,30 @@ async fn start_registration(
     .collect();
 
   let user = db.user().get_user_by_id(auth.user_id).await?;
-  let (mut ccr, reg_state) =
-    webauthn.start_passkey_registration(auth.user_id, &user.email, &user.name, Some(passkeys))?;
+  // model restrictions are only as good as the attestation they rest on, so
+  // those registrations have to chain up to a configured root
+  let (mut ccr, reg_state) = match &policy.attestation_cas {
+    Some(cas) => {
+      let (ccr, reg_state) = webauthn.start_attested_passkey_registration(
+        auth.user_id,
+        &user.email,
+        &user.name,
+        Some(passkeys),
+        cas.clone(),
+        None,
+      )?;
+      (ccr, Registration::Attested(reg_state))
+    }
+    None => {
+      let (ccr, reg_state) = webauthn.start_passkey_registration(
+        auth.user_id,
+        &user.email,
+        &user.name,
+        Some(passkeys),
+      )?;
+      (ccr, Registration::Passkey(reg_state))
+    }
+  };
 
   if let Some(test) = &mut ccr.public_key.authenticator_selection {
     test.resident_key = Some(ResidentKeyRequirement::Required);
@@ -111,6 +204,7 @@ async fn finish_registration(
   auth: JwtAuthOther<JwtSpecial>,
   db: Connection,
   webauthn: WebauthnState,
+  policy: PasskeyPolicy,
   state: PasskeyState,
   updater: Updater,
   Json(req): Json<RegFinishReq>,
@@ -133,7 +227,17 @@ async fn finish_registration(
     .remove(&user.id)
     .context("state not found")?;
 
-  let key = webauthn.finish_passkey_registration(&reg, &reg_state)?;
+  let key = match reg_state {
+    Registration::Passkey(reg_state) => webauthn.finish_passkey_registration(&reg, &reg_state)?,
+    Registration::Attested(reg_state) => {
+      let Ok(key) = webauthn.finish_attested_passkey_registration(&reg, &reg_state) else {
+        bail!(FORBIDDEN, "Authenticator attestation could not be verified");
+      };
+      Passkey::from(key)
+    }
+  };
+  let authenticator = Authenticator::from_passkey(&key);
+  check_policy(&policy, &authenticator)?;
 
   let json_key = serde_json::to_string(&key)?;
   db.passkey()
@@ -145,8 +249,14 @@ async fn finish_registration(
       name: req.name,
       created: Utc::now().naive_utc(),
       used: Utc::now().naive_utc(),
+      aaguid: authenticator.aaguid,
+      authenticator_type: authenticator.kind.to_string(),
+      user_verified: authenticator.user_verified,
+      backup_eligible: authenticator.backup_eligible,
+      backup_state: authenticator.backup_state,
     })
     .await?;
+  finish_enrolment(&db, &updater, auth.user_id).await?;
   updater.send_to(auth.user_id, UpdateMessage::Passkey).await;
 
   Ok(StatusCode::OK)
@@ -192,6 +302,7 @@ struct AuthRes {
   user: Uuid,
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn finish_authentication(
   Path(FinishAuthPath { auth_id }): Path<FinishAuthPath>,
   db: Connection,
@@ -199,6 +310,7 @@ async fn finish_authentication(
   state: PasskeyState,
   jwt: JwtState,
   mut cookies: CookieJar,
+  client: ClientInfo,
   Json(auth_req): Json<AuthReq>,
 ) -> Result<(CookieJar, TokenRes<AuthRes>)> {
   let Ok(auth) = serde_json::from_value::<PublicKeyCredential>(auth_req.res) else {
@@ -226,13 +338,23 @@ async fn finish_authentication(
   }
 
   let json_key = serde_json::to_string(&passkey)?;
+  let backup_state = Authenticator::from_passkey(&passkey).backup_state;
 
   let _ = db
     .passkey()
-    .update_passkey_record(passkey_db.id, json_key)
+    .update_passkey_record(passkey_db.id, json_key, backup_state)
     .await;
 
-  let cookie = create_session_cookie(&db, &jwt, user.id, false, auth_req.session).await?;
+  let cookie = create_session_cookie(
+    &db,
+    &jwt,
+    user.id,
+    false,
+    AuthMethods::new(&[AuthMethod::Passkey]),
+    auth_req.session,
+    &client,
+  )
+  .await?;
   cookies = cookies.add(cookie);
 
   Ok((cookies, TokenRes(AuthRes { user: user.id })))
@@ -289,9 +411,10 @@ async fn finish_special_access(
   }
 
   let json_key = serde_json::to_string(&passkey)?;
+  let backup_state = Authenticator::from_passkey(&passkey).backup_state;
   let _ = db
     .passkey()
-    .update_passkey_record(passkey_db.id, json_key)
+    .update_passkey_record(passkey_db.id, json_key, backup_state)
     .await;
 
   let cookie = jwt.create_token::<JwtSpecial>(auth.user_id)?;
@@ -306,6 +429,12 @@ struct PasskeyInfo {
   name: String,
   created: DateTime<Utc>,
   used: DateTime<Utc>,
+  aaguid: Option<Uuid>,
+  /// `platform`, `cross-platform` or `unknown`
+  authenticator_type: String,
+  user_verified: bool,
+  backup_eligible: bool,
+  backup_state: bool,
 }
 
 async fn list(auth: JwtAuth, db: Connection) -> Result<Json<Vec<PasskeyInfo>>> {
@@ -318,6 +447,11 @@ async fn list(auth: JwtAuth, db: Connection) -> Result<Json<Vec<PasskeyInfo>>> {
       name: p.name,
       created: p.created.and_utc(),
       used: p.used.and_utc(),
+      aaguid: p.aaguid,
+      authenticator_type: p.authenticator_type,
+      user_verified: p.user_verified,
+      backup_eligible: p.backup_eligible,
+      backup_state: p.backup_state,
     })
     .collect();
 
@@ -336,6 +470,22 @@ async fn remove(
   Json(req): Json<PasskeyRemove>,
 ) -> Result<()> {
   let user = db.user().get_user_by_id(auth.user_id).await?;
+  if db
+    .passkey()
+    .passkey_name_exists(user.id, req.name.clone())
+    .await?
+  {
+    let enrolment = db.user_ext().mfa_enrolment(user.id).await?;
+    ensure_removable(
+      &db,
+      user.id,
+      MfaEnrolment {
+        passkeys: enrolment.passkeys - 1,
+        ..enrolment
+      },
+    )
+    .await?;
+  }
 
   db.passkey()
     .remove_passkey_by_name(user.id, req.name.clone())
@@ -382,6 +532,7 @@ async fn edit_name(
 mod test {
   use crate::{
     auth::jwt::JwtSpecial,
+    db::DBTrait,
     db::test::{
       auth_cookie, body_json, insert_passkey, insert_user, jwt_states, other_cookie, test_db,
       updater,
@@ -400,6 +551,10 @@ mod test {
   };
   use serde_json::{Value, json};
   use tower::ServiceExt;
+  use webauthn_rs_proto::AuthenticatorTransport;
+
+  use super::{authenticator_type, check_policy};
+  use crate::auth::state::PasskeyPolicy;
 
   struct Ctx {
     db: Connection,
@@ -462,6 +617,56 @@ mod test {
     assert_eq!(resp.status(), StatusCode::OK);
     let body: Value = body_json(resp).await;
     assert_eq!(body.as_array().unwrap().len(), 2);
+    // rows from before metadata was recorded fall back to the column defaults
+    assert_eq!(body[0]["authenticator_type"], "unknown");
+    assert!(body[0]["aaguid"].is_null());
+    assert_eq!(body[0]["backup_eligible"], false);
+  }
+
+  fn authenticator(aaguid: Option<uuid::Uuid>, user_verified: bool) -> super::Authenticator {
+    super::Authenticator {
+      aaguid,
+      kind: "platform",
+      user_verified,
+      backup_eligible: true,
+      backup_state: true,
+    }
+  }
+
+  #[test]
+  fn policy_restricts_models_and_user_verification() {
+    let allowed = uuid::Uuid::new_v4();
+    let mut policy = PasskeyPolicy {
+      allowed_aaguids: vec![],
+      require_user_verification: false,
+      attestation_cas: None,
+    };
+    assert!(check_policy(&policy, &authenticator(None, false)).is_ok());
+
+    policy.require_user_verification = true;
+    assert!(check_policy(&policy, &authenticator(None, false)).is_err());
+
+    policy.allowed_aaguids = vec![allowed];
+    assert!(check_policy(&policy, &authenticator(Some(allowed), true)).is_ok());
+    // unattested registrations cannot prove their model
+    assert!(check_policy(&policy, &authenticator(None, true)).is_err());
+    assert!(check_policy(&policy, &authenticator(Some(uuid::Uuid::new_v4()), true)).is_err());
+  }
+
+  #[test]
+  fn authenticator_type_follows_transports() {
+    assert_eq!(
+      authenticator_type(&[
+        AuthenticatorTransport::Internal,
+        AuthenticatorTransport::Hybrid
+      ]),
+      "platform"
+    );
+    assert_eq!(
+      authenticator_type(&[AuthenticatorTransport::Usb, AuthenticatorTransport::Nfc]),
+      "cross-platform"
+    );
+    assert_eq!(authenticator_type(&[]), "unknown");
   }
 
   #[tokio::test]
@@ -535,4 +740,300 @@ mod test {
       .unwrap();
     assert!(resp.status().is_client_error());
   }
+
+  /// Minimal CBOR for the attestation objects of the soft authenticator.
+  enum Cbor {
+    Int(i64),
+    Bytes(Vec<u8>),
+    Text(&'static str),
+    Array(Vec<Cbor>),
+    Map(Vec<(Cbor, Cbor)>),
+  }
+
+  impl Cbor {
+    fn encode(&self, out: &mut Vec<u8>) {
+      fn head(out: &mut Vec<u8>, major: u8, len: u64) {
+        match len {
+          0..24 => out.push(major << 5 | len as u8),
+          24..256 => out.extend([major << 5 | 24, len as u8]),
+          256..65536 => {
+            out.push(major << 5 | 25);
+            out.extend((len as u16).to_be_bytes());
+          }
+          _ => {
+            out.push(major << 5 | 26);
+            out.extend((len as u32).to_be_bytes());
+          }
+        }
+      }
+
+      match self {
+        Cbor::Int(value) if *value >= 0 => head(out, 0, *value as u64),
+        Cbor::Int(value) => head(out, 1, (-1 - value) as u64),
+        Cbor::Bytes(bytes) => {
+          head(out, 2, bytes.len() as u64);
+          out.extend(bytes);
+        }
+        Cbor::Text(text) => {
+          head(out, 3, text.len() as u64);
+          out.extend(text.as_bytes());
+        }
+        Cbor::Array(items) => {
+          head(out, 4, items.len() as u64);
+          items.iter().for_each(|item| item.encode(out));
+        }
+        Cbor::Map(entries) => {
+          head(out, 5, entries.len() as u64);
+          for (key, value) in entries {
+            key.encode(out);
+            value.encode(out);
+          }
+        }
+      }
+    }
+  }
+
+  fn ec_key() -> openssl::pkey::PKey<openssl::pkey::Private> {
+    use openssl::{
+      ec::{EcGroup, EcKey},
+      nid::Nid,
+      pkey::PKey,
+    };
+
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap()
+  }
+
+  /// Certificate for `key`, signed by `issuer` or by itself.
+  fn certificate(
+    key: &openssl::pkey::PKey<openssl::pkey::Private>,
+    issuer: Option<(
+      &openssl::x509::X509,
+      &openssl::pkey::PKey<openssl::pkey::Private>,
+    )>,
+    ca: bool,
+  ) -> openssl::x509::X509 {
+    use openssl::{
+      asn1::{Asn1Integer, Asn1Time},
+      bn::BigNum,
+      hash::MessageDigest,
+      x509::{X509, X509NameBuilder, extension::BasicConstraints},
+    };
+
+    let mut name = X509NameBuilder::new().unwrap();
+    name.append_entry_by_text("C", "DE").unwrap();
+    name.append_entry_by_text("O", "Vendor").unwrap();
+    name
+      .append_entry_by_text("OU", "Authenticator Attestation")
+      .unwrap();
+    name
+      .append_entry_by_text("CN", if ca { "Root" } else { "Key" })
+      .unwrap();
+    let name = name.build();
+
+    let mut cert = X509::builder().unwrap();
+    cert.set_version(2).unwrap();
+    let serial = Asn1Integer::from_bn(&BigNum::from_u32(rand::random()).unwrap()).unwrap();
+    cert.set_serial_number(&serial).unwrap();
+    cert.set_subject_name(&name).unwrap();
+    cert
+      .set_issuer_name(issuer.map_or(&name, |(cert, _)| cert.subject_name()))
+      .unwrap();
+    cert.set_pubkey(key).unwrap();
+    cert
+      .set_not_before(&Asn1Time::from_unix(0).unwrap())
+      .unwrap();
+    cert
+      .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+      .unwrap();
+    let mut constraints = BasicConstraints::new();
+    if ca {
+      constraints.critical().ca();
+    }
+    cert.append_extension(constraints.build().unwrap()).unwrap();
+    cert
+      .sign(issuer.map_or(key, |(_, key)| key), MessageDigest::sha256())
+      .unwrap();
+    cert.build()
+  }
+
+  /// A packed attestation of a new credential for the registration
+  /// `options`, signed with `att_key` and claiming `aaguid`.
+  fn packed_registration(
+    options: &Value,
+    aaguid: uuid::Uuid,
+    att_key: &openssl::pkey::PKey<openssl::pkey::Private>,
+    x5c: &[&openssl::x509::X509],
+  ) -> Value {
+    use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+    use openssl::{bn::BigNumContext, hash::MessageDigest, sign::Signer};
+    use sha2::{Digest, Sha256};
+
+    let client_data = json!({
+      "type": "webauthn.create",
+      "challenge": options["publicKey"]["challenge"],
+      "origin": "http://localhost",
+      "crossOrigin": false,
+    })
+    .to_string();
+
+    let cred_key = ec_key();
+    let ec = cred_key.ec_key().unwrap();
+    let mut x = openssl::bn::BigNum::new().unwrap();
+    let mut y = openssl::bn::BigNum::new().unwrap();
+    ec.public_key()
+      .affine_coordinates(
+        ec.group(),
+        &mut x,
+        &mut y,
+        &mut BigNumContext::new().unwrap(),
+      )
+      .unwrap();
+    let mut cose_key = Vec::new();
+    Cbor::Map(vec![
+      (Cbor::Int(1), Cbor::Int(2)),
+      (Cbor::Int(3), Cbor::Int(-7)),
+      (Cbor::Int(-1), Cbor::Int(1)),
+      (Cbor::Int(-2), Cbor::Bytes(x.to_vec_padded(32).unwrap())),
+      (Cbor::Int(-3), Cbor::Bytes(y.to_vec_padded(32).unwrap())),
+    ])
+    .encode(&mut cose_key);
+
+    let cred_id = uuid::Uuid::new_v4().as_bytes().to_vec();
+    let mut auth_data = Sha256::digest(b"localhost").to_vec();
+    // user present, user verified, attested credential data
+    auth_data.push(0x45);
+    auth_data.extend(0u32.to_be_bytes());
+    auth_data.extend(aaguid.as_bytes());
+    auth_data.extend((cred_id.len() as u16).to_be_bytes());
+    auth_data.extend(&cred_id);
+    auth_data.extend(cose_key);
+
+    let mut signer = Signer::new(MessageDigest::sha256(), att_key).unwrap();
+    signer.update(&auth_data).unwrap();
+    signer.update(&Sha256::digest(&client_data)).unwrap();
+    let sig = signer.sign_to_vec().unwrap();
+
+    let mut att_stmt = vec![
+      (Cbor::Text("alg"), Cbor::Int(-7)),
+      (Cbor::Text("sig"), Cbor::Bytes(sig)),
+    ];
+    if !x5c.is_empty() {
+      att_stmt.push((
+        Cbor::Text("x5c"),
+        Cbor::Array(
+          x5c
+            .iter()
+            .map(|cert| Cbor::Bytes(cert.to_der().unwrap()))
+            .collect(),
+        ),
+      ));
+    }
+    let mut attestation = Vec::new();
+    Cbor::Map(vec![
+      (Cbor::Text("fmt"), Cbor::Text("packed")),
+      (Cbor::Text("attStmt"), Cbor::Map(att_stmt)),
+      (Cbor::Text("authData"), Cbor::Bytes(auth_data)),
+    ])
+    .encode(&mut attestation);
+
+    json!({
+      "id": BASE64_URL_SAFE_NO_PAD.encode(&cred_id),
+      "rawId": BASE64_URL_SAFE_NO_PAD.encode(&cred_id),
+      "type": "public-key",
+      "response": {
+        "attestationObject": BASE64_URL_SAFE_NO_PAD.encode(attestation),
+        "clientDataJSON": BASE64_URL_SAFE_NO_PAD.encode(client_data),
+        "transports": ["usb"],
+      },
+    })
+  }
+
+  #[tokio::test]
+  async fn allowlist_only_accepts_attestation_from_configured_roots() {
+    use crate::auth::state::{PasskeyState, WebauthnState, attestation_cas};
+
+    let c = ctx().await;
+    let aaguid = uuid::Uuid::new_v4();
+    let root_key = ec_key();
+    let root = certificate(&root_key, None, true);
+    let policy = PasskeyPolicy {
+      allowed_aaguids: vec![aaguid],
+      require_user_verification: false,
+      attestation_cas: Some(attestation_cas(&root.to_pem().unwrap(), &[aaguid]).unwrap()),
+    };
+    let config = crate::config::Config {
+      webauthn_id: Some("localhost".into()),
+      webauthn_rp_origin: Some(webauthn_rs::prelude::Url::parse("http://localhost").unwrap()),
+      ..Default::default()
+    };
+    let app = Router::new()
+      .route("/start_registration", get(super::start_registration))
+      .route("/finish_registration", post(super::finish_registration))
+      .layer(Extension(PasskeyState::init()))
+      .layer(Extension(WebauthnState::init(&config)))
+      .layer(Extension(policy))
+      .layer(Extension(c.upd.clone()))
+      .layer(Extension(c.jwt.clone()))
+      .layer(Extension(c.other.clone()))
+      .layer(Extension(c.db.clone()));
+    let cookie = other_cookie::<JwtSpecial>(&c.other, c.user);
+
+    let register = |att_key, x5c: Vec<openssl::x509::X509>, name: &'static str| {
+      let app = app.clone();
+      let cookie = cookie.clone();
+      async move {
+        let resp = app
+          .clone()
+          .oneshot(req("GET", "/start_registration", &cookie, None))
+          .await
+          .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let options = body_json(resp).await;
+        let x5c: Vec<_> = x5c.iter().collect();
+        let reg = packed_registration(&options, aaguid, &att_key, &x5c);
+        app
+          .oneshot(req(
+            "POST",
+            "/finish_registration",
+            &cookie,
+            Some(json!({ "reg": reg, "name": name })),
+          ))
+          .await
+          .unwrap()
+          .status()
+      }
+    };
+
+    // a self-signed packed attestation can claim any model
+    let key = ec_key();
+    let self_signed = certificate(&key, None, false);
+    assert_eq!(
+      register(key, vec![self_signed], "self-signed").await,
+      StatusCode::FORBIDDEN
+    );
+    // so can self attestation without a certificate
+    assert_eq!(
+      register(ec_key(), vec![], "self").await,
+      StatusCode::FORBIDDEN
+    );
+    assert!(
+      c.db
+        .passkey()
+        .get_passkeys_for_user(c.user)
+        .await
+        .unwrap()
+        .is_empty()
+    );
+
+    let key = ec_key();
+    let attested = certificate(&key, Some((&root, &root_key)), false);
+    assert_eq!(
+      register(key, vec![attested], "attested").await,
+      StatusCode::OK
+    );
+    let passkeys = c.db.passkey().get_passkeys_for_user(c.user).await.unwrap();
+    assert_eq!(passkeys.len(), 1);
+    assert_eq!(passkeys[0].aaguid, Some(aaguid));
+  }
 }
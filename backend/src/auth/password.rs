@@ -19,8 +19,10 @@ use uuid::Uuid;
 
 use crate::{
   auth::{
+    amr::{AuthMethod, AuthMethods},
+    device::ClientInfo,
     jwt::{JwtAuthOther, JwtSpecial, JwtStateOther, JwtTotpRequired},
-    session_auth::{SessionMeta, create_session_cookie},
+    session_auth::{SessionMeta, create_session_cookie, ensure_not_suspended},
   },
   db::DBTrait,
 };
@@ -51,6 +53,8 @@ struct LoginReq {
 #[derive(Serialize, JsonSchema, Debug)]
 struct AuthRes {
   user: Option<Uuid>,
+  /// The session may only be used to enrol TOTP or a passkey.
+  mfa_enrolment_required: bool,
 }
 
 async fn authenticate(
@@ -59,6 +63,7 @@ async fn authenticate(
   other: JwtStateOther,
   db: Connection,
   mut cookies: CookieJar,
+  client: ClientInfo,
   Json(req): Json<LoginReq>,
 ) -> Result<(CookieJar, TokenRes<AuthRes>)> {
   let user = db.user_ext().get_user_by_email(&req.email).await?;
@@ -67,11 +72,31 @@ async fn authenticate(
   if hash != user.password {
     bail!(UNAUTHORIZED, "Invalid email or password");
   }
+  // checked before the totp step so suspended users are not asked for a code
+  ensure_not_suspended(&db, user.id).await?;
+
+  // a password alone is not enough for members of groups that require mfa
+  let enrolment_only = user.totp.is_none() && db.group_ext().user_requires_mfa(user.id).await?;
+  if enrolment_only && db.user_ext().mfa_enrolment(user.id).await?.enrolled() {
+    bail!(FORBIDDEN, "MFA is required, sign in with a passkey");
+  }
 
   let (cookie, totp) = if user.totp.is_some() {
     (other.create_token::<JwtTotpRequired>(user.id)?, true)
   } else {
-    let cookie = create_session_cookie(&db, &jwt, user.id, false, req.session).await?;
+    let cookie = create_session_cookie(
+      &db,
+      &jwt,
+      user.id,
+      false,
+      AuthMethods::new(&[AuthMethod::Password]),
+      req.session,
+      &client,
+    )
+    .await?;
+    if enrolment_only {
+      db.session().restrict_to_enrolment(cookie.value()).await?;
+    }
 
     (cookie, false)
   };
@@ -82,6 +107,7 @@ async fn authenticate(
     cookies,
     TokenRes(AuthRes {
       user: (!totp).then_some(user.id),
+      mfa_enrolment_required: enrolment_only,
     }),
   ))
 }
@@ -143,8 +169,12 @@ async fn change(
 mod test {
   use crate::{
     auth::jwt::{JwtSpecial, JwtStateOther},
-    db::test::{
-      auth_cookie, body_json, jwt_states, other_cookie, password_state, test_db, updater,
+    db::{
+      DBTrait,
+      test::{
+        add_user_to_group, auth_cookie, body_json, insert_group, jwt_states, other_cookie,
+        password_state, test_db, updater,
+      },
     },
     utils::UpdateMessage,
   };
@@ -196,6 +226,10 @@ mod test {
       oidc_user: Set(false),
       totp: Set(totp),
       oidc_subject: Set(None),
+      disabled: Set(false),
+      disabled_reason: Set(None),
+      disabled_until: Set(None),
+      tokens_revoked_at: Set(None),
     })
     .exec(&db.0)
     .await
@@ -273,6 +307,31 @@ mod test {
     assert!(body_json(resp).await["user"].is_null());
   }
 
+  #[tokio::test]
+  async fn authenticate_without_mfa_in_mfa_group_restricts_session() {
+    let db = test_db().await;
+    let pw = password_state().await;
+    let (jwt, other) = jwt_states(&db).await;
+    let user = insert_user(&db, &pw, "secret", None).await;
+    let group = insert_group(&db, "admins").await;
+    add_user_to_group(&db, group, user).await;
+    db.group_ext().set_require_mfa(group, true).await.unwrap();
+    let app = app(db.clone(), pw.clone(), jwt, other, updater().await);
+
+    let resp = app
+      .oneshot(post_req(
+        "/authenticate",
+        None,
+        json!({ "email": "user@x.com", "password": encrypt(&pw, "secret"), "name": "", "application": "", "operating_system": "" }),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(body_json(resp).await["mfa_enrolment_required"], true);
+    let sessions = db.session().list_for_user(user).await.unwrap();
+    assert!(sessions.iter().all(|s| s.enrolment_only));
+  }
+
   #[tokio::test]
   async fn authenticate_with_wrong_password_is_unauthorized() {
     let db = test_db().await;
@@ -1,5 +1,5 @@
 use aide::axum::{ApiRouter, routing::get_with};
-use axum::Json;
+use axum::{Extension, Json};
 use axum_extra::{
   TypedHeader,
   extract::CookieJar,
@@ -13,6 +13,7 @@ use centaurus::{
     },
     request::response::TokenRes,
   },
+  bail,
   db::init::Connection,
   error::{ErrorReportStatusExt, Result},
   eyre::ContextCompat,
@@ -21,7 +22,10 @@ use chrono::{Duration, Utc};
 use schemars::JsonSchema;
 use serde::Serialize;
 
-use crate::{auth::session_auth::create_session_raw_token, db::DBTrait};
+use crate::{
+  auth::session_auth::{CurrentSession, create_session_raw_token},
+  db::DBTrait,
+};
 
 pub fn router() -> ApiRouter {
   ApiRouter::new()
@@ -69,6 +73,7 @@ async fn test_token(
 
 async fn refresh_token(
   auth: JwtAuth,
+  Extension(session): Extension<CurrentSession>,
   bearer: Option<TypedHeader<Authorization<Bearer>>>,
   mut cookies: CookieJar,
   jwt: JwtState,
@@ -80,10 +85,24 @@ async fn refresh_token(
     .or_else(|| cookies.get(JWT_COOKIE_NAME).map(|c| c.value().to_string()))
     .status_context(http::StatusCode::UNAUTHORIZED, "Missing auth token")?;
 
+  // impersonation has a fixed lifetime
+  if db
+    .session()
+    .get_by_token(&old_token)
+    .await?
+    .impersonator_id
+    .is_some()
+  {
+    bail!(FORBIDDEN, "impersonation sessions cannot be refreshed");
+  }
+
   let token = create_session_raw_token(&jwt, auth.user_id).await?;
-  let exp = Utc::now()
+  let mut exp = Utc::now()
     .checked_add_signed(Duration::seconds(jwt.exp))
     .context("Failed to add exp")?;
+  if let Some(end) = session.lifetime_end {
+    exp = exp.min(end.and_utc());
+  }
   db.session().refresh(&old_token, token.clone(), exp).await?;
   cookies = cookies.add(jwt.create_cookie(JWT_COOKIE_NAME, token));
 
@@ -303,4 +322,72 @@ mod test {
     assert_eq!(sessions.len(), 1);
     assert!(sessions[0].refreshed_at.is_some());
   }
+
+  #[tokio::test]
+  async fn refresh_token_rejects_impersonation_sessions() {
+    use crate::{auth::session_auth::create_session_raw_token, db::DBTrait};
+
+    let db = test_db().await;
+    let jwt = auth_state(&db).await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let admin = insert_user(&db, "a", "a@x.com").await;
+    let token = create_session_raw_token(&jwt, user).await.unwrap();
+    db.session()
+      .create_impersonation(
+        user,
+        admin,
+        token.clone(),
+        chrono::Utc::now() + chrono::Duration::minutes(5),
+      )
+      .await
+      .unwrap();
+
+    let resp = app(db, jwt)
+      .oneshot(
+        Request::builder()
+          .uri("/refresh_token")
+          .header(header::COOKIE, format!("{JWT_COOKIE_NAME}={token}"))
+          .body(Body::empty())
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+  }
+
+  #[tokio::test]
+  async fn refresh_token_stops_at_max_lifetime() {
+    use crate::{
+      auth::session_auth::{SessionAuth, SessionLimits, SessionPolicy},
+      db::DBTrait,
+    };
+
+    let db = test_db().await;
+    let jwt = auth_state(&db).await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let cookie = auth_cookie(&db, &jwt, user).await;
+    let policy = SessionPolicy {
+      web: SessionLimits {
+        idle_timeout: None,
+        max_lifetime: Some(chrono::Duration::hours(1)),
+      },
+      ..Default::default()
+    };
+    let limited = JwtState::init_with_auth(&Config::default().auth, &db, SessionAuth(policy)).await;
+
+    let resp = app(db.clone(), limited)
+      .oneshot(
+        Request::builder()
+          .uri("/refresh_token")
+          .header(header::COOKIE, &cookie)
+          .body(Body::empty())
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+    assert!(resp.status().is_success());
+
+    let session = &db.session().list_for_user(user).await.unwrap()[0];
+    assert!(session.expires_at <= session.created_at + chrono::Duration::hours(1));
+  }
 }
@@ -1,4 +1,9 @@
-use axum_extra::extract::cookie::Cookie;
+use axum::{extract::Request, middleware::Next, response::Response};
+use axum_extra::{
+  TypedHeader,
+  extract::{CookieJar, cookie::Cookie},
+  headers::{Authorization, authorization::Bearer},
+};
 use centaurus::{
   backend::auth::{
     jwt_auth::Auth,
@@ -9,16 +14,110 @@ use centaurus::{
   error::Result,
   eyre::ContextCompat,
 };
-use chrono::{Duration, Utc};
+use chrono::{Duration, NaiveDateTime, Utc};
+use entity::session;
 use http::request::Parts;
 use migration::async_trait;
 use schemars::JsonSchema;
 use serde::Deserialize;
+use tracing::info;
 use uuid::Uuid;
 
-use crate::db::DBTrait;
+use crate::{
+  auth::{
+    amr::AuthMethods,
+    device::{ClientInfo, describe, fingerprint, hash_token},
+  },
+  config::Config,
+  db::{DBTrait, user::session::SessionDevice},
+  utils::generate_secret,
+};
+
+#[derive(Default)]
+pub struct SessionAuth(pub SessionPolicy);
+
+/// Instance limits on how long sessions stay usable, on top of `expires_at`.
+#[derive(Clone, Default, Debug)]
+pub struct SessionPolicy {
+  pub web: SessionLimits,
+  pub app: SessionLimits,
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct SessionLimits {
+  /// Longest time a session may go unused.
+  pub idle_timeout: Option<Duration>,
+  /// Longest time since sign-in, refreshing does not extend it.
+  pub max_lifetime: Option<Duration>,
+}
+
+impl SessionPolicy {
+  pub fn from_config(config: &Config) -> Self {
+    Self {
+      web: SessionLimits::from_secs(config.session_idle_timeout, config.session_max_lifetime),
+      app: SessionLimits::from_secs(
+        config.app_session_idle_timeout,
+        config.app_session_max_lifetime,
+      ),
+    }
+  }
+
+  pub fn limits(&self, is_app: bool) -> SessionLimits {
+    if is_app { self.app } else { self.web }
+  }
+}
+
+impl SessionLimits {
+  fn from_secs(idle_timeout: i64, max_lifetime: i64) -> Self {
+    let limit = |secs: i64| (secs > 0).then(|| Duration::seconds(secs));
+    Self {
+      idle_timeout: limit(idle_timeout),
+      max_lifetime: limit(max_lifetime),
+    }
+  }
 
-pub struct SessionAuth;
+  /// Sessions last used before this are idle.
+  pub fn idle_cutoff(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+    self.idle_timeout.map(|idle| now - idle)
+  }
+
+  /// Sessions created before this have reached their maximum lifetime.
+  pub fn lifetime_cutoff(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+    self.max_lifetime.map(|max| now - max)
+  }
+
+  pub fn lifetime_end(&self, created_at: NaiveDateTime) -> Option<NaiveDateTime> {
+    self.max_lifetime.map(|max| created_at + max)
+  }
+
+  fn check(&self, session: &session::Model, now: NaiveDateTime) -> Result<()> {
+    if self
+      .idle_cutoff(now)
+      .is_some_and(|cutoff| session.last_used_at < cutoff)
+    {
+      bail!(UNAUTHORIZED, "session idle timeout");
+    }
+    if self
+      .lifetime_cutoff(now)
+      .is_some_and(|cutoff| session.created_at < cutoff)
+    {
+      bail!(UNAUTHORIZED, "session lifetime exceeded");
+    }
+    Ok(())
+  }
+}
+
+/// The session behind an authenticated request, inserted into the request
+/// extensions by [`SessionAuth`].
+#[derive(Clone, Debug)]
+pub struct CurrentSession {
+  pub id: Uuid,
+  pub impersonator: Option<Uuid>,
+  pub expires_at: chrono::NaiveDateTime,
+  pub auth_methods: AuthMethods,
+  /// When the maximum session lifetime runs out, refreshing stops there.
+  pub lifetime_end: Option<NaiveDateTime>,
+}
 
 #[derive(Deserialize, JsonSchema, Debug, Clone)]
 pub struct SessionMeta {
@@ -43,7 +142,7 @@ impl Auth for SessionAuth {
   async fn check(
     &self,
     db: &Connection,
-    _parts: &mut Parts,
+    parts: &mut Parts,
     token: &str,
     claims: &JwtClaims,
   ) -> Result<()> {
@@ -55,7 +154,28 @@ impl Auth for SessionAuth {
       bail!(UNAUTHORIZED, "invalid token");
     }
 
+    // impersonation sessions expire long before the jwt they are bound to
+    let now = Utc::now().naive_utc();
+    if session.expires_at < now {
+      bail!(UNAUTHORIZED, "session expired");
+    }
+    let limits = self.0.limits(session.is_app);
+    limits.check(&session, now)?;
+
+    ensure_not_suspended(db, session.user_id).await?;
+
+    if session.enrolment_only && parts.extensions.get::<EnrolmentAllowed>().is_none() {
+      bail!(FORBIDDEN, "MFA enrolment required");
+    }
+
     db.session().touch_last_used(token).await?;
+    parts.extensions.insert(CurrentSession {
+      id: session.id,
+      impersonator: session.impersonator_id,
+      expires_at: session.expires_at,
+      auth_methods: AuthMethods::parse(&session.auth_methods),
+      lifetime_end: limits.lifetime_end(session.created_at),
+    });
 
     Ok(())
   }
@@ -66,14 +186,25 @@ pub async fn create_session_cookie<'c>(
   jwt: &JwtState,
   user_id: Uuid,
   is_app: bool,
+  methods: AuthMethods,
   session: SessionMeta,
+  client: &ClientInfo,
 ) -> Result<Cookie<'c>> {
+  ensure_not_suspended(db, user_id).await?;
+
   let token = create_session_raw_token(jwt, user_id).await?;
 
   let exp = Utc::now()
     .checked_add_signed(Duration::seconds(jwt.exp))
     .context("Failed to add exp")?;
 
+  // the very first sign-in of an account has nothing to compare against
+  let fingerprint = fingerprint(&session);
+  let has_devices = db.known_device().count_for_user(user_id).await? > 0;
+  let new_device = !db.known_device().remember(user_id, &fingerprint).await? && has_devices;
+  let revoke_token = new_device.then(generate_secret);
+  let device = describe(&session);
+
   db.session()
     .create(
       user_id,
@@ -83,11 +214,73 @@ pub async fn create_session_cookie<'c>(
       session.name,
       session.application,
       session.operating_system,
+      methods.to_string(),
+      SessionDevice {
+        ip: client.ip.clone(),
+        fingerprint,
+        new_device,
+        revoke_token: revoke_token.as_deref().map(hash_token),
+      },
     )
     .await?;
+
+  if let Some(revoke_token) = &revoke_token {
+    client.alert(db, user_id, device, revoke_token);
+  }
+
+  // signing in during the grace period restores the account
+  if db.account_deletion().cancel(user_id).await? {
+    info!("User {} restored their account", user_id);
+  }
+
   Ok(jwt.create_cookie(JWT_COOKIE_NAME, token))
 }
 
+/// Fails for accounts that are currently suspended.
+pub async fn ensure_not_suspended(db: &Connection, user_id: Uuid) -> Result<()> {
+  if let Some(suspension) = db.user_ext().suspension(user_id).await? {
+    match suspension.reason {
+      Some(reason) => bail!(FORBIDDEN, "account is suspended: {}", reason),
+      None => bail!(FORBIDDEN, "account is suspended"),
+    }
+  }
+  Ok(())
+}
+
+/// Marks routes that sessions restricted to MFA enrolment may still use.
+#[derive(Clone)]
+pub struct EnrolmentAllowed;
+
+/// Layered onto the routes needed to enrol TOTP or a passkey, every other
+/// route rejects enrolment-only sessions.
+pub async fn allow_enrolment(mut req: Request, next: Next) -> Response {
+  req.extensions_mut().insert(EnrolmentAllowed);
+  next.run(req).await
+}
+
+/// Rejects requests made through an impersonation session, layered onto the
+/// routes that manage credentials.
+pub async fn deny_impersonation(
+  db: Connection,
+  bearer: Option<TypedHeader<Authorization<Bearer>>>,
+  cookies: CookieJar,
+  req: Request,
+  next: Next,
+) -> Result<Response> {
+  let token = bearer
+    .map(|TypedHeader(bearer)| bearer.token().to_string())
+    .or_else(|| cookies.get(JWT_COOKIE_NAME).map(|c| c.value().to_string()));
+
+  if let Some(token) = token
+    && let Ok(session) = db.session().get_by_token(&token).await
+    && session.impersonator_id.is_some()
+  {
+    bail!(FORBIDDEN, "not allowed while impersonating a user");
+  }
+
+  Ok(next.run(req).await)
+}
+
 pub async fn revoke_session(db: &Connection, token: &str) -> Result<()> {
   db.session().delete_by_token(token).await?;
   Ok(())
@@ -111,11 +304,13 @@ mod test {
       &jwt,
       user,
       false,
+      AuthMethods::default(),
       SessionMeta {
         name: String::new(),
         application: String::new(),
         operating_system: String::new(),
       },
+      &Default::default(),
     )
     .await
     .unwrap();
@@ -135,11 +330,13 @@ mod test {
       &jwt,
       user,
       false,
+      AuthMethods::default(),
       SessionMeta {
         name: String::new(),
         application: String::new(),
         operating_system: String::new(),
       },
+      &Default::default(),
     )
     .await
     .unwrap();
@@ -148,11 +345,13 @@ mod test {
       &jwt,
       user,
       true,
+      AuthMethods::default(),
       SessionMeta {
         name: String::new(),
         application: String::new(),
         operating_system: String::new(),
       },
+      &Default::default(),
     )
     .await
     .unwrap();
@@ -170,11 +369,13 @@ mod test {
       &jwt,
       user,
       false,
+      AuthMethods::default(),
       SessionMeta {
         name: String::new(),
         application: String::new(),
         operating_system: String::new(),
       },
+      &Default::default(),
     )
     .await
     .unwrap();
@@ -183,7 +384,7 @@ mod test {
 
     revoke_session(&db, token).await.unwrap();
 
-    let auth = SessionAuth;
+    let auth = SessionAuth::default();
     let mut parts = Request::new(()).into_parts().0;
     assert!(auth.check(&db, &mut parts, token, &claims).await.is_err());
   }
@@ -199,18 +400,20 @@ mod test {
       &jwt,
       user,
       false,
+      AuthMethods::default(),
       SessionMeta {
         name: String::new(),
         application: String::new(),
         operating_system: String::new(),
       },
+      &Default::default(),
     )
     .await
     .unwrap();
     let token = cookie.value();
     let claims = jwt.validate_token(token).unwrap();
 
-    let auth = SessionAuth;
+    let auth = SessionAuth::default();
     let mut parts = Request::new(()).into_parts().0;
     auth
       .check(&db, &mut parts, token, &claims)
@@ -218,6 +421,95 @@ mod test {
       .expect("valid session must pass");
   }
 
+  #[tokio::test]
+  async fn enrolment_only_session_is_limited_to_enrolment_routes() {
+    let db = test_db().await;
+    let jwt = auth_state(&db).await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+
+    let cookie = create_session_cookie(
+      &db,
+      &jwt,
+      user,
+      false,
+      AuthMethods::default(),
+      SessionMeta {
+        name: String::new(),
+        application: String::new(),
+        operating_system: String::new(),
+      },
+      &Default::default(),
+    )
+    .await
+    .unwrap();
+    let token = cookie.value();
+    let claims = jwt.validate_token(token).unwrap();
+    db.session().restrict_to_enrolment(token).await.unwrap();
+
+    let auth = SessionAuth::default();
+    let mut parts = Request::new(()).into_parts().0;
+    assert!(auth.check(&db, &mut parts, token, &claims).await.is_err());
+
+    let mut enrolment = Request::new(()).into_parts().0;
+    enrolment.extensions.insert(EnrolmentAllowed);
+    assert!(
+      auth
+        .check(&db, &mut enrolment, token, &claims)
+        .await
+        .is_ok()
+    );
+
+    db.session().lift_enrolment_restriction(user).await.unwrap();
+    assert!(auth.check(&db, &mut parts, token, &claims).await.is_ok());
+  }
+
+  #[tokio::test]
+  async fn suspended_user_is_rejected_until_resumed() {
+    let db = test_db().await;
+    let jwt = auth_state(&db).await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let meta = SessionMeta {
+      name: String::new(),
+      application: String::new(),
+      operating_system: String::new(),
+    };
+
+    let cookie = create_session_cookie(
+      &db,
+      &jwt,
+      user,
+      false,
+      AuthMethods::default(),
+      meta.clone(),
+      &Default::default(),
+    )
+    .await
+    .unwrap();
+    let token = cookie.value();
+    let claims = jwt.validate_token(token).unwrap();
+    db.user_ext().suspend(user, None, None).await.unwrap();
+
+    let auth = SessionAuth::default();
+    let mut parts = Request::new(()).into_parts().0;
+    assert!(auth.check(&db, &mut parts, token, &claims).await.is_err());
+    assert!(
+      create_session_cookie(
+        &db,
+        &jwt,
+        user,
+        false,
+        AuthMethods::default(),
+        meta,
+        &Default::default()
+      )
+      .await
+      .is_err()
+    );
+
+    db.user_ext().resume(user).await.unwrap();
+    assert!(auth.check(&db, &mut parts, token, &claims).await.is_ok());
+  }
+
   #[tokio::test]
   async fn check_rejects_user_mismatch() {
     let db = test_db().await;
@@ -230,11 +522,13 @@ mod test {
       &jwt,
       user,
       false,
+      AuthMethods::default(),
       SessionMeta {
         name: String::new(),
         application: String::new(),
         operating_system: String::new(),
       },
+      &Default::default(),
     )
     .await
     .unwrap();
@@ -243,7 +537,7 @@ mod test {
     let mut claims = jwt.validate_token(token).unwrap();
     claims.sub = other;
 
-    let auth = SessionAuth;
+    let auth = SessionAuth::default();
     let mut parts = Request::new(()).into_parts().0;
     assert!(auth.check(&db, &mut parts, token, &claims).await.is_err());
   }
@@ -259,11 +553,13 @@ mod test {
       &jwt,
       user,
       false,
+      AuthMethods::default(),
       SessionMeta {
         name: "My Laptop".into(),
         application: "Firefox".into(),
         operating_system: "Linux".into(),
       },
+      &Default::default(),
     )
     .await
     .unwrap();
@@ -273,6 +569,34 @@ mod test {
     assert_eq!(row.operating_system, "Linux");
   }
 
+  #[tokio::test]
+  async fn create_session_cookie_withdraws_pending_account_deletion() {
+    let db = test_db().await;
+    let jwt = auth_state(&db).await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    db.account_deletion()
+      .request(user, None, Utc::now() + Duration::days(14))
+      .await
+      .unwrap();
+
+    create_session_cookie(
+      &db,
+      &jwt,
+      user,
+      false,
+      AuthMethods::default(),
+      SessionMeta {
+        name: String::new(),
+        application: String::new(),
+        operating_system: String::new(),
+      },
+      &Default::default(),
+    )
+    .await
+    .unwrap();
+    assert!(db.account_deletion().get(user).await.unwrap().is_none());
+  }
+
   #[tokio::test]
   async fn create_session_raw_token_is_unique_per_call() {
     let db = test_db().await;
@@ -295,11 +619,13 @@ mod test {
       &jwt,
       user,
       false,
+      AuthMethods::default(),
       SessionMeta {
         name: String::new(),
         application: String::new(),
         operating_system: String::new(),
       },
+      &Default::default(),
     )
     .await
     .unwrap();
@@ -307,4 +633,124 @@ mod test {
     revoke_session(&db, token).await.unwrap();
     assert!(db.session().get_by_token(token).await.is_err());
   }
+
+  #[tokio::test]
+  async fn create_session_cookie_flags_new_devices() {
+    let db = test_db().await;
+    let jwt = auth_state(&db).await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let meta = |application: &str| SessionMeta {
+      name: String::new(),
+      application: application.into(),
+      operating_system: "macOS 15.1".into(),
+    };
+
+    let mut flagged = Vec::new();
+    for application in ["Chrome 126", "Chrome 127", "Firefox 128"] {
+      let cookie = create_session_cookie(
+        &db,
+        &jwt,
+        user,
+        false,
+        AuthMethods::default(),
+        meta(application),
+        &Default::default(),
+      )
+      .await
+      .unwrap();
+      let row = db.session().get_by_token(cookie.value()).await.unwrap();
+      assert_eq!(row.revoke_token.is_some(), row.new_device);
+      flagged.push(row.new_device);
+    }
+
+    // the first sign-in has nothing to compare against, browser updates are
+    // the same device
+    assert_eq!(flagged, [false, false, true]);
+  }
+
+  fn limits(idle: i64, max: i64) -> SessionPolicy {
+    let config = Config {
+      session_idle_timeout: idle,
+      session_max_lifetime: max,
+      app_session_idle_timeout: 0,
+      app_session_max_lifetime: 0,
+      ..Default::default()
+    };
+    SessionPolicy::from_config(&config)
+  }
+
+  async fn backdate(db: &Connection, token: &str, last_used: Duration, created: Duration) {
+    use sea_orm::{ActiveModelTrait, ActiveValue::Set};
+
+    let now = Utc::now().naive_utc();
+    let mut row: session::ActiveModel = db.session().get_by_token(token).await.unwrap().into();
+    row.last_used_at = Set(now - last_used);
+    row.created_at = Set(now - created);
+    row.update(&db.0).await.unwrap();
+  }
+
+  #[test]
+  fn policy_from_config_disables_zero_limits() {
+    let policy = limits(60, 0);
+    assert_eq!(policy.web.idle_timeout, Some(Duration::seconds(60)));
+    assert!(policy.web.max_lifetime.is_none());
+    assert!(policy.limits(true).idle_timeout.is_none());
+  }
+
+  #[tokio::test]
+  async fn check_enforces_idle_timeout_and_max_lifetime() {
+    let db = test_db().await;
+    let jwt = auth_state(&db).await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+
+    let cookie = create_session_cookie(
+      &db,
+      &jwt,
+      user,
+      false,
+      AuthMethods::default(),
+      SessionMeta {
+        name: String::new(),
+        application: String::new(),
+        operating_system: String::new(),
+      },
+      &Default::default(),
+    )
+    .await
+    .unwrap();
+    let token = cookie.value();
+    let claims = jwt.validate_token(token).unwrap();
+    let mut parts = Request::new(()).into_parts().0;
+
+    backdate(&db, token, Duration::hours(2), Duration::days(2)).await;
+    let idle = SessionAuth(limits(3600, 0));
+    assert!(idle.check(&db, &mut parts, token, &claims).await.is_err());
+    // app limits do not apply to web sessions
+    let app_only = SessionAuth(SessionPolicy {
+      web: SessionLimits::default(),
+      app: limits(3600, 0).web,
+    });
+    assert!(
+      app_only
+        .check(&db, &mut parts, token, &claims)
+        .await
+        .is_ok()
+    );
+
+    let lifetime = SessionAuth(limits(0, 60 * 60 * 24));
+    assert!(
+      lifetime
+        .check(&db, &mut parts, token, &claims)
+        .await
+        .is_err()
+    );
+
+    let generous = SessionAuth(limits(60 * 60 * 24, 60 * 60 * 24 * 7));
+    generous
+      .check(&db, &mut parts, token, &claims)
+      .await
+      .expect("session within limits must pass");
+    let current = parts.extensions.get::<CurrentSession>().unwrap();
+    assert!(current.lifetime_end.unwrap() > Utc::now().naive_utc());
+  }
 }
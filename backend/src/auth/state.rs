@@ -1,14 +1,18 @@
-use std::{ops::Deref, sync::Arc, time::Instant};
+use std::{collections::BTreeMap, fs, ops::Deref, sync::Arc, time::Instant};
 
 use aide::OperationIo;
 use axum::{Extension, extract::FromRequestParts};
 use dashmap::DashMap;
+use openssl::x509::X509;
 use tokio::spawn;
 use totp_rs::TOTP;
 use uuid::Uuid;
 use webauthn_rs::{
   Webauthn, WebauthnBuilder,
-  prelude::{DiscoverableAuthentication, PasskeyAuthentication, PasskeyRegistration, Url},
+  prelude::{
+    AttestationCaList, AttestationCaListBuilder, AttestedPasskeyRegistration,
+    DiscoverableAuthentication, PasskeyAuthentication, PasskeyRegistration, Url,
+  },
 };
 
 use crate::config::Config;
@@ -16,11 +20,18 @@ use crate::config::Config;
 #[derive(Clone, FromRequestParts, OperationIo)]
 #[from_request(via(Extension))]
 pub struct PasskeyState {
-  pub reg_state: Arc<DashMap<Uuid, (PasskeyRegistration, Instant)>>,
+  pub reg_state: Arc<DashMap<Uuid, (Registration, Instant)>>,
   pub auth_state: Arc<DashMap<Uuid, (DiscoverableAuthentication, Instant)>>,
   pub special_access_state: Arc<DashMap<Uuid, (PasskeyAuthentication, Instant)>>,
 }
 
+/// A started passkey registration, attested when the instance restricts the
+/// authenticator models.
+pub enum Registration {
+  Passkey(PasskeyRegistration),
+  Attested(AttestedPasskeyRegistration),
+}
+
 #[derive(Clone, FromRequestParts, OperationIo)]
 #[from_request(via(Extension))]
 pub struct TotpState {
@@ -32,6 +43,18 @@ pub struct TotpState {
 #[from_request(via(Extension))]
 pub struct WebauthnState(Webauthn);
 
+/// Instance rules for which authenticators may be enrolled as passkeys.
+#[derive(Clone, FromRequestParts, OperationIo)]
+#[from_request(via(Extension))]
+pub struct PasskeyPolicy {
+  /// Allowed authenticator models, any model is accepted when empty.
+  pub allowed_aaguids: Vec<Uuid>,
+  pub require_user_verification: bool,
+  /// Roots the attestation of an allowed model has to chain up to, set
+  /// whenever `allowed_aaguids` is.
+  pub attestation_cas: Option<AttestationCaList>,
+}
+
 impl Deref for WebauthnState {
   type Target = Webauthn;
 
@@ -71,6 +94,61 @@ impl WebauthnState {
   }
 }
 
+impl PasskeyPolicy {
+  pub fn from_config(config: &Config) -> Result<Self, String> {
+    let allowed_aaguids = config
+      .webauthn_allowed_aaguids
+      .split(',')
+      .map(str::trim)
+      .filter(|s| !s.is_empty())
+      .map(|s| {
+        Uuid::parse_str(s).map_err(|_| format!("Invalid AAGUID in WEBAUTHN_ALLOWED_AAGUIDS: {s}"))
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+
+    let attestation_cas = if allowed_aaguids.is_empty() {
+      None
+    } else {
+      if config.webauthn_attestation_ca.is_empty() {
+        return Err("WEBAUTHN_ALLOWED_AAGUIDS requires WEBAUTHN_ATTESTATION_CA".into());
+      }
+      let pem = fs::read(&config.webauthn_attestation_ca)
+        .map_err(|err| format!("Failed to read WEBAUTHN_ATTESTATION_CA: {err}"))?;
+      Some(attestation_cas(&pem, &allowed_aaguids)?)
+    };
+
+    Ok(Self {
+      allowed_aaguids,
+      require_user_verification: config.webauthn_require_user_verification,
+      attestation_cas,
+    })
+  }
+
+  /// Restricting models needs the authenticator to attest to its AAGUID.
+  pub fn requires_attestation(&self) -> bool {
+    !self.allowed_aaguids.is_empty()
+  }
+}
+
+/// Trusts every certificate of the PEM bundle for the allowed models only.
+pub fn attestation_cas(pem: &[u8], aaguids: &[Uuid]) -> Result<AttestationCaList, String> {
+  let cas = X509::stack_from_pem(pem)
+    .map_err(|err| format!("Invalid certificate in WEBAUTHN_ATTESTATION_CA: {err}"))?;
+  if cas.is_empty() {
+    return Err("WEBAUTHN_ATTESTATION_CA contains no certificates".into());
+  }
+
+  let mut builder = AttestationCaListBuilder::new();
+  for ca in cas {
+    for aaguid in aaguids {
+      builder
+        .insert_device_x509(ca.clone(), *aaguid, aaguid.to_string(), BTreeMap::new())
+        .map_err(|err| format!("Invalid certificate in WEBAUTHN_ATTESTATION_CA: {err}"))?;
+    }
+  }
+  Ok(builder.build())
+}
+
 impl PasskeyState {
   pub fn init() -> Self {
     let reg_state = Arc::new(DashMap::new());
@@ -131,8 +209,9 @@ impl TotpState {
 
 #[cfg(test)]
 mod test {
-  use super::{PasskeyState, TotpState, WebauthnState};
+  use super::{PasskeyPolicy, PasskeyState, TotpState, WebauthnState, attestation_cas};
   use crate::config::Config;
+  use uuid::Uuid;
   use webauthn_rs::prelude::Url;
 
   #[tokio::test]
@@ -152,6 +231,82 @@ mod test {
     let _ = TotpState::init(&config);
   }
 
+  #[test]
+  fn passkey_policy_parses_allowed_aaguids() {
+    let aaguid = Uuid::new_v4();
+    let config = Config {
+      webauthn_allowed_aaguids: format!(" {aaguid} ,"),
+      ..Default::default()
+    };
+    let err = PasskeyPolicy::from_config(&config).err().unwrap();
+    assert!(err.contains("WEBAUTHN_ATTESTATION_CA"), "{err}");
+
+    let dir = std::env::temp_dir().join(format!("positron-ca-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("ca.pem");
+    std::fs::write(&path, test_ca_pem()).unwrap();
+    let config = Config {
+      webauthn_attestation_ca: path.to_string_lossy().into_owned(),
+      ..config
+    };
+    let policy = PasskeyPolicy::from_config(&config).unwrap();
+    assert_eq!(policy.allowed_aaguids, [aaguid]);
+    assert!(policy.requires_attestation());
+    let cas = policy.attestation_cas.unwrap();
+    assert!(
+      cas
+        .cas()
+        .values()
+        .all(|ca| ca.aaguids().contains_key(&aaguid))
+    );
+
+    let policy = PasskeyPolicy::from_config(&Config::default()).unwrap();
+    assert!(!policy.requires_attestation());
+    assert!(policy.attestation_cas.is_none());
+  }
+
+  #[test]
+  fn passkey_policy_rejects_invalid_aaguids_and_certificates() {
+    let config = Config {
+      webauthn_allowed_aaguids: "yubikey".into(),
+      ..Default::default()
+    };
+    let err = PasskeyPolicy::from_config(&config).err().unwrap();
+    assert_eq!(err, "Invalid AAGUID in WEBAUTHN_ALLOWED_AAGUIDS: yubikey");
+
+    assert!(attestation_cas(b"not a certificate", &[Uuid::new_v4()]).is_err());
+  }
+
+  fn test_ca_pem() -> Vec<u8> {
+    use openssl::{
+      asn1::Asn1Time,
+      ec::{EcGroup, EcKey},
+      hash::MessageDigest,
+      nid::Nid,
+      pkey::PKey,
+      x509::{X509, X509NameBuilder},
+    };
+
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+    let mut name = X509NameBuilder::new().unwrap();
+    name.append_entry_by_text("CN", "Test CA").unwrap();
+    let name = name.build();
+    let mut cert = X509::builder().unwrap();
+    cert.set_version(2).unwrap();
+    cert.set_subject_name(&name).unwrap();
+    cert.set_issuer_name(&name).unwrap();
+    cert.set_pubkey(&key).unwrap();
+    cert
+      .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+      .unwrap();
+    cert
+      .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+      .unwrap();
+    cert.sign(&key, MessageDigest::sha256()).unwrap();
+    cert.build().to_pem().unwrap()
+  }
+
   #[tokio::test]
   async fn passkey_state_init_starts_empty() {
     let state = PasskeyState::init();
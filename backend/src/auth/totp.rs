@@ -24,10 +24,13 @@ use uuid::Uuid;
 
 use crate::{
   auth::{
+    amr::{AuthMethod, AuthMethods},
+    device::ClientInfo,
     jwt::{JwtAuthOther, JwtSpecial, JwtTotpRequired},
+    mfa::{ensure_removable, finish_enrolment},
     session_auth::{SessionMeta, create_session_cookie},
   },
-  db::DBTrait,
+  db::{DBTrait, user::user_ext::MfaEnrolment},
   utils::{UpdateMessage, Updater},
 };
 
@@ -127,6 +130,7 @@ async fn finish_setup(
 
   drop(totp);
   state.reg_state.remove(&auth.user_id);
+  finish_enrolment(&db, &updater, auth.user_id).await?;
   updater
     .send_to(auth.user_id, UpdateMessage::User { uuid: auth.user_id })
     .await;
@@ -139,12 +143,13 @@ struct AuthRes {
   user: Uuid,
 }
 
-#[instrument(skip(db, jwt, cookies))]
+#[instrument(skip(db, jwt, cookies, client))]
 async fn confirm(
   auth: JwtAuthOther<JwtTotpRequired>,
   db: Connection,
   jwt: JwtState,
   mut cookies: CookieJar,
+  client: ClientInfo,
   Json(req): Json<TotpConfirmReq>,
 ) -> Result<(CookieJar, TokenRes<AuthRes>)> {
   let user = db.user_ext().get_user_by_id(auth.user_id).await?;
@@ -166,7 +171,16 @@ async fn confirm(
   {
     bail!(UNAUTHORIZED, "Invalid TOTP code");
   } else {
-    let cookie = create_session_cookie(&db, &jwt, auth.user_id, false, req.session).await?;
+    let cookie = create_session_cookie(
+      &db,
+      &jwt,
+      auth.user_id,
+      false,
+      AuthMethods::new(&[AuthMethod::Password, AuthMethod::Otp]),
+      req.session,
+      &client,
+    )
+    .await?;
     cookies = cookies.add(cookie);
 
     Ok((cookies, TokenRes(AuthRes { user: auth.user_id })))
@@ -179,6 +193,17 @@ async fn remove(
   db: Connection,
   updater: Updater,
 ) -> Result<StatusCode> {
+  let enrolment = db.user_ext().mfa_enrolment(auth.user_id).await?;
+  ensure_removable(
+    &db,
+    auth.user_id,
+    MfaEnrolment {
+      totp: false,
+      ..enrolment
+    },
+  )
+  .await?;
+
   db.user_ext().totp_remove(auth.user_id).await?;
   updater
     .send_to(auth.user_id, UpdateMessage::User { uuid: auth.user_id })
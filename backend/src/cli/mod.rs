@@ -5,7 +5,7 @@ use tracing::{error, level_filters::LevelFilter};
 use crate::{
   cli::{
     apod::ApodCommands, group::GroupCommands, oauth_client::OAuthClientCommands,
-    oauth_policy::OAuthPolicyCommands, oauth_scope::OAuthScopeCommands,
+    oauth_policy::OAuthPolicyCommands, oauth_scope::OAuthScopeCommands, user::UserCommands,
   },
   config::Config,
 };
@@ -15,6 +15,7 @@ mod group;
 mod oauth_client;
 mod oauth_policy;
 mod oauth_scope;
+mod user;
 
 #[derive(Parser)]
 pub struct Cli {
@@ -50,6 +51,10 @@ enum Commands {
     #[command(subcommand)]
     command: ApodCommands,
   },
+  User {
+    #[command(subcommand)]
+    command: UserCommands,
+  },
 }
 
 impl Cli {
@@ -80,6 +85,7 @@ impl Cli {
       Commands::OauthPolicy { command } => command.run(db).await?,
       Commands::OauthScope { command } => command.run(db).await?,
       Commands::Apod { command } => command.run(db).await?,
+      Commands::User { command } => command.run(db).await?,
       Commands::Serve => unreachable!(),
     }
 
@@ -80,6 +80,7 @@ impl OAuthClientCommands {
             redirect_uri: redirect_uri.to_string(),
             confidential: true,
             require_pkce: *require_pkce,
+            required_acr: None,
             salt,
             client_secret,
           })
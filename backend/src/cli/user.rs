@@ -0,0 +1,116 @@
+use centaurus::{bail, db::init::Connection, error::Result};
+use chrono::{DateTime, Utc};
+use tracing::info;
+
+use crate::db::DBTrait;
+
+#[derive(clap::Subcommand)]
+pub enum UserCommands {
+  Suspend {
+    email: String,
+    #[clap(long)]
+    reason: Option<String>,
+    /// RFC 3339 timestamp after which the suspension ends
+    #[clap(long)]
+    until: Option<DateTime<Utc>>,
+  },
+  Resume {
+    email: String,
+  },
+}
+
+impl UserCommands {
+  pub async fn run(&self, db: Connection) -> Result<()> {
+    match self {
+      UserCommands::Suspend {
+        email,
+        reason,
+        until,
+      } => {
+        if until.is_some_and(|until| until <= Utc::now()) {
+          bail!("Suspension must end in the future");
+        }
+        let Ok(user) = db.user_ext().get_user_by_email(email).await else {
+          bail!("User with email {} does not exist", email);
+        };
+
+        db.user_ext()
+          .suspend(user.id, reason.clone(), *until)
+          .await?;
+        info!("User {} with UUID {} suspended", email, user.id);
+      }
+      UserCommands::Resume { email } => {
+        let Ok(user) = db.user_ext().get_user_by_email(email).await else {
+          bail!("User with email {} does not exist", email);
+        };
+
+        db.user_ext().resume(user.id).await?;
+        info!("User {} with UUID {} resumed", email, user.id);
+      }
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use chrono::{Duration, Utc};
+
+  use super::UserCommands;
+  use crate::db::{
+    DBTrait,
+    test::{insert_user, test_db},
+  };
+
+  #[tokio::test]
+  async fn suspend_then_resume() {
+    let db = test_db().await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+
+    UserCommands::Suspend {
+      email: "u@x.com".into(),
+      reason: Some("spam".into()),
+      until: Some(Utc::now() + Duration::days(1)),
+    }
+    .run(db.clone())
+    .await
+    .unwrap();
+    let suspension = db.user_ext().suspension(user).await.unwrap().unwrap();
+    assert_eq!(suspension.reason.as_deref(), Some("spam"));
+    assert!(suspension.until.is_some());
+
+    UserCommands::Resume {
+      email: "u@x.com".into(),
+    }
+    .run(db.clone())
+    .await
+    .unwrap();
+    assert!(db.user_ext().suspension(user).await.unwrap().is_none());
+  }
+
+  #[tokio::test]
+  async fn unknown_user_and_past_expiry_error() {
+    let db = test_db().await;
+    insert_user(&db, "u", "u@x.com").await;
+
+    assert!(
+      UserCommands::Resume {
+        email: "ghost@x.com".into(),
+      }
+      .run(db.clone())
+      .await
+      .is_err()
+    );
+    assert!(
+      UserCommands::Suspend {
+        email: "u@x.com".into(),
+        reason: None,
+        until: Some(Utc::now() - Duration::days(1)),
+      }
+      .run(db)
+      .await
+      .is_err()
+    );
+  }
+}
@@ -16,6 +16,8 @@ use serde::{Deserialize, Serialize};
 use tracing::instrument;
 use url::Url;
 
+use crate::auth::state::PasskeyPolicy;
+
 #[derive(Deserialize, Serialize, Clone, Config)]
 pub struct Config {
   #[base]
@@ -48,12 +50,34 @@ pub struct Config {
   pub webauthn_rp_origin: Option<Url>,
   pub webauthn_name: String,
   pub webauthn_additional_origins: String,
+  pub webauthn_allowed_aaguids: String,
+  pub webauthn_attestation_ca: String,
+  pub webauthn_require_user_verification: bool,
+
+  //sessions, in seconds, 0 disables the limit
+  pub session_idle_timeout: i64,
+  pub session_max_lifetime: i64,
+  pub app_session_idle_timeout: i64,
+  pub app_session_max_lifetime: i64,
 
   //oidc
   pub oidc_refresh_exp: i64,
 
+  //user
+  pub account_deletion_grace_days: i64,
+
   //notes
   pub notes_max_per_user: u32,
+  pub notes_trash_retention_days: i64,
+  pub notes_snapshot_min_interval_mins: i64,
+  pub notes_snapshot_max_interval_mins: i64,
+  pub notes_snapshot_min_size_change: i64,
+  pub notes_snapshot_retention: String,
+  pub notes_snapshot_override_min_interval_mins: i64,
+  pub notes_snapshot_override_max_days: i64,
+  pub notes_snapshot_max_kept: u64,
+  pub notes_attachment_max_size_mb: u64,
+  pub notes_attachment_quota_mb: u64,
 }
 
 impl Default for Config {
@@ -67,9 +91,27 @@ impl Default for Config {
       webauthn_rp_origin: None,
       webauthn_name: "Positron".to_string(),
       webauthn_additional_origins: "".to_string(),
+      webauthn_allowed_aaguids: "".to_string(),
+      webauthn_attestation_ca: "".to_string(),
+      webauthn_require_user_verification: false,
+      session_idle_timeout: 0,
+      session_max_lifetime: 0,
+      app_session_idle_timeout: 0,
+      app_session_max_lifetime: 0,
       oidc_refresh_exp: 604800,
       storage: StorageConfig::default(),
+      account_deletion_grace_days: 14,
       notes_max_per_user: 20,
+      notes_trash_retention_days: 30,
+      notes_snapshot_min_interval_mins: 10,
+      notes_snapshot_max_interval_mins: 60,
+      notes_snapshot_min_size_change: 100,
+      notes_snapshot_retention: "2h:30m,4h:1h,1d:6h,3d:1d,7d:7d,30d:30d,365d:365d".to_string(),
+      notes_snapshot_override_min_interval_mins: 1,
+      notes_snapshot_override_max_days: 730,
+      notes_snapshot_max_kept: 50,
+      notes_attachment_max_size_mb: 10,
+      notes_attachment_quota_mb: 100,
       metrics: MetricsConfig::default(),
       site: SiteConfig::default(),
       auth: AuthConfig {
@@ -106,6 +148,10 @@ impl Config {
 
     config.storage.validate();
 
+    if let Err(err) = PasskeyPolicy::from_config(&config) {
+      panic!("{err}");
+    }
+
     let mut origins: Vec<String> = config
       .base
       .allowed_origins
@@ -142,10 +188,23 @@ mod test {
     let config = Config::default();
     assert!(config.db_url.is_empty());
     assert_eq!(config.webauthn_name, "Positron");
+    assert!(config.webauthn_allowed_aaguids.is_empty());
+    assert!(!config.webauthn_require_user_verification);
+    assert_eq!(config.session_idle_timeout, 0);
+    assert_eq!(config.session_max_lifetime, 0);
+    assert_eq!(config.app_session_idle_timeout, 0);
+    assert_eq!(config.app_session_max_lifetime, 0);
     assert_eq!(config.oidc_refresh_exp, 604800);
     assert_eq!(config.assetlinks, "{}");
     assert_eq!(config.auth.auth_jwt_expiration, 60 * 60 * 24 * 31);
+    assert_eq!(config.account_deletion_grace_days, 14);
     assert_eq!(config.notes_max_per_user, 20);
+    assert_eq!(config.notes_trash_retention_days, 30);
+    assert_eq!(config.notes_snapshot_min_interval_mins, 10);
+    assert_eq!(config.notes_snapshot_max_interval_mins, 60);
+    assert_eq!(config.notes_snapshot_min_size_change, 100);
+    assert_eq!(config.notes_attachment_max_size_mb, 10);
+    assert_eq!(config.notes_attachment_quota_mb, 100);
   }
 
   #[test]
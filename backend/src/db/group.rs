@@ -0,0 +1,363 @@
+use std::collections::{HashMap, HashSet};
+
+use entity::{group, group_parent, group_permission, group_user, prelude::*};
+use sea_orm::{ActiveValue::Set, QuerySelect, TransactionTrait, prelude::*};
+use uuid::Uuid;
+
+/// Group settings and the group hierarchy on top of the centaurus group table.
+///
+/// Members of a group are also members of all of its ancestors, so they
+/// inherit the ancestors' permissions, client access and policies.
+pub struct GroupExtTable<'db> {
+  db: &'db DatabaseConnection,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParentsUpdate {
+  Updated,
+  NotFound,
+  Cycle,
+}
+
+impl<'db> GroupExtTable<'db> {
+  pub fn new(db: &'db DatabaseConnection) -> Self {
+    Self { db }
+  }
+
+  /// Returns false if the group does not exist.
+  pub async fn set_require_mfa(&self, group_id: Uuid, require_mfa: bool) -> Result<bool, DbErr> {
+    let res = Group::update_many()
+      .col_expr(group::Column::RequireMfa, Expr::value(require_mfa))
+      .filter(group::Column::Id.eq(group_id))
+      .exec(self.db)
+      .await?;
+    Ok(res.rows_affected > 0)
+  }
+
+  pub async fn list_require_mfa(&self) -> Result<Vec<Uuid>, DbErr> {
+    Group::find()
+      .select_only()
+      .column(group::Column::Id)
+      .filter(group::Column::RequireMfa.eq(true))
+      .into_tuple()
+      .all(self.db)
+      .await
+  }
+
+  /// Members of at least one group that requires MFA, directly or through a
+  /// parent group.
+  pub async fn users_requiring_mfa(&self) -> Result<Vec<Uuid>, DbErr> {
+    let required = self.list_require_mfa().await?;
+    self.members(&required).await
+  }
+
+  pub async fn user_requires_mfa(&self, user_id: Uuid) -> Result<bool, DbErr> {
+    let groups = self.effective_group_ids(user_id).await?;
+    let count = Group::find()
+      .filter(group::Column::Id.is_in(groups))
+      .filter(group::Column::RequireMfa.eq(true))
+      .count(self.db)
+      .await?;
+    Ok(count > 0)
+  }
+
+  pub async fn list_parents(&self) -> Result<Vec<group_parent::Model>, DbErr> {
+    GroupParent::find().all(self.db).await
+  }
+
+  /// Replaces the parents of a group unless one of the groups is missing or
+  /// the group would become its own ancestor.
+  pub async fn set_parents(
+    &self,
+    group_id: Uuid,
+    mut parents: Vec<Uuid>,
+  ) -> Result<ParentsUpdate, DbErr> {
+    parents.sort();
+    parents.dedup();
+    let txn = self.db.begin().await?;
+
+    // locks every group so concurrent updates cannot close a cycle together
+    let groups: HashSet<Uuid> = Group::find()
+      .select_only()
+      .column(group::Column::Id)
+      .lock_exclusive()
+      .into_tuple::<Uuid>()
+      .all(&txn)
+      .await?
+      .into_iter()
+      .collect();
+    if !std::iter::once(&group_id)
+      .chain(&parents)
+      .all(|group| groups.contains(group))
+    {
+      return Ok(ParentsUpdate::NotFound);
+    }
+    if expand(&parent_map(&txn).await?, parents.iter().copied()).contains(&group_id) {
+      return Ok(ParentsUpdate::Cycle);
+    }
+
+    GroupParent::delete_many()
+      .filter(group_parent::Column::GroupId.eq(group_id))
+      .exec(&txn)
+      .await?;
+
+    if !parents.is_empty() {
+      GroupParent::insert_many(
+        parents
+          .into_iter()
+          .map(|parent_id| group_parent::ActiveModel {
+            group_id: Set(group_id),
+            parent_id: Set(parent_id),
+          }),
+      )
+      .exec(&txn)
+      .await?;
+    }
+
+    txn.commit().await?;
+    Ok(ParentsUpdate::Updated)
+  }
+
+  /// Groups the user belongs to directly or through a child group.
+  pub async fn effective_group_ids(&self, user_id: Uuid) -> Result<Vec<Uuid>, DbErr> {
+    let direct: Vec<Uuid> = GroupUser::find()
+      .select_only()
+      .column(group_user::Column::GroupId)
+      .filter(group_user::Column::UserId.eq(user_id))
+      .into_tuple()
+      .all(self.db)
+      .await?;
+    if direct.is_empty() {
+      return Ok(direct);
+    }
+
+    let parents = parent_map(self.db).await?;
+    Ok(expand(&parents, direct).into_iter().collect())
+  }
+
+  pub async fn effective_groups(&self, user_id: Uuid) -> Result<Vec<group::Model>, DbErr> {
+    let ids = self.effective_group_ids(user_id).await?;
+    Group::find()
+      .filter(group::Column::Id.is_in(ids))
+      .all(self.db)
+      .await
+  }
+
+  pub async fn is_member(&self, group_id: Uuid, user_id: Uuid) -> Result<bool, DbErr> {
+    Ok(self.effective_group_ids(user_id).await?.contains(&group_id))
+  }
+
+  pub async fn effective_permissions(&self, user_id: Uuid) -> Result<Vec<String>, DbErr> {
+    let groups = self.effective_group_ids(user_id).await?;
+    GroupPermission::find()
+      .select_only()
+      .column(group_permission::Column::Permission)
+      .distinct()
+      .filter(group_permission::Column::GroupId.is_in(groups))
+      .into_tuple()
+      .all(self.db)
+      .await
+  }
+
+  /// Permissions granted to members of the groups, including those of their
+  /// ancestors.
+  pub async fn group_permissions(&self, groups: Vec<Uuid>) -> Result<Vec<String>, DbErr> {
+    let groups = expand(&parent_map(self.db).await?, groups);
+    GroupPermission::find()
+      .select_only()
+      .column(group_permission::Column::Permission)
+      .distinct()
+      .filter(group_permission::Column::GroupId.is_in(groups))
+      .into_tuple()
+      .all(self.db)
+      .await
+  }
+
+  pub async fn user_has_permission(&self, user_id: Uuid, permission: &str) -> Result<bool, DbErr> {
+    let groups = self.effective_group_ids(user_id).await?;
+    let count = GroupPermission::find()
+      .filter(group_permission::Column::GroupId.is_in(groups))
+      .filter(group_permission::Column::Permission.eq(permission))
+      .count(self.db)
+      .await?;
+    Ok(count > 0)
+  }
+
+  /// Users belonging to any of the groups, directly or through a child group.
+  pub async fn members(&self, groups: &[Uuid]) -> Result<Vec<Uuid>, DbErr> {
+    if groups.is_empty() {
+      return Ok(Vec::new());
+    }
+
+    let parents = parent_map(self.db).await?;
+    let groups: Vec<Uuid> = Group::find()
+      .select_only()
+      .column(group::Column::Id)
+      .into_tuple::<Uuid>()
+      .all(self.db)
+      .await?
+      .into_iter()
+      .filter(|group| {
+        expand(&parents, [*group])
+          .iter()
+          .any(|ancestor| groups.contains(ancestor))
+      })
+      .collect();
+
+    GroupUser::find()
+      .select_only()
+      .column(group_user::Column::UserId)
+      .distinct()
+      .filter(group_user::Column::GroupId.is_in(groups))
+      .into_tuple()
+      .all(self.db)
+      .await
+  }
+}
+
+/// Parent links of every group, the hierarchy is small enough to resolve in
+/// memory.
+async fn parent_map(db: &impl ConnectionTrait) -> Result<HashMap<Uuid, Vec<Uuid>>, DbErr> {
+  let mut map: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+  for link in GroupParent::find().all(db).await? {
+    map.entry(link.group_id).or_default().push(link.parent_id);
+  }
+  Ok(map)
+}
+
+/// The given groups together with all of their ancestors.
+fn expand(
+  parents: &HashMap<Uuid, Vec<Uuid>>,
+  groups: impl IntoIterator<Item = Uuid>,
+) -> HashSet<Uuid> {
+  let mut seen = HashSet::new();
+  let mut queue: Vec<Uuid> = groups.into_iter().collect();
+
+  while let Some(group) = queue.pop() {
+    if seen.insert(group)
+      && let Some(next) = parents.get(&group)
+    {
+      queue.extend(next);
+    }
+  }
+
+  seen
+}
+
+#[cfg(test)]
+mod test {
+  use centaurus::db::tables::ConnectionExt;
+
+  use super::ParentsUpdate;
+  use crate::db::{
+    DBTrait,
+    test::{add_user_to_group, insert_group, insert_user, test_db},
+  };
+
+  #[tokio::test]
+  async fn require_mfa_applies_to_members() {
+    let db = test_db().await;
+    let member = insert_user(&db, "m", "m@x.com").await;
+    let other = insert_user(&db, "o", "o@x.com").await;
+    let admins = insert_group(&db, "admins").await;
+    let users = insert_group(&db, "users").await;
+    add_user_to_group(&db, admins, member).await;
+    add_user_to_group(&db, users, member).await;
+    add_user_to_group(&db, users, other).await;
+
+    assert!(!db.group_ext().user_requires_mfa(member).await.unwrap());
+
+    db.group_ext().set_require_mfa(admins, true).await.unwrap();
+    assert_eq!(db.group_ext().list_require_mfa().await.unwrap(), [admins]);
+    assert!(db.group_ext().user_requires_mfa(member).await.unwrap());
+    assert!(!db.group_ext().user_requires_mfa(other).await.unwrap());
+    assert_eq!(
+      db.group_ext().users_requiring_mfa().await.unwrap(),
+      [member]
+    );
+  }
+
+  #[tokio::test]
+  async fn members_inherit_from_ancestors() {
+    let db = test_db().await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let org = insert_group(&db, "org").await;
+    let eng = insert_group(&db, "eng").await;
+    let backend = insert_group(&db, "backend").await;
+    add_user_to_group(&db, backend, user).await;
+    db.group()
+      .add_permissions_to_group(org, vec!["user:view".into()])
+      .await
+      .unwrap();
+    db.group_ext().set_require_mfa(org, true).await.unwrap();
+
+    assert!(
+      !db
+        .group_ext()
+        .user_has_permission(user, "user:view")
+        .await
+        .unwrap()
+    );
+    assert!(
+      db.group_ext()
+        .users_requiring_mfa()
+        .await
+        .unwrap()
+        .is_empty()
+    );
+
+    db.group_ext()
+      .set_parents(backend, vec![eng])
+      .await
+      .unwrap();
+    db.group_ext().set_parents(eng, vec![org]).await.unwrap();
+    let mut groups = db.group_ext().effective_group_ids(user).await.unwrap();
+    groups.sort();
+    let mut expected = vec![org, eng, backend];
+    expected.sort();
+    assert_eq!(groups, expected);
+    assert!(db.group_ext().is_member(org, user).await.unwrap());
+    assert!(
+      db.group_ext()
+        .user_has_permission(user, "user:view")
+        .await
+        .unwrap()
+    );
+    assert_eq!(
+      db.group_ext().effective_permissions(user).await.unwrap(),
+      ["user:view"]
+    );
+    assert!(db.group_ext().user_requires_mfa(user).await.unwrap());
+    assert_eq!(db.group_ext().users_requiring_mfa().await.unwrap(), [user]);
+  }
+
+  #[tokio::test]
+  async fn cycles_are_detected() {
+    let db = test_db().await;
+    let a = insert_group(&db, "a").await;
+    let b = insert_group(&db, "b").await;
+    let c = insert_group(&db, "c").await;
+    db.group_ext().set_parents(b, vec![a]).await.unwrap();
+    db.group_ext().set_parents(c, vec![b]).await.unwrap();
+
+    let groups = db.group_ext();
+    let set_parents = |group, parents| groups.set_parents(group, parents);
+    assert_eq!(set_parents(a, vec![a]).await.unwrap(), ParentsUpdate::Cycle);
+    assert_eq!(set_parents(a, vec![c]).await.unwrap(), ParentsUpdate::Cycle);
+    assert_eq!(
+      set_parents(c, vec![uuid::Uuid::new_v4()]).await.unwrap(),
+      ParentsUpdate::NotFound
+    );
+    assert_eq!(db.group_ext().list_parents().await.unwrap().len(), 2);
+    assert_eq!(
+      set_parents(c, vec![a, b, a]).await.unwrap(),
+      ParentsUpdate::Updated
+    );
+    assert_eq!(db.group_ext().list_parents().await.unwrap().len(), 3);
+
+    db.group().delete_group(b).await.unwrap();
+    let links = db.group_ext().list_parents().await.unwrap();
+    assert_eq!(links.len(), 1);
+    assert_eq!((links[0].group_id, links[0].parent_id), (c, a));
+  }
+}
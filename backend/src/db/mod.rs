@@ -1,15 +1,30 @@
 use centaurus::db::init::Connection;
+use group::GroupExtTable;
 use notes::NoteTable;
 use oauth::{
   oauth_client::OauthClientTable, oauth_policy::OAuthPolicyTable, oauth_scope::OAuthScopeTable,
 };
+use oidc::{identity::UserIdentityTable, provider::OidcProviderTable};
 use services::apod::ApodTable;
-use user::{passkey::PasskeyTable, session::SessionTable, settings::SettingsTable};
+use user::{
+  deletion::AccountDeletionTable, export::DataExportTable, impersonation::ImpersonationTable,
+  known_device::KnownDeviceTable, passkey::PasskeyTable, session::SessionTable,
+  settings::SettingsTable,
+};
 
-use crate::db::{notes::snapshot::NoteSnapshotTable, user::user_ext::UserExtTable};
+use crate::db::{
+  notes::{
+    attachment::NoteAttachmentTable, comment::NoteCommentTable, folder::NoteFolderTable,
+    link::NoteLinkTable, mention::NoteMentionTable, search::NoteSearchTable,
+    snapshot::NoteSnapshotTable, snapshot_policy::NoteSnapshotPolicyTable, tag::NoteTagTable,
+  },
+  user::user_ext::UserExtTable,
+};
 
+pub mod group;
 pub mod notes;
 pub mod oauth;
+pub mod oidc;
 pub mod services;
 pub mod user;
 
@@ -24,6 +39,21 @@ pub trait DBTrait {
   fn settings(&self) -> SettingsTable<'_>;
   fn notes(&self) -> NoteTable<'_>;
   fn note_snapshot(&self) -> NoteSnapshotTable<'_>;
+  fn note_snapshot_policy(&self) -> NoteSnapshotPolicyTable<'_>;
+  fn note_attachment(&self) -> NoteAttachmentTable<'_>;
+  fn note_comment(&self) -> NoteCommentTable<'_>;
+  fn note_mention(&self) -> NoteMentionTable<'_>;
+  fn note_link(&self) -> NoteLinkTable<'_>;
+  fn note_folder(&self) -> NoteFolderTable<'_>;
+  fn note_tag(&self) -> NoteTagTable<'_>;
+  fn note_search(&self) -> NoteSearchTable<'_>;
+  fn oidc_provider(&self) -> OidcProviderTable<'_>;
+  fn user_identity(&self) -> UserIdentityTable<'_>;
+  fn impersonation(&self) -> ImpersonationTable<'_>;
+  fn data_export(&self) -> DataExportTable<'_>;
+  fn account_deletion(&self) -> AccountDeletionTable<'_>;
+  fn known_device(&self) -> KnownDeviceTable<'_>;
+  fn group_ext(&self) -> GroupExtTable<'_>;
 }
 
 impl DBTrait for Connection {
@@ -66,6 +96,66 @@ impl DBTrait for Connection {
   fn note_snapshot(&self) -> NoteSnapshotTable<'_> {
     NoteSnapshotTable::new(&self.0)
   }
+
+  fn note_snapshot_policy(&self) -> NoteSnapshotPolicyTable<'_> {
+    NoteSnapshotPolicyTable::new(&self.0)
+  }
+
+  fn note_attachment(&self) -> NoteAttachmentTable<'_> {
+    NoteAttachmentTable::new(&self.0)
+  }
+
+  fn note_comment(&self) -> NoteCommentTable<'_> {
+    NoteCommentTable::new(&self.0)
+  }
+
+  fn note_mention(&self) -> NoteMentionTable<'_> {
+    NoteMentionTable::new(&self.0)
+  }
+
+  fn note_link(&self) -> NoteLinkTable<'_> {
+    NoteLinkTable::new(&self.0)
+  }
+
+  fn note_folder(&self) -> NoteFolderTable<'_> {
+    NoteFolderTable::new(&self.0)
+  }
+
+  fn note_tag(&self) -> NoteTagTable<'_> {
+    NoteTagTable::new(&self.0)
+  }
+
+  fn note_search(&self) -> NoteSearchTable<'_> {
+    NoteSearchTable::new(&self.0)
+  }
+
+  fn oidc_provider(&self) -> OidcProviderTable<'_> {
+    OidcProviderTable::new(&self.0)
+  }
+
+  fn user_identity(&self) -> UserIdentityTable<'_> {
+    UserIdentityTable::new(&self.0)
+  }
+
+  fn impersonation(&self) -> ImpersonationTable<'_> {
+    ImpersonationTable::new(&self.0)
+  }
+
+  fn data_export(&self) -> DataExportTable<'_> {
+    DataExportTable::new(&self.0)
+  }
+
+  fn account_deletion(&self) -> AccountDeletionTable<'_> {
+    AccountDeletionTable::new(&self.0)
+  }
+
+  fn known_device(&self) -> KnownDeviceTable<'_> {
+    KnownDeviceTable::new(&self.0)
+  }
+
+  fn group_ext(&self) -> GroupExtTable<'_> {
+    GroupExtTable::new(&self.0)
+  }
 }
 
 #[cfg(test)]
@@ -110,6 +200,10 @@ pub mod test {
       oidc_user: Set(false),
       totp: Set(None),
       oidc_subject: Set(None),
+      disabled: Set(false),
+      disabled_reason: Set(None),
+      disabled_until: Set(None),
+      tokens_revoked_at: Set(None),
     })
     .exec(&conn.0)
     .await
@@ -123,6 +217,7 @@ pub mod test {
     group::Entity::insert(group::ActiveModel {
       id: Set(id),
       name: Set(name.to_string()),
+      require_mfa: Set(false),
     })
     .exec(&conn.0)
     .await
@@ -153,6 +248,7 @@ pub mod test {
       user_id: Set(user_id),
       created: Set(Utc::now().naive_utc()),
       used: Set(Utc::now().naive_utc()),
+      ..Default::default()
     })
     .exec(&conn.0)
     .await
@@ -209,7 +305,7 @@ pub mod test {
     centaurus::backend::auth::jwt_state::JwtState::init_with_auth(
       &config.auth,
       conn,
-      crate::auth::session_auth::SessionAuth,
+      crate::auth::session_auth::SessionAuth::default(),
     )
     .await
   }
@@ -238,24 +334,57 @@ pub mod test {
     jwt: &centaurus::backend::auth::jwt_state::JwtState,
     user: Uuid,
   ) -> String {
-    use crate::auth::session_auth::SessionMeta;
+    use crate::auth::{
+      amr::{AuthMethod, AuthMethods},
+      session_auth::SessionMeta,
+    };
 
     let cookie = crate::auth::session_auth::create_session_cookie(
       conn,
       jwt,
       user,
       false,
+      AuthMethods::new(&[AuthMethod::Password]),
       SessionMeta {
         name: String::new(),
         application: String::new(),
         operating_system: String::new(),
       },
+      &Default::default(),
     )
     .await
     .expect("create token");
     format!("{}={}", cookie.name(), cookie.value())
   }
 
+  /// Produces a `Cookie:` header value for a session of `user` opened by
+  /// `admin` through impersonation.
+  pub async fn impersonation_cookie(
+    conn: &Connection,
+    jwt: &centaurus::backend::auth::jwt_state::JwtState,
+    user: Uuid,
+    admin: Uuid,
+  ) -> String {
+    use centaurus::backend::auth::jwt_state::JWT_COOKIE_NAME;
+
+    use crate::db::DBTrait;
+
+    let token = crate::auth::session_auth::create_session_raw_token(jwt, user)
+      .await
+      .expect("create token");
+    conn
+      .session()
+      .create_impersonation(
+        user,
+        admin,
+        token.clone(),
+        chrono::Utc::now() + chrono::Duration::minutes(15),
+      )
+      .await
+      .expect("create impersonation session");
+    format!("{JWT_COOKIE_NAME}={token}")
+  }
+
   /// Seeds a single jwt key and builds both the centaurus `JwtState` (used by
   /// `JwtAuth`) and the positron `JwtStateOther` (used by `JwtAuthOther<_>`)
   /// from it, so cookies minted by either validate.
@@ -270,7 +399,7 @@ pub mod test {
     let jwt = centaurus::backend::auth::jwt_state::JwtState::init_with_auth(
       &config.auth,
       conn,
-      crate::auth::session_auth::SessionAuth,
+      crate::auth::session_auth::SessionAuth::default(),
     )
     .await;
     let other = crate::auth::jwt::JwtStateOther::init(&config.auth, conn).await;
@@ -0,0 +1,111 @@
+use centaurus::error::Result;
+use chrono::Utc;
+use entity::{note, note_attachment, prelude::*};
+use sea_orm::{ActiveValue::Set, QueryOrder, QuerySelect, TransactionTrait, prelude::*};
+use uuid::Uuid;
+
+/// Files uploaded into notes, the content lives in the note's attachment
+/// folder of the file storage.
+pub struct NoteAttachmentTable<'db> {
+  db: &'db DatabaseConnection,
+}
+
+pub struct NewAttachment {
+  pub id: Uuid,
+  pub note_id: Uuid,
+  pub uploaded_by: Uuid,
+  pub file_name: String,
+  pub content_type: String,
+  pub size: i64,
+  pub has_thumbnail: bool,
+}
+
+impl<'db> NoteAttachmentTable<'db> {
+  pub fn new(db: &'db DatabaseConnection) -> Self {
+    Self { db }
+  }
+
+  /// Inserts the attachment unless it would push the note owner's usage over
+  /// `quota`, `None` then.
+  pub async fn create(
+    &self,
+    attachment: NewAttachment,
+    quota: i64,
+  ) -> Result<Option<note_attachment::Model>> {
+    let txn = self.db.begin().await?;
+
+    // locks the owner so parallel uploads cannot both pass the check
+    let owner: Uuid = Note::find_by_id(attachment.note_id)
+      .select_only()
+      .column(note::Column::Owner)
+      .into_tuple()
+      .one(&txn)
+      .await?
+      .ok_or(DbErr::RecordNotFound("note not found".into()))?;
+    User::find_by_id(owner).lock_exclusive().one(&txn).await?;
+    if used_by_owner(&txn, owner).await? + attachment.size > quota {
+      return Ok(None);
+    }
+
+    let model = note_attachment::ActiveModel {
+      id: Set(attachment.id),
+      note_id: Set(attachment.note_id),
+      uploaded_by: Set(Some(attachment.uploaded_by)),
+      file_name: Set(attachment.file_name),
+      content_type: Set(attachment.content_type),
+      size: Set(attachment.size),
+      has_thumbnail: Set(attachment.has_thumbnail),
+      created_at: Set(Utc::now().naive_utc()),
+    }
+    .insert(&txn)
+    .await?;
+
+    txn.commit().await?;
+    Ok(Some(model))
+  }
+
+  pub async fn get(&self, attachment_id: Uuid) -> Result<Option<note_attachment::Model>> {
+    Ok(
+      NoteAttachment::find_by_id(attachment_id)
+        .one(self.db)
+        .await?,
+    )
+  }
+
+  pub async fn list_for_note(&self, note_id: Uuid) -> Result<Vec<note_attachment::Model>> {
+    Ok(
+      NoteAttachment::find()
+        .filter(note_attachment::Column::NoteId.eq(note_id))
+        .order_by_asc(note_attachment::Column::CreatedAt)
+        .order_by_asc(note_attachment::Column::Id)
+        .all(self.db)
+        .await?,
+    )
+  }
+
+  pub async fn delete(&self, attachment_id: Uuid) -> Result<()> {
+    NoteAttachment::delete_by_id(attachment_id)
+      .exec(self.db)
+      .await?;
+    Ok(())
+  }
+
+  /// Bytes used by attachments in notes the user owns, trashed notes count
+  /// until they are purged.
+  pub async fn used_by_owner(&self, owner: Uuid) -> Result<i64> {
+    used_by_owner(self.db, owner).await
+  }
+}
+
+async fn used_by_owner(db: &impl ConnectionTrait, owner: Uuid) -> Result<i64> {
+  let sizes: Vec<i64> = NoteAttachment::find()
+    .select_only()
+    .column(note_attachment::Column::Size)
+    .inner_join(Note)
+    .filter(note::Column::Owner.eq(owner))
+    .into_tuple()
+    .all(db)
+    .await?;
+
+  Ok(sizes.into_iter().sum())
+}
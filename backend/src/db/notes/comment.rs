@@ -0,0 +1,189 @@
+use centaurus::error::Result;
+use chrono::Utc;
+use entity::{note_comment, note_comment_thread, prelude::*};
+use sea_orm::{ActiveValue::Set, QueryOrder, TransactionTrait, prelude::*};
+use uuid::Uuid;
+
+/// Comment threads are anchored to a range of the note text, every thread
+/// starts with the comment it was opened with.
+pub struct NoteCommentTable<'db> {
+  db: &'db DatabaseConnection,
+}
+
+pub struct NewThread {
+  pub note_id: Uuid,
+  pub author: Uuid,
+  pub anchor_start: Vec<u8>,
+  pub anchor_end: Vec<u8>,
+  pub quote: String,
+  pub body: String,
+}
+
+impl<'db> NoteCommentTable<'db> {
+  pub fn new(db: &'db DatabaseConnection) -> Self {
+    Self { db }
+  }
+
+  pub async fn create_thread(
+    &self,
+    thread: NewThread,
+  ) -> Result<(note_comment_thread::Model, note_comment::Model)> {
+    let now = Utc::now().naive_utc();
+    let txn = self.db.begin().await?;
+
+    let model = note_comment_thread::ActiveModel {
+      id: Set(Uuid::now_v7()),
+      note_id: Set(thread.note_id),
+      anchor_start: Set(thread.anchor_start),
+      anchor_end: Set(thread.anchor_end),
+      quote: Set(thread.quote),
+      created_at: Set(now),
+      resolved_at: Set(None),
+      resolved_by: Set(None),
+    }
+    .insert(&txn)
+    .await?;
+
+    let comment = note_comment::ActiveModel {
+      id: Set(Uuid::now_v7()),
+      thread_id: Set(model.id),
+      author: Set(thread.author),
+      body: Set(thread.body),
+      created_at: Set(now),
+      edited_at: Set(None),
+    }
+    .insert(&txn)
+    .await?;
+
+    txn.commit().await?;
+    Ok((model, comment))
+  }
+
+  pub async fn reply(
+    &self,
+    thread_id: Uuid,
+    author: Uuid,
+    body: String,
+  ) -> Result<note_comment::Model> {
+    Ok(
+      note_comment::ActiveModel {
+        id: Set(Uuid::now_v7()),
+        thread_id: Set(thread_id),
+        author: Set(author),
+        body: Set(body),
+        created_at: Set(Utc::now().naive_utc()),
+        edited_at: Set(None),
+      }
+      .insert(self.db)
+      .await?,
+    )
+  }
+
+  pub async fn thread(&self, thread_id: Uuid) -> Result<Option<note_comment_thread::Model>> {
+    Ok(
+      NoteCommentThread::find_by_id(thread_id)
+        .one(self.db)
+        .await?,
+    )
+  }
+
+  /// The comment together with the note of its thread.
+  pub async fn comment(&self, comment_id: Uuid) -> Result<Option<(note_comment::Model, Uuid)>> {
+    let Some(comment) = NoteComment::find_by_id(comment_id).one(self.db).await? else {
+      return Ok(None);
+    };
+    let Some(thread) = self.thread(comment.thread_id).await? else {
+      return Ok(None);
+    };
+    Ok(Some((comment, thread.note_id)))
+  }
+
+  /// Author of the comment the thread was opened with.
+  pub async fn thread_author(&self, thread_id: Uuid) -> Result<Option<Uuid>> {
+    Ok(
+      NoteComment::find()
+        .filter(note_comment::Column::ThreadId.eq(thread_id))
+        .order_by_asc(note_comment::Column::CreatedAt)
+        .order_by_asc(note_comment::Column::Id)
+        .one(self.db)
+        .await?
+        .map(|comment| comment.author),
+    )
+  }
+
+  /// Threads of the note in creation order, each with its comments.
+  pub async fn list_for_note(
+    &self,
+    note_id: Uuid,
+  ) -> Result<Vec<(note_comment_thread::Model, Vec<note_comment::Model>)>> {
+    Ok(
+      NoteCommentThread::find()
+        .filter(note_comment_thread::Column::NoteId.eq(note_id))
+        .order_by_asc(note_comment_thread::Column::CreatedAt)
+        .order_by_asc(note_comment_thread::Column::Id)
+        .find_with_related(NoteComment)
+        .order_by_asc(note_comment::Column::CreatedAt)
+        .order_by_asc(note_comment::Column::Id)
+        .all(self.db)
+        .await?,
+    )
+  }
+
+  /// Resolves the thread on behalf of `user_id`, `None` reopens it.
+  pub async fn set_resolved(&self, thread_id: Uuid, user_id: Option<Uuid>) -> Result<()> {
+    NoteCommentThread::update_many()
+      .col_expr(
+        note_comment_thread::Column::ResolvedAt,
+        Expr::value(user_id.map(|_| Utc::now().naive_utc())),
+      )
+      .col_expr(
+        note_comment_thread::Column::ResolvedBy,
+        Expr::value(user_id),
+      )
+      .filter(note_comment_thread::Column::Id.eq(thread_id))
+      .exec(self.db)
+      .await?;
+
+    Ok(())
+  }
+
+  pub async fn edit(&self, comment_id: Uuid, body: String) -> Result<()> {
+    NoteComment::update_many()
+      .col_expr(note_comment::Column::Body, Expr::value(body))
+      .col_expr(
+        note_comment::Column::EditedAt,
+        Expr::value(Utc::now().naive_utc()),
+      )
+      .filter(note_comment::Column::Id.eq(comment_id))
+      .exec(self.db)
+      .await?;
+
+    Ok(())
+  }
+
+  /// Deletes the comment, a thread without comments left is deleted with it.
+  pub async fn delete(&self, comment_id: Uuid, thread_id: Uuid) -> Result<()> {
+    let txn = self.db.begin().await?;
+
+    NoteComment::delete_by_id(comment_id).exec(&txn).await?;
+    let remaining = NoteComment::find()
+      .filter(note_comment::Column::ThreadId.eq(thread_id))
+      .count(&txn)
+      .await?;
+    if remaining == 0 {
+      NoteCommentThread::delete_by_id(thread_id)
+        .exec(&txn)
+        .await?;
+    }
+
+    txn.commit().await?;
+    Ok(())
+  }
+
+  pub async fn delete_thread(&self, thread_id: Uuid) -> Result<()> {
+    NoteCommentThread::delete_by_id(thread_id)
+      .exec(self.db)
+      .await?;
+    Ok(())
+  }
+}
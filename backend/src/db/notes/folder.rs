@@ -0,0 +1,408 @@
+use std::collections::HashMap;
+
+use centaurus::error::Result;
+use entity::{note_folder, note_folder_note, prelude::*};
+use schemars::JsonSchema;
+use sea_orm::{ActiveValue::Set, QueryOrder, QuerySelect, TransactionTrait, prelude::*};
+use serde::Serialize;
+use uuid::Uuid;
+
+/// Folders are personal, every user files the notes they can see into their
+/// own tree.
+pub struct NoteFolderTable<'db> {
+  db: &'db DatabaseConnection,
+}
+
+#[derive(Serialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct NoteFolderInfo {
+  pub id: Uuid,
+  pub parent: Option<Uuid>,
+  pub name: String,
+  pub position: i32,
+}
+
+impl From<note_folder::Model> for NoteFolderInfo {
+  fn from(folder: note_folder::Model) -> Self {
+    Self {
+      id: folder.id,
+      parent: folder.parent_id,
+      name: folder.name,
+      position: folder.position,
+    }
+  }
+}
+
+impl<'db> NoteFolderTable<'db> {
+  pub fn new(db: &'db DatabaseConnection) -> Self {
+    Self { db }
+  }
+
+  pub async fn list_for_user(&self, user_id: Uuid) -> Result<Vec<NoteFolderInfo>> {
+    Ok(
+      NoteFolder::find()
+        .filter(note_folder::Column::UserId.eq(user_id))
+        .order_by_asc(note_folder::Column::Position)
+        .order_by_asc(note_folder::Column::Name)
+        .all(self.db)
+        .await?
+        .into_iter()
+        .map(NoteFolderInfo::from)
+        .collect(),
+    )
+  }
+
+  /// Returns the folder only if it belongs to the user.
+  pub async fn get(&self, user_id: Uuid, folder_id: Uuid) -> Result<Option<note_folder::Model>> {
+    Ok(
+      NoteFolder::find_by_id(folder_id)
+        .filter(note_folder::Column::UserId.eq(user_id))
+        .one(self.db)
+        .await?,
+    )
+  }
+
+  /// New folders are appended after their siblings.
+  pub async fn create(&self, user_id: Uuid, name: String, parent: Option<Uuid>) -> Result<Uuid> {
+    let id = Uuid::now_v7();
+    let position = self
+      .siblings(self.db, user_id, parent, None)
+      .await?
+      .last()
+      .map(|folder| folder.position + 1)
+      .unwrap_or(0);
+
+    note_folder::ActiveModel {
+      id: Set(id),
+      user_id: Set(user_id),
+      parent_id: Set(parent),
+      name: Set(name),
+      position: Set(position),
+    }
+    .insert(self.db)
+    .await?;
+
+    Ok(id)
+  }
+
+  pub async fn rename(&self, folder_id: Uuid, name: String) -> Result<()> {
+    NoteFolder::update_many()
+      .col_expr(note_folder::Column::Name, Expr::value(name))
+      .filter(note_folder::Column::Id.eq(folder_id))
+      .exec(self.db)
+      .await?;
+    Ok(())
+  }
+
+  /// Moves a folder under `parent` at `position` among its new siblings,
+  /// `false` when `parent` lies within the folder and nothing was changed.
+  pub async fn move_folder(
+    &self,
+    user_id: Uuid,
+    folder_id: Uuid,
+    parent: Option<Uuid>,
+    position: usize,
+  ) -> Result<bool> {
+    let txn = self.db.begin().await?;
+
+    // locks the user's folders so concurrent moves cannot close a cycle together
+    let parents: HashMap<Uuid, Option<Uuid>> = NoteFolder::find()
+      .select_only()
+      .column(note_folder::Column::Id)
+      .column(note_folder::Column::ParentId)
+      .filter(note_folder::Column::UserId.eq(user_id))
+      .lock_exclusive()
+      .into_tuple()
+      .all(&txn)
+      .await?
+      .into_iter()
+      .collect();
+    if let Some(parent) = parent
+      && is_within(&parents, parent, folder_id)
+    {
+      return Ok(false);
+    }
+
+    let mut order: Vec<Uuid> = self
+      .siblings(&txn, user_id, parent, Some(folder_id))
+      .await?
+      .into_iter()
+      .map(|folder| folder.id)
+      .collect();
+    order.insert(position.min(order.len()), folder_id);
+
+    NoteFolder::update_many()
+      .col_expr(note_folder::Column::ParentId, Expr::value(parent))
+      .filter(note_folder::Column::Id.eq(folder_id))
+      .exec(&txn)
+      .await?;
+
+    for (position, id) in order.into_iter().enumerate() {
+      NoteFolder::update_many()
+        .col_expr(note_folder::Column::Position, Expr::value(position as i32))
+        .filter(note_folder::Column::Id.eq(id))
+        .exec(&txn)
+        .await?;
+    }
+
+    txn.commit().await?;
+    Ok(true)
+  }
+
+  /// Removes the folder with all subfolders, the notes in them become
+  /// unfiled.
+  pub async fn delete(&self, folder_id: Uuid) -> Result<()> {
+    NoteFolder::delete_by_id(folder_id).exec(self.db).await?;
+    Ok(())
+  }
+
+  /// Files the note into a folder of the user, `None` takes it out of any
+  /// folder.
+  pub async fn file_note(&self, user_id: Uuid, note_id: Uuid, folder: Option<Uuid>) -> Result<()> {
+    let txn = self.db.begin().await?;
+
+    NoteFolderNote::delete_many()
+      .filter(note_folder_note::Column::UserId.eq(user_id))
+      .filter(note_folder_note::Column::NoteId.eq(note_id))
+      .exec(&txn)
+      .await?;
+
+    if let Some(folder_id) = folder {
+      note_folder_note::ActiveModel {
+        user_id: Set(user_id),
+        note_id: Set(note_id),
+        folder_id: Set(folder_id),
+      }
+      .insert(&txn)
+      .await?;
+    }
+
+    txn.commit().await?;
+    Ok(())
+  }
+
+  /// Maps each filed note to the folder the user put it in.
+  pub async fn folders_for_notes(
+    &self,
+    user_id: Uuid,
+    note_ids: &[Uuid],
+  ) -> Result<HashMap<Uuid, Uuid>> {
+    if note_ids.is_empty() {
+      return Ok(HashMap::new());
+    }
+
+    Ok(
+      NoteFolderNote::find()
+        .filter(note_folder_note::Column::UserId.eq(user_id))
+        .filter(note_folder_note::Column::NoteId.is_in(note_ids.to_vec()))
+        .all(self.db)
+        .await?
+        .into_iter()
+        .map(|row| (row.note_id, row.folder_id))
+        .collect(),
+    )
+  }
+
+  async fn siblings<C: ConnectionTrait>(
+    &self,
+    conn: &C,
+    user_id: Uuid,
+    parent: Option<Uuid>,
+    exclude: Option<Uuid>,
+  ) -> Result<Vec<note_folder::Model>> {
+    let mut query = NoteFolder::find()
+      .filter(note_folder::Column::UserId.eq(user_id))
+      .filter(match parent {
+        Some(parent) => note_folder::Column::ParentId.eq(parent),
+        None => note_folder::Column::ParentId.is_null(),
+      })
+      .order_by_asc(note_folder::Column::Position)
+      .order_by_asc(note_folder::Column::Name);
+    if let Some(exclude) = exclude {
+      query = query.filter(note_folder::Column::Id.ne(exclude));
+    }
+
+    Ok(query.all(conn).await?)
+  }
+}
+
+/// Whether `folder_id` is `ancestor` or nested somewhere below it.
+fn is_within(parents: &HashMap<Uuid, Option<Uuid>>, folder_id: Uuid, ancestor: Uuid) -> bool {
+  let mut current = Some(folder_id);
+  // bounded by the number of folders in case the tree is already broken
+  for _ in 0..=parents.len() {
+    match current {
+      Some(id) if id == ancestor => return true,
+      Some(id) => current = parents.get(&id).copied().flatten(),
+      None => return false,
+    }
+  }
+
+  false
+}
+
+#[cfg(test)]
+mod test {
+  use std::collections::HashMap;
+
+  use uuid::Uuid;
+
+  use super::is_within;
+  use crate::db::{
+    DBTrait,
+    test::{insert_user, test_db},
+  };
+
+  #[tokio::test]
+  async fn folders_nest_and_reorder() {
+    let db = test_db().await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let work = db
+      .note_folder()
+      .create(user, "Work".into(), None)
+      .await
+      .unwrap();
+    let home = db
+      .note_folder()
+      .create(user, "Home".into(), None)
+      .await
+      .unwrap();
+    let ops = db
+      .note_folder()
+      .create(user, "Ops".into(), Some(work))
+      .await
+      .unwrap();
+
+    let top: Vec<_> = db
+      .note_folder()
+      .list_for_user(user)
+      .await
+      .unwrap()
+      .into_iter()
+      .filter(|f| f.parent.is_none())
+      .map(|f| f.id)
+      .collect();
+    assert_eq!(top, [work, home]);
+
+    assert!(
+      db.note_folder()
+        .move_folder(user, home, None, 0)
+        .await
+        .unwrap()
+    );
+    assert!(
+      db.note_folder()
+        .move_folder(user, ops, None, 1)
+        .await
+        .unwrap()
+    );
+    let top: Vec<_> = db
+      .note_folder()
+      .list_for_user(user)
+      .await
+      .unwrap()
+      .into_iter()
+      .map(|f| (f.id, f.parent))
+      .collect();
+    assert_eq!(top, [(home, None), (ops, None), (work, None)]);
+  }
+
+  #[test]
+  fn is_within_follows_the_parents() {
+    let (root, child, other) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+    let parents = HashMap::from([(root, None), (child, Some(root)), (other, None)]);
+
+    assert!(is_within(&parents, root, root));
+    assert!(is_within(&parents, child, root));
+    assert!(!is_within(&parents, root, child));
+    assert!(!is_within(&parents, other, root));
+  }
+
+  #[tokio::test]
+  async fn moving_a_folder_below_itself_changes_nothing() {
+    let db = test_db().await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let work = db
+      .note_folder()
+      .create(user, "Work".into(), None)
+      .await
+      .unwrap();
+    let ops = db
+      .note_folder()
+      .create(user, "Ops".into(), Some(work))
+      .await
+      .unwrap();
+
+    for parent in [work, ops] {
+      assert!(
+        !db
+          .note_folder()
+          .move_folder(user, work, Some(parent), 0)
+          .await
+          .unwrap()
+      );
+    }
+    let folders: Vec<_> = db
+      .note_folder()
+      .list_for_user(user)
+      .await
+      .unwrap()
+      .into_iter()
+      .map(|f| (f.id, f.parent))
+      .collect();
+    assert_eq!(folders, [(ops, Some(work)), (work, None)]);
+  }
+
+  #[tokio::test]
+  async fn deleting_a_folder_unfiles_its_notes() {
+    let db = test_db().await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let note = db.notes().create(user, "T".into()).await.unwrap();
+    let parent = db
+      .note_folder()
+      .create(user, "P".into(), None)
+      .await
+      .unwrap();
+    let child = db
+      .note_folder()
+      .create(user, "C".into(), Some(parent))
+      .await
+      .unwrap();
+    db.note_folder()
+      .file_note(user, note, Some(child))
+      .await
+      .unwrap();
+    assert!(
+      db.note_folder()
+        .list_for_user(user)
+        .await
+        .unwrap()
+        .iter()
+        .any(|f| f.id == child && f.parent == Some(parent))
+    );
+    assert_eq!(
+      db.note_folder()
+        .folders_for_notes(user, &[note])
+        .await
+        .unwrap()
+        .get(&note),
+      Some(&child)
+    );
+
+    db.note_folder().delete(parent).await.unwrap();
+    assert!(
+      db.note_folder()
+        .list_for_user(user)
+        .await
+        .unwrap()
+        .is_empty()
+    );
+    assert!(
+      db.note_folder()
+        .folders_for_notes(user, &[note])
+        .await
+        .unwrap()
+        .is_empty()
+    );
+    assert!(db.notes().info(note, user).await.unwrap().is_some());
+  }
+}
@@ -0,0 +1,174 @@
+use centaurus::error::Result;
+use entity::{note, note_link, prelude::*};
+use schemars::JsonSchema;
+use sea_orm::{
+  ActiveValue::Set, QueryOrder, QuerySelect, QueryTrait, TransactionTrait, prelude::*,
+};
+use serde::Serialize;
+use uuid::Uuid;
+
+/// Index of the note links found in note content, rebuilt on every save.
+pub struct NoteLinkTable<'db> {
+  db: &'db DatabaseConnection,
+}
+
+#[derive(Serialize, JsonSchema, Debug, PartialEq)]
+pub struct LinkedNote {
+  pub id: Uuid,
+  pub title: String,
+}
+
+/// A link target as currently stored, `None` if the note no longer exists.
+pub struct LinkTarget {
+  pub id: Uuid,
+  pub note: Option<(String, Option<DateTime>)>,
+}
+
+impl<'db> NoteLinkTable<'db> {
+  pub fn new(db: &'db DatabaseConnection) -> Self {
+    Self { db }
+  }
+
+  /// Replaces the notes `source_id` links to.
+  pub async fn set_links(&self, source_id: Uuid, targets: &[Uuid]) -> Result<()> {
+    let txn = self.db.begin().await?;
+
+    NoteLink::delete_many()
+      .filter(note_link::Column::SourceId.eq(source_id))
+      .exec(&txn)
+      .await?;
+
+    if !targets.is_empty() {
+      NoteLink::insert_many(targets.iter().map(|target_id| note_link::ActiveModel {
+        source_id: Set(source_id),
+        target_id: Set(*target_id),
+      }))
+      .exec(&txn)
+      .await?;
+    }
+
+    txn.commit().await?;
+    Ok(())
+  }
+
+  /// Notes among `note_ids` that link to `target_id`, sorted by title.
+  pub async fn backlinks(&self, target_id: Uuid, note_ids: &[Uuid]) -> Result<Vec<LinkedNote>> {
+    if note_ids.is_empty() {
+      return Ok(Vec::new());
+    }
+
+    let sources = NoteLink::find()
+      .select_only()
+      .column(note_link::Column::SourceId)
+      .filter(note_link::Column::TargetId.eq(target_id))
+      .into_query();
+
+    Ok(
+      Note::find()
+        .select_only()
+        .columns([note::Column::Id, note::Column::Title])
+        .filter(note::Column::Id.in_subquery(sources))
+        .filter(note::Column::Id.is_in(note_ids.iter().copied()))
+        .filter(note::Column::DeletedAt.is_null())
+        .order_by_asc(note::Column::Title)
+        .order_by_asc(note::Column::Id)
+        .into_tuple::<(Uuid, String)>()
+        .all(self.db)
+        .await?
+        .into_iter()
+        .map(|(id, title)| LinkedNote { id, title })
+        .collect(),
+    )
+  }
+
+  /// The notes `source_id` links to, including targets that were deleted.
+  pub async fn targets(&self, source_id: Uuid) -> Result<Vec<LinkTarget>> {
+    let ids: Vec<Uuid> = NoteLink::find()
+      .select_only()
+      .column(note_link::Column::TargetId)
+      .filter(note_link::Column::SourceId.eq(source_id))
+      .order_by_asc(note_link::Column::TargetId)
+      .into_tuple()
+      .all(self.db)
+      .await?;
+    if ids.is_empty() {
+      return Ok(Vec::new());
+    }
+
+    let notes: Vec<(Uuid, String, Option<DateTime>)> = Note::find()
+      .select_only()
+      .columns([
+        note::Column::Id,
+        note::Column::Title,
+        note::Column::DeletedAt,
+      ])
+      .filter(note::Column::Id.is_in(ids.iter().copied()))
+      .into_tuple()
+      .all(self.db)
+      .await?;
+
+    Ok(
+      ids
+        .into_iter()
+        .map(|id| LinkTarget {
+          id,
+          note: notes
+            .iter()
+            .find(|(note_id, ..)| *note_id == id)
+            .map(|(_, title, deleted_at)| (title.clone(), *deleted_at)),
+        })
+        .collect(),
+    )
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::LinkedNote;
+  use crate::db::{
+    DBTrait,
+    test::{insert_user, test_db},
+  };
+
+  #[tokio::test]
+  async fn backlinks_follow_the_latest_links_and_filter() {
+    let db = test_db().await;
+    let user = insert_user(&db, "user", "user@x.com").await;
+    let target = db.notes().create(user, "Target".into()).await.unwrap();
+    let b = db.notes().create(user, "B".into()).await.unwrap();
+    let a = db.notes().create(user, "A".into()).await.unwrap();
+    let hidden = db.notes().create(user, "Hidden".into()).await.unwrap();
+
+    for source in [a, b, hidden] {
+      db.note_link().set_links(source, &[target]).await.unwrap();
+    }
+    let backlinks = db
+      .note_link()
+      .backlinks(target, &[a, b, target])
+      .await
+      .unwrap();
+    assert_eq!(
+      backlinks,
+      [
+        LinkedNote {
+          id: a,
+          title: "A".into()
+        },
+        LinkedNote {
+          id: b,
+          title: "B".into()
+        },
+      ]
+    );
+
+    db.note_link().set_links(a, &[]).await.unwrap();
+    db.notes().trash(b).await.unwrap();
+    assert!(
+      db.note_link()
+        .backlinks(target, &[a, b])
+        .await
+        .unwrap()
+        .is_empty()
+    );
+  }
+}
@@ -0,0 +1,74 @@
+use centaurus::error::Result;
+use chrono::Utc;
+use entity::{note_mention, prelude::*, user};
+use sea_orm::{ActiveValue::Set, QuerySelect, prelude::*};
+use uuid::Uuid;
+
+/// Users that were already notified about being mentioned in a note, so a
+/// mention only notifies once while it stays in the note.
+pub struct NoteMentionTable<'db> {
+  db: &'db DatabaseConnection,
+}
+
+impl<'db> NoteMentionTable<'db> {
+  pub fn new(db: &'db DatabaseConnection) -> Self {
+    Self { db }
+  }
+
+  pub async fn list(&self, note_id: Uuid) -> Result<Vec<Uuid>> {
+    Ok(
+      NoteMention::find()
+        .select_only()
+        .column(note_mention::Column::UserId)
+        .filter(note_mention::Column::NoteId.eq(note_id))
+        .into_tuple()
+        .all(self.db)
+        .await?,
+    )
+  }
+
+  /// Forgets mentions that were removed from the note, mentioning the user
+  /// again notifies them again.
+  pub async fn retain(&self, note_id: Uuid, mentioned: &[Uuid]) -> Result<()> {
+    NoteMention::delete_many()
+      .filter(note_mention::Column::NoteId.eq(note_id))
+      .filter(note_mention::Column::UserId.is_not_in(mentioned.iter().copied()))
+      .exec(self.db)
+      .await?;
+    Ok(())
+  }
+
+  pub async fn add(&self, note_id: Uuid, users: &[Uuid]) -> Result<()> {
+    if users.is_empty() {
+      return Ok(());
+    }
+
+    let now = Utc::now().naive_utc();
+    NoteMention::insert_many(users.iter().map(|user_id| note_mention::ActiveModel {
+      note_id: Set(note_id),
+      user_id: Set(*user_id),
+      created_at: Set(now),
+    }))
+    .on_conflict_do_nothing()
+    .exec(self.db)
+    .await?;
+    Ok(())
+  }
+
+  /// The ids that belong to existing users.
+  pub async fn existing_users(&self, users: &[Uuid]) -> Result<Vec<Uuid>> {
+    if users.is_empty() {
+      return Ok(Vec::new());
+    }
+
+    Ok(
+      User::find()
+        .select_only()
+        .column(user::Column::Id)
+        .filter(user::Column::Id.is_in(users.iter().copied()))
+        .into_tuple()
+        .all(self.db)
+        .await?,
+    )
+  }
+}
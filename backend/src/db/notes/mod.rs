@@ -2,16 +2,31 @@ use std::collections::HashMap;
 
 use centaurus::{db::tables::group::SimpleUserInfo, error::Result};
 use chrono::Utc;
-use entity::{note, note_user, prelude::*, sea_orm_active_enums::NoteShareAccess, user};
+use entity::{
+  group, note, note_group, note_user, prelude::*, sea_orm_active_enums::NoteShareAccess, user,
+};
 use schemars::JsonSchema;
 use sea_orm::{
   ActiveValue::Set, Condition, ConnectionTrait, DatabaseBackend, ExprTrait, JoinType, Order,
-  QueryOrder, QuerySelect, TransactionTrait, prelude::*,
+  QueryOrder, QuerySelect, QueryTrait, TransactionTrait, prelude::*,
 };
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::db::{
+  group::GroupExtTable,
+  notes::{folder::NoteFolderTable, search::NoteSearchTable, tag::NoteTagTable},
+};
+
+pub mod attachment;
+pub mod comment;
+pub mod folder;
+pub mod link;
+pub mod mention;
+pub mod search;
 pub mod snapshot;
+pub mod snapshot_policy;
+pub mod tag;
 
 #[derive(DerivePartialModel)]
 #[sea_orm(entity = "user::Entity")]
@@ -47,6 +62,19 @@ pub struct NoteShareEntry {
   pub access: NoteShareAccess,
 }
 
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
+pub struct SharedGroupInfo {
+  pub id: Uuid,
+  pub name: String,
+  pub access: NoteShareAccess,
+}
+
+#[derive(Deserialize, JsonSchema, PartialEq, Clone, Debug)]
+pub struct NoteGroupShareEntry {
+  pub group_id: Uuid,
+  pub access: NoteShareAccess,
+}
+
 #[derive(Serialize, Deserialize, JsonSchema)]
 pub struct NoteInfo {
   pub id: Uuid,
@@ -54,12 +82,26 @@ pub struct NoteInfo {
   pub preview: String,
   pub owner: SimpleUserInfo,
   pub shared_with: Vec<SharedUserInfo>,
+  pub shared_with_groups: Vec<SharedGroupInfo>,
   pub public_access: Option<NoteShareAccess>,
+  /// Folder the requesting user filed the note into.
+  pub folder: Option<Uuid>,
+  /// Tags the requesting user put on the note.
+  pub tags: Vec<String>,
+  pub favourite: bool,
   pub is_owner: bool,
   pub can_edit: bool,
   pub last_updated: DateTime,
 }
 
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct TrashedNoteInfo {
+  pub id: Uuid,
+  pub title: String,
+  pub preview: String,
+  pub deleted_at: DateTime,
+}
+
 #[derive(Serialize, Deserialize, JsonSchema)]
 pub struct NoteInfoPublic {
   pub id: Uuid,
@@ -78,6 +120,10 @@ impl<'db> NoteTable<'db> {
   }
 
   pub async fn has_access(&self, user_id: Uuid, note_id: Uuid) -> Result<bool> {
+    if self.is_trashed(note_id).await? {
+      return Ok(false);
+    }
+
     if self.is_owner(user_id, note_id).await? {
       return Ok(true);
     }
@@ -92,10 +138,14 @@ impl<'db> NoteTable<'db> {
       .count(self.db)
       .await?;
 
-    Ok(count > 0)
+    Ok(count > 0 || self.group_access(user_id, note_id).await?.is_some())
   }
 
   pub async fn can_edit(&self, user_id: Uuid, note_id: Uuid) -> Result<bool> {
+    if self.is_trashed(note_id).await? {
+      return Ok(false);
+    }
+
     if self.is_owner(user_id, note_id).await? {
       return Ok(true);
     }
@@ -109,6 +159,9 @@ impl<'db> NoteTable<'db> {
     if row.is_some_and(|r| r.access == NoteShareAccess::Edit) {
       return Ok(true);
     }
+    if self.group_access(user_id, note_id).await? == Some(NoteShareAccess::Edit) {
+      return Ok(true);
+    }
 
     let access = self.get_public_access(note_id).await?;
     Ok(access.is_some_and(|a| a == NoteShareAccess::Edit))
@@ -130,26 +183,74 @@ impl<'db> NoteTable<'db> {
     Ok(shared_users)
   }
 
+  pub async fn shared_groups(&self, note_id: Uuid) -> Result<Vec<NoteGroupShareEntry>> {
+    let shared_groups = note_group::Entity::find()
+      .filter(note_group::Column::NoteId.eq(note_id))
+      .order_by_asc(note_group::Column::GroupId)
+      .all(self.db)
+      .await?
+      .into_iter()
+      .map(|row| NoteGroupShareEntry {
+        group_id: row.group_id,
+        access: row.access,
+      })
+      .collect();
+
+    Ok(shared_groups)
+  }
+
+  /// Users the note is shared with, including the members of shared groups.
   pub async fn shared_user_ids(&self, note_id: Uuid) -> Result<Vec<Uuid>> {
-    Ok(
-      self
-        .shared_users(note_id)
-        .await?
-        .into_iter()
-        .map(|s| s.user_id)
-        .collect(),
-    )
+    let mut users: Vec<Uuid> = self
+      .shared_users(note_id)
+      .await?
+      .into_iter()
+      .map(|s| s.user_id)
+      .collect();
+
+    let groups: Vec<Uuid> = self
+      .shared_groups(note_id)
+      .await?
+      .into_iter()
+      .map(|s| s.group_id)
+      .collect();
+    users.extend(GroupExtTable::new(self.db).members(&groups).await?);
+
+    users.sort_unstable();
+    users.dedup();
+    Ok(users)
   }
 
+  /// Highest access the user has through groups the note is shared with.
+  async fn group_access(&self, user_id: Uuid, note_id: Uuid) -> Result<Option<NoteShareAccess>> {
+    let groups = GroupExtTable::new(self.db)
+      .effective_group_ids(user_id)
+      .await?;
+
+    let access = note_group::Entity::find()
+      .filter(note_group::Column::NoteId.eq(note_id))
+      .filter(note_group::Column::GroupId.is_in(groups))
+      .all(self.db)
+      .await?
+      .into_iter()
+      .map(|row| row.access)
+      .reduce(strongest);
+
+    Ok(access)
+  }
+
+  /// Trashed notes do not count toward the note limit.
   pub async fn count_owned(&self, owner: Uuid) -> Result<u64> {
     Ok(
       note::Entity::find()
         .filter(note::Column::Owner.eq(owner))
+        .filter(note::Column::DeletedAt.is_null())
         .count(self.db)
         .await?,
     )
   }
 
+  /// Includes trashed notes.
   pub async fn list_owned_ids(&self, owner: Uuid) -> Result<Vec<Uuid>> {
     Ok(
       note::Entity::find()
@@ -172,6 +273,17 @@ impl<'db> NoteTable<'db> {
     Ok(count > 0)
   }
 
+  /// Trashed notes keep their owner but cannot be opened until restored.
+  pub async fn is_trashed(&self, note_id: Uuid) -> Result<bool> {
+    let count = note::Entity::find()
+      .filter(note::Column::Id.eq(note_id))
+      .filter(note::Column::DeletedAt.is_not_null())
+      .count(self.db)
+      .await?;
+
+    Ok(count > 0)
+  }
+
   async fn shared_with_for_notes(
     &self,
     note_ids: &[Uuid],
@@ -201,8 +313,71 @@ impl<'db> NoteTable<'db> {
     Ok(map)
   }
 
+  async fn shared_groups_for_notes(
+    &self,
+    note_ids: &[Uuid],
+  ) -> Result<HashMap<Uuid, Vec<SharedGroupInfo>>> {
+    if note_ids.is_empty() {
+      return Ok(HashMap::new());
+    }
+
+    let rows = note_group::Entity::find()
+      .filter(note_group::Column::NoteId.is_in(note_ids.to_vec()))
+      .find_also_related(group::Entity)
+      .all(self.db)
+      .await?;
+
+    let mut map: HashMap<Uuid, Vec<SharedGroupInfo>> = HashMap::new();
+    for (share, group) in rows {
+      let Some(group) = group else {
+        continue;
+      };
+      map.entry(share.note_id).or_default().push(SharedGroupInfo {
+        id: group.id,
+        name: group.name,
+        access: share.access,
+      });
+    }
+
+    Ok(map)
+  }
+
+  /// Notes that show up in the user's list, owned or shared directly or via a
+  /// group. Public links and trashed notes are not included.
+  pub async fn visible_ids(&self, user_id: Uuid) -> Result<Vec<Uuid>> {
+    let groups = GroupExtTable::new(self.db)
+      .effective_group_ids(user_id)
+      .await?;
+    let shared = note_user::Entity::find()
+      .select_only()
+      .column(note_user::Column::NoteId)
+      .filter(note_user::Column::UserId.eq(user_id))
+      .into_query();
+    let group_shared = note_group::Entity::find()
+      .select_only()
+      .column(note_group::Column::NoteId)
+      .filter(note_group::Column::GroupId.is_in(groups))
+      .into_query();
+
+    Ok(
+      Note::find()
+        .select_only()
+        .column(note::Column::Id)
+        .filter(
+          Condition::any()
+            .add(note::Column::Owner.eq(user_id))
+            .add(note::Column::Id.in_subquery(shared))
+            .add(note::Column::Id.in_subquery(group_shared)),
+        )
+        .filter(note::Column::DeletedAt.is_null())
+        .into_tuple()
+        .all(self.db)
+        .await?,
+    )
+  }
+
   pub async fn list_for_user(&self, user_id: Uuid) -> Result<Vec<NoteInfo>> {
-    let shared_note_ids: Vec<Uuid> = note_user::Entity::find()
+    let mut shared_note_ids: Vec<Uuid> = note_user::Entity::find()
       .filter(note_user::Column::UserId.eq(user_id))
       .all(self.db)
       .await?
@@ -210,12 +385,30 @@ impl<'db> NoteTable<'db> {
       .map(|row| row.note_id)
       .collect();
 
+    let groups = GroupExtTable::new(self.db)
+      .effective_group_ids(user_id)
+      .await?;
+    let mut group_access: HashMap<Uuid, NoteShareAccess> = HashMap::new();
+    for share in note_group::Entity::find()
+      .filter(note_group::Column::GroupId.is_in(groups))
+      .all(self.db)
+      .await?
+    {
+      let access = match group_access.remove(&share.note_id) {
+        Some(current) => strongest(current, share.access),
+        None => share.access,
+      };
+      group_access.insert(share.note_id, access);
+    }
+    shared_note_ids.extend(group_access.keys());
+
     let notes_with_owners: Vec<NoteWithOwner> = note::Entity::find()
       .filter(
         Condition::any()
           .add(note::Column::Owner.eq(user_id))
           .add(note::Column::Id.is_in(shared_note_ids)),
       )
+      .filter(note::Column::DeletedAt.is_null())
       .join(JoinType::LeftJoin, note::Relation::User.def())
       .order_by(note::Column::LastUpdated, Order::Desc)
       .into_partial_model()
@@ -224,6 +417,13 @@ impl<'db> NoteTable<'db> {
 
     let note_ids: Vec<Uuid> = notes_with_owners.iter().map(|note| note.id).collect();
     let shared_by_note = self.shared_with_for_notes(&note_ids).await?;
+    let groups_by_note = self.shared_groups_for_notes(&note_ids).await?;
+    let folders = NoteFolderTable::new(self.db)
+      .folders_for_notes(user_id, &note_ids)
+      .await?;
+    let tags = NoteTagTable::new(self.db);
+    let mut tags_by_note = tags.tags_for_notes(user_id, &note_ids).await?;
+    let favourites = tags.favourites(user_id, &note_ids).await?;
 
     notes_with_owners
       .into_iter()
@@ -240,7 +440,8 @@ impl<'db> NoteTable<'db> {
                 .iter()
                 .any(|s| s.id == user_id && s.access == NoteShareAccess::Edit)
             })
-            .unwrap_or(false);
+            .unwrap_or(false)
+          || group_access.get(&note.id) == Some(&NoteShareAccess::Edit);
 
         Ok(NoteInfo {
           id: note.id,
@@ -251,7 +452,11 @@ impl<'db> NoteTable<'db> {
             name: owner.name,
           },
           shared_with: shared_by_note.get(&note.id).cloned().unwrap_or_default(),
+          shared_with_groups: groups_by_note.get(&note.id).cloned().unwrap_or_default(),
           public_access: note.public_access,
+          folder: folders.get(&note.id).copied(),
+          tags: tags_by_note.remove(&note.id).unwrap_or_default(),
+          favourite: favourites.contains(&note.id),
           is_owner,
           can_edit,
           last_updated: note.last_updated,
@@ -290,6 +495,28 @@ impl<'db> NoteTable<'db> {
       })
       .collect();
 
+    let shared_with_groups = self
+      .shared_groups_for_notes(&[note_id])
+      .await?
+      .remove(&note_id)
+      .unwrap_or_default();
+
+    let folder = NoteFolderTable::new(self.db)
+      .folders_for_notes(user_id, &[note_id])
+      .await?
+      .remove(&note_id);
+
+    let note_tags = NoteTagTable::new(self.db);
+    let tags = note_tags
+      .tags_for_notes(user_id, &[note_id])
+      .await?
+      .remove(&note_id)
+      .unwrap_or_default();
+    let favourite = note_tags
+      .favourites(user_id, &[note_id])
+      .await?
+      .contains(&note_id);
+
     let is_owner = owner.id == user_id;
     let can_edit = is_owner || self.can_edit(user_id, note_id).await?;
 
@@ -302,7 +529,11 @@ impl<'db> NoteTable<'db> {
         name: owner.name,
       },
       shared_with,
+      shared_with_groups,
       public_access: note.public_access,
+      folder,
+      tags,
+      favourite,
       is_owner,
       can_edit,
       last_updated: note.last_updated,
@@ -311,6 +542,7 @@ impl<'db> NoteTable<'db> {
 
   pub async fn info_public(&self, note_id: Uuid) -> Result<Option<NoteInfoPublic>> {
     let res: Option<NoteWithOwner> = Note::find_by_id(note_id)
+      .filter(note::Column::DeletedAt.is_null())
       .join(JoinType::LeftJoin, note::Relation::User.def())
       .into_partial_model()
       .one(self.db)
@@ -352,26 +584,95 @@ impl<'db> NoteTable<'db> {
       owner: Set(owner),
       public_access: Set(None),
       last_updated: Set(Utc::now().naive_utc()),
+      deleted_at: Set(None),
     }
     .insert(&txn)
     .await?;
 
     txn.commit().await?;
+    NoteSearchTable::new(self.db)
+      .index_body(id, String::new())
+      .await?;
 
     Ok(id)
   }
 
+  /// Moves the note to the owner's trash.
+  pub async fn trash(&self, note_id: Uuid) -> Result<()> {
+    note::Entity::update_many()
+      .col_expr(
+        note::Column::DeletedAt,
+        Expr::value(Some(Utc::now().naive_utc())),
+      )
+      .filter(note::Column::Id.eq(note_id))
+      .exec(self.db)
+      .await?;
+
+    Ok(())
+  }
+
+  pub async fn restore(&self, note_id: Uuid) -> Result<()> {
+    note::Entity::update_many()
+      .col_expr(note::Column::DeletedAt, Expr::value(None::<DateTime>))
+      .filter(note::Column::Id.eq(note_id))
+      .exec(self.db)
+      .await?;
+
+    Ok(())
+  }
+
+  /// The owner's trash, most recently deleted first.
+  pub async fn list_trash(&self, owner: Uuid) -> Result<Vec<TrashedNoteInfo>> {
+    Ok(
+      note::Entity::find()
+        .filter(note::Column::Owner.eq(owner))
+        .filter(note::Column::DeletedAt.is_not_null())
+        .order_by(note::Column::DeletedAt, Order::Desc)
+        .all(self.db)
+        .await?
+        .into_iter()
+        .filter_map(|note| {
+          Some(TrashedNoteInfo {
+            id: note.id,
+            title: note.title,
+            preview: note.preview,
+            deleted_at: note.deleted_at?,
+          })
+        })
+        .collect(),
+    )
+  }
+
+  /// Trashed notes deleted before `cutoff` together with their owner.
+  pub async fn trashed_before(&self, cutoff: DateTime) -> Result<Vec<(Uuid, Uuid)>> {
+    Ok(
+      Note::find()
+        .select_only()
+        .column(note::Column::Id)
+        .column(note::Column::Owner)
+        .filter(note::Column::DeletedAt.lt(cutoff))
+        .into_tuple()
+        .all(self.db)
+        .await?,
+    )
+  }
+
+  /// Removes the note for good, snapshot files have to be removed first.
   pub async fn delete(&self, note_id: Uuid) -> Result<()> {
     note::Entity::delete_by_id(note_id).exec(self.db).await?;
+    NoteSearchTable::new(self.db).remove(note_id).await?;
     Ok(())
   }
 
   pub async fn edit_title(&self, note_id: Uuid, title: String) -> Result<()> {
     note::Entity::update_many()
-      .col_expr(note::Column::Title, Expr::value(title))
+      .col_expr(note::Column::Title, Expr::value(title.clone()))
       .filter(note::Column::Id.eq(note_id))
       .exec(self.db)
       .await?;
+    NoteSearchTable::new(self.db)
+      .set_title(note_id, title)
+      .await?;
 
     Ok(())
   }
@@ -388,6 +689,31 @@ impl<'db> NoteTable<'db> {
     Ok(())
   }
 
+  pub async fn set_shared_groups(
+    &self,
+    note_id: Uuid,
+    shared_with: Vec<NoteGroupShareEntry>,
+  ) -> Result<()> {
+    let txn = self.db.begin().await?;
+
+    note_group::Entity::delete_many()
+      .filter(note_group::Column::NoteId.eq(note_id))
+      .exec(&txn)
+      .await?;
+
+    if !shared_with.is_empty() {
+      let models = shared_with.into_iter().map(|s| note_group::ActiveModel {
+        note_id: Set(note_id),
+        group_id: Set(s.group_id),
+        access: Set(s.access),
+      });
+      note_group::Entity::insert_many(models).exec(&txn).await?;
+    }
+
+    txn.commit().await?;
+    Ok(())
+  }
+
   pub async fn transfer_owner(
     &self,
     note_id: Uuid,
@@ -482,6 +808,7 @@ impl<'db> NoteTable<'db> {
 
   pub async fn get_public_access(&self, note_id: Uuid) -> Result<Option<NoteShareAccess>> {
     let Some(access) = Note::find_by_id(note_id)
+      .filter(note::Column::DeletedAt.is_null())
       .select_only()
       .column(note::Column::PublicAccess)
       .into_tuple()
@@ -536,6 +863,10 @@ impl<'db> NoteTable<'db> {
   }
 }
 
+fn strongest(a: NoteShareAccess, b: NoteShareAccess) -> NoteShareAccess {
+  if a == NoteShareAccess::Edit { a } else { b }
+}
+
 async fn replace_shared_users<C: ConnectionTrait>(
   conn: &C,
   note_id: Uuid,
@@ -577,8 +908,8 @@ mod test {
 
   use crate::db::{
     DBTrait,
-    notes::NoteShareEntry,
-    test::{insert_user, test_db},
+    notes::{NoteGroupShareEntry, NoteShareEntry},
+    test::{add_user_to_group, insert_group, insert_user, test_db},
   };
   use uuid::Uuid;
 
@@ -627,6 +958,56 @@ mod test {
     assert_eq!(shared_users, vec![edit(shared)]);
   }
 
+  #[tokio::test]
+  async fn group_share_follows_membership() {
+    let db = test_db().await;
+    let owner = insert_user(&db, "owner", "owner@x.com").await;
+    let member = insert_user(&db, "member", "member@x.com").await;
+    let nested = insert_user(&db, "nested", "nested@x.com").await;
+    let oncall = insert_group(&db, "oncall").await;
+    let primary = insert_group(&db, "primary").await;
+    add_user_to_group(&db, oncall, member).await;
+    add_user_to_group(&db, primary, nested).await;
+    db.group_ext()
+      .set_parents(primary, vec![oncall])
+      .await
+      .unwrap();
+
+    let id = db.notes().create(owner, "Runbook".into()).await.unwrap();
+    assert!(!db.notes().has_access(nested, id).await.unwrap());
+
+    db.notes()
+      .set_shared_groups(
+        id,
+        vec![NoteGroupShareEntry {
+          group_id: oncall,
+          access: NoteShareAccess::Edit,
+        }],
+      )
+      .await
+      .unwrap();
+    // a direct view share does not lower the access granted by the group
+    db.notes()
+      .set_shared_users(id, owner, vec![view(member)])
+      .await
+      .unwrap();
+
+    assert!(db.notes().can_edit(nested, id).await.unwrap());
+    assert!(db.notes().can_edit(member, id).await.unwrap());
+    let mut expected = vec![member, nested];
+    expected.sort();
+    assert_eq!(db.notes().shared_user_ids(id).await.unwrap(), expected);
+
+    let listed = db.notes().list_for_user(nested).await.unwrap();
+    assert_eq!(listed.len(), 1);
+    assert!(listed[0].can_edit);
+    assert_eq!(listed[0].shared_with_groups[0].id, oncall);
+
+    db.notes().set_shared_groups(id, vec![]).await.unwrap();
+    assert!(!db.notes().has_access(nested, id).await.unwrap());
+    assert!(db.notes().list_for_user(nested).await.unwrap().is_empty());
+  }
+
   #[tokio::test]
   async fn create_with_empty_shared_has_no_shared_users() {
     let db = test_db().await;
@@ -966,6 +1347,45 @@ mod test {
     db.notes().delete(Uuid::new_v4()).await.unwrap();
   }
 
+  #[tokio::test]
+  async fn trashed_notes_are_hidden_until_restored() {
+    let db = test_db().await;
+    let owner = insert_user(&db, "owner", "owner@x.com").await;
+    let shared = insert_user(&db, "shared", "shared@x.com").await;
+    let id = db.notes().create(owner, "T".into()).await.unwrap();
+    db.notes()
+      .set_shared_users(id, owner, vec![edit(shared)])
+      .await
+      .unwrap();
+    db.notes()
+      .set_public_access(id, Some(NoteShareAccess::View))
+      .await
+      .unwrap();
+
+    db.notes().trash(id).await.unwrap();
+    assert!(db.notes().is_trashed(id).await.unwrap());
+    assert!(db.notes().is_owner(owner, id).await.unwrap());
+    assert!(!db.notes().has_access(owner, id).await.unwrap());
+    assert!(!db.notes().can_edit(shared, id).await.unwrap());
+    assert_eq!(db.notes().get_public_access(id).await.unwrap(), None);
+    assert_eq!(db.notes().count_owned(owner).await.unwrap(), 0);
+    assert!(db.notes().list_for_user(shared).await.unwrap().is_empty());
+    assert_eq!(db.notes().list_trash(owner).await.unwrap()[0].id, id);
+    assert!(db.notes().list_trash(shared).await.unwrap().is_empty());
+
+    let later = chrono::Utc::now().naive_utc() + chrono::TimeDelta::seconds(1);
+    assert_eq!(
+      db.notes().trashed_before(later).await.unwrap(),
+      vec![(id, owner)]
+    );
+
+    db.notes().restore(id).await.unwrap();
+    assert!(db.notes().has_access(shared, id).await.unwrap());
+    assert_eq!(db.notes().count_owned(owner).await.unwrap(), 1);
+    assert!(db.notes().list_trash(owner).await.unwrap().is_empty());
+    assert!(db.notes().trashed_before(later).await.unwrap().is_empty());
+  }
+
   #[tokio::test]
   async fn public_access_get_set_and_clear() {
     let db = test_db().await;
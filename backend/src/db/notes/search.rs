@@ -0,0 +1,392 @@
+use centaurus::error::Result;
+use schemars::JsonSchema;
+use sea_orm::{
+  ConnectionTrait, DatabaseBackend, DatabaseConnection, FromQueryResult, Statement, Value,
+};
+use serde::Serialize;
+use uuid::Uuid;
+
+/// Highlight markers used inside the database, replaced after escaping so note
+/// text can never inject markup into the snippet.
+const MARK_START: char = '\u{2}';
+const MARK_END: char = '\u{3}';
+const MAX_TERMS: usize = 16;
+/// Indexed bytes of a note body, keeps the PostgreSQL `tsvector` well below its
+/// 1 MB limit even for text made of short unique words.
+const MAX_BODY_BYTES: usize = 256 * 1024;
+
+/// Full-text index over title and rendered content of every note, an FTS5
+/// table on SQLite and a `tsvector` column on PostgreSQL.
+pub struct NoteSearchTable<'db> {
+  db: &'db DatabaseConnection,
+}
+
+#[derive(Serialize, JsonSchema, Debug)]
+pub struct NoteSearchResult {
+  pub id: Uuid,
+  pub title: String,
+  /// Excerpt around the match, HTML escaped with matches wrapped in `<mark>`.
+  pub snippet: String,
+  /// Higher is more relevant.
+  pub rank: f64,
+}
+
+#[derive(FromQueryResult)]
+struct NoteSearchRow {
+  note_id: Uuid,
+  title: String,
+  snippet: String,
+  rank: f64,
+}
+
+impl<'db> NoteSearchTable<'db> {
+  pub fn new(db: &'db DatabaseConnection) -> Self {
+    Self { db }
+  }
+
+  /// Replaces the indexed text of the note, the title is taken from the note.
+  pub async fn index_body(&self, note_id: Uuid, mut body: String) -> Result<()> {
+    self.remove(note_id).await?;
+    self.clear_pending(note_id).await?;
+    truncate_body(&mut body);
+
+    let backend = self.db.get_database_backend();
+    let sql = format!(
+      r#"INSERT INTO "note_search" ("note_id", "title", "body") SELECT "id", "title", {} FROM "note" WHERE "id" = {}"#,
+      placeholder(backend, 1),
+      placeholder(backend, 2),
+    );
+    self
+      .db
+      .execute_raw(Statement::from_sql_and_values(
+        backend,
+        sql,
+        [body.into(), note_id.into()],
+      ))
+      .await?;
+
+    Ok(())
+  }
+
+  pub async fn set_title(&self, note_id: Uuid, title: String) -> Result<()> {
+    let backend = self.db.get_database_backend();
+    let sql = format!(
+      r#"UPDATE "note_search" SET "title" = {} WHERE "note_id" = {}"#,
+      placeholder(backend, 1),
+      placeholder(backend, 2),
+    );
+    self
+      .db
+      .execute_raw(Statement::from_sql_and_values(
+        backend,
+        sql,
+        [title.into(), note_id.into()],
+      ))
+      .await?;
+
+    Ok(())
+  }
+
+  /// The FTS5 table has no foreign key, so rows are removed explicitly.
+  pub async fn remove(&self, note_id: Uuid) -> Result<()> {
+    let backend = self.db.get_database_backend();
+    let sql = format!(
+      r#"DELETE FROM "note_search" WHERE "note_id" = {}"#,
+      placeholder(backend, 1),
+    );
+    self
+      .db
+      .execute_raw(Statement::from_sql_and_values(
+        backend,
+        sql,
+        [note_id.into()],
+      ))
+      .await?;
+
+    Ok(())
+  }
+
+  /// Notes whose full text has not been indexed yet.
+  pub async fn pending(&self, limit: u64) -> Result<Vec<Uuid>> {
+    let backend = self.db.get_database_backend();
+    let sql = format!(
+      r#"SELECT "note_id" FROM "note_search_pending" LIMIT {}"#,
+      placeholder(backend, 1),
+    );
+    let rows = self
+      .db
+      .query_all_raw(Statement::from_sql_and_values(backend, sql, [limit.into()]))
+      .await?;
+
+    Ok(
+      rows
+        .iter()
+        .map(|row| row.try_get("", "note_id"))
+        .collect::<std::result::Result<_, _>>()?,
+    )
+  }
+
+  pub async fn clear_pending(&self, note_id: Uuid) -> Result<()> {
+    let backend = self.db.get_database_backend();
+    let sql = format!(
+      r#"DELETE FROM "note_search_pending" WHERE "note_id" = {}"#,
+      placeholder(backend, 1),
+    );
+    self
+      .db
+      .execute_raw(Statement::from_sql_and_values(
+        backend,
+        sql,
+        [note_id.into()],
+      ))
+      .await?;
+
+    Ok(())
+  }
+
+  /// Ranked matches among `note_ids`, callers pass the notes the user may
+  /// open. Every term has to match, the last one also as a prefix.
+  pub async fn search(
+    &self,
+    query: &str,
+    note_ids: &[Uuid],
+    limit: u64,
+  ) -> Result<Vec<NoteSearchResult>> {
+    let terms = search_terms(query);
+    if terms.is_empty() || note_ids.is_empty() {
+      return Ok(Vec::new());
+    }
+
+    let backend = self.db.get_database_backend();
+    let ids = (0..note_ids.len())
+      .map(|i| placeholder(backend, i + 2))
+      .collect::<Vec<_>>()
+      .join(", ");
+    let limit_param = placeholder(backend, note_ids.len() + 2);
+
+    let (sql, query) = match backend {
+      DatabaseBackend::Postgres => (
+        format!(
+          r#"SELECT "note_id", "title",
+            ts_headline('simple', "body", q, 'StartSel={MARK_START}, StopSel={MARK_END}, MaxWords=24, MinWords=8') AS "snippet",
+            ts_rank("document", q)::float8 AS "rank"
+          FROM "note_search", to_tsquery('simple', $1) q
+          WHERE "document" @@ q AND "note_id" IN ({ids})
+          ORDER BY "rank" DESC
+          LIMIT {limit_param}"#
+        ),
+        postgres_query(&terms),
+      ),
+      DatabaseBackend::Sqlite => (
+        format!(
+          r#"SELECT "note_id", "title",
+            snippet("note_search", -1, '{MARK_START}', '{MARK_END}', '…', 24) AS "snippet",
+            -bm25("note_search", 0.0, 10.0, 1.0) AS "rank"
+          FROM "note_search"
+          WHERE "note_search" MATCH ? AND "note_id" IN ({ids})
+          ORDER BY "rank" DESC
+          LIMIT {limit_param}"#
+        ),
+        sqlite_query(&terms),
+      ),
+      backend => panic!("unsupported database backend: {backend:?}"),
+    };
+
+    let mut values: Vec<Value> = vec![query.into()];
+    values.extend(note_ids.iter().map(|id| Value::from(*id)));
+    values.push(limit.into());
+
+    let rows =
+      NoteSearchRow::find_by_statement(Statement::from_sql_and_values(backend, sql, values))
+        .all(self.db)
+        .await?;
+
+    Ok(
+      rows
+        .into_iter()
+        .map(|row| NoteSearchResult {
+          id: row.note_id,
+          title: row.title,
+          snippet: highlight(&row.snippet),
+          rank: row.rank,
+        })
+        .collect(),
+    )
+  }
+}
+
+fn truncate_body(body: &mut String) {
+  if body.len() > MAX_BODY_BYTES {
+    let mut end = MAX_BODY_BYTES;
+    while !body.is_char_boundary(end) {
+      end -= 1;
+    }
+    body.truncate(end);
+  }
+}
+
+fn placeholder(backend: DatabaseBackend, index: usize) -> String {
+  match backend {
+    DatabaseBackend::Postgres => format!("${index}"),
+    _ => "?".into(),
+  }
+}
+
+/// Splits the user input into lowercase alphanumeric terms, anything else is
+/// dropped so the input can never be interpreted as query syntax.
+fn search_terms(query: &str) -> Vec<String> {
+  query
+    .split(|c: char| !c.is_alphanumeric())
+    .filter(|term| !term.is_empty())
+    .map(str::to_lowercase)
+    .take(MAX_TERMS)
+    .collect()
+}
+
+fn sqlite_query(terms: &[String]) -> String {
+  let last = terms.len() - 1;
+  terms
+    .iter()
+    .enumerate()
+    .map(|(i, term)| {
+      if i == last {
+        format!("\"{term}\"*")
+      } else {
+        format!("\"{term}\"")
+      }
+    })
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+fn postgres_query(terms: &[String]) -> String {
+  let last = terms.len() - 1;
+  terms
+    .iter()
+    .enumerate()
+    .map(|(i, term)| {
+      if i == last {
+        format!("{term}:*")
+      } else {
+        term.clone()
+      }
+    })
+    .collect::<Vec<_>>()
+    .join(" & ")
+}
+
+fn highlight(snippet: &str) -> String {
+  let mut out = String::with_capacity(snippet.len());
+  for c in snippet.chars() {
+    match c {
+      MARK_START => out.push_str("<mark>"),
+      MARK_END => out.push_str("</mark>"),
+      '&' => out.push_str("&amp;"),
+      '<' => out.push_str("&lt;"),
+      '>' => out.push_str("&gt;"),
+      '"' => out.push_str("&quot;"),
+      '\'' => out.push_str("&#39;"),
+      c => out.push(c),
+    }
+  }
+  out
+}
+
+#[cfg(test)]
+mod test {
+  use centaurus::db::tables::ConnectionExt;
+
+  use super::{
+    MAX_BODY_BYTES, highlight, postgres_query, search_terms, sqlite_query, truncate_body,
+  };
+  use crate::db::{
+    DBTrait,
+    test::{insert_user, test_db},
+  };
+
+  #[test]
+  fn query_syntax_is_stripped() {
+    let terms = search_terms(r#"Foo* OR "bar"-baz"#);
+    assert_eq!(terms, ["foo", "or", "bar", "baz"]);
+    assert_eq!(sqlite_query(&terms), r#""foo" "or" "bar" "baz"*"#);
+    assert_eq!(postgres_query(&terms), "foo & or & bar & baz:*");
+  }
+
+  #[test]
+  fn snippets_are_escaped() {
+    assert_eq!(
+      highlight("<b>\u{2}x\u{3}</b>"),
+      "&lt;b&gt;<mark>x</mark>&lt;/b&gt;"
+    );
+  }
+
+  #[test]
+  fn long_bodies_are_truncated_on_a_char_boundary() {
+    let mut body = "ä".repeat(MAX_BODY_BYTES);
+    truncate_body(&mut body);
+    assert_eq!(body.len(), MAX_BODY_BYTES);
+    body.push('x');
+    truncate_body(&mut body);
+    assert_eq!(body.len(), MAX_BODY_BYTES);
+
+    let mut short = "short".to_string();
+    truncate_body(&mut short);
+    assert_eq!(short, "short");
+  }
+
+  #[tokio::test]
+  async fn deleting_the_owner_removes_indexed_notes() {
+    let db = test_db().await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let other = insert_user(&db, "o", "o@x.com").await;
+    let note = db.notes().create(user, "Diary".into()).await.unwrap();
+    let kept = db.notes().create(other, "Diary".into()).await.unwrap();
+    db.note_search()
+      .index_body(note, "private words".into())
+      .await
+      .unwrap();
+
+    db.user().delete_user(user).await.unwrap();
+
+    let hits = db
+      .note_search()
+      .search("diary", &[note, kept], 10)
+      .await
+      .unwrap();
+    assert_eq!(hits.iter().map(|hit| hit.id).collect::<Vec<_>>(), [kept]);
+    assert!(
+      db.note_search()
+        .search("private", &[note], 10)
+        .await
+        .unwrap()
+        .is_empty()
+    );
+  }
+
+  #[tokio::test]
+  async fn search_ranks_and_restricts_to_given_notes() {
+    let db = test_db().await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let rust = db.notes().create(user, "Rust notes".into()).await.unwrap();
+    let mention = db.notes().create(user, "Misc".into()).await.unwrap();
+    let hidden = db.notes().create(user, "Rust".into()).await.unwrap();
+    db.note_search()
+      .index_body(mention, "some text about rust and more".into())
+      .await
+      .unwrap();
+
+    let hits = db
+      .note_search()
+      .search("rus", &[rust, mention], 10)
+      .await
+      .unwrap();
+    let ids: Vec<_> = hits.iter().map(|hit| hit.id).collect();
+    assert_eq!(ids, [rust, mention]);
+    assert!(hits[1].snippet.contains("<mark>rust</mark>"));
+    assert!(!ids.contains(&hidden));
+
+    db.notes().delete(rust).await.unwrap();
+    let hits = db.note_search().search("rust", &[rust], 10).await.unwrap();
+    assert!(hits.is_empty());
+  }
+}
@@ -4,76 +4,65 @@ use entity::{note, note_snapshot};
 use schemars::JsonSchema;
 use sea_orm::{
   ActiveValue::Set,
-  DatabaseBackend, EntityTrait, ExprTrait, FromQueryResult, QueryOrder, QuerySelect,
+  Condition, DatabaseBackend, EntityTrait, ExprTrait, FromQueryResult, QueryOrder, QuerySelect,
   prelude::*,
   sea_query::{Alias, Expr, Func, Order, OverStatement, Query, WindowStatement},
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 pub struct NoteSnapshotTable<'db> {
   db: &'db DatabaseConnection,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum RetentionTier {
-  H2ToH4,
-  H4ToD1,
-  D1ToD3,
-  D3ToW1,
-  W1ToD30,
-  D30ToY1,
-  Y1Plus,
+/// Snapshots at least `after_mins` old keep one per `keep_every_mins` window,
+/// until the next tier of the table takes over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct RetentionTier {
+  pub after_mins: i64,
+  pub keep_every_mins: i64,
 }
 
 impl RetentionTier {
-  pub fn all() -> &'static [Self] {
-    &[
-      Self::H2ToH4,
-      Self::H4ToD1,
-      Self::D1ToD3,
-      Self::D3ToW1,
-      Self::W1ToD30,
-      Self::D30ToY1,
-      Self::Y1Plus,
+  /// Table used unless an instance or a note configures its own.
+  pub fn defaults() -> Vec<Self> {
+    [
+      (2 * 60, 30),
+      (4 * 60, 60),
+      (24 * 60, 6 * 60),
+      (3 * 24 * 60, 24 * 60),
+      (7 * 24 * 60, 7 * 24 * 60),
+      (30 * 24 * 60, 30 * 24 * 60),
+      (365 * 24 * 60, 365 * 24 * 60),
     ]
+    .into_iter()
+    .map(|(after_mins, keep_every_mins)| Self {
+      after_mins,
+      keep_every_mins,
+    })
+    .collect()
   }
 
-  fn bucket_secs(self) -> i64 {
-    match self {
-      Self::H2ToH4 => 1800,
-      Self::H4ToD1 => 3600,
-      Self::D1ToD3 => 21_600,
-      Self::D3ToW1 => 86_400,
-      Self::W1ToD30 => 604_800,
-      Self::D30ToY1 => 2_592_000,
-      Self::Y1Plus => 31_536_000,
-    }
+  /// Pairs every tier of a sorted table with the age in minutes where the
+  /// next one starts.
+  pub fn windows(tiers: &[Self]) -> impl Iterator<Item = (Self, Option<i64>)> + '_ {
+    tiers
+      .iter()
+      .enumerate()
+      .map(|(i, tier)| (*tier, tiers.get(i + 1).map(|next| next.after_mins)))
   }
 
-  fn newest_inclusive(self, now: DateTime<Utc>) -> DateTime<Utc> {
-    match self {
-      Self::H2ToH4 => now - TimeDelta::hours(2),
-      Self::H4ToD1 => now - TimeDelta::hours(4),
-      Self::D1ToD3 => now - TimeDelta::days(1),
-      Self::D3ToW1 => now - TimeDelta::days(3),
-      Self::W1ToD30 => now - TimeDelta::days(7),
-      Self::D30ToY1 => now - TimeDelta::days(30),
-      Self::Y1Plus => now - TimeDelta::days(365),
-    }
+  fn bucket_secs(self) -> Option<i64> {
+    self.keep_every_mins.checked_mul(60)
   }
+}
 
-  fn oldest_exclusive(self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
-    match self {
-      Self::H2ToH4 => Some(now - TimeDelta::hours(4)),
-      Self::H4ToD1 => Some(now - TimeDelta::days(1)),
-      Self::D1ToD3 => Some(now - TimeDelta::days(3)),
-      Self::D3ToW1 => Some(now - TimeDelta::days(7)),
-      Self::W1ToD30 => Some(now - TimeDelta::days(30)),
-      Self::D30ToY1 => Some(now - TimeDelta::days(365)),
-      Self::Y1Plus => None,
-    }
-  }
+/// Which notes an eviction pass covers, notes with their own retention table
+/// are evicted separately.
+#[derive(Debug, Clone, Copy)]
+pub enum EvictScope<'a> {
+  AllExcept(&'a [Uuid]),
+  Note(Uuid),
 }
 
 #[derive(Debug, FromQueryResult)]
@@ -83,6 +72,11 @@ pub struct SnapshotEvictRow {
   pub owner: Uuid,
 }
 
+/// Point in time `mins` before `now`, `None` when it is out of range.
+fn age_cutoff(now: DateTime<Utc>, mins: i64) -> Option<DateTime<Utc>> {
+  now.checked_sub_signed(TimeDelta::try_minutes(mins)?)
+}
+
 fn bucket_partition_expr(backend: DatabaseBackend, bucket_secs: i64) -> String {
   let table = note_snapshot::Entity.table_name();
   let col = note_snapshot::Column::CreatedAt.as_str();
@@ -104,6 +98,23 @@ pub struct NoteSnapshotInfo {
   pub note_id: Uuid,
   pub preview: String,
   pub created_at: sea_orm::prelude::DateTime,
+  /// Set for snapshots created or named by a user.
+  pub label: Option<String>,
+  /// Pinned snapshots are never thinned out by retention.
+  pub pinned: bool,
+}
+
+impl From<note_snapshot::Model> for NoteSnapshotInfo {
+  fn from(row: note_snapshot::Model) -> Self {
+    Self {
+      id: row.id,
+      note_id: row.note_id,
+      preview: row.preview,
+      created_at: row.created_at,
+      label: row.label,
+      pinned: row.pinned,
+    }
+  }
 }
 
 #[derive(Serialize, JsonSchema)]
@@ -124,6 +135,8 @@ impl<'db> NoteSnapshotTable<'db> {
       note_id: Set(note_id),
       preview: Set(preview),
       created_at: Set(Utc::now().naive_utc()),
+      label: Set(None),
+      pinned: Set(false),
     };
 
     let model = snapshot.insert(self.db).await?;
@@ -131,6 +144,61 @@ impl<'db> NoteSnapshotTable<'db> {
     Ok(model.id)
   }
 
+  /// Snapshot taken on request of a user instead of by the save heuristic.
+  pub async fn create_labelled(
+    &self,
+    note_id: Uuid,
+    preview: String,
+    label: String,
+    pinned: bool,
+  ) -> Result<NoteSnapshotInfo> {
+    let snapshot = note_snapshot::ActiveModel {
+      id: Set(Uuid::now_v7()),
+      note_id: Set(note_id),
+      preview: Set(preview),
+      created_at: Set(Utc::now().naive_utc()),
+      label: Set(Some(label)),
+      pinned: Set(pinned),
+    };
+
+    Ok(snapshot.insert(self.db).await?.into())
+  }
+
+  pub async fn set_label(&self, snapshot_id: Uuid, label: Option<String>) -> Result<()> {
+    note_snapshot::Entity::update_many()
+      .col_expr(note_snapshot::Column::Label, Expr::value(label))
+      .filter(note_snapshot::Column::Id.eq(snapshot_id))
+      .exec(self.db)
+      .await?;
+
+    Ok(())
+  }
+
+  pub async fn set_pinned(&self, snapshot_id: Uuid, pinned: bool) -> Result<()> {
+    note_snapshot::Entity::update_many()
+      .col_expr(note_snapshot::Column::Pinned, Expr::value(pinned))
+      .filter(note_snapshot::Column::Id.eq(snapshot_id))
+      .exec(self.db)
+      .await?;
+
+    Ok(())
+  }
+
+  /// Snapshots of the note that are pinned or labelled.
+  pub async fn count_kept(&self, note_id: Uuid) -> Result<u64> {
+    Ok(
+      note_snapshot::Entity::find()
+        .filter(note_snapshot::Column::NoteId.eq(note_id))
+        .filter(
+          Condition::any()
+            .add(note_snapshot::Column::Pinned.eq(true))
+            .add(note_snapshot::Column::Label.is_not_null()),
+        )
+        .count(self.db)
+        .await?,
+    )
+  }
+
   pub async fn list_for_note(&self, note_id: Uuid) -> Result<Vec<NoteSnapshotInfo>> {
     let rows = note_snapshot::Entity::find()
       .filter(note_snapshot::Column::NoteId.eq(note_id))
@@ -138,17 +206,7 @@ impl<'db> NoteSnapshotTable<'db> {
       .all(self.db)
       .await?;
 
-    Ok(
-      rows
-        .into_iter()
-        .map(|row| NoteSnapshotInfo {
-          id: row.id,
-          note_id: row.note_id,
-          preview: row.preview,
-          created_at: row.created_at,
-        })
-        .collect(),
-    )
+    Ok(rows.into_iter().map(NoteSnapshotInfo::from).collect())
   }
 
   pub async fn find(&self, snapshot_id: Uuid) -> Result<Option<note_snapshot::Model>> {
@@ -199,13 +257,22 @@ impl<'db> NoteSnapshotTable<'db> {
     Ok(snapshot.map(|s| s.created_at.and_utc()))
   }
 
+  /// Snapshots in the age window of `tier` that are not the newest of their
+  /// bucket. `until_mins` is the age where the next tier starts.
   pub async fn ids_to_evict_for_tier(
     &self,
     tier: RetentionTier,
+    until_mins: Option<i64>,
     now: DateTime<Utc>,
+    scope: EvictScope<'_>,
   ) -> Result<Vec<SnapshotEvictRow>> {
+    // a tier starting or thinning beyond the representable time range has
+    // nothing to evict
+    let (Some(bucket_secs), Some(after)) = (tier.bucket_secs(), age_cutoff(now, tier.after_mins))
+    else {
+      return Ok(Vec::new());
+    };
     let backend = self.db.get_database_backend();
-    let bucket_secs = tier.bucket_secs();
     let bucket_expr = bucket_partition_expr(backend, bucket_secs);
 
     let mut window = WindowStatement::new();
@@ -246,10 +313,21 @@ impl<'db> NoteSnapshotTable<'db> {
         Expr::col((note_snapshot::Entity, note_snapshot::Column::NoteId))
           .equals((note::Entity, note::Column::Id)),
       )
-      .and_where(note_snapshot::Column::CreatedAt.lte(tier.newest_inclusive(now).naive_utc()));
+      .and_where(note_snapshot::Column::CreatedAt.lte(after.naive_utc()))
+      // pinned snapshots are neither evicted nor count as the one kept per bucket
+      .and_where(note_snapshot::Column::Pinned.eq(false));
 
-    if let Some(oldest_exclusive) = tier.oldest_exclusive(now) {
-      inner.and_where(note_snapshot::Column::CreatedAt.gt(oldest_exclusive.naive_utc()));
+    if let Some(until) = until_mins.and_then(|mins| age_cutoff(now, mins)) {
+      inner.and_where(note_snapshot::Column::CreatedAt.gt(until.naive_utc()));
+    }
+    match scope {
+      EvictScope::AllExcept([]) => {}
+      EvictScope::AllExcept(notes) => {
+        inner.and_where(note_snapshot::Column::NoteId.is_not_in(notes.to_vec()));
+      }
+      EvictScope::Note(note_id) => {
+        inner.and_where(note_snapshot::Column::NoteId.eq(note_id));
+      }
     }
 
     let ranked = Alias::new("ranked");
@@ -293,6 +371,8 @@ impl<'db> NoteSnapshotTable<'db> {
       note_id: Set(note_id),
       preview: Set(preview),
       created_at: Set(created_at.naive_utc()),
+      label: Set(None),
+      pinned: Set(false),
     };
 
     let model = snapshot.insert(self.db).await?;
@@ -305,7 +385,7 @@ impl<'db> NoteSnapshotTable<'db> {
 mod test {
   use crate::db::{
     DBTrait,
-    notes::snapshot::RetentionTier,
+    notes::snapshot::{EvictScope, RetentionTier, SnapshotEvictRow},
     test::{insert_user, test_db},
   };
   use chrono::Utc;
@@ -469,15 +549,38 @@ mod test {
       .unwrap()
   }
 
-  async fn evict_ids(
+  // indexes into the default retention table
+  const H2_TO_H4: usize = 0;
+  const H4_TO_D1: usize = 1;
+  const D1_TO_D3: usize = 2;
+  const D3_TO_W1: usize = 3;
+  const W1_TO_D30: usize = 4;
+  const D30_TO_Y1: usize = 5;
+  const Y1_PLUS: usize = 6;
+  const ALL_TIERS: [usize; 7] = [
+    H2_TO_H4, H4_TO_D1, D1_TO_D3, D3_TO_W1, W1_TO_D30, D30_TO_Y1, Y1_PLUS,
+  ];
+
+  async fn evict_rows(
     db: &centaurus::db::init::Connection,
-    tier: RetentionTier,
+    tier: usize,
     now: chrono::DateTime<Utc>,
-  ) -> Vec<Uuid> {
+  ) -> Vec<SnapshotEvictRow> {
+    let defaults = RetentionTier::defaults();
+    let (tier, until_mins) = RetentionTier::windows(&defaults).nth(tier).unwrap();
     db.note_snapshot()
-      .ids_to_evict_for_tier(tier, now)
+      .ids_to_evict_for_tier(tier, until_mins, now, EvictScope::AllExcept(&[]))
       .await
       .unwrap()
+  }
+
+  async fn evict_ids(
+    db: &centaurus::db::init::Connection,
+    tier: usize,
+    now: chrono::DateTime<Utc>,
+  ) -> Vec<Uuid> {
+    evict_rows(db, tier, now)
+      .await
       .into_iter()
       .map(|row| row.id)
       .collect()
@@ -507,17 +610,10 @@ mod test {
     )
     .await;
 
-    let evicted = evict_ids(&db, RetentionTier::H2ToH4, now).await;
+    let evicted = evict_ids(&db, H2_TO_H4, now).await;
 
     assert_eq!(evicted, vec![dropped]);
-    assert_eq!(
-      db.note_snapshot()
-        .ids_to_evict_for_tier(RetentionTier::H2ToH4, now)
-        .await
-        .unwrap()[0]
-        .owner,
-      owner
-    );
+    assert_eq!(evict_rows(&db, H2_TO_H4, now).await[0].owner, owner);
     assert!(db.note_snapshot().find(kept).await.unwrap().is_some());
   }
 
@@ -538,10 +634,7 @@ mod test {
     )
     .await;
 
-    assert_eq!(
-      evict_ids(&db, RetentionTier::H4ToD1, now).await,
-      vec![dropped]
-    );
+    assert_eq!(evict_ids(&db, H4_TO_D1, now).await, vec![dropped]);
     assert!(db.note_snapshot().find(kept).await.unwrap().is_some());
   }
 
@@ -562,10 +655,7 @@ mod test {
     )
     .await;
 
-    assert_eq!(
-      evict_ids(&db, RetentionTier::D3ToW1, now).await,
-      vec![dropped]
-    );
+    assert_eq!(evict_ids(&db, D3_TO_W1, now).await, vec![dropped]);
     assert!(db.note_snapshot().find(kept).await.unwrap().is_some());
   }
 
@@ -586,10 +676,7 @@ mod test {
     )
     .await;
 
-    assert_eq!(
-      evict_ids(&db, RetentionTier::W1ToD30, now).await,
-      vec![dropped]
-    );
+    assert_eq!(evict_ids(&db, W1_TO_D30, now).await, vec![dropped]);
     assert!(db.note_snapshot().find(kept).await.unwrap().is_some());
   }
 
@@ -610,13 +697,115 @@ mod test {
     )
     .await;
 
-    assert_eq!(
-      evict_ids(&db, RetentionTier::D30ToY1, now).await,
-      vec![dropped]
-    );
+    assert_eq!(evict_ids(&db, D30_TO_Y1, now).await, vec![dropped]);
     assert!(db.note_snapshot().find(kept).await.unwrap().is_some());
   }
 
+  #[tokio::test]
+  async fn ids_to_evict_skips_pinned_snapshots() {
+    let db = test_db().await;
+    let owner = insert_user(&db, "owner", "owner@x.com").await;
+    let note = db.notes().create(owner, "T".into()).await.unwrap();
+    let now = fixed_now();
+
+    let days = chrono::TimeDelta::days;
+    let pinned = snapshot_at(&db, note, now, days(60), "pinned").await;
+    let dropped = snapshot_at(
+      &db,
+      note,
+      now,
+      days(60) - chrono::TimeDelta::hours(12),
+      "old",
+    )
+    .await;
+    let kept = snapshot_at(&db, note, now, days(60) - days(1), "new").await;
+    db.note_snapshot().set_pinned(pinned, true).await.unwrap();
+    db.note_snapshot()
+      .set_label(pinned, Some("Release".into()))
+      .await
+      .unwrap();
+
+    assert_eq!(evict_ids(&db, D30_TO_Y1, now).await, vec![dropped]);
+    let listed = db.note_snapshot().list_for_note(note).await.unwrap();
+    let info = listed.iter().find(|info| info.id == pinned).unwrap();
+    assert!(info.pinned);
+    assert_eq!(info.label.as_deref(), Some("Release"));
+    assert!(listed.iter().any(|info| info.id == kept && !info.pinned));
+  }
+
+  #[tokio::test]
+  async fn ids_to_evict_scope_limits_notes() {
+    let db = test_db().await;
+    let owner = insert_user(&db, "owner", "owner@x.com").await;
+    let note_a = db.notes().create(owner, "A".into()).await.unwrap();
+    let note_b = db.notes().create(owner, "B".into()).await.unwrap();
+    let now = fixed_now();
+    let offset = chrono::TimeDelta::hours(2) + chrono::TimeDelta::minutes(15);
+    let older = chrono::TimeDelta::minutes(5);
+    snapshot_at(&db, note_a, now, offset, "a-new").await;
+    let a_dropped = snapshot_at(&db, note_a, now, offset + older, "a-old").await;
+    snapshot_at(&db, note_b, now, offset, "b-new").await;
+    let b_dropped = snapshot_at(&db, note_b, now, offset + older, "b-old").await;
+
+    let tier = RetentionTier::defaults()[H2_TO_H4];
+    let db = &db;
+    let evict = |scope| async move {
+      db.note_snapshot()
+        .ids_to_evict_for_tier(tier, Some(4 * 60), now, scope)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| row.id)
+        .collect::<Vec<_>>()
+    };
+    let overridden = [note_a];
+    assert_eq!(evict(EvictScope::AllExcept(&overridden)).await, [b_dropped]);
+    assert_eq!(evict(EvictScope::Note(note_a)).await, [a_dropped]);
+  }
+
+  #[tokio::test]
+  async fn ids_to_evict_skips_tiers_beyond_the_time_range() {
+    let db = test_db().await;
+    let owner = insert_user(&db, "owner", "owner@x.com").await;
+    let note = db.notes().create(owner, "T".into()).await.unwrap();
+    let now = fixed_now();
+    let offset = chrono::TimeDelta::hours(2) + chrono::TimeDelta::minutes(15);
+    snapshot_at(&db, note, now, offset, "new").await;
+    let dropped = snapshot_at(
+      &db,
+      note,
+      now,
+      offset + chrono::TimeDelta::minutes(5),
+      "old",
+    )
+    .await;
+
+    for (tier, until_mins) in [
+      ((i64::MAX, 30), None),
+      ((60, i64::MAX), None),
+      ((60, 30), Some(i64::MAX)),
+    ] {
+      let tier = RetentionTier {
+        after_mins: tier.0,
+        keep_every_mins: tier.1,
+      };
+      let evicted = db
+        .note_snapshot()
+        .ids_to_evict_for_tier(tier, until_mins, now, EvictScope::Note(note))
+        .await
+        .unwrap();
+      let expected = if until_mins.is_some() {
+        vec![dropped]
+      } else {
+        vec![]
+      };
+      assert_eq!(
+        evicted.into_iter().map(|row| row.id).collect::<Vec<_>>(),
+        expected
+      );
+    }
+  }
+
   #[tokio::test]
   async fn ids_to_evict_y1_plus_thins_to_one_per_year() {
     let db = test_db().await;
@@ -634,10 +823,7 @@ mod test {
     )
     .await;
 
-    assert_eq!(
-      evict_ids(&db, RetentionTier::Y1Plus, now).await,
-      vec![dropped]
-    );
+    assert_eq!(evict_ids(&db, Y1_PLUS, now).await, vec![dropped]);
     assert!(db.note_snapshot().find(kept).await.unwrap().is_some());
   }
 
@@ -665,7 +851,7 @@ mod test {
     )
     .await;
 
-    assert!(evict_ids(&db, RetentionTier::H2ToH4, now).await.is_empty());
+    assert!(evict_ids(&db, H2_TO_H4, now).await.is_empty());
     assert!(db.note_snapshot().find(first).await.unwrap().is_some());
     assert!(db.note_snapshot().find(second).await.unwrap().is_some());
   }
@@ -679,8 +865,8 @@ mod test {
 
     let h4_tier = snapshot_at(&db, note, now, chrono::TimeDelta::hours(5), "h4").await;
 
-    assert!(evict_ids(&db, RetentionTier::H2ToH4, now).await.is_empty());
-    assert!(evict_ids(&db, RetentionTier::H4ToD1, now).await.is_empty());
+    assert!(evict_ids(&db, H2_TO_H4, now).await.is_empty());
+    assert!(evict_ids(&db, H4_TO_D1, now).await.is_empty());
     assert!(db.note_snapshot().find(h4_tier).await.unwrap().is_some());
   }
 
@@ -693,7 +879,7 @@ mod test {
 
     snapshot_at(&db, note, now, chrono::TimeDelta::minutes(90), "recent").await;
 
-    for tier in RetentionTier::all() {
+    for tier in &ALL_TIERS {
       assert!(evict_ids(&db, *tier, now).await.is_empty());
     }
   }
@@ -708,10 +894,10 @@ mod test {
     let too_recent = snapshot_at(&db, note, now, chrono::TimeDelta::minutes(90), "recent").await;
     let too_old_for_h2 = snapshot_at(&db, note, now, chrono::TimeDelta::hours(5), "old").await;
 
-    for tier in RetentionTier::all() {
+    for tier in &ALL_TIERS {
       let evicted = evict_ids(&db, *tier, now).await;
       assert!(!evicted.contains(&too_recent));
-      if *tier == RetentionTier::H2ToH4 {
+      if *tier == H2_TO_H4 {
         assert!(!evicted.contains(&too_old_for_h2));
       }
     }
@@ -745,7 +931,7 @@ mod test {
     )
     .await;
 
-    let mut evicted = evict_ids(&db, RetentionTier::H2ToH4, now).await;
+    let mut evicted = evict_ids(&db, H2_TO_H4, now).await;
     evicted.sort();
 
     let mut expected = vec![dropped_a, dropped_b];
@@ -779,8 +965,8 @@ mod test {
     )
     .await;
 
-    assert!(evict_ids(&db, RetentionTier::H2ToH4, now).await.is_empty());
-    assert!(evict_ids(&db, RetentionTier::H4ToD1, now).await.is_empty());
+    assert!(evict_ids(&db, H2_TO_H4, now).await.is_empty());
+    assert!(evict_ids(&db, H4_TO_D1, now).await.is_empty());
     assert!(db.note_snapshot().find(in_h2_tier).await.unwrap().is_some());
     assert!(db.note_snapshot().find(in_h4_tier).await.unwrap().is_some());
   }
@@ -796,9 +982,9 @@ mod test {
     let at_4h = snapshot_at(&db, note, now, chrono::TimeDelta::hours(4), "4h").await;
     let at_1d = snapshot_at(&db, note, now, chrono::TimeDelta::days(1), "1d").await;
 
-    assert!(evict_ids(&db, RetentionTier::H2ToH4, now).await.is_empty());
-    assert!(evict_ids(&db, RetentionTier::H4ToD1, now).await.is_empty());
-    assert!(evict_ids(&db, RetentionTier::D1ToD3, now).await.is_empty());
+    assert!(evict_ids(&db, H2_TO_H4, now).await.is_empty());
+    assert!(evict_ids(&db, H4_TO_D1, now).await.is_empty());
+    assert!(evict_ids(&db, D1_TO_D3, now).await.is_empty());
     assert!(db.note_snapshot().find(at_2h).await.unwrap().is_some());
     assert!(db.note_snapshot().find(at_4h).await.unwrap().is_some());
     assert!(db.note_snapshot().find(at_1d).await.unwrap().is_some());
@@ -828,10 +1014,7 @@ mod test {
     )
     .await;
 
-    assert_eq!(
-      evict_ids(&db, RetentionTier::D1ToD3, now).await,
-      vec![dropped]
-    );
+    assert_eq!(evict_ids(&db, D1_TO_D3, now).await, vec![dropped]);
     assert!(db.note_snapshot().find(kept).await.unwrap().is_some());
   }
 
@@ -877,7 +1060,7 @@ mod test {
     .await;
 
     let mut evicted = Vec::new();
-    for tier in RetentionTier::all() {
+    for tier in &ALL_TIERS {
       evicted.extend(evict_ids(&db, *tier, now).await);
     }
     evicted.sort();
@@ -980,7 +1163,7 @@ mod test {
     )
     .await;
 
-    let mut evicted = evict_ids(&db, RetentionTier::H2ToH4, now).await;
+    let mut evicted = evict_ids(&db, H2_TO_H4, now).await;
     evicted.sort();
 
     let mut expected = vec![oldest, middle];
@@ -998,7 +1181,7 @@ mod test {
 
     let recent = snapshot_at(&db, note, now, chrono::TimeDelta::minutes(119), "recent").await;
 
-    for tier in RetentionTier::all() {
+    for tier in &ALL_TIERS {
       assert!(evict_ids(&db, *tier, now).await.is_empty());
     }
     assert!(db.note_snapshot().find(recent).await.unwrap().is_some());
@@ -1016,7 +1199,7 @@ mod test {
     let at_30d = snapshot_at(&db, note, now, chrono::TimeDelta::days(30), "30d").await;
     let at_365d = snapshot_at(&db, note, now, chrono::TimeDelta::days(365), "365d").await;
 
-    for tier in RetentionTier::all() {
+    for tier in &ALL_TIERS {
       assert!(evict_ids(&db, *tier, now).await.is_empty());
     }
     for kept in [at_3d, at_7d, at_30d, at_365d] {
@@ -1041,10 +1224,7 @@ mod test {
     )
     .await;
 
-    assert_eq!(
-      evict_ids(&db, RetentionTier::Y1Plus, now).await,
-      vec![dropped]
-    );
+    assert_eq!(evict_ids(&db, Y1_PLUS, now).await, vec![dropped]);
     assert!(db.note_snapshot().find(kept).await.unwrap().is_some());
   }
 
@@ -1070,7 +1250,7 @@ mod test {
     // run cleanup daily from day 8 to day 60
     for d in 8..=60 {
       let now = t0 + TimeDelta::days(d);
-      for tier in RetentionTier::all() {
+      for tier in &ALL_TIERS {
         let ids: Vec<Uuid> = evict_ids(&db, *tier, now).await;
         db.note_snapshot().delete_many(&ids).await.unwrap();
       }
@@ -1111,10 +1291,7 @@ mod test {
       (second, first)
     };
 
-    assert_eq!(
-      evict_ids(&db, RetentionTier::H2ToH4, now).await,
-      vec![lower_id]
-    );
+    assert_eq!(evict_ids(&db, H2_TO_H4, now).await, vec![lower_id]);
     assert!(db.note_snapshot().find(higher_id).await.unwrap().is_some());
   }
 
@@ -1142,11 +1319,8 @@ mod test {
     )
     .await;
 
-    assert!(evict_ids(&db, RetentionTier::H2ToH4, now).await.is_empty());
-    assert_eq!(
-      evict_ids(&db, RetentionTier::H4ToD1, now).await,
-      vec![dropped]
-    );
+    assert!(evict_ids(&db, H2_TO_H4, now).await.is_empty());
+    assert_eq!(evict_ids(&db, H4_TO_D1, now).await, vec![dropped]);
     assert!(db.note_snapshot().find(kept).await.unwrap().is_some());
   }
 }
@@ -0,0 +1,67 @@
+use centaurus::error::Result;
+use entity::{note_snapshot_policy, prelude::*};
+use sea_orm::{ActiveValue::Set, QuerySelect, prelude::*, sea_query::OnConflict};
+use uuid::Uuid;
+
+/// Per note overrides of the instance snapshot policy, unset fields fall back
+/// to the instance value.
+pub struct NoteSnapshotPolicyTable<'db> {
+  db: &'db DatabaseConnection,
+}
+
+impl<'db> NoteSnapshotPolicyTable<'db> {
+  pub fn new(db: &'db DatabaseConnection) -> Self {
+    Self { db }
+  }
+
+  pub async fn get(&self, note_id: Uuid) -> Result<Option<note_snapshot_policy::Model>> {
+    Ok(NoteSnapshotPolicy::find_by_id(note_id).one(self.db).await?)
+  }
+
+  pub async fn set(&self, policy: note_snapshot_policy::Model) -> Result<()> {
+    let model = note_snapshot_policy::ActiveModel {
+      note_id: Set(policy.note_id),
+      min_interval_mins: Set(policy.min_interval_mins),
+      max_interval_mins: Set(policy.max_interval_mins),
+      min_size_change: Set(policy.min_size_change),
+      retention: Set(policy.retention),
+    };
+
+    NoteSnapshotPolicy::insert(model)
+      .on_conflict(
+        OnConflict::column(note_snapshot_policy::Column::NoteId)
+          .update_columns([
+            note_snapshot_policy::Column::MinIntervalMins,
+            note_snapshot_policy::Column::MaxIntervalMins,
+            note_snapshot_policy::Column::MinSizeChange,
+            note_snapshot_policy::Column::Retention,
+          ])
+          .to_owned(),
+      )
+      .exec(self.db)
+      .await?;
+
+    Ok(())
+  }
+
+  pub async fn delete(&self, note_id: Uuid) -> Result<()> {
+    NoteSnapshotPolicy::delete_by_id(note_id)
+      .exec(self.db)
+      .await?;
+    Ok(())
+  }
+
+  /// Notes with their own retention table, for the cleanup job.
+  pub async fn retention_overrides(&self) -> Result<Vec<(Uuid, String)>> {
+    Ok(
+      NoteSnapshotPolicy::find()
+        .select_only()
+        .column(note_snapshot_policy::Column::NoteId)
+        .column(note_snapshot_policy::Column::Retention)
+        .filter(note_snapshot_policy::Column::Retention.is_not_null())
+        .into_tuple()
+        .all(self.db)
+        .await?,
+    )
+  }
+}
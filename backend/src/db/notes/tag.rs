@@ -0,0 +1,184 @@
+use std::collections::{HashMap, HashSet};
+
+use centaurus::error::Result;
+use entity::{note_favourite, note_tag, prelude::*};
+use sea_orm::{ActiveValue::Set, QueryOrder, QuerySelect, TransactionTrait, prelude::*};
+use uuid::Uuid;
+
+/// Tags and favourites are personal, like folders every user keeps their own
+/// on the notes they can see.
+pub struct NoteTagTable<'db> {
+  db: &'db DatabaseConnection,
+}
+
+impl<'db> NoteTagTable<'db> {
+  pub fn new(db: &'db DatabaseConnection) -> Self {
+    Self { db }
+  }
+
+  /// Replaces the tags the user put on the note.
+  pub async fn set_tags(&self, user_id: Uuid, note_id: Uuid, tags: Vec<String>) -> Result<()> {
+    let txn = self.db.begin().await?;
+
+    NoteTag::delete_many()
+      .filter(note_tag::Column::UserId.eq(user_id))
+      .filter(note_tag::Column::NoteId.eq(note_id))
+      .exec(&txn)
+      .await?;
+
+    if !tags.is_empty() {
+      NoteTag::insert_many(tags.into_iter().map(|tag| note_tag::ActiveModel {
+        user_id: Set(user_id),
+        note_id: Set(note_id),
+        tag: Set(tag),
+      }))
+      .exec(&txn)
+      .await?;
+    }
+
+    txn.commit().await?;
+    Ok(())
+  }
+
+  /// All distinct tags the user has used, sorted by name.
+  pub async fn list_tags(&self, user_id: Uuid) -> Result<Vec<String>> {
+    Ok(
+      NoteTag::find()
+        .select_only()
+        .column(note_tag::Column::Tag)
+        .distinct()
+        .filter(note_tag::Column::UserId.eq(user_id))
+        .order_by_asc(note_tag::Column::Tag)
+        .into_tuple()
+        .all(self.db)
+        .await?,
+    )
+  }
+
+  /// Maps each tagged note to the user's tags on it, sorted by name.
+  pub async fn tags_for_notes(
+    &self,
+    user_id: Uuid,
+    note_ids: &[Uuid],
+  ) -> Result<HashMap<Uuid, Vec<String>>> {
+    if note_ids.is_empty() {
+      return Ok(HashMap::new());
+    }
+
+    let mut map: HashMap<Uuid, Vec<String>> = HashMap::new();
+    for row in NoteTag::find()
+      .filter(note_tag::Column::UserId.eq(user_id))
+      .filter(note_tag::Column::NoteId.is_in(note_ids.to_vec()))
+      .order_by_asc(note_tag::Column::Tag)
+      .all(self.db)
+      .await?
+    {
+      map.entry(row.note_id).or_default().push(row.tag);
+    }
+
+    Ok(map)
+  }
+
+  pub async fn set_favourite(&self, user_id: Uuid, note_id: Uuid, favourite: bool) -> Result<()> {
+    let txn = self.db.begin().await?;
+
+    NoteFavourite::delete_many()
+      .filter(note_favourite::Column::UserId.eq(user_id))
+      .filter(note_favourite::Column::NoteId.eq(note_id))
+      .exec(&txn)
+      .await?;
+
+    if favourite {
+      note_favourite::ActiveModel {
+        user_id: Set(user_id),
+        note_id: Set(note_id),
+      }
+      .insert(&txn)
+      .await?;
+    }
+
+    txn.commit().await?;
+    Ok(())
+  }
+
+  /// The subset of `note_ids` the user marked as favourite.
+  pub async fn favourites(&self, user_id: Uuid, note_ids: &[Uuid]) -> Result<HashSet<Uuid>> {
+    if note_ids.is_empty() {
+      return Ok(HashSet::new());
+    }
+
+    Ok(
+      NoteFavourite::find()
+        .filter(note_favourite::Column::UserId.eq(user_id))
+        .filter(note_favourite::Column::NoteId.is_in(note_ids.to_vec()))
+        .all(self.db)
+        .await?
+        .into_iter()
+        .map(|row| row.note_id)
+        .collect(),
+    )
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::db::{
+    DBTrait,
+    test::{insert_user, test_db},
+  };
+
+  #[tokio::test]
+  async fn tags_and_favourites_are_per_user() {
+    let db = test_db().await;
+    let alice = insert_user(&db, "alice", "a@x.com").await;
+    let bob = insert_user(&db, "bob", "b@x.com").await;
+    let note = db.notes().create(alice, "T".into()).await.unwrap();
+
+    db.note_tag()
+      .set_tags(alice, note, vec!["work".into(), "ideas".into()])
+      .await
+      .unwrap();
+    db.note_tag()
+      .set_tags(bob, note, vec!["later".into()])
+      .await
+      .unwrap();
+    db.note_tag()
+      .set_favourite(alice, note, true)
+      .await
+      .unwrap();
+
+    assert_eq!(
+      db.note_tag().tags_for_notes(alice, &[note]).await.unwrap()[&note],
+      ["ideas", "work"]
+    );
+    assert_eq!(db.note_tag().list_tags(bob).await.unwrap(), ["later"]);
+    assert!(
+      db.note_tag()
+        .favourites(alice, &[note])
+        .await
+        .unwrap()
+        .contains(&note)
+    );
+    assert!(
+      db.note_tag()
+        .favourites(bob, &[note])
+        .await
+        .unwrap()
+        .is_empty()
+    );
+
+    db.note_tag().set_tags(alice, note, vec![]).await.unwrap();
+    db.note_tag()
+      .set_favourite(alice, note, false)
+      .await
+      .unwrap();
+    assert!(db.note_tag().list_tags(alice).await.unwrap().is_empty());
+    assert!(
+      db.note_tag()
+        .favourites(alice, &[note])
+        .await
+        .unwrap()
+        .is_empty()
+    );
+  }
+}
@@ -1,7 +1,8 @@
 use centaurus::db::tables::{group::SimpleUserInfo, user::SimpleGroupInfo};
 use entity::{
-  group, group_user, o_auth_client, o_auth_client_additional_redirect_uri, o_auth_client_group,
-  o_auth_client_o_auth_scope, o_auth_client_user, o_auth_scope, prelude::*, user,
+  group, o_auth_client, o_auth_client_additional_redirect_uri, o_auth_client_group,
+  o_auth_client_o_auth_scope, o_auth_client_user, o_auth_scope, prelude::*,
+  sea_orm_active_enums::AuthLevel, user,
 };
 use schemars::JsonSchema;
 use sea_orm::{ActiveValue::Set, Condition, IntoActiveModel, JoinType, QuerySelect, prelude::*};
@@ -9,7 +10,7 @@ use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use webauthn_rs::prelude::Url;
 
-use crate::db::oauth::oauth_scope::SimpleOAuthScopeInfo;
+use crate::db::{group::GroupExtTable, oauth::oauth_scope::SimpleOAuthScopeInfo};
 
 #[derive(Serialize, Deserialize, JsonSchema)]
 pub struct OAuthClientInfo {
@@ -22,6 +23,7 @@ pub struct OAuthClientInfo {
   pub user_access: Vec<SimpleUserInfo>,
   pub confidential: bool,
   pub require_pkce: bool,
+  pub required_acr: Option<AuthLevel>,
 }
 
 pub struct OauthClientTable<'db> {
@@ -57,6 +59,9 @@ impl<'db> OauthClientTable<'db> {
   }
 
   pub async fn has_user_access(&self, user: Uuid, client_id: Uuid) -> Result<bool, DbErr> {
+    let groups = GroupExtTable::new(self.db)
+      .effective_group_ids(user)
+      .await?;
     let count = o_auth_client::Entity::find()
       .filter(o_auth_client::Column::Id.eq(client_id))
       .join_rev(
@@ -67,15 +72,10 @@ impl<'db> OauthClientTable<'db> {
         JoinType::LeftJoin,
         o_auth_client_group::Relation::OAuthClient.def(),
       )
-      .join(
-        JoinType::LeftJoin,
-        o_auth_client_group::Relation::Group.def(),
-      )
-      .join_rev(JoinType::LeftJoin, group_user::Relation::Group.def())
       .filter(
         Condition::any()
           .add(o_auth_client_user::Column::UserId.eq(user))
-          .add(group_user::Column::UserId.eq(user)),
+          .add(o_auth_client_group::Column::GroupId.is_in(groups)),
       )
       .count(self.db)
       .await?;
@@ -83,6 +83,29 @@ impl<'db> OauthClientTable<'db> {
     Ok(count > 0)
   }
 
+  pub async fn list_for_user(&self, user: Uuid) -> Result<Vec<o_auth_client::Model>, DbErr> {
+    let groups = GroupExtTable::new(self.db)
+      .effective_group_ids(user)
+      .await?;
+    o_auth_client::Entity::find()
+      .join_rev(
+        JoinType::LeftJoin,
+        o_auth_client_user::Relation::OAuthClient.def(),
+      )
+      .join_rev(
+        JoinType::LeftJoin,
+        o_auth_client_group::Relation::OAuthClient.def(),
+      )
+      .filter(
+        Condition::any()
+          .add(o_auth_client_user::Column::UserId.eq(user))
+          .add(o_auth_client_group::Column::GroupId.is_in(groups)),
+      )
+      .distinct()
+      .all(self.db)
+      .await
+  }
+
   pub async fn list_client(&self) -> Result<Vec<OAuthClientInfo>, DbErr> {
     let clients = o_auth_client::Entity::find().all(self.db).await?;
     let group_client = clients
@@ -143,6 +166,7 @@ impl<'db> OauthClientTable<'db> {
             .collect(),
           confidential: client.confidential,
           require_pkce: client.require_pkce,
+          required_acr: client.required_acr,
         },
       )
       .collect();
@@ -241,6 +265,7 @@ impl<'db> OauthClientTable<'db> {
       user_access,
       confidential: client.confidential,
       require_pkce: client.require_pkce,
+      required_acr: client.required_acr,
     }))
   }
 
@@ -356,6 +381,7 @@ impl<'db> OauthClientTable<'db> {
     client_id: Uuid,
     name: String,
     require_pkce: bool,
+    required_acr: Option<AuthLevel>,
     redirect_uri: String,
     additional_redirect_uris: Vec<String>,
     default_scope: Vec<Uuid>,
@@ -367,6 +393,7 @@ impl<'db> OauthClientTable<'db> {
     client.name = Set(name);
     client.redirect_uri = Set(redirect_uri);
     client.require_pkce = Set(require_pkce);
+    client.required_acr = Set(required_acr);
 
     client.update(self.db).await?;
 
@@ -446,6 +473,7 @@ mod test {
         salt: "salt".to_string(),
         confidential,
         require_pkce: false,
+        required_acr: None,
       })
       .await
       .unwrap();
@@ -590,6 +618,7 @@ mod test {
         id,
         "App".into(),
         false,
+        None,
         "https://example.com/cb".into(),
         vec![],
         vec![],
@@ -623,6 +652,74 @@ mod test {
     );
   }
 
+  #[tokio::test]
+  async fn has_user_access_through_parent_group() {
+    let db = test_db().await;
+    let id = create_client(&db, "App", true).await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let parent = insert_group(&db, "parent").await;
+    let child = insert_group(&db, "child").await;
+    add_user_to_group(&db, child, user).await;
+    db.oauth_client()
+      .add_groups_to_client(id, vec![parent])
+      .await
+      .unwrap();
+    assert!(!db.oauth_client().has_user_access(user, id).await.unwrap());
+
+    db.group_ext()
+      .set_parents(child, vec![parent])
+      .await
+      .unwrap();
+    assert!(db.oauth_client().has_user_access(user, id).await.unwrap());
+    assert_eq!(
+      db.oauth_client().list_for_user(user).await.unwrap().len(),
+      1
+    );
+  }
+
+  #[tokio::test]
+  async fn list_for_user_combines_direct_and_group_access() {
+    let db = test_db().await;
+    let direct = create_client(&db, "Direct", true).await;
+    let via_group = create_client(&db, "Group", true).await;
+    create_client(&db, "Other", true).await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let group = insert_group(&db, "g").await;
+    add_user_to_group(&db, group, user).await;
+
+    for (id, name, users, groups) in [
+      (direct, "Direct", vec![user], vec![group]),
+      (via_group, "Group", vec![], vec![group]),
+    ] {
+      db.oauth_client()
+        .edit_client(
+          id,
+          name.into(),
+          false,
+          None,
+          "https://example.com/cb".into(),
+          vec![],
+          vec![],
+          users,
+          groups,
+        )
+        .await
+        .unwrap();
+    }
+
+    // a client reachable both directly and through a group shows up once
+    let mut names: Vec<_> = db
+      .oauth_client()
+      .list_for_user(user)
+      .await
+      .unwrap()
+      .into_iter()
+      .map(|c| c.name)
+      .collect();
+    names.sort();
+    assert_eq!(names, ["Direct", "Group"]);
+  }
+
   #[tokio::test]
   async fn edit_client_replaces_all_relations() {
     let db = test_db().await;
@@ -640,6 +737,7 @@ mod test {
         id,
         "Renamed".into(),
         true,
+        None,
         "https://new.example.com/cb".into(),
         vec!["https://extra.example.com/cb".into()],
         vec![scope],
@@ -679,6 +777,7 @@ mod test {
         id,
         "Renamed".into(),
         false,
+        None,
         "https://new.example.com/cb".into(),
         vec![],
         vec![],
@@ -712,6 +811,7 @@ mod test {
         id,
         "App".into(),
         false,
+        None,
         "https://example.com/cb".into(),
         vec!["https://extra.example.com/cb".into()],
         vec![scope],
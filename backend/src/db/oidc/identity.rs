@@ -0,0 +1,328 @@
+use chrono::Utc;
+use entity::{oidc_provider, prelude::*, user, user_identity};
+use schemars::JsonSchema;
+use sea_orm::{ActiveValue::Set, QueryOrder, prelude::*};
+use serde::Serialize;
+
+#[derive(Serialize, Debug, JsonSchema)]
+pub struct UserIdentityInfo {
+  pub uuid: Uuid,
+  pub provider: Uuid,
+  pub provider_name: String,
+  pub email: String,
+  pub linked_at: DateTime,
+  pub last_login_at: Option<DateTime>,
+}
+
+pub struct UserIdentityTable<'db> {
+  db: &'db DatabaseConnection,
+}
+
+impl<'db> UserIdentityTable<'db> {
+  pub fn new(db: &'db DatabaseConnection) -> Self {
+    Self { db }
+  }
+
+  pub async fn find(
+    &self,
+    provider: Uuid,
+    subject: &str,
+  ) -> Result<Option<user_identity::Model>, DbErr> {
+    UserIdentity::find()
+      .filter(user_identity::Column::ProviderId.eq(provider))
+      .filter(user_identity::Column::Subject.eq(subject))
+      .one(self.db)
+      .await
+  }
+
+  pub async fn create(
+    &self,
+    user: Uuid,
+    provider: Uuid,
+    subject: String,
+    email: String,
+  ) -> Result<Uuid, DbErr> {
+    let id = Uuid::now_v7();
+    user_identity::ActiveModel {
+      id: Set(id),
+      user_id: Set(user),
+      provider_id: Set(provider),
+      subject: Set(subject),
+      email: Set(email.to_lowercase()),
+      linked_at: Set(Utc::now().naive_utc()),
+      last_login_at: Set(None),
+    }
+    .insert(self.db)
+    .await?;
+
+    Ok(id)
+  }
+
+  pub async fn touch_login(&self, id: Uuid, email: String) -> Result<(), DbErr> {
+    let Some(identity) = UserIdentity::find_by_id(id).one(self.db).await? else {
+      return Err(DbErr::RecordNotFound("identity not found".into()));
+    };
+
+    let mut identity: user_identity::ActiveModel = identity.into();
+    identity.email = Set(email.to_lowercase());
+    identity.last_login_at = Set(Some(Utc::now().naive_utc()));
+    identity.update(self.db).await?;
+
+    Ok(())
+  }
+
+  pub async fn list_for_user(&self, user: Uuid) -> Result<Vec<UserIdentityInfo>, DbErr> {
+    let rows = UserIdentity::find()
+      .filter(user_identity::Column::UserId.eq(user))
+      .find_also_related(oidc_provider::Entity)
+      .order_by_asc(user_identity::Column::LinkedAt)
+      .all(self.db)
+      .await?;
+
+    Ok(
+      rows
+        .into_iter()
+        .map(|(identity, provider)| UserIdentityInfo {
+          uuid: identity.id,
+          provider: identity.provider_id,
+          provider_name: provider.map(|p| p.name).unwrap_or_default(),
+          email: identity.email,
+          linked_at: identity.linked_at,
+          last_login_at: identity.last_login_at,
+        })
+        .collect(),
+    )
+  }
+
+  pub async fn count_for_user(&self, user: Uuid) -> Result<u64, DbErr> {
+    UserIdentity::find()
+      .filter(user_identity::Column::UserId.eq(user))
+      .count(self.db)
+      .await
+  }
+
+  /// Links every user that signed in through the former single provider
+  /// setup, which stored the upstream subject on the user itself.
+  pub async fn import_legacy(&self, provider: Uuid) -> Result<u64, DbErr> {
+    let users = User::find()
+      .filter(user::Column::OidcSubject.is_not_null())
+      .all(self.db)
+      .await?;
+
+    let mut imported = 0;
+    for user in users {
+      let Some(subject) = user.oidc_subject else {
+        continue;
+      };
+      if self.find(provider, &subject).await?.is_some() {
+        continue;
+      }
+      self.create(user.id, provider, subject, user.email).await?;
+      imported += 1;
+    }
+
+    Ok(imported)
+  }
+
+  pub async fn delete(&self, id: Uuid, user: Uuid) -> Result<bool, DbErr> {
+    let res = UserIdentity::delete_many()
+      .filter(user_identity::Column::Id.eq(id))
+      .filter(user_identity::Column::UserId.eq(user))
+      .exec(self.db)
+      .await?;
+    Ok(res.rows_affected > 0)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::db::{
+    DBTrait,
+    oidc::provider::test::provider_data,
+    test::{insert_user, test_db},
+  };
+  use uuid::Uuid;
+
+  #[tokio::test]
+  async fn create_find_and_list() {
+    let db = test_db().await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let provider = db
+      .oidc_provider()
+      .create(provider_data("GitLab", "https://gitlab.example.com"))
+      .await
+      .unwrap();
+
+    let id = db
+      .user_identity()
+      .create(user, provider, "sub-1".into(), "U@Upstream.com".into())
+      .await
+      .unwrap();
+
+    let found = db
+      .user_identity()
+      .find(provider, "sub-1")
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(found.id, id);
+    assert_eq!(found.email, "u@upstream.com");
+    assert!(found.last_login_at.is_none());
+    assert!(
+      db.user_identity()
+        .find(provider, "other")
+        .await
+        .unwrap()
+        .is_none()
+    );
+
+    let list = db.user_identity().list_for_user(user).await.unwrap();
+    assert_eq!(list.len(), 1);
+    assert_eq!(list[0].provider_name, "GitLab");
+    assert_eq!(db.user_identity().count_for_user(user).await.unwrap(), 1);
+  }
+
+  #[tokio::test]
+  async fn subject_is_unique_per_provider() {
+    let db = test_db().await;
+    let a = insert_user(&db, "a", "a@x.com").await;
+    let b = insert_user(&db, "b", "b@x.com").await;
+    let first = db
+      .oidc_provider()
+      .create(provider_data("A", "https://a.example.com"))
+      .await
+      .unwrap();
+    let second = db
+      .oidc_provider()
+      .create(provider_data("B", "https://b.example.com"))
+      .await
+      .unwrap();
+
+    db.user_identity()
+      .create(a, first, "sub".into(), "a@x.com".into())
+      .await
+      .unwrap();
+    // the same subject at another provider is a different identity
+    db.user_identity()
+      .create(b, second, "sub".into(), "b@x.com".into())
+      .await
+      .unwrap();
+    assert!(
+      db.user_identity()
+        .create(b, first, "sub".into(), "b@x.com".into())
+        .await
+        .is_err()
+    );
+  }
+
+  #[tokio::test]
+  async fn touch_login_updates_email_and_timestamp() {
+    let db = test_db().await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let provider = db
+      .oidc_provider()
+      .create(provider_data("A", "https://a.example.com"))
+      .await
+      .unwrap();
+    let id = db
+      .user_identity()
+      .create(user, provider, "sub".into(), "old@x.com".into())
+      .await
+      .unwrap();
+
+    db.user_identity()
+      .touch_login(id, "new@x.com".into())
+      .await
+      .unwrap();
+
+    let found = db
+      .user_identity()
+      .find(provider, "sub")
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(found.email, "new@x.com");
+    assert!(found.last_login_at.is_some());
+
+    assert!(
+      db.user_identity()
+        .touch_login(Uuid::new_v4(), "x@x.com".into())
+        .await
+        .is_err()
+    );
+  }
+
+  #[tokio::test]
+  async fn delete_is_scoped_to_owner() {
+    let db = test_db().await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let other = insert_user(&db, "o", "o@x.com").await;
+    let provider = db
+      .oidc_provider()
+      .create(provider_data("A", "https://a.example.com"))
+      .await
+      .unwrap();
+    let id = db
+      .user_identity()
+      .create(user, provider, "sub".into(), "u@x.com".into())
+      .await
+      .unwrap();
+
+    assert!(!db.user_identity().delete(id, other).await.unwrap());
+    assert!(db.user_identity().delete(id, user).await.unwrap());
+    assert_eq!(db.user_identity().count_for_user(user).await.unwrap(), 0);
+  }
+
+  #[tokio::test]
+  async fn import_legacy_links_stored_subjects_once() {
+    use entity::user;
+    use sea_orm::{ActiveModelTrait, ActiveValue::Set};
+
+    let db = test_db().await;
+    let legacy = insert_user(&db, "l", "l@x.com").await;
+    insert_user(&db, "p", "p@x.com").await;
+    user::ActiveModel {
+      id: Set(legacy),
+      oidc_subject: Set(Some("legacy-sub".into())),
+      ..Default::default()
+    }
+    .update(&db.0)
+    .await
+    .unwrap();
+    let provider = db
+      .oidc_provider()
+      .create(provider_data("SSO", "https://sso.example.com"))
+      .await
+      .unwrap();
+
+    assert_eq!(db.user_identity().import_legacy(provider).await.unwrap(), 1);
+    // running it again does not duplicate the identity
+    assert_eq!(db.user_identity().import_legacy(provider).await.unwrap(), 0);
+
+    let found = db
+      .user_identity()
+      .find(provider, "legacy-sub")
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(found.user_id, legacy);
+  }
+
+  #[tokio::test]
+  async fn deleting_provider_removes_identities() {
+    let db = test_db().await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let provider = db
+      .oidc_provider()
+      .create(provider_data("A", "https://a.example.com"))
+      .await
+      .unwrap();
+    db.user_identity()
+      .create(user, provider, "sub".into(), "u@x.com".into())
+      .await
+      .unwrap();
+
+    db.oidc_provider().delete(provider).await.unwrap();
+    assert_eq!(db.user_identity().count_for_user(user).await.unwrap(), 0);
+  }
+}
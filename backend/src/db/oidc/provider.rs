@@ -0,0 +1,526 @@
+use std::collections::HashSet;
+
+use entity::{group_user, oidc_provider, oidc_provider_group, prelude::*};
+use schemars::JsonSchema;
+use sea_orm::{ActiveValue::Set, QueryOrder, QuerySelect, prelude::*};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
+pub struct OidcGroupMapping {
+  pub claim_value: String,
+  pub group: Uuid,
+}
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub struct OidcProviderInfo {
+  pub uuid: Uuid,
+  pub name: String,
+  pub issuer: Url,
+  pub client_id: String,
+  pub scopes: Vec<String>,
+  pub pkce: bool,
+  pub create_user: bool,
+  pub enabled: bool,
+  pub name_claim: String,
+  pub email_claim: String,
+  pub group_claim: Option<String>,
+  pub group_mappings: Vec<OidcGroupMapping>,
+}
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub struct OidcProviderLoginInfo {
+  pub uuid: Uuid,
+  pub name: String,
+}
+
+#[derive(Deserialize, Debug, Clone, JsonSchema)]
+pub struct OidcProviderData {
+  pub name: String,
+  pub issuer: Url,
+  pub client_id: String,
+  /// Left unchanged on edit when `None`.
+  pub client_secret: Option<String>,
+  pub scopes: Vec<String>,
+  pub pkce: bool,
+  pub create_user: bool,
+  pub enabled: bool,
+  pub name_claim: String,
+  pub email_claim: String,
+  pub group_claim: Option<String>,
+  pub group_mappings: Vec<OidcGroupMapping>,
+}
+
+pub struct OidcProviderTable<'db> {
+  db: &'db DatabaseConnection,
+}
+
+impl<'db> OidcProviderTable<'db> {
+  pub fn new(db: &'db DatabaseConnection) -> Self {
+    Self { db }
+  }
+
+  pub async fn list(&self) -> Result<Vec<OidcProviderInfo>, DbErr> {
+    let providers = OidcProvider::find()
+      .order_by_asc(oidc_provider::Column::Name)
+      .all(self.db)
+      .await?;
+
+    let mut res = Vec::with_capacity(providers.len());
+    for provider in providers {
+      let mappings = self.group_mappings(provider.id).await?;
+      res.push(provider_info(provider, mappings));
+    }
+
+    Ok(res)
+  }
+
+  pub async fn list_enabled(&self) -> Result<Vec<OidcProviderLoginInfo>, DbErr> {
+    let res: Vec<(Uuid, String)> = OidcProvider::find()
+      .filter(oidc_provider::Column::Enabled.eq(true))
+      .order_by_asc(oidc_provider::Column::Name)
+      .select_only()
+      .column(oidc_provider::Column::Id)
+      .column(oidc_provider::Column::Name)
+      .into_tuple()
+      .all(self.db)
+      .await?;
+
+    Ok(
+      res
+        .into_iter()
+        .map(|(uuid, name)| OidcProviderLoginInfo { uuid, name })
+        .collect(),
+    )
+  }
+
+  pub async fn get(&self, id: Uuid) -> Result<Option<oidc_provider::Model>, DbErr> {
+    OidcProvider::find_by_id(id).one(self.db).await
+  }
+
+  pub async fn info(&self, id: Uuid) -> Result<Option<OidcProviderInfo>, DbErr> {
+    let Some(provider) = self.get(id).await? else {
+      return Ok(None);
+    };
+    let mappings = self.group_mappings(id).await?;
+
+    Ok(Some(provider_info(provider, mappings)))
+  }
+
+  pub async fn by_issuer(&self, issuer: &str) -> Result<Option<Uuid>, DbErr> {
+    let res = OidcProvider::find()
+      .filter(oidc_provider::Column::Issuer.eq(issuer))
+      .one(self.db)
+      .await?;
+    Ok(res.map(|p| p.id))
+  }
+
+  pub async fn exists(&self, name: String, uuid: Uuid) -> Result<bool, DbErr> {
+    let provider = OidcProvider::find()
+      .filter(oidc_provider::Column::Name.eq(name))
+      .filter(oidc_provider::Column::Id.ne(uuid))
+      .one(self.db)
+      .await?;
+
+    Ok(provider.is_some())
+  }
+
+  pub async fn create(&self, data: OidcProviderData) -> Result<Uuid, DbErr> {
+    let uuid = Uuid::now_v7();
+    let provider = oidc_provider::ActiveModel {
+      id: Set(uuid),
+      name: Set(data.name),
+      issuer: Set(data.issuer.to_string()),
+      client_id: Set(data.client_id),
+      client_secret: Set(data.client_secret.unwrap_or_default()),
+      scopes: Set(data.scopes.join(" ")),
+      pkce: Set(data.pkce),
+      create_user: Set(data.create_user),
+      enabled: Set(data.enabled),
+      name_claim: Set(data.name_claim),
+      email_claim: Set(data.email_claim),
+      group_claim: Set(data.group_claim),
+    };
+
+    provider.insert(self.db).await?;
+    self.set_group_mappings(uuid, data.group_mappings).await?;
+
+    Ok(uuid)
+  }
+
+  pub async fn edit(&self, uuid: Uuid, data: OidcProviderData) -> Result<(), DbErr> {
+    let Some(provider) = self.get(uuid).await? else {
+      return Err(DbErr::RecordNotFound("Not Found".into()));
+    };
+    let mut provider: oidc_provider::ActiveModel = provider.into();
+
+    provider.name = Set(data.name);
+    provider.issuer = Set(data.issuer.to_string());
+    provider.client_id = Set(data.client_id);
+    if let Some(secret) = data.client_secret {
+      provider.client_secret = Set(secret);
+    }
+    provider.scopes = Set(data.scopes.join(" "));
+    provider.pkce = Set(data.pkce);
+    provider.create_user = Set(data.create_user);
+    provider.enabled = Set(data.enabled);
+    provider.name_claim = Set(data.name_claim);
+    provider.email_claim = Set(data.email_claim);
+    provider.group_claim = Set(data.group_claim);
+
+    provider.update(self.db).await?;
+    self.set_group_mappings(uuid, data.group_mappings).await?;
+
+    Ok(())
+  }
+
+  pub async fn delete(&self, uuid: Uuid) -> Result<bool, DbErr> {
+    let res = OidcProvider::delete_by_id(uuid).exec(self.db).await?;
+    Ok(res.rows_affected > 0)
+  }
+
+  pub async fn group_mappings(&self, provider: Uuid) -> Result<Vec<OidcGroupMapping>, DbErr> {
+    let rows = OidcProviderGroup::find()
+      .filter(oidc_provider_group::Column::ProviderId.eq(provider))
+      .order_by_asc(oidc_provider_group::Column::ClaimValue)
+      .all(self.db)
+      .await?;
+
+    Ok(
+      rows
+        .into_iter()
+        .map(|row| OidcGroupMapping {
+          claim_value: row.claim_value,
+          group: row.group_id,
+        })
+        .collect(),
+    )
+  }
+
+  pub async fn set_group_mappings(
+    &self,
+    provider: Uuid,
+    mappings: Vec<OidcGroupMapping>,
+  ) -> Result<(), DbErr> {
+    OidcProviderGroup::delete_many()
+      .filter(oidc_provider_group::Column::ProviderId.eq(provider))
+      .exec(self.db)
+      .await?;
+
+    let mut seen = HashSet::new();
+    let rows: Vec<_> = mappings
+      .into_iter()
+      .filter(|m| seen.insert((m.claim_value.clone(), m.group)))
+      .map(|m| oidc_provider_group::ActiveModel {
+        provider_id: Set(provider),
+        claim_value: Set(m.claim_value),
+        group_id: Set(m.group),
+      })
+      .collect();
+
+    if !rows.is_empty() {
+      OidcProviderGroup::insert_many(rows).exec(self.db).await?;
+    }
+
+    Ok(())
+  }
+
+  /// Makes the user's membership in the groups mapped by this provider match
+  /// the upstream claim values. Groups that aren't mapped by the provider and
+  /// groups listed in `keep` are never removed. Returns whether anything changed.
+  pub async fn sync_user_groups(
+    &self,
+    provider: Uuid,
+    user: Uuid,
+    claim_values: &[String],
+    keep: &[Uuid],
+  ) -> Result<bool, DbErr> {
+    let mappings = self.group_mappings(provider).await?;
+    if mappings.is_empty() {
+      return Ok(false);
+    }
+
+    let managed: HashSet<Uuid> = mappings.iter().map(|m| m.group).collect();
+    let wanted: HashSet<Uuid> = mappings
+      .iter()
+      .filter(|m| claim_values.contains(&m.claim_value))
+      .map(|m| m.group)
+      .collect();
+
+    let current: HashSet<Uuid> = GroupUser::find()
+      .filter(group_user::Column::UserId.eq(user))
+      .filter(group_user::Column::GroupId.is_in(managed.iter().copied()))
+      .select_only()
+      .column(group_user::Column::GroupId)
+      .into_tuple::<Uuid>()
+      .all(self.db)
+      .await?
+      .into_iter()
+      .collect();
+
+    let to_remove: Vec<Uuid> = current
+      .iter()
+      .filter(|g| !wanted.contains(g) && !keep.contains(g))
+      .copied()
+      .collect();
+    let to_add: Vec<Uuid> = wanted.difference(&current).copied().collect();
+
+    if !to_remove.is_empty() {
+      GroupUser::delete_many()
+        .filter(group_user::Column::UserId.eq(user))
+        .filter(group_user::Column::GroupId.is_in(to_remove.clone()))
+        .exec(self.db)
+        .await?;
+    }
+
+    if !to_add.is_empty() {
+      GroupUser::insert_many(to_add.iter().map(|group| group_user::ActiveModel {
+        group_id: Set(*group),
+        user_id: Set(user),
+      }))
+      .exec(self.db)
+      .await?;
+    }
+
+    Ok(!to_remove.is_empty() || !to_add.is_empty())
+  }
+}
+
+fn provider_info(
+  provider: oidc_provider::Model,
+  mappings: Vec<OidcGroupMapping>,
+) -> OidcProviderInfo {
+  OidcProviderInfo {
+    uuid: provider.id,
+    name: provider.name,
+    // only valid urls are ever stored
+    issuer: Url::parse(&provider.issuer).expect("stored issuer is a valid url"),
+    client_id: provider.client_id,
+    scopes: split_scopes(&provider.scopes),
+    pkce: provider.pkce,
+    create_user: provider.create_user,
+    enabled: provider.enabled,
+    name_claim: provider.name_claim,
+    email_claim: provider.email_claim,
+    group_claim: provider.group_claim,
+    group_mappings: mappings,
+  }
+}
+
+pub fn split_scopes(scopes: &str) -> Vec<String> {
+  scopes.split_whitespace().map(str::to_string).collect()
+}
+
+#[cfg(test)]
+pub mod test {
+  use super::{OidcGroupMapping, OidcProviderData};
+  use crate::db::{
+    DBTrait,
+    test::{add_user_to_group, insert_group, insert_user, test_db},
+  };
+  use centaurus::db::tables::ConnectionExt;
+  use url::Url;
+  use uuid::Uuid;
+
+  pub fn provider_data(name: &str, issuer: &str) -> OidcProviderData {
+    OidcProviderData {
+      name: name.into(),
+      issuer: Url::parse(issuer).unwrap(),
+      client_id: "client".into(),
+      client_secret: Some("secret".into()),
+      scopes: vec!["openid".into(), "email".into()],
+      pkce: true,
+      create_user: false,
+      enabled: true,
+      name_claim: "name".into(),
+      email_claim: "email".into(),
+      group_claim: None,
+      group_mappings: vec![],
+    }
+  }
+
+  #[tokio::test]
+  async fn create_info_and_list() {
+    let db = test_db().await;
+    let group = insert_group(&db, "devs").await;
+    let mut data = provider_data("GitLab", "https://gitlab.example.com");
+    data.group_claim = Some("groups".into());
+    data.group_mappings = vec![
+      OidcGroupMapping {
+        claim_value: "dev".into(),
+        group,
+      },
+      // duplicates are collapsed
+      OidcGroupMapping {
+        claim_value: "dev".into(),
+        group,
+      },
+    ];
+    let id = db.oidc_provider().create(data).await.unwrap();
+
+    let info = db.oidc_provider().info(id).await.unwrap().unwrap();
+    assert_eq!(info.name, "GitLab");
+    assert_eq!(info.scopes, vec!["openid", "email"]);
+    assert_eq!(info.group_claim.as_deref(), Some("groups"));
+    assert_eq!(info.group_mappings.len(), 1);
+
+    let model = db.oidc_provider().get(id).await.unwrap().unwrap();
+    assert_eq!(model.client_secret, "secret");
+
+    assert_eq!(db.oidc_provider().list().await.unwrap().len(), 1);
+    assert!(
+      db.oidc_provider()
+        .info(Uuid::new_v4())
+        .await
+        .unwrap()
+        .is_none()
+    );
+  }
+
+  #[tokio::test]
+  async fn list_enabled_hides_disabled_providers() {
+    let db = test_db().await;
+    db.oidc_provider()
+      .create(provider_data("A", "https://a.example.com"))
+      .await
+      .unwrap();
+    let mut disabled = provider_data("B", "https://b.example.com");
+    disabled.enabled = false;
+    db.oidc_provider().create(disabled).await.unwrap();
+
+    let enabled = db.oidc_provider().list_enabled().await.unwrap();
+    assert_eq!(enabled.len(), 1);
+    assert_eq!(enabled[0].name, "A");
+  }
+
+  #[tokio::test]
+  async fn edit_keeps_secret_when_not_given() {
+    let db = test_db().await;
+    let id = db
+      .oidc_provider()
+      .create(provider_data("A", "https://a.example.com"))
+      .await
+      .unwrap();
+
+    let mut data = provider_data("Renamed", "https://a.example.com");
+    data.client_secret = None;
+    db.oidc_provider().edit(id, data).await.unwrap();
+
+    let model = db.oidc_provider().get(id).await.unwrap().unwrap();
+    assert_eq!(model.name, "Renamed");
+    assert_eq!(model.client_secret, "secret");
+
+    assert!(
+      db.oidc_provider()
+        .edit(Uuid::new_v4(), provider_data("X", "https://x.example.com"))
+        .await
+        .is_err()
+    );
+  }
+
+  #[tokio::test]
+  async fn exists_by_issuer_and_delete() {
+    let db = test_db().await;
+    let id = db
+      .oidc_provider()
+      .create(provider_data("A", "https://a.example.com"))
+      .await
+      .unwrap();
+
+    assert!(
+      db.oidc_provider()
+        .exists("A".into(), Uuid::max())
+        .await
+        .unwrap()
+    );
+    // the provider itself is excluded
+    assert!(!db.oidc_provider().exists("A".into(), id).await.unwrap());
+    assert_eq!(
+      db.oidc_provider()
+        .by_issuer("https://a.example.com/")
+        .await
+        .unwrap(),
+      Some(id)
+    );
+
+    assert!(db.oidc_provider().delete(id).await.unwrap());
+    assert!(!db.oidc_provider().delete(id).await.unwrap());
+  }
+
+  #[tokio::test]
+  async fn sync_user_groups_only_touches_mapped_groups() {
+    let db = test_db().await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let devs = insert_group(&db, "devs").await;
+    let ops = insert_group(&db, "ops").await;
+    let local = insert_group(&db, "local").await;
+    add_user_to_group(&db, ops, user).await;
+    add_user_to_group(&db, local, user).await;
+
+    let mut data = provider_data("A", "https://a.example.com");
+    data.group_mappings = vec![
+      OidcGroupMapping {
+        claim_value: "dev".into(),
+        group: devs,
+      },
+      OidcGroupMapping {
+        claim_value: "ops".into(),
+        group: ops,
+      },
+    ];
+    let provider = db.oidc_provider().create(data).await.unwrap();
+
+    let changed = db
+      .oidc_provider()
+      .sync_user_groups(provider, user, &["dev".into()], &[])
+      .await
+      .unwrap();
+    assert!(changed);
+
+    let groups: Vec<Uuid> = db
+      .user()
+      .get_user_groups(user)
+      .await
+      .unwrap()
+      .into_iter()
+      .map(|g| g.uuid)
+      .collect();
+    assert!(groups.contains(&devs));
+    assert!(!groups.contains(&ops));
+    // unmapped groups are left alone
+    assert!(groups.contains(&local));
+
+    // a second sync with the same claims is a no-op
+    assert!(
+      !db
+        .oidc_provider()
+        .sync_user_groups(provider, user, &["dev".into()], &[])
+        .await
+        .unwrap()
+    );
+  }
+
+  #[tokio::test]
+  async fn sync_user_groups_respects_keep() {
+    let db = test_db().await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let admins = insert_group(&db, "admins").await;
+    add_user_to_group(&db, admins, user).await;
+
+    let mut data = provider_data("A", "https://a.example.com");
+    data.group_mappings = vec![OidcGroupMapping {
+      claim_value: "admin".into(),
+      group: admins,
+    }];
+    let provider = db.oidc_provider().create(data).await.unwrap();
+
+    assert!(
+      !db
+        .oidc_provider()
+        .sync_user_groups(provider, user, &[], &[admins])
+        .await
+        .unwrap()
+    );
+    assert!(db.group().is_in_group(admins, user).await.unwrap());
+  }
+}
@@ -0,0 +1,137 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use entity::{account_deletion, prelude::*};
+use schemars::JsonSchema;
+use sea_orm::{ActiveValue::Set, prelude::*, sea_query::OnConflict};
+use serde::Serialize;
+use uuid::Uuid;
+
+#[derive(Serialize, JsonSchema)]
+pub struct AccountDeletionInfo {
+  pub transfer_notes_to: Option<Uuid>,
+  pub requested_at: NaiveDateTime,
+  pub delete_at: NaiveDateTime,
+}
+
+impl From<account_deletion::Model> for AccountDeletionInfo {
+  fn from(value: account_deletion::Model) -> Self {
+    Self {
+      transfer_notes_to: value.transfer_notes_to,
+      requested_at: value.requested_at,
+      delete_at: value.delete_at,
+    }
+  }
+}
+
+pub struct AccountDeletionTable<'db> {
+  db: &'db DatabaseConnection,
+}
+
+impl<'db> AccountDeletionTable<'db> {
+  pub fn new(db: &'db DatabaseConnection) -> Self {
+    Self { db }
+  }
+
+  /// Creates the request or replaces an existing one for the user.
+  pub async fn request(
+    &self,
+    user_id: Uuid,
+    transfer_notes_to: Option<Uuid>,
+    delete_at: DateTime<Utc>,
+  ) -> Result<(), DbErr> {
+    AccountDeletion::insert(account_deletion::ActiveModel {
+      user_id: Set(user_id),
+      transfer_notes_to: Set(transfer_notes_to),
+      requested_at: Set(Utc::now().naive_utc()),
+      delete_at: Set(delete_at.naive_utc()),
+    })
+    .on_conflict(
+      OnConflict::column(account_deletion::Column::UserId)
+        .update_columns([
+          account_deletion::Column::TransferNotesTo,
+          account_deletion::Column::RequestedAt,
+          account_deletion::Column::DeleteAt,
+        ])
+        .to_owned(),
+    )
+    .exec_without_returning(self.db)
+    .await?;
+    Ok(())
+  }
+
+  pub async fn get(&self, user_id: Uuid) -> Result<Option<AccountDeletionInfo>, DbErr> {
+    Ok(
+      AccountDeletion::find_by_id(user_id)
+        .one(self.db)
+        .await?
+        .map(AccountDeletionInfo::from),
+    )
+  }
+
+  /// Returns whether a pending request was withdrawn.
+  pub async fn cancel(&self, user_id: Uuid) -> Result<bool, DbErr> {
+    let res = AccountDeletion::delete_by_id(user_id).exec(self.db).await?;
+    Ok(res.rows_affected > 0)
+  }
+
+  pub async fn list_due(&self, now: DateTime<Utc>) -> Result<Vec<account_deletion::Model>, DbErr> {
+    AccountDeletion::find()
+      .filter(account_deletion::Column::DeleteAt.lte(now.naive_utc()))
+      .all(self.db)
+      .await
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use chrono::{Duration, Utc};
+
+  use crate::db::{
+    DBTrait,
+    test::{insert_user, test_db},
+  };
+
+  #[tokio::test]
+  async fn request_replaces_and_cancel_withdraws() {
+    let db = test_db().await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let other = insert_user(&db, "o", "o@x.com").await;
+
+    assert!(db.account_deletion().get(user).await.unwrap().is_none());
+    db.account_deletion()
+      .request(user, None, Utc::now() + Duration::days(1))
+      .await
+      .unwrap();
+    db.account_deletion()
+      .request(user, Some(other), Utc::now() + Duration::days(2))
+      .await
+      .unwrap();
+
+    let info = db.account_deletion().get(user).await.unwrap().unwrap();
+    assert_eq!(info.transfer_notes_to, Some(other));
+    assert!(info.delete_at > (Utc::now() + Duration::days(1)).naive_utc());
+
+    assert!(db.account_deletion().cancel(user).await.unwrap());
+    assert!(!db.account_deletion().cancel(user).await.unwrap());
+    assert!(db.account_deletion().get(user).await.unwrap().is_none());
+  }
+
+  #[tokio::test]
+  async fn only_due_requests_are_listed() {
+    let db = test_db().await;
+    let due = insert_user(&db, "d", "d@x.com").await;
+    let later = insert_user(&db, "l", "l@x.com").await;
+
+    db.account_deletion()
+      .request(due, None, Utc::now() - Duration::minutes(1))
+      .await
+      .unwrap();
+    db.account_deletion()
+      .request(later, None, Utc::now() + Duration::days(1))
+      .await
+      .unwrap();
+
+    let rows = db.account_deletion().list_due(Utc::now()).await.unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].user_id, due);
+  }
+}
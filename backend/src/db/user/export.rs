@@ -0,0 +1,208 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use entity::{data_export, prelude::*, sea_orm_active_enums::DataExportStatus};
+use schemars::JsonSchema;
+use sea_orm::{ActiveValue::Set, QueryOrder, prelude::*};
+use serde::Serialize;
+use uuid::Uuid;
+
+#[derive(Serialize, JsonSchema)]
+pub struct DataExportInfo {
+  pub uuid: Uuid,
+  pub status: DataExportStatus,
+  pub size: Option<i64>,
+  pub created_at: NaiveDateTime,
+  pub finished_at: Option<NaiveDateTime>,
+  pub expires_at: NaiveDateTime,
+}
+
+impl From<data_export::Model> for DataExportInfo {
+  fn from(value: data_export::Model) -> Self {
+    Self {
+      uuid: value.id,
+      status: value.status,
+      size: value.size,
+      created_at: value.created_at,
+      finished_at: value.finished_at,
+      expires_at: value.expires_at,
+    }
+  }
+}
+
+pub struct DataExportTable<'db> {
+  db: &'db DatabaseConnection,
+}
+
+impl<'db> DataExportTable<'db> {
+  pub fn new(db: &'db DatabaseConnection) -> Self {
+    Self { db }
+  }
+
+  pub async fn create(
+    &self,
+    user_id: Uuid,
+    send_mail: bool,
+    expires_at: DateTime<Utc>,
+  ) -> Result<Uuid, DbErr> {
+    let id = Uuid::now_v7();
+    data_export::ActiveModel {
+      id: Set(id),
+      user_id: Set(user_id),
+      status: Set(DataExportStatus::Pending),
+      send_mail: Set(send_mail),
+      size: Set(None),
+      created_at: Set(Utc::now().naive_utc()),
+      finished_at: Set(None),
+      expires_at: Set(expires_at.naive_utc()),
+    }
+    .insert(self.db)
+    .await?;
+
+    Ok(id)
+  }
+
+  pub async fn get(&self, id: Uuid, user_id: Uuid) -> Result<Option<data_export::Model>, DbErr> {
+    DataExport::find_by_id(id)
+      .filter(data_export::Column::UserId.eq(user_id))
+      .one(self.db)
+      .await
+  }
+
+  pub async fn has_pending(&self, user_id: Uuid) -> Result<bool, DbErr> {
+    let count = DataExport::find()
+      .filter(data_export::Column::UserId.eq(user_id))
+      .filter(data_export::Column::Status.eq(DataExportStatus::Pending))
+      .count(self.db)
+      .await?;
+    Ok(count > 0)
+  }
+
+  pub async fn list_for_user(&self, user_id: Uuid) -> Result<Vec<DataExportInfo>, DbErr> {
+    Ok(
+      DataExport::find()
+        .filter(data_export::Column::UserId.eq(user_id))
+        .order_by_desc(data_export::Column::CreatedAt)
+        .all(self.db)
+        .await?
+        .into_iter()
+        .map(DataExportInfo::from)
+        .collect(),
+    )
+  }
+
+  pub async fn finish(
+    &self,
+    id: Uuid,
+    status: DataExportStatus,
+    size: Option<i64>,
+  ) -> Result<(), DbErr> {
+    DataExport::update_many()
+      .col_expr(data_export::Column::Status, Expr::value(status))
+      .col_expr(data_export::Column::Size, Expr::value(size))
+      .col_expr(
+        data_export::Column::FinishedAt,
+        Expr::value(Utc::now().naive_utc()),
+      )
+      .filter(data_export::Column::Id.eq(id))
+      .exec(self.db)
+      .await?;
+    Ok(())
+  }
+
+  /// Jobs run in-process, anything still pending after a restart was lost.
+  pub async fn fail_pending(&self) -> Result<u64, DbErr> {
+    let res = DataExport::update_many()
+      .col_expr(
+        data_export::Column::Status,
+        Expr::value(DataExportStatus::Failed),
+      )
+      .col_expr(
+        data_export::Column::FinishedAt,
+        Expr::value(Utc::now().naive_utc()),
+      )
+      .filter(data_export::Column::Status.eq(DataExportStatus::Pending))
+      .exec(self.db)
+      .await?;
+    Ok(res.rows_affected)
+  }
+
+  pub async fn list_expired(&self) -> Result<Vec<data_export::Model>, DbErr> {
+    DataExport::find()
+      .filter(data_export::Column::ExpiresAt.lt(Utc::now().naive_utc()))
+      .filter(data_export::Column::Status.ne(DataExportStatus::Pending))
+      .all(self.db)
+      .await
+  }
+
+  pub async fn delete(&self, id: Uuid) -> Result<(), DbErr> {
+    DataExport::delete_by_id(id).exec(self.db).await?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use chrono::{Duration, Utc};
+  use entity::sea_orm_active_enums::DataExportStatus;
+
+  use crate::db::{
+    DBTrait,
+    test::{insert_user, test_db},
+  };
+
+  #[tokio::test]
+  async fn lifecycle() {
+    let db = test_db().await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let other = insert_user(&db, "o", "o@x.com").await;
+
+    let id = db
+      .data_export()
+      .create(user, false, Utc::now() + Duration::days(1))
+      .await
+      .unwrap();
+    assert!(db.data_export().has_pending(user).await.unwrap());
+    assert!(db.data_export().get(id, other).await.unwrap().is_none());
+
+    db.data_export()
+      .finish(id, DataExportStatus::Ready, Some(42))
+      .await
+      .unwrap();
+    assert!(!db.data_export().has_pending(user).await.unwrap());
+
+    let list = db.data_export().list_for_user(user).await.unwrap();
+    assert_eq!(list.len(), 1);
+    assert_eq!(list[0].status, DataExportStatus::Ready);
+    assert_eq!(list[0].size, Some(42));
+    assert!(list[0].finished_at.is_some());
+    assert!(
+      db.data_export()
+        .list_for_user(other)
+        .await
+        .unwrap()
+        .is_empty()
+    );
+  }
+
+  #[tokio::test]
+  async fn pending_jobs_fail_and_expired_jobs_are_listed() {
+    let db = test_db().await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+
+    let pending = db
+      .data_export()
+      .create(user, false, Utc::now() - Duration::hours(1))
+      .await
+      .unwrap();
+    // pending jobs are never cleaned up underneath a running export
+    assert!(db.data_export().list_expired().await.unwrap().is_empty());
+
+    assert_eq!(db.data_export().fail_pending().await.unwrap(), 1);
+    let row = db.data_export().get(pending, user).await.unwrap().unwrap();
+    assert_eq!(row.status, DataExportStatus::Failed);
+
+    let expired = db.data_export().list_expired().await.unwrap();
+    assert_eq!(expired.len(), 1);
+    db.data_export().delete(pending).await.unwrap();
+    assert!(db.data_export().get(pending, user).await.unwrap().is_none());
+  }
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use entity::{impersonation_audit, prelude::*, user};
+use schemars::JsonSchema;
+use sea_orm::{ActiveValue::Set, QueryOrder, QuerySelect, prelude::*};
+use serde::Serialize;
+
+#[derive(Serialize, Debug, JsonSchema)]
+pub struct ImpersonationAuditInfo {
+  pub uuid: Uuid,
+  pub admin: Uuid,
+  pub admin_name: Option<String>,
+  pub user: Uuid,
+  pub user_name: Option<String>,
+  pub reason: String,
+  pub started_at: chrono::NaiveDateTime,
+  pub expires_at: chrono::NaiveDateTime,
+  pub ended_at: Option<chrono::NaiveDateTime>,
+}
+
+pub struct ImpersonationTable<'db> {
+  db: &'db DatabaseConnection,
+}
+
+impl<'db> ImpersonationTable<'db> {
+  pub fn new(db: &'db DatabaseConnection) -> Self {
+    Self { db }
+  }
+
+  pub async fn start(
+    &self,
+    admin: Uuid,
+    user: Uuid,
+    session: Uuid,
+    reason: String,
+    expires_at: DateTime<Utc>,
+  ) -> Result<Uuid, DbErr> {
+    let id = Uuid::now_v7();
+    impersonation_audit::ActiveModel {
+      id: Set(id),
+      admin_id: Set(admin),
+      user_id: Set(user),
+      session_id: Set(session),
+      reason: Set(reason),
+      started_at: Set(Utc::now().naive_utc()),
+      expires_at: Set(expires_at.naive_utc()),
+      ended_at: Set(None),
+    }
+    .insert(self.db)
+    .await?;
+
+    Ok(id)
+  }
+
+  pub async fn stop(&self, session: Uuid) -> Result<(), DbErr> {
+    ImpersonationAudit::update_many()
+      .col_expr(
+        impersonation_audit::Column::EndedAt,
+        Expr::value(Utc::now().naive_utc()),
+      )
+      .filter(impersonation_audit::Column::SessionId.eq(session))
+      .filter(impersonation_audit::Column::EndedAt.is_null())
+      .exec(self.db)
+      .await?;
+    Ok(())
+  }
+
+  pub async fn list(&self, limit: u64) -> Result<Vec<ImpersonationAuditInfo>, DbErr> {
+    let entries = ImpersonationAudit::find()
+      .order_by_desc(impersonation_audit::Column::StartedAt)
+      .limit(limit)
+      .all(self.db)
+      .await?;
+
+    let ids = entries
+      .iter()
+      .flat_map(|e| [e.admin_id, e.user_id])
+      .collect::<Vec<_>>();
+    let names = User::find()
+      .filter(user::Column::Id.is_in(ids))
+      .all(self.db)
+      .await?
+      .into_iter()
+      .map(|u| (u.id, u.name))
+      .collect::<HashMap<_, _>>();
+
+    Ok(
+      entries
+        .into_iter()
+        .map(|e| ImpersonationAuditInfo {
+          uuid: e.id,
+          admin: e.admin_id,
+          admin_name: names.get(&e.admin_id).cloned(),
+          user: e.user_id,
+          user_name: names.get(&e.user_id).cloned(),
+          reason: e.reason,
+          started_at: e.started_at,
+          expires_at: e.expires_at,
+          ended_at: e.ended_at,
+        })
+        .collect(),
+    )
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use chrono::{Duration, Utc};
+  use uuid::Uuid;
+
+  use crate::db::{
+    DBTrait,
+    test::{insert_user, test_db},
+  };
+
+  #[tokio::test]
+  async fn start_stop_and_list() {
+    let db = test_db().await;
+    let admin = insert_user(&db, "Admin", "a@x.com").await;
+    let user = insert_user(&db, "User", "u@x.com").await;
+    let session = Uuid::new_v4();
+
+    db.impersonation()
+      .start(
+        admin,
+        user,
+        session,
+        "ticket 42".into(),
+        Utc::now() + Duration::minutes(15),
+      )
+      .await
+      .unwrap();
+
+    let list = db.impersonation().list(10).await.unwrap();
+    assert_eq!(list.len(), 1);
+    assert_eq!(list[0].admin_name.as_deref(), Some("Admin"));
+    assert_eq!(list[0].user_name.as_deref(), Some("User"));
+    assert_eq!(list[0].reason, "ticket 42");
+    assert!(list[0].ended_at.is_none());
+
+    db.impersonation().stop(session).await.unwrap();
+    let list = db.impersonation().list(10).await.unwrap();
+    assert!(list[0].ended_at.is_some());
+  }
+
+  #[tokio::test]
+  async fn entries_survive_user_deletion() {
+    use entity::user;
+    use sea_orm::EntityTrait;
+
+    let db = test_db().await;
+    let admin = insert_user(&db, "Admin", "a@x.com").await;
+    let user_id = insert_user(&db, "User", "u@x.com").await;
+    db.impersonation()
+      .start(admin, user_id, Uuid::new_v4(), String::new(), Utc::now())
+      .await
+      .unwrap();
+
+    user::Entity::delete_by_id(user_id)
+      .exec(&db.0)
+      .await
+      .unwrap();
+
+    let list = db.impersonation().list(10).await.unwrap();
+    assert_eq!(list.len(), 1);
+    assert_eq!(list[0].user, user_id);
+    assert!(list[0].user_name.is_none());
+  }
+}
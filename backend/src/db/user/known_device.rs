@@ -0,0 +1,76 @@
+use chrono::Utc;
+use entity::{known_device, prelude::*};
+use sea_orm::{ActiveValue::Set, prelude::*};
+use uuid::Uuid;
+
+pub struct KnownDeviceTable<'db> {
+  db: &'db DatabaseConnection,
+}
+
+impl<'db> KnownDeviceTable<'db> {
+  pub fn new(db: &'db DatabaseConnection) -> Self {
+    Self { db }
+  }
+
+  /// Records a sign-in from the device and returns whether the user had
+  /// signed in from it before.
+  pub async fn remember(&self, user_id: Uuid, fingerprint: &str) -> Result<bool, DbErr> {
+    let now = Utc::now().naive_utc();
+    if let Some(row) = KnownDevice::find_by_id((user_id, fingerprint.to_string()))
+      .one(self.db)
+      .await?
+    {
+      let mut row: known_device::ActiveModel = row.into();
+      row.last_seen_at = Set(now);
+      row.update(self.db).await?;
+      return Ok(true);
+    }
+
+    KnownDevice::insert(known_device::ActiveModel {
+      user_id: Set(user_id),
+      fingerprint: Set(fingerprint.to_string()),
+      first_seen_at: Set(now),
+      last_seen_at: Set(now),
+    })
+    .exec_without_returning(self.db)
+    .await?;
+    Ok(false)
+  }
+
+  pub async fn count_for_user(&self, user_id: Uuid) -> Result<u64, DbErr> {
+    KnownDevice::find()
+      .filter(known_device::Column::UserId.eq(user_id))
+      .count(self.db)
+      .await
+  }
+
+  pub async fn forget(&self, user_id: Uuid, fingerprint: &str) -> Result<(), DbErr> {
+    KnownDevice::delete_by_id((user_id, fingerprint.to_string()))
+      .exec(self.db)
+      .await?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::db::{
+    DBTrait,
+    test::{insert_user, test_db},
+  };
+
+  #[tokio::test]
+  async fn remember_reports_known_devices_per_user() {
+    let db = test_db().await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let other = insert_user(&db, "o", "o@x.com").await;
+
+    assert!(!db.known_device().remember(user, "fp").await.unwrap());
+    assert!(db.known_device().remember(user, "fp").await.unwrap());
+    assert!(!db.known_device().remember(other, "fp").await.unwrap());
+    assert_eq!(db.known_device().count_for_user(user).await.unwrap(), 1);
+
+    db.known_device().forget(user, "fp").await.unwrap();
+    assert!(!db.known_device().remember(user, "fp").await.unwrap());
+  }
+}
@@ -1,3 +1,7 @@
+pub mod deletion;
+pub mod export;
+pub mod impersonation;
+pub mod known_device;
 pub mod passkey;
 pub mod session;
 pub mod settings;
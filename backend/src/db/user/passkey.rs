@@ -56,11 +56,17 @@ impl<'db> PasskeyTable<'db> {
     res.ok_or(DbErr::RecordNotFound("Not Found".into()))
   }
 
-  pub async fn update_passkey_record(&self, id: Uuid, data: String) -> Result<(), DbErr> {
+  pub async fn update_passkey_record(
+    &self,
+    id: Uuid,
+    data: String,
+    backup_state: bool,
+  ) -> Result<(), DbErr> {
     let mut key: passkey::ActiveModel = self.get_passkey(id).await?.into();
 
     key.data = Set(data);
     key.used = Set(Utc::now().naive_utc());
+    key.backup_state = Set(backup_state);
 
     key.update(self.db).await?;
 
@@ -124,6 +130,11 @@ mod test {
       user_id,
       created: Utc::now().naive_utc(),
       used: Utc::now().naive_utc(),
+      aaguid: None,
+      authenticator_type: "unknown".to_string(),
+      user_verified: true,
+      backup_eligible: false,
+      backup_state: false,
     }
   }
 
@@ -220,15 +231,17 @@ mod test {
     let id = insert_passkey(&db, user, "a", "credA").await;
 
     db.passkey()
-      .update_passkey_record(id, "newdata".into())
+      .update_passkey_record(id, "newdata".into(), true)
       .await
       .unwrap();
-    assert_eq!(db.passkey().get_passkey(id).await.unwrap().data, "newdata");
+    let key = db.passkey().get_passkey(id).await.unwrap();
+    assert_eq!(key.data, "newdata");
+    assert!(key.backup_state);
 
     // updating a missing record errors (get_passkey inside fails)
     assert!(
       db.passkey()
-        .update_passkey_record(Uuid::new_v4(), "x".into())
+        .update_passkey_record(Uuid::new_v4(), "x".into(), false)
         .await
         .is_err()
     );
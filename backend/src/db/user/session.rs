@@ -1,8 +1,18 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDateTime, Utc};
 use entity::{prelude::*, session};
-use sea_orm::{ActiveValue::Set, QueryOrder, prelude::*};
+use sea_orm::{ActiveValue::Set, Condition, QueryOrder, prelude::*};
 use uuid::Uuid;
 
+/// Where a session signed in from.
+#[derive(Default)]
+pub struct SessionDevice {
+  pub ip: Option<String>,
+  pub fingerprint: String,
+  pub new_device: bool,
+  /// Hash of the token behind the one-click revoke link.
+  pub revoke_token: Option<String>,
+}
+
 pub struct SessionTable<'db> {
   db: &'db DatabaseConnection,
 }
@@ -22,6 +32,8 @@ impl<'db> SessionTable<'db> {
     name: String,
     application: String,
     operating_system: String,
+    auth_methods: String,
+    device: SessionDevice,
   ) -> Result<(), DbErr> {
     let now = Utc::now().naive_utc();
     session::Entity::insert(session::ActiveModel {
@@ -36,12 +48,53 @@ impl<'db> SessionTable<'db> {
       name: Set(name),
       application: Set(application),
       operating_system: Set(operating_system),
+      impersonator_id: Set(None),
+      auth_methods: Set(auth_methods),
+      ip: Set(device.ip),
+      fingerprint: Set(device.fingerprint),
+      new_device: Set(device.new_device),
+      revoke_token: Set(device.revoke_token),
+      enrolment_only: Set(false),
     })
     .exec(self.db)
     .await?;
     Ok(())
   }
 
+  pub async fn create_impersonation(
+    &self,
+    user_id: Uuid,
+    impersonator: Uuid,
+    token: String,
+    expires_at: DateTime<Utc>,
+  ) -> Result<Uuid, DbErr> {
+    let id = Uuid::now_v7();
+    let now = Utc::now().naive_utc();
+    session::Entity::insert(session::ActiveModel {
+      id: Set(id),
+      token: Set(token),
+      user_id: Set(user_id),
+      is_app: Set(false),
+      expires_at: Set(expires_at.naive_utc()),
+      created_at: Set(now),
+      last_used_at: Set(now),
+      refreshed_at: Set(None),
+      name: Set("Impersonation".into()),
+      application: Set(String::new()),
+      operating_system: Set(String::new()),
+      impersonator_id: Set(Some(impersonator)),
+      auth_methods: Set(String::new()),
+      ip: Set(None),
+      fingerprint: Set(String::new()),
+      new_device: Set(false),
+      revoke_token: Set(None),
+      enrolment_only: Set(false),
+    })
+    .exec(self.db)
+    .await?;
+    Ok(id)
+  }
+
   pub async fn get_by_token(&self, token: &str) -> Result<session::Model, DbErr> {
     Session::find()
       .filter(session::Column::Token.eq(token))
@@ -50,6 +103,14 @@ impl<'db> SessionTable<'db> {
       .ok_or(DbErr::RecordNotFound("session not found".into()))
   }
 
+  pub async fn get_by_revoke_token(&self, token: &str) -> Result<session::Model, DbErr> {
+    Session::find()
+      .filter(session::Column::RevokeToken.eq(token))
+      .one(self.db)
+      .await?
+      .ok_or(DbErr::RecordNotFound("session not found".into()))
+  }
+
   pub async fn touch_last_used(&self, token: &str) -> Result<(), DbErr> {
     let mut row: session::ActiveModel = self.get_by_token(token).await?.into();
     row.last_used_at = Set(Utc::now().naive_utc());
@@ -57,6 +118,23 @@ impl<'db> SessionTable<'db> {
     Ok(())
   }
 
+  /// Limits the session to setting up MFA.
+  pub async fn restrict_to_enrolment(&self, token: &str) -> Result<(), DbErr> {
+    let mut row: session::ActiveModel = self.get_by_token(token).await?.into();
+    row.enrolment_only = Set(true);
+    row.update(self.db).await?;
+    Ok(())
+  }
+
+  pub async fn lift_enrolment_restriction(&self, user_id: Uuid) -> Result<(), DbErr> {
+    Session::update_many()
+      .col_expr(session::Column::EnrolmentOnly, Expr::value(false))
+      .filter(session::Column::UserId.eq(user_id))
+      .exec(self.db)
+      .await?;
+    Ok(())
+  }
+
   pub async fn refresh(
     &self,
     old_token: &str,
@@ -99,6 +177,41 @@ impl<'db> SessionTable<'db> {
     Ok(())
   }
 
+  pub async fn delete_for_user(&self, user_id: Uuid) -> Result<u64, DbErr> {
+    let res = Session::delete_many()
+      .filter(session::Column::UserId.eq(user_id))
+      .exec(self.db)
+      .await?;
+    Ok(res.rows_affected)
+  }
+
+  /// Deletes sessions of one kind that went unused since `idle_before` or
+  /// were created before `created_before`.
+  pub async fn delete_outside_limits(
+    &self,
+    is_app: bool,
+    idle_before: Option<NaiveDateTime>,
+    created_before: Option<NaiveDateTime>,
+  ) -> Result<u64, DbErr> {
+    let mut violates = Condition::any();
+    if let Some(idle_before) = idle_before {
+      violates = violates.add(session::Column::LastUsedAt.lt(idle_before));
+    }
+    if let Some(created_before) = created_before {
+      violates = violates.add(session::Column::CreatedAt.lt(created_before));
+    }
+    if violates.is_empty() {
+      return Ok(0);
+    }
+
+    let res = Session::delete_many()
+      .filter(session::Column::IsApp.eq(is_app))
+      .filter(violates)
+      .exec(self.db)
+      .await?;
+    Ok(res.rows_affected)
+  }
+
   pub async fn delete_expired(&self) -> Result<u64, DbErr> {
     let now = Utc::now().naive_utc();
     let res = Session::delete_many()
@@ -133,6 +246,8 @@ mod test {
         "".to_string(),
         "".to_string(),
         "".to_string(),
+        String::new(),
+        Default::default(),
       )
       .await
       .unwrap();
@@ -158,6 +273,8 @@ mod test {
         "".to_string(),
         "".to_string(),
         "".to_string(),
+        String::new(),
+        Default::default(),
       )
       .await
       .unwrap();
@@ -187,6 +304,8 @@ mod test {
         "".to_string(),
         "".to_string(),
         "".to_string(),
+        String::new(),
+        Default::default(),
       )
       .await
       .unwrap();
@@ -222,6 +341,8 @@ mod test {
         "My Laptop".into(),
         "Firefox".into(),
         "Linux".into(),
+        String::new(),
+        Default::default(),
       )
       .await
       .unwrap();
@@ -247,6 +368,8 @@ mod test {
         "".into(),
         "".into(),
         "".into(),
+        String::new(),
+        Default::default(),
       )
       .await
       .unwrap();
@@ -275,6 +398,8 @@ mod test {
         "".to_string(),
         "".to_string(),
         "".to_string(),
+        String::new(),
+        Default::default(),
       )
       .await
       .unwrap();
@@ -313,6 +438,8 @@ mod test {
         "".into(),
         "".into(),
         "".into(),
+        String::new(),
+        Default::default(),
       )
       .await
       .unwrap();
@@ -350,6 +477,8 @@ mod test {
         "".into(),
         "".into(),
         "".into(),
+        String::new(),
+        Default::default(),
       )
       .await
       .unwrap();
@@ -362,6 +491,8 @@ mod test {
         "".into(),
         "".into(),
         "".into(),
+        String::new(),
+        Default::default(),
       )
       .await
       .unwrap();
@@ -374,6 +505,8 @@ mod test {
         "".into(),
         "".into(),
         "".into(),
+        String::new(),
+        Default::default(),
       )
       .await
       .unwrap();
@@ -403,6 +536,8 @@ mod test {
         "".to_string(),
         "".to_string(),
         "".to_string(),
+        String::new(),
+        Default::default(),
       )
       .await
       .unwrap();
@@ -415,6 +550,8 @@ mod test {
         "".to_string(),
         "".to_string(),
         "".to_string(),
+        String::new(),
+        Default::default(),
       )
       .await
       .unwrap();
@@ -424,4 +561,74 @@ mod test {
     assert_eq!(remaining.len(), 1);
     assert_eq!(remaining[0].token, "active");
   }
+
+  #[tokio::test]
+  async fn create_impersonation_records_impersonator() {
+    let db = test_db().await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let admin = insert_user(&db, "a", "a@x.com").await;
+
+    let id = db
+      .session()
+      .create_impersonation(user, admin, "imp".into(), Utc::now())
+      .await
+      .unwrap();
+
+    let row = db.session().get_by_token("imp").await.unwrap();
+    assert_eq!(row.id, id);
+    assert_eq!(row.user_id, user);
+    assert_eq!(row.impersonator_id, Some(admin));
+    assert_eq!(db.session().list_for_user(user).await.unwrap().len(), 1);
+  }
+
+  #[tokio::test]
+  async fn delete_outside_limits_only_removes_violators_of_one_kind() {
+    use chrono::Duration;
+    use entity::session;
+    use sea_orm::{ActiveModelTrait, ActiveValue::Set};
+
+    let db = test_db().await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let now = Utc::now();
+    for (token, is_app) in [("idle", false), ("fresh", false), ("idle-app", true)] {
+      db.session()
+        .create(
+          user,
+          token.into(),
+          is_app,
+          now + Duration::days(1),
+          String::new(),
+          String::new(),
+          String::new(),
+          String::new(),
+          Default::default(),
+        )
+        .await
+        .unwrap();
+    }
+    for token in ["idle", "idle-app"] {
+      let mut row: session::ActiveModel = db.session().get_by_token(token).await.unwrap().into();
+      row.last_used_at = Set((now - Duration::hours(2)).naive_utc());
+      row.update(&db.0).await.unwrap();
+    }
+
+    let cutoff = (now - Duration::hours(1)).naive_utc();
+    assert_eq!(
+      db.session()
+        .delete_outside_limits(false, Some(cutoff), None)
+        .await
+        .unwrap(),
+      1
+    );
+    assert_eq!(
+      db.session()
+        .delete_outside_limits(true, None, None)
+        .await
+        .unwrap(),
+      0
+    );
+    assert!(db.session().get_by_token("idle").await.is_err());
+    assert!(db.session().get_by_token("fresh").await.is_ok());
+    assert!(db.session().get_by_token("idle-app").await.is_ok());
+  }
 }
@@ -1,7 +1,50 @@
-use entity::{prelude::*, user, user_avatar};
-use sea_orm::{ActiveValue::Set, prelude::*};
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use entity::{passkey, prelude::*, user, user_avatar};
+use schemars::JsonSchema;
+use sea_orm::{ActiveValue::Set, QuerySelect, prelude::*};
+use serde::Serialize;
 use uuid::Uuid;
 
+#[derive(Serialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct Suspension {
+  pub uuid: Uuid,
+  pub reason: Option<String>,
+  pub until: Option<NaiveDateTime>,
+}
+
+impl Suspension {
+  /// The suspension currently in effect, expired ones no longer count.
+  pub fn active(user: &user::Model) -> Option<Self> {
+    if !user.disabled
+      || user
+        .disabled_until
+        .is_some_and(|until| until <= Utc::now().naive_utc())
+    {
+      return None;
+    }
+
+    Some(Self {
+      uuid: user.id,
+      reason: user.disabled_reason.clone(),
+      until: user.disabled_until,
+    })
+  }
+}
+
+#[derive(Serialize, JsonSchema, Default, Clone, Copy)]
+pub struct MfaEnrolment {
+  pub totp: bool,
+  pub passkeys: u64,
+}
+
+impl MfaEnrolment {
+  pub fn enrolled(&self) -> bool {
+    self.totp || self.passkeys > 0
+  }
+}
+
 pub struct UserExtTable<'db> {
   db: &'db DatabaseConnection,
 }
@@ -46,6 +89,94 @@ impl<'db> UserExtTable<'db> {
     Ok(())
   }
 
+  pub async fn mfa_enrolment(&self, id: Uuid) -> Result<MfaEnrolment, DbErr> {
+    let user = self.get_user_by_id(id).await?;
+    let passkeys = Passkey::find()
+      .filter(passkey::Column::UserId.eq(id))
+      .count(self.db)
+      .await?;
+    Ok(MfaEnrolment {
+      totp: user.totp.is_some(),
+      passkeys,
+    })
+  }
+
+  pub async fn list_mfa_enrolment(&self) -> Result<HashMap<Uuid, MfaEnrolment>, DbErr> {
+    let mut enrolment: HashMap<Uuid, MfaEnrolment> = User::find()
+      .all(self.db)
+      .await?
+      .into_iter()
+      .map(|user| {
+        (
+          user.id,
+          MfaEnrolment {
+            totp: user.totp.is_some(),
+            passkeys: 0,
+          },
+        )
+      })
+      .collect();
+
+    let passkeys: Vec<Uuid> = Passkey::find()
+      .select_only()
+      .column(passkey::Column::UserId)
+      .into_tuple()
+      .all(self.db)
+      .await?;
+    for user in passkeys {
+      enrolment.entry(user).or_default().passkeys += 1;
+    }
+
+    Ok(enrolment)
+  }
+
+  pub async fn suspension(&self, id: Uuid) -> Result<Option<Suspension>, DbErr> {
+    Ok(Suspension::active(&self.get_user_by_id(id).await?))
+  }
+
+  pub async fn list_suspended(&self) -> Result<Vec<Suspension>, DbErr> {
+    Ok(
+      User::find()
+        .filter(user::Column::Disabled.eq(true))
+        .all(self.db)
+        .await?
+        .iter()
+        .filter_map(Suspension::active)
+        .collect(),
+    )
+  }
+
+  /// Disables the account and invalidates every refresh token issued so far.
+  pub async fn suspend(
+    &self,
+    id: Uuid,
+    reason: Option<String>,
+    until: Option<DateTime<Utc>>,
+  ) -> Result<(), DbErr> {
+    let mut user: user::ActiveModel = self.get_user_by_id(id).await?.into();
+
+    user.disabled = Set(true);
+    user.disabled_reason = Set(reason);
+    user.disabled_until = Set(until.map(|until| until.naive_utc()));
+    user.tokens_revoked_at = Set(Some(Utc::now().naive_utc()));
+
+    user.update(self.db).await?;
+
+    Ok(())
+  }
+
+  pub async fn resume(&self, id: Uuid) -> Result<(), DbErr> {
+    let mut user: user::ActiveModel = self.get_user_by_id(id).await?.into();
+
+    user.disabled = Set(false);
+    user.disabled_reason = Set(None);
+    user.disabled_until = Set(None);
+
+    user.update(self.db).await?;
+
+    Ok(())
+  }
+
   pub async fn has_avatar(&self, uuid: Uuid) -> Result<bool, DbErr> {
     let count = user_avatar::Entity::find()
       .filter(user_avatar::Column::UserId.eq(uuid))
@@ -62,6 +193,7 @@ mod test {
     DBTrait,
     test::{insert_user, test_db},
   };
+  use chrono::{Duration, Utc};
   use entity::user_avatar;
   use sea_orm::{ActiveValue::Set, DbErr, EntityTrait};
   use uuid::Uuid;
@@ -150,4 +282,47 @@ mod test {
 
     assert!(db.user_ext().has_avatar(user).await.unwrap());
   }
+
+  #[tokio::test]
+  async fn suspend_and_resume() {
+    let db = test_db().await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    assert!(db.user_ext().suspension(user).await.unwrap().is_none());
+
+    db.user_ext()
+      .suspend(user, Some("spam".into()), None)
+      .await
+      .unwrap();
+    let suspension = db.user_ext().suspension(user).await.unwrap().unwrap();
+    assert_eq!(suspension.reason.as_deref(), Some("spam"));
+    assert_eq!(db.user_ext().list_suspended().await.unwrap(), [suspension]);
+    let revoked_at = db.user_ext().get_user_by_id(user).await.unwrap();
+    assert!(revoked_at.tokens_revoked_at.is_some());
+
+    db.user_ext().resume(user).await.unwrap();
+    assert!(db.user_ext().suspension(user).await.unwrap().is_none());
+    assert!(db.user_ext().list_suspended().await.unwrap().is_empty());
+    // resuming does not make old refresh tokens valid again
+    assert_eq!(
+      db.user_ext()
+        .get_user_by_id(user)
+        .await
+        .unwrap()
+        .tokens_revoked_at,
+      revoked_at.tokens_revoked_at
+    );
+  }
+
+  #[tokio::test]
+  async fn expired_suspension_is_not_active() {
+    let db = test_db().await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+
+    db.user_ext()
+      .suspend(user, None, Some(Utc::now() - Duration::minutes(1)))
+      .await
+      .unwrap();
+    assert!(db.user_ext().suspension(user).await.unwrap().is_none());
+    assert!(db.user_ext().list_suspended().await.unwrap().is_empty());
+  }
 }
@@ -0,0 +1,314 @@
+use aide::axum::{
+  ApiRouter,
+  routing::{delete_with, get_with, post_with, put_with},
+};
+use axum::{Json, extract::Path};
+use centaurus::{
+  backend::{auth::jwt_auth::JwtAuth, endpoints::websocket::state::Updater},
+  bail,
+  db::{
+    init::Connection,
+    tables::{
+      ConnectionExt,
+      group::{GroupDetails, GroupInfo, SimpleUserInfo},
+    },
+  },
+  error::Result,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+  db::DBTrait,
+  utils::{GroupEdit, GroupView, UpdateMessage, lacks_permissions},
+};
+
+/// The centaurus group endpoints, with permissions also granted through
+/// parent groups.
+pub fn router() -> ApiRouter {
+  ApiRouter::new()
+    .api_route("/", get_with(list_groups, |op| op.id("listGroups")))
+    .api_route("/", post_with(create_group, |op| op.id("createGroup")))
+    .api_route("/", delete_with(delete_group, |op| op.id("deleteGroup")))
+    .api_route("/", put_with(edit_group, |op| op.id("editGroup")))
+    .api_route("/{uuid}", get_with(group_info, |op| op.id("groupInfo")))
+    .api_route(
+      "/users",
+      get_with(list_users_simple, |op| op.id("listUsersSimple")),
+    )
+}
+
+#[derive(Serialize, JsonSchema)]
+struct ListGroupResponse {
+  groups: Vec<GroupInfo>,
+  admin_group: Option<Uuid>,
+}
+
+async fn list_groups(_auth: JwtAuth<GroupView>, db: Connection) -> Result<Json<ListGroupResponse>> {
+  Ok(Json(ListGroupResponse {
+    groups: db.group().list_groups().await?,
+    admin_group: db.setup().get_admin_group_id().await?,
+  }))
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct GroupPath {
+  uuid: Uuid,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct GroupDetailsResponse {
+  group: GroupDetails,
+  admin_group: Uuid,
+}
+
+async fn group_info(
+  _auth: JwtAuth<GroupView>,
+  db: Connection,
+  Path(path): Path<GroupPath>,
+) -> Result<Json<GroupDetailsResponse>> {
+  let Some(group) = db.group().group_info(path.uuid).await? else {
+    bail!(NOT_FOUND, "Group not found");
+  };
+  let Some(admin_group) = db.setup().get_admin_group_id().await? else {
+    bail!(INTERNAL_SERVER_ERROR, "Admin group not configured");
+  };
+
+  Ok(Json(GroupDetailsResponse { group, admin_group }))
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct CreateGroupRequest {
+  name: String,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct GroupCreateResponse {
+  uuid: Uuid,
+}
+
+async fn create_group(
+  _auth: JwtAuth<GroupEdit>,
+  db: Connection,
+  updater: Updater<UpdateMessage>,
+  Json(data): Json<CreateGroupRequest>,
+) -> Result<Json<GroupCreateResponse>> {
+  if data.name.trim().is_empty() {
+    bail!(BAD_REQUEST, "Group name cannot be empty");
+  }
+  if db.group().find_group_by_name(&data.name).await?.is_some() {
+    bail!(CONFLICT, "A group with this name already exists");
+  }
+
+  let uuid = db.group().create_group(data.name).await?;
+  updater.broadcast(UpdateMessage::Group { uuid }).await;
+
+  Ok(Json(GroupCreateResponse { uuid }))
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct DeleteGroupRequest {
+  uuid: Uuid,
+}
+
+async fn delete_group(
+  _auth: JwtAuth<GroupEdit>,
+  db: Connection,
+  updater: Updater<UpdateMessage>,
+  Json(data): Json<DeleteGroupRequest>,
+) -> Result<()> {
+  if db.setup().get_admin_group_id().await? == Some(data.uuid) {
+    bail!(BAD_REQUEST, "Cannot delete the admin group");
+  }
+
+  // members of child groups lose what they inherited as well
+  let users = db.group_ext().members(&[data.uuid]).await?;
+  db.group().delete_group(data.uuid).await?;
+
+  updater
+    .broadcast(UpdateMessage::Group { uuid: data.uuid })
+    .await;
+  for user in users {
+    updater.send_to(user, UpdateMessage::UserPermissions).await;
+  }
+
+  Ok(())
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct EditGroupRequest {
+  uuid: Uuid,
+  name: String,
+  permissions: Vec<String>,
+  users: Vec<Uuid>,
+}
+
+async fn edit_group(
+  auth: JwtAuth<GroupEdit>,
+  db: Connection,
+  updater: Updater<UpdateMessage>,
+  Json(data): Json<EditGroupRequest>,
+) -> Result<()> {
+  if data.name.trim().is_empty() {
+    bail!(BAD_REQUEST, "Group name cannot be empty");
+  }
+  if let Some(existing) = db.group().find_group_by_name(&data.name).await?
+    && existing != data.uuid
+  {
+    bail!(CONFLICT, "A group with this name already exists");
+  }
+  let Some(group) = db.group().group_info(data.uuid).await? else {
+    bail!(NOT_FOUND, "Group not found");
+  };
+
+  if lacks_permissions(&db, auth.user_id, &group.permissions).await? {
+    bail!(
+      FORBIDDEN,
+      "Cannot edit a group with permissions you do not have"
+    );
+  }
+  if lacks_permissions(&db, auth.user_id, &data.permissions).await? {
+    bail!(
+      FORBIDDEN,
+      "Cannot assign permissions you do not have to a group"
+    );
+  }
+
+  if db.setup().get_admin_group_id().await? == Some(data.uuid) {
+    if group
+      .permissions
+      .iter()
+      .any(|perm| !data.permissions.contains(perm))
+    {
+      bail!(BAD_REQUEST, "Cannot change permissions of the admin group");
+    } else if data.users.is_empty() {
+      bail!(NOT_ACCEPTABLE, "Admin group must have at least one user");
+    }
+  }
+
+  let permissions_changed = group.permissions.len() != data.permissions.len()
+    || group
+      .permissions
+      .iter()
+      .any(|perm| !data.permissions.contains(perm));
+  let old_users = db.group_ext().members(&[data.uuid]).await?;
+
+  db.group()
+    .edit_group(data.uuid, data.name, data.permissions, data.users)
+    .await?;
+  updater
+    .broadcast(UpdateMessage::Group { uuid: data.uuid })
+    .await;
+
+  // members of child groups are affected as well
+  let new_users = db.group_ext().members(&[data.uuid]).await?;
+  let mut users: Vec<Uuid> = old_users
+    .iter()
+    .chain(&new_users)
+    .copied()
+    .filter(|user| permissions_changed || old_users.contains(user) != new_users.contains(user))
+    .collect();
+  users.sort_unstable();
+  users.dedup();
+  for user in users {
+    updater.send_to(user, UpdateMessage::UserPermissions).await;
+  }
+
+  Ok(())
+}
+
+async fn list_users_simple(
+  _auth: JwtAuth<GroupView>,
+  db: Connection,
+) -> Result<Json<Vec<SimpleUserInfo>>> {
+  Ok(Json(db.user().list_users_simple().await?))
+}
+
+#[cfg(test)]
+mod test {
+  use axum::{
+    Extension, Router,
+    body::Body,
+    http::{Request, StatusCode, header},
+    routing::get,
+  };
+  use centaurus::db::tables::ConnectionExt;
+  use serde_json::json;
+  use tower::ServiceExt;
+
+  use crate::db::{
+    DBTrait,
+    test::{
+      add_user_to_group, auth_cookie, auth_state, body_json, insert_group, insert_user, test_db,
+      updater,
+    },
+  };
+
+  #[tokio::test]
+  async fn inherited_permissions_grant_group_management() {
+    let db = test_db().await;
+    let jwt = auth_state(&db).await;
+    let user = insert_user(&db, "user", "user@x.com").await;
+    let parent = insert_group(&db, "parent").await;
+    let child = insert_group(&db, "child").await;
+    let target = insert_group(&db, "target").await;
+    db.group()
+      .add_permissions_to_group(parent, vec!["group:view".into(), "group:edit".into()])
+      .await
+      .unwrap();
+    add_user_to_group(&db, child, user).await;
+    let cookie = auth_cookie(&db, &jwt, user).await;
+
+    let app = Router::new()
+      .route("/", get(super::list_groups).put(super::edit_group))
+      .layer(Extension(updater().await))
+      .layer(Extension(jwt))
+      .layer(Extension(db.clone()));
+    let list = || {
+      Request::builder()
+        .uri("/")
+        .header(header::COOKIE, cookie.clone())
+        .body(Body::empty())
+        .unwrap()
+    };
+    let edit = |permissions: Vec<&str>| {
+      Request::builder()
+        .method("PUT")
+        .uri("/")
+        .header(header::COOKIE, cookie.clone())
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(
+          json!({
+            "uuid": target,
+            "name": "target",
+            "permissions": permissions,
+            "users": [],
+          })
+          .to_string(),
+        ))
+        .unwrap()
+    };
+
+    let resp = app.clone().oneshot(list()).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    db.group_ext()
+      .set_parents(child, vec![parent])
+      .await
+      .unwrap();
+    let resp = app.clone().oneshot(list()).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(body_json(resp).await["groups"].is_array());
+
+    // inherited permissions can be handed on, others cannot
+    let resp = app.clone().oneshot(edit(vec!["user:edit"])).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = app.oneshot(edit(vec!["group:view"])).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+      db.group().get_group_permissions(target).await.unwrap(),
+      ["group:view"]
+    );
+  }
+}
@@ -0,0 +1,238 @@
+use aide::axum::{
+  ApiRouter,
+  routing::{get_with, post_with},
+};
+use axum::Json;
+use centaurus::{
+  backend::{auth::jwt_auth::JwtAuth, endpoints::websocket::state::Updater},
+  bail,
+  db::init::Connection,
+  error::Result,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+  db::{DBTrait, group::ParentsUpdate},
+  utils::{GroupEdit, GroupView, UpdateMessage},
+};
+
+mod management;
+
+pub fn router() -> ApiRouter {
+  management::router()
+    .api_route(
+      "/mfa",
+      get_with(list_require_mfa, |op| op.id("listGroupsRequireMfa")),
+    )
+    .api_route(
+      "/mfa",
+      post_with(set_require_mfa, |op| op.id("setGroupRequireMfa")),
+    )
+    .api_route(
+      "/parents",
+      get_with(list_parents, |op| op.id("listGroupParents")),
+    )
+    .api_route(
+      "/parents",
+      post_with(set_parents, |op| op.id("setGroupParents")),
+    )
+}
+
+async fn list_require_mfa(_auth: JwtAuth<GroupView>, db: Connection) -> Result<Json<Vec<Uuid>>> {
+  Ok(Json(db.group_ext().list_require_mfa().await?))
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct RequireMfaRequest {
+  uuid: Uuid,
+  require_mfa: bool,
+}
+
+async fn set_require_mfa(
+  auth: JwtAuth<GroupEdit>,
+  db: Connection,
+  updater: Updater<UpdateMessage>,
+  Json(data): Json<RequireMfaRequest>,
+) -> Result<()> {
+  if !db
+    .group_ext()
+    .set_require_mfa(data.uuid, data.require_mfa)
+    .await?
+  {
+    bail!(NOT_FOUND, "Group not found");
+  }
+  info!(
+    "User {} set require MFA to {} for group {}",
+    auth.user_id, data.require_mfa, data.uuid
+  );
+  updater
+    .broadcast(UpdateMessage::Group { uuid: data.uuid })
+    .await;
+
+  Ok(())
+}
+
+#[derive(Serialize, JsonSchema)]
+struct GroupParent {
+  uuid: Uuid,
+  parent: Uuid,
+}
+
+async fn list_parents(_auth: JwtAuth<GroupView>, db: Connection) -> Result<Json<Vec<GroupParent>>> {
+  let parents = db
+    .group_ext()
+    .list_parents()
+    .await?
+    .into_iter()
+    .map(|link| GroupParent {
+      uuid: link.group_id,
+      parent: link.parent_id,
+    })
+    .collect();
+
+  Ok(Json(parents))
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct SetParentsRequest {
+  uuid: Uuid,
+  parents: Vec<Uuid>,
+}
+
+async fn set_parents(
+  auth: JwtAuth<GroupEdit>,
+  db: Connection,
+  updater: Updater<UpdateMessage>,
+  Json(data): Json<SetParentsRequest>,
+) -> Result<()> {
+  match db.group_ext().set_parents(data.uuid, data.parents).await? {
+    ParentsUpdate::Updated => {}
+    ParentsUpdate::NotFound => bail!(NOT_FOUND, "Group not found"),
+    ParentsUpdate::Cycle => bail!(CONFLICT, "A group cannot be its own ancestor"),
+  }
+  info!(
+    "User {} changed the parents of group {}",
+    auth.user_id, data.uuid
+  );
+  updater
+    .broadcast(UpdateMessage::Group { uuid: data.uuid })
+    .await;
+  // members of the group and its descendants may have gained or lost permissions
+  updater.broadcast(UpdateMessage::UserPermissions).await;
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use axum::{
+    Extension, Router,
+    body::Body,
+    http::{Request, StatusCode, header},
+    routing::post,
+  };
+  use serde_json::json;
+  use tower::ServiceExt;
+
+  use crate::db::{
+    DBTrait,
+    test::{
+      auth_cookie, auth_state, grant_permissions, insert_group, insert_user, test_db, updater,
+    },
+  };
+
+  #[tokio::test]
+  async fn set_require_mfa_needs_group_edit() {
+    let db = test_db().await;
+    let jwt = auth_state(&db).await;
+    let admin = insert_user(&db, "admin", "admin@x.com").await;
+    let viewer = insert_user(&db, "viewer", "viewer@x.com").await;
+    grant_permissions(&db, admin, &["group:edit"]).await;
+    let group = insert_group(&db, "staff").await;
+
+    let app = Router::new()
+      .route("/mfa", post(super::set_require_mfa))
+      .layer(Extension(updater().await))
+      .layer(Extension(jwt.clone()))
+      .layer(Extension(db.clone()));
+    let request = |cookie: String, uuid| {
+      Request::builder()
+        .method("POST")
+        .uri("/mfa")
+        .header(header::COOKIE, cookie)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(
+          json!({ "uuid": uuid, "require_mfa": true }).to_string(),
+        ))
+        .unwrap()
+    };
+
+    let cookie = auth_cookie(&db, &jwt, viewer).await;
+    let resp = app.clone().oneshot(request(cookie, group)).await.unwrap();
+    assert!(!resp.status().is_success());
+    assert!(db.group_ext().list_require_mfa().await.unwrap().is_empty());
+
+    let cookie = auth_cookie(&db, &jwt, admin).await;
+    let resp = app
+      .clone()
+      .oneshot(request(cookie.clone(), uuid::Uuid::new_v4()))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let resp = app.oneshot(request(cookie, group)).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(db.group_ext().list_require_mfa().await.unwrap(), [group]);
+  }
+
+  #[tokio::test]
+  async fn set_parents_rejects_cycles() {
+    let db = test_db().await;
+    let jwt = auth_state(&db).await;
+    let admin = insert_user(&db, "admin", "admin@x.com").await;
+    grant_permissions(&db, admin, &["group:edit"]).await;
+    let cookie = auth_cookie(&db, &jwt, admin).await;
+    let parent = insert_group(&db, "parent").await;
+    let child = insert_group(&db, "child").await;
+
+    let app = Router::new()
+      .route("/parents", post(super::set_parents))
+      .layer(Extension(updater().await))
+      .layer(Extension(jwt))
+      .layer(Extension(db.clone()));
+    let request = |uuid, parents: Vec<uuid::Uuid>| {
+      Request::builder()
+        .method("POST")
+        .uri("/parents")
+        .header(header::COOKIE, cookie.clone())
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(
+          json!({ "uuid": uuid, "parents": parents }).to_string(),
+        ))
+        .unwrap()
+    };
+
+    let resp = app
+      .clone()
+      .oneshot(request(child, vec![parent]))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = app
+      .clone()
+      .oneshot(request(parent, vec![child]))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let resp = app.oneshot(request(parent, vec![parent])).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let links = db.group_ext().list_parents().await.unwrap();
+    assert_eq!(links.len(), 1);
+    assert_eq!((links[0].group_id, links[0].parent_id), (child, parent));
+  }
+}
@@ -3,7 +3,7 @@ use axum::{Extension, Router};
 use centaurus::{
   backend::{
     endpoints::{
-      self, group, mail,
+      self, mail,
       websocket::{self, state::UpdateState},
     },
     init::{listener_setup, run_app_connect_info},
@@ -25,6 +25,7 @@ mod auth;
 mod cli;
 mod config;
 mod db;
+mod group;
 mod notes;
 mod oauth;
 mod oauth_management;
@@ -83,7 +84,7 @@ fn api_router(rate_limiter: &mut RateLimiter) -> ApiRouter {
     .nest("/user", user::router(rate_limiter))
     .nest("/settings", settings::router())
     .nest("/mail", mail::router(rate_limiter))
-    .nest("/group", group::router::<UpdateMessage>())
+    .nest("/group", group::router())
     .nest("/services", services::router())
     .nest("/oauth", oauth::router())
     .nest("/oauth_management", oauth_management::router())
@@ -99,12 +100,19 @@ async fn state(mut router: ApiRouter, config: Config) -> ApiRouter {
     .await
     .expect("Failed to create admin group");
   oauth_management::init(&db).await;
+  auth::oidc::init(&db).await;
 
   let storage = storage::state(&config).await;
   let (state, updater) = UpdateState::<UpdateMessage>::init().await;
 
   router = endpoints::user::state(router);
-  router = user::state(router, db.clone());
+  router = user::state(
+    router,
+    db.clone(),
+    storage.clone(),
+    updater.clone(),
+    &config,
+  );
   router = notes::state(
     router,
     storage.clone(),
@@ -161,8 +169,14 @@ mod test {
     let storage = storage::state(&config).await;
     let db = test_db().await;
     let (_state, updater) = UpdateState::<UpdateMessage>::init().await;
-    router = notes::state(router, storage.clone(), updater, db.clone(), &config);
-    router = user::state(router, db.clone());
+    router = notes::state(
+      router,
+      storage.clone(),
+      updater.clone(),
+      db.clone(),
+      &config,
+    );
+    router = user::state(router, db.clone(), storage.clone(), updater, &config);
     router = services::state(router).await;
     router = oauth::state(router, &config, &db).await;
     router = well_known::state(router, &config).await;
@@ -0,0 +1,775 @@
+use std::io::Cursor;
+
+use aide::axum::{
+  ApiRouter,
+  routing::{delete_with, get_with, post_with},
+};
+use axum::{
+  Extension, Json,
+  body::Body,
+  extract::{DefaultBodyLimit, Path, Query},
+  response::Response,
+};
+use centaurus::{
+  backend::auth::jwt_auth::JwtAuth, bail, db::init::Connection, error::Result, eyre::Context,
+  storage::FileStorage,
+};
+use entity::note_attachment;
+use http::header;
+use image::{ImageFormat, imageops::FilterType};
+use schemars::JsonSchema;
+use sea_orm::prelude::DateTime;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+  config::Config,
+  db::{DBTrait, notes::attachment::NewAttachment},
+  storage::StorageExt,
+  utils::{UpdateMessage, Updater},
+};
+
+const MAX_FILE_NAME_LENGTH: usize = 255;
+const THUMBNAIL_SIZE: u32 = 256;
+
+#[derive(Clone)]
+pub struct AttachmentLimits {
+  /// Largest accepted file in bytes.
+  pub max_size: usize,
+  /// Bytes of attachments a user may store across the notes they own.
+  pub quota: i64,
+}
+
+impl AttachmentLimits {
+  pub fn from_config(config: &Config) -> Self {
+    const MB: u64 = 1024 * 1024;
+    Self {
+      max_size: (config.notes_attachment_max_size_mb * MB) as usize,
+      quota: (config.notes_attachment_quota_mb * MB) as i64,
+    }
+  }
+}
+
+pub fn router() -> ApiRouter {
+  ApiRouter::new()
+    .api_route("/usage", get_with(usage, |op| op.id("noteAttachmentUsage")))
+    .api_route(
+      "/{note_uuid}",
+      get_with(list, |op| op.id("listNoteAttachments")),
+    )
+    .api_route(
+      "/{note_uuid}",
+      post_with(upload, |op| op.id("uploadNoteAttachment")).layer(DefaultBodyLimit::disable()),
+    )
+    .api_route(
+      "/{note_uuid}/{attachment_uuid}",
+      get_with(content, |op| op.id("noteAttachmentContent")),
+    )
+    .api_route(
+      "/{note_uuid}/{attachment_uuid}/thumbnail",
+      get_with(thumbnail, |op| op.id("noteAttachmentThumbnail")),
+    )
+    .api_route(
+      "/public/{note_uuid}/{attachment_uuid}",
+      get_with(content_public, |op| op.id("noteAttachmentContentShare")),
+    )
+    .api_route(
+      "/public/{note_uuid}/{attachment_uuid}/thumbnail",
+      get_with(thumbnail_public, |op| op.id("noteAttachmentThumbnailShare")),
+    )
+    .api_route("/", delete_with(delete, |op| op.id("deleteNoteAttachment")))
+}
+
+#[derive(Serialize, JsonSchema)]
+struct Attachment {
+  id: Uuid,
+  note_id: Uuid,
+  file_name: String,
+  content_type: String,
+  size: i64,
+  has_thumbnail: bool,
+  uploaded_by: Option<Uuid>,
+  created_at: DateTime,
+}
+
+impl From<note_attachment::Model> for Attachment {
+  fn from(model: note_attachment::Model) -> Self {
+    Self {
+      id: model.id,
+      note_id: model.note_id,
+      file_name: model.file_name,
+      content_type: model.content_type,
+      size: model.size,
+      has_thumbnail: model.has_thumbnail,
+      uploaded_by: model.uploaded_by,
+      created_at: model.created_at,
+    }
+  }
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct NotePath {
+  note_uuid: Uuid,
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct AttachmentPath {
+  note_uuid: Uuid,
+  attachment_uuid: Uuid,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct AttachmentUsage {
+  /// Bytes used by attachments in notes the user owns.
+  used: i64,
+  quota: i64,
+}
+
+async fn usage(
+  auth: JwtAuth,
+  db: Connection,
+  Extension(limits): Extension<AttachmentLimits>,
+) -> Result<Json<AttachmentUsage>> {
+  Ok(Json(AttachmentUsage {
+    used: db.note_attachment().used_by_owner(auth.user_id).await?,
+    quota: limits.quota,
+  }))
+}
+
+async fn list(
+  auth: JwtAuth,
+  db: Connection,
+  Path(NotePath { note_uuid }): Path<NotePath>,
+) -> Result<Json<Vec<Attachment>>> {
+  if !db.notes().has_access(auth.user_id, note_uuid).await? {
+    bail!(NOT_FOUND, "note not found");
+  }
+
+  let attachments = db.note_attachment().list_for_note(note_uuid).await?;
+  Ok(Json(
+    attachments.into_iter().map(Attachment::from).collect(),
+  ))
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct UploadQuery {
+  /// Name of the uploaded file, shown when the attachment is downloaded.
+  filename: String,
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn upload(
+  auth: JwtAuth,
+  db: Connection,
+  storage: FileStorage,
+  updater: Updater,
+  Extension(limits): Extension<AttachmentLimits>,
+  Path(NotePath { note_uuid }): Path<NotePath>,
+  Query(query): Query<UploadQuery>,
+  body: Body,
+) -> Result<Json<Attachment>> {
+  if !db.notes().has_access(auth.user_id, note_uuid).await? {
+    bail!(NOT_FOUND, "note not found");
+  }
+  if !db.notes().can_edit(auth.user_id, note_uuid).await? {
+    bail!(FORBIDDEN, "no edit access to this note");
+  }
+
+  let Ok(data) = axum::body::to_bytes(body, limits.max_size).await else {
+    bail!(PAYLOAD_TOO_LARGE, "attachment is too large");
+  };
+  if data.is_empty() {
+    bail!(BAD_REQUEST, "attachment must not be empty");
+  }
+
+  // cheap early reject, the insert checks the quota again atomically
+  let Some(owner) = db.notes().get_owner_id(note_uuid).await? else {
+    bail!(NOT_FOUND, "note not found");
+  };
+  let used = db.note_attachment().used_by_owner(owner).await?;
+  if used + data.len() as i64 > limits.quota {
+    bail!(PAYLOAD_TOO_LARGE, "storage quota exceeded");
+  }
+
+  let image = image::guess_format(&data).ok().filter(|format| {
+    matches!(
+      format,
+      ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::WebP
+    )
+  });
+  let thumbnail = match image {
+    Some(_) => {
+      let data = data.clone();
+      tokio::task::spawn_blocking(move || create_thumbnail(&data))
+        .await
+        .ok()
+        .flatten()
+    }
+    None => None,
+  };
+  let content_type = image.map_or("application/octet-stream", |format| format.to_mime_type());
+
+  let id = Uuid::now_v7();
+  let folder = storage.note_attachment();
+  folder.create(note_uuid, id, &data).await?;
+  let stored = match &thumbnail {
+    Some(thumbnail) => folder.create_thumbnail(note_uuid, id, thumbnail).await,
+    None => Ok(()),
+  };
+
+  let created = match stored {
+    Ok(()) => {
+      db.note_attachment()
+        .create(
+          NewAttachment {
+            id,
+            note_id: note_uuid,
+            uploaded_by: auth.user_id,
+            file_name: sanitize_file_name(&query.filename),
+            content_type: content_type.to_string(),
+            size: data.len() as i64,
+            has_thumbnail: thumbnail.is_some(),
+          },
+          limits.quota,
+        )
+        .await
+    }
+    Err(err) => Err(err),
+  };
+  let model = match created {
+    Ok(Some(model)) => model,
+    result => {
+      if let Err(err) = folder.delete(note_uuid, id).await {
+        warn!(?err, %note_uuid, attachment_id = %id, "failed to delete attachment file");
+      }
+      result?;
+      bail!(PAYLOAD_TOO_LARGE, "storage quota exceeded");
+    }
+  };
+
+  notify_attachments(&db, &updater, note_uuid).await?;
+  Ok(Json(model.into()))
+}
+
+/// WebP thumbnail of the image, `None` when the image can not be decoded.
+fn create_thumbnail(data: &[u8]) -> Option<Vec<u8>> {
+  let image = image::load_from_memory(data).ok()?;
+  let scaled = image.resize(THUMBNAIL_SIZE, THUMBNAIL_SIZE, FilterType::Lanczos3);
+
+  let mut cursor = Cursor::new(Vec::new());
+  scaled.write_to(&mut cursor, ImageFormat::WebP).ok()?;
+  Some(cursor.into_inner())
+}
+
+/// Keeps the last path component of the uploaded name without control
+/// characters or quotes.
+fn sanitize_file_name(name: &str) -> String {
+  let name = name.rsplit(['/', '\\']).next().unwrap_or_default();
+  let name: String = name
+    .chars()
+    .filter(|c| !c.is_control() && *c != '"')
+    .take(MAX_FILE_NAME_LENGTH)
+    .collect();
+  let name = name.trim();
+
+  if name.is_empty() || name == "." || name == ".." {
+    "attachment".to_string()
+  } else {
+    name.to_string()
+  }
+}
+
+async fn find_attachment(
+  db: &Connection,
+  note_id: Uuid,
+  attachment_id: Uuid,
+) -> Result<note_attachment::Model> {
+  match db.note_attachment().get(attachment_id).await? {
+    Some(attachment) if attachment.note_id == note_id => Ok(attachment),
+    _ => bail!(NOT_FOUND, "attachment not found"),
+  }
+}
+
+async fn attachment_response(
+  storage: &FileStorage,
+  attachment: note_attachment::Model,
+  thumbnail: bool,
+) -> Result<Response> {
+  let folder = storage.note_attachment();
+  let (body, content_type) = if thumbnail {
+    if !attachment.has_thumbnail {
+      bail!(NOT_FOUND, "attachment has no thumbnail");
+    }
+    (
+      folder
+        .read_thumbnail(attachment.note_id, attachment.id)
+        .await?,
+      "image/webp",
+    )
+  } else {
+    (
+      folder.read(attachment.note_id, attachment.id).await?,
+      attachment.content_type.as_str(),
+    )
+  };
+
+  // only the image types detected on upload are shown inline
+  let disposition = if attachment.has_thumbnail {
+    "inline"
+  } else {
+    "attachment"
+  };
+  let file_name: String = attachment
+    .file_name
+    .chars()
+    .map(|c| {
+      if c.is_ascii_graphic() && c != '\\' || c == ' ' {
+        c
+      } else {
+        '_'
+      }
+    })
+    .collect();
+
+  Ok(
+    Response::builder()
+      .header(header::CONTENT_TYPE, content_type)
+      .header(
+        header::CONTENT_DISPOSITION,
+        format!("{disposition}; filename=\"{file_name}\""),
+      )
+      .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+      .body(body)
+      .context("Failed to create response")?,
+  )
+}
+
+async fn content(
+  auth: JwtAuth,
+  db: Connection,
+  storage: FileStorage,
+  Path(AttachmentPath {
+    note_uuid,
+    attachment_uuid,
+  }): Path<AttachmentPath>,
+) -> Result<Response> {
+  if !db.notes().has_access(auth.user_id, note_uuid).await? {
+    bail!(NOT_FOUND, "note not found");
+  }
+
+  let attachment = find_attachment(&db, note_uuid, attachment_uuid).await?;
+  attachment_response(&storage, attachment, false).await
+}
+
+async fn thumbnail(
+  auth: JwtAuth,
+  db: Connection,
+  storage: FileStorage,
+  Path(AttachmentPath {
+    note_uuid,
+    attachment_uuid,
+  }): Path<AttachmentPath>,
+) -> Result<Response> {
+  if !db.notes().has_access(auth.user_id, note_uuid).await? {
+    bail!(NOT_FOUND, "note not found");
+  }
+
+  let attachment = find_attachment(&db, note_uuid, attachment_uuid).await?;
+  attachment_response(&storage, attachment, true).await
+}
+
+async fn content_public(
+  db: Connection,
+  storage: FileStorage,
+  Path(AttachmentPath {
+    note_uuid,
+    attachment_uuid,
+  }): Path<AttachmentPath>,
+) -> Result<Response> {
+  if db.notes().get_public_access(note_uuid).await?.is_none() {
+    bail!(NOT_FOUND, "note not found");
+  }
+
+  let attachment = find_attachment(&db, note_uuid, attachment_uuid).await?;
+  attachment_response(&storage, attachment, false).await
+}
+
+async fn thumbnail_public(
+  db: Connection,
+  storage: FileStorage,
+  Path(AttachmentPath {
+    note_uuid,
+    attachment_uuid,
+  }): Path<AttachmentPath>,
+) -> Result<Response> {
+  if db.notes().get_public_access(note_uuid).await?.is_none() {
+    bail!(NOT_FOUND, "note not found");
+  }
+
+  let attachment = find_attachment(&db, note_uuid, attachment_uuid).await?;
+  attachment_response(&storage, attachment, true).await
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct DeleteAttachmentReq {
+  attachment_id: Uuid,
+}
+
+async fn delete(
+  auth: JwtAuth,
+  db: Connection,
+  storage: FileStorage,
+  updater: Updater,
+  Json(req): Json<DeleteAttachmentReq>,
+) -> Result<()> {
+  let Some(attachment) = db.note_attachment().get(req.attachment_id).await? else {
+    bail!(NOT_FOUND, "attachment not found");
+  };
+  let note_id = attachment.note_id;
+  if !db.notes().has_access(auth.user_id, note_id).await? {
+    bail!(NOT_FOUND, "attachment not found");
+  }
+  if !db.notes().can_edit(auth.user_id, note_id).await? {
+    bail!(FORBIDDEN, "no edit access to this note");
+  }
+
+  storage
+    .note_attachment()
+    .delete(note_id, attachment.id)
+    .await?;
+  db.note_attachment().delete(attachment.id).await?;
+
+  notify_attachments(&db, &updater, note_id).await
+}
+
+async fn notify_attachments(db: &Connection, updater: &Updater, note_id: Uuid) -> Result<()> {
+  let mut users = db.notes().shared_user_ids(note_id).await?;
+  users.extend(db.notes().get_owner_id(note_id).await?);
+
+  let message = UpdateMessage::NoteAttachments { note_id };
+  for user_id in users {
+    updater.send_to(user_id, message).await;
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use std::io::Cursor;
+
+  use axum::{
+    Extension, Router,
+    body::Body,
+    http::{Request, StatusCode, header},
+    routing::get,
+  };
+  use centaurus::{backend::auth::jwt_state::JwtState, db::init::Connection, storage::FileStorage};
+  use entity::sea_orm_active_enums::NoteShareAccess;
+  use image::{ImageFormat, RgbImage};
+  use serde_json::{Value, json};
+  use tower::ServiceExt;
+  use uuid::Uuid;
+
+  use super::{AttachmentLimits, sanitize_file_name};
+  use crate::{
+    db::{
+      DBTrait,
+      notes::{NoteShareEntry, attachment::NewAttachment},
+      test::{auth_cookie, auth_state, body_json, insert_user, test_db, updater},
+    },
+    notes::delete_storage_for_note,
+    storage::StorageExt,
+  };
+
+  struct Setup {
+    db: Connection,
+    jwt: JwtState,
+    storage: FileStorage,
+    owner: Uuid,
+    note: Uuid,
+  }
+
+  async fn setup() -> Setup {
+    let db = test_db().await;
+    let jwt = auth_state(&db).await;
+    let storage = crate::storage::test::init_test_storage().await;
+    let owner = insert_user(&db, "owner", "owner@x.com").await;
+    let note = db.notes().create(owner, "T".into()).await.unwrap();
+
+    Setup {
+      db,
+      jwt,
+      storage,
+      owner,
+      note,
+    }
+  }
+
+  async fn app(s: &Setup, limits: AttachmentLimits) -> Router {
+    Router::new()
+      .route("/usage", get(super::usage))
+      .route("/{note_uuid}", get(super::list).post(super::upload))
+      .route("/{note_uuid}/{attachment_uuid}", get(super::content))
+      .route(
+        "/{note_uuid}/{attachment_uuid}/thumbnail",
+        get(super::thumbnail),
+      )
+      .route(
+        "/public/{note_uuid}/{attachment_uuid}",
+        get(super::content_public),
+      )
+      .route("/", axum::routing::delete(super::delete))
+      .layer(Extension(limits))
+      .layer(Extension(s.storage.clone()))
+      .layer(Extension(updater().await))
+      .layer(Extension(s.jwt.clone()))
+      .layer(Extension(s.db.clone()))
+  }
+
+  fn limits() -> AttachmentLimits {
+    AttachmentLimits {
+      max_size: 1024 * 1024,
+      quota: 2 * 1024 * 1024,
+    }
+  }
+
+  fn png() -> Vec<u8> {
+    let mut cursor = Cursor::new(Vec::new());
+    RgbImage::new(600, 300)
+      .write_to(&mut cursor, ImageFormat::Png)
+      .unwrap();
+    cursor.into_inner()
+  }
+
+  async fn upload(
+    app: &Router,
+    note: Uuid,
+    filename: &str,
+    cookie: &str,
+    data: Vec<u8>,
+  ) -> (StatusCode, Value) {
+    let req = Request::builder()
+      .method("POST")
+      .uri(format!("/{note}?filename={filename}"))
+      .header(header::COOKIE, cookie)
+      .body(Body::from(data))
+      .unwrap();
+    let resp = app.clone().oneshot(req).await.unwrap();
+    let status = resp.status();
+    if status == StatusCode::OK {
+      (status, body_json(resp).await)
+    } else {
+      (status, Value::Null)
+    }
+  }
+
+  async fn get_raw(
+    app: &Router,
+    uri: &str,
+    cookie: Option<&str>,
+  ) -> (StatusCode, Option<String>, Vec<u8>) {
+    let mut req = Request::builder().uri(uri);
+    if let Some(cookie) = cookie {
+      req = req.header(header::COOKIE, cookie);
+    }
+    let resp = app
+      .clone()
+      .oneshot(req.body(Body::empty()).unwrap())
+      .await
+      .unwrap();
+    let status = resp.status();
+    let content_type = resp
+      .headers()
+      .get(header::CONTENT_TYPE)
+      .map(|value| value.to_str().unwrap().to_string());
+    let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+      .await
+      .unwrap();
+    (status, content_type, bytes.to_vec())
+  }
+
+  #[test]
+  fn sanitize_file_name_keeps_the_last_component() {
+    assert_eq!(sanitize_file_name("../../etc/passwd"), "passwd");
+    assert_eq!(sanitize_file_name("C:\\Users\\me\\a \"b\".png"), "a b.png");
+    assert_eq!(sanitize_file_name("line\nbreak.txt"), "linebreak.txt");
+    assert_eq!(sanitize_file_name("dir/"), "attachment");
+    assert_eq!(sanitize_file_name(".."), "attachment");
+  }
+
+  #[tokio::test]
+  async fn uploaded_image_is_served_with_a_thumbnail_to_users_with_access() {
+    let s = setup().await;
+    let viewer = insert_user(&s.db, "viewer", "viewer@x.com").await;
+    let stranger = insert_user(&s.db, "stranger", "stranger@x.com").await;
+    s.db
+      .notes()
+      .set_shared_users(
+        s.note,
+        s.owner,
+        vec![NoteShareEntry {
+          user_id: viewer,
+          access: NoteShareAccess::View,
+        }],
+      )
+      .await
+      .unwrap();
+    let app = app(&s, limits()).await;
+    let owner_cookie = auth_cookie(&s.db, &s.jwt, s.owner).await;
+    let viewer_cookie = auth_cookie(&s.db, &s.jwt, viewer).await;
+    let stranger_cookie = auth_cookie(&s.db, &s.jwt, stranger).await;
+
+    let data = png();
+    let (status, body) = upload(&app, s.note, "chart.png", &owner_cookie, data.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["file_name"], "chart.png");
+    assert_eq!(body["content_type"], "image/png");
+    assert_eq!(body["has_thumbnail"], true);
+    let id = body["id"].as_str().unwrap().to_string();
+
+    // viewers can read but not upload
+    let (status, _) = upload(&app, s.note, "x.txt", &viewer_cookie, b"x".to_vec()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, content_type, bytes) =
+      get_raw(&app, &format!("/{}/{id}", s.note), Some(&viewer_cookie)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type.as_deref(), Some("image/png"));
+    assert_eq!(bytes, data);
+
+    let (status, content_type, bytes) = get_raw(
+      &app,
+      &format!("/{}/{id}/thumbnail", s.note),
+      Some(&viewer_cookie),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type.as_deref(), Some("image/webp"));
+    let thumbnail = image::load_from_memory(&bytes).unwrap();
+    assert_eq!((thumbnail.width(), thumbnail.height()), (256, 128));
+
+    let (status, _, _) = get_raw(&app, &format!("/{}/{id}", s.note), Some(&stranger_cookie)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _, _) = get_raw(&app, &format!("/{}", s.note), Some(&stranger_cookie)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // the attachment is only reachable through its own note
+    let other = s.db.notes().create(s.owner, "O".into()).await.unwrap();
+    let (status, _, _) = get_raw(&app, &format!("/{other}/{id}"), Some(&owner_cookie)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+  }
+
+  #[tokio::test]
+  async fn inserts_recheck_the_quota() {
+    let s = setup().await;
+    let attachment = |size| NewAttachment {
+      id: Uuid::now_v7(),
+      note_id: s.note,
+      uploaded_by: s.owner,
+      file_name: "a.bin".into(),
+      content_type: "application/octet-stream".into(),
+      size,
+      has_thumbnail: false,
+    };
+
+    // both uploads passed the early check, only the first one still fits
+    let table = s.db.note_attachment();
+    assert!(table.create(attachment(100), 150).await.unwrap().is_some());
+    assert!(table.create(attachment(100), 150).await.unwrap().is_none());
+    assert_eq!(table.used_by_owner(s.owner).await.unwrap(), 100);
+    assert_eq!(table.list_for_note(s.note).await.unwrap().len(), 1);
+  }
+
+  #[tokio::test]
+  async fn uploads_respect_size_limit_and_owner_quota() {
+    let s = setup().await;
+    let editor = insert_user(&s.db, "editor", "editor@x.com").await;
+    s.db
+      .notes()
+      .set_shared_users(
+        s.note,
+        s.owner,
+        vec![NoteShareEntry {
+          user_id: editor,
+          access: NoteShareAccess::Edit,
+        }],
+      )
+      .await
+      .unwrap();
+    let app = app(
+      &s,
+      AttachmentLimits {
+        max_size: 100,
+        quota: 150,
+      },
+    )
+    .await;
+    let owner_cookie = auth_cookie(&s.db, &s.jwt, s.owner).await;
+    let editor_cookie = auth_cookie(&s.db, &s.jwt, editor).await;
+
+    let (status, _) = upload(&app, s.note, "big.bin", &owner_cookie, vec![0; 101]).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    let (status, _) = upload(&app, s.note, "empty.bin", &owner_cookie, Vec::new()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) = upload(&app, s.note, "a.bin", &editor_cookie, vec![0; 100]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["content_type"], "application/octet-stream");
+    assert_eq!(body["has_thumbnail"], false);
+
+    // uploads by editors count against the owner of the note
+    let (status, _) = upload(&app, s.note, "b.bin", &owner_cookie, vec![0; 60]).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    let (status, _, bytes) = get_raw(&app, "/usage", Some(&owner_cookie)).await;
+    assert_eq!(status, StatusCode::OK);
+    let usage: Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(usage, json!({ "used": 100, "quota": 150 }));
+    let (_, _, bytes) = get_raw(&app, "/usage", Some(&editor_cookie)).await;
+    let usage: Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(usage["used"], 0);
+
+    // deleting frees the quota again
+    let req = Request::builder()
+      .method("DELETE")
+      .uri("/")
+      .header(header::COOKIE, &editor_cookie)
+      .header(header::CONTENT_TYPE, "application/json")
+      .body(Body::from(
+        json!({ "attachment_id": body["id"] }).to_string(),
+      ))
+      .unwrap();
+    let resp = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let (status, _) = upload(&app, s.note, "b.bin", &owner_cookie, vec![0; 60]).await;
+    assert_eq!(status, StatusCode::OK);
+  }
+
+  #[tokio::test]
+  async fn public_notes_serve_attachments_and_purge_removes_files() {
+    let s = setup().await;
+    let app = app(&s, limits()).await;
+    let cookie = auth_cookie(&s.db, &s.jwt, s.owner).await;
+
+    let (_, body) = upload(&app, s.note, "notes.txt", &cookie, b"hello".to_vec()).await;
+    let id: Uuid = body["id"].as_str().unwrap().parse().unwrap();
+    let uri = format!("/public/{}/{id}", s.note);
+
+    let (status, _, _) = get_raw(&app, &uri, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    s.db
+      .notes()
+      .set_public_access(s.note, Some(NoteShareAccess::View))
+      .await
+      .unwrap();
+    let (status, _, bytes) = get_raw(&app, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(bytes, b"hello");
+
+    delete_storage_for_note(&s.db, &s.storage, s.note)
+      .await
+      .unwrap();
+    assert!(s.storage.note_attachment().read(s.note, id).await.is_err());
+  }
+}
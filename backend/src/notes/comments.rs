@@ -0,0 +1,802 @@
+use std::collections::HashSet;
+
+use aide::axum::{
+  ApiRouter,
+  routing::{delete_with, get_with, post_with, put_with},
+};
+use axum::{Json, extract::Path};
+use centaurus::{
+  backend::auth::jwt_auth::JwtAuth, bail, db::init::Connection, error::Result, eyre::ContextCompat,
+};
+use entity::{note_comment, note_comment_thread};
+use schemars::JsonSchema;
+use sea_orm::prelude::DateTime;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use yrs::{
+  Assoc, AsyncTransact, BranchID, ClientID, ID, IndexScope, ReadTxn, StickyIndex, XmlFragment,
+  XmlOut,
+  updates::{decoder::Decode, encoder::Encode},
+};
+
+use crate::{
+  db::{DBTrait, notes::comment::NewThread},
+  notes::{export::load_doc, state::NoteEditing},
+  utils::{UpdateMessage, Updater},
+};
+
+const MAX_BODY_LENGTH: usize = 10_000;
+const MAX_QUOTE_LENGTH: usize = 1_000;
+
+pub fn router() -> ApiRouter {
+  ApiRouter::new()
+    .api_route(
+      "/{note_uuid}",
+      get_with(list, |op| op.id("listNoteComments")),
+    )
+    .api_route(
+      "/",
+      post_with(create_thread, |op| op.id("createCommentThread")),
+    )
+    .api_route("/", put_with(edit, |op| op.id("editNoteComment")))
+    .api_route("/", delete_with(delete, |op| op.id("deleteNoteComment")))
+    .api_route("/reply", post_with(reply, |op| op.id("replyNoteComment")))
+    .api_route(
+      "/resolve",
+      put_with(resolve, |op| op.id("resolveCommentThread")),
+    )
+    .api_route(
+      "/thread",
+      delete_with(delete_thread, |op| op.id("deleteCommentThread")),
+    )
+}
+
+/// Relative position in the `default` fragment, in the format of
+/// `Y.relativePositionToJSON`. Exactly one of `item`, `type` or `tname` is set.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct RelativePosition {
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  item: Option<PositionId>,
+  #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+  nested: Option<PositionId>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  tname: Option<String>,
+  /// Negative values stick to the character before the position.
+  #[serde(default)]
+  assoc: i8,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq)]
+struct PositionId {
+  client: u64,
+  clock: u32,
+}
+
+impl PositionId {
+  /// Y.js client ids are limited to 53 bits.
+  fn to_id(self) -> Option<ID> {
+    (self.client < 1 << 53).then(|| ID::new(ClientID::new(self.client), self.clock))
+  }
+}
+
+impl RelativePosition {
+  fn to_sticky(&self) -> Option<StickyIndex> {
+    let scope = match (self.item, self.nested, &self.tname) {
+      (Some(id), None, None) => IndexScope::Relative(id.to_id()?),
+      (None, Some(id), None) => IndexScope::Nested(id.to_id()?),
+      (None, None, Some(name)) => IndexScope::Root(name.as_str().into()),
+      _ => return None,
+    };
+    let assoc = if self.assoc < 0 {
+      Assoc::Before
+    } else {
+      Assoc::After
+    };
+    Some(StickyIndex::new(scope, assoc))
+  }
+
+  fn from_sticky(index: &StickyIndex) -> Self {
+    let id = |id: &ID| {
+      Some(PositionId {
+        client: id.client.get(),
+        clock: id.clock,
+      })
+    };
+    let (item, nested, tname) = match index.scope() {
+      IndexScope::Relative(item) => (id(item), None, None),
+      IndexScope::Nested(nested) => (None, id(nested), None),
+      IndexScope::Root(name) => (None, None, Some(name.to_string())),
+    };
+    Self {
+      item,
+      nested,
+      tname,
+      assoc: match index.assoc {
+        Assoc::Before => -1,
+        Assoc::After => 0,
+      },
+    }
+  }
+}
+
+/// Live note content, the stored content if nobody has the note open.
+async fn current_content(db: &Connection, editing: &NoteEditing, note_id: Uuid) -> Result<Vec<u8>> {
+  Ok(match editing.live_content(note_id).await {
+    Some((content, _)) => content,
+    None => db.notes().get_content(note_id).await?,
+  })
+}
+
+/// Branches of the `default` fragment, an anchor is only valid inside one of
+/// them.
+fn fragment_branches<T: ReadTxn>(txn: &T) -> HashSet<BranchID> {
+  let mut branches = HashSet::new();
+  let Some(fragment) = txn.get_xml_fragment("default") else {
+    return branches;
+  };
+  branches.insert(BranchID::Root("default".into()));
+
+  let mut nodes: Vec<XmlOut> = fragment.children(txn).collect();
+  while let Some(node) = nodes.pop() {
+    match node {
+      XmlOut::Element(element) => {
+        branches.insert(AsRef::<yrs::branch::Branch>::as_ref(&element).id());
+        nodes.extend(element.children(txn));
+      }
+      XmlOut::Text(text) => {
+        branches.insert(AsRef::<yrs::branch::Branch>::as_ref(&text).id());
+      }
+      XmlOut::Fragment(fragment) => nodes.extend(fragment.children(txn)),
+    }
+  }
+  branches
+}
+
+fn is_attached<T: ReadTxn>(txn: &T, branches: &HashSet<BranchID>, index: &StickyIndex) -> bool {
+  index
+    .get_offset(txn)
+    .is_some_and(|offset| branches.contains(&offset.branch.id()))
+}
+
+#[derive(Serialize, JsonSchema)]
+struct Comment {
+  id: Uuid,
+  author: Uuid,
+  body: String,
+  created_at: DateTime,
+  edited_at: Option<DateTime>,
+}
+
+impl From<note_comment::Model> for Comment {
+  fn from(comment: note_comment::Model) -> Self {
+    Self {
+      id: comment.id,
+      author: comment.author,
+      body: comment.body,
+      created_at: comment.created_at,
+      edited_at: comment.edited_at,
+    }
+  }
+}
+
+#[derive(Serialize, JsonSchema)]
+struct CommentThread {
+  id: Uuid,
+  note_id: Uuid,
+  start: RelativePosition,
+  end: RelativePosition,
+  /// Text the thread was opened on.
+  quote: String,
+  created_at: DateTime,
+  resolved_at: Option<DateTime>,
+  resolved_by: Option<Uuid>,
+  /// The anchored text no longer exists in the note.
+  detached: bool,
+  comments: Vec<Comment>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct NotePath {
+  note_uuid: Uuid,
+}
+
+async fn list(
+  auth: JwtAuth,
+  db: Connection,
+  editing: NoteEditing,
+  Path(path): Path<NotePath>,
+) -> Result<Json<Vec<CommentThread>>> {
+  if !db.notes().has_access(auth.user_id, path.note_uuid).await? {
+    bail!(NOT_FOUND, "note not found");
+  }
+
+  let threads = db.note_comment().list_for_note(path.note_uuid).await?;
+  let content = current_content(&db, &editing, path.note_uuid).await?;
+  let doc = load_doc(&content)
+    .await
+    .context("failed to decode note content")?;
+  let txn = doc.transact().await;
+  let branches = fragment_branches(&txn);
+
+  Ok(Json(
+    threads
+      .into_iter()
+      .filter(|(_, comments)| !comments.is_empty())
+      .filter_map(|(thread, comments)| {
+        let start = StickyIndex::decode_v1(&thread.anchor_start).ok()?;
+        let end = StickyIndex::decode_v1(&thread.anchor_end).ok()?;
+        Some(CommentThread {
+          id: thread.id,
+          note_id: thread.note_id,
+          detached: !is_attached(&txn, &branches, &start) || !is_attached(&txn, &branches, &end),
+          start: RelativePosition::from_sticky(&start),
+          end: RelativePosition::from_sticky(&end),
+          quote: thread.quote,
+          created_at: thread.created_at,
+          resolved_at: thread.resolved_at,
+          resolved_by: thread.resolved_by,
+          comments: comments.into_iter().map(Comment::from).collect(),
+        })
+      })
+      .collect(),
+  ))
+}
+
+fn normalize_body(body: String) -> Result<String> {
+  let body = body.trim().to_string();
+  if body.is_empty() {
+    bail!(BAD_REQUEST, "comment is empty");
+  }
+  if body.chars().count() > MAX_BODY_LENGTH {
+    bail!(BAD_REQUEST, "comment too long");
+  }
+  Ok(body)
+}
+
+async fn require_editor(auth: &JwtAuth, db: &Connection, note_id: Uuid) -> Result<()> {
+  if !db.notes().can_edit(auth.user_id, note_id).await? {
+    bail!(FORBIDDEN, "forbidden");
+  }
+  Ok(())
+}
+
+/// Thread and note, editors of the note may take part in the thread.
+async fn editable_thread(
+  auth: &JwtAuth,
+  db: &Connection,
+  thread_id: Uuid,
+) -> Result<note_comment_thread::Model> {
+  let Some(thread) = db.note_comment().thread(thread_id).await? else {
+    bail!(NOT_FOUND, "comment thread not found");
+  };
+  require_editor(auth, db, thread.note_id).await?;
+  Ok(thread)
+}
+
+/// Tells everyone who can see the note that its comments changed.
+async fn notify_comments(db: &Connection, updater: &Updater, note_id: Uuid) -> Result<()> {
+  let mut users = db.notes().shared_user_ids(note_id).await?;
+  users.extend(db.notes().get_owner_id(note_id).await?);
+
+  let message = UpdateMessage::NoteComments { note_id };
+  for user_id in users {
+    updater.send_to(user_id, message).await;
+  }
+  Ok(())
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct CreateThreadReq {
+  note_id: Uuid,
+  start: RelativePosition,
+  end: RelativePosition,
+  quote: String,
+  body: String,
+}
+
+async fn create_thread(
+  auth: JwtAuth,
+  db: Connection,
+  editing: NoteEditing,
+  updater: Updater,
+  Json(req): Json<CreateThreadReq>,
+) -> Result<Json<CommentThread>> {
+  require_editor(&auth, &db, req.note_id).await?;
+  let body = normalize_body(req.body)?;
+
+  let (Some(start), Some(end)) = (req.start.to_sticky(), req.end.to_sticky()) else {
+    bail!(BAD_REQUEST, "invalid anchor");
+  };
+  {
+    let content = current_content(&db, &editing, req.note_id).await?;
+    let doc = load_doc(&content)
+      .await
+      .context("failed to decode note content")?;
+    let txn = doc.transact().await;
+    let branches = fragment_branches(&txn);
+    if !is_attached(&txn, &branches, &start) || !is_attached(&txn, &branches, &end) {
+      bail!(BAD_REQUEST, "anchor is not part of the note");
+    }
+  }
+
+  let (thread, comment) = db
+    .note_comment()
+    .create_thread(NewThread {
+      note_id: req.note_id,
+      author: auth.user_id,
+      anchor_start: start.encode_v1(),
+      anchor_end: end.encode_v1(),
+      quote: req.quote.chars().take(MAX_QUOTE_LENGTH).collect(),
+      body,
+    })
+    .await?;
+  notify_comments(&db, &updater, req.note_id).await?;
+
+  Ok(Json(CommentThread {
+    id: thread.id,
+    note_id: thread.note_id,
+    start: RelativePosition::from_sticky(&start),
+    end: RelativePosition::from_sticky(&end),
+    quote: thread.quote,
+    created_at: thread.created_at,
+    resolved_at: None,
+    resolved_by: None,
+    detached: false,
+    comments: vec![comment.into()],
+  }))
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct ReplyReq {
+  thread_id: Uuid,
+  body: String,
+}
+
+async fn reply(
+  auth: JwtAuth,
+  db: Connection,
+  updater: Updater,
+  Json(req): Json<ReplyReq>,
+) -> Result<Json<Comment>> {
+  let thread = editable_thread(&auth, &db, req.thread_id).await?;
+  let body = normalize_body(req.body)?;
+
+  let comment = db
+    .note_comment()
+    .reply(thread.id, auth.user_id, body)
+    .await?;
+  notify_comments(&db, &updater, thread.note_id).await?;
+
+  Ok(Json(comment.into()))
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct ResolveReq {
+  thread_id: Uuid,
+  /// `false` reopens the thread.
+  resolved: bool,
+}
+
+async fn resolve(
+  auth: JwtAuth,
+  db: Connection,
+  updater: Updater,
+  Json(req): Json<ResolveReq>,
+) -> Result<()> {
+  let thread = editable_thread(&auth, &db, req.thread_id).await?;
+
+  db.note_comment()
+    .set_resolved(thread.id, req.resolved.then_some(auth.user_id))
+    .await?;
+  notify_comments(&db, &updater, thread.note_id).await
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct EditReq {
+  comment_id: Uuid,
+  body: String,
+}
+
+/// Authors may edit their own comments while they can edit the note.
+async fn edit(
+  auth: JwtAuth,
+  db: Connection,
+  updater: Updater,
+  Json(req): Json<EditReq>,
+) -> Result<()> {
+  let Some((comment, note_id)) = db.note_comment().comment(req.comment_id).await? else {
+    bail!(NOT_FOUND, "comment not found");
+  };
+  require_editor(&auth, &db, note_id).await?;
+  if comment.author != auth.user_id {
+    bail!(FORBIDDEN, "forbidden");
+  }
+  let body = normalize_body(req.body)?;
+
+  db.note_comment().edit(comment.id, body).await?;
+  notify_comments(&db, &updater, note_id).await
+}
+
+/// Authors who can still edit the note and the note owner may delete.
+async fn require_author_or_owner(
+  auth: &JwtAuth,
+  db: &Connection,
+  note_id: Uuid,
+  author: Option<Uuid>,
+) -> Result<()> {
+  if db.notes().is_owner(auth.user_id, note_id).await? {
+    return Ok(());
+  }
+  if author == Some(auth.user_id) && db.notes().can_edit(auth.user_id, note_id).await? {
+    return Ok(());
+  }
+  bail!(FORBIDDEN, "forbidden");
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct CommentIdReq {
+  comment_id: Uuid,
+}
+
+/// Deleting the last comment of a thread deletes the thread.
+async fn delete(
+  auth: JwtAuth,
+  db: Connection,
+  updater: Updater,
+  Json(req): Json<CommentIdReq>,
+) -> Result<()> {
+  let Some((comment, note_id)) = db.note_comment().comment(req.comment_id).await? else {
+    bail!(NOT_FOUND, "comment not found");
+  };
+  require_author_or_owner(&auth, &db, note_id, Some(comment.author)).await?;
+
+  db.note_comment()
+    .delete(comment.id, comment.thread_id)
+    .await?;
+  notify_comments(&db, &updater, note_id).await
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct ThreadIdReq {
+  thread_id: Uuid,
+}
+
+async fn delete_thread(
+  auth: JwtAuth,
+  db: Connection,
+  updater: Updater,
+  Json(req): Json<ThreadIdReq>,
+) -> Result<()> {
+  let Some(thread) = db.note_comment().thread(req.thread_id).await? else {
+    bail!(NOT_FOUND, "comment thread not found");
+  };
+  let author = db.note_comment().thread_author(thread.id).await?;
+  require_author_or_owner(&auth, &db, thread.note_id, author).await?;
+
+  db.note_comment().delete_thread(thread.id).await?;
+  notify_comments(&db, &updater, thread.note_id).await
+}
+
+#[cfg(test)]
+mod test {
+  use axum::{
+    Extension, Router,
+    body::Body,
+    http::{Request, StatusCode, header},
+    routing::{get, post, put},
+  };
+  use centaurus::{backend::auth::jwt_state::JwtState, db::init::Connection};
+  use entity::sea_orm_active_enums::NoteShareAccess;
+  use serde_json::{Value, json};
+  use tower::ServiceExt;
+  use uuid::Uuid;
+  use yrs::{
+    Assoc, Doc, IndexedSequence, ReadTxn, StateVector, Transact, XmlElementPrelim, XmlFragment,
+    XmlOut, XmlTextPrelim,
+  };
+
+  use crate::{
+    db::{
+      DBTrait,
+      notes::NoteShareEntry,
+      test::{auth_cookie, auth_state, body_json, insert_user, test_db, updater},
+    },
+    notes::state::NoteEditing,
+  };
+
+  struct Setup {
+    db: Connection,
+    jwt: JwtState,
+    app: Router,
+    owner: Uuid,
+    note: Uuid,
+    /// Anchors around "world" in the second paragraph.
+    start: Value,
+    end: Value,
+  }
+
+  /// A note with two paragraphs, the second one anchored by `start`/`end`.
+  async fn setup() -> Setup {
+    let db = test_db().await;
+    let jwt = auth_state(&db).await;
+    let storage = crate::storage::test::init_test_storage().await;
+    let owner = insert_user(&db, "owner", "owner@x.com").await;
+    let note = db.notes().create(owner, "T".into()).await.unwrap();
+
+    let doc = Doc::new();
+    let fragment = doc.get_or_insert_xml_fragment("default");
+    let (start, end) = {
+      let mut txn = doc.transact_mut();
+      for text in ["hello", "brave new world"] {
+        let paragraph = fragment.push_back(&mut txn, XmlElementPrelim::empty("paragraph"));
+        paragraph.push_back(&mut txn, XmlTextPrelim::new(text));
+      }
+      let Some(XmlOut::Element(paragraph)) = fragment.get(&txn, 1) else {
+        unreachable!()
+      };
+      let Some(XmlOut::Text(text)) = paragraph.get(&txn, 0) else {
+        unreachable!()
+      };
+      (
+        text.sticky_index(&txn, 10, Assoc::After).unwrap(),
+        text.sticky_index(&txn, 15, Assoc::Before).unwrap(),
+      )
+    };
+    let content = doc
+      .transact()
+      .encode_state_as_update_v1(&StateVector::default());
+    db.notes()
+      .set_content(note, content, String::new())
+      .await
+      .unwrap();
+
+    let app = Router::new()
+      .route(
+        "/",
+        post(super::create_thread)
+          .put(super::edit)
+          .delete(super::delete),
+      )
+      .route("/{note_uuid}", get(super::list))
+      .route("/reply", post(super::reply))
+      .route("/resolve", put(super::resolve))
+      .route("/thread", axum::routing::delete(super::delete_thread))
+      .layer(Extension(NoteEditing::init_test(storage).await))
+      .layer(Extension(updater().await))
+      .layer(Extension(jwt.clone()))
+      .layer(Extension(db.clone()));
+
+    Setup {
+      db,
+      jwt,
+      app,
+      owner,
+      note,
+      start: serde_json::to_value(start).unwrap(),
+      end: serde_json::to_value(end).unwrap(),
+    }
+  }
+
+  async fn send(
+    app: &Router,
+    method: &str,
+    uri: &str,
+    cookie: &str,
+    body: Value,
+  ) -> (StatusCode, Value) {
+    let req = Request::builder()
+      .method(method)
+      .uri(uri)
+      .header(header::COOKIE, cookie)
+      .header(header::CONTENT_TYPE, "application/json")
+      .body(Body::from(body.to_string()))
+      .unwrap();
+    let resp = app.clone().oneshot(req).await.unwrap();
+    let status = resp.status();
+    let body = if status == StatusCode::OK {
+      body_json(resp).await
+    } else {
+      Value::Null
+    };
+    (status, body)
+  }
+
+  async fn list(s: &Setup, cookie: &str) -> (StatusCode, Value) {
+    let req = Request::builder()
+      .uri(format!("/{}", s.note))
+      .header(header::COOKIE, cookie)
+      .body(Body::empty())
+      .unwrap();
+    let resp = s.app.clone().oneshot(req).await.unwrap();
+    let status = resp.status();
+    if status != StatusCode::OK {
+      return (status, Value::Null);
+    }
+    (status, body_json(resp).await)
+  }
+
+  async fn share(s: &Setup, user: Uuid, access: NoteShareAccess) {
+    s.db
+      .notes()
+      .set_shared_users(
+        s.note,
+        s.owner,
+        vec![NoteShareEntry {
+          user_id: user,
+          access,
+        }],
+      )
+      .await
+      .unwrap();
+  }
+
+  #[tokio::test]
+  async fn editors_open_threads_and_viewers_read_them() {
+    let s = setup().await;
+    let viewer = insert_user(&s.db, "viewer", "viewer@x.com").await;
+    let stranger = insert_user(&s.db, "stranger", "stranger@x.com").await;
+    share(&s, viewer, NoteShareAccess::View).await;
+    let owner_cookie = auth_cookie(&s.db, &s.jwt, s.owner).await;
+    let viewer_cookie = auth_cookie(&s.db, &s.jwt, viewer).await;
+    let stranger_cookie = auth_cookie(&s.db, &s.jwt, stranger).await;
+
+    let create = json!({
+      "note_id": s.note,
+      "start": s.start,
+      "end": s.end,
+      "quote": "world",
+      "body": "  typo?  ",
+    });
+    let (status, _) = send(&s.app, "POST", "/", &viewer_cookie, create.clone()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let mut unknown = create.clone();
+    unknown["start"] = json!({ "item": { "client": 1, "clock": 99 }, "assoc": 0 });
+    let (status, _) = send(&s.app, "POST", "/", &owner_cookie, unknown).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, thread) = send(&s.app, "POST", "/", &owner_cookie, create).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(thread["comments"][0]["body"], "typo?");
+    assert_eq!(thread["start"], s.start);
+    let thread_id = thread["id"].clone();
+
+    let (status, _) = send(
+      &s.app,
+      "POST",
+      "/reply",
+      &viewer_cookie,
+      json!({ "thread_id": thread_id, "body": "no" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = send(
+      &s.app,
+      "PUT",
+      "/resolve",
+      &owner_cookie,
+      json!({ "thread_id": thread_id, "resolved": true }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, threads) = list(&s, &viewer_cookie).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(threads[0]["quote"], "world");
+    assert_eq!(threads[0]["detached"], false);
+    assert_eq!(threads[0]["resolved_by"], json!(s.owner));
+    assert_eq!(list(&s, &stranger_cookie).await.0, StatusCode::NOT_FOUND);
+  }
+
+  #[tokio::test]
+  async fn anchors_detach_when_their_text_is_removed() {
+    let s = setup().await;
+    let owner_cookie = auth_cookie(&s.db, &s.jwt, s.owner).await;
+    let (_, thread) = send(
+      &s.app,
+      "POST",
+      "/",
+      &owner_cookie,
+      json!({
+        "note_id": s.note,
+        "start": s.start,
+        "end": s.end,
+        "quote": "world",
+        "body": "check",
+      }),
+    )
+    .await;
+
+    // a concurrent edit removes the second paragraph
+    let content = s.db.notes().get_content(s.note).await.unwrap();
+    let doc = crate::notes::load_doc(&content).await.unwrap();
+    {
+      let fragment = doc.get_or_insert_xml_fragment("default");
+      let mut txn = doc.transact_mut();
+      fragment.remove_range(&mut txn, 1, 1);
+    }
+    let content = doc
+      .transact()
+      .encode_state_as_update_v1(&StateVector::default());
+    s.db
+      .notes()
+      .set_content(s.note, content, String::new())
+      .await
+      .unwrap();
+
+    let (_, threads) = list(&s, &owner_cookie).await;
+    assert_eq!(threads[0]["id"], thread["id"]);
+    assert_eq!(threads[0]["detached"], true);
+  }
+
+  #[tokio::test]
+  async fn authors_edit_and_owner_deletes_comments() {
+    let s = setup().await;
+    let editor = insert_user(&s.db, "editor", "editor@x.com").await;
+    share(&s, editor, NoteShareAccess::Edit).await;
+    let owner_cookie = auth_cookie(&s.db, &s.jwt, s.owner).await;
+    let editor_cookie = auth_cookie(&s.db, &s.jwt, editor).await;
+
+    let (_, thread) = send(
+      &s.app,
+      "POST",
+      "/",
+      &editor_cookie,
+      json!({
+        "note_id": s.note,
+        "start": s.start,
+        "end": s.end,
+        "quote": "world",
+        "body": "first",
+      }),
+    )
+    .await;
+    let comment_id = thread["comments"][0]["id"].clone();
+
+    let edit = json!({ "comment_id": comment_id, "body": "edited" });
+    assert_eq!(
+      send(&s.app, "PUT", "/", &owner_cookie, edit.clone())
+        .await
+        .0,
+      StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+      send(&s.app, "PUT", "/", &editor_cookie, edit).await.0,
+      StatusCode::OK
+    );
+    let (_, threads) = list(&s, &owner_cookie).await;
+    assert_eq!(threads[0]["comments"][0]["body"], "edited");
+    assert!(threads[0]["comments"][0]["edited_at"].is_string());
+
+    // the owner may moderate, the last comment takes the thread with it
+    assert_eq!(
+      send(
+        &s.app,
+        "DELETE",
+        "/",
+        &owner_cookie,
+        json!({ "comment_id": comment_id })
+      )
+      .await
+      .0,
+      StatusCode::OK
+    );
+    assert!(
+      list(&s, &owner_cookie)
+        .await
+        .1
+        .as_array()
+        .unwrap()
+        .is_empty()
+    );
+    let thread_id: Uuid = serde_json::from_value(thread["id"].clone()).unwrap();
+    assert!(
+      s.db
+        .note_comment()
+        .thread(thread_id)
+        .await
+        .unwrap()
+        .is_none()
+    );
+  }
+}
@@ -0,0 +1,288 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::notes::{export::load_doc, markdown::render_markdown_blocks};
+
+/// Above this many table cells the changed range is reported as removed and
+/// added wholesale instead of computing the longest common subsequence.
+const MAX_CELLS: usize = 4_000_000;
+
+#[derive(Serialize, JsonSchema, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+  Unchanged,
+  Added,
+  Removed,
+}
+
+#[derive(Serialize, JsonSchema, Debug, PartialEq)]
+pub struct TextChange {
+  pub kind: ChangeKind,
+  pub text: String,
+}
+
+/// One block of the rendered Markdown, in document order.
+#[derive(Serialize, JsonSchema, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BlockChange {
+  Unchanged {
+    text: String,
+  },
+  Added {
+    text: String,
+  },
+  Removed {
+    text: String,
+  },
+  /// Edited in place, `changes` is the word level diff of the block.
+  Changed {
+    changes: Vec<TextChange>,
+  },
+}
+
+/// Compares two stored Yrs v1 documents, `None` if either cannot be decoded.
+pub async fn diff_content(old: &[u8], new: &[u8]) -> Option<Vec<BlockChange>> {
+  let old = render_markdown_blocks(&load_doc(old).await?).await;
+  let new = render_markdown_blocks(&load_doc(new).await?).await;
+  Some(diff_blocks(&old, &new))
+}
+
+fn diff_blocks(old: &[String], new: &[String]) -> Vec<BlockChange> {
+  let mut out = Vec::new();
+  let mut removed: Vec<&String> = Vec::new();
+  let mut added: Vec<&String> = Vec::new();
+
+  for op in diff_ops(old, new) {
+    match op {
+      Op::Equal(i) => {
+        flush(&mut out, &mut removed, &mut added);
+        out.push(BlockChange::Unchanged {
+          text: old[i].clone(),
+        });
+      }
+      Op::Delete(i) => removed.push(&old[i]),
+      Op::Insert(j) => added.push(&new[j]),
+    }
+  }
+  flush(&mut out, &mut removed, &mut added);
+
+  out
+}
+
+/// Pairs up a run of removed and added blocks, similar pairs become a single
+/// changed block.
+fn flush(out: &mut Vec<BlockChange>, removed: &mut Vec<&String>, added: &mut Vec<&String>) {
+  let mut removed = removed.drain(..);
+  let mut added = added.drain(..);
+  loop {
+    match (removed.next(), added.next()) {
+      (None, None) => break,
+      (Some(old), Some(new)) => {
+        let changes = diff_text(old, new);
+        let kept: usize = changes
+          .iter()
+          .filter(|change| change.kind == ChangeKind::Unchanged)
+          .map(|change| change.text.len())
+          .sum();
+        if kept * 2 >= old.len().max(new.len()) {
+          out.push(BlockChange::Changed { changes });
+        } else {
+          out.push(BlockChange::Removed { text: old.clone() });
+          out.push(BlockChange::Added { text: new.clone() });
+        }
+      }
+      (Some(old), None) => out.push(BlockChange::Removed { text: old.clone() }),
+      (None, Some(new)) => out.push(BlockChange::Added { text: new.clone() }),
+    }
+  }
+}
+
+fn diff_text(old: &str, new: &str) -> Vec<TextChange> {
+  let old = tokens(old);
+  let new = tokens(new);
+
+  let mut out: Vec<TextChange> = Vec::new();
+  for op in diff_ops(&old, &new) {
+    let (kind, token) = match op {
+      Op::Equal(i) => (ChangeKind::Unchanged, old[i]),
+      Op::Delete(i) => (ChangeKind::Removed, old[i]),
+      Op::Insert(j) => (ChangeKind::Added, new[j]),
+    };
+    match out.last_mut() {
+      Some(last) if last.kind == kind => last.text.push_str(token),
+      _ => out.push(TextChange {
+        kind,
+        text: token.into(),
+      }),
+    }
+  }
+  out
+}
+
+/// Words and whitespace runs, every other character is a token of its own.
+fn tokens(text: &str) -> Vec<&str> {
+  let class = |c: char| {
+    if c.is_alphanumeric() {
+      1
+    } else if c.is_whitespace() {
+      2
+    } else {
+      0
+    }
+  };
+
+  let mut out = Vec::new();
+  let mut start = 0;
+  let mut chars = text.char_indices().peekable();
+  while let Some((_, c)) = chars.next() {
+    let next = chars.peek().map(|&(i, next)| (i, class(next)));
+    match next {
+      Some((_, next_class)) if next_class == class(c) && next_class != 0 => {}
+      Some((i, _)) => {
+        out.push(&text[start..i]);
+        start = i;
+      }
+      None => out.push(&text[start..]),
+    }
+  }
+  out
+}
+
+enum Op {
+  /// Index into the old sequence.
+  Equal(usize),
+  Delete(usize),
+  Insert(usize),
+}
+
+/// Longest common subsequence diff, common prefix and suffix are stripped
+/// first since edits are usually local.
+fn diff_ops<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Op> {
+  let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+  let suffix = old[prefix..]
+    .iter()
+    .rev()
+    .zip(new[prefix..].iter().rev())
+    .take_while(|(a, b)| a == b)
+    .count();
+  let a = &old[prefix..old.len() - suffix];
+  let b = &new[prefix..new.len() - suffix];
+  let (n, m) = (a.len(), b.len());
+
+  let mut ops: Vec<Op> = (0..prefix).map(Op::Equal).collect();
+  let (mut i, mut j) = (0, 0);
+  if n * m <= MAX_CELLS {
+    let width = m + 1;
+    let mut table = vec![0u32; (n + 1) * width];
+    for i in (0..n).rev() {
+      for j in (0..m).rev() {
+        table[i * width + j] = if a[i] == b[j] {
+          table[(i + 1) * width + j + 1] + 1
+        } else {
+          table[(i + 1) * width + j].max(table[i * width + j + 1])
+        };
+      }
+    }
+
+    while i < n && j < m {
+      if a[i] == b[j] {
+        ops.push(Op::Equal(prefix + i));
+        i += 1;
+        j += 1;
+      } else if table[(i + 1) * width + j] >= table[i * width + j + 1] {
+        ops.push(Op::Delete(prefix + i));
+        i += 1;
+      } else {
+        ops.push(Op::Insert(prefix + j));
+        j += 1;
+      }
+    }
+  }
+  ops.extend((i..n).map(|i| Op::Delete(prefix + i)));
+  ops.extend((j..m).map(|j| Op::Insert(prefix + j)));
+  ops.extend((old.len() - suffix..old.len()).map(Op::Equal));
+
+  ops
+}
+
+#[cfg(test)]
+mod test {
+  use super::{BlockChange, ChangeKind, TextChange, diff_blocks, diff_text, tokens};
+
+  fn blocks(text: &[&str]) -> Vec<String> {
+    text.iter().map(|block| block.to_string()).collect()
+  }
+
+  fn change(kind: ChangeKind, text: &str) -> TextChange {
+    TextChange {
+      kind,
+      text: text.into(),
+    }
+  }
+
+  #[test]
+  fn tokens_split_words_spaces_and_symbols() {
+    assert_eq!(
+      tokens("- [x] two  words"),
+      ["-", " ", "[", "x", "]", " ", "two", "  ", "words"]
+    );
+    assert!(tokens("").is_empty());
+  }
+
+  #[test]
+  fn edited_words_are_marked_inline() {
+    assert_eq!(
+      diff_text("the quick fox", "the slow fox"),
+      [
+        change(ChangeKind::Unchanged, "the "),
+        change(ChangeKind::Removed, "quick"),
+        change(ChangeKind::Added, "slow"),
+        change(ChangeKind::Unchanged, " fox"),
+      ]
+    );
+  }
+
+  #[test]
+  fn blocks_are_added_removed_and_changed() {
+    let old = blocks(&["# Title", "first paragraph here", "- one", "gone"]);
+    let new = blocks(&["# Title", "first paragraph there", "- one", "- two"]);
+
+    let diff = diff_blocks(&old, &new);
+    assert_eq!(
+      diff[0],
+      BlockChange::Unchanged {
+        text: "# Title".into()
+      }
+    );
+    assert!(matches!(&diff[1], BlockChange::Changed { changes } if changes.len() == 3));
+    assert_eq!(
+      diff[2],
+      BlockChange::Unchanged {
+        text: "- one".into()
+      }
+    );
+    // nothing in common, so not paired into a changed block
+    assert_eq!(
+      &diff[3..],
+      [
+        BlockChange::Removed {
+          text: "gone".into()
+        },
+        BlockChange::Added {
+          text: "- two".into()
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn identical_documents_have_no_changes() {
+    let doc = blocks(&["a", "b"]);
+    assert!(
+      diff_blocks(&doc, &doc)
+        .iter()
+        .all(|block| matches!(block, BlockChange::Unchanged { .. }))
+    );
+    assert!(diff_blocks(&[], &[]).is_empty());
+  }
+}
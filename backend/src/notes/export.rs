@@ -0,0 +1,82 @@
+use axum::{body::Body, response::Response};
+use centaurus::{
+  error::Result,
+  eyre::{Context, ContextCompat},
+};
+use http::header;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use uuid::Uuid;
+use yrs::{AsyncTransact, Doc, Update, updates::decoder::Decode};
+
+use crate::notes::{
+  html::{escape, render_html},
+  markdown::render_markdown,
+};
+
+#[derive(Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+  Md,
+  Html,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ExportQuery {
+  pub format: ExportFormat,
+}
+
+/// Builds a document from stored Yrs v1 content, `None` if it cannot be
+/// decoded. Empty content is an empty document.
+pub async fn load_doc(content: &[u8]) -> Option<Doc> {
+  let doc = Doc::new();
+  if content.is_empty() {
+    return Some(doc);
+  }
+
+  let update = Update::decode_v1(content).ok()?;
+  doc.transact_mut().await.apply_update(update).ok()?;
+  Some(doc)
+}
+
+/// Renders the note as a downloadable Markdown or standalone HTML file.
+pub async fn export_response(
+  id: Uuid,
+  title: &str,
+  content: &[u8],
+  format: ExportFormat,
+) -> Result<Response> {
+  let doc = load_doc(content)
+    .await
+    .context("failed to decode note content")?;
+
+  let (body, content_type, extension) = match format {
+    ExportFormat::Md => (
+      format!("# {title}\n\n{}", render_markdown(&doc).await),
+      "text/markdown; charset=utf-8",
+      "md",
+    ),
+    ExportFormat::Html => {
+      let title = escape(title);
+      (
+        format!(
+          "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n</head>\n<body>\n<h1>{title}</h1>\n{}\n</body>\n</html>\n",
+          render_html(&doc).await
+        ),
+        "text/html; charset=utf-8",
+        "html",
+      )
+    }
+  };
+
+  Ok(
+    Response::builder()
+      .header(header::CONTENT_TYPE, content_type)
+      .header(
+        header::CONTENT_DISPOSITION,
+        format!("attachment; filename=\"{id}.{extension}\""),
+      )
+      .body(Body::from(body))
+      .context("Failed to create response")?,
+  )
+}
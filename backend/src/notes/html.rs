@@ -0,0 +1,263 @@
+use yrs::{
+  Any, AsyncTransact, Doc, GetString, Out, ReadTxn, Text, Xml, XmlElementRef, XmlFragment, XmlOut,
+  types::Attrs,
+};
+
+/// Renders the TipTap document of a note as HTML. Only a fixed set of tags is
+/// emitted, all text and attributes are escaped and links or images with
+/// unsafe schemes are dropped.
+pub async fn render_html(doc: &Doc) -> String {
+  let txn = doc.transact().await;
+  let Some(fragment) = txn.get_xml_fragment("default") else {
+    return String::new();
+  };
+
+  let nodes: Vec<XmlOut> = fragment.children(&txn).collect();
+  blocks(&txn, &nodes)
+}
+
+fn blocks<T: ReadTxn>(txn: &T, nodes: &[XmlOut]) -> String {
+  nodes.iter().map(|node| block(txn, node)).collect()
+}
+
+fn block<T: ReadTxn>(txn: &T, node: &XmlOut) -> String {
+  let element = match node {
+    XmlOut::Element(element) => element,
+    XmlOut::Text(_) => return format!("<p>{}</p>", inline(txn, std::slice::from_ref(node))),
+    XmlOut::Fragment(fragment) => {
+      let nodes: Vec<XmlOut> = fragment.children(txn).collect();
+      return blocks(txn, &nodes);
+    }
+  };
+  let children: Vec<XmlOut> = element.children(txn).collect();
+
+  match element.tag().as_ref() {
+    "paragraph" => format!("<p>{}</p>", inline(txn, &children)),
+    "heading" => {
+      let level = attribute(txn, element, "level")
+        .and_then(|level| level.parse::<f64>().ok())
+        .map(|level| level.clamp(1.0, 6.0) as usize)
+        .unwrap_or(1);
+      format!("<h{level}>{}</h{level}>", inline(txn, &children))
+    }
+    "blockquote" => format!("<blockquote>{}</blockquote>", blocks(txn, &children)),
+    "codeBlock" => {
+      let code: String = children
+        .iter()
+        .map(|child| match child {
+          XmlOut::Text(text) => text.get_string(txn),
+          _ => String::new(),
+        })
+        .collect();
+      let class = attribute(txn, element, "language")
+        .filter(|language| {
+          !language.is_empty()
+            && language
+              .chars()
+              .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '+'))
+        })
+        .map(|language| format!(" class=\"language-{language}\""))
+        .unwrap_or_default();
+      format!("<pre><code{class}>{}</code></pre>", escape(&code))
+    }
+    "horizontalRule" => "<hr>".into(),
+    "image" => image(txn, element),
+    "bulletList" => format!("<ul>{}</ul>", list_items(txn, &children)),
+    "orderedList" => {
+      let start = attribute(txn, element, "start")
+        .and_then(|start| start.parse::<f64>().ok())
+        .map(|start| start as usize)
+        .unwrap_or(1);
+      if start == 1 {
+        format!("<ol>{}</ol>", list_items(txn, &children))
+      } else {
+        format!("<ol start=\"{start}\">{}</ol>", list_items(txn, &children))
+      }
+    }
+    "taskList" => format!(
+      "<ul class=\"task-list\">{}</ul>",
+      list_items(txn, &children)
+    ),
+    _ => blocks(txn, &children),
+  }
+}
+
+fn list_items<T: ReadTxn>(txn: &T, items: &[XmlOut]) -> String {
+  items
+    .iter()
+    .map(|item| match item {
+      XmlOut::Element(element) => {
+        let children: Vec<XmlOut> = element.children(txn).collect();
+        let checkbox = if element.tag().as_ref() == "taskItem" {
+          let checked = attribute(txn, element, "checked").is_some_and(|c| c == "true");
+          if checked {
+            "<input type=\"checkbox\" disabled checked> "
+          } else {
+            "<input type=\"checkbox\" disabled> "
+          }
+        } else {
+          ""
+        };
+        format!("<li>{checkbox}{}</li>", blocks(txn, &children))
+      }
+      _ => format!("<li>{}</li>", block(txn, item)),
+    })
+    .collect()
+}
+
+fn inline<T: ReadTxn>(txn: &T, nodes: &[XmlOut]) -> String {
+  let mut out = String::new();
+  for node in nodes {
+    match node {
+      XmlOut::Text(text) => {
+        for chunk in text.diff(txn, |_| ()) {
+          if let Out::Any(Any::String(value)) = chunk.insert {
+            out.push_str(&format_marks(&value, chunk.attributes.as_deref()));
+          }
+        }
+      }
+      XmlOut::Element(element) => match element.tag().as_ref() {
+        "hardBreak" => out.push_str("<br>"),
+        "image" => out.push_str(&image(txn, element)),
+        _ => {
+          let children: Vec<XmlOut> = element.children(txn).collect();
+          out.push_str(&inline(txn, &children));
+        }
+      },
+      XmlOut::Fragment(_) => {}
+    }
+  }
+  out
+}
+
+fn format_marks(text: &str, marks: Option<&Attrs>) -> String {
+  let mut inner = escape(text).replace('\n', "<br>");
+  let Some(marks) = marks else {
+    return inner;
+  };
+
+  for (mark, tag) in [
+    ("code", "code"),
+    ("italic", "em"),
+    ("bold", "strong"),
+    ("underline", "u"),
+    ("strike", "s"),
+  ] {
+    if marks.contains_key(mark) {
+      inner = format!("<{tag}>{inner}</{tag}>");
+    }
+  }
+  if let Some(Any::Map(link)) = marks.get("link")
+    && let Some(Any::String(href)) = link.get("href")
+    && is_safe_url(href)
+  {
+    inner = format!(
+      "<a href=\"{}\" rel=\"noopener noreferrer\">{inner}</a>",
+      escape(href)
+    );
+  }
+
+  inner
+}
+
+fn image<T: ReadTxn>(txn: &T, element: &XmlElementRef) -> String {
+  let Some(src) = attribute(txn, element, "src").filter(|src| is_safe_url(src)) else {
+    return String::new();
+  };
+  let alt = attribute(txn, element, "alt").unwrap_or_default();
+  format!("<img src=\"{}\" alt=\"{}\">", escape(&src), escape(&alt))
+}
+
+fn attribute<T: ReadTxn>(txn: &T, element: &XmlElementRef, name: &str) -> Option<String> {
+  match element.get_attribute(txn, name)? {
+    Out::Any(Any::Null | Any::Undefined) => None,
+    Out::Any(Any::String(value)) => Some(value.to_string()),
+    Out::Any(value) => Some(value.to_string()),
+    other => Some(other.to_string(txn)),
+  }
+}
+
+/// Relative URLs and a few known schemes, so `javascript:` and friends never
+/// end up in an attribute.
+fn is_safe_url(url: &str) -> bool {
+  let url = url.trim();
+  match url.split_once(':') {
+    Some((scheme, _)) if !scheme.contains(['/', '?', '#']) => {
+      matches!(
+        scheme.to_ascii_lowercase().as_str(),
+        "http" | "https" | "mailto"
+      )
+    }
+    _ => true,
+  }
+}
+
+pub fn escape(text: &str) -> String {
+  let mut out = String::with_capacity(text.len());
+  for c in text.chars() {
+    match c {
+      '&' => out.push_str("&amp;"),
+      '<' => out.push_str("&lt;"),
+      '>' => out.push_str("&gt;"),
+      '"' => out.push_str("&quot;"),
+      '\'' => out.push_str("&#39;"),
+      c => out.push(c),
+    }
+  }
+  out
+}
+
+#[cfg(test)]
+mod test {
+  use std::collections::HashMap;
+
+  use super::{is_safe_url, render_html};
+  use crate::notes::markdown::test::sample_doc;
+  use yrs::{
+    Any, Doc, Text, Transact, Xml, XmlElementPrelim, XmlFragment, XmlTextPrelim, types::Attrs,
+  };
+
+  #[tokio::test]
+  async fn renders_blocks_and_marks() {
+    assert_eq!(
+      render_html(&sample_doc()).await,
+      "<h2>Title</h2><p>plain <strong>bold</strong><a href=\"https://x.dev\" rel=\"noopener noreferrer\"> link</a></p><ul><li><p>one</p></li><li><p>two</p></li></ul><pre><code class=\"language-rust\">let a = *b;</code></pre>"
+    );
+  }
+
+  #[tokio::test]
+  async fn escapes_text_and_drops_unsafe_links() {
+    let doc = Doc::new();
+    let fragment = doc.get_or_insert_xml_fragment("default");
+    {
+      let mut txn = doc.transact_mut();
+      let paragraph = fragment.push_back(&mut txn, XmlElementPrelim::empty("paragraph"));
+      let text = paragraph.push_back(&mut txn, XmlTextPrelim::new("<script>x</script> "));
+      let link = Attrs::from([(
+        "link".into(),
+        Any::Map(HashMap::from([("href".to_string(), Any::from("javascript:alert(1)"))]).into()),
+      )]);
+      text.insert_with_attributes(&mut txn, 19, "click", link);
+
+      let list = fragment.push_back(&mut txn, XmlElementPrelim::empty("taskList"));
+      let item = list.push_back(&mut txn, XmlElementPrelim::empty("taskItem"));
+      item.insert_attribute(&mut txn, "checked", true);
+      let paragraph = item.push_back(&mut txn, XmlElementPrelim::empty("paragraph"));
+      paragraph.push_back(&mut txn, XmlTextPrelim::new("done"));
+    }
+
+    assert_eq!(
+      render_html(&doc).await,
+      "<p>&lt;script&gt;x&lt;/script&gt; click</p><ul class=\"task-list\"><li><input type=\"checkbox\" disabled checked> <p>done</p></li></ul>"
+    );
+  }
+
+  #[test]
+  fn only_known_schemes_are_safe() {
+    assert!(is_safe_url("https://x.dev"));
+    assert!(is_safe_url("/notes/1"));
+    assert!(is_safe_url("a/b:c"));
+    assert!(!is_safe_url(" JavaScript:alert(1)"));
+    assert!(!is_safe_url("data:text/html,x"));
+  }
+}
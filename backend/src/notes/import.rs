@@ -0,0 +1,924 @@
+use std::collections::{HashMap, HashSet};
+
+use centaurus::{error::Result, eyre::Context};
+use tokio::task::spawn_blocking;
+use yrs::{
+  Any, Doc, In, ReadTxn, StateVector, Text, Transact, TransactionMut, Xml, XmlElementPrelim,
+  XmlElementRef, XmlFragment, XmlTextPrelim,
+  types::{Attrs, Delta},
+};
+
+use crate::notes::preview::{render_preview, render_text};
+
+/// A Markdown file converted into the editor document schema.
+pub struct ImportedNote {
+  /// Taken from a leading level one heading.
+  pub title: Option<String>,
+  pub content: Vec<u8>,
+  pub preview: String,
+  pub text: String,
+}
+
+/// Parsing runs on a blocking thread, the cost grows with the file size.
+pub async fn import_markdown(markdown: String) -> Result<ImportedNote> {
+  let (title, doc, content) = spawn_blocking(move || build_doc(&markdown))
+    .await
+    .context("failed to parse markdown")?;
+
+  Ok(ImportedNote {
+    title,
+    content,
+    preview: render_preview(&doc).await,
+    text: render_text(&doc).await,
+  })
+}
+
+fn build_doc(markdown: &str) -> (Option<String>, Doc, Vec<u8>) {
+  let lines: Vec<String> = markdown
+    .replace("\r\n", "\n")
+    .replace('\t', "    ")
+    .split('\n')
+    .map(str::to_string)
+    .collect();
+  let mut blocks = parse_blocks(&lines, 0);
+
+  let title = match blocks.first() {
+    Some(Block::Heading(1, inlines)) => {
+      let title = plain_text(inlines);
+      blocks.remove(0);
+      Some(title)
+    }
+    _ => None,
+  };
+
+  let doc = Doc::new();
+  let fragment = doc.get_or_insert_xml_fragment("default");
+  let content = {
+    let mut txn = doc.transact_mut();
+    for block in &blocks {
+      write_block(&mut txn, &fragment, block);
+    }
+    txn.encode_state_as_update_v1(&StateVector::default())
+  };
+
+  (title, doc, content)
+}
+
+/// Quotes, lists, links and emphasis nested deeper than this are kept as
+/// plain text, parsing recurses once per level.
+const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, PartialEq)]
+enum Block {
+  Paragraph(Vec<Inline>),
+  Heading(u8, Vec<Inline>),
+  Code {
+    language: Option<String>,
+    code: String,
+  },
+  Quote(Vec<Block>),
+  List {
+    start: Option<u64>,
+    task: bool,
+    items: Vec<ListItem>,
+  },
+  Rule,
+  Image {
+    src: String,
+    alt: String,
+  },
+}
+
+#[derive(Debug, PartialEq)]
+struct ListItem {
+  checked: bool,
+  blocks: Vec<Block>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Inline {
+  Text(String, Marks),
+  HardBreak,
+  Image { src: String, alt: String },
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+struct Marks {
+  bold: bool,
+  italic: bool,
+  strike: bool,
+  code: bool,
+  link: Option<String>,
+}
+
+struct ListMarker<'a> {
+  number: Option<u64>,
+  delimiter: char,
+  content_indent: usize,
+  rest: &'a str,
+}
+
+fn parse_blocks(lines: &[String], depth: usize) -> Vec<Block> {
+  let nested = depth < MAX_DEPTH;
+  let mut blocks = Vec::new();
+  let mut i = 0;
+
+  while i < lines.len() {
+    let line = &lines[i];
+    if line.trim().is_empty() {
+      i += 1;
+      continue;
+    }
+
+    if let Some((fence_char, fence_len, info)) = fence(line) {
+      let indent = indent_of(line);
+      let language = info.split_whitespace().next().map(str::to_string);
+      let mut code = Vec::new();
+      i += 1;
+      while i < lines.len() {
+        if let Some((c, len, info)) = fence(&lines[i])
+          && c == fence_char
+          && len >= fence_len
+          && info.is_empty()
+        {
+          i += 1;
+          break;
+        }
+        code.push(strip_indent(&lines[i], indent));
+        i += 1;
+      }
+      blocks.push(Block::Code {
+        language,
+        code: code.join("\n"),
+      });
+      continue;
+    }
+
+    if let Some((level, text)) = heading(line) {
+      blocks.push(Block::Heading(level, parse_inlines(text)));
+      i += 1;
+      continue;
+    }
+
+    if is_rule(line) {
+      blocks.push(Block::Rule);
+      i += 1;
+      continue;
+    }
+
+    if nested && quote_line(line).is_some() {
+      let mut inner: Vec<String> = Vec::new();
+      while i < lines.len() {
+        match quote_line(&lines[i]) {
+          Some(rest) => inner.push(rest.to_string()),
+          // lazy continuation of a quoted paragraph
+          None
+            if !lines[i].trim().is_empty()
+              && inner.last().is_some_and(|last| !last.trim().is_empty())
+              && !starts_block(&lines[i]) =>
+          {
+            inner.push(lines[i].trim_start().to_string())
+          }
+          None => break,
+        }
+        i += 1;
+      }
+      blocks.push(Block::Quote(parse_blocks(&inner, depth + 1)));
+      continue;
+    }
+
+    if nested && list_marker(line).is_some() {
+      let (list, next) = parse_list(lines, i, depth);
+      blocks.push(list);
+      i = next;
+      continue;
+    }
+
+    let mut paragraph = vec![line.trim_start()];
+    i += 1;
+    let mut level = None;
+    while i < lines.len() && !lines[i].trim().is_empty() {
+      if let Some(setext) = setext_level(&lines[i]) {
+        level = Some(setext);
+        i += 1;
+        break;
+      }
+      if starts_block(&lines[i]) {
+        break;
+      }
+      paragraph.push(lines[i].trim_start());
+      i += 1;
+    }
+
+    let inlines = parse_inlines(&join_paragraph(&paragraph));
+    blocks.push(match (level, inlines.as_slice()) {
+      (Some(level), _) => Block::Heading(level, inlines),
+      (None, [Inline::Image { src, alt }]) => Block::Image {
+        src: src.clone(),
+        alt: alt.clone(),
+      },
+      _ => Block::Paragraph(inlines),
+    });
+  }
+
+  blocks
+}
+
+fn parse_list(lines: &[String], start: usize, depth: usize) -> (Block, usize) {
+  let Some(first) = list_marker(&lines[start]) else {
+    return (Block::Paragraph(Vec::new()), start + 1);
+  };
+  let ordered = first.number.is_some();
+
+  let mut items: Vec<(Option<bool>, Vec<String>)> = Vec::new();
+  let mut i = start;
+  while i < lines.len() {
+    let Some(marker) = list_marker(&lines[i]) else {
+      break;
+    };
+    if marker.number.is_some() != ordered || marker.delimiter != first.delimiter {
+      break;
+    }
+
+    let (checked, rest) = task_checkbox(marker.rest);
+    let mut content = vec![rest.to_string()];
+    let mut previous_blank = false;
+    i += 1;
+    while i < lines.len() {
+      let line = &lines[i];
+      if line.trim().is_empty() {
+        content.push(String::new());
+        previous_blank = true;
+      } else if indent_of(line) >= marker.content_indent {
+        content.push(line[marker.content_indent..].to_string());
+        previous_blank = false;
+      } else if !previous_blank && !starts_block(line) {
+        content.push(line.trim_start().to_string());
+      } else {
+        break;
+      }
+      i += 1;
+    }
+
+    items.push((checked, content));
+  }
+
+  let task = items.first().is_some_and(|(checked, _)| checked.is_some());
+  let list = Block::List {
+    start: first.number,
+    task,
+    items: items
+      .into_iter()
+      .map(|(checked, content)| ListItem {
+        checked: checked.unwrap_or(false),
+        blocks: parse_blocks(&content, depth + 1),
+      })
+      .collect(),
+  };
+  (list, i)
+}
+
+fn task_checkbox(rest: &str) -> (Option<bool>, &str) {
+  for (prefix, checked) in [("[ ]", false), ("[x]", true), ("[X]", true)] {
+    if let Some(after) = rest.strip_prefix(prefix)
+      && (after.is_empty() || after.starts_with(' '))
+    {
+      return (Some(checked), after.trim_start());
+    }
+  }
+  (None, rest)
+}
+
+fn join_paragraph(lines: &[&str]) -> String {
+  let mut out = String::new();
+  for (i, line) in lines.iter().enumerate() {
+    if i == lines.len() - 1 {
+      out.push_str(line.trim_end());
+    } else if let Some(line) = line.strip_suffix('\\') {
+      out.push_str(line);
+      out.push('\n');
+    } else if line.ends_with("  ") {
+      out.push_str(line.trim_end());
+      out.push('\n');
+    } else {
+      out.push_str(line.trim_end());
+      out.push(' ');
+    }
+  }
+  out
+}
+
+fn indent_of(line: &str) -> usize {
+  line.len() - line.trim_start_matches(' ').len()
+}
+
+fn strip_indent(line: &str, indent: usize) -> String {
+  line[indent_of(line).min(indent)..].to_string()
+}
+
+/// Block syntax is only recognised with at most three spaces of indentation.
+fn block_start(line: &str) -> Option<&str> {
+  (indent_of(line) <= 3).then(|| line.trim_start_matches(' '))
+}
+
+fn fence(line: &str) -> Option<(char, usize, &str)> {
+  let trimmed = block_start(line)?;
+  let fence_char = trimmed.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+  let len = trimmed.chars().take_while(|&c| c == fence_char).count();
+  let info = trimmed[len..].trim();
+  (len >= 3 && !(fence_char == '`' && info.contains('`'))).then_some((fence_char, len, info))
+}
+
+fn heading(line: &str) -> Option<(u8, &str)> {
+  let trimmed = block_start(line)?;
+  let level = trimmed.chars().take_while(|&c| c == '#').count();
+  let rest = &trimmed[level..];
+  if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with(' ')) {
+    return None;
+  }
+
+  let text = rest.trim();
+  let without_closing = text.trim_end_matches('#');
+  let text = if without_closing.is_empty() || without_closing.ends_with(' ') {
+    without_closing.trim_end()
+  } else {
+    text
+  };
+  Some((level as u8, text))
+}
+
+fn setext_level(line: &str) -> Option<u8> {
+  let trimmed = block_start(line)?.trim_end();
+  if !trimmed.is_empty() && trimmed.chars().all(|c| c == '=') {
+    Some(1)
+  } else if !trimmed.is_empty() && trimmed.chars().all(|c| c == '-') {
+    Some(2)
+  } else {
+    None
+  }
+}
+
+fn is_rule(line: &str) -> bool {
+  let Some(trimmed) = block_start(line) else {
+    return false;
+  };
+  let chars: Vec<char> = trimmed.chars().filter(|c| !c.is_whitespace()).collect();
+  chars.len() >= 3 && matches!(chars[0], '-' | '*' | '_') && chars.iter().all(|&c| c == chars[0])
+}
+
+fn quote_line(line: &str) -> Option<&str> {
+  let rest = block_start(line)?.strip_prefix('>')?;
+  Some(rest.strip_prefix(' ').unwrap_or(rest))
+}
+
+fn list_marker(line: &str) -> Option<ListMarker<'_>> {
+  let indent = indent_of(line);
+  let trimmed = block_start(line)?;
+
+  let (number, delimiter, marker_len) = match trimmed.chars().next()? {
+    c @ ('-' | '*' | '+') => (None, c, 1),
+    _ => {
+      let digits = trimmed.chars().take_while(char::is_ascii_digit).count();
+      let delimiter = trimmed[digits..].chars().next()?;
+      if !(1..=9).contains(&digits) || !matches!(delimiter, '.' | ')') {
+        return None;
+      }
+      (Some(trimmed[..digits].parse().ok()?), delimiter, digits + 1)
+    }
+  };
+
+  let after = &trimmed[marker_len..];
+  if !(after.is_empty() || after.starts_with(' ')) {
+    return None;
+  }
+  let spaces = indent_of(after);
+  let spaces = if after.trim().is_empty() || spaces > 4 {
+    1
+  } else {
+    spaces
+  };
+
+  Some(ListMarker {
+    number,
+    delimiter,
+    content_indent: indent + marker_len + spaces,
+    rest: after.trim_start(),
+  })
+}
+
+fn starts_block(line: &str) -> bool {
+  fence(line).is_some()
+    || heading(line).is_some()
+    || is_rule(line)
+    || quote_line(line).is_some()
+    || list_marker(line).is_some_and(|marker| marker.number.is_none_or(|n| n == 1))
+}
+
+fn parse_inlines(text: &str) -> Vec<Inline> {
+  let chars: Vec<char> = text.chars().collect();
+  let mut out = Vec::new();
+  InlineText::new(&chars).parse(0, chars.len(), &Marks::default(), 0, &mut out);
+  out
+}
+
+/// Paragraph text with brackets and code spans paired up front, so unmatched
+/// delimiters do not rescan the rest of the text.
+struct InlineText<'a> {
+  chars: &'a [char],
+  /// Closing bracket or parenthesis of each opening one.
+  closers: HashMap<usize, usize>,
+  /// Start of every backtick run by its length.
+  code_runs: HashMap<usize, Vec<usize>>,
+}
+
+impl<'a> InlineText<'a> {
+  fn new(chars: &'a [char]) -> Self {
+    let mut closers = HashMap::new();
+    let mut code_runs: HashMap<usize, Vec<usize>> = HashMap::new();
+    let (mut brackets, mut parens) = (Vec::new(), Vec::new());
+    let mut i = 0;
+    while i < chars.len() {
+      match chars[i] {
+        '\\' => i += 1,
+        '[' => brackets.push(i),
+        '(' => parens.push(i),
+        ']' => {
+          if let Some(open) = brackets.pop() {
+            closers.insert(open, i);
+          }
+        }
+        ')' => {
+          if let Some(open) = parens.pop() {
+            closers.insert(open, i);
+          }
+        }
+        _ => {}
+      }
+      i += 1;
+    }
+
+    let mut i = 0;
+    while i < chars.len() {
+      let len = run(chars, i, chars.len(), '`');
+      if len > 0 {
+        code_runs.entry(len).or_default().push(i);
+      }
+      i += len.max(1);
+    }
+
+    Self {
+      chars,
+      closers,
+      code_runs,
+    }
+  }
+
+  fn parse(&self, start: usize, end: usize, marks: &Marks, depth: usize, out: &mut Vec<Inline>) {
+    let chars = self.chars;
+    let nested = depth < MAX_DEPTH;
+    // positions only grow, an emphasis that found no end once finds none later
+    let mut unclosed = HashSet::new();
+    let mut text = String::new();
+    let mut i = start;
+
+    while i < end {
+      let c = chars[i];
+      match c {
+        '\\' if i + 1 < end && chars[i + 1].is_ascii_punctuation() => {
+          text.push(chars[i + 1]);
+          i += 2;
+        }
+        '\n' => {
+          push_text(out, &mut text, marks);
+          out.push(Inline::HardBreak);
+          i += 1;
+        }
+        '`' => {
+          let len = run(chars, i, end, '`');
+          match self.code_end(i + len, end, len) {
+            Some(code_end) => {
+              push_text(out, &mut text, marks);
+              let mut code: String = chars[i + len..code_end].iter().collect();
+              if code.len() >= 2
+                && code.starts_with(' ')
+                && code.ends_with(' ')
+                && !code.trim().is_empty()
+              {
+                code = code[1..code.len() - 1].to_string();
+              }
+              let code_marks = Marks {
+                code: true,
+                ..marks.clone()
+              };
+              push_text(out, &mut code, &code_marks);
+              i = code_end + len;
+            }
+            None => {
+              text.extend(&chars[i..i + len]);
+              i += len;
+            }
+          }
+        }
+        '!' if i + 1 < end && chars[i + 1] == '[' => match self.link_at(i + 1, end) {
+          Some((alt, src, after)) => {
+            push_text(out, &mut text, marks);
+            out.push(Inline::Image {
+              src,
+              alt: chars[alt.0..alt.1].iter().collect(),
+            });
+            i = after;
+          }
+          None => {
+            text.push(c);
+            i += 1;
+          }
+        },
+        '[' if nested => match self.link_at(i, end) {
+          Some((label, href, after)) => {
+            push_text(out, &mut text, marks);
+            let link_marks = Marks {
+              link: Some(href),
+              ..marks.clone()
+            };
+            self.parse(label.0, label.1, &link_marks, depth + 1, out);
+            i = after;
+          }
+          None => {
+            text.push(c);
+            i += 1;
+          }
+        },
+        '*' | '_' | '~' => {
+          let len = run(chars, i, end, c);
+          let width = if c == '~' { 2 } else { len.min(3) };
+          let can_open = nested
+            && i + width < end
+            && !chars[i + width].is_whitespace()
+            && !(c == '_' && i > start && chars[i - 1].is_alphanumeric())
+            && (c != '~' || len == 2)
+            && !unclosed.contains(&(c, width));
+          let emphasis_end = can_open
+            .then(|| self.emphasis_end(i + width, end, c, width))
+            .flatten();
+          if can_open && emphasis_end.is_none() {
+            unclosed.insert((c, width));
+          }
+
+          if let Some(emphasis_end) = emphasis_end {
+            push_text(out, &mut text, marks);
+            let mut inner = marks.clone();
+            match (c, width) {
+              ('~', _) => inner.strike = true,
+              (_, 1) => inner.italic = true,
+              (_, 2) => inner.bold = true,
+              _ => {
+                inner.bold = true;
+                inner.italic = true;
+              }
+            }
+            self.parse(i + width, emphasis_end, &inner, depth + 1, out);
+            i = emphasis_end + width;
+          } else {
+            text.extend(&chars[i..i + len]);
+            i += len;
+          }
+        }
+        c => {
+          text.push(c);
+          i += 1;
+        }
+      }
+    }
+
+    push_text(out, &mut text, marks);
+  }
+
+  /// Start of the next backtick run of exactly `len` within `from..end`.
+  fn code_end(&self, from: usize, end: usize, len: usize) -> Option<usize> {
+    let runs = self.code_runs.get(&len)?;
+    let next = runs[runs.partition_point(|&at| at < from)..].first()?;
+    (*next < end).then_some(*next)
+  }
+
+  fn emphasis_end(&self, from: usize, end: usize, c: char, width: usize) -> Option<usize> {
+    let chars = self.chars;
+    let mut i = from;
+    while i < end {
+      match chars[i] {
+        '\\' => i += 2,
+        '`' => {
+          let len = run(chars, i, end, '`');
+          i = self
+            .code_end(i + len, end, len)
+            .map_or(i + len, |code_end| code_end + len);
+        }
+        x if x == c => {
+          let len = run(chars, i, end, c);
+          let closes = i > from
+            && !chars[i - 1].is_whitespace()
+            && !(c == '_' && i + len < end && chars[i + len].is_alphanumeric());
+          if closes && len == width {
+            return Some(i);
+          }
+          // `***` closes an inner strong run together with the outer emphasis
+          if closes && len == 3 && width < 3 {
+            return Some(i + len - width);
+          }
+          i += len;
+        }
+        _ => i += 1,
+      }
+    }
+    None
+  }
+
+  /// Parses `[label](destination)` starting at the opening bracket, returns
+  /// the label range, the destination and the index after the closing
+  /// parenthesis.
+  fn link_at(&self, at: usize, end: usize) -> Option<((usize, usize), String, usize)> {
+    let label_end = self.closer(at, end)?;
+    if label_end + 1 >= end || self.chars[label_end + 1] != '(' {
+      return None;
+    }
+    let dest_end = self.closer(label_end + 1, end)?;
+
+    let dest: String = self.chars[label_end + 2..dest_end].iter().collect();
+    let dest = dest.trim();
+    let href = match dest.strip_prefix('<') {
+      Some(rest) => rest.split('>').next().unwrap_or_default(),
+      None => dest.split_whitespace().next().unwrap_or_default(),
+    };
+
+    Some(((at + 1, label_end), href.to_string(), dest_end + 1))
+  }
+
+  fn closer(&self, at: usize, end: usize) -> Option<usize> {
+    self.closers.get(&at).copied().filter(|&close| close < end)
+  }
+}
+
+/// Appends text to the output, merging it with a preceding run of the same
+/// marks.
+fn push_text(out: &mut Vec<Inline>, text: &mut String, marks: &Marks) {
+  if text.is_empty() {
+    return;
+  }
+  let text = std::mem::take(text);
+  if let Some(Inline::Text(previous, previous_marks)) = out.last_mut()
+    && previous_marks == marks
+  {
+    previous.push_str(&text);
+  } else {
+    out.push(Inline::Text(text, marks.clone()));
+  }
+}
+
+fn run(chars: &[char], at: usize, end: usize, c: char) -> usize {
+  chars[at..end].iter().take_while(|&&x| x == c).count()
+}
+
+fn plain_text(inlines: &[Inline]) -> String {
+  inlines
+    .iter()
+    .map(|inline| match inline {
+      Inline::Text(text, _) => text.as_str(),
+      Inline::HardBreak => " ",
+      Inline::Image { alt, .. } => alt.as_str(),
+    })
+    .collect()
+}
+
+fn write_block<P: XmlFragment>(txn: &mut TransactionMut, parent: &P, block: &Block) {
+  match block {
+    Block::Paragraph(inlines) => {
+      let paragraph = parent.push_back(txn, XmlElementPrelim::empty("paragraph"));
+      write_inlines(txn, &paragraph, inlines);
+    }
+    Block::Heading(level, inlines) => {
+      let heading = parent.push_back(txn, XmlElementPrelim::empty("heading"));
+      heading.insert_attribute(txn, "level", *level as f64);
+      write_inlines(txn, &heading, inlines);
+    }
+    Block::Code { language, code } => {
+      let block = parent.push_back(txn, XmlElementPrelim::empty("codeBlock"));
+      if let Some(language) = language {
+        block.insert_attribute(txn, "language", language.as_str());
+      }
+      if !code.is_empty() {
+        block.push_back(txn, XmlTextPrelim::new(code.as_str()));
+      }
+    }
+    Block::Quote(blocks) => {
+      let quote = parent.push_back(txn, XmlElementPrelim::empty("blockquote"));
+      for block in blocks {
+        write_block(txn, &quote, block);
+      }
+    }
+    Block::List { start, task, items } => {
+      let tag = match (task, start) {
+        (true, _) => "taskList",
+        (false, Some(_)) => "orderedList",
+        (false, None) => "bulletList",
+      };
+      let list = parent.push_back(txn, XmlElementPrelim::empty(tag));
+      if let (false, Some(start)) = (task, start) {
+        list.insert_attribute(txn, "start", *start as f64);
+      }
+
+      for item in items {
+        let element = if *task {
+          let element = list.push_back(txn, XmlElementPrelim::empty("taskItem"));
+          element.insert_attribute(txn, "checked", item.checked);
+          element
+        } else {
+          list.push_back(txn, XmlElementPrelim::empty("listItem"))
+        };
+        if item.blocks.is_empty() {
+          element.push_back(txn, XmlElementPrelim::empty("paragraph"));
+        }
+        for block in &item.blocks {
+          write_block(txn, &element, block);
+        }
+      }
+    }
+    Block::Rule => {
+      parent.push_back(txn, XmlElementPrelim::empty("horizontalRule"));
+    }
+    Block::Image { src, alt } => {
+      write_image(txn, parent, src, alt);
+    }
+  }
+}
+
+fn write_inlines(txn: &mut TransactionMut, element: &XmlElementRef, inlines: &[Inline]) {
+  let mut runs = Vec::new();
+  for inline in inlines {
+    match inline {
+      Inline::Text(text, marks) => runs.push(Delta::Inserted(
+        In::from(text.as_str()),
+        Some(Box::new(marks.attrs())),
+      )),
+      Inline::HardBreak => {
+        write_text(txn, element, &mut runs);
+        element.push_back(txn, XmlElementPrelim::empty("hardBreak"));
+      }
+      Inline::Image { src, alt } => {
+        write_text(txn, element, &mut runs);
+        write_image(txn, element, src, alt);
+      }
+    }
+  }
+  write_text(txn, element, &mut runs);
+}
+
+/// Writes the runs as one text node, applied as a delta so each run does not
+/// search the text for its position again.
+fn write_text(txn: &mut TransactionMut, element: &XmlElementRef, runs: &mut Vec<Delta<In>>) {
+  if runs.is_empty() {
+    return;
+  }
+  let node = element.push_back(txn, XmlTextPrelim::new(""));
+  node.apply_delta(txn, runs.drain(..));
+}
+
+fn write_image<P: XmlFragment>(txn: &mut TransactionMut, parent: &P, src: &str, alt: &str) {
+  let image = parent.push_back(txn, XmlElementPrelim::empty("image"));
+  image.insert_attribute(txn, "src", src);
+  if !alt.is_empty() {
+    image.insert_attribute(txn, "alt", alt);
+  }
+}
+
+impl Marks {
+  /// Marks are stored like y-prosemirror does, one attribute per mark with
+  /// the mark attributes as value.
+  fn attrs(&self) -> Attrs {
+    let mut attrs = Attrs::new();
+    for (set, name) in [
+      (self.bold, "bold"),
+      (self.italic, "italic"),
+      (self.strike, "strike"),
+      (self.code, "code"),
+    ] {
+      if set {
+        attrs.insert(name.into(), Any::Map(HashMap::new().into()));
+      }
+    }
+    if let Some(href) = &self.link {
+      attrs.insert(
+        "link".into(),
+        Any::Map(HashMap::from([("href".to_string(), Any::from(href.as_str()))]).into()),
+      );
+    }
+    attrs
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use yrs::{Doc, GetString, Transact, Update, updates::decoder::Decode};
+
+  use super::{build_doc, import_markdown};
+  use crate::notes::markdown::render_markdown;
+
+  async fn round_trip(markdown: &str) -> (Option<String>, String) {
+    let imported = import_markdown(markdown.to_string()).await.unwrap();
+    let doc = Doc::new();
+    doc
+      .transact_mut()
+      .apply_update(Update::decode_v1(&imported.content).unwrap())
+      .unwrap();
+    (imported.title, render_markdown(&doc).await)
+  }
+
+  #[tokio::test]
+  async fn blocks_and_marks_round_trip() {
+    let (title, body) = round_trip(
+      "# Title\n\nplain **bold** [link](https://x.dev)\n\n- one\n- two\n\n```rust\nlet a = *b;\n```\n",
+    )
+    .await;
+    assert_eq!(title.as_deref(), Some("Title"));
+    assert_eq!(
+      body,
+      "plain **bold** [link](https://x.dev)\n\n- one\n- two\n\n```rust\nlet a = *b;\n```\n"
+    );
+  }
+
+  #[tokio::test]
+  async fn nested_containers_and_tasks() {
+    let (title, body) = round_trip(
+      "Intro\n===\n\n> 3. outer\n>    - inner\n\n- [x] done\n- [ ] open\n\n***\n\n*it* ~~gone~~ `co*de`  \nnext",
+    )
+    .await;
+    assert_eq!(title.as_deref(), Some("Intro"));
+    assert_eq!(
+      body,
+      "> 3. outer\n>    - inner\n\n- [x] done\n- [ ] open\n\n---\n\n*it* ~~gone~~ `co*de`\\\nnext\n"
+    );
+  }
+
+  #[tokio::test]
+  async fn unmatched_delimiters_stay_literal() {
+    let (title, body) = round_trip("## a * b [c] d_e_f").await;
+    assert_eq!(title, None);
+    assert_eq!(body, "## a \\* b \\[c\\] d\\_e\\_f\n");
+  }
+
+  /// Runs on a thread with the stack of a tokio worker.
+  fn build_on_small_stack(markdown: String) -> String {
+    std::thread::Builder::new()
+      .stack_size(2 * 1024 * 1024)
+      .spawn(move || {
+        let (_, doc, _) = build_doc(&markdown);
+        doc
+          .get_or_insert_xml_fragment("default")
+          .get_string(&doc.transact())
+      })
+      .unwrap()
+      .join()
+      .unwrap()
+  }
+
+  #[test]
+  fn deep_nesting_is_kept_as_text() {
+    let quotes = build_on_small_stack(">".repeat(10_000) + " x");
+    assert_eq!(quotes.matches("<blockquote>").count(), super::MAX_DEPTH);
+    assert!(quotes.contains(&">".repeat(100)));
+
+    let lists = build_on_small_stack("- ".repeat(10_000) + "x");
+    assert_eq!(lists.matches("<bulletList>").count(), super::MAX_DEPTH);
+
+    let links = build_on_small_stack("[".repeat(10_000) + "x" + &"](y)".repeat(10_000));
+    assert!(links.contains("x"));
+    let emphasis = build_on_small_stack("*a _".repeat(10_000) + &"b_ c*".repeat(10_000));
+    assert!(emphasis.contains("b"));
+  }
+
+  #[test]
+  fn unmatched_delimiters_do_not_rescan() {
+    let start = std::time::Instant::now();
+    let mut backticks = String::new();
+    for len in 1..1_000 {
+      backticks.push_str(&"`".repeat(len));
+      backticks.push(' ');
+    }
+    for markdown in [
+      "[".repeat(200_000),
+      "[a](".repeat(100_000),
+      "*a ".repeat(100_000),
+      "**a _b ~~c ".repeat(50_000),
+      // many differently marked runs in one paragraph
+      "*a* b ".repeat(50_000),
+      backticks,
+    ] {
+      let (_, doc, _) = build_doc(&markdown);
+      assert!(
+        !doc
+          .get_or_insert_xml_fragment("default")
+          .get_string(&doc.transact())
+          .is_empty()
+      );
+    }
+    assert!(start.elapsed() < std::time::Duration::from_secs(10));
+  }
+}
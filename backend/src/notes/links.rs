@@ -0,0 +1,70 @@
+use uuid::Uuid;
+use yrs::{Any, Out, ReadTxn, Xml, XmlFragment, XmlOut};
+
+/// Notes referenced by note link nodes (`<noteLink id="{note id}">`), a note
+/// linking to itself is ignored.
+pub fn linked_notes<T: ReadTxn>(txn: &T, note_id: Uuid) -> Vec<Uuid> {
+  let mut notes = referenced_ids(txn, "noteLink");
+  notes.retain(|id| *id != note_id);
+  notes
+}
+
+/// Ids in the `id` attribute of every `tag` node in the `default` fragment,
+/// sorted and without duplicates. Attributes that are no UUID are skipped.
+pub fn referenced_ids<T: ReadTxn>(txn: &T, tag: &str) -> Vec<Uuid> {
+  let mut ids = Vec::new();
+  let Some(fragment) = txn.get_xml_fragment("default") else {
+    return ids;
+  };
+
+  let mut nodes: Vec<XmlOut> = fragment.children(txn).collect();
+  while let Some(node) = nodes.pop() {
+    match node {
+      XmlOut::Element(element) => {
+        if element.tag().as_ref() == tag
+          && let Some(Out::Any(Any::String(id))) = element.get_attribute(txn, "id")
+          && let Ok(id) = id.parse()
+        {
+          ids.push(id);
+        }
+        nodes.extend(element.children(txn));
+      }
+      XmlOut::Fragment(fragment) => nodes.extend(fragment.children(txn)),
+      XmlOut::Text(_) => {}
+    }
+  }
+
+  ids.sort_unstable();
+  ids.dedup();
+  ids
+}
+
+#[cfg(test)]
+mod test {
+  use uuid::Uuid;
+  use yrs::{Doc, Transact, Xml, XmlElementPrelim, XmlFragment};
+
+  use super::linked_notes;
+
+  #[test]
+  fn linked_notes_skips_self_links_and_other_nodes() {
+    let note = Uuid::new_v4();
+    let other = Uuid::new_v4();
+    let doc = Doc::new();
+    let fragment = doc.get_or_insert_xml_fragment("default");
+    {
+      let mut txn = doc.transact_mut();
+      let paragraph = fragment.push_back(&mut txn, XmlElementPrelim::empty("paragraph"));
+      for (tag, id) in [
+        ("noteLink", other),
+        ("noteLink", note),
+        ("mention", Uuid::new_v4()),
+      ] {
+        let node = paragraph.push_back(&mut txn, XmlElementPrelim::empty(tag));
+        node.insert_attribute(&mut txn, "id", id.to_string());
+      }
+    }
+
+    assert_eq!(linked_notes(&doc.transact(), note), [other]);
+  }
+}
@@ -1,33 +1,49 @@
+use std::collections::HashMap;
+
 use aide::axum::{
   ApiRouter,
   routing::{delete_with, get_with, post_with, put_with},
 };
-use axum::{Extension, Json, body::Bytes, extract::Path};
+use axum::{
+  Extension, Json,
+  body::Bytes,
+  extract::{DefaultBodyLimit, Path, Query},
+  response::Response,
+};
 use centaurus::{
   backend::auth::jwt_auth::JwtAuth,
   bail,
   db::{
     init::Connection,
-    tables::{ConnectionExt, group::SimpleUserInfo},
+    tables::{ConnectionExt, group::SimpleUserInfo, user::SimpleGroupInfo},
   },
   error::Result,
   eyre::Context,
-  storage::FileStorage,
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use tokio::task::spawn_blocking;
+use tracing::warn;
 use uuid::Uuid;
 use yrs::{AsyncTransact, Doc, ReadTxn, StateVector, Update, updates::decoder::Decode};
 
 use crate::{
   db::{
     DBTrait,
-    notes::{NoteInfo, NoteInfoPublic, NoteShareEntry},
+    notes::{
+      NoteGroupShareEntry, NoteInfo, NoteInfoPublic, NoteShareEntry, folder::NoteFolderInfo,
+      link::LinkedNote, search::NoteSearchResult,
+    },
   },
   notes::{
-    NotesLimits, PublicNoteUpdateMessage, PublicNoteUpdater, delete_storage_for_note, preview,
-    state::NoteEditing,
+    NotesLimits, PublicNoteUpdateMessage, PublicNoteUpdater,
+    export::{ExportQuery, export_response},
+    import::{ImportedNote, import_markdown},
+    mentions::{MentionMail, notify_pending_mentions},
+    preview,
+    state::{MB, NoteEditing, store_content},
   },
+  user::export::zip::read_entries,
   utils::{UpdateMessage, Updater},
 };
 
@@ -37,6 +53,10 @@ pub fn router() -> ApiRouter {
     .api_route("/", post_with(create, |op| op.id("createNote")))
     .api_route("/", put_with(edit, |op| op.id("editNote")))
     .api_route("/", delete_with(delete, |op| op.id("deleteNote")))
+    .api_route(
+      "/import",
+      post_with(import, |op| op.id("importNotes")).layer(DefaultBodyLimit::max(IMPORT_MAX_SIZE)),
+    )
     .api_route("/{uuid}", get_with(info, |op| op.id("infoNote")))
     .api_route(
       "/{uuid}",
@@ -50,13 +70,57 @@ pub fn router() -> ApiRouter {
       "/{uuid}/content",
       get_with(note_content, |op| op.id("noteContent")),
     )
+    .api_route(
+      "/{uuid}/backlinks",
+      get_with(backlinks, |op| op.id("listNoteBacklinks")),
+    )
+    .api_route(
+      "/{uuid}/links",
+      get_with(links, |op| op.id("listNoteLinks")),
+    )
+    .api_route(
+      "/{uuid}/export",
+      get_with(export_note, |op| op.id("exportNote")),
+    )
     .api_route("/users", get_with(list_users, |op| op.id("listUsersNote")))
+    .api_route(
+      "/groups",
+      get_with(list_groups, |op| op.id("listGroupsNote")),
+    )
     .api_route("/share", put_with(share, |op| op.id("shareNote")))
     .api_route(
       "/share/public",
       put_with(share_public, |op| op.id("shareNotePublic")),
     )
     .api_route("/transfer", put_with(transfer, |op| op.id("transferNote")))
+    .api_route(
+      "/folders",
+      get_with(list_folders, |op| op.id("listNoteFolders")),
+    )
+    .api_route(
+      "/folders",
+      post_with(create_folder, |op| op.id("createNoteFolder")),
+    )
+    .api_route(
+      "/folders",
+      put_with(rename_folder, |op| op.id("renameNoteFolder")),
+    )
+    .api_route(
+      "/folders",
+      delete_with(delete_folder, |op| op.id("deleteNoteFolder")),
+    )
+    .api_route(
+      "/folders/move",
+      put_with(move_folder, |op| op.id("moveNoteFolder")),
+    )
+    .api_route("/folders/note", put_with(file_note, |op| op.id("fileNote")))
+    .api_route("/search", get_with(search, |op| op.id("searchNotes")))
+    .api_route("/tags", get_with(list_tags, |op| op.id("listNoteTags")))
+    .api_route("/tags", put_with(set_tags, |op| op.id("setNoteTags")))
+    .api_route(
+      "/favourite",
+      put_with(set_favourite, |op| op.id("setNoteFavourite")),
+    )
     .api_route("/config", get_with(notes_config, |op| op.id("notesConfig")))
 }
 
@@ -74,8 +138,22 @@ async fn notes_config(
   }))
 }
 
-async fn list(auth: JwtAuth, db: Connection) -> Result<Json<Vec<NoteInfo>>> {
-  Ok(Json(db.notes().list_for_user(auth.user_id).await?))
+#[derive(Deserialize, JsonSchema)]
+struct NoteListQuery {
+  /// Only return notes the user tagged with this tag.
+  tag: Option<String>,
+}
+
+async fn list(
+  auth: JwtAuth,
+  db: Connection,
+  Query(query): Query<NoteListQuery>,
+) -> Result<Json<Vec<NoteInfo>>> {
+  let mut notes = db.notes().list_for_user(auth.user_id).await?;
+  if let Some(tag) = query.tag {
+    notes.retain(|note| note.tags.contains(&tag));
+  }
+  Ok(Json(notes))
 }
 
 #[derive(Deserialize, JsonSchema)]
@@ -106,6 +184,199 @@ async fn create(
   Ok(Json(NoteCreateRes { id }))
 }
 
+const IMPORT_MAX_SIZE: usize = 20 * MB;
+const IMPORT_MAX_UNPACKED_SIZE: usize = 100 * MB;
+
+#[derive(Deserialize, JsonSchema)]
+struct NoteImportQuery {
+  /// Name of the uploaded file, `.zip` archives are imported as a folder
+  /// tree, anything else as a single Markdown file.
+  filename: String,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct NoteImportResult {
+  path: String,
+  note_id: Option<Uuid>,
+  error: Option<String>,
+}
+
+fn is_markdown(path: &str) -> bool {
+  let path = path.to_lowercase();
+  path.ends_with(".md") || path.ends_with(".markdown")
+}
+
+/// What importing a single file created, so a file that fails half-way can
+/// be removed again without failing the whole import.
+#[derive(Default)]
+struct ImportedFile {
+  note_id: Option<Uuid>,
+  folders: Vec<String>,
+}
+
+impl ImportedFile {
+  async fn store(
+    &mut self,
+    db: &Connection,
+    user_id: Uuid,
+    folders: &mut HashMap<String, Uuid>,
+    dirs: Option<&str>,
+    title: String,
+    imported: ImportedNote,
+  ) -> Result<Uuid> {
+    let id = db.notes().create(user_id, title).await?;
+    self.note_id = Some(id);
+    db.notes()
+      .set_content(id, imported.content, imported.preview)
+      .await?;
+    db.note_search().index_body(id, imported.text).await?;
+
+    let Some(dirs) = dirs else {
+      return Ok(id);
+    };
+    let mut parent = None;
+    let mut key = String::new();
+    for dir in dirs.split('/').filter(|dir| !dir.is_empty()) {
+      key.push_str(dir);
+      key.push('/');
+      let folder = match folders.get(&key) {
+        Some(folder) => *folder,
+        None => {
+          let folder = db
+            .note_folder()
+            .create(user_id, dir.to_string(), parent)
+            .await?;
+          folders.insert(key.clone(), folder);
+          self.folders.push(key.clone());
+          folder
+        }
+      };
+      parent = Some(folder);
+    }
+    db.note_folder().file_note(user_id, id, parent).await?;
+
+    Ok(id)
+  }
+
+  async fn roll_back(self, db: &Connection, folders: &mut HashMap<String, Uuid>) {
+    if let Some(id) = self.note_id
+      && let Err(err) = db.notes().delete(id).await
+    {
+      warn!(?err, %id, "failed to remove partially imported note");
+    }
+    for key in self.folders.into_iter().rev() {
+      if let Some(folder) = folders.remove(&key)
+        && let Err(err) = db.note_folder().delete(folder).await
+      {
+        warn!(?err, %folder, "failed to remove partially imported folder");
+      }
+    }
+  }
+}
+
+async fn import(
+  auth: JwtAuth,
+  db: Connection,
+  updater: Updater,
+  Extension(limits): Extension<NotesLimits>,
+  Query(query): Query<NoteImportQuery>,
+  data: Bytes,
+) -> Result<Json<Vec<NoteImportResult>>> {
+  let mut files = if query.filename.to_lowercase().ends_with(".zip") {
+    spawn_blocking(move || read_entries(&data, IMPORT_MAX_UNPACKED_SIZE))
+      .await
+      .context("failed to read zip archive")??
+      .into_iter()
+      // skip hidden files and the resource forks macOS adds to archives
+      .filter(|(path, _)| {
+        !path
+          .split('/')
+          .any(|part| part.starts_with('.') || part == "__MACOSX")
+      })
+      .collect()
+  } else {
+    vec![(query.filename, data.to_vec())]
+  };
+  files.sort_by(|a, b| a.0.cmp(&b.0));
+
+  let mut folders: HashMap<String, Uuid> = HashMap::new();
+  let mut results = Vec::new();
+  for (path, content) in files {
+    let error = |error: &str| NoteImportResult {
+      path: path.clone(),
+      note_id: None,
+      error: Some(error.into()),
+    };
+
+    if !is_markdown(&path) {
+      results.push(error("not a markdown file"));
+      continue;
+    }
+    let Ok(markdown) = String::from_utf8(content) else {
+      results.push(error("file is not valid UTF-8"));
+      continue;
+    };
+    match db.notes().count_owned(auth.user_id).await {
+      Ok(count) if count >= limits.max_per_user as u64 => {
+        results.push(error("note limit reached"));
+        continue;
+      }
+      Ok(_) => {}
+      Err(err) => {
+        warn!(?err, path, "failed to import note");
+        results.push(error("failed to import"));
+        continue;
+      }
+    }
+
+    let mut imported = match import_markdown(markdown).await {
+      Ok(imported) => imported,
+      Err(err) => {
+        warn!(?err, path, "failed to import note");
+        results.push(error("failed to import"));
+        continue;
+      }
+    };
+    let (dirs, file) = match path.rsplit_once('/') {
+      Some((dirs, file)) => (Some(dirs), file),
+      None => (None, path.as_str()),
+    };
+    let title = imported.title.take().unwrap_or_else(|| {
+      let stem = file.rsplit_once('.').map_or(file, |(stem, _)| stem);
+      stem.to_string()
+    });
+
+    let mut created = ImportedFile::default();
+    let stored = created
+      .store(&db, auth.user_id, &mut folders, dirs, title, imported)
+      .await;
+    let id = match stored {
+      Ok(id) => id,
+      Err(err) => {
+        warn!(?err, path, "failed to import note");
+        created.roll_back(&db, &mut folders).await;
+        results.push(error("failed to import"));
+        continue;
+      }
+    };
+
+    notify_note_update(&updater, vec![auth.user_id], id).await;
+    results.push(NoteImportResult {
+      path,
+      note_id: Some(id),
+      error: None,
+    });
+  }
+
+  if !folders.is_empty() {
+    updater
+      .send_to(auth.user_id, UpdateMessage::NoteFolders)
+      .await;
+  }
+
+  Ok(Json(results))
+}
+
 #[derive(Deserialize, JsonSchema)]
 struct NotePath {
   uuid: Uuid,
@@ -132,6 +403,7 @@ async fn apply_note_edit(
   db: Connection,
   updater: Updater,
   state: NoteEditing,
+  mail: MentionMail,
   Path(NotePath { uuid }): Path<NotePath>,
   data: Bytes,
 ) -> Result<()> {
@@ -158,7 +430,7 @@ async fn apply_note_edit(
 
   if new_content != content {
     state.apply_update(uuid, &new_content).await?;
-    db.notes().set_content(uuid, new_content, preview).await?;
+    store_content(&db, &updater, &mail, uuid, &doc, new_content, preview).await?;
   }
   drop(lock);
 
@@ -177,6 +449,70 @@ async fn apply_note_edit(
   Ok(())
 }
 
+/// Notes linking to this one that the caller can see in their note list.
+async fn backlinks(
+  auth: JwtAuth,
+  db: Connection,
+  Path(NotePath { uuid }): Path<NotePath>,
+) -> Result<Json<Vec<LinkedNote>>> {
+  if !db.notes().has_access(auth.user_id, uuid).await? {
+    bail!(NOT_FOUND, "note not found");
+  }
+
+  let visible = db.notes().visible_ids(auth.user_id).await?;
+  Ok(Json(db.note_link().backlinks(uuid, &visible).await?))
+}
+
+#[derive(Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum BrokenLink {
+  /// The target is in the trash or was purged.
+  Deleted,
+  /// The target exists but is not shared with the caller (anymore).
+  NoAccess,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct NoteLink {
+  id: Uuid,
+  /// Only set while the caller can open the target.
+  title: Option<String>,
+  broken: Option<BrokenLink>,
+}
+
+/// Notes this note links to, links the caller can not follow are marked as
+/// broken.
+async fn links(
+  auth: JwtAuth,
+  db: Connection,
+  Path(NotePath { uuid }): Path<NotePath>,
+) -> Result<Json<Vec<NoteLink>>> {
+  if !db.notes().has_access(auth.user_id, uuid).await? {
+    bail!(NOT_FOUND, "note not found");
+  }
+
+  let mut links = Vec::new();
+  for target in db.note_link().targets(uuid).await? {
+    let (title, broken) = match target.note {
+      None | Some((_, Some(_))) => (None, Some(BrokenLink::Deleted)),
+      Some((title, None)) => {
+        if db.notes().has_access(auth.user_id, target.id).await? {
+          (Some(title), None)
+        } else {
+          (None, Some(BrokenLink::NoAccess))
+        }
+      }
+    };
+    links.push(NoteLink {
+      id: target.id,
+      title,
+      broken,
+    });
+  }
+
+  Ok(Json(links))
+}
+
 async fn info_public(
   db: Connection,
   Path(NotePath { uuid }): Path<NotePath>,
@@ -204,6 +540,24 @@ async fn note_content(
   Ok(Bytes::from(content))
 }
 
+async fn export_note(
+  auth: JwtAuth,
+  db: Connection,
+  Path(NotePath { uuid }): Path<NotePath>,
+  Query(query): Query<ExportQuery>,
+) -> Result<Response> {
+  if !db.notes().has_access(auth.user_id, uuid).await? {
+    bail!(NOT_FOUND, "note not found");
+  }
+
+  let Some(info) = db.notes().info(uuid, auth.user_id).await? else {
+    bail!(NOT_FOUND, "note not found");
+  };
+  let content = db.notes().content(uuid).await?.unwrap_or_default();
+
+  export_response(uuid, &info.title, &content, query.format).await
+}
+
 #[derive(Deserialize, JsonSchema)]
 struct NoteEditReq {
   note_id: Uuid,
@@ -219,6 +573,9 @@ async fn edit(
   if !db.notes().is_owner(auth.user_id, req.note_id).await? {
     bail!(FORBIDDEN, "forbidden");
   }
+  if db.notes().is_trashed(req.note_id).await? {
+    bail!(NOT_FOUND, "note not found");
+  }
 
   db.notes().edit_title(req.note_id, req.title).await?;
 
@@ -235,22 +592,25 @@ struct NoteDeleteReq {
   note_id: Uuid,
 }
 
+/// Moves the note to the trash, it is purged for good once the retention
+/// period ends.
 async fn delete(
   auth: JwtAuth,
   db: Connection,
-  storage: FileStorage,
   updater: Updater,
   Json(req): Json<NoteDeleteReq>,
 ) -> Result<()> {
   if !db.notes().is_owner(auth.user_id, req.note_id).await? {
     bail!(FORBIDDEN, "forbidden");
   }
+  if db.notes().is_trashed(req.note_id).await? {
+    bail!(NOT_FOUND, "note not found");
+  }
 
   let mut users = db.notes().shared_user_ids(req.note_id).await?;
   users.push(auth.user_id);
 
-  delete_storage_for_note(&db, &storage, req.note_id).await?;
-  db.notes().delete(req.note_id).await?;
+  db.notes().trash(req.note_id).await?;
 
   notify_note_update(&updater, users, req.note_id).await;
 
@@ -261,30 +621,42 @@ async fn delete(
 struct NoteShareReq {
   note_id: Uuid,
   shared_with: Vec<NoteShareEntry>,
+  /// Left unchanged when omitted.
+  shared_with_groups: Option<Vec<NoteGroupShareEntry>>,
 }
 
 async fn share(
   auth: JwtAuth,
   db: Connection,
   updater: Updater,
+  state: NoteEditing,
+  mail: MentionMail,
   Json(req): Json<NoteShareReq>,
 ) -> Result<()> {
   if !db.notes().is_owner(auth.user_id, req.note_id).await? {
     bail!(FORBIDDEN, "forbidden");
   }
+  if db.notes().is_trashed(req.note_id).await? {
+    bail!(NOT_FOUND, "note not found");
+  }
 
   let mut users = db.notes().shared_user_ids(req.note_id).await?;
   users.push(auth.user_id);
-  users.extend(req.shared_with.iter().map(|s| s.user_id));
 
   db.notes()
     .set_shared_users(req.note_id, auth.user_id, req.shared_with)
     .await?;
+  if let Some(groups) = req.shared_with_groups {
+    db.notes().set_shared_groups(req.note_id, groups).await?;
+  }
+  // both the users who lost and who gained access need to refresh
+  users.extend(db.notes().shared_user_ids(req.note_id).await?);
 
   users.sort_unstable();
   users.dedup();
 
   notify_note_update(&updater, users, req.note_id).await;
+  notify_pending_mentions(&db, &updater, &mail, &state, req.note_id).await?;
 
   Ok(())
 }
@@ -300,11 +672,16 @@ async fn share_public(
   db: Connection,
   updater: Updater,
   public_updater: PublicNoteUpdater,
+  state: NoteEditing,
+  mail: MentionMail,
   Json(req): Json<NotePublicShareReq>,
 ) -> Result<()> {
   if !db.notes().is_owner(auth.user_id, req.note_id).await? {
     bail!(FORBIDDEN, "forbidden");
   }
+  if db.notes().is_trashed(req.note_id).await? {
+    bail!(NOT_FOUND, "note not found");
+  }
 
   db.notes()
     .set_public_access(req.note_id, req.public_access.clone())
@@ -323,6 +700,7 @@ async fn share_public(
   users.push(auth.user_id);
 
   notify_note_update(&updater, users, req.note_id).await;
+  notify_pending_mentions(&db, &updater, &mail, &state, req.note_id).await?;
 
   Ok(())
 }
@@ -343,6 +721,9 @@ async fn transfer(
   if !db.notes().is_owner(auth.user_id, req.note_id).await? {
     bail!(FORBIDDEN, "forbidden");
   }
+  if db.notes().is_trashed(req.note_id).await? {
+    bail!(NOT_FOUND, "note not found");
+  }
 
   if req.new_owner_id == auth.user_id {
     bail!(BAD_REQUEST, "cannot transfer to self");
@@ -368,12 +749,255 @@ async fn transfer(
   Ok(())
 }
 
+async fn list_folders(auth: JwtAuth, db: Connection) -> Result<Json<Vec<NoteFolderInfo>>> {
+  Ok(Json(db.note_folder().list_for_user(auth.user_id).await?))
+}
+
+/// Rejects folders that do not exist or belong to someone else.
+async fn ensure_own_folder(db: &Connection, user_id: Uuid, folder_id: Uuid) -> Result<()> {
+  if db.note_folder().get(user_id, folder_id).await?.is_none() {
+    bail!(NOT_FOUND, "folder not found");
+  }
+  Ok(())
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct FolderCreateReq {
+  name: String,
+  parent: Option<Uuid>,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct FolderCreateRes {
+  id: Uuid,
+}
+
+async fn create_folder(
+  auth: JwtAuth,
+  db: Connection,
+  updater: Updater,
+  Json(req): Json<FolderCreateReq>,
+) -> Result<Json<FolderCreateRes>> {
+  if let Some(parent) = req.parent {
+    ensure_own_folder(&db, auth.user_id, parent).await?;
+  }
+
+  let id = db
+    .note_folder()
+    .create(auth.user_id, req.name, req.parent)
+    .await?;
+  updater
+    .send_to(auth.user_id, UpdateMessage::NoteFolders)
+    .await;
+
+  Ok(Json(FolderCreateRes { id }))
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct FolderRenameReq {
+  folder_id: Uuid,
+  name: String,
+}
+
+async fn rename_folder(
+  auth: JwtAuth,
+  db: Connection,
+  updater: Updater,
+  Json(req): Json<FolderRenameReq>,
+) -> Result<()> {
+  ensure_own_folder(&db, auth.user_id, req.folder_id).await?;
+
+  db.note_folder().rename(req.folder_id, req.name).await?;
+  updater
+    .send_to(auth.user_id, UpdateMessage::NoteFolders)
+    .await;
+
+  Ok(())
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct FolderDeleteReq {
+  folder_id: Uuid,
+}
+
+async fn delete_folder(
+  auth: JwtAuth,
+  db: Connection,
+  updater: Updater,
+  Json(req): Json<FolderDeleteReq>,
+) -> Result<()> {
+  ensure_own_folder(&db, auth.user_id, req.folder_id).await?;
+
+  db.note_folder().delete(req.folder_id).await?;
+  updater
+    .send_to(auth.user_id, UpdateMessage::NoteFolders)
+    .await;
+
+  Ok(())
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct FolderMoveReq {
+  folder_id: Uuid,
+  parent: Option<Uuid>,
+  /// Index among the new siblings, appended when past the end.
+  position: usize,
+}
+
+async fn move_folder(
+  auth: JwtAuth,
+  db: Connection,
+  updater: Updater,
+  Json(req): Json<FolderMoveReq>,
+) -> Result<()> {
+  ensure_own_folder(&db, auth.user_id, req.folder_id).await?;
+  if let Some(parent) = req.parent {
+    ensure_own_folder(&db, auth.user_id, parent).await?;
+  }
+
+  if !db
+    .note_folder()
+    .move_folder(auth.user_id, req.folder_id, req.parent, req.position)
+    .await?
+  {
+    bail!(CONFLICT, "cannot move a folder into itself");
+  }
+  updater
+    .send_to(auth.user_id, UpdateMessage::NoteFolders)
+    .await;
+
+  Ok(())
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct FileNoteReq {
+  note_id: Uuid,
+  folder_id: Option<Uuid>,
+}
+
+async fn file_note(
+  auth: JwtAuth,
+  db: Connection,
+  updater: Updater,
+  Json(req): Json<FileNoteReq>,
+) -> Result<()> {
+  if !db.notes().has_access(auth.user_id, req.note_id).await? {
+    bail!(NOT_FOUND, "note not found");
+  }
+  if let Some(folder) = req.folder_id {
+    ensure_own_folder(&db, auth.user_id, folder).await?;
+  }
+
+  db.note_folder()
+    .file_note(auth.user_id, req.note_id, req.folder_id)
+    .await?;
+  notify_note_update(&updater, vec![auth.user_id], req.note_id).await;
+
+  Ok(())
+}
+
+const SEARCH_DEFAULT_LIMIT: u64 = 20;
+const SEARCH_MAX_LIMIT: u64 = 100;
+
+#[derive(Deserialize, JsonSchema)]
+struct NoteSearchQuery {
+  q: String,
+  limit: Option<u64>,
+}
+
+async fn search(
+  auth: JwtAuth,
+  db: Connection,
+  Query(query): Query<NoteSearchQuery>,
+) -> Result<Json<Vec<NoteSearchResult>>> {
+  let limit = query
+    .limit
+    .unwrap_or(SEARCH_DEFAULT_LIMIT)
+    .clamp(1, SEARCH_MAX_LIMIT);
+  let visible = db.notes().visible_ids(auth.user_id).await?;
+
+  Ok(Json(
+    db.note_search().search(&query.q, &visible, limit).await?,
+  ))
+}
+
+const MAX_TAG_LEN: usize = 64;
+
+async fn list_tags(auth: JwtAuth, db: Connection) -> Result<Json<Vec<String>>> {
+  Ok(Json(db.note_tag().list_tags(auth.user_id).await?))
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct NoteTagsReq {
+  note_id: Uuid,
+  tags: Vec<String>,
+}
+
+async fn set_tags(
+  auth: JwtAuth,
+  db: Connection,
+  updater: Updater,
+  Json(req): Json<NoteTagsReq>,
+) -> Result<()> {
+  if !db.notes().has_access(auth.user_id, req.note_id).await? {
+    bail!(NOT_FOUND, "note not found");
+  }
+
+  let mut tags: Vec<String> = req
+    .tags
+    .iter()
+    .map(|tag| tag.trim().to_string())
+    .filter(|tag| !tag.is_empty())
+    .collect();
+  if tags.iter().any(|tag| tag.chars().count() > MAX_TAG_LEN) {
+    bail!(BAD_REQUEST, "tags can be at most {MAX_TAG_LEN} characters");
+  }
+  tags.sort();
+  tags.dedup();
+
+  db.note_tag()
+    .set_tags(auth.user_id, req.note_id, tags)
+    .await?;
+  notify_note_update(&updater, vec![auth.user_id], req.note_id).await;
+
+  Ok(())
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct NoteFavouriteReq {
+  note_id: Uuid,
+  favourite: bool,
+}
+
+async fn set_favourite(
+  auth: JwtAuth,
+  db: Connection,
+  updater: Updater,
+  Json(req): Json<NoteFavouriteReq>,
+) -> Result<()> {
+  if !db.notes().has_access(auth.user_id, req.note_id).await? {
+    bail!(NOT_FOUND, "note not found");
+  }
+
+  db.note_tag()
+    .set_favourite(auth.user_id, req.note_id, req.favourite)
+    .await?;
+  notify_note_update(&updater, vec![auth.user_id], req.note_id).await;
+
+  Ok(())
+}
+
 async fn list_users(_auth: JwtAuth, db: Connection) -> Result<Json<Vec<SimpleUserInfo>>> {
   let users = db.user().list_users_simple().await?;
   Ok(Json(users))
 }
 
-async fn notify_note_update(updater: &Updater, users: Vec<Uuid>, note_id: Uuid) {
+async fn list_groups(_auth: JwtAuth, db: Connection) -> Result<Json<Vec<SimpleGroupInfo>>> {
+  let groups = db.group().list_groups_simple().await?;
+  Ok(Json(groups))
+}
+
+pub(super) async fn notify_note_update(updater: &Updater, users: Vec<Uuid>, note_id: Uuid) {
   let message = UpdateMessage::Note { uuid: note_id };
   for user_id in users {
     updater.send_to(user_id, message).await;
@@ -391,7 +1015,10 @@ mod test {
   use crate::db::{
     DBTrait,
     notes::NoteShareEntry,
-    test::{auth_cookie, auth_state, body_json, insert_user, test_db, updater},
+    test::{
+      add_user_to_group, auth_cookie, auth_state, body_json, insert_group, insert_user, test_db,
+      updater,
+    },
   };
   use axum::{
     Extension, Router,
@@ -419,7 +1046,7 @@ mod test {
     storage: FileStorage,
   ) -> Router {
     let (public_state, _) = PublicNoteUpdateState::init();
-    let editing = super::NoteEditing::init(storage.clone(), upd.clone());
+    let editing = super::NoteEditing::init(storage.clone(), upd.clone(), Default::default());
     Router::new()
       .route(
         "/",
@@ -431,11 +1058,27 @@ mod test {
       .route("/{uuid}", get(super::info).put(super::apply_note_edit))
       .route("/{uuid}/public", get(super::info_public))
       .route("/{uuid}/content", get(super::note_content))
+      .route("/{uuid}/export", get(super::export_note))
+      .route("/{uuid}/backlinks", get(super::backlinks))
+      .route("/{uuid}/links", get(super::links))
+      .route("/import", axum::routing::post(super::import))
       .route("/users", get(super::list_users))
       .route("/share", axum::routing::put(super::share))
       .route("/share/public", axum::routing::put(super::share_public))
       .route("/transfer", axum::routing::put(super::transfer))
       .route("/config", get(super::notes_config))
+      .route(
+        "/folders",
+        get(super::list_folders)
+          .post(super::create_folder)
+          .put(super::rename_folder)
+          .delete(super::delete_folder),
+      )
+      .route("/folders/move", axum::routing::put(super::move_folder))
+      .route("/folders/note", axum::routing::put(super::file_note))
+      .route("/tags", get(super::list_tags).put(super::set_tags))
+      .route("/search", get(super::search))
+      .route("/favourite", axum::routing::put(super::set_favourite))
       .layer(Extension(public_state))
       .layer(Extension(public_upd))
       .layer(Extension(limits))
@@ -708,7 +1351,7 @@ mod test {
   }
 
   #[tokio::test]
-  async fn delete_as_owner_removes_note_and_snapshot_files() {
+  async fn delete_as_owner_moves_note_to_trash_and_keeps_snapshot_files() {
     let s = setup().await;
     let note = s.db.notes().create(s.user, "T".into()).await.unwrap();
     let snapshot_id = s
@@ -741,9 +1384,9 @@ mod test {
       .await
       .unwrap();
     assert_eq!(resp.status(), StatusCode::OK);
-    assert!(s.db.notes().info(note, s.user).await.unwrap().is_none());
+    assert!(s.db.notes().is_trashed(note).await.unwrap());
     assert!(
-      !s.storage
+      s.storage
         .note_snapshot()
         .exists(note, snapshot_id)
         .await
@@ -752,20 +1395,72 @@ mod test {
   }
 
   #[tokio::test]
-  async fn delete_as_owner_removes_note() {
+  async fn trashed_notes_cannot_be_edited_shared_or_transferred() {
     let s = setup().await;
+    let recipient = insert_user(&s.db, "recipient", "r@x.com").await;
     let note = s.db.notes().create(s.user, "T".into()).await.unwrap();
+    s.db.notes().trash(note).await.unwrap();
     let app = app(
       s.db.clone(),
       s.jwt,
       s.upd,
       s.public_upd,
       NotesLimits { max_per_user: 20 },
-      s.storage.clone(),
+      s.storage,
     );
 
-    let resp = app
-      .oneshot(request(
+    for (uri, body) in [
+      ("/", json!({ "note_id": note, "title": "Renamed" })),
+      (
+        "/share",
+        json!({ "note_id": note, "shared_with": [{ "user_id": recipient, "access": "view" }] }),
+      ),
+      (
+        "/share/public",
+        json!({ "note_id": note, "public_access": "view" }),
+      ),
+      (
+        "/transfer",
+        json!({ "note_id": note, "new_owner_id": recipient }),
+      ),
+    ] {
+      let resp = app
+        .clone()
+        .oneshot(request("PUT", uri, Some(&s.cookie), Some(body)))
+        .await
+        .unwrap();
+      assert_eq!(resp.status(), StatusCode::NOT_FOUND, "{uri}");
+    }
+
+    let info = s.db.notes().info(note, s.user).await.unwrap().unwrap();
+    assert_eq!(info.title, "T");
+    assert!(info.shared_with.is_empty());
+    assert!(s.db.notes().is_owner(s.user, note).await.unwrap());
+    assert!(
+      s.db
+        .notes()
+        .get_public_access(note)
+        .await
+        .unwrap()
+        .is_none()
+    );
+  }
+
+  #[tokio::test]
+  async fn delete_as_owner_hides_note_and_frees_limit() {
+    let s = setup().await;
+    let note = s.db.notes().create(s.user, "T".into()).await.unwrap();
+    let app = app(
+      s.db.clone(),
+      s.jwt,
+      s.upd,
+      s.public_upd,
+      NotesLimits { max_per_user: 20 },
+      s.storage.clone(),
+    );
+
+    let resp = app
+      .oneshot(request(
         "DELETE",
         "/",
         Some(&s.cookie),
@@ -774,7 +1469,9 @@ mod test {
       .await
       .unwrap();
     assert_eq!(resp.status(), StatusCode::OK);
-    assert!(s.db.notes().info(note, s.user).await.unwrap().is_none());
+    assert!(s.db.notes().list_for_user(s.user).await.unwrap().is_empty());
+    assert_eq!(s.db.notes().count_owned(s.user).await.unwrap(), 0);
+    assert!(!s.db.notes().has_access(s.user, note).await.unwrap());
   }
 
   #[tokio::test]
@@ -813,6 +1510,116 @@ mod test {
     );
   }
 
+  #[tokio::test]
+  async fn share_notifies_users_mentioned_before_they_had_access() {
+    use yrs::{Doc, ReadTxn, StateVector, Transact, Xml, XmlElementPrelim, XmlFragment};
+
+    let s = setup().await;
+    let friend = insert_user(&s.db, "friend", "f@x.com").await;
+    let stranger = insert_user(&s.db, "stranger", "s@x.com").await;
+    let note = s.db.notes().create(s.user, "T".into()).await.unwrap();
+
+    let doc = Doc::new();
+    let fragment = doc.get_or_insert_xml_fragment("default");
+    {
+      let mut txn = doc.transact_mut();
+      let paragraph = fragment.push_back(&mut txn, XmlElementPrelim::empty("paragraph"));
+      for id in [friend, stranger] {
+        let mention = paragraph.push_back(&mut txn, XmlElementPrelim::empty("mention"));
+        mention.insert_attribute(&mut txn, "id", id.to_string());
+      }
+    }
+    let content = doc
+      .transact()
+      .encode_state_as_update_v1(&StateVector::default());
+    s.db
+      .notes()
+      .set_content(note, content, String::new())
+      .await
+      .unwrap();
+
+    let app = app(
+      s.db.clone(),
+      s.jwt,
+      s.upd,
+      s.public_upd,
+      NotesLimits { max_per_user: 20 },
+      s.storage.clone(),
+    );
+    let resp = app
+      .clone()
+      .oneshot(request(
+        "PUT",
+        "/share",
+        Some(&s.cookie),
+        Some(json!({
+          "note_id": note,
+          "shared_with": [{ "user_id": friend, "access": "view" }]
+        })),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(s.db.note_mention().list(note).await.unwrap(), [friend]);
+
+    let resp = app
+      .oneshot(request(
+        "PUT",
+        "/share/public",
+        Some(&s.cookie),
+        Some(json!({ "note_id": note, "public_access": "view" })),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let mut mentioned = s.db.note_mention().list(note).await.unwrap();
+    mentioned.sort_unstable();
+    let mut expected = vec![friend, stranger];
+    expected.sort_unstable();
+    assert_eq!(mentioned, expected);
+  }
+
+  #[tokio::test]
+  async fn share_with_group_grants_members_access() {
+    let s = setup().await;
+    let member = insert_user(&s.db, "member", "m@x.com").await;
+    let oncall = insert_group(&s.db, "oncall").await;
+    add_user_to_group(&s.db, oncall, member).await;
+    let note = s.db.notes().create(s.user, "Runbook".into()).await.unwrap();
+    let app = app(
+      s.db.clone(),
+      s.jwt,
+      s.upd,
+      s.public_upd,
+      NotesLimits { max_per_user: 20 },
+      s.storage.clone(),
+    );
+
+    let resp = app
+      .oneshot(request(
+        "PUT",
+        "/share",
+        Some(&s.cookie),
+        Some(json!({
+          "note_id": note,
+          "shared_with": [],
+          "shared_with_groups": [{ "group_id": oncall, "access": "view" }]
+        })),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(s.db.notes().has_access(member, note).await.unwrap());
+    assert!(!s.db.notes().can_edit(member, note).await.unwrap());
+    assert_eq!(
+      s.db.notes().shared_user_ids(note).await.unwrap(),
+      vec![member]
+    );
+    let info = s.db.notes().info(note, s.user).await.unwrap().unwrap();
+    assert_eq!(info.shared_with_groups.len(), 1);
+    assert_eq!(info.shared_with_groups[0].name, "oncall");
+  }
+
   #[tokio::test]
   async fn share_forbidden_for_non_owner() {
     let s = setup().await;
@@ -1624,6 +2431,139 @@ mod test {
     );
   }
 
+  #[tokio::test]
+  async fn apply_note_edit_indexes_links_for_backlinks() {
+    use entity::sea_orm_active_enums::NoteShareAccess;
+    use yrs::{Doc, ReadTxn, StateVector, Transact, Xml, XmlElementPrelim, XmlFragment};
+
+    let s = setup().await;
+    let reader = insert_user(&s.db, "reader", "reader@x.com").await;
+    let stranger = insert_user(&s.db, "stranger", "stranger@x.com").await;
+    let reader_cookie = auth_cookie(&s.db, &s.jwt, reader).await;
+    let source = s.db.notes().create(s.user, "Source".into()).await.unwrap();
+    let target = s.db.notes().create(s.user, "Target".into()).await.unwrap();
+    let trashed = s.db.notes().create(s.user, "Trashed".into()).await.unwrap();
+    let private = s
+      .db
+      .notes()
+      .create(stranger, "Private".into())
+      .await
+      .unwrap();
+    for note in [source, target] {
+      s.db
+        .notes()
+        .set_shared_users(
+          note,
+          s.user,
+          vec![NoteShareEntry {
+            user_id: reader,
+            access: NoteShareAccess::View,
+          }],
+        )
+        .await
+        .unwrap();
+    }
+    s.db
+      .notes()
+      .set_content(source, yrs_state(""), String::new())
+      .await
+      .unwrap();
+
+    let doc = Doc::new();
+    let fragment = doc.get_or_insert_xml_fragment("default");
+    {
+      let mut txn = doc.transact_mut();
+      let paragraph = fragment.push_back(&mut txn, XmlElementPrelim::empty("paragraph"));
+      for id in [target, trashed, private] {
+        let link = paragraph.push_back(&mut txn, XmlElementPrelim::empty("noteLink"));
+        link.insert_attribute(&mut txn, "id", id.to_string());
+      }
+    }
+    let update = doc
+      .transact()
+      .encode_state_as_update_v1(&StateVector::default());
+
+    let app = app(
+      s.db.clone(),
+      s.jwt,
+      s.upd,
+      s.public_upd,
+      NotesLimits { max_per_user: 20 },
+      s.storage.clone(),
+    );
+    let resp = app
+      .clone()
+      .oneshot(request_bytes(
+        "PUT",
+        &format!("/{source}"),
+        Some(&s.cookie),
+        update,
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    s.db.notes().trash(trashed).await.unwrap();
+
+    let resp = app
+      .clone()
+      .oneshot(request(
+        "GET",
+        &format!("/{target}/backlinks"),
+        Some(&reader_cookie),
+        None,
+      ))
+      .await
+      .unwrap();
+    assert_eq!(
+      body_json(resp).await,
+      json!([{ "id": source, "title": "Source" }])
+    );
+    let resp = app
+      .clone()
+      .oneshot(request(
+        "GET",
+        &format!("/{private}/backlinks"),
+        Some(&reader_cookie),
+        None,
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let resp = app
+      .oneshot(request(
+        "GET",
+        &format!("/{source}/links"),
+        Some(&reader_cookie),
+        None,
+      ))
+      .await
+      .unwrap();
+    let links = body_json(resp).await;
+    let link = |id: Uuid| {
+      links
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|link| link["id"] == json!(id))
+        .unwrap()
+        .clone()
+    };
+    assert_eq!(links.as_array().unwrap().len(), 3);
+    assert_eq!(
+      link(target),
+      json!({ "id": target, "title": "Target", "broken": null })
+    );
+    assert_eq!(
+      link(trashed),
+      json!({ "id": trashed, "title": null, "broken": "deleted" })
+    );
+    assert_eq!(
+      link(private),
+      json!({ "id": private, "title": null, "broken": "no_access" })
+    );
+  }
+
   #[tokio::test]
   async fn apply_note_edit_not_found_for_no_access() {
     let s = setup().await;
@@ -1713,4 +2653,496 @@ mod test {
       .unwrap();
     assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
   }
+
+  #[tokio::test]
+  async fn folders_cannot_be_moved_into_themselves() {
+    let s = setup().await;
+    let outer = s
+      .db
+      .note_folder()
+      .create(s.user, "Outer".into(), None)
+      .await
+      .unwrap();
+    let inner = s
+      .db
+      .note_folder()
+      .create(s.user, "Inner".into(), Some(outer))
+      .await
+      .unwrap();
+    let app = app(
+      s.db.clone(),
+      s.jwt,
+      s.upd,
+      s.public_upd,
+      NotesLimits { max_per_user: 20 },
+      s.storage.clone(),
+    );
+
+    let resp = app
+      .oneshot(request(
+        "PUT",
+        "/folders/move",
+        Some(&s.cookie),
+        Some(json!({ "folder_id": outer, "parent": inner, "position": 0 })),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+  }
+
+  #[tokio::test]
+  async fn file_note_sets_folder_for_the_caller_only() {
+    let s = setup().await;
+    let friend = insert_user(&s.db, "friend", "f@x.com").await;
+    let note = s.db.notes().create(s.user, "T".into()).await.unwrap();
+    s.db
+      .notes()
+      .set_shared_users(
+        note,
+        s.user,
+        vec![NoteShareEntry {
+          user_id: friend,
+          access: NoteShareAccess::View,
+        }],
+      )
+      .await
+      .unwrap();
+    let folder = s
+      .db
+      .note_folder()
+      .create(s.user, "Runbooks".into(), None)
+      .await
+      .unwrap();
+    let foreign = s
+      .db
+      .note_folder()
+      .create(friend, "Mine".into(), None)
+      .await
+      .unwrap();
+    let app = app(
+      s.db.clone(),
+      s.jwt,
+      s.upd,
+      s.public_upd,
+      NotesLimits { max_per_user: 20 },
+      s.storage.clone(),
+    );
+
+    let resp = app
+      .clone()
+      .oneshot(request(
+        "PUT",
+        "/folders/note",
+        Some(&s.cookie),
+        Some(json!({ "note_id": note, "folder_id": foreign })),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let resp = app
+      .clone()
+      .oneshot(request(
+        "PUT",
+        "/folders/note",
+        Some(&s.cookie),
+        Some(json!({ "note_id": note, "folder_id": folder })),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = app
+      .oneshot(request("GET", "/", Some(&s.cookie), None))
+      .await
+      .unwrap();
+    assert_eq!(body_json(resp).await[0]["folder"], folder.to_string());
+    let info = s.db.notes().info(note, friend).await.unwrap().unwrap();
+    assert_eq!(info.folder, None);
+  }
+
+  #[tokio::test]
+  async fn list_filters_by_tag_and_reports_favourites() {
+    let s = setup().await;
+    let tagged = s.db.notes().create(s.user, "Tagged".into()).await.unwrap();
+    s.db.notes().create(s.user, "Other".into()).await.unwrap();
+    let app = app(
+      s.db.clone(),
+      s.jwt,
+      s.upd,
+      s.public_upd,
+      NotesLimits { max_per_user: 20 },
+      s.storage.clone(),
+    );
+
+    let resp = app
+      .clone()
+      .oneshot(request(
+        "PUT",
+        "/tags",
+        Some(&s.cookie),
+        Some(json!({ "note_id": tagged, "tags": [" work ", "work", ""] })),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = app
+      .clone()
+      .oneshot(request(
+        "PUT",
+        "/favourite",
+        Some(&s.cookie),
+        Some(json!({ "note_id": tagged, "favourite": true })),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = app
+      .clone()
+      .oneshot(request("GET", "/?tag=work", Some(&s.cookie), None))
+      .await
+      .unwrap();
+    let body = body_json(resp).await;
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["id"], tagged.to_string());
+    assert_eq!(body[0]["tags"], json!(["work"]));
+    assert_eq!(body[0]["favourite"], true);
+
+    let resp = app
+      .oneshot(request("GET", "/tags", Some(&s.cookie), None))
+      .await
+      .unwrap();
+    assert_eq!(body_json(resp).await, json!(["work"]));
+  }
+
+  #[tokio::test]
+  async fn set_tags_requires_access_and_bounded_length() {
+    let s = setup().await;
+    let stranger = insert_user(&s.db, "stranger", "s@x.com").await;
+    let note = s.db.notes().create(stranger, "T".into()).await.unwrap();
+    let own = s.db.notes().create(s.user, "Mine".into()).await.unwrap();
+    let app = app(
+      s.db.clone(),
+      s.jwt,
+      s.upd,
+      s.public_upd,
+      NotesLimits { max_per_user: 20 },
+      s.storage.clone(),
+    );
+
+    let resp = app
+      .clone()
+      .oneshot(request(
+        "PUT",
+        "/tags",
+        Some(&s.cookie),
+        Some(json!({ "note_id": note, "tags": ["x"] })),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let resp = app
+      .oneshot(request(
+        "PUT",
+        "/tags",
+        Some(&s.cookie),
+        Some(json!({ "note_id": own, "tags": ["x".repeat(65)] })),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+  }
+
+  #[tokio::test]
+  async fn search_only_returns_visible_notes() {
+    let s = setup().await;
+    let stranger = insert_user(&s.db, "stranger", "s@x.com").await;
+    let own = s.db.notes().create(s.user, "Deploy".into()).await.unwrap();
+    let foreign = s
+      .db
+      .notes()
+      .create(stranger, "Deploy".into())
+      .await
+      .unwrap();
+    s.db
+      .notes()
+      .set_public_access(foreign, Some(NoteShareAccess::View))
+      .await
+      .unwrap();
+    s.db
+      .note_search()
+      .index_body(own, "steps to deploy the backend".into())
+      .await
+      .unwrap();
+    let app = app(
+      s.db.clone(),
+      s.jwt,
+      s.upd,
+      s.public_upd,
+      NotesLimits { max_per_user: 20 },
+      s.storage.clone(),
+    );
+
+    let resp = app
+      .clone()
+      .oneshot(request("GET", "/search?q=backend", Some(&s.cookie), None))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = body_json(resp).await;
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["id"], own.to_string());
+    assert!(
+      body[0]["snippet"]
+        .as_str()
+        .unwrap()
+        .contains("<mark>backend</mark>")
+    );
+
+    let resp = app
+      .oneshot(request("GET", "/search?q=deploy", Some(&s.cookie), None))
+      .await
+      .unwrap();
+    assert_eq!(body_json(resp).await.as_array().unwrap().len(), 1);
+  }
+
+  #[tokio::test]
+  async fn export_note_renders_html_for_viewers() {
+    let s = setup().await;
+    let viewer = insert_user(&s.db, "viewer", "v@x.com").await;
+    let viewer_cookie = auth_cookie(&s.db, &s.jwt, viewer).await;
+    let stranger = insert_user(&s.db, "stranger", "s@x.com").await;
+    let stranger_cookie = auth_cookie(&s.db, &s.jwt, stranger).await;
+    let note = s.db.notes().create(s.user, "<Plans>".into()).await.unwrap();
+    let content = {
+      use yrs::{ReadTxn, StateVector, Transact};
+      crate::notes::markdown::test::sample_doc()
+        .transact()
+        .encode_state_as_update_v1(&StateVector::default())
+    };
+    s.db
+      .notes()
+      .set_content(note, content, "p".into())
+      .await
+      .unwrap();
+    s.db
+      .notes()
+      .set_shared_users(
+        note,
+        s.user,
+        vec![NoteShareEntry {
+          user_id: viewer,
+          access: NoteShareAccess::View,
+        }],
+      )
+      .await
+      .unwrap();
+    let app = app(
+      s.db.clone(),
+      s.jwt,
+      s.upd,
+      s.public_upd,
+      NotesLimits { max_per_user: 20 },
+      s.storage.clone(),
+    );
+
+    let resp = app
+      .clone()
+      .oneshot(request(
+        "GET",
+        &format!("/{note}/export?format=html"),
+        Some(&viewer_cookie),
+        None,
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+      resp.headers()[header::CONTENT_TYPE],
+      "text/html; charset=utf-8"
+    );
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+      .await
+      .unwrap();
+    let html = String::from_utf8(body.to_vec()).unwrap();
+    assert!(html.contains("<h1>&lt;Plans&gt;</h1>"));
+    assert!(html.contains("<h2>Title</h2><p>plain <strong>bold</strong>"));
+
+    let resp = app
+      .clone()
+      .oneshot(request(
+        "GET",
+        &format!("/{note}/export?format=md"),
+        Some(&stranger_cookie),
+        None,
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let resp = app
+      .oneshot(request(
+        "GET",
+        &format!("/{note}/export?format=pdf"),
+        Some(&s.cookie),
+        None,
+      ))
+      .await
+      .unwrap();
+    assert!(resp.status().is_client_error());
+  }
+
+  #[tokio::test]
+  async fn import_single_markdown_file() {
+    let s = setup().await;
+    let app = app(
+      s.db.clone(),
+      s.jwt,
+      s.upd,
+      s.public_upd,
+      NotesLimits { max_per_user: 20 },
+      s.storage.clone(),
+    );
+
+    let resp = app
+      .oneshot(request_bytes(
+        "POST",
+        "/import?filename=runbook.md",
+        Some(&s.cookie),
+        b"# Runbook\n\nRestart the **worker**.\n".to_vec(),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = body_json(resp).await;
+    assert_eq!(body[0]["path"], "runbook.md");
+    let id: Uuid = serde_json::from_value(body[0]["note_id"].clone()).unwrap();
+
+    let info = s.db.notes().info(id, s.user).await.unwrap().unwrap();
+    assert_eq!(info.title, "Runbook");
+    assert_eq!(info.preview, "Restart the worker.");
+    assert_eq!(
+      s.db
+        .note_search()
+        .search("worker", &[id], 10)
+        .await
+        .unwrap()
+        .len(),
+      1
+    );
+  }
+
+  #[tokio::test]
+  async fn import_zip_recreates_folders_and_reports_failures() {
+    let s = setup().await;
+    let mut zip = crate::user::export::zip::ZipWriter::new();
+    zip.add("docs/guides/setup.md", b"Install it").unwrap();
+    zip.add("docs/readme.md", b"# Readme").unwrap();
+    zip.add("docs/logo.png", b"png").unwrap();
+    zip.add("docs/extra.md", b"over the limit").unwrap();
+    let data = zip.finish().unwrap();
+    let app = app(
+      s.db.clone(),
+      s.jwt,
+      s.upd,
+      s.public_upd,
+      NotesLimits { max_per_user: 2 },
+      s.storage.clone(),
+    );
+
+    let resp = app
+      .oneshot(request_bytes(
+        "POST",
+        "/import?filename=docs.zip",
+        Some(&s.cookie),
+        data,
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = body_json(resp).await;
+    let results: Vec<(String, bool, Value)> = body
+      .as_array()
+      .unwrap()
+      .iter()
+      .map(|r| {
+        (
+          r["path"].as_str().unwrap().to_string(),
+          r["note_id"].is_string(),
+          r["error"].clone(),
+        )
+      })
+      .collect();
+    assert_eq!(
+      results,
+      [
+        ("docs/extra.md".into(), true, Value::Null),
+        ("docs/guides/setup.md".into(), true, Value::Null),
+        ("docs/logo.png".into(), false, json!("not a markdown file")),
+        ("docs/readme.md".into(), false, json!("note limit reached")),
+      ]
+    );
+
+    let folders = s.db.note_folder().list_for_user(s.user).await.unwrap();
+    let docs = folders.iter().find(|f| f.name == "docs").unwrap();
+    let guides = folders.iter().find(|f| f.name == "guides").unwrap();
+    assert_eq!(guides.parent, Some(docs.id));
+    let setup: Uuid = serde_json::from_value(body[1]["note_id"].clone()).unwrap();
+    let info = s.db.notes().info(setup, s.user).await.unwrap().unwrap();
+    assert_eq!(info.title, "setup");
+    assert_eq!(info.folder, Some(guides.id));
+  }
+
+  #[tokio::test]
+  async fn import_reports_and_rolls_back_files_that_fail_to_store() {
+    use sea_orm::ConnectionTrait;
+
+    let s = setup().await;
+    let mut zip = crate::user::export::zip::ZipWriter::new();
+    zip.add("docs/guides/setup.md", b"Install it").unwrap();
+    zip.add("readme.md", b"# Readme").unwrap();
+    let data = zip.finish().unwrap();
+    // filing notes into folders fails, notes at the top level still import
+    s.db
+      .execute_unprepared(r#"DROP TABLE "note_folder_note""#)
+      .await
+      .unwrap();
+    let app = app(
+      s.db.clone(),
+      s.jwt,
+      s.upd,
+      s.public_upd,
+      NotesLimits { max_per_user: 20 },
+      s.storage.clone(),
+    );
+
+    let resp = app
+      .oneshot(request_bytes(
+        "POST",
+        "/import?filename=docs.zip",
+        Some(&s.cookie),
+        data,
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = body_json(resp).await;
+    assert_eq!(body[0]["path"], "docs/guides/setup.md");
+    assert_eq!(body[0]["error"], "failed to import");
+    assert_eq!(body[1]["path"], "readme.md");
+    assert!(body[1]["note_id"].is_string());
+
+    assert_eq!(s.db.notes().count_owned(s.user).await.unwrap(), 1);
+    assert!(
+      s.db
+        .note_folder()
+        .list_for_user(s.user)
+        .await
+        .unwrap()
+        .is_empty()
+    );
+  }
 }
@@ -0,0 +1,364 @@
+use yrs::{
+  Any, AsyncTransact, Doc, GetString, Out, ReadTxn, Text, Xml, XmlElementRef, XmlFragment, XmlOut,
+  types::Attrs,
+};
+
+/// Renders the TipTap document of a note as CommonMark.
+pub async fn render_markdown(doc: &Doc) -> String {
+  let txn = doc.transact().await;
+  let Some(fragment) = txn.get_xml_fragment("default") else {
+    return String::new();
+  };
+
+  let nodes: Vec<XmlOut> = fragment.children(&txn).collect();
+  let mut out = blocks(&txn, &nodes);
+  if !out.is_empty() {
+    out.push('\n');
+  }
+  out
+}
+
+/// Renders every top-level block on its own. List items are split into
+/// separate blocks so comparing two versions can point at a single item.
+pub async fn render_markdown_blocks(doc: &Doc) -> Vec<String> {
+  let txn = doc.transact().await;
+  let Some(fragment) = txn.get_xml_fragment("default") else {
+    return Vec::new();
+  };
+
+  let mut out = Vec::new();
+  for node in fragment.children(&txn) {
+    let XmlOut::Element(element) = &node else {
+      out.extend(block(&txn, &node));
+      continue;
+    };
+    let items: Vec<XmlOut> = element.children(&txn).collect();
+    match element.tag().as_ref() {
+      "bulletList" | "taskList" => {
+        out.extend(
+          items
+            .iter()
+            .map(|item| list(&txn, std::slice::from_ref(item), |_| "- ".into())),
+        );
+      }
+      "orderedList" => {
+        let start = ordered_start(&txn, element);
+        out.extend(items.iter().enumerate().map(|(i, item)| {
+          list(&txn, std::slice::from_ref(item), |_| {
+            format!("{}. ", start + i)
+          })
+        }));
+      }
+      _ => out.extend(block(&txn, &node)),
+    }
+  }
+  out
+}
+
+fn blocks<T: ReadTxn>(txn: &T, nodes: &[XmlOut]) -> String {
+  nodes
+    .iter()
+    .filter_map(|node| block(txn, node))
+    .collect::<Vec<_>>()
+    .join("\n\n")
+}
+
+fn block<T: ReadTxn>(txn: &T, node: &XmlOut) -> Option<String> {
+  let element = match node {
+    XmlOut::Element(element) => element,
+    XmlOut::Text(_) => return Some(inline(txn, std::slice::from_ref(node))),
+    XmlOut::Fragment(fragment) => {
+      let nodes: Vec<XmlOut> = fragment.children(txn).collect();
+      return Some(blocks(txn, &nodes));
+    }
+  };
+  let children: Vec<XmlOut> = element.children(txn).collect();
+
+  let rendered = match element.tag().as_ref() {
+    "paragraph" => inline(txn, &children),
+    "heading" => {
+      let level = attribute(txn, element, "level")
+        .and_then(|level| level.parse::<f64>().ok())
+        .map(|level| level.clamp(1.0, 6.0) as usize)
+        .unwrap_or(1);
+      format!("{} {}", "#".repeat(level), inline(txn, &children))
+    }
+    "blockquote" => prefix_lines(&blocks(txn, &children), "> ", "> "),
+    "codeBlock" => {
+      let language = attribute(txn, element, "language").unwrap_or_default();
+      let code: String = children
+        .iter()
+        .map(|child| match child {
+          XmlOut::Text(text) => text.get_string(txn),
+          _ => String::new(),
+        })
+        .collect();
+      let fence = "`".repeat(longest_run(&code, '`').max(2) + 1);
+      format!("{fence}{language}\n{code}\n{fence}")
+    }
+    "horizontalRule" => "---".into(),
+    "image" => image(txn, element),
+    "bulletList" => list(txn, &children, |_| "- ".into()),
+    "orderedList" => {
+      let start = ordered_start(txn, element);
+      list(txn, &children, |i| format!("{}. ", start + i))
+    }
+    "taskList" => list(txn, &children, |_| "- ".into()),
+    _ => blocks(txn, &children),
+  };
+
+  (!rendered.is_empty() || element.tag().as_ref() == "paragraph").then_some(rendered)
+}
+
+fn ordered_start<T: ReadTxn>(txn: &T, element: &XmlElementRef) -> usize {
+  attribute(txn, element, "start")
+    .and_then(|start| start.parse::<f64>().ok())
+    .map(|start| start as usize)
+    .unwrap_or(1)
+}
+
+fn list<T: ReadTxn>(txn: &T, items: &[XmlOut], marker: impl Fn(usize) -> String) -> String {
+  items
+    .iter()
+    .enumerate()
+    .map(|(i, item)| {
+      let mut marker = marker(i);
+      let body = match item {
+        XmlOut::Element(element) => {
+          if element.tag().as_ref() == "taskItem" {
+            let checked = attribute(txn, element, "checked").is_some_and(|c| c == "true");
+            marker.push_str(if checked { "[x] " } else { "[ ] " });
+          }
+          let children: Vec<XmlOut> = element.children(txn).collect();
+          children
+            .iter()
+            .filter_map(|child| block(txn, child))
+            .collect::<Vec<_>>()
+            .join("\n")
+        }
+        _ => block(txn, item).unwrap_or_default(),
+      };
+      let indent = " ".repeat(marker.len());
+      prefix_lines(&body, &marker, &indent)
+    })
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+fn inline<T: ReadTxn>(txn: &T, nodes: &[XmlOut]) -> String {
+  let mut out = String::new();
+  for node in nodes {
+    match node {
+      XmlOut::Text(text) => {
+        for chunk in text.diff(txn, |_| ()) {
+          if let Out::Any(Any::String(value)) = chunk.insert {
+            out.push_str(&format_marks(&value, chunk.attributes.as_deref()));
+          }
+        }
+      }
+      XmlOut::Element(element) => match element.tag().as_ref() {
+        "hardBreak" => out.push_str("\\\n"),
+        "image" => out.push_str(&image(txn, element)),
+        _ => {
+          let children: Vec<XmlOut> = element.children(txn).collect();
+          out.push_str(&inline(txn, &children));
+        }
+      },
+      XmlOut::Fragment(_) => {}
+    }
+  }
+  out
+}
+
+fn format_marks(text: &str, marks: Option<&Attrs>) -> String {
+  let Some(marks) = marks else {
+    return escape(text);
+  };
+
+  // emphasis markers must hug the text, surrounding whitespace stays outside
+  let trimmed = text.trim();
+  if trimmed.is_empty() {
+    return text.to_string();
+  }
+  let leading = &text[..text.len() - text.trim_start().len()];
+  let trailing = &text[text.trim_end().len()..];
+
+  let mut inner = if marks.contains_key("code") {
+    let fence = "`".repeat(longest_run(trimmed, '`') + 1);
+    format!("{fence}{trimmed}{fence}")
+  } else {
+    escape(trimmed)
+  };
+  for (mark, wrap) in [("italic", "*"), ("bold", "**"), ("strike", "~~")] {
+    if marks.contains_key(mark) {
+      inner = format!("{wrap}{inner}{wrap}");
+    }
+  }
+  if let Some(Any::Map(link)) = marks.get("link")
+    && let Some(Any::String(href)) = link.get("href")
+  {
+    inner = format!("[{inner}]({href})");
+  }
+
+  format!("{leading}{inner}{trailing}")
+}
+
+fn image<T: ReadTxn>(txn: &T, element: &XmlElementRef) -> String {
+  let src = attribute(txn, element, "src").unwrap_or_default();
+  let alt = attribute(txn, element, "alt").unwrap_or_default();
+  format!("![{}]({src})", escape(&alt))
+}
+
+fn attribute<T: ReadTxn>(txn: &T, element: &XmlElementRef, name: &str) -> Option<String> {
+  match element.get_attribute(txn, name)? {
+    Out::Any(Any::Null | Any::Undefined) => None,
+    Out::Any(Any::String(value)) => Some(value.to_string()),
+    Out::Any(value) => Some(value.to_string()),
+    other => Some(other.to_string(txn)),
+  }
+}
+
+fn prefix_lines(text: &str, first: &str, rest: &str) -> String {
+  text
+    .split('\n')
+    .enumerate()
+    .map(|(i, line)| {
+      let prefix = if i == 0 { first } else { rest };
+      if line.is_empty() {
+        prefix.trim_end().to_string()
+      } else {
+        format!("{prefix}{line}")
+      }
+    })
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+fn escape(text: &str) -> String {
+  let mut out = String::with_capacity(text.len());
+  for c in text.chars() {
+    if matches!(
+      c,
+      '\\' | '*' | '_' | '`' | '[' | ']' | '<' | '>' | '#' | '~'
+    ) {
+      out.push('\\');
+    }
+    out.push(c);
+  }
+  out
+}
+
+fn longest_run(text: &str, needle: char) -> usize {
+  let (mut longest, mut current) = (0, 0);
+  for c in text.chars() {
+    current = if c == needle { current + 1 } else { 0 };
+    longest = longest.max(current);
+  }
+  longest
+}
+
+#[cfg(test)]
+pub mod test {
+  use std::collections::HashMap;
+
+  use super::{render_markdown, render_markdown_blocks};
+  use yrs::{
+    Any, Doc, Text, Transact, Xml, XmlElementPrelim, XmlFragment, XmlTextPrelim, types::Attrs,
+  };
+
+  /// Builds a doc shaped like the TipTap/y-prosemirror output.
+  pub fn sample_doc() -> Doc {
+    let doc = Doc::new();
+    let fragment = doc.get_or_insert_xml_fragment("default");
+    let mut txn = doc.transact_mut();
+
+    let heading = fragment.push_back(&mut txn, XmlElementPrelim::empty("heading"));
+    heading.insert_attribute(&mut txn, "level", "2");
+    heading.push_back(&mut txn, XmlTextPrelim::new("Title"));
+
+    let paragraph = fragment.push_back(&mut txn, XmlElementPrelim::empty("paragraph"));
+    let text = paragraph.push_back(&mut txn, XmlTextPrelim::new(""));
+    text.insert(&mut txn, 0, "plain ");
+    let bold = Attrs::from([("bold".into(), Any::Map(HashMap::new().into()))]);
+    text.insert_with_attributes(&mut txn, 6, "bold", bold);
+    let link = Attrs::from([(
+      "link".into(),
+      Any::Map(HashMap::from([("href".to_string(), Any::from("https://x.dev"))]).into()),
+    )]);
+    text.insert_with_attributes(&mut txn, 10, " link", link);
+
+    let list = fragment.push_back(&mut txn, XmlElementPrelim::empty("bulletList"));
+    for entry in ["one", "two"] {
+      let item = list.push_back(&mut txn, XmlElementPrelim::empty("listItem"));
+      let paragraph = item.push_back(&mut txn, XmlElementPrelim::empty("paragraph"));
+      paragraph.push_back(&mut txn, XmlTextPrelim::new(entry));
+    }
+
+    let code = fragment.push_back(&mut txn, XmlElementPrelim::empty("codeBlock"));
+    code.insert_attribute(&mut txn, "language", "rust");
+    code.push_back(&mut txn, XmlTextPrelim::new("let a = *b;"));
+
+    drop(txn);
+    doc
+  }
+
+  #[tokio::test]
+  async fn empty_doc_renders_nothing() {
+    assert_eq!(render_markdown(&Doc::new()).await, "");
+  }
+
+  #[tokio::test]
+  async fn renders_blocks_and_marks() {
+    assert_eq!(
+      render_markdown(&sample_doc()).await,
+      "## Title\n\nplain **bold** [link](https://x.dev)\n\n- one\n- two\n\n```rust\nlet a = *b;\n```\n"
+    );
+  }
+
+  #[tokio::test]
+  async fn blocks_split_list_items() {
+    assert_eq!(
+      render_markdown_blocks(&sample_doc()).await,
+      [
+        "## Title",
+        "plain **bold** [link](https://x.dev)",
+        "- one",
+        "- two",
+        "```rust\nlet a = *b;\n```",
+      ]
+    );
+  }
+
+  #[tokio::test]
+  async fn escapes_markdown_in_plain_text() {
+    let doc = Doc::new();
+    let fragment = doc.get_or_insert_xml_fragment("default");
+    {
+      let mut txn = doc.transact_mut();
+      let paragraph = fragment.push_back(&mut txn, XmlElementPrelim::empty("paragraph"));
+      paragraph.push_back(&mut txn, XmlTextPrelim::new("a *b* [c]"));
+    }
+
+    assert_eq!(render_markdown(&doc).await, "a \\*b\\* \\[c\\]\n");
+  }
+
+  #[tokio::test]
+  async fn nested_lists_and_quotes_are_indented() {
+    let doc = Doc::new();
+    let fragment = doc.get_or_insert_xml_fragment("default");
+    {
+      let mut txn = doc.transact_mut();
+      let quote = fragment.push_back(&mut txn, XmlElementPrelim::empty("blockquote"));
+      let list = quote.push_back(&mut txn, XmlElementPrelim::empty("orderedList"));
+      let item = list.push_back(&mut txn, XmlElementPrelim::empty("listItem"));
+      let paragraph = item.push_back(&mut txn, XmlElementPrelim::empty("paragraph"));
+      paragraph.push_back(&mut txn, XmlTextPrelim::new("outer"));
+      let inner = item.push_back(&mut txn, XmlElementPrelim::empty("bulletList"));
+      let inner_item = inner.push_back(&mut txn, XmlElementPrelim::empty("listItem"));
+      let paragraph = inner_item.push_back(&mut txn, XmlElementPrelim::empty("paragraph"));
+      paragraph.push_back(&mut txn, XmlTextPrelim::new("inner"));
+    }
+
+    assert_eq!(render_markdown(&doc).await, "> 1. outer\n>    - inner\n");
+  }
+}
@@ -0,0 +1,325 @@
+use std::convert::Infallible;
+
+use aide::OperationIo;
+use axum::extract::FromRequestParts;
+use centaurus::{backend::config::SiteConfig, db::init::Connection, error::Result, mail::Mailer};
+use http::request::Parts;
+use tracing::warn;
+use url::Url;
+use uuid::Uuid;
+use yrs::{AsyncTransact, ReadTxn};
+
+use crate::{
+  db::DBTrait,
+  notes::{export::load_doc, links::referenced_ids, state::NoteEditing},
+  utils::{UpdateMessage, Updater},
+};
+
+/// Users referenced by TipTap mention nodes (`<mention id="{user id}">`).
+pub fn mentioned_users<T: ReadTxn>(txn: &T) -> Vec<Uuid> {
+  referenced_ids(txn, "mention")
+}
+
+/// What is needed to mail mentioned users, mails are only sent while the
+/// mail service is configured.
+#[derive(Clone, Default, OperationIo)]
+pub struct MentionMail {
+  mailer: Option<Mailer>,
+  site: Option<SiteConfig>,
+}
+
+impl<S: Sync> FromRequestParts<S> for MentionMail {
+  type Rejection = Infallible;
+
+  async fn from_request_parts(
+    parts: &mut Parts,
+    _state: &S,
+  ) -> std::result::Result<Self, Self::Rejection> {
+    Ok(Self {
+      mailer: parts.extensions.get::<Mailer>().cloned(),
+      site: parts.extensions.get::<SiteConfig>().cloned(),
+    })
+  }
+}
+
+impl MentionMail {
+  /// Sends the mail in the background, saving the note does not wait for the
+  /// mail server.
+  fn send(&self, db: &Connection, user_id: Uuid, note_id: Uuid) {
+    let (Some(mailer), Some(site)) = (self.mailer.clone(), self.site.clone()) else {
+      return;
+    };
+    let db = db.clone();
+
+    tokio::spawn(async move {
+      if !mailer.is_active().await {
+        return;
+      }
+      if let Err(err) = send_mail(&db, &mailer, &site.site_url, user_id, note_id).await {
+        warn!(?err, %user_id, %note_id, "failed to send mention mail");
+      }
+    });
+  }
+}
+
+/// Notifies users that were mentioned since the last save. Only users that
+/// can open the note, directly, through a group or a public link, are
+/// notified, the others are left to [`notify_pending_mentions`].
+pub async fn notify_new_mentions(
+  db: &Connection,
+  updater: &Updater,
+  mail: &MentionMail,
+  note_id: Uuid,
+  mentioned: &[Uuid],
+) -> Result<()> {
+  let mentions = db.note_mention();
+  mentions.retain(note_id, mentioned).await?;
+  let known = mentions.list(note_id).await?;
+
+  let new: Vec<Uuid> = mentioned
+    .iter()
+    .filter(|user_id| !known.contains(user_id))
+    .copied()
+    .collect();
+  let mut notified = Vec::new();
+  for user_id in mentions.existing_users(&new).await? {
+    if db.notes().has_access(user_id, note_id).await? {
+      notified.push(user_id);
+    }
+  }
+  mentions.add(note_id, &notified).await?;
+
+  for user_id in notified {
+    updater
+      .send_to(user_id, UpdateMessage::NoteMention { note_id })
+      .await;
+    mail.send(db, user_id, note_id);
+  }
+
+  Ok(())
+}
+
+/// Notifies mentioned users that gained access to the note since they were
+/// mentioned, runs after sharing as the content itself did not change.
+pub async fn notify_pending_mentions(
+  db: &Connection,
+  updater: &Updater,
+  mail: &MentionMail,
+  editing: &NoteEditing,
+  note_id: Uuid,
+) -> Result<()> {
+  let content = match editing.live_content(note_id).await {
+    Some((content, _)) => content,
+    None => db.notes().get_content(note_id).await?,
+  };
+  let Some(doc) = load_doc(&content).await else {
+    return Ok(());
+  };
+  let mentioned = mentioned_users(&doc.transact().await);
+
+  notify_new_mentions(db, updater, mail, note_id, &mentioned).await
+}
+
+async fn send_mail(
+  db: &Connection,
+  mailer: &Mailer,
+  site_url: &Url,
+  user_id: Uuid,
+  note_id: Uuid,
+) -> Result<()> {
+  let user = db.user_ext().get_user_by_id(user_id).await?;
+  let title = db
+    .notes()
+    .info(note_id, user_id)
+    .await?
+    .map(|info| info.title)
+    .unwrap_or_default();
+  let link = note_link(site_url, note_id);
+
+  mailer
+    .send_mail(
+      user.name,
+      user.email,
+      "Positron Mention".to_string(),
+      mail_template(&escape_html(&title), link.as_str(), site_url.as_str()),
+    )
+    .await
+}
+
+fn note_link(site_url: &Url, note_id: Uuid) -> Url {
+  let mut link = site_url.clone();
+  if let Ok(mut segments) = link.path_segments_mut() {
+    segments.pop_if_empty();
+    segments.extend(["notes", &note_id.to_string()]);
+  }
+  link
+}
+
+/// Note titles are chosen by their owner.
+fn escape_html(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+fn mail_template(title: &str, note_link: &str, link: &str) -> String {
+  format!(
+    r#"
+  <!DOCTYPE html>
+  <html lang="en">
+    <head>
+      <meta charset="UTF-8">
+      <meta name="viewport" content="width=device-width, initial-scale=1.0">
+      <title>Mention</title>
+    </head>
+    <body>
+      <div style="display: flex; flex-direction: column;">
+        <header style="padding: 1rem; display: flex; flex-direction: column; align-items: center; justify-content: center;">
+          <h2 style="margin: 0;">Mention</h2>
+          <p style="margin: 0;">You were mentioned in the note "{title}"</p>
+        </header>
+        <div style="display: flex; align-items: center; justify-content: center;">
+          <a href="{note_link}">Open Note</a>
+        </div>
+        <footer style="display: flex; align-items: center; justify-content: center;">
+          <p>Mail send from <a href="{link}">{link}</a></p>
+        </footer>
+      </div>
+    </body>
+  </html>
+  "#
+  )
+}
+
+#[cfg(test)]
+mod test {
+  use std::time::Duration;
+
+  use centaurus::backend::endpoints::websocket::state::UpdateState;
+  use entity::sea_orm_active_enums::NoteShareAccess;
+  use tokio::{sync::mpsc::Receiver, time::timeout};
+  use url::Url;
+  use uuid::Uuid;
+  use yrs::{Doc, Transact, Xml, XmlElementPrelim, XmlFragment, XmlTextPrelim};
+
+  use super::{MentionMail, mentioned_users, note_link, notify_new_mentions};
+  use crate::{
+    db::{
+      DBTrait,
+      notes::NoteShareEntry,
+      test::{insert_user, test_db},
+    },
+    utils::UpdateMessage,
+  };
+
+  #[test]
+  fn mentioned_users_finds_nested_mentions() {
+    let alice = Uuid::new_v4();
+    let bob = Uuid::new_v4();
+    let doc = Doc::new();
+    let fragment = doc.get_or_insert_xml_fragment("default");
+    {
+      let mut txn = doc.transact_mut();
+      let paragraph = fragment.push_back(&mut txn, XmlElementPrelim::empty("paragraph"));
+      paragraph.push_back(&mut txn, XmlTextPrelim::new("hi "));
+      for (tag, id) in [
+        ("mention", bob.to_string()),
+        ("mention", alice.to_string()),
+        ("mention", bob.to_string()),
+        ("mention", "not a user".to_string()),
+        ("image", Uuid::new_v4().to_string()),
+      ] {
+        let node = paragraph.push_back(&mut txn, XmlElementPrelim::empty(tag));
+        node.insert_attribute(&mut txn, "id", id);
+      }
+      let quote = fragment.push_back(&mut txn, XmlElementPrelim::empty("blockquote"));
+      let nested = quote.push_back(&mut txn, XmlElementPrelim::empty("paragraph"));
+      let mention = nested.push_back(&mut txn, XmlElementPrelim::empty("mention"));
+      mention.insert_attribute(&mut txn, "id", alice.to_string());
+    }
+
+    let mut expected = vec![alice, bob];
+    expected.sort_unstable();
+    assert_eq!(mentioned_users(&doc.transact()), expected);
+  }
+
+  #[test]
+  fn note_link_points_at_the_note_page() {
+    let note = Uuid::nil();
+    let site = Url::parse("https://positron.example/").unwrap();
+    assert_eq!(
+      note_link(&site, note).as_str(),
+      format!("https://positron.example/notes/{note}")
+    );
+  }
+
+  async fn received(rx: &mut Receiver<UpdateMessage>, note: Uuid) -> usize {
+    let mut count = 0;
+    while let Ok(Some(msg)) = timeout(Duration::from_millis(100), rx.recv()).await {
+      if matches!(msg, UpdateMessage::NoteMention { note_id } if note_id == note) {
+        count += 1;
+      }
+    }
+    count
+  }
+
+  #[tokio::test]
+  async fn only_new_mentions_of_users_with_access_notify() {
+    let db = test_db().await;
+    let owner = insert_user(&db, "owner", "owner@x.com").await;
+    let viewer = insert_user(&db, "viewer", "viewer@x.com").await;
+    let stranger = insert_user(&db, "stranger", "stranger@x.com").await;
+    let note = db.notes().create(owner, "T".into()).await.unwrap();
+    db.notes()
+      .set_shared_users(
+        note,
+        owner,
+        vec![NoteShareEntry {
+          user_id: viewer,
+          access: NoteShareAccess::View,
+        }],
+      )
+      .await
+      .unwrap();
+
+    let (state, updater) = UpdateState::<UpdateMessage>::init().await;
+    let (_, mut viewer_rx) = state.create_session(viewer).await;
+    let (_, mut stranger_rx) = state.create_session(stranger).await;
+    let mail = MentionMail::default();
+    let mentioned = [viewer, stranger, Uuid::new_v4()];
+    notify_new_mentions(&db, &updater, &mail, note, &mentioned)
+      .await
+      .unwrap();
+    assert_eq!(received(&mut viewer_rx, note).await, 1);
+    assert_eq!(received(&mut stranger_rx, note).await, 0);
+    assert_eq!(db.note_mention().list(note).await.unwrap(), [viewer]);
+
+    // a mention that stays in the note notifies once
+    notify_new_mentions(&db, &updater, &mail, note, &mentioned)
+      .await
+      .unwrap();
+    assert_eq!(received(&mut viewer_rx, note).await, 0);
+
+    // once the note is public the stranger can open it
+    db.notes()
+      .set_public_access(note, Some(NoteShareAccess::View))
+      .await
+      .unwrap();
+    notify_new_mentions(&db, &updater, &mail, note, &mentioned)
+      .await
+      .unwrap();
+    assert_eq!(received(&mut stranger_rx, note).await, 1);
+
+    // removing a mention forgets it, mentioning again notifies again
+    notify_new_mentions(&db, &updater, &mail, note, &[stranger])
+      .await
+      .unwrap();
+    assert_eq!(db.note_mention().list(note).await.unwrap(), [stranger]);
+    notify_new_mentions(&db, &updater, &mail, note, &mentioned)
+      .await
+      .unwrap();
+    assert_eq!(received(&mut viewer_rx, note).await, 1);
+  }
+}
@@ -4,16 +4,35 @@ use centaurus::{db::init::Connection, storage::FileStorage};
 
 use crate::{
   config::Config,
-  notes::{snapshot::SnapshotCleanup, state::NoteEditing},
+  notes::{
+    policy::{OverrideLimits, SnapshotPolicy},
+    snapshot::{SnapshotCleanup, SnapshotLimits},
+    state::NoteEditing,
+    trash::{TrashPurge, TrashSettings},
+  },
   utils::Updater,
 };
 
+pub use attachments::AttachmentLimits;
+pub use export::load_doc;
 pub use snapshot::{delete_storage_for_note, delete_storage_for_user};
 
+mod attachments;
+mod comments;
+mod diff;
+mod export;
+mod html;
+mod import;
+mod links;
 mod management;
+pub mod markdown;
+mod mentions;
+mod policy;
 mod preview;
+mod search;
 mod snapshot;
 mod state;
+mod trash;
 pub mod update;
 mod websocket;
 
@@ -35,7 +54,11 @@ impl NotesLimits {
 pub fn router() -> ApiRouter {
   ApiRouter::new()
     .nest("/management", management::router())
+    .nest("/attachments", attachments::router())
+    .nest("/comments", comments::router())
     .nest("/snapshots", snapshot::router())
+    .nest("/snapshot-policy", policy::router())
+    .nest("/trash", trash::router())
     .nest("/websocket", websocket::router())
     .nest("/update", update::router())
 }
@@ -48,14 +71,34 @@ pub fn state(
   config: &Config,
 ) -> ApiRouter {
   let (public_note_state, public_note_updater) = update::PublicNoteUpdateState::init();
+  let trash = TrashSettings::from_config(config);
+  let snapshot_policy = SnapshotPolicy::from_config(config);
+  search::spawn_reindex(db.clone());
 
   router
     .layer(Extension(public_note_state))
     .layer(Extension(public_note_updater))
     .layer(Extension(NotesLimits::from_config(config)))
+    .layer(Extension(AttachmentLimits::from_config(config)))
     .layer(Extension(NoteEditing::init(
       storage.clone(),
       updater.clone(),
+      snapshot_policy.clone(),
     )))
-    .layer(Extension(SnapshotCleanup::init(db, storage, updater)))
+    .layer(Extension(TrashPurge::init(
+      db.clone(),
+      storage.clone(),
+      updater.clone(),
+      trash.clone(),
+    )))
+    .layer(Extension(trash))
+    .layer(Extension(SnapshotCleanup::init(
+      db,
+      storage,
+      updater,
+      snapshot_policy.clone(),
+    )))
+    .layer(Extension(snapshot_policy))
+    .layer(Extension(OverrideLimits::from_config(config)))
+    .layer(Extension(SnapshotLimits::from_config(config)))
 }
@@ -0,0 +1,531 @@
+use aide::axum::{
+  ApiRouter,
+  routing::{get_with, put_with},
+};
+use axum::{Extension, Json, extract::Path};
+use centaurus::{backend::auth::jwt_auth::JwtAuth, bail, db::init::Connection, error::Result};
+use chrono::TimeDelta;
+use entity::note_snapshot_policy;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+  config::Config,
+  db::{DBTrait, notes::snapshot::RetentionTier},
+  notes::state::NoteEditing,
+};
+
+pub fn router() -> ApiRouter {
+  ApiRouter::new()
+    .api_route(
+      "/{note_uuid}",
+      get_with(get_policy, |op| op.id("getNoteSnapshotPolicy")),
+    )
+    .api_route(
+      "/",
+      put_with(set_policy, |op| op.id("setNoteSnapshotPolicy")),
+    )
+}
+
+/// When automatic snapshots are taken and how they are thinned out with age.
+#[derive(Serialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct SnapshotPolicy {
+  /// Minutes that have to pass between two automatic snapshots.
+  pub min_interval_mins: i64,
+  /// After this many minutes a snapshot is taken regardless of the size change.
+  pub max_interval_mins: i64,
+  /// Bytes the content has to grow or shrink by for an earlier snapshot.
+  pub min_size_change: i64,
+  /// Sorted by age, snapshots younger than the first tier are all kept.
+  pub retention: Vec<RetentionTier>,
+}
+
+impl Default for SnapshotPolicy {
+  fn default() -> Self {
+    Self {
+      min_interval_mins: 10,
+      max_interval_mins: 60,
+      min_size_change: 100,
+      retention: RetentionTier::defaults(),
+    }
+  }
+}
+
+impl SnapshotPolicy {
+  pub fn from_config(config: &Config) -> Self {
+    let policy = Self {
+      min_interval_mins: config.notes_snapshot_min_interval_mins,
+      max_interval_mins: config.notes_snapshot_max_interval_mins,
+      min_size_change: config.notes_snapshot_min_size_change,
+      retention: parse_retention(&config.notes_snapshot_retention)
+        .expect("Invalid NOTES_SNAPSHOT_RETENTION"),
+    };
+    policy.validate().expect("Invalid snapshot policy");
+    policy
+  }
+
+  /// Applies the fields a note owner has set on top of this policy.
+  pub fn with_override(&self, policy: &note_snapshot_policy::Model) -> Self {
+    Self {
+      min_interval_mins: policy.min_interval_mins.unwrap_or(self.min_interval_mins),
+      max_interval_mins: policy.max_interval_mins.unwrap_or(self.max_interval_mins),
+      min_size_change: policy.min_size_change.unwrap_or(self.min_size_change),
+      retention: policy
+        .retention
+        .as_deref()
+        .and_then(|spec| parse_retention(spec).ok())
+        .unwrap_or_else(|| self.retention.clone()),
+    }
+  }
+
+  /// Effective policy of a note.
+  pub async fn for_note(&self, db: &Connection, note_id: Uuid) -> Result<Self> {
+    Ok(match db.note_snapshot_policy().get(note_id).await? {
+      Some(policy) => self.with_override(&policy),
+      None => self.clone(),
+    })
+  }
+
+  /// Whether a save should also store a snapshot.
+  pub fn is_due(&self, elapsed: TimeDelta, size_change: usize) -> bool {
+    // an interval beyond the representable range is never reached
+    let reached = |mins| TimeDelta::try_minutes(mins).is_some_and(|interval| elapsed >= interval);
+    reached(self.min_interval_mins)
+      && (reached(self.max_interval_mins) || size_change as i64 > self.min_size_change)
+  }
+
+  fn validate(&self) -> std::result::Result<(), &'static str> {
+    if self.min_interval_mins < 0 || self.min_size_change < 0 {
+      return Err("values must not be negative");
+    }
+    if self.max_interval_mins < self.min_interval_mins {
+      return Err("maximum interval is below the minimum interval");
+    }
+    validate_retention(&self.retention)
+  }
+}
+
+/// Bounds for the values a note owner may override, the instance policy
+/// itself is not restricted by them.
+#[derive(Clone, Debug)]
+pub struct OverrideLimits {
+  /// Smallest minimum interval, keeps a note from being snapshotted on every
+  /// save.
+  pub min_interval_mins: i64,
+  /// Largest interval or retention age in minutes.
+  pub max_mins: i64,
+}
+
+impl OverrideLimits {
+  pub fn from_config(config: &Config) -> Self {
+    Self {
+      min_interval_mins: config.notes_snapshot_override_min_interval_mins,
+      max_mins: config
+        .notes_snapshot_override_max_days
+        .saturating_mul(24 * 60),
+    }
+  }
+
+  fn check(&self, overrides: &PolicyOverride) -> std::result::Result<(), &'static str> {
+    if overrides
+      .min_interval_mins
+      .is_some_and(|mins| mins < self.min_interval_mins)
+    {
+      return Err("minimum interval is below the instance limit");
+    }
+    if [overrides.min_interval_mins, overrides.max_interval_mins]
+      .into_iter()
+      .flatten()
+      .any(|mins| mins > self.max_mins)
+    {
+      return Err("interval exceeds the instance limit");
+    }
+    if let Some(retention) = &overrides.retention {
+      // an empty table would keep every snapshot forever
+      if retention.is_empty() {
+        return Err("retention table must not be empty");
+      }
+      if retention
+        .iter()
+        .any(|tier| tier.after_mins > self.max_mins || tier.keep_every_mins > self.max_mins)
+      {
+        return Err("retention exceeds the instance limit");
+      }
+    }
+    Ok(())
+  }
+}
+
+fn validate_retention(tiers: &[RetentionTier]) -> std::result::Result<(), &'static str> {
+  if tiers
+    .iter()
+    .any(|tier| tier.after_mins < 0 || tier.keep_every_mins <= 0)
+  {
+    return Err("retention ages must not be negative and intervals positive");
+  }
+  if tiers
+    .windows(2)
+    .any(|pair| pair[0].after_mins >= pair[1].after_mins)
+  {
+    return Err("retention tiers must be sorted by age");
+  }
+  Ok(())
+}
+
+/// Parses `age:interval` pairs such as `2h:30m,1d:6h`, an empty table keeps
+/// every snapshot.
+pub fn parse_retention(spec: &str) -> std::result::Result<Vec<RetentionTier>, &'static str> {
+  let tiers = spec
+    .split(',')
+    .map(str::trim)
+    .filter(|pair| !pair.is_empty())
+    .map(|pair| -> std::result::Result<_, &'static str> {
+      let (age, interval) = pair.split_once(':').ok_or("expected age:interval")?;
+      Ok(RetentionTier {
+        after_mins: parse_mins(age)?,
+        keep_every_mins: parse_mins(interval)?,
+      })
+    })
+    .collect::<std::result::Result<Vec<_>, _>>()?;
+  validate_retention(&tiers)?;
+  Ok(tiers)
+}
+
+fn parse_mins(value: &str) -> std::result::Result<i64, &'static str> {
+  let value = value.trim();
+  let unit = match value.chars().last() {
+    Some('m') => 1,
+    Some('h') => 60,
+    Some('d') => 24 * 60,
+    _ => return Err("durations need an m, h or d unit"),
+  };
+  value[..value.len() - 1]
+    .parse::<i64>()
+    .ok()
+    .and_then(|amount| amount.checked_mul(unit))
+    .ok_or("invalid duration")
+}
+
+pub fn format_retention(tiers: &[RetentionTier]) -> String {
+  let format = |mins: i64| match mins {
+    0 => "0m".to_string(),
+    mins if mins % (24 * 60) == 0 => format!("{}d", mins / (24 * 60)),
+    mins if mins % 60 == 0 => format!("{}h", mins / 60),
+    mins => format!("{mins}m"),
+  };
+  tiers
+    .iter()
+    .map(|tier| {
+      format!(
+        "{}:{}",
+        format(tier.after_mins),
+        format(tier.keep_every_mins)
+      )
+    })
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+/// Fields left out use the instance value.
+#[derive(Serialize, Deserialize, JsonSchema, Default)]
+struct PolicyOverride {
+  min_interval_mins: Option<i64>,
+  max_interval_mins: Option<i64>,
+  min_size_change: Option<i64>,
+  retention: Option<Vec<RetentionTier>>,
+}
+
+impl PolicyOverride {
+  fn is_empty(&self) -> bool {
+    self.min_interval_mins.is_none()
+      && self.max_interval_mins.is_none()
+      && self.min_size_change.is_none()
+      && self.retention.is_none()
+  }
+
+  fn into_model(self, note_id: Uuid) -> note_snapshot_policy::Model {
+    note_snapshot_policy::Model {
+      note_id,
+      min_interval_mins: self.min_interval_mins,
+      max_interval_mins: self.max_interval_mins,
+      min_size_change: self.min_size_change,
+      retention: self.retention.as_deref().map(format_retention),
+    }
+  }
+}
+
+impl From<note_snapshot_policy::Model> for PolicyOverride {
+  fn from(policy: note_snapshot_policy::Model) -> Self {
+    Self {
+      min_interval_mins: policy.min_interval_mins,
+      max_interval_mins: policy.max_interval_mins,
+      min_size_change: policy.min_size_change,
+      retention: policy
+        .retention
+        .and_then(|spec| parse_retention(&spec).ok()),
+    }
+  }
+}
+
+#[derive(Serialize, JsonSchema)]
+struct NoteSnapshotPolicy {
+  overrides: PolicyOverride,
+  effective: SnapshotPolicy,
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct NotePath {
+  note_uuid: Uuid,
+}
+
+async fn require_owner(auth: &JwtAuth, db: &Connection, note_id: Uuid) -> Result<()> {
+  if !db.notes().is_owner(auth.user_id, note_id).await? {
+    bail!(FORBIDDEN, "forbidden");
+  }
+  Ok(())
+}
+
+async fn get_policy(
+  auth: JwtAuth,
+  db: Connection,
+  Extension(policy): Extension<SnapshotPolicy>,
+  Path(path): Path<NotePath>,
+) -> Result<Json<NoteSnapshotPolicy>> {
+  require_owner(&auth, &db, path.note_uuid).await?;
+
+  let overrides = db.note_snapshot_policy().get(path.note_uuid).await?;
+  Ok(Json(NoteSnapshotPolicy {
+    effective: match &overrides {
+      Some(overrides) => policy.with_override(overrides),
+      None => policy,
+    },
+    overrides: overrides.map(PolicyOverride::from).unwrap_or_default(),
+  }))
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct SetPolicyReq {
+  note_id: Uuid,
+  #[serde(flatten)]
+  overrides: PolicyOverride,
+}
+
+/// Replaces the overrides of a note, an empty request restores the instance
+/// policy.
+async fn set_policy(
+  auth: JwtAuth,
+  db: Connection,
+  editing: NoteEditing,
+  Extension(policy): Extension<SnapshotPolicy>,
+  Extension(limits): Extension<OverrideLimits>,
+  Json(req): Json<SetPolicyReq>,
+) -> Result<Json<SnapshotPolicy>> {
+  require_owner(&auth, &db, req.note_id).await?;
+
+  let effective = if req.overrides.is_empty() {
+    db.note_snapshot_policy().delete(req.note_id).await?;
+    policy
+  } else {
+    // checked before storing, a table that does not parse back would silently
+    // fall back to the instance one
+    if let Some(Err(err)) = req.overrides.retention.as_deref().map(validate_retention) {
+      bail!(BAD_REQUEST, "{err}");
+    }
+    if let Err(err) = limits.check(&req.overrides) {
+      bail!(BAD_REQUEST, "{err}");
+    }
+    let overrides = req.overrides.into_model(req.note_id);
+    let effective = policy.with_override(&overrides);
+    if let Err(err) = effective.validate() {
+      bail!(BAD_REQUEST, "{err}");
+    }
+    db.note_snapshot_policy().set(overrides).await?;
+    effective
+  };
+
+  editing.set_policy(req.note_id, effective.clone()).await;
+
+  Ok(Json(effective))
+}
+
+#[cfg(test)]
+mod test {
+  use axum::{
+    Extension, Router,
+    body::Body,
+    http::{Request, StatusCode, header},
+    routing::{get, put},
+  };
+  use chrono::TimeDelta;
+  use serde_json::{Value, json};
+  use tower::ServiceExt;
+
+  use super::{OverrideLimits, SnapshotPolicy, format_retention, parse_retention};
+  use crate::{
+    config::Config,
+    db::{
+      DBTrait,
+      notes::snapshot::RetentionTier,
+      test::{auth_cookie, auth_state, body_json, insert_user, test_db},
+    },
+    notes::state::NoteEditing,
+  };
+
+  #[test]
+  fn retention_spec_round_trips_and_rejects_unsorted_tiers() {
+    let default = "2h:30m,4h:1h,1d:6h,3d:1d,7d:7d,30d:30d,365d:365d";
+    let tiers = parse_retention(default).unwrap();
+    assert_eq!(tiers, RetentionTier::defaults());
+    assert_eq!(format_retention(&tiers), default);
+    assert_eq!(
+      format_retention(&parse_retention("90m:45m").unwrap()),
+      "90m:45m"
+    );
+
+    assert!(parse_retention("").unwrap().is_empty());
+    assert!(parse_retention("1d:1h,2h:1h").is_err());
+    assert!(parse_retention("1d:0m").is_err());
+    assert!(parse_retention("1w:1d").is_err());
+    assert!(parse_retention("1d").is_err());
+  }
+
+  #[test]
+  fn snapshots_are_due_by_time_or_size_change() {
+    let policy = SnapshotPolicy::default();
+    assert!(!policy.is_due(TimeDelta::minutes(9), 10_000));
+    assert!(!policy.is_due(TimeDelta::minutes(30), 100));
+    assert!(policy.is_due(TimeDelta::minutes(30), 101));
+    assert!(policy.is_due(TimeDelta::minutes(60), 0));
+
+    let policy = SnapshotPolicy {
+      max_interval_mins: i64::MAX,
+      ..policy
+    };
+    assert!(!policy.is_due(TimeDelta::days(365), 0));
+    assert!(policy.is_due(TimeDelta::days(365), 101));
+  }
+
+  #[tokio::test]
+  async fn owner_overrides_policy_per_note() {
+    let db = test_db().await;
+    let jwt = auth_state(&db).await;
+    let storage = crate::storage::test::init_test_storage().await;
+    let owner = insert_user(&db, "owner", "owner@x.com").await;
+    let other = insert_user(&db, "other", "other@x.com").await;
+    let owner_cookie = auth_cookie(&db, &jwt, owner).await;
+    let other_cookie = auth_cookie(&db, &jwt, other).await;
+    let note = db.notes().create(owner, "T".into()).await.unwrap();
+    let app = Router::new()
+      .route("/", put(super::set_policy))
+      .route("/{note_uuid}", get(super::get_policy))
+      .layer(Extension(NoteEditing::init_test(storage).await))
+      .layer(Extension(SnapshotPolicy::default()))
+      .layer(Extension(OverrideLimits::from_config(&Config::default())))
+      .layer(Extension(jwt))
+      .layer(Extension(db.clone()));
+
+    let put = |cookie: &str, body: Value| {
+      Request::builder()
+        .method("PUT")
+        .uri("/")
+        .header(header::COOKIE, cookie)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+    };
+    let retention = json!([{ "after_mins": 1440, "keep_every_mins": 60 }]);
+
+    let resp = app
+      .clone()
+      .oneshot(put(
+        &other_cookie,
+        json!({ "note_id": note, "min_interval_mins": 5 }),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let resp = app
+      .clone()
+      .oneshot(put(
+        &owner_cookie,
+        json!({ "note_id": note, "min_interval_mins": 90 }),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resp = app
+      .clone()
+      .oneshot(put(
+        &owner_cookie,
+        json!({ "note_id": note, "retention": [
+          { "after_mins": 1440, "keep_every_mins": 60 },
+          { "after_mins": 60, "keep_every_mins": 60 },
+        ] }),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // overrides outside the instance bounds
+    for overrides in [
+      json!({ "min_interval_mins": 0, "max_interval_mins": 0, "min_size_change": 0 }),
+      json!({ "max_interval_mins": i64::MAX }),
+      json!({ "retention": [] }),
+      json!({ "retention": [{ "after_mins": i64::MAX, "keep_every_mins": 60 }] }),
+      json!({ "retention": [{ "after_mins": 60, "keep_every_mins": i64::MAX }] }),
+    ] {
+      let mut body = overrides.clone();
+      body["note_id"] = json!(note);
+      let resp = app.clone().oneshot(put(&owner_cookie, body)).await.unwrap();
+      assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{overrides}");
+    }
+    assert!(db.note_snapshot_policy().get(note).await.unwrap().is_none());
+
+    let resp = app
+      .clone()
+      .oneshot(put(
+        &owner_cookie,
+        json!({ "note_id": note, "min_interval_mins": 5, "retention": retention }),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+      db.note_snapshot_policy()
+        .get(note)
+        .await
+        .unwrap()
+        .unwrap()
+        .retention
+        .as_deref(),
+      Some("1d:1h")
+    );
+
+    let resp = app
+      .clone()
+      .oneshot(
+        Request::builder()
+          .uri(format!("/{note}"))
+          .header(header::COOKIE, &owner_cookie)
+          .body(Body::empty())
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+    let body: Value = body_json(resp).await;
+    assert_eq!(body["overrides"]["min_interval_mins"], 5);
+    assert!(body["overrides"]["max_interval_mins"].is_null());
+    assert_eq!(body["effective"]["max_interval_mins"], 60);
+    assert_eq!(body["effective"]["retention"], retention);
+
+    let resp = app
+      .oneshot(put(&owner_cookie, json!({ "note_id": note })))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(db.note_snapshot_policy().get(note).await.unwrap().is_none());
+  }
+}
@@ -1,18 +1,42 @@
 use yrs::{AsyncTransact, Doc, GetString, ReadTxn};
 
+use crate::notes::export::load_doc;
+
 const PREVIEW_MAX_LENGTH: usize = 500;
 
 pub async fn render_preview(doc: &Doc) -> String {
+  xml_to_string(&render_xml(doc).await)
+}
+
+/// The whole text of the note without a length limit, used for the search
+/// index.
+pub async fn render_text(doc: &Doc) -> String {
+  xml_to_text(&render_xml(doc).await, None)
+}
+
+/// Like [`render_text`] for stored content, undecodable content yields an
+/// empty text.
+pub async fn render_content_text(content: &[u8]) -> String {
+  match load_doc(content).await {
+    Some(doc) => render_text(&doc).await,
+    None => String::new(),
+  }
+}
+
+async fn render_xml(doc: &Doc) -> String {
   let txn = doc.transact().await;
   let Some(fragment) = txn.get_xml_fragment("default") else {
     return String::new();
   };
 
-  let content = fragment.get_string(&txn);
-  xml_to_string(&content)
+  fragment.get_string(&txn)
 }
 
 fn xml_to_string(content: &str) -> String {
+  xml_to_text(content, Some(PREVIEW_MAX_LENGTH))
+}
+
+fn xml_to_text(content: &str, max_length: Option<usize>) -> String {
   let mut result = String::new();
 
   let mut in_tag = false;
@@ -20,7 +44,9 @@ fn xml_to_string(content: &str) -> String {
   let mut is_trimmed = false;
 
   for c in content.chars() {
-    if result.chars().count() >= PREVIEW_MAX_LENGTH {
+    if let Some(max_length) = max_length
+      && result.chars().count() >= max_length
+    {
       is_trimmed = true;
       break;
     }
@@ -53,8 +79,8 @@ fn xml_to_string(content: &str) -> String {
   }
 
   let trimmed = result.trim();
-  if is_trimmed {
-    format!("{}...", &trimmed[..PREVIEW_MAX_LENGTH])
+  if let Some(max_length) = max_length.filter(|_| is_trimmed) {
+    format!("{}...", &trimmed[..max_length])
   } else {
     trimmed.to_string()
   }
@@ -62,7 +88,7 @@ fn xml_to_string(content: &str) -> String {
 
 #[cfg(test)]
 mod test {
-  use super::{PREVIEW_MAX_LENGTH, render_preview, xml_to_string};
+  use super::{PREVIEW_MAX_LENGTH, render_preview, xml_to_string, xml_to_text};
   use yrs::Doc;
 
   #[test]
@@ -124,6 +150,12 @@ mod test {
     assert!(!out.ends_with("..."));
   }
 
+  #[test]
+  fn full_text_is_not_truncated() {
+    let input = format!("<paragraph>{}</paragraph>", "a".repeat(600));
+    assert_eq!(xml_to_text(&input, None), "a".repeat(600));
+  }
+
   #[tokio::test]
   async fn render_preview_empty_doc_returns_empty_string() {
     // a fresh Doc has no "default" xml fragment -> None branch
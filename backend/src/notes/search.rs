@@ -0,0 +1,97 @@
+use centaurus::{db::init::Connection, error::Result};
+use tokio::spawn;
+use tracing::{info, warn};
+
+use crate::{db::DBTrait, notes::preview::render_content_text};
+
+const BATCH_SIZE: u64 = 100;
+
+/// Indexes the full text of notes that only have their preview in the search
+/// index, which are the notes that existed when the index was created.
+pub fn spawn_reindex(db: Connection) {
+  spawn(async move {
+    match reindex_pending(&db).await {
+      Ok(0) => {}
+      Ok(count) => info!(count, "indexed the full text of existing notes"),
+      Err(err) => warn!(?err, "note search reindex failed"),
+    }
+  });
+}
+
+async fn reindex_pending(db: &Connection) -> Result<usize> {
+  let mut count = 0;
+  loop {
+    let notes = db.note_search().pending(BATCH_SIZE).await?;
+    if notes.is_empty() {
+      return Ok(count);
+    }
+
+    for note_id in notes {
+      let content = db.notes().content(note_id).await?.unwrap_or_default();
+      let text = render_content_text(&content).await;
+      if let Err(err) = db.note_search().index_body(note_id, text).await {
+        // the preview stays indexed, the next save retries
+        warn!(?err, %note_id, "failed to index note");
+        db.note_search().clear_pending(note_id).await?;
+        continue;
+      }
+      count += 1;
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use sea_orm::ConnectionTrait;
+  use yrs::{ReadTxn, StateVector, Transact, XmlFragment, XmlTextPrelim};
+
+  use super::reindex_pending;
+  use crate::db::{
+    DBTrait,
+    test::{insert_user, test_db},
+  };
+
+  #[tokio::test]
+  async fn pending_notes_are_indexed_from_stored_content() {
+    let db = test_db().await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let note = db.notes().create(user, "Plan".into()).await.unwrap();
+
+    let doc = yrs::Doc::new();
+    {
+      let fragment = doc.get_or_insert_xml_fragment("default");
+      let mut txn = doc.transact_mut();
+      fragment.push_back(&mut txn, XmlTextPrelim::new("beyond the preview"));
+    }
+    let content = doc
+      .transact()
+      .encode_state_as_update_v1(&StateVector::default());
+    db.notes()
+      .set_content(note, content, String::new())
+      .await
+      .unwrap();
+    // as seeded by the migration for notes that existed before the index
+    db.note_search()
+      .index_body(note, String::new())
+      .await
+      .unwrap();
+    db.execute_unprepared(
+      r#"INSERT INTO "note_search_pending" ("note_id") SELECT "id" FROM "note""#,
+    )
+    .await
+    .unwrap();
+    assert_eq!(db.note_search().pending(10).await.unwrap(), [note]);
+
+    assert_eq!(reindex_pending(&db).await.unwrap(), 1);
+    assert!(db.note_search().pending(10).await.unwrap().is_empty());
+    let hits = db
+      .note_search()
+      .search("preview", &[note], 10)
+      .await
+      .unwrap();
+    assert_eq!(hits.len(), 1);
+
+    // nothing left to do on the next start
+    assert_eq!(reindex_pending(&db).await.unwrap(), 0);
+  }
+}
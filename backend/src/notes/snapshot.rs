@@ -2,11 +2,20 @@ use std::{collections::HashSet, sync::Arc, time::Duration};
 
 use aide::axum::{
   ApiRouter,
-  routing::{delete_with, get_with, put_with},
+  routing::{delete_with, get_with, post_with, put_with},
+};
+use axum::{
+  Extension, Json,
+  body::Bytes,
+  extract::{Path, Query},
+  response::Response,
 };
-use axum::{Json, extract::Path, response::Response};
 use centaurus::{
-  backend::auth::jwt_auth::JwtAuth, bail, db::init::Connection, error::Result, eyre::Context,
+  backend::auth::jwt_auth::JwtAuth,
+  bail,
+  db::init::Connection,
+  error::Result,
+  eyre::{Context, ContextCompat},
   storage::FileStorage,
 };
 use http::header;
@@ -16,22 +25,50 @@ use tokio::{spawn, task::JoinHandle, time::sleep};
 use uuid::Uuid;
 
 use crate::{
+  config::Config,
   db::{
     DBTrait,
-    notes::snapshot::{NoteSnapshotDetail, NoteSnapshotInfo, RetentionTier},
+    notes::snapshot::{EvictScope, NoteSnapshotDetail, NoteSnapshotInfo, RetentionTier},
+  },
+  notes::{
+    diff::{BlockChange, diff_content},
+    export::{ExportQuery, export_response, load_doc},
+    mentions::MentionMail,
+    policy::{SnapshotPolicy, parse_retention},
+    preview::render_preview,
+    state::{MB, NoteEditing, store_content},
   },
-  notes::state::{MB, NoteEditing},
   storage::StorageExt,
   utils::{UpdateMessage, Updater},
 };
 
+const MAX_LABEL_LENGTH: usize = 100;
+
+#[derive(Clone)]
+pub struct SnapshotLimits {
+  /// Pinned or labelled snapshots a note may keep, these are exempt from
+  /// retention.
+  pub max_kept: u64,
+}
+
+impl SnapshotLimits {
+  pub fn from_config(config: &Config) -> Self {
+    Self {
+      max_kept: config.notes_snapshot_max_kept,
+    }
+  }
+}
+
 pub fn router() -> ApiRouter {
   ApiRouter::new()
     .api_route(
       "/{note_uuid}",
       get_with(list, |op| op.id("listNoteSnapshots")),
     )
+    .api_route("/", post_with(create, |op| op.id("createNoteSnapshot")))
     .api_route("/", delete_with(delete, |op| op.id("deleteNoteSnapshot")))
+    .api_route("/pin", put_with(pin, |op| op.id("pinNoteSnapshot")))
+    .api_route("/label", put_with(label, |op| op.id("labelNoteSnapshot")))
     .api_route(
       "/restore",
       put_with(restore, |op| op.id("restoreNoteSnapshot")),
@@ -44,6 +81,11 @@ pub fn router() -> ApiRouter {
       "/{snapshot_id}/content",
       get_with(content, |op| op.id("getNoteSnapshotContent")),
     )
+    .api_route(
+      "/{snapshot_id}/export",
+      get_with(export, |op| op.id("exportNoteSnapshot")),
+    )
+    .api_route("/diff", get_with(diff, |op| op.id("diffNoteSnapshots")))
 }
 
 #[derive(Clone)]
@@ -52,13 +94,18 @@ pub struct SnapshotCleanup {
 }
 
 impl SnapshotCleanup {
-  pub fn init(db: Connection, storage: FileStorage, updater: Updater) -> Self {
+  pub fn init(
+    db: Connection,
+    storage: FileStorage,
+    updater: Updater,
+    policy: SnapshotPolicy,
+  ) -> Self {
     let db = db.clone();
     let storage = storage.clone();
     let updater = updater.clone();
     let handle = spawn(async move {
       loop {
-        if let Err(err) = run_cleanup_cycle(&db, &storage, &updater).await {
+        if let Err(err) = run_cleanup_cycle(&db, &storage, &updater, &policy).await {
           tracing::warn!(?err, "note snapshot cleanup failed");
         }
         sleep(Duration::from_mins(10)).await;
@@ -75,8 +122,9 @@ async fn run_cleanup_cycle(
   db: &Connection,
   storage: &FileStorage,
   updater: &Updater,
+  policy: &SnapshotPolicy,
 ) -> Result<()> {
-  run_cleanup_cycle_at(db, storage, updater, chrono::Utc::now()).await
+  run_cleanup_cycle_at(db, storage, updater, policy, chrono::Utc::now()).await
 }
 
 pub async fn delete_storage_for_note(
@@ -97,6 +145,22 @@ pub async fn delete_storage_for_note(
       );
     }
   }
+
+  let attachments = db.note_attachment().list_for_note(note_id).await?;
+  for attachment in attachments {
+    if let Err(err) = storage
+      .note_attachment()
+      .delete(note_id, attachment.id)
+      .await
+    {
+      tracing::warn!(
+        ?err,
+        %note_id,
+        attachment_id = %attachment.id,
+        "failed to delete note attachment file"
+      );
+    }
+  }
   Ok(())
 }
 
@@ -112,31 +176,54 @@ pub async fn delete_storage_for_user(
   Ok(())
 }
 
+/// Thins out snapshots by the instance retention table, notes with their own
+/// table are handled in a separate pass each.
 async fn run_cleanup_cycle_at(
   db: &Connection,
   storage: &FileStorage,
   updater: &Updater,
+  policy: &SnapshotPolicy,
   now: chrono::DateTime<chrono::Utc>,
 ) -> Result<()> {
-  let mut affected_owners = HashSet::new();
-
-  for tier in RetentionTier::all() {
-    let rows = db.note_snapshot().ids_to_evict_for_tier(*tier, now).await?;
-    if rows.is_empty() {
-      continue;
+  let mut overrides = Vec::new();
+  for (note_id, spec) in db.note_snapshot_policy().retention_overrides().await? {
+    match parse_retention(&spec) {
+      Ok(tiers) => overrides.push((note_id, tiers)),
+      Err(err) => tracing::warn!(%err, %note_id, "invalid note snapshot retention"),
     }
+  }
+  let overridden: Vec<Uuid> = overrides.iter().map(|(note_id, _)| *note_id).collect();
+
+  let mut passes = vec![(EvictScope::AllExcept(&overridden), &policy.retention)];
+  passes.extend(
+    overrides
+      .iter()
+      .map(|(note_id, tiers)| (EvictScope::Note(*note_id), tiers)),
+  );
 
-    for row in &rows {
-      if storage.note_snapshot().exists(row.note, row.id).await? {
-        storage.note_snapshot().delete(row.note, row.id).await?;
+  let mut affected_owners = HashSet::new();
+  for (scope, tiers) in passes {
+    for (tier, until_mins) in RetentionTier::windows(tiers) {
+      let rows = db
+        .note_snapshot()
+        .ids_to_evict_for_tier(tier, until_mins, now, scope)
+        .await?;
+      if rows.is_empty() {
+        continue;
       }
-    }
 
-    let ids: Vec<Uuid> = rows.iter().map(|row| row.id).collect();
-    let deleted = db.note_snapshot().delete_many(&ids).await?;
-    if deleted > 0 {
-      for row in rows {
-        affected_owners.insert(row.owner);
+      for row in &rows {
+        if storage.note_snapshot().exists(row.note, row.id).await? {
+          storage.note_snapshot().delete(row.note, row.id).await?;
+        }
+      }
+
+      let ids: Vec<Uuid> = rows.iter().map(|row| row.id).collect();
+      let deleted = db.note_snapshot().delete_many(&ids).await?;
+      if deleted > 0 {
+        for row in rows {
+          affected_owners.insert(row.owner);
+        }
       }
     }
   }
@@ -150,6 +237,15 @@ async fn run_cleanup_cycle_at(
   Ok(())
 }
 
+async fn read_snapshot(storage: &FileStorage, note_id: Uuid, snapshot_id: Uuid) -> Result<Bytes> {
+  let content = storage.note_snapshot().read(note_id, snapshot_id).await?;
+  Ok(
+    axum::body::to_bytes(content, 10 * MB)
+      .await
+      .context("Failed to read snapshot")?,
+  )
+}
+
 #[derive(Deserialize, JsonSchema)]
 struct NotePath {
   note_uuid: Uuid,
@@ -172,6 +268,36 @@ async fn require_owner(auth: &JwtAuth, db: &Connection, note_id: Uuid) -> Result
   Ok(())
 }
 
+/// Editors may take snapshots, everything else is up to the owner.
+async fn require_editor(auth: &JwtAuth, db: &Connection, note_id: Uuid) -> Result<()> {
+  if !db.notes().can_edit(auth.user_id, note_id).await? {
+    bail!(FORBIDDEN, "forbidden");
+  }
+  Ok(())
+}
+
+/// Trims the label, empty labels are `None`.
+fn normalize_label(label: Option<String>) -> Result<Option<String>> {
+  let Some(label) = label.map(|label| label.trim().to_string()) else {
+    return Ok(None);
+  };
+  if label.chars().count() > MAX_LABEL_LENGTH {
+    bail!(BAD_REQUEST, "label too long");
+  }
+  Ok((!label.is_empty()).then_some(label))
+}
+
+async fn require_below_limit(
+  db: &Connection,
+  limits: &SnapshotLimits,
+  note_id: Uuid,
+) -> Result<()> {
+  if db.note_snapshot().count_kept(note_id).await? >= limits.max_kept {
+    bail!(CONFLICT, "pinned snapshot limit reached");
+  }
+  Ok(())
+}
+
 async fn list(
   auth: JwtAuth,
   db: Connection,
@@ -181,6 +307,134 @@ async fn list(
   Ok(Json(db.note_snapshot().list_for_note(note_uuid).await?))
 }
 
+#[derive(Deserialize, JsonSchema)]
+struct NoteSnapshotCreateReq {
+  note_id: Uuid,
+  label: String,
+  /// Defaults to pinned, so the snapshot is kept until deleted.
+  pinned: Option<bool>,
+}
+
+/// Takes a snapshot of the current content right away, including edits of
+/// connected clients that have not been saved yet.
+async fn create(
+  auth: JwtAuth,
+  db: Connection,
+  storage: FileStorage,
+  state: NoteEditing,
+  updater: Updater,
+  Extension(limits): Extension<SnapshotLimits>,
+  Json(req): Json<NoteSnapshotCreateReq>,
+) -> Result<Json<NoteSnapshotInfo>> {
+  require_editor(&auth, &db, req.note_id).await?;
+  let Some(label) = normalize_label(Some(req.label))? else {
+    bail!(BAD_REQUEST, "label must not be empty");
+  };
+  // labelled snapshots count towards the limit even when not pinned
+  require_below_limit(&db, &limits, req.note_id).await?;
+
+  let (content, preview) = match state.live_content(req.note_id).await {
+    Some(live) => live,
+    None => {
+      let content = db.notes().content(req.note_id).await?.unwrap_or_default();
+      let doc = load_doc(&content)
+        .await
+        .context("failed to decode note content")?;
+      let preview = render_preview(&doc).await;
+      (content, preview)
+    }
+  };
+  if content.len() > 10 * MB {
+    bail!(PAYLOAD_TOO_LARGE, "note too large for a snapshot");
+  }
+
+  let snapshot = db
+    .note_snapshot()
+    .create_labelled(req.note_id, preview, label, req.pinned.unwrap_or(true))
+    .await?;
+  storage
+    .note_snapshot()
+    .create(req.note_id, snapshot.id, &content)
+    .await?;
+
+  notify_snapshot(&db, &updater, snapshot.id, req.note_id).await?;
+  Ok(Json(snapshot))
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct NoteSnapshotPinReq {
+  snapshot_id: Uuid,
+  pinned: bool,
+}
+
+async fn pin(
+  auth: JwtAuth,
+  db: Connection,
+  updater: Updater,
+  Extension(limits): Extension<SnapshotLimits>,
+  Json(req): Json<NoteSnapshotPinReq>,
+) -> Result<()> {
+  let Some(snapshot) = db.note_snapshot().find(req.snapshot_id).await? else {
+    bail!(NOT_FOUND, "snapshot not found");
+  };
+
+  require_owner(&auth, &db, snapshot.note_id).await?;
+  if req.pinned && !snapshot.pinned && snapshot.label.is_none() {
+    require_below_limit(&db, &limits, snapshot.note_id).await?;
+  }
+  db.note_snapshot()
+    .set_pinned(snapshot.id, req.pinned)
+    .await?;
+  notify_snapshot(&db, &updater, snapshot.id, snapshot.note_id).await
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct NoteSnapshotLabelReq {
+  snapshot_id: Uuid,
+  /// Removes the label when empty or omitted.
+  label: Option<String>,
+}
+
+async fn label(
+  auth: JwtAuth,
+  db: Connection,
+  updater: Updater,
+  Extension(limits): Extension<SnapshotLimits>,
+  Json(req): Json<NoteSnapshotLabelReq>,
+) -> Result<()> {
+  let Some(snapshot) = db.note_snapshot().find(req.snapshot_id).await? else {
+    bail!(NOT_FOUND, "snapshot not found");
+  };
+
+  require_owner(&auth, &db, snapshot.note_id).await?;
+  let label = normalize_label(req.label)?;
+  if label.is_some() && !snapshot.pinned && snapshot.label.is_none() {
+    require_below_limit(&db, &limits, snapshot.note_id).await?;
+  }
+  db.note_snapshot().set_label(snapshot.id, label).await?;
+  notify_snapshot(&db, &updater, snapshot.id, snapshot.note_id).await
+}
+
+async fn notify_snapshot(
+  db: &Connection,
+  updater: &Updater,
+  snapshot_id: Uuid,
+  note_id: Uuid,
+) -> Result<()> {
+  if let Some(owner) = db.notes().get_owner_id(note_id).await? {
+    updater
+      .send_to(
+        owner,
+        UpdateMessage::NoteSnapshot {
+          uuid: snapshot_id,
+          note_id,
+        },
+      )
+      .await;
+  }
+  Ok(())
+}
+
 async fn delete(
   auth: JwtAuth,
   db: Connection,
@@ -227,6 +481,8 @@ async fn restore(
   storage: FileStorage,
   db: Connection,
   state: NoteEditing,
+  updater: Updater,
+  mail: MentionMail,
   Json(req): Json<NoteSnapshotIdReq>,
 ) -> Result<()> {
   let Some(snapshot) = db.note_snapshot().find(req.snapshot_id).await? else {
@@ -235,19 +491,22 @@ async fn restore(
 
   require_owner(&auth, &db, snapshot.note_id).await?;
 
-  let content = storage
-    .note_snapshot()
-    .read(snapshot.note_id, snapshot.id)
-    .await?;
-  let data = axum::body::to_bytes(content, 10 * MB)
-    .await
-    .context("Failed to read snapshot")?;
-
-  db.notes()
-    .set_content(snapshot.note_id, data.to_vec(), snapshot.preview)
+  let data = read_snapshot(&storage, snapshot.note_id, snapshot.id).await?;
+
+  // an open note saves the restored document like any other edit
+  if !state.restore(snapshot.note_id, &data).await? {
+    let doc = load_doc(&data).await.context("failed to decode snapshot")?;
+    store_content(
+      &db,
+      &updater,
+      &mail,
+      snapshot.note_id,
+      &doc,
+      data.to_vec(),
+      snapshot.preview,
+    )
     .await?;
-
-  state.restore(snapshot.note_id, &data).await?;
+  }
 
   Ok(())
 }
@@ -291,6 +550,71 @@ async fn content(
   )
 }
 
+async fn export(
+  auth: JwtAuth,
+  db: Connection,
+  storage: FileStorage,
+  Path(SnapshotPath { snapshot_id }): Path<SnapshotPath>,
+  Query(query): Query<ExportQuery>,
+) -> Result<Response> {
+  let Some(snapshot) = db.note_snapshot().find(snapshot_id).await? else {
+    bail!(NOT_FOUND, "snapshot not found");
+  };
+
+  require_owner(&auth, &db, snapshot.note_id).await?;
+
+  let Some(note) = db.notes().info(snapshot.note_id, auth.user_id).await? else {
+    bail!(NOT_FOUND, "note not found");
+  };
+  let data = read_snapshot(&storage, snapshot.note_id, snapshot.id).await?;
+
+  export_response(snapshot.id, &note.title, &data, query.format).await
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct SnapshotDiffQuery {
+  from: Uuid,
+  /// Compared against the current note content when omitted.
+  to: Option<Uuid>,
+}
+
+async fn diff(
+  auth: JwtAuth,
+  db: Connection,
+  storage: FileStorage,
+  state: NoteEditing,
+  Query(query): Query<SnapshotDiffQuery>,
+) -> Result<Json<Vec<BlockChange>>> {
+  let Some(from) = db.note_snapshot().find(query.from).await? else {
+    bail!(NOT_FOUND, "snapshot not found");
+  };
+
+  require_owner(&auth, &db, from.note_id).await?;
+
+  let old = read_snapshot(&storage, from.note_id, from.id).await?;
+  let new = match query.to {
+    Some(to) => {
+      let Some(to) = db.note_snapshot().find(to).await? else {
+        bail!(NOT_FOUND, "snapshot not found");
+      };
+      if to.note_id != from.note_id {
+        bail!(BAD_REQUEST, "snapshots belong to different notes");
+      }
+      read_snapshot(&storage, to.note_id, to.id).await?.to_vec()
+    }
+    // includes edits of connected clients that have not been saved yet
+    None => match state.live_content(from.note_id).await {
+      Some((content, _)) => content,
+      None => db.notes().content(from.note_id).await?.unwrap_or_default(),
+    },
+  };
+
+  let changes = diff_content(&old, &new)
+    .await
+    .context("failed to decode note content")?;
+  Ok(Json(changes))
+}
+
 #[cfg(test)]
 mod test {
   use entity::sea_orm_active_enums::NoteShareAccess;
@@ -318,18 +642,27 @@ mod test {
       crate::utils::UpdateMessage,
     >::init()
     .await;
-    let editing = crate::notes::state::NoteEditing::init(storage.clone(), updater.clone());
+    let editing =
+      crate::notes::state::NoteEditing::init(storage.clone(), updater.clone(), Default::default());
     Router::new()
       .route("/{note_uuid}", get(super::list))
-      .route("/", axum::routing::delete(super::delete))
+      .route(
+        "/",
+        axum::routing::post(super::create).delete(super::delete),
+      )
       .route("/restore", axum::routing::put(super::restore))
+      .route("/pin", axum::routing::put(super::pin))
+      .route("/label", axum::routing::put(super::label))
       .route("/{snapshot_id}/info", get(super::snapshot_info))
       .route("/{snapshot_id}/content", get(super::content))
+      .route("/{snapshot_id}/export", get(super::export))
+      .route("/diff", get(super::diff))
       .layer(Extension(jwt))
       .layer(Extension(db))
       .layer(Extension(storage))
       .layer(Extension(updater))
       .layer(Extension(editing))
+      .layer(Extension(super::SnapshotLimits { max_kept: 2 }))
   }
 
   async fn create_snapshot_in_storage(
@@ -624,7 +957,7 @@ mod test {
     let storage = crate::storage::test::init_test_storage().await;
     let note = s.db.notes().create(s.user, "T".into()).await.unwrap();
     let snapshot_id =
-      create_snapshot_in_storage(&s.db, &storage, note, "preview", b"snapshot").await;
+      create_snapshot_in_storage(&s.db, &storage, note, "preview", &linking_doc(&[])).await;
     let app = app(s.db.clone(), s.jwt, storage).await;
 
     let resp = app
@@ -639,6 +972,77 @@ mod test {
     assert_eq!(resp.status(), StatusCode::OK);
   }
 
+  fn linking_doc(targets: &[Uuid]) -> Vec<u8> {
+    use yrs::{ReadTxn, StateVector, Transact, Xml, XmlElementPrelim, XmlFragment};
+
+    let doc = yrs::Doc::new();
+    let fragment = doc.get_or_insert_xml_fragment("default");
+    {
+      let mut txn = doc.transact_mut();
+      let paragraph = fragment.push_back(&mut txn, XmlElementPrelim::empty("paragraph"));
+      for target in targets {
+        let link = paragraph.push_back(&mut txn, XmlElementPrelim::empty("noteLink"));
+        link.insert_attribute(&mut txn, "id", target.to_string());
+      }
+    }
+    doc
+      .transact()
+      .encode_state_as_update_v1(&StateVector::default())
+  }
+
+  #[tokio::test]
+  async fn restore_rebuilds_the_link_index() {
+    let s = setup().await;
+    let storage = crate::storage::test::init_test_storage().await;
+    let note = s.db.notes().create(s.user, "T".into()).await.unwrap();
+    let old_target = s.db.notes().create(s.user, "Old".into()).await.unwrap();
+    let new_target = s.db.notes().create(s.user, "New".into()).await.unwrap();
+    let snapshot_id = create_snapshot_in_storage(
+      &s.db,
+      &storage,
+      note,
+      "preview",
+      &linking_doc(&[old_target]),
+    )
+    .await;
+    s.db
+      .notes()
+      .set_content(note, linking_doc(&[new_target]), String::new())
+      .await
+      .unwrap();
+    s.db
+      .note_link()
+      .set_links(note, &[new_target])
+      .await
+      .unwrap();
+    let app = app(s.db.clone(), s.jwt, storage).await;
+
+    let resp = app
+      .oneshot(request(
+        "PUT",
+        "/restore",
+        Some(&s.cookie),
+        Some(json!({ "snapshot_id": snapshot_id })),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let visible = [note, old_target, new_target];
+    let links = s.db.note_link();
+    assert_eq!(
+      links.backlinks(old_target, &visible).await.unwrap().len(),
+      1
+    );
+    assert!(
+      links
+        .backlinks(new_target, &visible)
+        .await
+        .unwrap()
+        .is_empty()
+    );
+  }
+
   #[tokio::test]
   async fn restore_forbidden_for_non_owner() {
     let s = setup().await;
@@ -881,7 +1285,7 @@ mod test {
       .await
       .unwrap();
 
-    super::run_cleanup_cycle_at(&s.db, &storage, &updater, now)
+    super::run_cleanup_cycle_at(&s.db, &storage, &updater, &Default::default(), now)
       .await
       .unwrap();
 
@@ -906,6 +1310,51 @@ mod test {
     );
   }
 
+  #[tokio::test]
+  async fn cleanup_uses_instance_policy_and_note_overrides() {
+    use chrono::{TimeDelta, TimeZone, Utc};
+
+    let s = setup().await;
+    let storage = crate::storage::test::init_test_storage().await;
+    let updater = crate::db::test::updater().await;
+    let note = s.db.notes().create(s.user, "T".into()).await.unwrap();
+    let kept_all = s.db.notes().create(s.user, "K".into()).await.unwrap();
+    let now = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+
+    for note in [note, kept_all] {
+      for offset in [TimeDelta::minutes(90), TimeDelta::hours(3)] {
+        s.db
+          .note_snapshot()
+          .create_at(note, "p".into(), now - offset)
+          .await
+          .unwrap();
+      }
+    }
+    // an empty table keeps every snapshot of the note
+    s.db
+      .note_snapshot_policy()
+      .set(entity::note_snapshot_policy::Model {
+        note_id: kept_all,
+        min_interval_mins: None,
+        max_interval_mins: None,
+        min_size_change: None,
+        retention: Some(String::new()),
+      })
+      .await
+      .unwrap();
+    let policy = crate::notes::policy::SnapshotPolicy {
+      retention: crate::notes::policy::parse_retention("1h:1d").unwrap(),
+      ..Default::default()
+    };
+
+    super::run_cleanup_cycle_at(&s.db, &storage, &updater, &policy, now)
+      .await
+      .unwrap();
+
+    assert_eq!(snapshot_ids(&s.db, note).await.len(), 1);
+    assert_eq!(snapshot_ids(&s.db, kept_all).await.len(), 2);
+  }
+
   #[tokio::test]
   async fn cleanup_mixed_ages_deletes_across_tiers() {
     use chrono::{TimeDelta, TimeZone, Utc};
@@ -971,7 +1420,7 @@ mod test {
         .unwrap();
     }
 
-    super::run_cleanup_cycle_at(&s.db, &storage, &updater, now)
+    super::run_cleanup_cycle_at(&s.db, &storage, &updater, &Default::default(), now)
       .await
       .unwrap();
 
@@ -1020,7 +1469,7 @@ mod test {
       .await
       .unwrap();
 
-    super::run_cleanup_cycle_at(&s.db, &storage, &updater, now)
+    super::run_cleanup_cycle_at(&s.db, &storage, &updater, &Default::default(), now)
       .await
       .unwrap();
 
@@ -1064,7 +1513,7 @@ mod test {
       .await
       .unwrap();
 
-    super::run_cleanup_cycle_at(&s.db, &storage, &updater, now)
+    super::run_cleanup_cycle_at(&s.db, &storage, &updater, &Default::default(), now)
       .await
       .unwrap();
 
@@ -1117,7 +1566,7 @@ mod test {
         .unwrap();
     }
 
-    super::run_cleanup_cycle_at(&s.db, &storage, &updater, now)
+    super::run_cleanup_cycle_at(&s.db, &storage, &updater, &Default::default(), now)
       .await
       .unwrap();
 
@@ -1174,7 +1623,7 @@ mod test {
       }
     }
 
-    super::run_cleanup_cycle_at(&s.db, &storage, &updater, now)
+    super::run_cleanup_cycle_at(&s.db, &storage, &updater, &Default::default(), now)
       .await
       .unwrap();
 
@@ -1235,7 +1684,7 @@ mod test {
       }
     }
 
-    super::run_cleanup_cycle_at(&s.db, &storage, &updater, now)
+    super::run_cleanup_cycle_at(&s.db, &storage, &updater, &Default::default(), now)
       .await
       .unwrap();
 
@@ -1277,7 +1726,7 @@ mod test {
       .await
       .unwrap();
 
-    super::run_cleanup_cycle_at(&s.db, &storage, &updater, now)
+    super::run_cleanup_cycle_at(&s.db, &storage, &updater, &Default::default(), now)
       .await
       .unwrap();
 
@@ -1323,7 +1772,7 @@ mod test {
         .unwrap();
     }
 
-    super::run_cleanup_cycle_at(&s.db, &storage, &updater, now)
+    super::run_cleanup_cycle_at(&s.db, &storage, &updater, &Default::default(), now)
       .await
       .unwrap();
 
@@ -1334,4 +1783,301 @@ mod test {
       assert!(!storage.note_snapshot().exists(note, evicted).await.unwrap());
     }
   }
+
+  #[tokio::test]
+  async fn export_renders_snapshot_for_owner_only() {
+    use yrs::{ReadTxn, StateVector, Transact};
+
+    let s = setup().await;
+    let storage = crate::storage::test::init_test_storage().await;
+    let note = s.db.notes().create(s.user, "Ideas".into()).await.unwrap();
+    let content = crate::notes::markdown::test::sample_doc()
+      .transact()
+      .encode_state_as_update_v1(&StateVector::default());
+    let snapshot_id = create_snapshot_in_storage(&s.db, &storage, note, "p", &content).await;
+    let stranger = insert_user(&s.db, "stranger", "s@x.com").await;
+    let stranger_cookie = auth_cookie(&s.db, &s.jwt, stranger).await;
+    let app = app(s.db.clone(), s.jwt, storage).await;
+
+    let resp = app
+      .clone()
+      .oneshot(request(
+        "GET",
+        &format!("/{snapshot_id}/export?format=md"),
+        Some(&s.cookie),
+        None,
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+      resp.headers()[header::CONTENT_TYPE],
+      "text/markdown; charset=utf-8"
+    );
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+      .await
+      .unwrap();
+    assert!(body.starts_with(b"# Ideas\n\n## Title\n"));
+
+    let resp = app
+      .oneshot(request(
+        "GET",
+        &format!("/{snapshot_id}/export?format=html"),
+        Some(&stranger_cookie),
+        None,
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+  }
+
+  #[tokio::test]
+  async fn diff_compares_snapshots_and_live_note() {
+    use yrs::{ReadTxn, StateVector, Transact, XmlElementPrelim, XmlFragment, XmlTextPrelim};
+
+    let s = setup().await;
+    let storage = crate::storage::test::init_test_storage().await;
+    let note = s.db.notes().create(s.user, "Ideas".into()).await.unwrap();
+    let other = s.db.notes().create(s.user, "Other".into()).await.unwrap();
+
+    let doc = crate::notes::markdown::test::sample_doc();
+    let old = doc
+      .transact()
+      .encode_state_as_update_v1(&StateVector::default());
+    {
+      let fragment = doc.get_or_insert_xml_fragment("default");
+      let mut txn = doc.transact_mut();
+      let paragraph = fragment.push_back(&mut txn, XmlElementPrelim::empty("paragraph"));
+      paragraph.push_back(&mut txn, XmlTextPrelim::new("extra"));
+    }
+    let new = doc
+      .transact()
+      .encode_state_as_update_v1(&StateVector::default());
+
+    let from = create_snapshot_in_storage(&s.db, &storage, note, "a", &old).await;
+    let to = create_snapshot_in_storage(&s.db, &storage, note, "b", &new).await;
+    let foreign = create_snapshot_in_storage(&s.db, &storage, other, "c", &old).await;
+    s.db
+      .notes()
+      .set_content(note, new, "b".into())
+      .await
+      .unwrap();
+    let app = app(s.db.clone(), s.jwt, storage).await;
+
+    for uri in [
+      format!("/diff?from={from}&to={to}"),
+      format!("/diff?from={from}"),
+    ] {
+      let resp = app
+        .clone()
+        .oneshot(request("GET", &uri, Some(&s.cookie), None))
+        .await
+        .unwrap();
+      assert_eq!(resp.status(), StatusCode::OK);
+      let body = body_json(resp).await;
+      let blocks = body.as_array().unwrap();
+      assert_eq!(blocks.len(), 6);
+      assert_eq!(
+        blocks[0],
+        json!({ "kind": "unchanged", "text": "## Title" })
+      );
+      assert_eq!(blocks[5], json!({ "kind": "added", "text": "extra" }));
+    }
+
+    let resp = app
+      .oneshot(request(
+        "GET",
+        &format!("/diff?from={from}&to={foreign}"),
+        Some(&s.cookie),
+        None,
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+  }
+
+  #[tokio::test]
+  async fn diff_against_an_open_note_includes_unsaved_edits() {
+    use yrs::{ReadTxn, StateVector, Transact, XmlElementPrelim, XmlFragment, XmlTextPrelim};
+
+    let s = setup().await;
+    let storage = crate::storage::test::init_test_storage().await;
+    let note = s.db.notes().create(s.user, "Ideas".into()).await.unwrap();
+
+    let doc = crate::notes::markdown::test::sample_doc();
+    let saved = doc
+      .transact()
+      .encode_state_as_update_v1(&StateVector::default());
+    {
+      let fragment = doc.get_or_insert_xml_fragment("default");
+      let mut txn = doc.transact_mut();
+      let paragraph = fragment.push_back(&mut txn, XmlElementPrelim::empty("paragraph"));
+      paragraph.push_back(&mut txn, XmlTextPrelim::new("unsaved"));
+    }
+    let live = doc
+      .transact()
+      .encode_state_as_update_v1(&StateVector::default());
+
+    let from = create_snapshot_in_storage(&s.db, &storage, note, "a", &saved).await;
+    s.db
+      .notes()
+      .set_content(note, saved, "a".into())
+      .await
+      .unwrap();
+    let (_state, updater) = centaurus::backend::endpoints::websocket::state::UpdateState::<
+      crate::utils::UpdateMessage,
+    >::init()
+    .await;
+    let editing =
+      crate::notes::state::NoteEditing::init(storage.clone(), updater, Default::default());
+    editing
+      .get_or_open_note(note, &s.db, Default::default())
+      .await
+      .unwrap();
+    editing.apply_update(note, &live).await.unwrap();
+
+    let app = Router::new()
+      .route("/diff", get(super::diff))
+      .layer(Extension(s.jwt))
+      .layer(Extension(s.db.clone()))
+      .layer(Extension(storage))
+      .layer(Extension(editing));
+    let resp = app
+      .oneshot(request(
+        "GET",
+        &format!("/diff?from={from}"),
+        Some(&s.cookie),
+        None,
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = body_json(resp).await;
+    let blocks = body.as_array().unwrap();
+    assert_eq!(
+      blocks.last().unwrap(),
+      &json!({ "kind": "added", "text": "unsaved" })
+    );
+  }
+
+  #[tokio::test]
+  async fn editors_create_labelled_snapshots_owners_pin_them() {
+    let s = setup().await;
+    let storage = crate::storage::test::init_test_storage().await;
+    let note = s.db.notes().create(s.user, "T".into()).await.unwrap();
+    let editor = insert_user(&s.db, "editor", "e@x.com").await;
+    let viewer = insert_user(&s.db, "viewer", "v@x.com").await;
+    s.db
+      .notes()
+      .set_shared_users(
+        note,
+        s.user,
+        vec![
+          NoteShareEntry {
+            user_id: editor,
+            access: NoteShareAccess::Edit,
+          },
+          NoteShareEntry {
+            user_id: viewer,
+            access: NoteShareAccess::View,
+          },
+        ],
+      )
+      .await
+      .unwrap();
+    let editor_cookie = auth_cookie(&s.db, &s.jwt, editor).await;
+    let viewer_cookie = auth_cookie(&s.db, &s.jwt, viewer).await;
+    let app = app(s.db.clone(), s.jwt, storage.clone()).await;
+
+    let resp = app
+      .clone()
+      .oneshot(request(
+        "POST",
+        "/",
+        Some(&editor_cookie),
+        Some(json!({ "note_id": note, "label": "  Before rewrite " })),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = body_json(resp).await;
+    assert_eq!(body["label"], "Before rewrite");
+    assert_eq!(body["pinned"], true);
+    let snapshot: Uuid = serde_json::from_value(body["id"].clone()).unwrap();
+    assert!(
+      storage
+        .note_snapshot()
+        .exists(note, snapshot)
+        .await
+        .unwrap()
+    );
+
+    for (cookie, body, status) in [
+      (
+        &viewer_cookie,
+        json!({ "note_id": note, "label": "x" }),
+        StatusCode::FORBIDDEN,
+      ),
+      (
+        &s.cookie,
+        json!({ "note_id": note, "label": " " }),
+        StatusCode::BAD_REQUEST,
+      ),
+    ] {
+      let resp = app
+        .clone()
+        .oneshot(request("POST", "/", Some(cookie), Some(body)))
+        .await
+        .unwrap();
+      assert_eq!(resp.status(), status);
+    }
+
+    // editors cannot list snapshots, so pinning and labelling is up to the owner
+    for (cookie, status) in [
+      (&editor_cookie, StatusCode::FORBIDDEN),
+      (&s.cookie, StatusCode::OK),
+    ] {
+      for (uri, body) in [
+        ("/pin", json!({ "snapshot_id": snapshot, "pinned": false })),
+        ("/label", json!({ "snapshot_id": snapshot, "label": "" })),
+      ] {
+        let resp = app
+          .clone()
+          .oneshot(request("PUT", uri, Some(cookie), Some(body)))
+          .await
+          .unwrap();
+        assert_eq!(resp.status(), status);
+      }
+    }
+
+    let found = s.db.note_snapshot().find(snapshot).await.unwrap().unwrap();
+    assert!(!found.pinned);
+    assert_eq!(found.label, None);
+
+    // at most two pinned or labelled snapshots per note
+    for status in [StatusCode::OK, StatusCode::OK, StatusCode::CONFLICT] {
+      let resp = app
+        .clone()
+        .oneshot(request(
+          "POST",
+          "/",
+          Some(&editor_cookie),
+          Some(json!({ "note_id": note, "label": "x", "pinned": false })),
+        ))
+        .await
+        .unwrap();
+      assert_eq!(resp.status(), status);
+    }
+    for (uri, body) in [
+      ("/pin", json!({ "snapshot_id": snapshot, "pinned": true })),
+      ("/label", json!({ "snapshot_id": snapshot, "label": "y" })),
+    ] {
+      let resp = app
+        .clone()
+        .oneshot(request("PUT", uri, Some(&s.cookie), Some(body)))
+        .await
+        .unwrap();
+      assert_eq!(resp.status(), StatusCode::CONFLICT);
+    }
+  }
 }
@@ -21,7 +21,7 @@ use centaurus::{
   eyre::{Context, ContextCompat},
   storage::FileStorage,
 };
-use chrono::{DateTime, TimeDelta, Utc};
+use chrono::{DateTime, Utc};
 use dashmap::DashMap;
 use image::EncodableLayout;
 use sha2::{Digest, Sha256};
@@ -51,7 +51,12 @@ use yrs::{
 
 use crate::{
   db::DBTrait,
-  notes::preview::render_preview,
+  notes::{
+    links::linked_notes,
+    mentions::{MentionMail, mentioned_users, notify_new_mentions},
+    policy::SnapshotPolicy,
+    preview::{render_preview, render_text},
+  },
   storage::StorageExt,
   utils::{UpdateMessage, Updater},
 };
@@ -65,11 +70,13 @@ pub struct NoteEditing {
   note_lock: Arc<DashMap<Uuid, Arc<Mutex<()>>>>,
   storage: Arc<FileStorage>,
   updater: Updater,
+  policy: SnapshotPolicy,
 }
 
 struct SnapshotData {
   last_snapshot: DateTime<Utc>,
   last_snapshot_size: usize,
+  policy: SnapshotPolicy,
 }
 
 pub struct NoteState {
@@ -84,17 +91,19 @@ pub struct NoteState {
   storage: Arc<FileStorage>,
   updater: Updater,
   owner_id: Uuid,
+  mail: MentionMail,
   snapshot_data: Mutex<SnapshotData>,
   old_content_hash: Mutex<Vec<u8>>,
 }
 
 impl NoteEditing {
-  pub fn init(storage: FileStorage, updater: Updater) -> Self {
+  pub fn init(storage: FileStorage, updater: Updater, policy: SnapshotPolicy) -> Self {
     Self {
       docs: Arc::new(DashMap::new()),
       note_lock: Arc::new(DashMap::new()),
       storage: Arc::new(storage),
       updater,
+      policy,
     }
   }
 
@@ -102,10 +111,23 @@ impl NoteEditing {
   pub async fn init_test(storage: FileStorage) -> Self {
     let (_state, updater) =
       centaurus::backend::endpoints::websocket::state::UpdateState::<UpdateMessage>::init().await;
-    Self::init(storage, updater)
+    Self::init(storage, updater, SnapshotPolicy::default())
+  }
+
+  /// Makes an open note pick up a changed snapshot policy.
+  pub async fn set_policy(&self, note_id: Uuid, policy: SnapshotPolicy) {
+    let Some(state) = self.docs.get(&note_id).map(|state| state.clone()) else {
+      return;
+    };
+    state.snapshot_data.lock().await.policy = policy;
   }
 
-  pub async fn get_or_open_note(&self, note_id: Uuid, db: &Connection) -> Result<Arc<NoteState>> {
+  pub async fn get_or_open_note(
+    &self,
+    note_id: Uuid,
+    db: &Connection,
+    mail: MentionMail,
+  ) -> Result<Arc<NoteState>> {
     let lock = self.lock_note(note_id).await;
     if let Some(state) = self.docs.get(&note_id) {
       drop(lock);
@@ -124,6 +146,7 @@ impl NoteEditing {
       .get_owner_id(note_id)
       .await?
       .context("No owner for note")?;
+    let policy = self.policy.for_note(db, note_id).await?;
 
     let doc = Doc::new();
     if !content.is_empty() {
@@ -185,9 +208,11 @@ impl NoteEditing {
       storage: self.storage.clone(),
       updater: self.updater.clone(),
       owner_id,
+      mail,
       snapshot_data: Mutex::new(SnapshotData {
         last_snapshot: latest_snapshot,
         last_snapshot_size: content.len(),
+        policy,
       }),
       old_content_hash: Mutex::new(hash_content(&content)),
     });
@@ -223,14 +248,30 @@ impl NoteEditing {
     Ok(())
   }
 
-  pub async fn restore(&self, note_id: Uuid, data: &[u8]) -> Result<()> {
+  /// Rebuilds the document of an open note from a snapshot, `false` when the
+  /// note is not open.
+  pub async fn restore(&self, note_id: Uuid, data: &[u8]) -> Result<bool> {
     let Some(state) = self.docs.get(&note_id) else {
-      return Ok(());
+      return Ok(false);
     };
 
     state.restore(data).await?;
 
-    Ok(())
+    Ok(true)
+  }
+
+  /// Content and preview of the note while it is open, including edits that
+  /// have not been saved yet.
+  pub async fn live_content(&self, note_id: Uuid) -> Option<(Vec<u8>, String)> {
+    let state = self.docs.get(&note_id)?.clone();
+    let awareness = state.doc.lock().await;
+    let doc = awareness.doc();
+    let content = doc
+      .transact()
+      .await
+      .encode_state_as_update_v1(&StateVector::default());
+
+    Some((content, render_preview(doc).await))
   }
 
   pub async fn lock_note(&self, note_id: Uuid) -> OwnedMutexGuard<()> {
@@ -352,11 +393,8 @@ impl NoteState {
     let mut snapshot_data = self.snapshot_data.lock().await;
     let last_elapsed = Utc::now() - snapshot_data.last_snapshot;
 
-    // snapshots are created when the last snapshot is at least older than 10 minutes and (the snapshot is older than 1 hour or the snapshot has changed in size by more than 100B)
-    if last_elapsed >= TimeDelta::minutes(10)
-      && (last_elapsed >= TimeDelta::hours(1)
-        || (content.len() as isize - snapshot_data.last_snapshot_size as isize).abs() > 100)
-    {
+    let size_change = content.len().abs_diff(snapshot_data.last_snapshot_size);
+    if snapshot_data.policy.is_due(last_elapsed, size_change) {
       let snapshot_id = db.note_snapshot().create(note_id, preview.clone()).await?;
       self
         .storage
@@ -383,7 +421,16 @@ impl NoteState {
     let content_hash = hash_content(&content);
     let mut old_content_hash = self.old_content_hash.lock().await;
     if content_hash != *old_content_hash {
-      db.notes().set_content(note_id, content, preview).await?;
+      store_content(
+        db,
+        &self.updater,
+        &self.mail,
+        note_id,
+        doc,
+        content,
+        preview,
+      )
+      .await?;
       *old_content_hash = content_hash;
     }
 
@@ -454,11 +501,37 @@ impl NoteState {
     for child in children {
       fragment.push_back(&mut txn, child);
     }
+    drop(txn);
+    drop(awareness);
+    self.save_counter.fetch_add(1, Ordering::Relaxed);
 
     Ok(())
   }
 }
 
+/// Stores changed note content along with what is derived from it, the
+/// search index, the outgoing links and new mentions.
+pub async fn store_content(
+  db: &Connection,
+  updater: &Updater,
+  mail: &MentionMail,
+  note_id: Uuid,
+  doc: &Doc,
+  content: Vec<u8>,
+  preview: String,
+) -> Result<()> {
+  db.notes().set_content(note_id, content, preview).await?;
+  db.note_search()
+    .index_body(note_id, render_text(doc).await)
+    .await?;
+  let (mentioned, links) = {
+    let txn = doc.transact().await;
+    (mentioned_users(&txn), linked_notes(&txn, note_id))
+  };
+  db.note_link().set_links(note_id, &links).await?;
+  notify_new_mentions(db, updater, mail, note_id, &mentioned).await
+}
+
 async fn handle_read_only_message(
   awareness: &mut Awareness,
   data: &[u8],
@@ -511,8 +584,14 @@ mod note_editing_test {
     let storage = crate::storage::test::init_test_storage().await;
 
     let editing = NoteEditing::init_test(storage).await;
-    let first = editing.get_or_open_note(note_id, &db).await.unwrap();
-    let second = editing.get_or_open_note(note_id, &db).await.unwrap();
+    let first = editing
+      .get_or_open_note(note_id, &db, Default::default())
+      .await
+      .unwrap();
+    let second = editing
+      .get_or_open_note(note_id, &db, Default::default())
+      .await
+      .unwrap();
 
     // second open must return the cached Arc, not a fresh document
     assert!(Arc::ptr_eq(&first, &second));
@@ -524,7 +603,12 @@ mod note_editing_test {
     let storage = crate::storage::test::init_test_storage().await;
     let editing = NoteEditing::init_test(storage).await;
     // no note row exists -> get_content fails -> error propagates
-    assert!(editing.get_or_open_note(Uuid::new_v4(), &db).await.is_err());
+    assert!(
+      editing
+        .get_or_open_note(Uuid::new_v4(), &db, Default::default())
+        .await
+        .is_err()
+    );
   }
 
   #[tokio::test]
@@ -545,12 +629,21 @@ mod note_editing_test {
     let storage = crate::storage::test::init_test_storage().await;
     let editing = NoteEditing::init_test(storage).await;
     // two subscribers
-    let first = editing.get_or_open_note(note_id, &db).await.unwrap();
-    let _second = editing.get_or_open_note(note_id, &db).await.unwrap();
+    let first = editing
+      .get_or_open_note(note_id, &db, Default::default())
+      .await
+      .unwrap();
+    let _second = editing
+      .get_or_open_note(note_id, &db, Default::default())
+      .await
+      .unwrap();
 
     // first close just decrements the subscriber count; the doc stays cached
     editing.close_note(note_id, &db).await.unwrap();
-    let reopened = editing.get_or_open_note(note_id, &db).await.unwrap();
+    let reopened = editing
+      .get_or_open_note(note_id, &db, Default::default())
+      .await
+      .unwrap();
     assert!(Arc::ptr_eq(&first, &reopened));
 
     // drain remaining subscribers; final close removes and persists the note
@@ -558,7 +651,10 @@ mod note_editing_test {
     editing.close_note(note_id, &db).await.unwrap();
 
     // after full close a new open allocates a fresh document
-    let fresh = editing.get_or_open_note(note_id, &db).await.unwrap();
+    let fresh = editing
+      .get_or_open_note(note_id, &db, Default::default())
+      .await
+      .unwrap();
     assert!(!Arc::ptr_eq(&first, &fresh));
   }
 
@@ -570,7 +666,10 @@ mod note_editing_test {
 
     let storage = crate::storage::test::init_test_storage().await;
     let editing = NoteEditing::init_test(storage).await;
-    let state = editing.get_or_open_note(note_id, &db).await.unwrap();
+    let state = editing
+      .get_or_open_note(note_id, &db, Default::default())
+      .await
+      .unwrap();
 
     // an empty doc saves successfully (covers gc/encode/render_preview/set_content)
     state.save(&db, note_id).await.unwrap();
@@ -586,7 +685,10 @@ mod note_editing_test {
 
     let storage = crate::storage::test::init_test_storage().await;
     let editing = NoteEditing::init_test(storage).await;
-    let state = editing.get_or_open_note(note_id, &db).await.unwrap();
+    let state = editing
+      .get_or_open_note(note_id, &db, Default::default())
+      .await
+      .unwrap();
     // a fresh subscriber has no buffered messages
     let mut rx = state.receiver();
     assert!(rx.try_recv().is_err());
@@ -594,6 +696,7 @@ mod note_editing_test {
 
   #[tokio::test]
   async fn restore_rebuilds_live_doc_from_snapshot_content() {
+    use std::sync::atomic::Ordering;
     use yrs::{Doc, GetString, ReadTxn, StateVector, Transact, XmlFragment, XmlTextPrelim};
 
     let db = test_db().await;
@@ -602,7 +705,10 @@ mod note_editing_test {
 
     let storage = crate::storage::test::init_test_storage().await;
     let editing = NoteEditing::init_test(storage).await;
-    let state = editing.get_or_open_note(note_id, &db).await.unwrap();
+    let state = editing
+      .get_or_open_note(note_id, &db, Default::default())
+      .await
+      .unwrap();
 
     // snapshot document carrying the content we want to restore to
     let snapshot_doc = Doc::new();
@@ -624,7 +730,9 @@ mod note_editing_test {
     }
 
     // restore through the public NoteEditing entry point (covers the lookup)
-    editing.restore(note_id, &data).await.unwrap();
+    assert!(editing.restore(note_id, &data).await.unwrap());
+    // the restored document is saved like an edit
+    assert!(state.save_counter.load(Ordering::Relaxed) > 0);
 
     let awareness = state.doc.lock().await;
     let fragment = awareness.doc().get_or_insert_xml_fragment("default");
@@ -642,10 +750,12 @@ mod note_editing_test {
     let storage = crate::storage::test::init_test_storage().await;
     let editing = NoteEditing::init_test(storage).await;
     // note was never opened -> early return, data is never even decoded
-    editing
-      .restore(Uuid::new_v4(), b"not even valid yrs data")
-      .await
-      .unwrap();
+    assert!(
+      !editing
+        .restore(Uuid::new_v4(), b"not even valid yrs data")
+        .await
+        .unwrap()
+    );
   }
 
   #[tokio::test]
@@ -659,7 +769,10 @@ mod note_editing_test {
 
     let storage = crate::storage::test::init_test_storage().await;
     let editing = NoteEditing::init_test(storage.clone()).await;
-    let state = editing.get_or_open_note(note_id, &db).await.unwrap();
+    let state = editing
+      .get_or_open_note(note_id, &db, Default::default())
+      .await
+      .unwrap();
 
     // seed enough content that the doc is non-trivial
     {
@@ -717,7 +830,10 @@ mod note_editing_test {
 
     let storage = crate::storage::test::init_test_storage().await;
     let editing = NoteEditing::init_test(storage).await;
-    let state = editing.get_or_open_note(note_id, &db).await.unwrap();
+    let state = editing
+      .get_or_open_note(note_id, &db, Default::default())
+      .await
+      .unwrap();
 
     {
       let awareness = state.doc.lock().await;
@@ -762,7 +878,10 @@ mod note_editing_test {
 
     let storage = crate::storage::test::init_test_storage().await;
     let editing = NoteEditing::init_test(storage).await;
-    let state = editing.get_or_open_note(note_id, &db).await.unwrap();
+    let state = editing
+      .get_or_open_note(note_id, &db, Default::default())
+      .await
+      .unwrap();
 
     {
       let awareness = state.doc.lock().await;
@@ -808,7 +927,10 @@ mod note_editing_test {
 
     let storage = crate::storage::test::init_test_storage().await;
     let editing = NoteEditing::init_test(storage).await;
-    let state = editing.get_or_open_note(note_id, &db).await.unwrap();
+    let state = editing
+      .get_or_open_note(note_id, &db, Default::default())
+      .await
+      .unwrap();
 
     {
       let awareness = state.doc.lock().await;
@@ -896,7 +1018,10 @@ mod note_editing_test {
 
     let storage = crate::storage::test::init_test_storage().await;
     let editing = NoteEditing::init_test(storage).await;
-    let state = editing.get_or_open_note(note_id, &db).await.unwrap();
+    let state = editing
+      .get_or_open_note(note_id, &db, Default::default())
+      .await
+      .unwrap();
 
     // build an update from an independent doc and apply it through the entry point
     let other = Doc::new();
@@ -922,7 +1047,10 @@ mod note_editing_test {
 
     let storage = crate::storage::test::init_test_storage().await;
     let editing = NoteEditing::init_test(storage).await;
-    let _state = editing.get_or_open_note(note_id, &db).await.unwrap();
+    let _state = editing
+      .get_or_open_note(note_id, &db, Default::default())
+      .await
+      .unwrap();
 
     // an opened doc actually decodes the payload -> garbage bytes error
     assert!(editing.apply_update(note_id, b"garbage").await.is_err());
@@ -953,8 +1081,11 @@ mod note_editing_test {
 
     let storage = crate::storage::test::init_test_storage().await;
     let (update_state, updater) = UpdateState::<UpdateMessage>::init().await;
-    let editing = NoteEditing::init(storage, updater);
-    let state = editing.get_or_open_note(note_id, &db).await.unwrap();
+    let editing = NoteEditing::init(storage, updater, Default::default());
+    let state = editing
+      .get_or_open_note(note_id, &db, Default::default())
+      .await
+      .unwrap();
 
     let (_owner_sid, mut owner_rx) = update_state.create_session(owner).await;
     let (_shared_sid, mut shared_rx) = update_state.create_session(shared).await;
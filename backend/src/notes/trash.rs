@@ -0,0 +1,301 @@
+use std::{sync::Arc, time::Duration};
+
+use aide::axum::{
+  ApiRouter,
+  routing::{delete_with, get_with, put_with},
+};
+use axum::{Extension, Json};
+use centaurus::{
+  backend::auth::jwt_auth::JwtAuth, bail, db::init::Connection, error::Result, storage::FileStorage,
+};
+use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::{spawn, task::JoinHandle, time::sleep};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+  config::Config,
+  db::{DBTrait, notes::TrashedNoteInfo},
+  notes::{NotesLimits, delete_storage_for_note, management::notify_note_update},
+  utils::{UpdateMessage, Updater},
+};
+
+pub fn router() -> ApiRouter {
+  ApiRouter::new()
+    .api_route("/", get_with(list, |op| op.id("listTrashedNotes")))
+    .api_route("/", delete_with(purge, |op| op.id("purgeNote")))
+    .api_route("/restore", put_with(restore, |op| op.id("restoreNote")))
+}
+
+#[derive(Clone)]
+pub struct TrashSettings {
+  pub retention: TimeDelta,
+}
+
+impl TrashSettings {
+  pub fn from_config(config: &Config) -> Self {
+    Self {
+      retention: TimeDelta::days(config.notes_trash_retention_days),
+    }
+  }
+}
+
+#[derive(Clone)]
+pub struct TrashPurge {
+  _handle: Arc<JoinHandle<()>>,
+}
+
+impl TrashPurge {
+  pub fn init(
+    db: Connection,
+    storage: FileStorage,
+    updater: Updater,
+    settings: TrashSettings,
+  ) -> Self {
+    let handle = spawn(async move {
+      loop {
+        let cutoff = Utc::now() - settings.retention;
+        if let Err(err) = run_purge(&db, &storage, &updater, cutoff).await {
+          warn!(?err, "note trash purge failed");
+        }
+        sleep(Duration::from_secs(3600)).await;
+      }
+    });
+
+    Self {
+      _handle: Arc::new(handle),
+    }
+  }
+}
+
+async fn run_purge(
+  db: &Connection,
+  storage: &FileStorage,
+  updater: &Updater,
+  cutoff: DateTime<Utc>,
+) -> Result<()> {
+  for (note_id, owner) in db.notes().trashed_before(cutoff.naive_utc()).await? {
+    if let Err(err) = purge_note(db, storage, note_id).await {
+      warn!(?err, %note_id, "failed to purge trashed note");
+      continue;
+    }
+    updater
+      .send_to(owner, UpdateMessage::Note { uuid: note_id })
+      .await;
+  }
+  Ok(())
+}
+
+async fn purge_note(db: &Connection, storage: &FileStorage, note_id: Uuid) -> Result<()> {
+  delete_storage_for_note(db, storage, note_id).await?;
+  db.notes().delete(note_id).await
+}
+
+#[derive(Serialize, JsonSchema)]
+struct TrashedNote {
+  #[serde(flatten)]
+  info: TrashedNoteInfo,
+  /// When the note is removed for good.
+  purge_at: NaiveDateTime,
+}
+
+async fn list(
+  auth: JwtAuth,
+  db: Connection,
+  Extension(settings): Extension<TrashSettings>,
+) -> Result<Json<Vec<TrashedNote>>> {
+  let notes = db.notes().list_trash(auth.user_id).await?;
+  Ok(Json(
+    notes
+      .into_iter()
+      .map(|info| TrashedNote {
+        purge_at: info.deleted_at + settings.retention,
+        info,
+      })
+      .collect(),
+  ))
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct TrashedNoteReq {
+  note_id: Uuid,
+}
+
+async fn require_trashed(auth: &JwtAuth, db: &Connection, note_id: Uuid) -> Result<()> {
+  if !db.notes().is_owner(auth.user_id, note_id).await? {
+    bail!(FORBIDDEN, "forbidden");
+  }
+  if !db.notes().is_trashed(note_id).await? {
+    bail!(NOT_FOUND, "note not in trash");
+  }
+  Ok(())
+}
+
+async fn restore(
+  auth: JwtAuth,
+  db: Connection,
+  updater: Updater,
+  Extension(limits): Extension<NotesLimits>,
+  Json(req): Json<TrashedNoteReq>,
+) -> Result<()> {
+  require_trashed(&auth, &db, req.note_id).await?;
+  if db.notes().count_owned(auth.user_id).await? >= limits.max_per_user as u64 {
+    bail!(CONFLICT, "note limit reached");
+  }
+
+  db.notes().restore(req.note_id).await?;
+
+  let mut users = db.notes().shared_user_ids(req.note_id).await?;
+  users.push(auth.user_id);
+  notify_note_update(&updater, users, req.note_id).await;
+
+  Ok(())
+}
+
+/// Deletes a trashed note and its snapshots right away.
+async fn purge(
+  auth: JwtAuth,
+  db: Connection,
+  storage: FileStorage,
+  updater: Updater,
+  Json(req): Json<TrashedNoteReq>,
+) -> Result<()> {
+  require_trashed(&auth, &db, req.note_id).await?;
+
+  purge_note(&db, &storage, req.note_id).await?;
+  notify_note_update(&updater, vec![auth.user_id], req.note_id).await;
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use axum::{
+    Extension, Router,
+    body::Body,
+    http::{Request, StatusCode, header},
+    routing::get,
+  };
+  use centaurus::{backend::auth::jwt_state::JwtState, db::init::Connection, storage::FileStorage};
+  use chrono::{TimeDelta, Utc};
+  use serde_json::{Value, json};
+  use tower::ServiceExt;
+
+  use super::TrashSettings;
+  use crate::{
+    db::{
+      DBTrait,
+      test::{auth_cookie, auth_state, body_json, insert_user, test_db, updater},
+    },
+    notes::NotesLimits,
+    storage::StorageExt,
+  };
+
+  fn app(db: Connection, jwt: JwtState, storage: FileStorage, max_per_user: u32) -> Router {
+    Router::new()
+      .route("/", get(super::list).delete(super::purge))
+      .route("/restore", axum::routing::put(super::restore))
+      .layer(Extension(TrashSettings {
+        retention: TimeDelta::days(30),
+      }))
+      .layer(Extension(NotesLimits { max_per_user }))
+      .layer(Extension(storage))
+      .layer(Extension(jwt))
+      .layer(Extension(db))
+  }
+
+  async fn send(app: Router, method: &str, uri: &str, cookie: &str, body: Value) -> StatusCode {
+    let req = Request::builder()
+      .method(method)
+      .uri(uri)
+      .header(header::COOKIE, cookie)
+      .header(header::CONTENT_TYPE, "application/json")
+      .body(Body::from(body.to_string()))
+      .unwrap();
+    app.oneshot(req).await.unwrap().status()
+  }
+
+  #[tokio::test]
+  async fn restore_respects_limit_and_purge_removes_snapshots() {
+    let db = test_db().await;
+    let jwt = auth_state(&db).await;
+    let upd = updater().await;
+    let storage = crate::storage::test::init_test_storage().await;
+    let user = insert_user(&db, "owner", "owner@x.com").await;
+    let cookie = auth_cookie(&db, &jwt, user).await;
+    let trashed = db.notes().create(user, "Old".into()).await.unwrap();
+    let snapshot = db
+      .note_snapshot()
+      .create(trashed, "p".into())
+      .await
+      .unwrap();
+    storage
+      .note_snapshot()
+      .create(trashed, snapshot, b"s")
+      .await
+      .unwrap();
+    db.notes().trash(trashed).await.unwrap();
+    db.notes().create(user, "New".into()).await.unwrap();
+    let app = app(db.clone(), jwt, storage.clone(), 1).layer(Extension(upd));
+
+    let resp = app
+      .clone()
+      .oneshot(
+        Request::builder()
+          .uri("/")
+          .header(header::COOKIE, &cookie)
+          .body(Body::empty())
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+    let body: Value = body_json(resp).await;
+    assert_eq!(body[0]["id"], json!(trashed));
+    assert!(body[0]["purge_at"].is_string());
+
+    let req = json!({ "note_id": trashed });
+    assert_eq!(
+      send(app.clone(), "PUT", "/restore", &cookie, req.clone()).await,
+      StatusCode::CONFLICT
+    );
+    assert_eq!(
+      send(app.clone(), "DELETE", "/", &cookie, req.clone()).await,
+      StatusCode::OK
+    );
+    assert!(db.notes().content(trashed).await.unwrap().is_none());
+    assert!(
+      !storage
+        .note_snapshot()
+        .exists(trashed, snapshot)
+        .await
+        .unwrap()
+    );
+    assert_eq!(
+      send(app, "PUT", "/restore", &cookie, req).await,
+      StatusCode::FORBIDDEN
+    );
+  }
+
+  #[tokio::test]
+  async fn purge_job_only_removes_expired_notes() {
+    let db = test_db().await;
+    let upd = updater().await;
+    let storage = crate::storage::test::init_test_storage().await;
+    let user = insert_user(&db, "owner", "owner@x.com").await;
+    let recent = db.notes().create(user, "Recent".into()).await.unwrap();
+    let kept = db.notes().create(user, "Kept".into()).await.unwrap();
+    db.notes().trash(recent).await.unwrap();
+
+    let cutoff = Utc::now() - TimeDelta::days(30);
+    super::run_purge(&db, &storage, &upd, cutoff).await.unwrap();
+    assert!(db.notes().is_trashed(recent).await.unwrap());
+
+    super::run_purge(&db, &storage, &upd, Utc::now() + TimeDelta::seconds(1))
+      .await
+      .unwrap();
+    assert!(db.notes().content(recent).await.unwrap().is_none());
+    assert!(db.notes().content(kept).await.unwrap().is_some());
+  }
+}
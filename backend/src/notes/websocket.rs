@@ -14,7 +14,10 @@ use serde::Deserialize;
 use uuid::Uuid;
 use yrs::ClientID;
 
-use crate::{db::DBTrait, notes::state::NoteEditing};
+use crate::{
+  db::DBTrait,
+  notes::{mentions::MentionMail, state::NoteEditing},
+};
 
 pub fn router() -> ApiRouter {
   ApiRouter::new()
@@ -30,6 +33,7 @@ struct NotePath {
 async fn notes_websocket(
   auth: JwtAuth,
   state: NoteEditing,
+  mail: MentionMail,
   Path(NotePath { uuid }): Path<NotePath>,
   db: Connection,
   ws: WebSocketUpgrade,
@@ -40,11 +44,12 @@ async fn notes_websocket(
 
   let can_edit = db.notes().can_edit(auth.user_id, uuid).await?;
 
-  Ok(ws.on_upgrade(move |ws| handle_socket(ws, state, db, uuid, can_edit)))
+  Ok(ws.on_upgrade(move |ws| handle_socket(ws, state, mail, db, uuid, can_edit)))
 }
 
 async fn public_share_websocket(
   state: NoteEditing,
+  mail: MentionMail,
   Path(NotePath { uuid }): Path<NotePath>,
   db: Connection,
   ws: WebSocketUpgrade,
@@ -54,17 +59,18 @@ async fn public_share_websocket(
   };
 
   let can_edit = access == NoteShareAccess::Edit;
-  Ok(ws.on_upgrade(move |ws| handle_socket(ws, state, db, uuid, can_edit)))
+  Ok(ws.on_upgrade(move |ws| handle_socket(ws, state, mail, db, uuid, can_edit)))
 }
 
 async fn handle_socket(
   mut ws: WebSocket,
   state: NoteEditing,
+  mail: MentionMail,
   db: Connection,
   note_id: Uuid,
   can_edit: bool,
 ) {
-  let doc_state = match state.get_or_open_note(note_id, &db).await {
+  let doc_state = match state.get_or_open_note(note_id, &db, mail).await {
     Ok(arc) => arc,
     Err(e) => {
       tracing::warn!("failed to get or open note: {}", e);
@@ -2,8 +2,9 @@ use std::{str::FromStr, time::Instant};
 
 use aide::axum::{ApiRouter, routing::post_with};
 use axum::{
-  Form, Json,
+  Extension, Form, Json,
   extract::{Path, Query},
+  middleware::from_fn,
   routing::{get, post},
 };
 use centaurus::{
@@ -17,7 +18,7 @@ use centaurus::{
   error::{ErrorReport, Result},
   serde::empty_string_as_none,
 };
-use entity::o_auth_client;
+use entity::{o_auth_client, sea_orm_active_enums::AuthLevel};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use tracing::instrument;
@@ -25,6 +26,10 @@ use uuid::Uuid;
 use webauthn_rs::prelude::Url;
 
 use crate::{
+  auth::{
+    amr::{AuthMethods, acr, parse_acr},
+    session_auth::{CurrentSession, deny_impersonation},
+  },
   db::DBTrait,
   oauth::state::{CodeChallenge, CodeChallengeMethod},
 };
@@ -42,30 +47,41 @@ pub fn router() -> ApiRouter {
       "/authorize_confirm",
       post_with(authorize_confirm, |op| op.id("authorizeConfirm")),
     )
+    // an impersonation session must not be handed to a client
+    .route_layer(from_fn(deny_impersonation))
     .route("/logout/{client_id}", get(logout))
 }
 
 async fn authorize_get(
   auth: Option<JwtAuth>,
+  session: Option<Extension<CurrentSession>>,
   Query(req): Query<AuthReq>,
   state: AuthorizeState,
   db: Connection,
 ) -> Result<Redirect> {
-  authorize_start(auth, req, state, db).await
+  let methods = session
+    .map(|Extension(s)| s.auth_methods)
+    .unwrap_or_default();
+  authorize_start(auth, methods, req, state, db).await
 }
 
 async fn authorize_post(
   auth: Option<JwtAuth>,
+  session: Option<Extension<CurrentSession>>,
   state: AuthorizeState,
   db: Connection,
   Form(req): Form<AuthReq>,
 ) -> Result<Redirect> {
-  authorize_start(auth, req, state, db).await
+  let methods = session
+    .map(|Extension(s)| s.auth_methods)
+    .unwrap_or_default();
+  authorize_start(auth, methods, req, state, db).await
 }
 
 #[instrument(skip(state, db, auth))]
 async fn authorize_start(
   auth: Option<JwtAuth>,
+  methods: AuthMethods,
   req: AuthReq,
   state: AuthorizeState,
   db: Connection,
@@ -82,13 +98,18 @@ async fn authorize_start(
     bail!("Invalid code challenge length: {}", challenge.len());
   }
 
+  let client = db.oauth_client().get_client(client_id).await?;
+  let required = required_level(&client, &req);
+
+  // sessions below the required level have to sign in again first
   let fast_url = if let Some(auth) = &auth
+    && required.is_none_or(|level| methods.level() >= level)
     && let Ok(conf) = <Connection as DBTrait>::settings(&db)
       .get(auth.user_id)
       .await
     && conf.o_auth_instant_confirm
   {
-    auth_redirect(req.clone(), &db, auth, &state)
+    auth_redirect(req.clone(), &db, auth, &methods, &state)
       .await
       .map(|(url, _)| url)
       .ok()
@@ -99,16 +120,18 @@ async fn authorize_start(
   let url = if let Some(fast_url) = fast_url {
     fast_url
   } else {
-    let client = db.oauth_client().get_client(client_id).await?;
-
     state.auth_pending.insert(uuid, (Instant::now(), req));
 
-    // unwrap is safe because the URL is constructed from a trusted base and query parameters are properly encoded
-    Url::from_str(&format!(
+    let mut login = format!(
       "{}login?code={}&name={}",
       state.frontend_url, uuid, client.name,
-    ))
-    .unwrap()
+    );
+    if let Some(level) = required {
+      login.push_str(&format!("&acr={}", acr(level)));
+    }
+
+    // unwrap is safe because the URL is constructed from a trusted base and query parameters are properly encoded
+    Url::from_str(&login).unwrap()
   };
 
   Ok(Redirect::found(url.to_string()))
@@ -128,6 +151,7 @@ struct AuthConfirmQuery {
 
 async fn authorize_confirm(
   auth: JwtAuth,
+  Extension(session): Extension<CurrentSession>,
   state: AuthorizeState,
   db: Connection,
   Query(query): Query<AuthConfirmQuery>,
@@ -144,7 +168,7 @@ async fn authorize_confirm(
     bail!("authorization request not found")
   };
 
-  let (url, client_name) = auth_redirect(data, &db, &auth, &state).await?;
+  let (url, client_name) = auth_redirect(data, &db, &auth, &session.auth_methods, &state).await?;
   state.auth_pending.remove(&query.code);
 
   tracing::info!("User {} logged in to {}", auth.user_id, client_name);
@@ -157,6 +181,7 @@ async fn auth_redirect(
   mut data: AuthReq,
   db: &Connection,
   auth: &JwtAuth,
+  methods: &AuthMethods,
   state: &AuthorizeState,
 ) -> Result<(Url, String)> {
   let client_id = data.client_id;
@@ -195,6 +220,23 @@ async fn auth_redirect(
     ));
   }
 
+  if let Some(level) = required_level(&client, &data)
+    && methods.level() < level
+  {
+    tracing::warn!(
+      "User {} did not meet the required authentication level {:?}",
+      auth.user_id,
+      level
+    );
+    return Ok((
+      Url::parse(&format!(
+        "{}?error=unmet_authentication_requirements",
+        client.redirect_uri
+      ))?,
+      client.name,
+    ));
+  }
+
   let auth_code = Uuid::new_v4();
   state.auth_codes.insert(
     auth_code,
@@ -220,6 +262,7 @@ async fn auth_redirect(
             })
             .unwrap_or(CodeChallengeMethod::Plain),
         }),
+        auth_methods: methods.clone(),
       },
     ),
   );
@@ -234,6 +277,16 @@ async fn auth_redirect(
   Ok((url, client.name))
 }
 
+/// The stricter of the client requirement and the weakest recognized
+/// `acr_values` entry the request asked for.
+fn required_level(client: &o_auth_client::Model, req: &AuthReq) -> Option<AuthLevel> {
+  let requested = req
+    .acr_values
+    .as_deref()
+    .and_then(|values| values.split_whitespace().filter_map(parse_acr).min());
+  client.required_acr.max(requested)
+}
+
 #[instrument]
 fn validate_req(
   req: &mut AuthReq,
@@ -323,8 +376,8 @@ async fn logout(
 
 #[cfg(test)]
 mod test {
-  use super::{AuthReq, Scope, validate_req};
-  use entity::o_auth_client;
+  use super::{AuthReq, Scope, required_level, validate_req};
+  use entity::{o_auth_client, sea_orm_active_enums::AuthLevel};
   use uuid::Uuid;
   use webauthn_rs::prelude::Url;
 
@@ -339,6 +392,7 @@ mod test {
       salt: "salt".into(),
       confidential,
       require_pkce,
+      required_acr: None,
     }
   }
 
@@ -352,6 +406,7 @@ mod test {
       nonce: None,
       code_challenge: Some("a".repeat(43)),
       code_challenge_method: None,
+      acr_values: None,
     }
   }
 
@@ -431,6 +486,21 @@ mod test {
     assert!(validate_req(&mut r, &client(true, false), vec![], default_scope()).is_ok());
   }
 
+  #[test]
+  fn required_level_combines_client_and_acr_values() {
+    let mut c = client(true, false);
+    let mut r = req();
+    assert_eq!(required_level(&c, &r), None);
+
+    // unknown values are ignored, otherwise the weakest requested level counts
+    r.acr_values = Some("urn:unknown passkey mfa".into());
+    assert_eq!(required_level(&c, &r), Some(AuthLevel::Mfa));
+
+    // the client requirement cannot be lowered by the request
+    c.required_acr = Some(AuthLevel::Passkey);
+    assert_eq!(required_level(&c, &r), Some(AuthLevel::Passkey));
+  }
+
   #[test]
   fn invalid_code_challenge_method() {
     let mut r = req();
@@ -471,6 +541,7 @@ mod test {
 
   mod handlers {
     use super::super::{authorize_start, logout};
+    use crate::auth::amr::AuthMethods;
     use crate::{
       config::Config,
       db::{DBTrait, test::test_db},
@@ -491,6 +562,7 @@ mod test {
           salt: "salt".into(),
           confidential: true,
           require_pkce: false,
+          required_acr: None,
         })
         .await
         .unwrap();
@@ -507,6 +579,7 @@ mod test {
         nonce: None,
         code_challenge: None,
         code_challenge_method: None,
+        acr_values: None,
       }
     }
 
@@ -517,7 +590,14 @@ mod test {
       let state = AuthorizeState::init(&Config::default());
 
       assert!(state.auth_pending.is_empty());
-      let res = authorize_start(None, auth_req(client_id), state.clone(), db).await;
+      let res = authorize_start(
+        None,
+        AuthMethods::default(),
+        auth_req(client_id),
+        state.clone(),
+        db,
+      )
+      .await;
       assert!(res.is_ok());
       assert_eq!(state.auth_pending.len(), 1);
     }
@@ -527,7 +607,14 @@ mod test {
       let db = test_db().await;
       let state = AuthorizeState::init(&Config::default());
       // no client inserted -> get_client fails
-      let res = authorize_start(None, auth_req(Uuid::new_v4()), state, db).await;
+      let res = authorize_start(
+        None,
+        AuthMethods::default(),
+        auth_req(Uuid::new_v4()),
+        state,
+        db,
+      )
+      .await;
       assert!(res.is_err());
     }
 
@@ -539,7 +626,11 @@ mod test {
 
       let mut req = auth_req(client_id);
       req.code_challenge = Some("too-short".into()); // < 43 chars
-      assert!(authorize_start(None, req, state, db).await.is_err());
+      assert!(
+        authorize_start(None, AuthMethods::default(), req, state, db)
+          .await
+          .is_err()
+      );
     }
 
     #[tokio::test]
@@ -551,7 +642,11 @@ mod test {
       let mut req = auth_req(client_id);
       // 43 chars but contains a space (not URL-safe)
       req.code_challenge = Some(format!(" {}", "a".repeat(42)));
-      assert!(authorize_start(None, req, state, db).await.is_err());
+      assert!(
+        authorize_start(None, AuthMethods::default(), req, state, db)
+          .await
+          .is_err()
+      );
     }
 
     #[tokio::test]
@@ -562,7 +657,11 @@ mod test {
 
       let mut req = auth_req(client_id);
       req.code_challenge = Some("a".repeat(43));
-      assert!(authorize_start(None, req, state, db).await.is_ok());
+      assert!(
+        authorize_start(None, AuthMethods::default(), req, state, db)
+          .await
+          .is_ok()
+      );
     }
 
     #[tokio::test]
@@ -583,10 +682,11 @@ mod test {
 
   mod endpoints {
     use crate::{
+      auth::session_auth::deny_impersonation,
       config::Config,
       db::{
         DBTrait,
-        test::{auth_cookie, auth_state, body_json, insert_user, test_db},
+        test::{auth_cookie, auth_state, body_json, impersonation_cookie, insert_user, test_db},
         user::settings::SettingsInfo,
       },
       oauth::state::{AuthReq, AuthorizeState},
@@ -595,6 +695,7 @@ mod test {
       Extension, Router,
       body::Body,
       http::{Request, StatusCode, header},
+      middleware::from_fn,
       routing::{get, post},
     };
     use centaurus::{backend::auth::jwt_state::JwtState, db::init::Connection};
@@ -623,6 +724,7 @@ mod test {
           salt: "salt".into(),
           confidential: true,
           require_pkce: false,
+          required_acr: None,
         })
         .await
         .unwrap();
@@ -631,6 +733,7 @@ mod test {
           client_id,
           "App".into(),
           false,
+          None,
           REDIRECT.into(),
           vec![],
           vec![scope],
@@ -652,6 +755,7 @@ mod test {
         nonce: None,
         code_challenge: None,
         code_challenge_method: None,
+        acr_values: None,
       }
     }
 
@@ -675,11 +779,53 @@ mod test {
           get(super::super::authorize_get).post(super::super::authorize_post),
         )
         .route("/authorize_confirm", post(super::super::authorize_confirm))
+        .route_layer(from_fn(deny_impersonation))
         .layer(Extension(state))
         .layer(Extension(jwt))
         .layer(Extension(db))
     }
 
+    #[tokio::test]
+    async fn impersonation_session_cannot_authorize_clients() {
+      let db = test_db().await;
+      let jwt = auth_state(&db).await;
+      let admin = insert_user(&db, "admin", "admin@x.com").await;
+      let user = insert_user(&db, "u", "u@x.com").await;
+      let cookie = impersonation_cookie(&db, &jwt, user, admin).await;
+      let client_id = setup_client(&db, user, true).await;
+      enable_instant_confirm(&db, user).await;
+
+      let state = AuthorizeState::init(&Config::default());
+      let code = Uuid::new_v4();
+      state
+        .auth_pending
+        .insert(code, (Instant::now(), auth_req(client_id)));
+      let app = app(db, jwt, state.clone());
+
+      for (method, uri) in [
+        (
+          "GET",
+          format!("/authorize?response_type=code&client_id={client_id}"),
+        ),
+        ("POST", format!("/authorize_confirm?code={code}&allow=true")),
+      ] {
+        let resp = app
+          .clone()
+          .oneshot(
+            Request::builder()
+              .method(method)
+              .uri(&uri)
+              .header(header::COOKIE, &cookie)
+              .body(Body::empty())
+              .unwrap(),
+          )
+          .await
+          .unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN, "{uri}");
+      }
+      assert!(state.auth_pending.contains_key(&code));
+    }
+
     #[tokio::test]
     async fn authorize_confirm_allow_issues_code() {
       let db = test_db().await;
@@ -777,6 +923,43 @@ mod test {
       assert!(state.auth_pending.is_empty());
     }
 
+    #[tokio::test]
+    async fn authorize_confirm_with_unmet_acr_redirects_with_error() {
+      let db = test_db().await;
+      let jwt = auth_state(&db).await;
+      let user = insert_user(&db, "u", "u@x.com").await;
+      let cookie = auth_cookie(&db, &jwt, user).await;
+      let client_id = setup_client(&db, user, true).await;
+
+      let state = AuthorizeState::init(&Config::default());
+      let code = Uuid::new_v4();
+      let mut req = auth_req(client_id);
+      req.acr_values = Some("passkey".into());
+      state.auth_pending.insert(code, (Instant::now(), req));
+
+      let resp = app(db, jwt, state.clone())
+        .oneshot(
+          Request::builder()
+            .method("POST")
+            .uri(format!("/authorize_confirm?code={code}&allow=true"))
+            .header(header::COOKIE, &cookie)
+            .body(Body::empty())
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+      assert_eq!(resp.status(), StatusCode::OK);
+      let location = body_json(resp).await["location"]
+        .as_str()
+        .unwrap()
+        .to_string();
+      assert!(
+        location.contains("error=unmet_authentication_requirements"),
+        "location was {location}"
+      );
+      assert!(state.auth_codes.is_empty());
+    }
+
     #[tokio::test]
     async fn authorize_confirm_without_access_is_unauthorized() {
       let db = test_db().await;
@@ -891,6 +1074,43 @@ mod test {
       assert!(state.auth_pending.is_empty());
     }
 
+    #[tokio::test]
+    async fn authorize_get_below_requested_acr_asks_for_step_up() {
+      let db = test_db().await;
+      let jwt = auth_state(&db).await;
+      let user = insert_user(&db, "u", "u@x.com").await;
+      let cookie = auth_cookie(&db, &jwt, user).await;
+      let client_id = setup_client(&db, user, true).await;
+      enable_instant_confirm(&db, user).await;
+      let state = AuthorizeState::init(&Config::default());
+
+      let resp = app(db, jwt, state.clone())
+        .oneshot(
+          Request::builder()
+            .uri(format!(
+              "/authorize?response_type=code&client_id={client_id}&acr_values=mfa"
+            ))
+            .header(header::COOKIE, &cookie)
+            .body(Body::empty())
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+      assert_eq!(resp.status(), StatusCode::FOUND);
+      let location = resp
+        .headers()
+        .get(header::LOCATION)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+      // the password-only session skips the fast track and the login page is
+      // told which level to reach
+      assert!(location.contains("login?code="), "location was {location}");
+      assert!(location.contains("acr=mfa"), "location was {location}");
+      assert_eq!(state.auth_pending.len(), 1);
+    }
+
     #[tokio::test]
     async fn authorize_get_with_session_without_access_falls_back_to_login() {
       let db = test_db().await;
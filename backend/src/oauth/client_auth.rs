@@ -301,6 +301,7 @@ mod test {
           salt: SALT.into(),
           confidential,
           require_pkce: false,
+          required_acr: None,
         })
         .await
         .unwrap();
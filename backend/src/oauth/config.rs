@@ -1,9 +1,10 @@
 use axum::{Json, Router, routing::get};
 use centaurus::{db::init::Connection, error::Result};
+use entity::sea_orm_active_enums::AuthLevel;
 use serde::Serialize;
 use tracing::instrument;
 
-use crate::db::DBTrait;
+use crate::{auth::amr::acr, db::DBTrait};
 
 use super::state::ConfigurationState;
 
@@ -27,6 +28,7 @@ struct Configuration {
   token_endpoint_auth_methods_supported: Vec<String>,
   scopes_supported: Vec<String>,
   claims_supported: Vec<String>,
+  acr_values_supported: Vec<String>,
 }
 
 #[instrument(skip(state, db))]
@@ -58,6 +60,8 @@ async fn config(state: ConfigurationState, db: Connection) -> Result<Json<Config
       "exp",
       "iat",
       "auth_time",
+      "amr",
+      "acr",
       "nonce",
       "email",
       "name",
@@ -67,6 +71,10 @@ async fn config(state: ConfigurationState, db: Connection) -> Result<Json<Config
     .into_iter()
     .map(|s| s.to_string())
     .collect(),
+    acr_values_supported: [AuthLevel::Basic, AuthLevel::Mfa, AuthLevel::Passkey]
+      .into_iter()
+      .map(|level| acr(level).to_string())
+      .collect(),
   }))
 }
 
@@ -101,6 +109,7 @@ mod test {
     );
     assert!(cfg.scopes_supported.contains(&"openid".to_string()));
     assert!(cfg.claims_supported.contains(&"sub".to_string()));
+    assert_eq!(cfg.acr_values_supported, ["basic", "mfa", "passkey"]);
   }
 
   #[tokio::test]
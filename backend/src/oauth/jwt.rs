@@ -25,6 +25,10 @@ pub struct OAuthClaims {
   pub aud: Uuid,
   pub iat: i64,
   pub auth_time: i64,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub amr: Vec<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub acr: Option<String>,
   #[serde(skip_serializing_if = "Option::is_none")]
   pub nonce: Option<String>,
   pub scope: Scope,
@@ -74,6 +78,14 @@ pub struct RefreshTokenClaims {
   pub aud: Uuid,
   pub scope: Scope,
   pub nonce: Option<String>,
+  /// Tokens from before this field existed count as issued at the epoch.
+  #[serde(default)]
+  pub iat: i64,
+  /// How the user authenticated when the client was authorized.
+  #[serde(default)]
+  pub amr: Vec<String>,
+  #[serde(default)]
+  pub acr: Option<String>,
 }
 
 #[cfg(test)]
@@ -90,6 +102,8 @@ mod test {
       aud: Uuid::new_v4(),
       iat: 1,
       auth_time: 1,
+      amr: vec![],
+      acr: None,
       nonce: None,
       scope: vec!["openid".to_string()].into(),
       email: None,
@@ -150,6 +164,9 @@ mod test {
       aud: Uuid::new_v4(),
       scope: vec!["openid".to_string(), "email".to_string()].into(),
       nonce: Some("n".into()),
+      iat: 42,
+      amr: vec![],
+      acr: None,
     };
     let json = serde_json::to_string(&original).unwrap();
     let back: RefreshTokenClaims = serde_json::from_str(&json).unwrap();
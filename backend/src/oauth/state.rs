@@ -10,7 +10,7 @@ use tokio::spawn;
 use url::Url;
 use uuid::Uuid;
 
-use crate::config::Config;
+use crate::{auth::amr::AuthMethods, config::Config};
 
 use super::scope::Scope;
 
@@ -30,6 +30,8 @@ pub struct AuthReq {
   pub code_challenge: Option<String>,
   #[serde(default, deserialize_with = "empty_string_as_none")]
   pub code_challenge_method: Option<String>,
+  #[serde(default, deserialize_with = "empty_string_as_none")]
+  pub acr_values: Option<String>,
 }
 
 pub struct CodeReq {
@@ -39,6 +41,7 @@ pub struct CodeReq {
   pub user: Uuid,
   pub nonce: Option<String>,
   pub code_challenge: Option<CodeChallenge>,
+  pub auth_methods: AuthMethods,
 }
 
 pub struct CodeChallenge {
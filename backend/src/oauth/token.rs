@@ -17,7 +17,7 @@ use tracing::instrument;
 use uuid::Uuid;
 
 use crate::{
-  auth::jwt::JwtStateOther,
+  auth::{amr::acr, jwt::JwtStateOther},
   db::DBTrait,
   oauth::{
     client_auth::{TokenIssueReq, TokenRefreshReq},
@@ -185,6 +185,9 @@ async fn issue_token(
     nonce: code_info.nonce,
     exp,
     iss: config.issuer.clone().to_string(),
+    iat: Utc::now().timestamp(),
+    amr: code_info.auth_methods.amr(),
+    acr: Some(acr(code_info.auth_methods.level()).to_string()),
   };
 
   let token = create_access_token(&db, &jwt, &code_info, &config, client_id).await?;
@@ -246,6 +249,19 @@ async fn refresh_token(
     return Err(Error::from_str("invalid_client"));
   }
 
+  // suspending a user revokes every refresh token issued before it
+  let Ok(user) = db.user_ext().get_user_by_id(claims.sub).await else {
+    tracing::warn!("user not found: {}", claims.sub);
+    return Err(Error::from_str("invalid_grant"));
+  };
+  if user
+    .tokens_revoked_at
+    .is_some_and(|revoked_at| claims.iat <= revoked_at.and_utc().timestamp())
+  {
+    tracing::warn!("revoked refresh token for user: {}", claims.sub);
+    return Err(Error::from_str("invalid_grant"));
+  }
+
   let token = create_access_token(&db, &jwt, &claims, &config, client_id).await?;
 
   let exp = Utc::now()
@@ -254,6 +270,7 @@ async fn refresh_token(
     .timestamp();
 
   claims.exp = exp;
+  claims.iat = Utc::now().timestamp();
 
   let Ok(refresh_token) = jwt.create_generic_token(&claims) else {
     tracing::warn!("failed to create refresh token for client: {}", client_id);
@@ -287,12 +304,16 @@ async fn create_access_token(
     tracing::warn!("user not found: {}", code_info.sub);
     return Err(Error::from_str("unauthorized_client"));
   };
-  let Ok(groups) = db.user().get_user_groups(user.id).await else {
+  if !matches!(db.user_ext().suspension(user.id).await, Ok(None)) {
+    tracing::warn!("refusing token for suspended user: {}", user.id);
+    return Err(Error::from_str("invalid_grant"));
+  }
+  let Ok(groups) = db.group_ext().effective_groups(user.id).await else {
     tracing::warn!("failed to get groups for user: {}", user.id);
     return Err(Error::from_str("unauthorized_client"));
   };
 
-  let group_ids: Vec<Uuid> = groups.iter().map(|g| g.uuid).collect();
+  let group_ids: Vec<Uuid> = groups.iter().map(|g| g.id).collect();
   let Ok(rest) = db
     .oauth_scope()
     .get_values_for_user(code_info.scope.inner(), &group_ids)
@@ -332,6 +353,8 @@ async fn create_access_token(
     aud: code_info.aud,
     iat: time,
     auth_time: time,
+    amr: code_info.amr.clone(),
+    acr: code_info.acr.clone(),
     nonce: code_info.nonce.clone(),
     scope: code_info.scope.clone(),
     email,
@@ -405,8 +428,12 @@ async fn revoke(
 mod test {
   use super::{RevokeReqOption, create_access_token, issue_token, refresh_token, revoke, token};
   use crate::{
+    auth::amr::{AuthMethod, AuthMethods},
     config::Config,
-    db::test::{insert_user, jwt_state, test_db},
+    db::{
+      DBTrait,
+      test::{insert_user, jwt_state, test_db},
+    },
     oauth::{
       client_auth::{ClientAuth, Error, TokenIssueReq, TokenRefreshReq, TokenReq},
       jwt::{OAuthClaims, RefreshTokenClaims},
@@ -448,6 +475,7 @@ mod test {
       user,
       nonce: None,
       code_challenge: None,
+      auth_methods: AuthMethods::default(),
     }
   }
 
@@ -540,6 +568,33 @@ mod test {
     assert!(res.id_token.is_none());
   }
 
+  #[tokio::test]
+  async fn issue_token_carries_authentication_methods() {
+    let c = ctx().await;
+    let code = Uuid::new_v4();
+    let mut req = code_req(c.client_id, c.user, &["openid"]);
+    req.auth_methods = AuthMethods::new(&[AuthMethod::Password, AuthMethod::Otp]);
+    c.state.auth_codes.insert(code, (Instant::now(), req));
+
+    let body = TokenIssueReq {
+      grant_type: "authorization_code".into(),
+      code,
+      redirect_uri: None,
+      code_verifier: None,
+    };
+    let jwt = c.jwt.clone();
+    let axum::Json(res) = issue_token(c.state, c.jwt, c.db, c.config, body, c.client_id)
+      .await
+      .unwrap();
+
+    let id_token: OAuthClaims = jwt.validate_token(&res.id_token.unwrap()).unwrap();
+    assert_eq!(id_token.amr, ["pwd", "otp", "mfa"]);
+    assert_eq!(id_token.acr.as_deref(), Some("mfa"));
+    // refreshed tokens keep the level of the original sign in
+    let refresh: RefreshTokenClaims = jwt.validate_token(&res.refresh_token).unwrap();
+    assert_eq!(refresh.acr.as_deref(), Some("mfa"));
+  }
+
   #[tokio::test]
   async fn issue_token_unknown_code_is_invalid_grant() {
     let c = ctx().await;
@@ -830,6 +885,9 @@ mod test {
         "image".to_string(),
       ]),
       nonce: None,
+      iat: 0,
+      amr: vec![],
+      acr: None,
     };
 
     let token = create_access_token(&c.db, &c.jwt, &claims, &c.config, c.client_id)
@@ -853,6 +911,9 @@ mod test {
       aud: c.client_id,
       scope: Scope::from(vec!["openid".to_string()]),
       nonce: None,
+      iat: 0,
+      amr: vec![],
+      acr: None,
     };
     let token = create_access_token(&c.db, &c.jwt, &claims, &c.config, c.client_id)
       .await
@@ -873,6 +934,9 @@ mod test {
       aud,
       scope: Scope::from(vec!["openid".to_string()]),
       nonce: None,
+      iat: chrono::Utc::now().timestamp(),
+      amr: vec![],
+      acr: None,
     }
   }
 
@@ -890,6 +954,38 @@ mod test {
     assert!(res.id_token.is_some());
   }
 
+  #[tokio::test]
+  async fn suspension_revokes_refresh_tokens() {
+    let c = ctx().await;
+    let claims = refresh_claims(c.client_id, c.user, c.config.issuer.to_string());
+    let rt = c.jwt.create_generic_token(&claims).unwrap();
+    c.db.user_ext().suspend(c.user, None, None).await.unwrap();
+
+    let body = TokenRefreshReq {
+      refresh_token: rt.clone(),
+    };
+    let err = refresh_token(
+      c.jwt.clone(),
+      c.db.clone(),
+      c.config.clone(),
+      body,
+      c.client_id,
+    )
+    .await
+    .map(|_| ())
+    .unwrap_err();
+    assert_eq!(err_code(err), "invalid_grant");
+
+    // resuming does not bring the old token back
+    c.db.user_ext().resume(c.user).await.unwrap();
+    let body = TokenRefreshReq { refresh_token: rt };
+    let err = refresh_token(c.jwt, c.db, c.config, body, c.client_id)
+      .await
+      .map(|_| ())
+      .unwrap_err();
+    assert_eq!(err_code(err), "invalid_grant");
+  }
+
   #[tokio::test]
   async fn refresh_token_invalid_token_is_invalid_grant() {
     let c = ctx().await;
@@ -975,6 +1071,8 @@ mod test {
       aud: c.client_id,
       iat: 0,
       auth_time: 0,
+      amr: vec![],
+      acr: None,
       nonce: None,
       scope: Scope::from(vec!["openid".to_string()]),
       email: None,
@@ -114,6 +114,8 @@ mod test {
       aud: Uuid::new_v4(),
       iat: 50,
       auth_time: 40,
+      amr: vec![],
+      acr: None,
       nonce: Some("nonce".into()),
       scope: vec!["openid".to_string()].into(),
       email: Some("e@x.com".into()),
@@ -16,7 +16,7 @@ use centaurus::{
   },
   error::Result,
 };
-use entity::o_auth_client;
+use entity::{o_auth_client, sea_orm_active_enums::AuthLevel};
 use rsa::rand_core::OsRng;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -109,6 +109,8 @@ struct ClientCreate {
   scope: Vec<Uuid>,
   confidential: bool,
   require_pkce: bool,
+  #[serde(default)]
+  required_acr: Option<AuthLevel>,
 }
 
 #[derive(Serialize, JsonSchema)]
@@ -147,6 +149,7 @@ async fn create(
       salt,
       confidential: req.confidential,
       require_pkce: req.require_pkce,
+      required_acr: req.required_acr,
     })
     .await?;
 
@@ -185,6 +188,8 @@ struct OAuthClientEditReq {
   client_id: Uuid,
   name: String,
   require_pkce: bool,
+  #[serde(default)]
+  required_acr: Option<AuthLevel>,
   redirect_uri: Url,
   additional_redirect_uris: Vec<Url>,
   scope: Vec<Uuid>,
@@ -211,6 +216,7 @@ async fn edit(
       req.client_id,
       req.name,
       req.require_pkce,
+      req.required_acr,
       req.redirect_uri.to_string(),
       req
         .additional_redirect_uris
@@ -1,12 +1,13 @@
-use aide::axum::{ApiRouter, routing::post_with};
+use aide::axum::{
+  ApiRouter,
+  routing::{get_with, post_with},
+};
 use argon2::password_hash::SaltString;
 use axum::Json;
 use axum_extra::extract::CookieJar;
 use centaurus::{
   backend::{
-    auth::jwt_state::JwtState,
-    auth::pw_state::PasswordState,
-    endpoints::setup::{get_oidc_settings_route, init_oidc_route, is_setup_route},
+    auth::jwt_state::JwtState, auth::pw_state::PasswordState, endpoints::setup::is_setup_route,
   },
   bail,
   db::{init::Connection, tables::ConnectionExt},
@@ -18,14 +19,28 @@ use serde::{Deserialize, Serialize};
 use tracing::info;
 use uuid::Uuid;
 
-use crate::auth::session_auth::{SessionMeta, create_session_cookie};
+use crate::{
+  auth::{
+    amr::{AuthMethod, AuthMethods},
+    device::ClientInfo,
+    oidc::{OidcState, provider::validate},
+    session_auth::{SessionMeta, create_session_cookie},
+  },
+  db::{
+    DBTrait,
+    oidc::provider::{OidcProviderData, OidcProviderLoginInfo},
+  },
+};
 
 pub fn router() -> ApiRouter {
   ApiRouter::new()
     .api_route("/", post_with(complete_setup, |op| op.id("completeSetup")))
     .api_route("/", is_setup_route())
-    .api_route("/oidc", get_oidc_settings_route())
-    .api_route("/oidc", init_oidc_route())
+    .api_route(
+      "/oidc",
+      get_with(oidc_settings, |op| op.id("getOidcSettings")),
+    )
+    .api_route("/oidc", post_with(init_oidc, |op| op.id("initOidc")))
 }
 
 #[derive(Deserialize, JsonSchema)]
@@ -47,6 +62,7 @@ async fn complete_setup(
   jwt: JwtState,
   state: PasswordState,
   mut cookies: CookieJar,
+  client: ClientInfo,
   Json(payload): Json<SetupPayload>,
 ) -> Result<(CookieJar, Json<SetupResponse>)> {
   if db.setup().is_setup().await? {
@@ -89,9 +105,69 @@ async fn complete_setup(
   db.setup().mark_completed().await?;
   info!("Setup completed, created admin user with ID {}", admin);
 
-  let cookie = create_session_cookie(&db, &jwt, admin, false, payload.session).await?;
+  let cookie = create_session_cookie(
+    &db,
+    &jwt,
+    admin,
+    false,
+    AuthMethods::new(&[AuthMethod::Password]),
+    payload.session,
+    &client,
+  )
+  .await?;
   cookies = cookies.add(cookie);
   info!("Created post setup login token for admin user");
 
   Ok((cookies, Json(SetupResponse { user: admin })))
 }
+
+#[derive(Serialize, JsonSchema)]
+struct OidcSettingsRes {
+  providers: Vec<OidcProviderLoginInfo>,
+  redirect_base: String,
+}
+
+async fn oidc_settings(db: Connection, state: OidcState) -> Result<Json<OidcSettingsRes>> {
+  let mut redirect_base = state.redirect_uri(Uuid::nil());
+  redirect_base.set_path("/api/auth/oidc");
+
+  Ok(Json(OidcSettingsRes {
+    providers: db.oidc_provider().list_enabled().await?,
+    redirect_base: redirect_base.to_string(),
+  }))
+}
+
+#[derive(Serialize, JsonSchema)]
+struct InitOidcRes {
+  uuid: Uuid,
+}
+
+/// Registers the first upstream provider so the initial admin can sign in
+/// through it, the first user created by a provider becomes admin.
+async fn init_oidc(
+  db: Connection,
+  Json(mut payload): Json<OidcProviderData>,
+) -> Result<Json<InitOidcRes>> {
+  if db.setup().is_setup().await? {
+    bail!(CONFLICT, "Setup has already been completed");
+  }
+
+  validate(&payload)?;
+  if payload.client_secret.is_none() {
+    bail!(BAD_REQUEST, "client secret is required");
+  }
+  if db
+    .oidc_provider()
+    .exists(payload.name.clone(), Uuid::max())
+    .await?
+  {
+    bail!(CONFLICT, "provider with the given name already exists");
+  }
+
+  payload.create_user = true;
+  payload.enabled = true;
+  let uuid = db.oidc_provider().create(payload).await?;
+  info!("Registered OIDC provider {} during setup", uuid);
+
+  Ok(Json(InitOidcRes { uuid }))
+}
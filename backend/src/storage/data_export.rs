@@ -0,0 +1,44 @@
+use std::io::Cursor;
+
+use axum::body::Body;
+use centaurus::{error::Result, storage::FileStorage};
+use uuid::Uuid;
+
+pub struct DataExportFolder<'b> {
+  storage: &'b FileStorage,
+}
+
+impl<'b> DataExportFolder<'b> {
+  pub fn new(storage: &'b FileStorage) -> Self {
+    Self { storage }
+  }
+
+  fn path(&self, user_id: Uuid, export_id: Uuid) -> String {
+    format!("exports/{}/{}.zip", user_id, export_id)
+  }
+
+  pub async fn create(&self, user_id: Uuid, export_id: Uuid, data: &[u8]) -> Result<()> {
+    self
+      .storage
+      .save_file(&mut Cursor::new(data), &self.path(user_id, export_id))
+      .await
+  }
+
+  pub async fn exists(&self, user_id: Uuid, export_id: Uuid) -> Result<bool> {
+    self.storage.exists(&self.path(user_id, export_id)).await
+  }
+
+  pub async fn delete(&self, user_id: Uuid, export_id: Uuid) -> Result<()> {
+    self
+      .storage
+      .delete_file(&self.path(user_id, export_id))
+      .await
+  }
+
+  pub async fn read(&self, user_id: Uuid, export_id: Uuid) -> Result<Body> {
+    self
+      .storage
+      .get_file(&self.path(user_id, export_id), None)
+      .await
+  }
+}
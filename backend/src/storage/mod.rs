@@ -3,10 +3,14 @@ use centaurus::storage::FileStorage;
 use crate::config::Config;
 
 pub mod apod;
+pub mod data_export;
+pub mod note_attachment;
 pub mod note_snapshot;
 
 pub trait StorageExt {
   fn apod(&self) -> apod::ApodFolder<'_>;
+  fn data_export(&self) -> data_export::DataExportFolder<'_>;
+  fn note_attachment(&self) -> note_attachment::NoteAttachmentFolder<'_>;
   fn note_snapshot(&self) -> note_snapshot::NoteSnapshotFolder<'_>;
 }
 
@@ -15,6 +19,14 @@ impl StorageExt for FileStorage {
     apod::ApodFolder::new(self)
   }
 
+  fn data_export(&self) -> data_export::DataExportFolder<'_> {
+    data_export::DataExportFolder::new(self)
+  }
+
+  fn note_attachment(&self) -> note_attachment::NoteAttachmentFolder<'_> {
+    note_attachment::NoteAttachmentFolder::new(self)
+  }
+
   fn note_snapshot(&self) -> note_snapshot::NoteSnapshotFolder<'_> {
     note_snapshot::NoteSnapshotFolder::new(self)
   }
@@ -0,0 +1,76 @@
+use std::io::Cursor;
+
+use axum::body::Body;
+use centaurus::{error::Result, storage::FileStorage};
+use uuid::Uuid;
+
+/// Attachment files of a note, images are stored with a WebP thumbnail next
+/// to them.
+pub struct NoteAttachmentFolder<'b> {
+  storage: &'b FileStorage,
+}
+
+impl<'b> NoteAttachmentFolder<'b> {
+  pub fn new(storage: &'b FileStorage) -> Self {
+    Self { storage }
+  }
+
+  fn path(&self, note_id: Uuid, attachment_id: Uuid) -> String {
+    format!("notes/{}/attachments/{}", note_id, attachment_id)
+  }
+
+  fn thumbnail_path(&self, note_id: Uuid, attachment_id: Uuid) -> String {
+    format!(
+      "notes/{}/attachments/{}_thumbnail.webp",
+      note_id, attachment_id
+    )
+  }
+
+  pub async fn create(&self, note_id: Uuid, attachment_id: Uuid, data: &[u8]) -> Result<()> {
+    self
+      .storage
+      .save_file(&mut Cursor::new(data), &self.path(note_id, attachment_id))
+      .await
+  }
+
+  pub async fn create_thumbnail(
+    &self,
+    note_id: Uuid,
+    attachment_id: Uuid,
+    data: &[u8],
+  ) -> Result<()> {
+    self
+      .storage
+      .save_file(
+        &mut Cursor::new(data),
+        &self.thumbnail_path(note_id, attachment_id),
+      )
+      .await
+  }
+
+  /// Deletes the file and its thumbnail, missing files are ignored.
+  pub async fn delete(&self, note_id: Uuid, attachment_id: Uuid) -> Result<()> {
+    self
+      .storage
+      .delete_file(&self.path(note_id, attachment_id))
+      .await?;
+    self
+      .storage
+      .delete_file(&self.thumbnail_path(note_id, attachment_id))
+      .await
+  }
+
+  pub async fn read(&self, note_id: Uuid, attachment_id: Uuid) -> Result<Body> {
+    self
+      .storage
+      .get_file(&self.path(note_id, attachment_id), None)
+      .await
+  }
+
+  pub async fn read_thumbnail(&self, note_id: Uuid, attachment_id: Uuid) -> Result<Body> {
+    self
+      .storage
+      .get_file(&self.thumbnail_path(note_id, attachment_id), None)
+      .await
+  }
+}
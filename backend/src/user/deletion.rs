@@ -0,0 +1,438 @@
+use std::{sync::Arc, time::Duration};
+
+use aide::axum::{ApiRouter, routing::post_with};
+use axum::{Extension, Json};
+use axum_extra::extract::CookieJar;
+use centaurus::{
+  backend::auth::jwt_state::{JWT_COOKIE_NAME, JwtState},
+  bail,
+  db::{init::Connection, tables::ConnectionExt},
+  error::Result,
+  storage::FileStorage,
+};
+use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::{spawn, task::JoinHandle, time::sleep};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{
+  auth::jwt::{JwtAuthOther, JwtSpecial},
+  config::Config,
+  db::DBTrait,
+  notes::{AttachmentLimits, NotesLimits},
+  user::management::delete_account,
+  utils::{UpdateMessage, Updater},
+};
+
+#[derive(Clone)]
+pub struct AccountDeletionSettings {
+  pub grace_period: TimeDelta,
+  /// Limits the user receiving the notes has to stay within.
+  pub notes_limits: NotesLimits,
+  pub attachment_limits: AttachmentLimits,
+}
+
+impl AccountDeletionSettings {
+  pub fn from_config(config: &Config) -> Self {
+    Self {
+      grace_period: TimeDelta::days(config.account_deletion_grace_days),
+      notes_limits: NotesLimits::from_config(config),
+      attachment_limits: AttachmentLimits::from_config(config),
+    }
+  }
+}
+
+#[derive(Clone)]
+pub struct AccountDeletionJob {
+  _handle: Arc<JoinHandle<()>>,
+}
+
+impl AccountDeletionJob {
+  pub fn init(
+    db: Connection,
+    storage: FileStorage,
+    updater: Updater,
+    settings: AccountDeletionSettings,
+  ) -> Self {
+    let handle = spawn(async move {
+      loop {
+        if let Err(err) = run_due(&db, &storage, &updater, &settings, Utc::now()).await {
+          warn!(?err, "account deletion job failed");
+        }
+        sleep(Duration::from_secs(3600)).await;
+      }
+    });
+
+    Self {
+      _handle: Arc::new(handle),
+    }
+  }
+}
+
+async fn run_due(
+  db: &Connection,
+  storage: &FileStorage,
+  updater: &Updater,
+  settings: &AccountDeletionSettings,
+  now: DateTime<Utc>,
+) -> Result<()> {
+  for request in db.account_deletion().list_due(now).await? {
+    let user_id = request.user_id;
+    let target = request.transfer_notes_to;
+    if let Err(err) = carry_out(db, storage, updater, settings, user_id, target).await {
+      warn!(?err, %user_id, "failed to delete account");
+    }
+  }
+  Ok(())
+}
+
+async fn carry_out(
+  db: &Connection,
+  storage: &FileStorage,
+  updater: &Updater,
+  settings: &AccountDeletionSettings,
+  user_id: Uuid,
+  transfer_notes_to: Option<Uuid>,
+) -> Result<()> {
+  // the admin group may have shrunk since the request was accepted
+  if let Some(admin_group) = db.setup().get_admin_group_id().await?
+    && db.group().is_last_admin(admin_group, user_id).await?
+  {
+    bail!("refusing to delete the last admin");
+  }
+
+  // without a target the notes are removed together with the account
+  if let Some(target) = transfer_notes_to
+    && db.user().user_info(target).await?.is_some()
+  {
+    // the target may have filled up since the request, the account stays
+    // deactivated until it has room again or the user withdraws
+    ensure_room_for_notes(db, settings, user_id, target).await?;
+    for note_id in db.notes().list_owned_ids(user_id).await? {
+      db.notes().transfer_owner(note_id, user_id, target).await?;
+      updater
+        .send_to(target, UpdateMessage::Note { uuid: note_id })
+        .await;
+    }
+  }
+
+  delete_account(db, storage, updater, user_id).await?;
+  info!("Deleted account {} after its grace period", user_id);
+  Ok(())
+}
+
+/// Rejects transfers that would push the target over its note limit or
+/// attachment quota.
+async fn ensure_room_for_notes(
+  db: &Connection,
+  settings: &AccountDeletionSettings,
+  user_id: Uuid,
+  target: Uuid,
+) -> Result<()> {
+  let notes = db.notes().count_owned(user_id).await? + db.notes().count_owned(target).await?;
+  if notes > settings.notes_limits.max_per_user as u64 {
+    bail!(
+      CONFLICT,
+      "note limit of the receiving user would be exceeded"
+    );
+  }
+
+  let attachments = db.note_attachment();
+  let used = attachments.used_by_owner(user_id).await? + attachments.used_by_owner(target).await?;
+  if used > settings.attachment_limits.quota {
+    bail!(
+      CONFLICT,
+      "attachment quota of the receiving user would be exceeded"
+    );
+  }
+
+  Ok(())
+}
+
+pub fn router() -> ApiRouter {
+  ApiRouter::new().api_route(
+    "/",
+    post_with(request, |op| op.id("requestAccountDeletion")),
+  )
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct DeletionReq {
+  transfer_notes_to: Option<Uuid>,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct DeletionRes {
+  delete_at: NaiveDateTime,
+}
+
+/// Deactivates the account until the grace period ends, signing in again
+/// withdraws the request.
+async fn request(
+  auth: JwtAuthOther<JwtSpecial>,
+  db: Connection,
+  jwt: JwtState,
+  mut cookies: CookieJar,
+  updater: Updater,
+  Extension(settings): Extension<AccountDeletionSettings>,
+  Json(req): Json<DeletionReq>,
+) -> Result<(CookieJar, Json<DeletionRes>)> {
+  if let Some(admin_group) = db.setup().get_admin_group_id().await?
+    && db.group().is_last_admin(admin_group, auth.user_id).await?
+  {
+    bail!(CONFLICT, "Cannot delete the last user from the admin group");
+  }
+
+  if let Some(target) = req.transfer_notes_to {
+    if target == auth.user_id {
+      bail!(BAD_REQUEST, "cannot transfer notes to self");
+    }
+    if db.user().user_info(target).await?.is_none() {
+      bail!(NOT_FOUND, "user not found");
+    }
+    if db.account_deletion().get(target).await?.is_some() {
+      bail!(CONFLICT, "user is scheduled for deletion");
+    }
+    ensure_room_for_notes(&db, &settings, auth.user_id, target).await?;
+  }
+
+  let delete_at = Utc::now() + settings.grace_period;
+  db.account_deletion()
+    .request(auth.user_id, req.transfer_notes_to, delete_at)
+    .await?;
+  db.session().delete_for_user(auth.user_id).await?;
+  info!("User {} requested account deletion", auth.user_id);
+  updater.send_to(auth.user_id, UpdateMessage::Sessions).await;
+
+  cookies = cookies.remove(jwt.create_cookie(JWT_COOKIE_NAME, String::new()));
+
+  Ok((
+    cookies,
+    Json(DeletionRes {
+      delete_at: delete_at.naive_utc(),
+    }),
+  ))
+}
+
+#[cfg(test)]
+mod test {
+  use axum::{
+    Extension, Router,
+    body::Body,
+    http::{Request, StatusCode, header},
+    routing::post,
+  };
+  use centaurus::{
+    backend::auth::jwt_state::JwtState,
+    db::{init::Connection, tables::ConnectionExt},
+  };
+  use chrono::{Duration, TimeDelta, Utc};
+  use serde_json::{Value, json};
+  use tower::ServiceExt;
+  use uuid::Uuid;
+
+  use super::{AccountDeletionSettings, run_due};
+  use crate::{
+    auth::jwt::{JwtSpecial, JwtStateOther},
+    db::{
+      DBTrait,
+      test::{auth_cookie, body_json, insert_user, jwt_states, other_cookie, test_db, updater},
+    },
+    notes::{AttachmentLimits, NotesLimits},
+    utils::Updater,
+  };
+
+  fn app(db: Connection, jwt: JwtState, other: JwtStateOther, upd: Updater) -> Router {
+    Router::new()
+      .route("/", post(super::request))
+      .layer(Extension(settings()))
+      .layer(Extension(upd))
+      .layer(Extension(jwt))
+      .layer(Extension(other))
+      .layer(Extension(db))
+  }
+
+  fn settings() -> AccountDeletionSettings {
+    AccountDeletionSettings {
+      grace_period: TimeDelta::days(14),
+      notes_limits: NotesLimits { max_per_user: 2 },
+      attachment_limits: AttachmentLimits {
+        max_size: 1024,
+        quota: 1024,
+      },
+    }
+  }
+
+  fn request(cookie: &str, body: Value) -> Request<Body> {
+    Request::builder()
+      .method("POST")
+      .uri("/")
+      .header(header::COOKIE, cookie)
+      .header(header::CONTENT_TYPE, "application/json")
+      .body(Body::from(body.to_string()))
+      .unwrap()
+  }
+
+  #[tokio::test]
+  async fn request_deactivates_account_until_grace_period_ends() {
+    let db = test_db().await;
+    let (jwt, other) = jwt_states(&db).await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let target = insert_user(&db, "t", "t@x.com").await;
+    let session = auth_cookie(&db, &jwt, user).await;
+    let special = other_cookie::<JwtSpecial>(&other, user);
+    let app = app(db.clone(), jwt, other, updater().await);
+
+    // a regular session is not enough
+    let resp = app
+      .clone()
+      .oneshot(request(&session, json!({ "transfer_notes_to": null })))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let cookie = format!("{session}; {special}");
+    let resp = app
+      .clone()
+      .oneshot(request(&cookie, json!({ "transfer_notes_to": user })))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resp = app
+      .clone()
+      .oneshot(request(
+        &cookie,
+        json!({ "transfer_notes_to": Uuid::new_v4() }),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let resp = app
+      .oneshot(request(&cookie, json!({ "transfer_notes_to": target })))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = body_json(resp).await;
+    assert!(body["delete_at"].is_string());
+
+    let pending = db.account_deletion().get(user).await.unwrap().unwrap();
+    assert_eq!(pending.transfer_notes_to, Some(target));
+    assert!(pending.delete_at > (Utc::now() + Duration::days(13)).naive_utc());
+    assert!(db.session().list_for_user(user).await.unwrap().is_empty());
+  }
+
+  #[tokio::test]
+  async fn last_admin_cannot_request_deletion() {
+    let db = test_db().await;
+    let (jwt, other) = jwt_states(&db).await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let admin_group = db.group().create_group("admins".into()).await.unwrap();
+    db.setup()
+      .set_admin_group_created(admin_group)
+      .await
+      .unwrap();
+    db.group()
+      .add_users_to_group(admin_group, vec![user])
+      .await
+      .unwrap();
+    let cookie = format!(
+      "{}; {}",
+      auth_cookie(&db, &jwt, user).await,
+      other_cookie::<JwtSpecial>(&other, user)
+    );
+
+    let resp = app(db.clone(), jwt, other, updater().await)
+      .oneshot(request(&cookie, json!({ "transfer_notes_to": null })))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    assert!(db.account_deletion().get(user).await.unwrap().is_none());
+  }
+
+  #[tokio::test]
+  async fn due_requests_transfer_or_delete_notes() {
+    let db = test_db().await;
+    let storage = crate::storage::test::init_test_storage().await;
+    let keeper = insert_user(&db, "k", "k@x.com").await;
+    let leaving = insert_user(&db, "l", "l@x.com").await;
+    let dropping = insert_user(&db, "d", "d@x.com").await;
+    let waiting = insert_user(&db, "w", "w@x.com").await;
+    let kept = db.notes().create(leaving, "kept".into()).await.unwrap();
+    let dropped = db.notes().create(dropping, "dropped".into()).await.unwrap();
+
+    db.account_deletion()
+      .request(leaving, Some(keeper), Utc::now() - Duration::minutes(1))
+      .await
+      .unwrap();
+    db.account_deletion()
+      .request(dropping, None, Utc::now() - Duration::minutes(1))
+      .await
+      .unwrap();
+    db.account_deletion()
+      .request(waiting, None, Utc::now() + Duration::days(1))
+      .await
+      .unwrap();
+
+    run_due(&db, &storage, &updater().await, &settings(), Utc::now())
+      .await
+      .unwrap();
+
+    assert!(db.user().user_info(leaving).await.unwrap().is_none());
+    assert!(db.user().user_info(dropping).await.unwrap().is_none());
+    assert!(db.user().user_info(waiting).await.unwrap().is_some());
+    assert_eq!(db.notes().get_owner_id(kept).await.unwrap(), Some(keeper));
+    assert_eq!(db.notes().get_owner_id(dropped).await.unwrap(), None);
+    assert!(db.account_deletion().get(waiting).await.unwrap().is_some());
+  }
+
+  #[tokio::test]
+  async fn notes_are_only_transferred_to_users_with_room_for_them() {
+    let db = test_db().await;
+    let storage = crate::storage::test::init_test_storage().await;
+    let (jwt, other) = jwt_states(&db).await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let target = insert_user(&db, "t", "t@x.com").await;
+    let note = db.notes().create(user, "mine".into()).await.unwrap();
+    db.notes().create(target, "theirs".into()).await.unwrap();
+    let extra = db.notes().create(target, "extra".into()).await.unwrap();
+    let cookie = format!(
+      "{}; {}",
+      auth_cookie(&db, &jwt, user).await,
+      other_cookie::<JwtSpecial>(&other, user)
+    );
+    let app = app(db.clone(), jwt, other, updater().await);
+
+    let resp = app
+      .clone()
+      .oneshot(request(&cookie, json!({ "transfer_notes_to": target })))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    assert!(db.account_deletion().get(user).await.unwrap().is_none());
+
+    db.notes().delete(extra).await.unwrap();
+    let resp = app
+      .oneshot(request(&cookie, json!({ "transfer_notes_to": target })))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // the target fills up during the grace period
+    db.notes().create(target, "late".into()).await.unwrap();
+    db.account_deletion()
+      .request(user, Some(target), Utc::now() - Duration::minutes(1))
+      .await
+      .unwrap();
+    run_due(&db, &storage, &updater().await, &settings(), Utc::now())
+      .await
+      .unwrap();
+
+    assert!(db.user().user_info(user).await.unwrap().is_some());
+    assert_eq!(db.notes().get_owner_id(note).await.unwrap(), Some(user));
+    assert!(db.account_deletion().get(user).await.unwrap().is_some());
+  }
+}
@@ -0,0 +1,170 @@
+use centaurus::{
+  db::{
+    init::Connection,
+    tables::{ConnectionExt, user::SimpleGroupInfo},
+  },
+  error::Result,
+};
+use chrono::{NaiveDateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+use yrs::Doc;
+
+use crate::{
+  db::{
+    DBTrait,
+    notes::{NoteInfo, snapshot::NoteSnapshotInfo},
+    oidc::identity::UserIdentityInfo,
+    user::settings::SettingsInfo,
+  },
+  notes::{load_doc, markdown::render_markdown},
+  user::export::zip::ZipWriter,
+};
+
+#[derive(Serialize)]
+struct Profile {
+  uuid: Uuid,
+  name: String,
+  email: String,
+  totp_enabled: bool,
+  has_password: bool,
+  groups: Vec<SimpleGroupInfo>,
+  identities: Vec<UserIdentityInfo>,
+  settings: SettingsInfo,
+  exported_at: NaiveDateTime,
+}
+
+#[derive(Serialize)]
+struct NoteSnapshots {
+  note_id: Uuid,
+  snapshots: Vec<NoteSnapshotInfo>,
+}
+
+#[derive(Serialize)]
+struct Session {
+  uuid: Uuid,
+  name: String,
+  application: String,
+  operating_system: String,
+  is_app: bool,
+  impersonated_by: Option<Uuid>,
+  created_at: NaiveDateTime,
+  last_used_at: NaiveDateTime,
+  refreshed_at: Option<NaiveDateTime>,
+  expires_at: NaiveDateTime,
+}
+
+#[derive(Serialize)]
+struct Passkey {
+  uuid: Uuid,
+  name: String,
+  created: NaiveDateTime,
+  used: NaiveDateTime,
+}
+
+#[derive(Serialize)]
+struct OAuthAuthorization {
+  client_id: Uuid,
+  name: String,
+  redirect_uri: String,
+}
+
+/// Assembles the ZIP with everything stored about a user.
+pub async fn build(db: &Connection, user_id: Uuid) -> Result<Vec<u8>> {
+  let mut zip = ZipWriter::new();
+
+  let user = db.user_ext().get_user_by_id(user_id).await?;
+  let profile = Profile {
+    uuid: user.id,
+    name: user.name,
+    email: user.email,
+    totp_enabled: user.totp.is_some(),
+    has_password: !user.password.is_empty(),
+    groups: db.user().get_user_groups(user_id).await?,
+    identities: db.user_identity().list_for_user(user_id).await?,
+    settings: DBTrait::settings(db).get(user_id).await?,
+    exported_at: Utc::now().naive_utc(),
+  };
+  add_json(&mut zip, "profile.json", &profile)?;
+
+  // shared notes belong to their owners, only their metadata is included
+  let notes = db.notes().list_for_user(user_id).await?;
+  let mut snapshots = Vec::new();
+  for note in notes.iter().filter(|note| note.is_owner) {
+    let content = db.notes().get_content(note.id).await?;
+    zip.add(&format!("notes/{}/state.bin", note.id), &content)?;
+    zip.add(
+      &format!("notes/{}/content.md", note.id),
+      note_markdown(note, &content).await.as_bytes(),
+    )?;
+
+    snapshots.push(NoteSnapshots {
+      note_id: note.id,
+      snapshots: db.note_snapshot().list_for_note(note.id).await?,
+    });
+  }
+  add_json(&mut zip, "notes.json", &notes)?;
+  add_json(&mut zip, "snapshots.json", &snapshots)?;
+
+  let sessions: Vec<Session> = db
+    .session()
+    .list_for_user(user_id)
+    .await?
+    .into_iter()
+    .map(|session| Session {
+      uuid: session.id,
+      name: session.name,
+      application: session.application,
+      operating_system: session.operating_system,
+      is_app: session.is_app,
+      impersonated_by: session.impersonator_id,
+      created_at: session.created_at,
+      last_used_at: session.last_used_at,
+      refreshed_at: session.refreshed_at,
+      expires_at: session.expires_at,
+    })
+    .collect();
+  add_json(&mut zip, "sessions.json", &sessions)?;
+
+  let passkeys: Vec<Passkey> = db
+    .passkey()
+    .get_passkeys_for_user(user_id)
+    .await?
+    .into_iter()
+    .map(|passkey| Passkey {
+      uuid: passkey.id,
+      name: passkey.name,
+      created: passkey.created,
+      used: passkey.used,
+    })
+    .collect();
+  add_json(&mut zip, "passkeys.json", &passkeys)?;
+
+  let clients: Vec<OAuthAuthorization> = db
+    .oauth_client()
+    .list_for_user(user_id)
+    .await?
+    .into_iter()
+    .map(|client| OAuthAuthorization {
+      client_id: client.id,
+      name: client.name,
+      redirect_uri: client.redirect_uri,
+    })
+    .collect();
+  add_json(&mut zip, "oauth_clients.json", &clients)?;
+
+  zip.finish()
+}
+
+async fn note_markdown(note: &NoteInfo, content: &[u8]) -> String {
+  let doc = load_doc(content).await.unwrap_or_else(|| {
+    tracing::warn!(note_id = %note.id, "failed to decode note content for export");
+    Doc::new()
+  });
+
+  format!("# {}\n\n{}", note.title, render_markdown(&doc).await)
+}
+
+fn add_json<T: Serialize>(zip: &mut ZipWriter, name: &str, value: &T) -> Result<()> {
+  zip.add(name, &serde_json::to_vec_pretty(value)?)
+}
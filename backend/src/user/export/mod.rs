@@ -0,0 +1,525 @@
+use std::{sync::Arc, time::Duration};
+
+use aide::axum::{
+  ApiRouter,
+  routing::{get_with, post_with},
+};
+use axum::{Json, extract::Path, response::Response};
+use centaurus::{
+  backend::{auth::jwt_auth::JwtAuth, config::SiteConfig},
+  bail,
+  db::init::Connection,
+  error::Result,
+  eyre::Context,
+  mail::Mailer,
+  storage::FileStorage,
+};
+use chrono::Utc;
+use entity::sea_orm_active_enums::DataExportStatus;
+use http::header;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::{spawn, task::JoinHandle, time::sleep};
+use tracing::{info, warn};
+use url::Url;
+use uuid::Uuid;
+
+use crate::{
+  db::{DBTrait, user::export::DataExportInfo},
+  storage::StorageExt,
+  utils::{UpdateMessage, Updater},
+};
+
+mod archive;
+pub(crate) mod zip;
+
+const EXPORT_LIFETIME: i64 = 2 * 24 * 60 * 60;
+
+#[derive(Clone)]
+pub struct DataExportCleanup {
+  _handle: Arc<JoinHandle<()>>,
+}
+
+impl DataExportCleanup {
+  pub fn init(db: Connection, storage: FileStorage) -> Self {
+    let handle = spawn(async move {
+      match db.data_export().fail_pending().await {
+        Ok(0) => {}
+        Ok(count) => warn!(count, "marked interrupted data exports as failed"),
+        Err(err) => warn!(?err, "failed to reset interrupted data exports"),
+      }
+
+      loop {
+        if let Err(err) = cleanup(&db, &storage).await {
+          warn!(?err, "data export cleanup failed");
+        }
+        sleep(Duration::from_secs(3600)).await;
+      }
+    });
+
+    Self {
+      _handle: Arc::new(handle),
+    }
+  }
+}
+
+async fn cleanup(db: &Connection, storage: &FileStorage) -> Result<()> {
+  for export in db.data_export().list_expired().await? {
+    if storage
+      .data_export()
+      .exists(export.user_id, export.id)
+      .await?
+    {
+      storage
+        .data_export()
+        .delete(export.user_id, export.id)
+        .await?;
+    }
+    db.data_export().delete(export.id).await?;
+  }
+  Ok(())
+}
+
+pub fn router() -> ApiRouter {
+  ApiRouter::new()
+    .api_route("/", get_with(list, |op| op.id("listDataExports")))
+    .api_route("/", post_with(request, |op| op.id("requestDataExport")))
+    .api_route(
+      "/{uuid}",
+      get_with(download, |op| op.id("downloadDataExport")),
+    )
+}
+
+async fn list(auth: JwtAuth, db: Connection) -> Result<Json<Vec<DataExportInfo>>> {
+  Ok(Json(db.data_export().list_for_user(auth.user_id).await?))
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct ExportReq {
+  send_mail: bool,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct ExportRes {
+  uuid: Uuid,
+}
+
+async fn request(
+  auth: JwtAuth,
+  db: Connection,
+  storage: FileStorage,
+  mailer: Mailer,
+  site: SiteConfig,
+  updater: Updater,
+  Json(req): Json<ExportReq>,
+) -> Result<Json<ExportRes>> {
+  if db.data_export().has_pending(auth.user_id).await? {
+    bail!(CONFLICT, "an export is already running");
+  }
+  if req.send_mail && !mailer.is_active().await {
+    bail!(BAD_REQUEST, "mail is not configured");
+  }
+
+  let expires_at = Utc::now() + chrono::Duration::seconds(EXPORT_LIFETIME);
+  let uuid = db
+    .data_export()
+    .create(auth.user_id, req.send_mail, expires_at)
+    .await?;
+  info!("User {} requested data export {}", auth.user_id, uuid);
+  updater
+    .send_to(auth.user_id, UpdateMessage::DataExports)
+    .await;
+
+  spawn(async move {
+    let mail = req.send_mail.then_some((&mailer, &site.site_url));
+    run(&db, &storage, &updater, mail, auth.user_id, uuid).await;
+  });
+
+  Ok(Json(ExportRes { uuid }))
+}
+
+async fn run(
+  db: &Connection,
+  storage: &FileStorage,
+  updater: &Updater,
+  mail: Option<(&Mailer, &Url)>,
+  user_id: Uuid,
+  export_id: Uuid,
+) {
+  let status = match build_and_store(db, storage, user_id, export_id).await {
+    Ok(size) => db
+      .data_export()
+      .finish(export_id, DataExportStatus::Ready, Some(size))
+      .await
+      .map(|_| DataExportStatus::Ready),
+    Err(err) => {
+      warn!(?err, %user_id, %export_id, "data export failed");
+      db.data_export()
+        .finish(export_id, DataExportStatus::Failed, None)
+        .await
+        .map(|_| DataExportStatus::Failed)
+    }
+  };
+  let status = match status {
+    Ok(status) => status,
+    Err(err) => {
+      warn!(?err, %export_id, "failed to update data export");
+      return;
+    }
+  };
+  updater.send_to(user_id, UpdateMessage::DataExports).await;
+
+  if let (DataExportStatus::Ready, Some((mailer, site_url))) = (status, mail)
+    && let Err(err) = send_mail(db, mailer, site_url, user_id, export_id).await
+  {
+    warn!(?err, %user_id, %export_id, "failed to send data export mail");
+  }
+}
+
+async fn build_and_store(
+  db: &Connection,
+  storage: &FileStorage,
+  user_id: Uuid,
+  export_id: Uuid,
+) -> Result<i64> {
+  let data = archive::build(db, user_id).await?;
+  storage
+    .data_export()
+    .create(user_id, export_id, &data)
+    .await?;
+  Ok(data.len() as i64)
+}
+
+async fn send_mail(
+  db: &Connection,
+  mailer: &Mailer,
+  site_url: &Url,
+  user_id: Uuid,
+  export_id: Uuid,
+) -> Result<()> {
+  let user = db.user_ext().get_user_by_id(user_id).await?;
+  let link = download_link(site_url, export_id);
+
+  mailer
+    .send_mail(
+      user.name,
+      user.email,
+      "Positron Data Export".to_string(),
+      mail_template(link.as_str(), site_url.as_str()),
+    )
+    .await
+}
+
+fn download_link(site_url: &Url, export_id: Uuid) -> Url {
+  let mut link = site_url.clone();
+  if let Ok(mut segments) = link.path_segments_mut() {
+    segments.pop_if_empty();
+    segments.extend(["api", "user", "account", "export", &export_id.to_string()]);
+  }
+  link
+}
+
+fn mail_template(download_link: &str, link: &str) -> String {
+  format!(
+    r#"
+  <!DOCTYPE html>
+  <html lang="en">
+    <head>
+      <meta charset="UTF-8">
+      <meta name="viewport" content="width=device-width, initial-scale=1.0">
+      <title>Data Export</title>
+    </head>
+    <body>
+      <div style="display: flex; flex-direction: column;">
+        <header style="padding: 1rem; display: flex; flex-direction: column; align-items: center; justify-content: center;">
+          <h2 style="margin: 0;">Data Export</h2>
+          <p style="margin: 0;">Your data export is ready, sign in and use the link below to download it</p>
+        </header>
+        <div style="display: flex; align-items: center; justify-content: center;">
+          <a href="{download_link}">Download Export</a>
+        </div>
+        <footer style="display: flex; align-items: center; justify-content: center;">
+          <p>Mail send from <a href="{link}">{link}</a></p>
+        </footer>
+      </div>
+    </body>
+  </html>
+  "#
+  )
+}
+
+async fn download(
+  auth: JwtAuth,
+  db: Connection,
+  storage: FileStorage,
+  Path(uuid): Path<Uuid>,
+) -> Result<Response> {
+  let Some(export) = db.data_export().get(uuid, auth.user_id).await? else {
+    bail!(NOT_FOUND, "export not found");
+  };
+  if export.status != DataExportStatus::Ready || export.expires_at < Utc::now().naive_utc() {
+    bail!(NOT_FOUND, "export not available");
+  }
+
+  let body = storage.data_export().read(auth.user_id, uuid).await?;
+  let filename = format!(
+    "positron-export-{}.zip",
+    export.created_at.format("%Y-%m-%d")
+  );
+
+  Ok(
+    Response::builder()
+      .header(header::CONTENT_TYPE, "application/zip")
+      .header(
+        header::CONTENT_DISPOSITION,
+        format!("attachment; filename=\"{filename}\""),
+      )
+      .body(body)
+      .context("Failed to create response")?,
+  )
+}
+
+#[cfg(test)]
+mod test {
+  use std::time::Duration;
+
+  use axum::{
+    Extension, Router,
+    body::{Body, to_bytes},
+    http::{Request, StatusCode, header},
+    routing::get,
+  };
+  use centaurus::{
+    backend::{auth::jwt_state::JwtState, config::SiteConfig},
+    db::init::Connection,
+    mail::{MailSettings, Mailer},
+    storage::FileStorage,
+  };
+  use chrono::Utc;
+  use entity::sea_orm_active_enums::DataExportStatus;
+  use serde_json::{Value, json};
+  use tower::ServiceExt;
+  use uuid::Uuid;
+  use yrs::{ReadTxn, StateVector, Transact};
+
+  use super::{cleanup, download_link, zip::test::read_zip};
+  use crate::{
+    db::{
+      DBTrait,
+      test::{auth_cookie, auth_state, body_json, insert_passkey, insert_user, test_db, updater},
+    },
+    notes::markdown::test::sample_doc,
+    storage::{StorageExt, test::init_test_storage},
+  };
+
+  async fn app(db: Connection, jwt: JwtState, storage: FileStorage) -> Router {
+    Router::new()
+      .route("/", get(super::list).post(super::request))
+      .route("/{uuid}", get(super::download))
+      .layer(Extension(Mailer::new(MailSettings::default()).await))
+      .layer(Extension(SiteConfig::default()))
+      .layer(Extension(updater().await))
+      .layer(Extension(storage))
+      .layer(Extension(jwt))
+      .layer(Extension(db))
+  }
+
+  fn request(method: &str, uri: &str, cookie: &str, body: Option<Value>) -> Request<Body> {
+    let builder = Request::builder()
+      .method(method)
+      .uri(uri)
+      .header(header::COOKIE, cookie);
+    match body {
+      Some(value) => builder
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(value.to_string()))
+        .unwrap(),
+      None => builder.body(Body::empty()).unwrap(),
+    }
+  }
+
+  async fn wait_for(db: &Connection, user: Uuid, export: Uuid) -> DataExportStatus {
+    for _ in 0..100 {
+      let row = db.data_export().get(export, user).await.unwrap().unwrap();
+      if row.status != DataExportStatus::Pending {
+        return row.status;
+      }
+      tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("export did not finish");
+  }
+
+  #[tokio::test]
+  async fn export_contains_profile_notes_and_account_data() {
+    let db = test_db().await;
+    let jwt = auth_state(&db).await;
+    let storage = init_test_storage().await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let other = insert_user(&db, "o", "o@x.com").await;
+    insert_passkey(&db, user, "laptop", "cred").await;
+    let cookie = auth_cookie(&db, &jwt, user).await;
+    let other_cookie = auth_cookie(&db, &jwt, other).await;
+
+    let note = db.notes().create(user, "Ideas".into()).await.unwrap();
+    let content = sample_doc()
+      .transact()
+      .encode_state_as_update_v1(&StateVector::default());
+    db.notes()
+      .set_content(note, content.clone(), String::new())
+      .await
+      .unwrap();
+    db.note_snapshot().create(note, "old".into()).await.unwrap();
+
+    let app = app(db.clone(), jwt, storage).await;
+    let resp = app
+      .clone()
+      .oneshot(request(
+        "POST",
+        "/",
+        &cookie,
+        Some(json!({ "send_mail": false })),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let export: Uuid = serde_json::from_value(body_json(resp).await["uuid"].clone()).unwrap();
+    assert_eq!(wait_for(&db, user, export).await, DataExportStatus::Ready);
+
+    let resp = app
+      .clone()
+      .oneshot(request("GET", "/", &cookie, None))
+      .await
+      .unwrap();
+    let list = body_json(resp).await;
+    assert_eq!(list[0]["status"], "ready");
+
+    // exports are private to their owner
+    let resp = app
+      .clone()
+      .oneshot(request("GET", &format!("/{export}"), &other_cookie, None))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let resp = app
+      .oneshot(request("GET", &format!("/{export}"), &cookie, None))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()[header::CONTENT_TYPE], "application/zip");
+    let data = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    let entries = read_zip(&data);
+    let file = |name: &str| {
+      entries
+        .iter()
+        .find(|(entry, _)| entry == name)
+        .map(|(_, content)| content.clone())
+        .unwrap_or_else(|| panic!("missing {name}"))
+    };
+    let json_file = |name: &str| serde_json::from_slice::<Value>(&file(name)).unwrap();
+
+    assert_eq!(json_file("profile.json")["email"], "u@x.com");
+    assert_eq!(json_file("notes.json")[0]["title"], "Ideas");
+    assert_eq!(file(&format!("notes/{note}/state.bin")), content);
+    let markdown = String::from_utf8(file(&format!("notes/{note}/content.md"))).unwrap();
+    assert!(markdown.starts_with("# Ideas\n\n## Title\n"));
+    assert_eq!(
+      json_file("snapshots.json")[0]["snapshots"][0]["preview"],
+      "old"
+    );
+    assert_eq!(json_file("passkeys.json")[0]["name"], "laptop");
+    assert!(
+      json_file("oauth_clients.json")
+        .as_array()
+        .unwrap()
+        .is_empty()
+    );
+
+    // session tokens never leave the server
+    let sessions = json_file("sessions.json");
+    assert_eq!(sessions.as_array().unwrap().len(), 1);
+    assert!(sessions[0].get("token").is_none());
+  }
+
+  #[tokio::test]
+  async fn request_rejects_running_export_and_unconfigured_mail() {
+    let db = test_db().await;
+    let jwt = auth_state(&db).await;
+    let storage = init_test_storage().await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let cookie = auth_cookie(&db, &jwt, user).await;
+    let app = app(db.clone(), jwt, storage).await;
+
+    let resp = app
+      .clone()
+      .oneshot(request(
+        "POST",
+        "/",
+        &cookie,
+        Some(json!({ "send_mail": true })),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    db.data_export()
+      .create(user, false, Utc::now() + chrono::Duration::days(1))
+      .await
+      .unwrap();
+    let resp = app
+      .oneshot(request(
+        "POST",
+        "/",
+        &cookie,
+        Some(json!({ "send_mail": false })),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+  }
+
+  #[tokio::test]
+  async fn expired_exports_are_unavailable_and_cleaned_up() {
+    let db = test_db().await;
+    let jwt = auth_state(&db).await;
+    let storage = init_test_storage().await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let cookie = auth_cookie(&db, &jwt, user).await;
+
+    let export = db
+      .data_export()
+      .create(user, false, Utc::now() - chrono::Duration::hours(1))
+      .await
+      .unwrap();
+    db.data_export()
+      .finish(export, DataExportStatus::Ready, Some(3))
+      .await
+      .unwrap();
+    storage
+      .data_export()
+      .create(user, export, b"zip")
+      .await
+      .unwrap();
+
+    let resp = app(db.clone(), jwt, storage.clone())
+      .await
+      .oneshot(request("GET", &format!("/{export}"), &cookie, None))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    cleanup(&db, &storage).await.unwrap();
+    assert!(db.data_export().get(export, user).await.unwrap().is_none());
+    assert!(!storage.data_export().exists(user, export).await.unwrap());
+  }
+
+  #[test]
+  fn download_link_points_at_the_api() {
+    let export = Uuid::nil();
+    let link = download_link(&"https://positron.example.com/".parse().unwrap(), export);
+    assert_eq!(
+      link.as_str(),
+      format!("https://positron.example.com/api/user/account/export/{export}")
+    );
+  }
+}
@@ -0,0 +1,166 @@
+use std::io::{Cursor, Read, Write};
+
+use centaurus::{bail, error::Result, eyre::Context};
+use chrono::Utc;
+use zip::{CompressionMethod, DateTime, ZipArchive, write::SimpleFileOptions};
+
+/// In-memory ZIP writer, entries are deflated.
+pub struct ZipWriter {
+  zip: zip::ZipWriter<Cursor<Vec<u8>>>,
+  options: SimpleFileOptions,
+}
+
+impl ZipWriter {
+  pub fn new() -> Self {
+    let mut options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    if let Ok(time) = DateTime::try_from(Utc::now().naive_utc()) {
+      options = options.last_modified_time(time);
+    }
+
+    Self {
+      zip: zip::ZipWriter::new(Cursor::new(Vec::new())),
+      options,
+    }
+  }
+
+  pub fn add(&mut self, name: &str, content: &[u8]) -> Result<()> {
+    self
+      .zip
+      .start_file(name, self.options)
+      .context("failed to add zip entry")?;
+    self.zip.write_all(content)?;
+    Ok(())
+  }
+
+  pub fn finish(self) -> Result<Vec<u8>> {
+    Ok(
+      self
+        .zip
+        .finish()
+        .context("failed to finish zip archive")?
+        .into_inner(),
+    )
+  }
+}
+
+/// Reads all file entries of an archive, directories are skipped.
+/// `max_size` bounds the total uncompressed size, counted while unpacking as
+/// the sizes an archive declares are not trusted.
+pub fn read_entries(data: &[u8], max_size: usize) -> Result<Vec<(String, Vec<u8>)>> {
+  let Ok(mut archive) = ZipArchive::new(Cursor::new(data)) else {
+    bail!(BAD_REQUEST, "not a zip archive");
+  };
+
+  let max_size = max_size as u64;
+  let mut total = 0;
+  let mut entries = Vec::new();
+  for index in 0..archive.len() {
+    let Ok(mut file) = archive.by_index(index) else {
+      bail!(BAD_REQUEST, "invalid zip archive");
+    };
+    if file.is_dir() {
+      continue;
+    }
+    let Ok(name) = file.name().map(|name| name.into_owned()) else {
+      bail!(BAD_REQUEST, "invalid zip archive");
+    };
+    if total + file.size() > max_size {
+      bail!(PAYLOAD_TOO_LARGE, "zip archive is too large");
+    }
+
+    let mut content = Vec::new();
+    // decompression stops right after the limit, whatever the entry declares
+    if (&mut file)
+      .take(max_size - total + 1)
+      .read_to_end(&mut content)
+      .is_err()
+    {
+      bail!(BAD_REQUEST, "invalid zip entry {name}");
+    }
+    total += content.len() as u64;
+    if total > max_size {
+      bail!(PAYLOAD_TOO_LARGE, "zip archive is too large");
+    }
+
+    entries.push((name, content));
+  }
+
+  Ok(entries)
+}
+
+#[cfg(test)]
+pub mod test {
+  use std::io::{Cursor, Read};
+
+  use zip::ZipArchive;
+
+  use super::{ZipWriter, read_entries};
+
+  /// Reads an archive back including its directory entries.
+  pub fn read_zip(data: &[u8]) -> Vec<(String, Vec<u8>)> {
+    let mut archive = ZipArchive::new(Cursor::new(data)).unwrap();
+    (0..archive.len())
+      .map(|index| {
+        let mut file = archive.by_index(index).unwrap();
+        let mut content = Vec::new();
+        file.read_to_end(&mut content).unwrap();
+        (file.name().unwrap().into_owned(), content)
+      })
+      .collect()
+  }
+
+  #[test]
+  fn entries_round_trip() {
+    let mut zip = ZipWriter::new();
+    zip.add("profile.json", b"{\"name\":\"u\"}").unwrap();
+    zip.add("notes/ä.md", &[b'a'; 4096]).unwrap();
+    let data = zip.finish().unwrap();
+
+    let entries = read_zip(&data);
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].0, "profile.json");
+    assert_eq!(entries[0].1, b"{\"name\":\"u\"}");
+    assert_eq!(entries[1].0, "notes/ä.md");
+    assert_eq!(entries[1].1, vec![b'a'; 4096]);
+    // repetitive content is actually compressed
+    assert!(data.len() < 1024);
+  }
+
+  #[test]
+  fn read_entries_round_trips_and_bounds_size() {
+    let mut zip = ZipWriter::new();
+    zip.add("docs/", b"").unwrap();
+    zip.add("docs/a.md", b"# A").unwrap();
+    let data = zip.finish().unwrap();
+
+    let entries = read_entries(&data, 1024).unwrap();
+    assert_eq!(entries, [("docs/a.md".to_string(), b"# A".to_vec())]);
+    assert!(read_entries(&data, 2).is_err());
+    assert!(read_entries(b"not a zip", 1024).is_err());
+  }
+
+  #[test]
+  fn read_entries_does_not_trust_declared_sizes() {
+    let mut zip = ZipWriter::new();
+    zip.add("bomb.md", &[b'a'; 4096]).unwrap();
+    let mut data = zip.finish().unwrap();
+
+    // claim a single byte in the local header and the central directory
+    let central = data
+      .windows(4)
+      .position(|window| window == 0x02014b50u32.to_le_bytes())
+      .unwrap();
+    for at in [22, central + 24] {
+      data[at..at + 4].copy_from_slice(&1u32.to_le_bytes());
+    }
+
+    assert!(read_entries(&data, 16).is_err());
+  }
+
+  #[test]
+  fn empty_archive_is_only_the_directory_end() {
+    let data = ZipWriter::new().finish().unwrap();
+    assert_eq!(data.len(), 22);
+    assert!(read_zip(&data).is_empty());
+  }
+}
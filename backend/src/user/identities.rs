@@ -0,0 +1,219 @@
+use aide::axum::{
+  ApiRouter,
+  routing::{delete_with, get_with},
+};
+use axum::Json;
+use centaurus::{backend::auth::jwt_auth::JwtAuth, bail, db::init::Connection, error::Result};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+  auth::jwt::{JwtAuthOther, JwtSpecial},
+  db::{DBTrait, oidc::identity::UserIdentityInfo},
+  utils::{UpdateMessage, Updater},
+};
+
+pub fn router() -> ApiRouter {
+  ApiRouter::new()
+    .api_route("/", get_with(list, |op| op.id("listIdentities")))
+    .api_route("/", delete_with(unlink, |op| op.id("unlinkIdentity")))
+}
+
+async fn list(auth: JwtAuth, db: Connection) -> Result<Json<Vec<UserIdentityInfo>>> {
+  Ok(Json(db.user_identity().list_for_user(auth.user_id).await?))
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct UnlinkReq {
+  uuid: Uuid,
+}
+
+async fn unlink(
+  auth: JwtAuthOther<JwtSpecial>,
+  db: Connection,
+  updater: Updater,
+  Json(req): Json<UnlinkReq>,
+) -> Result<()> {
+  // accounts created through an upstream provider have no password, removing
+  // their last identity without a passkey would lock the user out
+  let user = db.user_ext().get_user_by_id(auth.user_id).await?;
+  if user.password.is_empty()
+    && db
+      .passkey()
+      .get_passkeys_for_user(auth.user_id)
+      .await?
+      .is_empty()
+    && db.user_identity().count_for_user(auth.user_id).await? <= 1
+  {
+    bail!(CONFLICT, "cannot unlink the last login method");
+  }
+
+  if !db.user_identity().delete(req.uuid, auth.user_id).await? {
+    bail!(NOT_FOUND, "identity not found");
+  }
+  info!("User {} unlinked identity {}", auth.user_id, req.uuid);
+  updater
+    .send_to(auth.user_id, UpdateMessage::Identities)
+    .await;
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use crate::{
+    auth::jwt::{JwtSpecial, JwtStateOther},
+    db::{
+      DBTrait,
+      oidc::provider::test::provider_data,
+      test::{auth_cookie, body_json, insert_user, jwt_states, other_cookie, test_db, updater},
+    },
+    utils::UpdateMessage,
+  };
+  use axum::{
+    Extension, Router,
+    body::Body,
+    http::{Request, StatusCode, header},
+    routing::get,
+  };
+  use centaurus::{
+    backend::{auth::jwt_state::JwtState, endpoints::websocket::state::Updater},
+    db::{init::Connection, tables::ConnectionExt},
+  };
+  use serde_json::{Value, json};
+  use tower::ServiceExt;
+  use uuid::Uuid;
+
+  fn app(
+    db: Connection,
+    jwt: JwtState,
+    other: JwtStateOther,
+    upd: Updater<UpdateMessage>,
+  ) -> Router {
+    Router::new()
+      .route("/", get(super::list).delete(super::unlink))
+      .layer(Extension(upd))
+      .layer(Extension(jwt))
+      .layer(Extension(other))
+      .layer(Extension(db))
+  }
+
+  fn request(method: &str, cookie: &str, body: Option<Value>) -> Request<Body> {
+    let builder = Request::builder()
+      .method(method)
+      .uri("/")
+      .header(header::COOKIE, cookie);
+    match body {
+      Some(value) => builder
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(value.to_string()))
+        .unwrap(),
+      None => builder.body(Body::empty()).unwrap(),
+    }
+  }
+
+  async fn link(db: &Connection, user: Uuid, name: &str) -> Uuid {
+    let provider = db
+      .oidc_provider()
+      .create(provider_data(name, &format!("https://{name}.example.com")))
+      .await
+      .unwrap();
+    db.user_identity()
+      .create(user, provider, format!("{name}-sub"), "u@x.com".into())
+      .await
+      .unwrap()
+  }
+
+  #[tokio::test]
+  async fn list_and_unlink() {
+    let db = test_db().await;
+    let (jwt, other) = jwt_states(&db).await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let identity = link(&db, user, "gitlab").await;
+    let cookie = auth_cookie(&db, &jwt, user).await;
+    let special = other_cookie::<JwtSpecial>(&other, user);
+    let app = app(db.clone(), jwt, other, updater().await);
+
+    let resp = app
+      .clone()
+      .oneshot(request("GET", &cookie, None))
+      .await
+      .unwrap();
+    let list = body_json(resp).await;
+    assert_eq!(list.as_array().unwrap().len(), 1);
+    assert_eq!(list[0]["provider_name"], "gitlab");
+
+    // a normal session token without special access is not enough to unlink
+    let resp = app
+      .clone()
+      .oneshot(request(
+        "DELETE",
+        &cookie,
+        Some(json!({ "uuid": identity })),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // the user still has a password, so the only identity can go
+    let resp = app
+      .clone()
+      .oneshot(request(
+        "DELETE",
+        &special,
+        Some(json!({ "uuid": identity })),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(db.user_identity().count_for_user(user).await.unwrap(), 0);
+
+    let resp = app
+      .oneshot(request(
+        "DELETE",
+        &special,
+        Some(json!({ "uuid": identity })),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+  }
+
+  #[tokio::test]
+  async fn unlink_keeps_last_login_method() {
+    let db = test_db().await;
+    let (jwt, other) = jwt_states(&db).await;
+    let user = db
+      .user()
+      .create_user(
+        "u".into(),
+        "u@x.com".into(),
+        String::new(),
+        "salt".into(),
+        true,
+        None,
+      )
+      .await
+      .unwrap();
+    let first = link(&db, user, "gitlab").await;
+    let second = link(&db, user, "keycloak").await;
+    let special = other_cookie::<JwtSpecial>(&other, user);
+    let app = app(db.clone(), jwt, other, updater().await);
+
+    let resp = app
+      .clone()
+      .oneshot(request("DELETE", &special, Some(json!({ "uuid": first }))))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = app
+      .oneshot(request("DELETE", &special, Some(json!({ "uuid": second }))))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    assert_eq!(db.user_identity().count_for_user(user).await.unwrap(), 1);
+  }
+}
@@ -0,0 +1,429 @@
+use aide::axum::{
+  ApiRouter,
+  routing::{get_with, post_with},
+};
+use axum::{Extension, Json};
+use axum_extra::extract::CookieJar;
+use centaurus::{
+  backend::auth::{
+    jwt_auth::JwtAuth,
+    jwt_state::{JWT_COOKIE_NAME, JwtState},
+    permission::Permission,
+  },
+  bail,
+  db::init::Connection,
+  error::Result,
+};
+use chrono::{Duration, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+  auth::session_auth::{CurrentSession, create_session_raw_token},
+  db::{DBTrait, user::impersonation::ImpersonationAuditInfo},
+  utils::{UpdateMessage, Updater, UserImpersonate},
+};
+
+/// Impersonation sessions can not be refreshed and end after this time.
+const IMPERSONATION_DURATION: i64 = 15 * 60;
+/// Holds the admin's own session token while they act as another user.
+const IMPERSONATOR_COOKIE_NAME: &str = "positron_impersonator";
+
+pub fn router() -> ApiRouter {
+  ApiRouter::new()
+    .api_route("/", post_with(start, |op| op.id("startImpersonation")))
+    .api_route("/stop", post_with(stop, |op| op.id("stopImpersonation")))
+    .api_route(
+      "/audit",
+      get_with(audit, |op| op.id("listImpersonationAudit")),
+    )
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct StartReq {
+  user: Uuid,
+  reason: String,
+}
+
+async fn start(
+  auth: JwtAuth<UserImpersonate>,
+  Extension(session): Extension<CurrentSession>,
+  db: Connection,
+  jwt: JwtState,
+  updater: Updater,
+  mut cookies: CookieJar,
+  Json(req): Json<StartReq>,
+) -> Result<CookieJar> {
+  if session.impersonator.is_some() {
+    bail!(CONFLICT, "already impersonating a user");
+  }
+  if req.user == auth.user_id {
+    bail!(BAD_REQUEST, "cannot impersonate yourself");
+  }
+  if req.reason.trim().is_empty() {
+    bail!(BAD_REQUEST, "a reason is required");
+  }
+  if db.user_ext().get_user_by_id(req.user).await.is_err() {
+    bail!(NOT_FOUND, "user not found");
+  }
+  // otherwise one admin could act with the privileges of another
+  if db
+    .group_ext()
+    .user_has_permission(req.user, UserImpersonate::name())
+    .await?
+  {
+    bail!(FORBIDDEN, "cannot impersonate a user who can impersonate");
+  }
+
+  let Some(admin_token) = cookies.get(JWT_COOKIE_NAME).map(|c| c.value().to_string()) else {
+    bail!(BAD_REQUEST, "impersonation requires a browser session");
+  };
+
+  let expires_at = Utc::now() + Duration::seconds(IMPERSONATION_DURATION);
+  let token = create_session_raw_token(&jwt, req.user).await?;
+  let session_id = db
+    .session()
+    .create_impersonation(req.user, auth.user_id, token.clone(), expires_at)
+    .await?;
+  db.impersonation()
+    .start(auth.user_id, req.user, session_id, req.reason, expires_at)
+    .await?;
+  info!("User {} started impersonating {}", auth.user_id, req.user);
+
+  cookies = cookies.add(jwt.create_cookie(IMPERSONATOR_COOKIE_NAME, admin_token));
+  cookies = cookies.add(jwt.create_cookie(JWT_COOKIE_NAME, token));
+  updater.send_to(req.user, UpdateMessage::Sessions).await;
+
+  Ok(cookies)
+}
+
+async fn stop(
+  auth: JwtAuth,
+  Extension(session): Extension<CurrentSession>,
+  db: Connection,
+  jwt: JwtState,
+  updater: Updater,
+  mut cookies: CookieJar,
+) -> Result<CookieJar> {
+  let Some(admin) = session.impersonator else {
+    bail!(BAD_REQUEST, "not impersonating a user");
+  };
+
+  db.session().delete_by_id(session.id, auth.user_id).await?;
+  db.impersonation().stop(session.id).await?;
+  info!("User {} stopped impersonating {}", admin, auth.user_id);
+
+  // hand the admin their own session back if it is still alive
+  let admin_token = cookies
+    .get(IMPERSONATOR_COOKIE_NAME)
+    .map(|c| c.value().to_string());
+  cookies = cookies.remove(jwt.create_cookie(IMPERSONATOR_COOKIE_NAME, String::new()));
+  match admin_token {
+    Some(token)
+      if db
+        .session()
+        .get_by_token(&token)
+        .await
+        .is_ok_and(|s| s.user_id == admin) =>
+    {
+      cookies = cookies.add(jwt.create_cookie(JWT_COOKIE_NAME, token));
+    }
+    _ => {
+      cookies = cookies.remove(jwt.create_cookie(JWT_COOKIE_NAME, String::new()));
+    }
+  }
+  updater.send_to(auth.user_id, UpdateMessage::Sessions).await;
+
+  Ok(cookies)
+}
+
+#[derive(Serialize, JsonSchema)]
+struct AuditRes {
+  entries: Vec<ImpersonationAuditInfo>,
+}
+
+async fn audit(_auth: JwtAuth<UserImpersonate>, db: Connection) -> Result<Json<AuditRes>> {
+  Ok(Json(AuditRes {
+    entries: db.impersonation().list(200).await?,
+  }))
+}
+
+#[cfg(test)]
+mod test {
+  use crate::{
+    auth::session_auth::deny_impersonation,
+    db::{
+      DBTrait,
+      test::{
+        auth_cookie, auth_state, body_json, grant_permissions, insert_user, test_db, updater,
+      },
+    },
+  };
+  use axum::{
+    Extension, Router,
+    body::Body,
+    http::{Request, StatusCode, header},
+    middleware::from_fn,
+    response::Response,
+    routing::{get, post},
+  };
+  use centaurus::{
+    backend::auth::{jwt_auth::JwtAuth, jwt_state::JwtState},
+    db::init::Connection,
+  };
+  use serde_json::{Value, json};
+  use tower::ServiceExt;
+  use uuid::Uuid;
+
+  fn app(db: Connection, jwt: JwtState, upd: crate::utils::Updater) -> Router {
+    Router::new()
+      .route("/", post(super::start))
+      .route("/stop", post(super::stop))
+      .route("/audit", get(super::audit))
+      .route(
+        "/whoami",
+        get(|auth: JwtAuth| async move { auth.user_id.to_string() }),
+      )
+      .route(
+        "/credentials",
+        post(|| async {}).route_layer(from_fn(deny_impersonation)),
+      )
+      .layer(Extension(upd))
+      .layer(Extension(jwt))
+      .layer(Extension(db))
+  }
+
+  fn request(method: &str, uri: &str, cookie: &str, body: Option<Value>) -> Request<Body> {
+    let builder = Request::builder()
+      .method(method)
+      .uri(uri)
+      .header(header::COOKIE, cookie);
+    match body {
+      Some(value) => builder
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(value.to_string()))
+        .unwrap(),
+      None => builder.body(Body::empty()).unwrap(),
+    }
+  }
+
+  /// Reads the `Set-Cookie` name/value pairs of a response.
+  fn cookies_of(resp: &Response) -> Vec<(String, String)> {
+    resp
+      .headers()
+      .get_all(header::SET_COOKIE)
+      .iter()
+      .map(|v| {
+        let pair = v.to_str().unwrap().split(';').next().unwrap();
+        let (name, value) = pair.split_once('=').unwrap();
+        (name.to_string(), value.to_string())
+      })
+      .collect()
+  }
+
+  /// Builds a `Cookie:` header, dropping cookies that were removed.
+  fn cookie_header(cookies: &[(String, String)]) -> String {
+    cookies
+      .iter()
+      .filter(|(_, v)| !v.is_empty())
+      .map(|(n, v)| format!("{n}={v}"))
+      .collect::<Vec<_>>()
+      .join("; ")
+  }
+
+  struct Setup {
+    db: Connection,
+    app: Router,
+    admin: Uuid,
+    user: Uuid,
+    cookie: String,
+  }
+
+  async fn setup() -> Setup {
+    let db = test_db().await;
+    let jwt = auth_state(&db).await;
+    let admin = insert_user(&db, "admin", "admin@x.com").await;
+    let user = insert_user(&db, "user", "user@x.com").await;
+    grant_permissions(&db, admin, &["user:impersonate"]).await;
+    let cookie = auth_cookie(&db, &jwt, admin).await;
+    let app = app(db.clone(), jwt, updater().await);
+    Setup {
+      db,
+      app,
+      admin,
+      user,
+      cookie,
+    }
+  }
+
+  #[tokio::test]
+  async fn start_marks_info_blocks_credentials_and_stop_restores_admin() {
+    let s = setup().await;
+
+    let resp = s
+      .app
+      .clone()
+      .oneshot(request(
+        "POST",
+        "/",
+        &s.cookie,
+        Some(json!({ "user": s.user, "reason": "ticket 42" })),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let impersonated = cookie_header(&cookies_of(&resp));
+
+    let resp = s
+      .app
+      .clone()
+      .oneshot(request("GET", "/whoami", &impersonated, None))
+      .await
+      .unwrap();
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+      .await
+      .unwrap();
+    assert_eq!(body, s.user.to_string());
+
+    // the user sees the session in their own list
+    let sessions = s.db.session().list_for_user(s.user).await.unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].impersonator_id, Some(s.admin));
+
+    let resp = s
+      .app
+      .clone()
+      .oneshot(request("POST", "/credentials", &impersonated, None))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let resp = s
+      .app
+      .clone()
+      .oneshot(request("POST", "/stop", &impersonated, None))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let restored = cookie_header(&cookies_of(&resp));
+    assert_eq!(restored, s.cookie);
+    assert!(
+      s.db
+        .session()
+        .list_for_user(s.user)
+        .await
+        .unwrap()
+        .is_empty()
+    );
+
+    let resp = s
+      .app
+      .oneshot(request("GET", "/audit", &s.cookie, None))
+      .await
+      .unwrap();
+    let body = body_json(resp).await;
+    assert_eq!(body["entries"][0]["reason"], "ticket 42");
+    assert!(!body["entries"][0]["ended_at"].is_null());
+  }
+
+  #[tokio::test]
+  async fn start_rejects_invalid_targets() {
+    let s = setup().await;
+    let other_admin = insert_user(&s.db, "other", "other@x.com").await;
+    grant_permissions(&s.db, other_admin, &["user:impersonate"]).await;
+
+    for (body, status) in [
+      (
+        json!({ "user": s.admin, "reason": "x" }),
+        StatusCode::BAD_REQUEST,
+      ),
+      (
+        json!({ "user": s.user, "reason": " " }),
+        StatusCode::BAD_REQUEST,
+      ),
+      (
+        json!({ "user": Uuid::new_v4(), "reason": "x" }),
+        StatusCode::NOT_FOUND,
+      ),
+      (
+        json!({ "user": other_admin, "reason": "x" }),
+        StatusCode::FORBIDDEN,
+      ),
+    ] {
+      let resp = s
+        .app
+        .clone()
+        .oneshot(request("POST", "/", &s.cookie, Some(body)))
+        .await
+        .unwrap();
+      assert_eq!(resp.status(), status);
+    }
+  }
+
+  #[tokio::test]
+  async fn start_requires_permission_and_stop_requires_impersonation() {
+    let s = setup().await;
+    let db = s.db.clone();
+    let jwt = auth_state(&db).await;
+    let user_cookie = auth_cookie(&db, &jwt, s.user).await;
+
+    let resp = s
+      .app
+      .clone()
+      .oneshot(request(
+        "POST",
+        "/",
+        &user_cookie,
+        Some(json!({ "user": s.admin, "reason": "x" })),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let resp = s
+      .app
+      .oneshot(request("POST", "/stop", &s.cookie, None))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+  }
+
+  #[tokio::test]
+  async fn expired_impersonation_is_rejected() {
+    let s = setup().await;
+    let resp = s
+      .app
+      .clone()
+      .oneshot(request(
+        "POST",
+        "/",
+        &s.cookie,
+        Some(json!({ "user": s.user, "reason": "x" })),
+      ))
+      .await
+      .unwrap();
+    let impersonated = cookie_header(&cookies_of(&resp));
+
+    use entity::session;
+    use sea_orm::{ActiveModelTrait, ActiveValue::Set};
+    let row = s
+      .db
+      .session()
+      .list_for_user(s.user)
+      .await
+      .unwrap()
+      .remove(0);
+    let mut row: session::ActiveModel = row.into();
+    row.expires_at = Set((chrono::Utc::now() - chrono::Duration::minutes(1)).naive_utc());
+    row.update(&s.db.0).await.unwrap();
+
+    let resp = s
+      .app
+      .oneshot(request("GET", "/whoami", &impersonated, None))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+  }
+}
@@ -1,16 +1,17 @@
 use aide::axum::{ApiRouter, routing::get_with};
-use axum::{Json, extract::Path};
+use axum::{Extension, Json, extract::Path};
 use centaurus::{
   backend::auth::jwt_auth::JwtAuth,
   db::{init::Connection, tables::ConnectionExt},
   error::Result,
 };
+use chrono::{DateTime, Utc};
 use http::StatusCode;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::db::DBTrait;
+use crate::{auth::session_auth::CurrentSession, db::DBTrait};
 
 pub fn router() -> ApiRouter {
   ApiRouter::new()
@@ -25,11 +26,33 @@ struct UserInfo {
   email: String,
   permissions: Vec<String>,
   totp_enabled: bool,
+  impersonation: Option<ImpersonationInfo>,
 }
 
-async fn info(auth: JwtAuth, db: Connection) -> Result<Json<UserInfo>> {
+/// Set when an admin is acting as this user in the current session.
+#[derive(Serialize, JsonSchema)]
+struct ImpersonationInfo {
+  admin: Uuid,
+  admin_name: String,
+  expires_at: DateTime<Utc>,
+}
+
+async fn info(
+  auth: JwtAuth,
+  Extension(session): Extension<CurrentSession>,
+  db: Connection,
+) -> Result<Json<UserInfo>> {
   let user = db.user_ext().get_user_by_id(auth.user_id).await?;
-  let permissions = db.group().get_user_permissions(auth.user_id).await?;
+  let permissions = db.group_ext().effective_permissions(auth.user_id).await?;
+
+  let impersonation = match session.impersonator {
+    Some(admin) => Some(ImpersonationInfo {
+      admin,
+      admin_name: db.user_ext().get_user_by_id(admin).await?.name,
+      expires_at: session.expires_at.and_utc(),
+    }),
+    None => None,
+  };
 
   Ok(Json(UserInfo {
     uuid: user.id,
@@ -37,6 +60,7 @@ async fn info(auth: JwtAuth, db: Connection) -> Result<Json<UserInfo>> {
     email: user.email,
     permissions,
     totp_enabled: user.totp.is_some(),
+    impersonation,
   }))
 }
 
@@ -86,6 +110,7 @@ mod test {
     assert_eq!(body["name"], "Alice");
     assert_eq!(body["email"], "alice@x.com");
     assert_eq!(body["totp_enabled"], false);
+    assert!(body["impersonation"].is_null());
     assert!(
       body["permissions"]
         .as_array()
@@ -95,6 +120,35 @@ mod test {
     );
   }
 
+  #[tokio::test]
+  async fn info_marks_impersonation_sessions() {
+    use crate::{auth::session_auth::create_session_raw_token, db::DBTrait};
+
+    let db = test_db().await;
+    let jwt = auth_state(&db).await;
+    let user = insert_user(&db, "Alice", "alice@x.com").await;
+    let admin = insert_user(&db, "Admin", "admin@x.com").await;
+    let token = create_session_raw_token(&jwt, user).await.unwrap();
+    db.session()
+      .create_impersonation(
+        user,
+        admin,
+        token.clone(),
+        chrono::Utc::now() + chrono::Duration::minutes(5),
+      )
+      .await
+      .unwrap();
+
+    let resp = app(db, jwt)
+      .oneshot(get_request(Some(&format!("centaurus_jwt={token}"))))
+      .await
+      .unwrap();
+    let body = body_json(resp).await;
+    assert_eq!(body["name"], "Alice");
+    assert_eq!(body["impersonation"]["admin"], admin.to_string());
+    assert_eq!(body["impersonation"]["admin_name"], "Admin");
+  }
+
   #[tokio::test]
   async fn info_rejects_unauthenticated() {
     let db = test_db().await;
@@ -1,48 +1,116 @@
-use aide::axum::{ApiRouter, routing::delete_with};
-use axum::Json;
+use aide::axum::{
+  ApiRouter,
+  routing::{delete_with, get_with, post_with, put_with},
+};
+use argon2::password_hash::SaltString;
+use axum::{Json, extract::Path};
+use base64::prelude::*;
 use centaurus::{
   backend::{
-    auth::{jwt_auth::JwtAuth, permission::UserEdit},
+    auth::{jwt_auth::JwtAuth, pw_state::PasswordState},
+    config::SiteConfig,
     endpoints::{
-      user::{email, management as cm},
+      user::{management as cm, template},
       websocket::state::Updater,
     },
   },
   bail,
-  db::{init::Connection, tables::ConnectionExt},
-  error::Result,
+  db::{
+    init::Connection,
+    tables::{
+      ConnectionExt,
+      user::{DetailUserInfo, SimpleGroupInfo, UserListInfo},
+    },
+  },
+  error::{ErrorReportStatusExt, Result},
+  mail::Mailer,
   storage::FileStorage,
 };
+use chrono::{DateTime, Utc};
+use http::StatusCode;
+use rand::{RngExt, distr::Alphanumeric};
+use rsa::rand_core::OsRng;
 use schemars::JsonSchema;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use tracing::info;
 use uuid::Uuid;
 
-use crate::notes::delete_storage_for_user;
+use crate::{
+  db::{
+    DBTrait,
+    user::user_ext::{MfaEnrolment, Suspension},
+  },
+  notes::delete_storage_for_user,
+  storage::StorageExt,
+  utils::{UserEdit, UserView, lacks_permissions},
+};
 
 pub fn router() -> ApiRouter {
   ApiRouter::new()
     .api_route(
       "/avatar",
-      cm::reset_user_avatar_route::<crate::utils::UpdateMessage>(),
+      delete_with(reset_user_avatar, |op| op.id("resetUserAvatar")),
     )
-    .api_route("/", cm::list_users_route())
-    .api_route("/", cm::create_user_route::<crate::utils::UpdateMessage>())
+    .api_route("/", get_with(list_users, |op| op.id("listUsers")))
+    .api_route("/", post_with(create_user, |op| op.id("createUser")))
     .api_route("/", delete_with(delete_user, |op| op.id("deleteUser")))
-    .api_route("/", cm::edit_user_route::<crate::utils::UpdateMessage>())
-    .api_route("/{uuid}", cm::user_info_route())
+    .api_route("/", put_with(edit_user, |op| op.id("editUser")))
+    .api_route(
+      "/suspended",
+      get_with(list_suspended, |op| op.id("listSuspendedUsers")),
+    )
+    .api_route(
+      "/suspend",
+      post_with(suspend_user, |op| op.id("suspendUser")),
+    )
+    .api_route("/resume", post_with(resume_user, |op| op.id("resumeUser")))
+    .api_route("/{uuid}", get_with(user_info, |op| op.id("userInfo")))
     .api_route("/mail", cm::mail_active_route())
-    .api_route("/groups", cm::list_groups_simple_route())
-    .api_route("/password", cm::reset_user_password_route())
+    .api_route(
+      "/groups",
+      get_with(list_groups_simple, |op| op.id("listGroupsSimple")),
+    )
+    .api_route(
+      "/password",
+      put_with(reset_user_password, |op| op.id("resetUserPassword")),
+    )
     .api_route(
       "/email",
-      email::change_email_route::<crate::utils::UpdateMessage>(),
+      post_with(change_user_email, |op| op.id("changeUserEmail")),
     )
     .api_route(
       "/convert-oidc",
-      cm::convert_oidc_user_route::<crate::utils::UpdateMessage>(),
+      put_with(convert_oidc_user, |op| op.id("convertOidcUser")),
     )
 }
 
+#[derive(Serialize, JsonSchema)]
+struct UserMfaInfo {
+  #[serde(flatten)]
+  user: UserListInfo,
+  mfa: MfaEnrolment,
+  mfa_required: bool,
+}
+
+async fn list_users(_auth: JwtAuth<UserView>, db: Connection) -> Result<Json<Vec<UserMfaInfo>>> {
+  let mut enrolment = db.user_ext().list_mfa_enrolment().await?;
+  let required = db.group_ext().users_requiring_mfa().await?;
+
+  let users = db
+    .user()
+    .list_users()
+    .await?
+    .into_iter()
+    .map(|user| UserMfaInfo {
+      mfa: enrolment.remove(&user.uuid).unwrap_or_default(),
+      mfa_required: required.contains(&user.uuid),
+      user,
+    })
+    .collect();
+
+  Ok(Json(users))
+}
+
 #[derive(Deserialize, JsonSchema)]
 struct DeleteUserRequest {
   uuid: Uuid,
@@ -63,8 +131,8 @@ async fn delete_user(
     bail!(CONFLICT, "Cannot delete the last user from the admin group");
   }
 
-  if db.group().is_in_group(admin_group, data.uuid).await?
-    && !db.group().is_in_group(admin_group, auth.user_id).await?
+  if db.group_ext().is_member(admin_group, data.uuid).await?
+    && !db.group_ext().is_member(admin_group, auth.user_id).await?
   {
     bail!(
       FORBIDDEN,
@@ -72,8 +140,48 @@ async fn delete_user(
     );
   }
 
-  delete_storage_for_user(&db, &storage, data.uuid).await?;
-  db.user().delete_user(data.uuid).await?;
+  delete_account(&db, &storage, &updater, data.uuid).await
+}
+
+async fn list_suspended(_auth: JwtAuth<UserEdit>, db: Connection) -> Result<Json<Vec<Suspension>>> {
+  Ok(Json(db.user_ext().list_suspended().await?))
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct SuspendUserRequest {
+  uuid: Uuid,
+  reason: Option<String>,
+  until: Option<DateTime<Utc>>,
+}
+
+async fn suspend_user(
+  auth: JwtAuth<UserEdit>,
+  db: Connection,
+  updater: Updater<crate::utils::UpdateMessage>,
+  Json(data): Json<SuspendUserRequest>,
+) -> Result<()> {
+  if data.uuid == auth.user_id {
+    bail!(BAD_REQUEST, "Cannot suspend yourself");
+  }
+  if data.until.is_some_and(|until| until <= Utc::now()) {
+    bail!(BAD_REQUEST, "Suspension must end in the future");
+  }
+
+  let Some(admin_group) = db.setup().get_admin_group_id().await? else {
+    bail!(INTERNAL_SERVER_ERROR, "Admin group is not set up");
+  };
+  if db.group_ext().is_member(admin_group, data.uuid).await?
+    && !db.group_ext().is_member(admin_group, auth.user_id).await?
+  {
+    bail!(
+      FORBIDDEN,
+      "User cannot suspend another user with higher permissions"
+    );
+  }
+
+  let reason = data.reason.filter(|reason| !reason.trim().is_empty());
+  db.user_ext().suspend(data.uuid, reason, data.until).await?;
+  info!("User {} suspended user {}", auth.user_id, data.uuid);
   updater
     .broadcast(crate::utils::UpdateMessage::User { uuid: data.uuid })
     .await;
@@ -81,13 +189,315 @@ async fn delete_user(
   Ok(())
 }
 
+#[derive(Deserialize, JsonSchema)]
+struct ResumeUserRequest {
+  uuid: Uuid,
+}
+
+async fn resume_user(
+  auth: JwtAuth<UserEdit>,
+  db: Connection,
+  updater: Updater<crate::utils::UpdateMessage>,
+  Json(data): Json<ResumeUserRequest>,
+) -> Result<()> {
+  db.user_ext().resume(data.uuid).await?;
+  info!("User {} resumed user {}", auth.user_id, data.uuid);
+  updater
+    .broadcast(crate::utils::UpdateMessage::User { uuid: data.uuid })
+    .await;
+
+  Ok(())
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct UserPath {
+  uuid: Uuid,
+}
+
+async fn user_info(
+  _auth: JwtAuth<UserView>,
+  db: Connection,
+  Path(path): Path<UserPath>,
+) -> Result<Json<DetailUserInfo>> {
+  let Some(info) = db.user().user_info(path.uuid).await? else {
+    bail!(NOT_FOUND, "User not found");
+  };
+  Ok(Json(info))
+}
+
+async fn list_groups_simple(
+  _auth: JwtAuth<UserView>,
+  db: Connection,
+) -> Result<Json<Vec<SimpleGroupInfo>>> {
+  Ok(Json(db.group().list_groups_simple().await?))
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct CreateUserRequest {
+  name: String,
+  email: String,
+  password: Option<String>,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct CreateUserResponse {
+  uuid: Uuid,
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn create_user(
+  _auth: JwtAuth<UserEdit>,
+  db: Connection,
+  updater: Updater<crate::utils::UpdateMessage>,
+  mailer: Mailer,
+  state: PasswordState,
+  site: SiteConfig,
+  Json(req): Json<CreateUserRequest>,
+) -> Result<Json<CreateUserResponse>> {
+  if req.name.trim().is_empty() {
+    bail!(BAD_REQUEST, "Name cannot be empty");
+  }
+  if req.email.trim().is_empty() {
+    bail!(BAD_REQUEST, "Email cannot be empty");
+  }
+  if db.user().try_get_user_by_email(&req.email).await?.is_some() {
+    bail!(CONFLICT, "User with this email already exists");
+  }
+
+  // with mail set up the user receives a generated initial password
+  let mail = mailer.is_active().await;
+  let password = if mail {
+    rand::rng()
+      .sample_iter(Alphanumeric)
+      .take(12)
+      .map(char::from)
+      .collect()
+  } else if let Some(password) = req.password {
+    let bytes = BASE64_STANDARD
+      .decode(password)
+      .status(StatusCode::BAD_REQUEST)?;
+    let password = state.decrypt(&bytes).status(StatusCode::BAD_REQUEST)?;
+    String::from_utf8_lossy(&password).to_string()
+  } else {
+    bail!(
+      BAD_REQUEST,
+      "Password must be provided when mail service is not active"
+    );
+  };
+
+  let salt = SaltString::generate(OsRng {}).to_string();
+  let hash = state.pw_hash_raw(&salt, &password)?;
+  let uuid = db
+    .user()
+    .create_user(req.name.clone(), req.email.clone(), hash, salt, false, None)
+    .await?;
+  if mail {
+    mailer
+      .send_mail(
+        req.name,
+        req.email,
+        "Your new account".to_string(),
+        template::init_password(site.site_url.as_str(), &password),
+      )
+      .await?;
+  }
+  updater
+    .broadcast(crate::utils::UpdateMessage::User { uuid })
+    .await;
+
+  Ok(Json(CreateUserResponse { uuid }))
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct EditUserRequest {
+  uuid: Uuid,
+  name: String,
+  groups: Vec<Uuid>,
+}
+
+async fn edit_user(
+  auth: JwtAuth<UserEdit>,
+  db: Connection,
+  updater: Updater<crate::utils::UpdateMessage>,
+  Json(req): Json<EditUserRequest>,
+) -> Result<()> {
+  if req.name.trim().is_empty() {
+    bail!(BAD_REQUEST, "Name cannot be empty");
+  }
+
+  let assigned = db.group_ext().group_permissions(req.groups.clone()).await?;
+  let current = db.group_ext().effective_permissions(req.uuid).await?;
+  if lacks_permissions(&db, auth.user_id, &assigned).await?
+    || lacks_permissions(&db, auth.user_id, &current).await?
+  {
+    bail!(
+      FORBIDDEN,
+      "Cannot assign permissions that the editor does not have"
+    );
+  }
+
+  let Some(admin_group) = db.setup().get_admin_group_id().await? else {
+    bail!(INTERNAL_SERVER_ERROR, "Admin group is not set up");
+  };
+  if !req.groups.contains(&admin_group) && db.group().is_last_admin(admin_group, req.uuid).await? {
+    bail!(CONFLICT, "Cannot remove the last user from the admin group");
+  }
+  if db.user().user_info(req.uuid).await?.is_none() {
+    bail!(NOT_FOUND, "User not found");
+  }
+
+  db.user().edit_user(req.uuid, req.name, req.groups).await?;
+  updater
+    .broadcast(crate::utils::UpdateMessage::User { uuid: req.uuid })
+    .await;
+
+  Ok(())
+}
+
+/// Editors may only act on users whose permissions they hold themselves.
+async fn check_outranks(db: &Connection, editor: Uuid, user: Uuid, action: &str) -> Result<()> {
+  let permissions = db.group_ext().effective_permissions(user).await?;
+  if lacks_permissions(db, editor, &permissions).await? {
+    bail!(FORBIDDEN, "Cannot {action} a user with higher permissions");
+  }
+  Ok(())
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct ResetAvatarRequest {
+  uuid: Uuid,
+}
+
+async fn reset_user_avatar(
+  _auth: JwtAuth<UserEdit>,
+  db: Connection,
+  updater: Updater<crate::utils::UpdateMessage>,
+  Json(req): Json<ResetAvatarRequest>,
+) -> Result<()> {
+  db.user().reset_avatar(req.uuid).await?;
+  updater
+    .broadcast(crate::utils::UpdateMessage::User { uuid: req.uuid })
+    .await;
+
+  Ok(())
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct ResetPasswordRequest {
+  uuid: Uuid,
+  new_password: String,
+}
+
+async fn reset_user_password(
+  auth: JwtAuth<UserEdit>,
+  db: Connection,
+  state: PasswordState,
+  Json(req): Json<ResetPasswordRequest>,
+) -> Result<()> {
+  check_outranks(&db, auth.user_id, req.uuid, "reset the password of").await?;
+
+  let user = db.user().get_user_by_id(req.uuid).await?;
+  if user.oidc_user {
+    bail!(BAD_REQUEST, "Cannot reset password for an OIDC user");
+  }
+
+  let hash = state.pw_hash(&user.salt, &req.new_password)?;
+  db.user().update_user_password(req.uuid, hash).await?;
+
+  Ok(())
+}
+
+async fn convert_oidc_user(
+  auth: JwtAuth<UserEdit>,
+  db: Connection,
+  state: PasswordState,
+  updater: Updater<crate::utils::UpdateMessage>,
+  Json(req): Json<ResetPasswordRequest>,
+) -> Result<()> {
+  check_outranks(&db, auth.user_id, req.uuid, "convert").await?;
+
+  let user = db.user().get_user_by_id(req.uuid).await?;
+  if !user.oidc_user {
+    bail!(BAD_REQUEST, "Cannot convert a non-OIDC user");
+  }
+
+  let hash = state.pw_hash(&user.salt, &req.new_password)?;
+  db.user().update_user_password(req.uuid, hash).await?;
+  db.user().to_local_user(req.uuid).await?;
+  updater
+    .broadcast(crate::utils::UpdateMessage::User { uuid: req.uuid })
+    .await;
+
+  Ok(())
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct ChangeEmailRequest {
+  uuid: Uuid,
+  new_email: String,
+}
+
+async fn change_user_email(
+  auth: JwtAuth<UserEdit>,
+  db: Connection,
+  updater: Updater<crate::utils::UpdateMessage>,
+  Json(req): Json<ChangeEmailRequest>,
+) -> Result<()> {
+  if req.new_email.is_empty() {
+    bail!(BAD_REQUEST, "New email cannot be empty");
+  }
+  check_outranks(&db, auth.user_id, req.uuid, "change the email of").await?;
+
+  let user = db.user().get_user_by_id(req.uuid).await?;
+  if user.oidc_user {
+    bail!(BAD_REQUEST, "Cannot change email for an OIDC user");
+  }
+  if db
+    .user()
+    .try_get_user_by_email(&req.new_email)
+    .await?
+    .is_some()
+  {
+    bail!(CONFLICT, "Email is already in use");
+  }
+
+  db.user().change_email(req.uuid, req.new_email).await?;
+  updater
+    .broadcast(crate::utils::UpdateMessage::User { uuid: req.uuid })
+    .await;
+
+  Ok(())
+}
+
+/// Removes a user together with the files stored for them.
+pub async fn delete_account(
+  db: &Connection,
+  storage: &FileStorage,
+  updater: &Updater<crate::utils::UpdateMessage>,
+  user_id: Uuid,
+) -> Result<()> {
+  delete_storage_for_user(db, storage, user_id).await?;
+  for export in db.data_export().list_for_user(user_id).await? {
+    if storage.data_export().exists(user_id, export.uuid).await? {
+      storage.data_export().delete(user_id, export.uuid).await?;
+    }
+  }
+
+  db.user().delete_user(user_id).await?;
+  updater
+    .broadcast(crate::utils::UpdateMessage::User { uuid: user_id })
+    .await;
+
+  Ok(())
+}
+
 #[cfg(test)]
 mod test {
   use axum::{
     Extension, Router,
     body::Body,
     http::{Request, StatusCode, header},
-    routing::delete,
+    routing::{delete, get, post},
   };
   use centaurus::{
     backend::auth::jwt_state::JwtState, db::init::Connection, db::tables::ConnectionExt,
@@ -281,4 +691,160 @@ mod test {
         .unwrap()
     );
   }
+
+  fn suspend_app(db: Connection, jwt: JwtState, upd: Updater) -> Router {
+    Router::new()
+      .route("/suspend", post(super::suspend_user))
+      .route("/resume", post(super::resume_user))
+      .layer(Extension(upd))
+      .layer(Extension(jwt))
+      .layer(Extension(db))
+  }
+
+  fn post_request(cookie: &str, uri: &str, body: serde_json::Value) -> Request<Body> {
+    Request::builder()
+      .method("POST")
+      .uri(uri)
+      .header(header::COOKIE, cookie)
+      .header(header::CONTENT_TYPE, "application/json")
+      .body(Body::from(body.to_string()))
+      .unwrap()
+  }
+
+  #[tokio::test]
+  async fn suspend_and_resume_user() {
+    let c = ctx().await;
+    make_admin_group(&c.db).await;
+    let target = insert_user(&c.db, "target", "target@x.com").await;
+    let app = suspend_app(c.db.clone(), c.jwt, c.upd);
+
+    let resp = app
+      .clone()
+      .oneshot(post_request(
+        &c.cookie,
+        "/suspend",
+        json!({ "uuid": target, "reason": "abuse", "until": null }),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let suspension = c.db.user_ext().suspension(target).await.unwrap().unwrap();
+    assert_eq!(suspension.reason.as_deref(), Some("abuse"));
+
+    let resp = app
+      .oneshot(post_request(
+        &c.cookie,
+        "/resume",
+        json!({ "uuid": target }),
+      ))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(c.db.user_ext().suspension(target).await.unwrap().is_none());
+  }
+
+  #[tokio::test]
+  async fn suspend_rejects_self_past_expiry_and_higher_permissions() {
+    let c = ctx().await;
+    let admin_group = make_admin_group(&c.db).await;
+    let target = insert_user(&c.db, "target", "target@x.com").await;
+    let admin = insert_user(&c.db, "admin", "admin@x.com").await;
+    c.db
+      .group()
+      .add_users_to_group(admin_group, vec![admin])
+      .await
+      .unwrap();
+    let app = suspend_app(c.db.clone(), c.jwt, c.upd);
+
+    for (body, status) in [
+      (json!({ "uuid": c.caller }), StatusCode::BAD_REQUEST),
+      (
+        json!({ "uuid": target, "until": "2000-01-01T00:00:00Z" }),
+        StatusCode::BAD_REQUEST,
+      ),
+      (json!({ "uuid": admin }), StatusCode::FORBIDDEN),
+    ] {
+      let resp = app
+        .clone()
+        .oneshot(post_request(&c.cookie, "/suspend", body))
+        .await
+        .unwrap();
+      assert_eq!(resp.status(), status);
+    }
+    assert!(c.db.user_ext().list_suspended().await.unwrap().is_empty());
+  }
+
+  #[tokio::test]
+  async fn inherited_permissions_grant_user_management() {
+    let c = ctx().await;
+    let editor = insert_user(&c.db, "editor", "editor@x.com").await;
+    let target = insert_user(&c.db, "target", "target@x.com").await;
+    let superior = insert_user(&c.db, "superior", "superior@x.com").await;
+    let parent = c.db.group().create_group("parent".into()).await.unwrap();
+    let child = c.db.group().create_group("child".into()).await.unwrap();
+    c.db
+      .group()
+      .add_permissions_to_group(parent, vec!["user:view".into(), "user:edit".into()])
+      .await
+      .unwrap();
+    c.db
+      .group()
+      .add_users_to_group(child, vec![editor])
+      .await
+      .unwrap();
+    c.db
+      .group_ext()
+      .set_parents(child, vec![parent])
+      .await
+      .unwrap();
+    grant_permissions(&c.db, superior, &["settings:edit"]).await;
+    let cookie = auth_cookie(&c.db, &c.jwt, editor).await;
+
+    let app = Router::new()
+      .route("/email", post(super::change_user_email))
+      .route("/{uuid}", get(super::user_info))
+      .layer(Extension(c.upd))
+      .layer(Extension(c.jwt))
+      .layer(Extension(c.db.clone()));
+
+    let resp = app
+      .clone()
+      .oneshot(
+        Request::builder()
+          .uri(format!("/{target}"))
+          .header(header::COOKIE, &cookie)
+          .body(Body::empty())
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let change_email = |uuid: Uuid, email: &str| {
+      Request::builder()
+        .method("POST")
+        .uri("/email")
+        .header(header::COOKIE, &cookie)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(
+          json!({ "uuid": uuid, "new_email": email }).to_string(),
+        ))
+        .unwrap()
+    };
+    let resp = app
+      .clone()
+      .oneshot(change_email(superior, "boss@x.com"))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = app
+      .oneshot(change_email(target, "new@x.com"))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+      c.db.user().get_user_by_id(target).await.unwrap().email,
+      "new@x.com"
+    );
+  }
 }
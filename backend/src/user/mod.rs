@@ -1,12 +1,21 @@
 use aide::axum::ApiRouter;
-use axum::Extension;
+use axum::{Extension, middleware::from_fn};
 use centaurus::{
   backend::{endpoints::user::account, middleware::rate_limiter::RateLimiter},
   db::init::Connection,
+  storage::FileStorage,
 };
 
-use crate::utils::UpdateMessage;
+use crate::{
+  auth::session_auth::{SessionPolicy, allow_enrolment, deny_impersonation},
+  config::Config,
+  utils::{UpdateMessage, Updater},
+};
 
+mod deletion;
+pub(crate) mod export;
+mod identities;
+mod impersonation;
 mod info;
 mod management;
 mod sessions;
@@ -16,11 +25,43 @@ pub fn router(rate_limiter: &mut RateLimiter) -> ApiRouter {
     .nest("/management", management::router())
     .nest(
       "/account",
-      account::router::<UpdateMessage>(rate_limiter).nest("/sessions", sessions::router()),
+      account::router::<UpdateMessage>(rate_limiter)
+        .nest("/identities", identities::router())
+        .nest("/export", export::router())
+        .nest("/deletion", deletion::router())
+        .route_layer(from_fn(deny_impersonation))
+        .nest("/sessions", sessions::router()),
+    )
+    .nest(
+      "/info",
+      info::router().route_layer(from_fn(allow_enrolment)),
     )
-    .nest("/info", info::router())
+    .nest("/impersonation", impersonation::router())
 }
 
-pub fn state(router: ApiRouter, db: Connection) -> ApiRouter {
-  router.layer(Extension(sessions::SessionCleanup::init(db)))
+pub fn state(
+  router: ApiRouter,
+  db: Connection,
+  storage: FileStorage,
+  updater: Updater,
+  config: &Config,
+) -> ApiRouter {
+  let deletion_settings = deletion::AccountDeletionSettings::from_config(config);
+
+  router
+    .layer(Extension(sessions::SessionCleanup::init(
+      db.clone(),
+      SessionPolicy::from_config(config),
+    )))
+    .layer(Extension(export::DataExportCleanup::init(
+      db.clone(),
+      storage.clone(),
+    )))
+    .layer(Extension(deletion::AccountDeletionJob::init(
+      db,
+      storage,
+      updater,
+      deletion_settings.clone(),
+    )))
+    .layer(Extension(deletion_settings))
 }
@@ -1,7 +1,7 @@
 use std::{sync::Arc, time::Duration};
 
 use aide::axum::{ApiRouter, routing::get_with, routing::post_with};
-use axum::Json;
+use axum::{Json, extract::Path, response::Html, routing::get};
 use axum_extra::extract::CookieJar;
 use centaurus::{
   backend::{
@@ -9,18 +9,22 @@ use centaurus::{
       jwt_auth::JwtAuth,
       jwt_state::{JWT_COOKIE_NAME, JwtState},
     },
-    request::response::TokenRes,
+    config::SiteConfig,
+    request::{redirect::Redirect, response::TokenRes},
   },
+  bail,
   db::init::Connection,
   error::Result,
 };
 use chrono::{DateTime, Utc};
+use entity::session;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use tokio::{spawn, task::JoinHandle, time::sleep};
 use uuid::Uuid;
 
 use crate::{
+  auth::{amr::AuthMethods, device::hash_token, session_auth::SessionPolicy},
   db::DBTrait,
   utils::{UpdateMessage, Updater},
 };
@@ -31,12 +35,23 @@ pub struct SessionCleanup {
 }
 
 impl SessionCleanup {
-  pub fn init(db: Connection) -> Self {
+  pub fn init(db: Connection, policy: SessionPolicy) -> Self {
     let handle = spawn(async move {
       loop {
         if let Err(err) = db.session().delete_expired().await {
           tracing::warn!(?err, "session cleanup failed");
         }
+        let now = Utc::now().naive_utc();
+        for is_app in [false, true] {
+          let limits = policy.limits(is_app);
+          if let Err(err) = db
+            .session()
+            .delete_outside_limits(is_app, limits.idle_cutoff(now), limits.lifetime_cutoff(now))
+            .await
+          {
+            tracing::warn!(?err, "session cleanup failed");
+          }
+        }
         sleep(Duration::from_secs(3600)).await;
       }
     });
@@ -51,6 +66,10 @@ pub fn router() -> ApiRouter {
   ApiRouter::new()
     .api_route("/", get_with(list, |op| op.id("listSessions")))
     .api_route("/", post_with(revoke, |op| op.id("revokeSession")))
+    .route(
+      "/revoke/{token}",
+      get(confirm_revoke_link).post(revoke_link),
+    )
 }
 
 #[derive(Serialize, JsonSchema)]
@@ -65,6 +84,12 @@ struct SessionInfo {
   refreshed_at: Option<DateTime<Utc>>,
   expires_at: DateTime<Utc>,
   current: bool,
+  impersonated_by: Option<Uuid>,
+  /// `amr` values of how the session signed in.
+  auth_methods: Vec<String>,
+  ip: Option<String>,
+  /// First sign-in from this device, the user got a mail about it.
+  new_device: bool,
 }
 
 async fn list(auth: JwtAuth, db: Connection, cookies: CookieJar) -> Result<Json<Vec<SessionInfo>>> {
@@ -85,6 +110,10 @@ async fn list(auth: JwtAuth, db: Connection, cookies: CookieJar) -> Result<Json<
       refreshed_at: s.refreshed_at.map(|t| t.and_utc()),
       expires_at: s.expires_at.and_utc(),
       current: current_token.as_ref() == Some(&s.token),
+      impersonated_by: s.impersonator_id,
+      auth_methods: AuthMethods::parse(&s.auth_methods).amr(),
+      ip: s.ip,
+      new_device: s.new_device,
     })
     .collect();
 
@@ -104,7 +133,7 @@ async fn revoke(
   mut cookies: CookieJar,
   Json(req): Json<RevokeSessionReq>,
 ) -> Result<(CookieJar, TokenRes)> {
-  let session = db.session().delete_by_id(req.id, auth.user_id).await?;
+  let session = revoke_by_id(&db, &updater, auth.user_id, req.id).await?;
 
   if cookies
     .get(JWT_COOKIE_NAME)
@@ -113,11 +142,83 @@ async fn revoke(
     cookies = cookies.remove(jwt.create_cookie(JWT_COOKIE_NAME, String::new()));
   }
 
-  updater.send_to(auth.user_id, UpdateMessage::Sessions).await;
-
   Ok((cookies, TokenRes(())))
 }
 
+async fn revoke_by_id(
+  db: &Connection,
+  updater: &Updater,
+  user_id: Uuid,
+  id: Uuid,
+) -> Result<session::Model> {
+  let session = db.session().delete_by_id(id, user_id).await?;
+  updater.send_to(user_id, UpdateMessage::Sessions).await;
+  Ok(session)
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct RevokeLinkPath {
+  token: String,
+}
+
+/// Target of the link in the "new sign-in" mail. Only asks for confirmation,
+/// mail scanners open every link of a mail.
+async fn confirm_revoke_link(
+  db: Connection,
+  Path(RevokeLinkPath { token }): Path<RevokeLinkPath>,
+) -> Result<Html<&'static str>> {
+  if db
+    .session()
+    .get_by_revoke_token(&hash_token(&token))
+    .await
+    .is_err()
+  {
+    bail!(NOT_FOUND, "session not found");
+  }
+
+  Ok(Html(CONFIRM_REVOKE_PAGE))
+}
+
+const CONFIRM_REVOKE_PAGE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+  <head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>Sign Out Device</title>
+  </head>
+  <body style="display: flex; flex-direction: column; align-items: center; font-family: sans-serif;">
+    <h2>Sign Out Device</h2>
+    <p>This ends the session of the new sign-in. Change your password if it was not you.</p>
+    <form method="post">
+      <button type="submit">Sign Out This Device</button>
+    </form>
+  </body>
+</html>
+"#;
+
+/// Submitted from the confirmation page, works without being signed in. The
+/// device is forgotten so signing in from it again alerts again.
+async fn revoke_link(
+  db: Connection,
+  updater: Updater,
+  site: SiteConfig,
+  Path(RevokeLinkPath { token }): Path<RevokeLinkPath>,
+) -> Result<Redirect> {
+  let Ok(session) = db.session().get_by_revoke_token(&hash_token(&token)).await else {
+    bail!(NOT_FOUND, "session not found");
+  };
+
+  revoke_by_id(&db, &updater, session.user_id, session.id).await?;
+  db.known_device()
+    .forget(session.user_id, &session.fingerprint)
+    .await?;
+
+  let mut url = site.site_url.clone();
+  url.set_path("/login");
+  url.query_pairs_mut().append_pair("session_revoked", "true");
+  Ok(Redirect::found(url.to_string()))
+}
+
 #[cfg(test)]
 mod test {
   use super::*;
@@ -131,12 +232,18 @@ mod test {
   };
   use centaurus::backend::auth::jwt_state::JwtState;
   use serde_json::json;
+
+  use crate::utils::generate_secret;
   use tower::ServiceExt;
 
   fn app(db: Connection, jwt: JwtState) -> Router {
     Router::new()
       .route("/sessions", get(super::list))
       .route("/sessions/revoke", post(super::revoke))
+      .route(
+        "/sessions/revoke/{token}",
+        get(super::confirm_revoke_link).post(super::revoke_link),
+      )
       .layer(Extension(jwt))
       .layer(Extension(db))
   }
@@ -165,6 +272,46 @@ mod test {
     assert_eq!(body[0]["name"], "");
     assert_eq!(body[0]["application"], "");
     assert_eq!(body[0]["operating_system"], "");
+    assert!(body[0]["impersonated_by"].is_null());
+    assert_eq!(body[0]["auth_methods"], json!(["pwd"]));
+    assert_eq!(body[0]["new_device"], false);
+  }
+
+  #[tokio::test]
+  async fn list_shows_impersonation_sessions() {
+    let db = test_db().await;
+    let jwt = auth_state(&db).await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let admin = insert_user(&db, "a", "a@x.com").await;
+    let cookie = auth_cookie(&db, &jwt, user).await;
+    db.session()
+      .create_impersonation(
+        user,
+        admin,
+        "impersonation".into(),
+        Utc::now() + chrono::Duration::minutes(5),
+      )
+      .await
+      .unwrap();
+
+    let resp = app(db, jwt)
+      .oneshot(
+        Request::builder()
+          .uri("/sessions")
+          .header(header::COOKIE, &cookie)
+          .body(Body::empty())
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+    let body = body_json(resp).await;
+    let sessions = body.as_array().unwrap();
+    assert_eq!(sessions.len(), 2);
+    assert!(
+      sessions
+        .iter()
+        .any(|s| s["impersonated_by"] == admin.to_string())
+    );
   }
 
   async fn extra_session(db: &Connection, jwt: &JwtState, user: Uuid) -> Uuid {
@@ -174,11 +321,13 @@ mod test {
       jwt,
       user,
       false,
+      Default::default(),
       SessionMeta {
         name: String::new(),
         application: String::new(),
         operating_system: String::new(),
       },
+      &Default::default(),
     )
     .await
     .unwrap();
@@ -388,4 +537,122 @@ mod test {
     assert!(resp.status().is_success());
     assert!(db.session().list_for_user(user).await.unwrap().is_empty());
   }
+
+  /// Signs in from an unfamiliar device and returns the session id along with
+  /// the token of its revoke link.
+  async fn new_device_session(db: &Connection, jwt: &JwtState, user: Uuid) -> (Uuid, String) {
+    use crate::{auth::session_auth::create_session_raw_token, db::user::session::SessionDevice};
+    let token = generate_secret();
+    db.session()
+      .create(
+        user,
+        create_session_raw_token(jwt, user).await.unwrap(),
+        false,
+        Utc::now() + chrono::Duration::days(1),
+        String::new(),
+        "Firefox 128".into(),
+        "Linux".into(),
+        String::new(),
+        SessionDevice {
+          ip: Some("203.0.113.7".into()),
+          fingerprint: "fp".into(),
+          new_device: true,
+          revoke_token: Some(hash_token(&token)),
+        },
+      )
+      .await
+      .unwrap();
+    db.known_device().remember(user, "fp").await.unwrap();
+    (db.session().list_for_user(user).await.unwrap()[0].id, token)
+  }
+
+  #[tokio::test]
+  async fn list_flags_new_devices() {
+    let db = test_db().await;
+    let jwt = auth_state(&db).await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let cookie = auth_cookie(&db, &jwt, user).await;
+    let (id, _) = new_device_session(&db, &jwt, user).await;
+
+    let resp = app(db, jwt)
+      .oneshot(
+        Request::builder()
+          .uri("/sessions")
+          .header(header::COOKIE, &cookie)
+          .body(Body::empty())
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+    let body = body_json(resp).await;
+    let flagged: Vec<_> = body
+      .as_array()
+      .unwrap()
+      .iter()
+      .filter(|s| s["new_device"] == true)
+      .collect();
+    assert_eq!(flagged.len(), 1);
+    assert_eq!(flagged[0]["id"], id.to_string());
+    assert_eq!(flagged[0]["ip"], "203.0.113.7");
+  }
+
+  #[tokio::test]
+  async fn revoke_link_removes_session_without_sign_in() {
+    let db = test_db().await;
+    let jwt = auth_state(&db).await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    auth_cookie(&db, &jwt, user).await;
+    let (id, token) = new_device_session(&db, &jwt, user).await;
+    let app = app(db.clone(), jwt)
+      .layer(Extension(updater().await))
+      .layer(Extension(SiteConfig::default()));
+    let request = |method: &str| {
+      Request::builder()
+        .method(method)
+        .uri(format!("/sessions/revoke/{token}"))
+        .body(Body::empty())
+        .unwrap()
+    };
+
+    // opening the link, as mail scanners do, changes nothing
+    let resp = app.clone().oneshot(request("GET")).await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(db.session().list_for_user(user).await.unwrap().len(), 2);
+
+    let resp = app.oneshot(request("POST")).await.unwrap();
+    assert!(resp.status().is_redirection());
+
+    let sessions = db.session().list_for_user(user).await.unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert_ne!(sessions[0].id, id);
+    // signing in from the device again alerts again
+    assert!(!db.known_device().remember(user, "fp").await.unwrap());
+  }
+
+  #[tokio::test]
+  async fn revoke_link_rejects_unknown_token() {
+    let db = test_db().await;
+    let jwt = auth_state(&db).await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    new_device_session(&db, &jwt, user).await;
+
+    let app = app(db.clone(), jwt)
+      .layer(Extension(updater().await))
+      .layer(Extension(SiteConfig::default()));
+    for method in ["GET", "POST"] {
+      let resp = app
+        .clone()
+        .oneshot(
+          Request::builder()
+            .method(method)
+            .uri("/sessions/revoke/not-a-token")
+            .body(Body::empty())
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+      assert_eq!(resp.status(), 404);
+    }
+    assert_eq!(db.session().list_for_user(user).await.unwrap().len(), 1);
+  }
 }
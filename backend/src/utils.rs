@@ -4,12 +4,17 @@ use centaurus::{
     auth::permission::{self, Permission},
     endpoints::websocket,
   },
-  permission,
+  bail,
+  db::init::Connection,
+  error::Result,
 };
+use http::request::Parts;
 use rand::{RngExt, distr::Alphanumeric};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::db::DBTrait;
+
 pub type Updater = websocket::state::Updater<UpdateMessage>;
 
 #[derive(Serialize, Deserialize, Clone, Copy, Debug, UpdateMessage)]
@@ -46,10 +51,25 @@ pub enum UpdateMessage {
     note_id: Uuid,
   },
   NoteSnapshotsCleaned,
+  NoteComments {
+    note_id: Uuid,
+  },
+  NoteMention {
+    note_id: Uuid,
+  },
+  NoteAttachments {
+    note_id: Uuid,
+  },
+  NoteFolders,
   Sessions,
   NoteContent {
     uuid: Uuid,
   },
+  OidcProvider {
+    uuid: Uuid,
+  },
+  Identities,
+  DataExports,
 }
 
 pub fn generate_secret() -> String {
@@ -57,6 +77,46 @@ pub fn generate_secret() -> String {
   (0..32).map(|_| rng.sample(Alphanumeric) as char).collect()
 }
 
+/// Like `centaurus::permission!`, but the permission is also granted through
+/// parent groups.
+macro_rules! permission {
+  ($type:ident, $name:literal) => {
+    pub struct $type;
+
+    impl Permission for $type {
+      fn name() -> &'static str {
+        $name
+      }
+
+      fn check(
+        db: &Connection,
+        user: Uuid,
+        _parts: &Parts,
+      ) -> impl Future<Output = Result<()>> + Send {
+        check_permission(db, user, $name)
+      }
+    }
+  };
+}
+
+async fn check_permission(db: &Connection, user: Uuid, permission: &str) -> Result<()> {
+  if !db.group_ext().user_has_permission(user, permission).await? {
+    bail!(FORBIDDEN, "insufficient permissions");
+  }
+  Ok(())
+}
+
+/// Whether `permissions` contain one the user does not have, directly or
+/// through a parent group.
+pub async fn lacks_permissions(
+  db: &Connection,
+  user: Uuid,
+  permissions: &[String],
+) -> Result<bool> {
+  let own = db.group_ext().effective_permissions(user).await?;
+  Ok(permissions.iter().any(|perm| !own.contains(perm)))
+}
+
 pub fn permissions() -> Vec<&'static str> {
   let mut perms = permission::permissions();
   perms.extend_from_slice(&[
@@ -68,10 +128,19 @@ pub fn permissions() -> Vec<&'static str> {
     OAuthScopeEdit::name(),
     OAuthPolicyView::name(),
     OAuthPolicyEdit::name(),
+    OidcProviderView::name(),
+    OidcProviderEdit::name(),
+    UserImpersonate::name(),
   ]);
   perms
 }
 
+// Groups and users, same names as the centaurus permissions
+permission!(GroupView, "group:view");
+permission!(GroupEdit, "group:edit");
+permission!(UserView, "user:view");
+permission!(UserEdit, "user:edit");
+
 // Apod
 permission!(ApodList, "apod:list");
 permission!(ApodSelect, "apod:select");
@@ -84,6 +153,13 @@ permission!(OAuthScopeEdit, "oauth_scope:edit");
 permission!(OAuthPolicyView, "oauth_policy:view");
 permission!(OAuthPolicyEdit, "oauth_policy:edit");
 
+// Oidc
+permission!(OidcProviderView, "oidc_provider:view");
+permission!(OidcProviderEdit, "oidc_provider:edit");
+
+// User
+permission!(UserImpersonate, "user:impersonate");
+
 #[cfg(test)]
 mod test {
   use super::*;
@@ -116,6 +192,9 @@ mod test {
       "oauth_scope:edit",
       "oauth_policy:view",
       "oauth_policy:edit",
+      "oidc_provider:view",
+      "oidc_provider:edit",
+      "user:impersonate",
     ] {
       assert!(perms.contains(&expected), "missing permission {expected}");
     }
@@ -125,8 +204,8 @@ mod test {
   fn permissions_include_base_permissions_and_have_no_duplicates() {
     let perms = permissions();
     // base centaurus permissions are appended too, so the list is larger than
-    // just the 8 app-specific ones.
-    assert!(perms.len() > 8);
+    // just the 11 app-specific ones.
+    assert!(perms.len() > 11);
 
     let mut sorted = perms.clone();
     sorted.sort_unstable();
@@ -143,4 +222,34 @@ mod test {
     assert_eq!(ApodList::name(), "apod:list");
     assert_eq!(OAuthPolicyEdit::name(), "oauth_policy:edit");
   }
+
+  #[tokio::test]
+  async fn permission_is_inherited_from_parent_groups() {
+    use crate::db::test::{
+      add_user_to_group, grant_permissions, insert_group, insert_user, test_db,
+    };
+    use centaurus::db::tables::ConnectionExt;
+
+    let db = test_db().await;
+    let user = insert_user(&db, "u", "u@x.com").await;
+    let child = insert_group(&db, "child").await;
+    add_user_to_group(&db, child, user).await;
+    let parts = http::Request::new(()).into_parts().0;
+    assert!(UserView::check(&db, user, &parts).await.is_err());
+
+    let admin = insert_user(&db, "a", "a@x.com").await;
+    grant_permissions(&db, admin, &["user:view"]).await;
+    let parent = db
+      .group()
+      .find_group_by_name("perm-group")
+      .await
+      .unwrap()
+      .unwrap();
+    db.group_ext()
+      .set_parents(child, vec![parent])
+      .await
+      .unwrap();
+    assert!(UserView::check(&db, user, &parts).await.is_ok());
+    assert!(UserEdit::check(&db, user, &parts).await.is_err());
+  }
 }
@@ -147,3 +147,35 @@ async fn auth_config_is_public() {
   let resp = server.get("/auth/config").await;
   assert_eq!(resp.status(), StatusCode::OK);
 }
+
+#[tokio::test]
+async fn init_oidc_registers_provider_before_setup() {
+  let server = TestServer::start().await;
+
+  let resp = server
+    .post(
+      "/setup/oidc",
+      serde_json::json!({
+        "name": "Keycloak",
+        "issuer": "https://sso.example.com/realms/main",
+        "client_id": "positron",
+        "client_secret": "secret",
+        "scopes": ["openid", "email", "profile"],
+        "pkce": true,
+        "create_user": false,
+        "enabled": false,
+        "name_claim": "preferred_username",
+        "email_claim": "email",
+        "group_claim": null,
+        "group_mappings": []
+      }),
+    )
+    .await;
+  assert_eq!(resp.status(), StatusCode::OK);
+
+  // the setup provider is always enabled so the first admin can sign in
+  let body: Value = server.get("/setup/oidc").await.json().await.unwrap();
+  assert_eq!(body["providers"][0]["name"], "Keycloak");
+  let body: Value = server.get("/auth/oidc").await.json().await.unwrap();
+  assert_eq!(body.as_array().unwrap().len(), 1);
+}